@@ -0,0 +1,759 @@
+/*
+ * Runtime of COOL programs compiled to native code: object allocation and garbage collection, the built-in methods
+ * of Object, IO and String, and the runtime errors. The generated code provides the class table and `cool_main`.
+ *
+ * Every object starts with a header of three words, the class tag, the size of the object in bytes and the
+ * dispatch table of its class. Attributes follow as one word each, an object or the raw value of an attribute declared
+ * `Int` or `Bool`. `Int` and `Bool` objects hold their value in the first word after the header, `String` objects
+ * their length followed by the characters and a terminating zero byte.
+ *
+ * The collector is chosen when the runtime is compiled, by defining COOL_GC:
+ *  - GC_NONE (0) never frees memory,
+ *  - GC_MARK_SWEEP (1) marks the live objects and sweeps the others into free lists,
+ *  - GC_GENERATIONAL (2) allocates in a nursery whose survivors a copying collection promotes to the old generation,
+ *    which a mark-compact collection reclaims when it has grown.
+ * Defining COOL_GC_STRESS collects at every allocation, so a missing root shows up at once. The generational
+ * collector then runs a minor collection at every allocation and a full one every STRESS_MAJOR_PERIOD allocations.
+ *
+ * Generated code is built with the frame pointer and keeps objects in stack slots only. Its stack maps give, for the
+ * return address of each call that may collect, the offsets from the frame pointer of the slots holding live
+ * objects, which the collector finds by walking the chain of frame pointers. Code compiled from LLVM IR, whose
+ * frames the runtime cannot read, instead links a frame holding its objects into `cool_shadow_stack`, and the
+ * runtime is then compiled with COOL_SHADOW_STACK defined to 1. The runtime itself protects the
+ * objects it holds across an allocation with PROTECT. Objects outside the heap, like string constants and
+ * prototypes, are never collected nor moved, and point only to objects outside the heap.
+ */
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+
+#define GC_NONE 0
+#define GC_MARK_SWEEP 1
+#define GC_GENERATIONAL 2
+
+#ifndef COOL_GC
+#define COOL_GC GC_NONE
+#endif
+#ifndef COOL_GC_STRESS
+#define COOL_GC_STRESS 0
+#endif
+#ifndef COOL_SHADOW_STACK
+#define COOL_SHADOW_STACK 0
+#endif
+
+typedef struct Object {
+  int64_t tag;
+  int64_t size;
+  void **dispatch;
+  int64_t fields[];
+} Object;
+
+#define HEADER_SIZE ((int64_t) sizeof(Object))
+
+typedef struct ClassInfo {
+  Object *name;
+  Object *prototype;
+  Object *(*init)(Object *);
+  /* One byte per attribute, non-zero if it holds a raw value, NULL if every attribute holds an object */
+  const uint8_t *raw_attributes;
+} ClassInfo;
+
+/* Provided by the generated code */
+extern ClassInfo cool_class_table[];
+extern int64_t cool_int_tag;
+extern int64_t cool_bool_tag;
+extern int64_t cool_string_tag;
+extern void cool_main(void);
+
+/* Kinds of `trap` and `check_void`, in the order of `TrapKind` */
+enum { TRAP_DISPATCH_ON_VOID, TRAP_CASE_ON_VOID, TRAP_CASE_NO_MATCH };
+
+static const char *string_chars(Object *string) {
+  return (const char *) &string->fields[1];
+}
+
+static void runtime_error(const char *format, const char *argument) {
+  fflush(stdout);
+  fprintf(stderr, format, argument);
+  fputc('\n', stderr);
+  exit(1);
+}
+
+/* Location of the operation running that may allocate or call a built-in method, a string like `file.cl:12` set by
+ * the generated code, NULL before the program starts */
+Object *cool_location;
+
+/* Stops the program with the error of the runtime itself, at the location of the operation running */
+static void located_error(const char *message) {
+  if (cool_location != NULL) {
+    fflush(stdout);
+    fprintf(stderr, "%s: ", string_chars(cool_location));
+  }
+  runtime_error("%s", message);
+}
+
+/* Flags kept in the low bits of the size word, as sizes are multiples of 8 */
+#define MARK_BIT 1
+#define REMEMBERED_BIT 2
+#define SIZE_MASK (~(int64_t) 7)
+
+/* Free blocks of the mark-sweep heap keep the next block of their free list in the dispatch word */
+#define FREE_TAG (-1)
+/* Nursery objects promoted by a minor collection keep their new address in the dispatch word */
+#define FORWARDED_TAG (-2)
+
+/* Address space reserved for the heap, only the pages the program uses take memory */
+#define HEAP_RESERVE ((size_t) 1 << 34)
+#define NURSERY_SIZE ((int64_t) 4 << 20)
+/* Larger objects skip the nursery and go straight to the old generation */
+#define LARGE_OBJECT_SIZE (NURSERY_SIZE / 8)
+/* Bytes allocated by the mark-sweep collector, or size of the old generation, before the first full collection */
+#define MIN_HEAP_LIMIT ((int64_t) 8 << 20)
+/* Number of minor collections between two full ones in stress mode */
+#define STRESS_MAJOR_PERIOD 64
+/* Free lists of the mark-sweep heap for blocks of 8, 16, ... bytes, larger blocks share the last list */
+#define SIZE_CLASSES 32
+
+/* Objects are bump allocated in [heap_start, heap_top), the whole heap without the generational collector and the
+ * old generation with it */
+static char *heap_start;
+static char *heap_top;
+static char *heap_end;
+
+/* Bounds of the nursery, read by the write barrier of generated code. It is empty unless the collector is
+ * generational, so the barrier never calls `cool_remember`. */
+char *cool_nursery_start;
+int64_t cool_nursery_size;
+#if COOL_GC == GC_GENERATIONAL
+static char *nursery_top;
+#endif
+
+/* Old objects that may point to the nursery, found by the write barrier */
+static Object **remembered;
+static int64_t remembered_count;
+static int64_t remembered_capacity;
+
+/* Frame of a function compiled from LLVM IR, linked from its prologue to its return. Its roots hold every object
+ * of the function, which the code loads again after anything that may collect. */
+typedef struct ShadowFrame {
+  struct ShadowFrame *next;
+  int64_t count;
+  Object *roots[];
+} ShadowFrame;
+
+ShadowFrame *cool_shadow_stack;
+
+/* Locals of the runtime holding objects across an allocation */
+#define MAX_PROTECTED 8
+static Object **protected[MAX_PROTECTED];
+static int protected_count;
+#define PROTECT(variable) (protected[protected_count++] = &(variable))
+#define UNPROTECT(count) (protected_count -= (count))
+
+static int64_t object_size(Object *object) {
+  return object->size & SIZE_MASK;
+}
+
+static int in_nursery(Object *object) {
+  return (uintptr_t) object - (uintptr_t) cool_nursery_start < (uintptr_t) cool_nursery_size;
+}
+
+/* Number of attributes: none for the basic classes, whose field is a raw value */
+static int64_t attribute_count(Object *object) {
+  if (object->tag == cool_int_tag || object->tag == cool_bool_tag || object->tag == cool_string_tag) {
+    return 0;
+  }
+  return (object_size(object) - HEADER_SIZE) / 8;
+}
+
+/* Returns non-zero if the attribute holds an object rather than a raw `Int` or `Bool` value */
+static int holds_object(Object *object, int64_t index) {
+  const uint8_t *raw_attributes = cool_class_table[object->tag].raw_attributes;
+  return raw_attributes == NULL || !raw_attributes[index];
+}
+
+/* Grows an array of objects by doubling, for the remembered set and the mark stack */
+static Object **grow(Object **array, int64_t *capacity) {
+  *capacity = *capacity == 0 ? 1024 : *capacity * 2;
+  array = realloc(array, (size_t) *capacity * sizeof(Object *));
+  if (array == NULL) {
+    located_error("Out of memory");
+  }
+  return array;
+}
+
+static void heap_init(void) {
+  void *heap = mmap(NULL, HEAP_RESERVE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE, -1, 0);
+  if (heap == MAP_FAILED) {
+    located_error("Out of memory");
+  }
+  heap_start = heap_top = heap;
+  heap_end = heap_start + HEAP_RESERVE;
+#if COOL_GC == GC_GENERATIONAL
+  cool_nursery_start = nursery_top = malloc((size_t) NURSERY_SIZE);
+  if (cool_nursery_start == NULL) {
+    located_error("Out of memory");
+  }
+  cool_nursery_size = NURSERY_SIZE;
+#endif
+}
+
+static Object *heap_bump(int64_t size) {
+  if (heap_end - heap_top < size) {
+    located_error("Out of memory");
+  }
+  Object *object = (Object *) heap_top;
+  heap_top += size;
+  return object;
+}
+
+/* Called by the write barrier when an old object is made to point to the nursery */
+void cool_remember(Object *object) {
+  if ((object->size & REMEMBERED_BIT) == 0) {
+    if (remembered_count == remembered_capacity) {
+      remembered = grow(remembered, &remembered_capacity);
+    }
+    object->size |= REMEMBERED_BIT;
+    remembered[remembered_count++] = object;
+  }
+}
+
+/* Write barrier for an object whose attributes the runtime copied from another one */
+static void remember_copy(Object *object) {
+  if (!in_nursery(object) && cool_nursery_size > 0 && attribute_count(object) > 0) {
+    cool_remember(object);
+  }
+}
+
+#if COOL_GC != GC_NONE
+static int in_heap(Object *object) {
+  return (char *) object >= heap_start && (char *) object < heap_top;
+}
+
+#if !COOL_SHADOW_STACK
+/* Live stack slots at a return address in generated code, see `StackMaps` in the x86-64 backend */
+typedef struct StackMap {
+  char *address;
+  int64_t first;
+  int64_t count;
+} StackMap;
+
+extern int64_t cool_stack_map_count;
+extern StackMap cool_stack_maps[];
+extern int64_t cool_stack_map_slots[];
+
+/* Frame of `main`, where the walk of the stack stops */
+static void **stack_bottom;
+
+static StackMap *find_stack_map(void *address) {
+  int64_t low = 0;
+  int64_t high = cool_stack_map_count;
+  while (low < high) {
+    int64_t middle = low + (high - low) / 2;
+    if (cool_stack_maps[middle].address < (char *) address) {
+      low = middle + 1;
+    } else {
+      high = middle;
+    }
+  }
+  return low < cool_stack_map_count && cool_stack_maps[low].address == (char *) address ? &cool_stack_maps[low] : NULL;
+}
+
+#endif
+
+/* Visits the protected locals of the runtime, then the live slots of each frame of generated code. The return
+ * address saved in a frame is in its caller, whose frame pointer the slot offsets are relative to. */
+static void for_each_root(void (*visit)(Object **)) {
+  for (int i = 0; i < protected_count; i++) {
+    visit(protected[i]);
+  }
+#if COOL_SHADOW_STACK
+  for (ShadowFrame *frame = cool_shadow_stack; frame != NULL; frame = frame->next) {
+    for (int64_t i = 0; i < frame->count; i++) {
+      visit(&frame->roots[i]);
+    }
+  }
+#else
+  void **frame = __builtin_frame_address(0);
+  while (frame != NULL && frame != stack_bottom) {
+    void **caller = (void **) frame[0];
+    StackMap *map = find_stack_map(frame[1]);
+    for (int64_t i = 0; map != NULL && i < map->count; i++) {
+      visit((Object **) ((char *) caller + cool_stack_map_slots[map->first + i]));
+    }
+    frame = caller;
+  }
+#endif
+}
+
+static Object **mark_stack;
+static int64_t mark_count;
+static int64_t mark_capacity;
+
+static void mark(Object *object) {
+  if (object != NULL && in_heap(object) && (object->size & MARK_BIT) == 0) {
+    object->size |= MARK_BIT;
+    if (mark_count == mark_capacity) {
+      mark_stack = grow(mark_stack, &mark_capacity);
+    }
+    mark_stack[mark_count++] = object;
+  }
+}
+
+static void mark_root(Object **slot) {
+  mark(*slot);
+}
+
+/* Sets the mark bit of every object of the heap reachable from the roots */
+static void mark_live(void) {
+  for_each_root(mark_root);
+  while (mark_count > 0) {
+    Object *object = mark_stack[--mark_count];
+    for (int64_t i = 0; i < attribute_count(object); i++) {
+      if (holds_object(object, i)) {
+        mark((Object *) object->fields[i]);
+      }
+    }
+  }
+}
+#endif
+
+#if COOL_GC == GC_NONE
+static Object *allocate(int64_t size) {
+  return heap_bump(size);
+}
+#endif
+
+#if COOL_GC == GC_MARK_SWEEP
+static Object *free_lists[SIZE_CLASSES + 1];
+static int64_t allocated_since_collection;
+static int64_t collection_limit = MIN_HEAP_LIMIT;
+
+static int size_class(int64_t size) {
+  return size / 8 - 1 < SIZE_CLASSES ? (int) (size / 8 - 1) : SIZE_CLASSES;
+}
+
+static void free_block(char *start, int64_t size) {
+  Object *block = (Object *) start;
+  block->tag = FREE_TAG;
+  block->size = size;
+  block->dispatch = (void **) free_lists[size_class(size)];
+  free_lists[size_class(size)] = block;
+}
+
+/* A free block of exactly the size, or the first large enough block of the last list, split if it leaves room for
+ * another block */
+static Object *take_free(int64_t size) {
+  int class = size_class(size);
+  if (class < SIZE_CLASSES && free_lists[class] != NULL) {
+    Object *block = free_lists[class];
+    free_lists[class] = (Object *) block->dispatch;
+    return block;
+  }
+  for (Object **link = &free_lists[SIZE_CLASSES]; *link != NULL; link = (Object **) &(*link)->dispatch) {
+    Object *block = *link;
+    if (block->size == size || block->size >= size + HEADER_SIZE) {
+      *link = (Object *) block->dispatch;
+      if (block->size > size) {
+        free_block((char *) block + size, block->size - size);
+      }
+      return block;
+    }
+  }
+  return NULL;
+}
+
+/* Rebuilds the free lists from the runs of dead objects and free blocks. A run at the end of the heap is given back
+ * to the bump allocator. */
+static void sweep(void) {
+  memset(free_lists, 0, sizeof(free_lists));
+  int64_t live = 0;
+  char *run = NULL;
+  for (char *position = heap_start; position < heap_top;) {
+    Object *object = (Object *) position;
+    int64_t size = object_size(object);
+    if (object->tag != FREE_TAG && (object->size & MARK_BIT) != 0) {
+      object->size &= ~(int64_t) MARK_BIT;
+      live += size;
+      if (run != NULL) {
+        free_block(run, position - run);
+        run = NULL;
+      }
+    } else if (run == NULL) {
+      run = position;
+    }
+    position += size;
+  }
+  if (run != NULL) {
+    heap_top = run;
+  }
+  allocated_since_collection = 0;
+  collection_limit = live > MIN_HEAP_LIMIT ? live : MIN_HEAP_LIMIT;
+}
+
+static Object *allocate(int64_t size) {
+  if (COOL_GC_STRESS || allocated_since_collection >= collection_limit) {
+    mark_live();
+    sweep();
+  }
+  allocated_since_collection += size;
+  Object *object = take_free(size);
+  return object != NULL ? object : heap_bump(size);
+}
+#endif
+
+#if COOL_GC == GC_GENERATIONAL
+static int64_t old_limit = MIN_HEAP_LIMIT;
+static int64_t stress_count;
+
+/* Copies a nursery object to the old generation once, later calls return the copy */
+static Object *promote(Object *object) {
+  if (object == NULL || !in_nursery(object)) {
+    return object;
+  }
+  if (object->tag == FORWARDED_TAG) {
+    return (Object *) object->dispatch;
+  }
+  int64_t size = object_size(object);
+  Object *copy = heap_bump(size);
+  memcpy(copy, object, (size_t) size);
+  object->tag = FORWARDED_TAG;
+  object->dispatch = (void **) copy;
+  return copy;
+}
+
+static void promote_root(Object **slot) {
+  *slot = promote(*slot);
+}
+
+static void promote_attributes(Object *object) {
+  for (int64_t i = 0; i < attribute_count(object); i++) {
+    if (holds_object(object, i)) {
+      object->fields[i] = (int64_t) promote((Object *) object->fields[i]);
+    }
+  }
+}
+
+/* Promotes the nursery objects reachable from the roots and the remembered set, then scans the promoted objects in
+ * the order they were copied, like Cheney's algorithm, until none points to the nursery. The nursery is then empty. */
+static void minor_collection(void) {
+  char *scan = heap_top;
+  for_each_root(promote_root);
+  for (int64_t i = 0; i < remembered_count; i++) {
+    remembered[i]->size &= ~(int64_t) REMEMBERED_BIT;
+    promote_attributes(remembered[i]);
+  }
+  remembered_count = 0;
+  while (scan < heap_top) {
+    Object *object = (Object *) scan;
+    promote_attributes(object);
+    scan += object_size(object);
+  }
+  nursery_top = cool_nursery_start;
+}
+
+/* New address of an old object during a major collection, kept in its dispatch word */
+static Object *forward(Object *object) {
+  return object != NULL && in_heap(object) ? (Object *) object->dispatch : object;
+}
+
+static void forward_root(Object **slot) {
+  *slot = forward(*slot);
+}
+
+/* Mark-compact of the old generation, after a minor collection emptied the nursery: computes the new address of
+ * each live object, updates the roots and attributes, then slides the objects down in order and restores their
+ * dispatch table from the prototype of their class. */
+static void major_collection(void) {
+  mark_live();
+  char *free = heap_start;
+  for (char *position = heap_start; position < heap_top; position += object_size((Object *) position)) {
+    Object *object = (Object *) position;
+    if ((object->size & MARK_BIT) != 0) {
+      object->dispatch = (void **) free;
+      free += object_size(object);
+    }
+  }
+
+  for_each_root(forward_root);
+  for (char *position = heap_start; position < heap_top; position += object_size((Object *) position)) {
+    Object *object = (Object *) position;
+    for (int64_t i = 0; (object->size & MARK_BIT) != 0 && i < attribute_count(object); i++) {
+      if (holds_object(object, i)) {
+        object->fields[i] = (int64_t) forward((Object *) object->fields[i]);
+      }
+    }
+  }
+
+  for (char *position = heap_start; position < heap_top;) {
+    Object *object = (Object *) position;
+    int64_t size = object_size(object);
+    if ((object->size & MARK_BIT) != 0) {
+      Object *moved = (Object *) object->dispatch;
+      memmove(moved, object, (size_t) size);
+      moved->size = size;
+      moved->dispatch = cool_class_table[moved->tag].prototype->dispatch;
+    }
+    position += size;
+  }
+  heap_top = free;
+  old_limit = 2 * (heap_top - heap_start) > MIN_HEAP_LIMIT ? 2 * (heap_top - heap_start) : MIN_HEAP_LIMIT;
+}
+
+static void full_collection(void) {
+  minor_collection();
+  major_collection();
+}
+
+static Object *allocate(int64_t size) {
+  if (COOL_GC_STRESS) {
+    if (++stress_count % STRESS_MAJOR_PERIOD == 0) {
+      full_collection();
+    } else {
+      minor_collection();
+    }
+  }
+  if (size > LARGE_OBJECT_SIZE) {
+    if (heap_top - heap_start + size > old_limit) {
+      full_collection();
+    }
+    return heap_bump(size);
+  }
+  if (nursery_top + size > cool_nursery_start + cool_nursery_size) {
+    minor_collection();
+    if (heap_top - heap_start > old_limit) {
+      major_collection();
+    }
+  }
+  Object *object = (Object *) nursery_top;
+  nursery_top += size;
+  return object;
+}
+#endif
+
+/* A zeroed object of the class, which may collect garbage first */
+Object *cool_alloc(int64_t tag, int64_t size) {
+  Object *object = allocate(size);
+  memset(object, 0, (size_t) size);
+  object->tag = tag;
+  object->size = size;
+  object->dispatch = cool_class_table[tag].prototype->dispatch;
+  return object;
+}
+/* A copy of the prototype of the class, with default attribute values. The caller runs the initialiser. */
+Object *cool_new(int64_t tag) {
+  Object *prototype = cool_class_table[tag].prototype;
+  Object *object = cool_alloc(tag, prototype->size);
+  memcpy(object->fields, prototype->fields, (size_t) (prototype->size - HEADER_SIZE));
+  return object;
+}
+
+/* `new SELF_TYPE`: a new initialised object of the dynamic class of `self` */
+Object *cool_new_like(Object *self) {
+  ClassInfo *class = &cool_class_table[self->tag];
+  Object *object = cool_new(self->tag);
+  return class->init != NULL ? class->init(object) : object;
+}
+
+Object *cool_box_int(int64_t value) {
+  Object *box = cool_alloc(cool_int_tag, HEADER_SIZE + 8);
+  box->fields[0] = value;
+  return box;
+}
+
+Object *cool_box_bool(int64_t value) {
+  Object *box = cool_alloc(cool_bool_tag, HEADER_SIZE + 8);
+  box->fields[0] = value;
+  return box;
+}
+
+/* A string of the given length, whose characters are filled in by the caller */
+static Object *alloc_string(int64_t length) {
+  int64_t size = (HEADER_SIZE + 8 + length + 1 + 7) & ~(int64_t) 7;
+  Object *string = cool_alloc(cool_string_tag, size);
+  string->fields[0] = length;
+  return string;
+}
+
+static Object *new_string(const char *chars, int64_t length) {
+  Object *string = alloc_string(length);
+  memcpy((char *) &string->fields[1], chars, (size_t) length);
+  return string;
+}
+
+/* COOL `=` on objects: the same object, or basic objects with equal values */
+int64_t cool_equals(Object *a, Object *b) {
+  if (a == b) {
+    return 1;
+  }
+  if (a == NULL || b == NULL || a->tag != b->tag) {
+    return 0;
+  }
+  if (a->tag == cool_int_tag || a->tag == cool_bool_tag) {
+    return a->fields[0] == b->fields[0];
+  }
+  if (a->tag == cool_string_tag) {
+    return a->fields[0] == b->fields[0] && memcmp(string_chars(a), string_chars(b), (size_t) a->fields[0]) == 0;
+  }
+  return 0;
+}
+
+/* `at` is the source location of the expression, a string like `file.cl:12` */
+void cool_trap(int64_t kind, Object *value, Object *at) {
+  switch (kind) {
+    case TRAP_DISPATCH_ON_VOID:
+      runtime_error("%s: Dispatch to void.", string_chars(at));
+      break;
+    case TRAP_CASE_ON_VOID:
+      runtime_error("%s: Match on void in case statement.", string_chars(at));
+      break;
+    default:
+      fflush(stdout);
+      fprintf(stderr, "%s: ", string_chars(at));
+      runtime_error("No match in case statement for Class %s", value == NULL ? "Void" : string_chars(cool_class_table[value->tag].name));
+  }
+}
+
+void cool_division_by_zero(Object *at) {
+  runtime_error("%s: Division by zero.", string_chars(at));
+}
+
+Object *cool_Object_abort(Object *self) {
+  runtime_error("Abort called from class %s", string_chars(cool_class_table[self->tag].name));
+  return self;
+}
+
+Object *cool_Object_type_name(Object *self) {
+  return cool_class_table[self->tag].name;
+}
+
+Object *cool_Object_copy(Object *self) {
+  PROTECT(self);
+  Object *copy = cool_alloc(self->tag, object_size(self));
+  UNPROTECT(1);
+  memcpy(copy->fields, self->fields, (size_t) (object_size(self) - HEADER_SIZE));
+  remember_copy(copy);
+  return copy;
+}
+
+Object *cool_IO_out_string(Object *self, Object *string) {
+  fwrite(string_chars(string), 1, (size_t) string->fields[0], stdout);
+  return self;
+}
+
+Object *cool_IO_out_int(Object *self, int32_t value) {
+  printf("%d", value);
+  return self;
+}
+
+/* A line of input without its line break, empty at the end of the input. The length is returned in `length`. */
+static char *read_line(int64_t *length) {
+  fflush(stdout);
+  size_t capacity = 64;
+  size_t used = 0;
+  char *line = malloc(capacity);
+  int c = EOF;
+  while (line != NULL && (c = getchar()) != EOF && c != '\n') {
+    if (used + 1 == capacity) {
+      capacity *= 2;
+      line = realloc(line, capacity);
+      if (line == NULL) {
+        break;
+      }
+    }
+    line[used++] = (char) c;
+  }
+  if (line == NULL) {
+    located_error("Out of memory");
+  }
+  if (c == '\n' && used > 0 && line[used - 1] == '\r') {
+    used--;
+  }
+  *length = (int64_t) used;
+  return line;
+}
+
+Object *cool_IO_in_string(Object *self) {
+  (void) self;
+  int64_t length;
+  char *line = read_line(&length);
+  Object *string = new_string(line, length);
+  free(line);
+  return string;
+}
+
+/* The integer at the start of the line, after blanks, 0 if there is none or it does not fit in an `Int` */
+int32_t cool_IO_in_int(Object *self) {
+  (void) self;
+  int64_t length;
+  char *line = read_line(&length);
+  int64_t i = 0;
+  while (i < length && (line[i] == ' ' || (line[i] >= '\t' && line[i] <= '\r'))) {
+    i++;
+  }
+  int negative = i < length && line[i] == '-';
+  if (negative) {
+    i++;
+  }
+  int64_t value = 0;
+  int64_t digits = 0;
+  for (; i < length && line[i] >= '0' && line[i] <= '9'; i++, digits++) {
+    value = value * 10 + (line[i] - '0');
+    if (value > (int64_t) INT32_MAX + 1) {
+      value = INT64_MAX;
+      break;
+    }
+  }
+  free(line);
+  value = negative ? -value : value;
+  if (digits == 0 || value < INT32_MIN || value > INT32_MAX) {
+    value = 0;
+  }
+  return (int32_t) value;
+}
+
+int32_t cool_String_length(Object *self) {
+  return (int32_t) self->fields[0];
+}
+
+Object *cool_String_concat(Object *self, Object *other) {
+  PROTECT(self);
+  PROTECT(other);
+  Object *result = alloc_string(self->fields[0] + other->fields[0]);
+  UNPROTECT(2);
+  char *chars = (char *) &result->fields[1];
+  memcpy(chars, string_chars(self), (size_t) self->fields[0]);
+  memcpy(chars + self->fields[0], string_chars(other), (size_t) other->fields[0]);
+  return result;
+}
+
+Object *cool_String_substr(Object *self, int32_t start, int32_t length) {
+  int64_t i = start;
+  int64_t l = length;
+  if (i < 0) {
+    located_error("Index to substr is negative");
+  } else if (l < 0) {
+    located_error("Length to substr is negative");
+  } else if (i > self->fields[0]) {
+    located_error("Index to substr is too big");
+  } else if (i + l > self->fields[0]) {
+    located_error("Length to substr too long");
+  }
+  PROTECT(self);
+  Object *result = alloc_string(l);
+  UNPROTECT(1);
+  memcpy((char *) &result->fields[1], string_chars(self) + i, (size_t) l);
+  return result;
+}
+
+int main(void) {
+  heap_init();
+#if COOL_GC != GC_NONE && !COOL_SHADOW_STACK
+  stack_bottom = __builtin_frame_address(0);
+#endif
+  cool_main();
+  fflush(stdout);
+  return 0;
+}
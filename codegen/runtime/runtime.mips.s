@@ -0,0 +1,877 @@
+# Runtime of COOL programs compiled to MIPS32: memory management, the built-in methods of Object, IO and String,
+# and the runtime errors. The generated code provides the class table, the collector settings `cool_gc` and
+# `cool_gc_stress`, and `cool_main`. It runs on SPIM with its default trap handler, whose start routine calls
+# `main`, or on the simulator of the `mips` crate.
+#
+# Every object starts with a header of three words, the class tag, the size of the object in bytes and the
+# dispatch table of its class. Attributes follow as one word each, an object or the raw value of an attribute declared
+# `Int` or `Bool`. `Int` and `Bool` objects hold their value in the first word after the header, `String` objects
+# their length followed by the characters and a terminating zero byte.
+#
+# Functions take their first four arguments in $a0-$a3 and the others on the stack, and return in $v0. They may
+# change the $t, $a and $v registers. SPIM has no error output, so runtime errors are printed with the rest of the
+# output.
+#
+# The heap grows with `sbrk` above the data. Unless `cool_gc` is 0, the collector marks the live objects and sweeps
+# the others into free lists, and never moves objects. Generated functions keep every value in a stack slot, the
+# objects first, and store their number below the saved frame pointer, so the roots are found by walking the chain
+# of frame pointers, which `main` ends with 0. The runtime itself only holds objects across an allocation that the
+# generated code holds too. Objects below the heap, like string constants and prototypes, are never collected.
+
+        .data
+        .align 2
+# Location of the operation running that may allocate or call a built-in method, a `String` like `file.cl:12` set by
+# the generated code, 0 before the program starts
+cool_location:
+        .word 0
+cool_heap_start:
+        .word 0
+# End of the allocated part of the heap, then end of the memory taken with `sbrk`
+cool_heap_top:
+        .word 0
+cool_heap_end:
+        .word 0
+# Bytes allocated since the last collection, and before the next one
+cool_allocated:
+        .word 0
+cool_threshold:
+        .word 1048576
+# Heads of the free lists, at the offset of the block size below 256 bytes, then the list of larger blocks at 256
+cool_free_lists:
+        .space 260
+# Top of the mark stack, and whether it was full
+cool_mark_top:
+        .word 0
+cool_mark_overflow:
+        .word 0
+cool_mark_stack:
+        .space 16384
+cool_mark_stack_end:
+cool_line_buffer:
+        .space 65536
+cool_newline:
+        .asciiz "\n"
+cool_dispatch_on_void:
+        .asciiz ": Dispatch to void."
+cool_case_on_void:
+        .asciiz ": Match on void in case statement."
+cool_case_no_match:
+        .asciiz ": No match in case statement for Class "
+cool_void_name:
+        .asciiz "Void"
+cool_division_message:
+        .asciiz ": Division by zero."
+cool_abort_message:
+        .asciiz "Abort called from class "
+cool_substr_negative_index:
+        .asciiz "Index to substr is negative"
+cool_substr_negative_length:
+        .asciiz "Length to substr is negative"
+cool_substr_index_too_big:
+        .asciiz "Index to substr is too big"
+cool_substr_length_too_long:
+        .asciiz "Length to substr too long"
+cool_out_of_memory:
+        .asciiz "Out of memory"
+cool_location_separator:
+        .asciiz ": "
+
+        .text
+        .globl main
+main:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        sw $fp, 0($sp)
+        move $a0, $zero
+        li $v0, 9
+        syscall
+        sw $v0, cool_heap_start
+        sw $v0, cool_heap_top
+        sw $v0, cool_heap_end
+        la $t0, cool_mark_stack
+        sw $t0, cool_mark_top
+        # The frame of the first generated function saves it, which ends the walk of the collector
+        move $fp, $zero
+        jal cool_main
+        lw $fp, 0($sp)
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        move $v0, $zero
+        jr $ra
+
+# $v0 = address of the entry of the class table for the tag in $a0, changes $t9
+cool_class_entry:
+        sll $v0, $a0, 4
+        la $t9, cool_class_table
+        addu $v0, $v0, $t9
+        jr $ra
+
+# cool_alloc(tag, size): a zeroed object of the class with its header filled in. A free block is reused unless
+# the collector is off, after a collection if enough was allocated since the last one, or every time in stress
+# mode.
+cool_alloc:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        sw $a0, 8($sp)
+        sw $a1, 4($sp)
+        lw $t0, cool_gc
+        beqz $t0, cool_alloc_top
+        lw $t0, cool_gc_stress
+        bnez $t0, cool_alloc_collect
+        lw $t0, cool_allocated
+        addu $t0, $t0, $a1
+        lw $t1, cool_threshold
+        sltu $t0, $t1, $t0
+        beqz $t0, cool_alloc_free
+cool_alloc_collect:
+        jal cool_collect
+cool_alloc_free:
+        lw $a0, 4($sp)
+        jal cool_take_free
+        bnez $v0, cool_alloc_zero
+cool_alloc_top:
+        lw $a0, 4($sp)
+        lw $v0, cool_heap_top
+        addu $t0, $v0, $a0
+        lw $t1, cool_heap_end
+        sltu $t2, $t1, $t0
+        beqz $t2, cool_alloc_bump
+        # Grows the heap by at least 1 MiB, in multiples of 8 bytes so that it stays contiguous
+        subu $a0, $t0, $t1
+        li $t2, 1048576
+        sltu $t3, $a0, $t2
+        beqz $t3, cool_alloc_grow
+        move $a0, $t2
+cool_alloc_grow:
+        addiu $a0, $a0, 7
+        li $t2, -8
+        and $a0, $a0, $t2
+        move $t3, $a0
+        li $v0, 9
+        syscall
+        la $a0, cool_out_of_memory
+        li $t0, -1
+        beq $v0, $t0, cool_located_error
+        lw $t1, cool_heap_end
+        addu $t1, $t1, $t3
+        sw $t1, cool_heap_end
+        lw $a0, 4($sp)
+        lw $v0, cool_heap_top
+        addu $t0, $v0, $a0
+cool_alloc_bump:
+        sw $t0, cool_heap_top
+cool_alloc_zero:
+        lw $t0, cool_allocated
+        lw $t1, 4($sp)
+        addu $t0, $t0, $t1
+        sw $t0, cool_allocated
+        move $t0, $zero
+cool_alloc_zero_loop:
+        addu $t2, $v0, $t0
+        sw $zero, 0($t2)
+        addiu $t0, $t0, 4
+        bne $t0, $t1, cool_alloc_zero_loop
+        sw $v0, 0($sp)
+        lw $a0, 8($sp)
+        jal cool_class_entry
+        lw $t0, 4($v0)
+        lw $t0, 8($t0)
+        lw $v0, 0($sp)
+        lw $t1, 8($sp)
+        sw $t1, 0($v0)
+        lw $t1, 4($sp)
+        sw $t1, 4($v0)
+        sw $t0, 8($v0)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+# $a0 = address of the head of the free list of blocks of the size in $a1, changes $t0
+cool_free_list:
+        la $a0, cool_free_lists
+        sltiu $t0, $a1, 256
+        beqz $t0, cool_free_list_large
+        addu $a0, $a0, $a1
+        jr $ra
+cool_free_list_large:
+        addiu $a0, $a0, 256
+        jr $ra
+
+# cool_add_free(block, size): adds a block to the free lists, marked free by a tag of -1 and linked through its
+# third word, changes $t0-$t2
+cool_add_free:
+        move $t1, $a0
+        move $t2, $ra
+        jal cool_free_list
+        move $ra, $t2
+        li $t0, -1
+        sw $t0, 0($t1)
+        sw $a1, 4($t1)
+        lw $t0, 0($a0)
+        sw $t0, 8($t1)
+        sw $t1, 0($a0)
+        jr $ra
+
+# cool_take_free(size): a free block of the size, or 0. Small blocks come from the list of their size, then larger
+# blocks are split.
+cool_take_free:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        move $t3, $a0
+        sltiu $t0, $t3, 256
+        beqz $t0, cool_take_free_large
+        la $t1, cool_free_lists
+        addu $t1, $t1, $t3
+        lw $v0, 0($t1)
+        beqz $v0, cool_take_free_large
+        lw $t0, 8($v0)
+        sw $t0, 0($t1)
+        b cool_take_free_done
+cool_take_free_large:
+        la $t1, cool_free_lists
+        addiu $t1, $t1, 256
+cool_take_free_loop:
+        lw $v0, 0($t1)
+        beqz $v0, cool_take_free_done
+        # The rest of a split block must hold the header of a free block
+        lw $t4, 4($v0)
+        subu $t4, $t4, $t3
+        beqz $t4, cool_take_free_found
+        slti $t0, $t4, 12
+        beqz $t0, cool_take_free_found
+        addiu $t1, $v0, 8
+        b cool_take_free_loop
+cool_take_free_found:
+        lw $t0, 8($v0)
+        sw $t0, 0($t1)
+        beqz $t4, cool_take_free_done
+        sw $v0, 0($sp)
+        addu $a0, $v0, $t3
+        move $a1, $t4
+        jal cool_add_free
+        lw $v0, 0($sp)
+cool_take_free_done:
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_mark(object): marks an object of the heap and pushes it on the mark stack, changes $t0-$t2. Void and the
+# objects below the heap are never collected.
+cool_mark:
+        lw $t0, cool_heap_start
+        sltu $t0, $a0, $t0
+        bnez $t0, cool_mark_done
+        lw $t0, 4($a0)
+        andi $t1, $t0, 1
+        bnez $t1, cool_mark_done
+        ori $t0, $t0, 1
+        sw $t0, 4($a0)
+        lw $t0, cool_mark_top
+        la $t1, cool_mark_stack_end
+        bne $t0, $t1, cool_mark_push
+        li $t0, 1
+        sw $t0, cool_mark_overflow
+        jr $ra
+cool_mark_push:
+        sw $a0, 0($t0)
+        addiu $t0, $t0, 4
+        sw $t0, cool_mark_top
+cool_mark_done:
+        jr $ra
+
+# cool_scan(object): marks the attributes of an object that hold objects, basic objects have none. Changes $t0-$t6.
+cool_scan:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        lw $t0, 0($a0)
+        lw $t1, cool_int_tag
+        beq $t0, $t1, cool_scan_done
+        lw $t1, cool_bool_tag
+        beq $t0, $t1, cool_scan_done
+        lw $t1, cool_string_tag
+        beq $t0, $t1, cool_scan_done
+        sll $t0, $t0, 4
+        la $t1, cool_class_table
+        addu $t0, $t0, $t1
+        lw $t5, 12($t0)                 # raw attributes, or 0
+        addiu $t3, $a0, 12              # attribute
+        lw $t4, 4($a0)
+        li $t0, -2
+        and $t4, $t4, $t0
+        addu $t4, $a0, $t4              # end of the object
+cool_scan_loop:
+        beq $t3, $t4, cool_scan_done
+        beqz $t5, cool_scan_object
+        lbu $t6, 0($t5)
+        addiu $t5, $t5, 1
+        bnez $t6, cool_scan_next
+cool_scan_object:
+        lw $a0, 0($t3)
+        jal cool_mark
+cool_scan_next:
+        addiu $t3, $t3, 4
+        b cool_scan_loop
+cool_scan_done:
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_drain(): scans the objects of the mark stack until it is empty, changes $t0-$t7
+cool_drain:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+cool_drain_loop:
+        lw $t7, cool_mark_top
+        la $t0, cool_mark_stack
+        beq $t7, $t0, cool_drain_done
+        addiu $t7, $t7, -4
+        sw $t7, cool_mark_top
+        lw $a0, 0($t7)
+        jal cool_scan
+        b cool_drain_loop
+cool_drain_done:
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_collect(): marks the objects reachable from the slots of the generated functions, then sweeps the others
+# into the free lists. If the mark stack fills up, the heap is scanned again for marked objects whose attributes
+# may not be marked yet.
+cool_collect:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        sw $fp, 8($sp)                  # frame walked
+cool_collect_frame:
+        lw $t0, 8($sp)
+        beqz $t0, cool_collect_marked
+        lw $t1, -12($t0)
+        sw $t1, 4($sp)                  # object slots left in the frame
+cool_collect_slot:
+        lw $t1, 4($sp)
+        beqz $t1, cool_collect_caller
+        addiu $t1, $t1, -1
+        sw $t1, 4($sp)
+        lw $t0, 8($sp)
+        sll $t1, $t1, 2
+        subu $t0, $t0, $t1
+        lw $a0, -16($t0)
+        jal cool_mark
+        jal cool_drain
+        b cool_collect_slot
+cool_collect_caller:
+        lw $t0, 8($sp)
+        lw $t0, -8($t0)
+        sw $t0, 8($sp)
+        b cool_collect_frame
+cool_collect_marked:
+        lw $t0, cool_mark_overflow
+        beqz $t0, cool_collect_sweep
+        sw $zero, cool_mark_overflow
+        lw $t0, cool_heap_start
+        sw $t0, 8($sp)                  # object scanned
+cool_collect_heap:
+        lw $t0, 8($sp)
+        lw $t1, cool_heap_top
+        beq $t0, $t1, cool_collect_marked
+        lw $t1, 4($t0)
+        andi $t1, $t1, 1
+        beqz $t1, cool_collect_next
+        move $a0, $t0
+        jal cool_scan
+        jal cool_drain
+cool_collect_next:
+        lw $t0, 8($sp)
+        lw $t1, 4($t0)
+        li $t2, -2
+        and $t1, $t1, $t2
+        addu $t0, $t0, $t1
+        sw $t0, 8($sp)
+        b cool_collect_heap
+cool_collect_sweep:
+        jal cool_sweep
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+# cool_sweep(): unmarks live objects and merges the runs of dead objects and free blocks between them into free
+# blocks. A run at the end of the heap goes back to the unallocated memory. The heap then grows to about twice the
+# live objects before the next collection.
+cool_sweep:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        la $t0, cool_free_lists
+        addiu $t1, $t0, 260
+cool_sweep_clear:
+        sw $zero, 0($t0)
+        addiu $t0, $t0, 4
+        bne $t0, $t1, cool_sweep_clear
+        lw $t3, cool_heap_start         # object
+        move $t4, $zero                 # start of the run of dead objects, or 0
+        move $t5, $zero                 # bytes of live objects
+cool_sweep_loop:
+        lw $t0, cool_heap_top
+        beq $t3, $t0, cool_sweep_end
+        lw $t6, 4($t3)
+        andi $t0, $t6, 1
+        beqz $t0, cool_sweep_dead
+        addiu $t6, $t6, -1
+        sw $t6, 4($t3)
+        addu $t5, $t5, $t6
+        beqz $t4, cool_sweep_next
+        move $a0, $t4
+        subu $a1, $t3, $t4
+        jal cool_add_free
+        move $t4, $zero
+        b cool_sweep_next
+cool_sweep_dead:
+        bnez $t4, cool_sweep_next
+        move $t4, $t3
+cool_sweep_next:
+        addu $t3, $t3, $t6
+        b cool_sweep_loop
+cool_sweep_end:
+        beqz $t4, cool_sweep_threshold
+        sw $t4, cool_heap_top
+cool_sweep_threshold:
+        sw $zero, cool_allocated
+        li $t0, 1048576
+        sltu $t1, $t5, $t0
+        beqz $t1, cool_sweep_done
+        move $t5, $t0
+cool_sweep_done:
+        sw $t5, cool_threshold
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_copy_words(destination, source, size): copies the attributes of an object of the size, after the header
+cool_copy_words:
+        li $t0, 12
+cool_copy_words_loop:
+        bge $t0, $a2, cool_copy_words_done
+        addu $t1, $a1, $t0
+        lw $t2, 0($t1)
+        addu $t1, $a0, $t0
+        sw $t2, 0($t1)
+        addiu $t0, $t0, 4
+        b cool_copy_words_loop
+cool_copy_words_done:
+        jr $ra
+
+# cool_copy_bytes(destination, source, count)
+cool_copy_bytes:
+        blez $a2, cool_copy_bytes_done
+        lbu $t0, 0($a1)
+        sb $t0, 0($a0)
+        addiu $a0, $a0, 1
+        addiu $a1, $a1, 1
+        addiu $a2, $a2, -1
+        b cool_copy_bytes
+cool_copy_bytes_done:
+        jr $ra
+
+# cool_new(tag): a copy of the prototype of the class, with default attribute values. The caller runs the
+# initialiser.
+cool_new:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        jal cool_class_entry
+        lw $t0, 4($v0)
+        sw $t0, 8($sp)
+        lw $a1, 4($t0)
+        jal cool_alloc
+        sw $v0, 4($sp)
+        move $a0, $v0
+        lw $a1, 8($sp)
+        lw $a2, 4($a1)
+        jal cool_copy_words
+        lw $v0, 4($sp)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+# cool_new_like(self): `new SELF_TYPE`, a new initialised object of the dynamic class of `self`
+cool_new_like:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        lw $a0, 0($a0)
+        sw $a0, 0($sp)
+        jal cool_new
+        lw $a0, 0($sp)
+        sw $v0, 0($sp)
+        jal cool_class_entry
+        lw $t0, 8($v0)
+        lw $v0, 0($sp)
+        beqz $t0, cool_new_like_done
+        move $a0, $v0
+        jalr $t0
+cool_new_like_done:
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_box_int(value)
+cool_box_int:
+        lw $t0, cool_int_tag
+        b cool_box
+
+# cool_box_bool(value)
+cool_box_bool:
+        lw $t0, cool_bool_tag
+cool_box:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        sw $a0, 0($sp)
+        move $a0, $t0
+        li $a1, 16
+        jal cool_alloc
+        lw $t0, 0($sp)
+        sw $t0, 12($v0)
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_alloc_string(length): a string of the length, whose characters are filled in by the caller
+cool_alloc_string:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        sw $a0, 0($sp)
+        # Header, length, characters and terminating zero byte, rounded up to whole words
+        addiu $a1, $a0, 20
+        li $t0, -4
+        and $a1, $a1, $t0
+        lw $a0, cool_string_tag
+        jal cool_alloc
+        lw $t0, 0($sp)
+        sw $t0, 12($v0)
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+# cool_equals(a, b): COOL `=` on objects, the same object or basic objects with equal values
+cool_equals:
+        li $v0, 1
+        beq $a0, $a1, cool_equals_done
+        move $v0, $zero
+        beqz $a0, cool_equals_done
+        beqz $a1, cool_equals_done
+        lw $t0, 0($a0)
+        lw $t1, 0($a1)
+        bne $t0, $t1, cool_equals_done
+        lw $t2, 12($a0)
+        lw $t3, 12($a1)
+        lw $t1, cool_int_tag
+        beq $t0, $t1, cool_equals_values
+        lw $t1, cool_bool_tag
+        beq $t0, $t1, cool_equals_values
+        lw $t1, cool_string_tag
+        bne $t0, $t1, cool_equals_done
+        bne $t2, $t3, cool_equals_done
+        addiu $a0, $a0, 16
+        addiu $a1, $a1, 16
+cool_equals_chars:
+        blez $t2, cool_equals_true
+        lbu $t0, 0($a0)
+        lbu $t1, 0($a1)
+        bne $t0, $t1, cool_equals_done
+        addiu $a0, $a0, 1
+        addiu $a1, $a1, 1
+        addiu $t2, $t2, -1
+        b cool_equals_chars
+cool_equals_values:
+        bne $t2, $t3, cool_equals_done
+cool_equals_true:
+        li $v0, 1
+cool_equals_done:
+        jr $ra
+
+# Prints the characters at $a0, then the name of the class of the object in $a1 or `Void` if it is 0, a line
+# break, and exits with code 1
+cool_error_with_class:
+        li $v0, 4
+        syscall
+        la $a0, cool_void_name
+        beqz $a1, cool_error_class_name
+        lw $a0, 0($a1)
+        jal cool_class_entry
+        lw $a0, 0($v0)
+        addiu $a0, $a0, 16
+cool_error_class_name:
+        li $v0, 4
+        syscall
+        b cool_error_end
+
+# Prints the characters at $a0 and a line break, and exits with code 1
+cool_error:
+        li $v0, 4
+        syscall
+cool_error_end:
+        la $a0, cool_newline
+        li $v0, 4
+        syscall
+        li $a0, 1
+        li $v0, 17
+        syscall
+
+# Prints the location in `cool_location` if it is set, then the characters at $a0 and a line break, and exits with
+# code 1
+cool_located_error:
+        move $t0, $a0
+        lw $t1, cool_location
+        beqz $t1, cool_located_error_message
+        addiu $a0, $t1, 16
+        li $v0, 4
+        syscall
+        la $a0, cool_location_separator
+        li $v0, 4
+        syscall
+cool_located_error_message:
+        move $a0, $t0
+        b cool_error
+
+# cool_trap(kind, value, at): the runtime errors of `trap` and `check_void`, in the order of `TrapKind`. `at` is the
+# source location of the expression, a `String` like `file.cl:12`.
+cool_trap:
+        la $t0, cool_dispatch_on_void
+        beqz $a0, cool_trap_message
+        la $t0, cool_case_on_void
+        li $t1, 1
+        beq $a0, $t1, cool_trap_message
+        addiu $a0, $a2, 16
+        li $v0, 4
+        syscall
+        la $a0, cool_case_no_match
+        b cool_error_with_class
+cool_trap_message:
+        addiu $a0, $a2, 16
+        li $v0, 4
+        syscall
+        move $a0, $t0
+        b cool_error
+
+# cool_division_by_zero(at)
+cool_division_by_zero:
+        addiu $a0, $a0, 16
+        li $v0, 4
+        syscall
+        la $a0, cool_division_message
+        b cool_error
+
+cool_Object_abort:
+        move $a1, $a0
+        la $a0, cool_abort_message
+        b cool_error_with_class
+
+cool_Object_type_name:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        lw $a0, 0($a0)
+        jal cool_class_entry
+        lw $v0, 0($v0)
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+cool_Object_copy:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        sw $a0, 8($sp)
+        lw $a1, 4($a0)
+        lw $a0, 0($a0)
+        jal cool_alloc
+        sw $v0, 4($sp)
+        move $a0, $v0
+        lw $a1, 8($sp)
+        lw $a2, 4($a1)
+        jal cool_copy_words
+        lw $v0, 4($sp)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+cool_IO_out_string:
+        move $t0, $a0
+        addiu $a0, $a1, 16
+        li $v0, 4
+        syscall
+        move $v0, $t0
+        jr $ra
+
+cool_IO_out_int:
+        move $t0, $a0
+        move $a0, $a1
+        li $v0, 1
+        syscall
+        move $v0, $t0
+        jr $ra
+
+# Reads a line of input into the line buffer, returns its length without the line break, 0 at the end of the
+# input
+cool_read_line:
+        la $a0, cool_line_buffer
+        li $a1, 65536
+        li $v0, 8
+        syscall
+        la $t0, cool_line_buffer
+        move $v0, $zero
+cool_read_line_loop:
+        addu $t1, $t0, $v0
+        lbu $t2, 0($t1)
+        beqz $t2, cool_read_line_done
+        li $t3, 10
+        beq $t2, $t3, cool_read_line_break
+        addiu $v0, $v0, 1
+        b cool_read_line_loop
+cool_read_line_break:
+        # A carriage return before the line break is dropped too
+        blez $v0, cool_read_line_done
+        lbu $t2, -1($t1)
+        li $t3, 13
+        bne $t2, $t3, cool_read_line_done
+        addiu $v0, $v0, -1
+cool_read_line_done:
+        jr $ra
+
+cool_IO_in_string:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        jal cool_read_line
+        sw $v0, 8($sp)
+        move $a0, $v0
+        jal cool_alloc_string
+        sw $v0, 4($sp)
+        addiu $a0, $v0, 16
+        la $a1, cool_line_buffer
+        lw $a2, 8($sp)
+        jal cool_copy_bytes
+        lw $v0, 4($sp)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+# The integer at the start of the line, after blanks, 0 if there is none or it does not fit in an `Int`
+cool_IO_in_int:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        jal cool_read_line
+        la $t0, cool_line_buffer
+cool_in_int_blanks:
+        lbu $t1, 0($t0)
+        li $t2, 32
+        beq $t1, $t2, cool_in_int_blank
+        addiu $t3, $t1, -9
+        sltiu $t3, $t3, 5
+        beqz $t3, cool_in_int_sign
+cool_in_int_blank:
+        addiu $t0, $t0, 1
+        b cool_in_int_blanks
+cool_in_int_sign:
+        move $t4, $zero                 # 1 if negative
+        li $t2, 45
+        bne $t1, $t2, cool_in_int_digits
+        li $t4, 1
+        addiu $t0, $t0, 1
+cool_in_int_digits:
+        move $t5, $zero                 # value, at most 2^31
+        move $t6, $zero                 # number of digits
+        move $t7, $zero                 # 1 if the value does not fit
+cool_in_int_loop:
+        lbu $t1, 0($t0)
+        addiu $t1, $t1, -48
+        sltiu $t2, $t1, 10
+        beqz $t2, cool_in_int_end
+        bnez $t7, cool_in_int_next
+        li $t2, 214748364
+        sltu $t3, $t2, $t5
+        bnez $t3, cool_in_int_overflow
+        bne $t5, $t2, cool_in_int_add
+        sltiu $t3, $t1, 9
+        beqz $t3, cool_in_int_overflow
+cool_in_int_add:
+        li $t2, 10
+        mul $t5, $t5, $t2
+        addu $t5, $t5, $t1
+        b cool_in_int_next
+cool_in_int_overflow:
+        li $t7, 1
+cool_in_int_next:
+        addiu $t6, $t6, 1
+        addiu $t0, $t0, 1
+        b cool_in_int_loop
+cool_in_int_end:
+        move $v0, $zero
+        beqz $t6, cool_in_int_done
+        bnez $t7, cool_in_int_done
+        bnez $t4, cool_in_int_negative
+        # 2^31 only fits when negative
+        lui $t2, 0x8000
+        beq $t5, $t2, cool_in_int_done
+        move $v0, $t5
+        b cool_in_int_done
+cool_in_int_negative:
+        subu $v0, $zero, $t5
+cool_in_int_done:
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+cool_String_length:
+        lw $v0, 12($a0)
+        jr $ra
+
+cool_String_concat:
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        sw $a0, 8($sp)
+        sw $a1, 4($sp)
+        lw $t0, 12($a0)
+        lw $t1, 12($a1)
+        addu $a0, $t0, $t1
+        jal cool_alloc_string
+        sw $v0, 0($sp)
+        addiu $a0, $v0, 16
+        lw $a1, 8($sp)
+        lw $a2, 12($a1)
+        addiu $a1, $a1, 16
+        jal cool_copy_bytes
+        lw $a1, 4($sp)
+        lw $a2, 12($a1)
+        addiu $a1, $a1, 16
+        jal cool_copy_bytes
+        lw $v0, 0($sp)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
+
+cool_String_substr:
+        move $t0, $a1                   # start
+        move $t1, $a2                   # length
+        lw $t2, 12($a0)                 # length of the string
+        move $t4, $a0
+        la $a0, cool_substr_negative_index
+        bltz $t0, cool_located_error
+        la $a0, cool_substr_negative_length
+        bltz $t1, cool_located_error
+        la $a0, cool_substr_index_too_big
+        bgt $t0, $t2, cool_located_error
+        la $a0, cool_substr_length_too_long
+        addu $t3, $t0, $t1
+        bgt $t3, $t2, cool_located_error
+        move $a0, $t4
+        addiu $sp, $sp, -16
+        sw $ra, 12($sp)
+        addu $t0, $a0, $t0
+        addiu $t0, $t0, 16
+        sw $t0, 8($sp)
+        sw $t1, 4($sp)
+        move $a0, $t1
+        jal cool_alloc_string
+        sw $v0, 0($sp)
+        addiu $a0, $v0, 16
+        lw $a1, 8($sp)
+        lw $a2, 4($sp)
+        jal cool_copy_bytes
+        lw $v0, 0($sp)
+        lw $ra, 12($sp)
+        addiu $sp, $sp, 16
+        jr $ra
@@ -0,0 +1,1421 @@
+;; Runtime of COOL programs compiled to WebAssembly: memory management, the built-in methods of Object, IO and
+;; String, and the runtime errors. These module fields are spliced into the module of every program, which provides
+;; the class table, the tags of the basic classes, the bounds of the shadow stack and heap, and `$cool_main`.
+;;
+;; Objects have the layout of the native runtime with 32-bit words: the class tag, the size of the object in bytes
+;; and the address of the dispatch table, which holds indices in the function table. Attributes follow as one word
+;; each, an object or the raw value of an attribute declared `Int` or `Bool`. `Int` and `Bool` objects hold their
+;; value in the first word after the header, `String` objects their length followed by the characters and a
+;; terminating zero byte.
+;;
+;; WebAssembly locals cannot be scanned, so generated functions keep their object values in a frame of the shadow
+;; stack as well. The collector marks from the shadow stack and sweeps the heap into free lists, and never moves
+;; objects. The memory below the generated data is laid out as:
+;;
+;;   16    iovec of the WASI calls, then the number of bytes they wrote or read at 24
+;;   32    heads of the free lists, indexed by block size below 256 bytes, then the list of larger blocks at 288
+;;   512   output buffer of 4096 bytes
+;;   4608  input buffer of 4096 bytes
+;;   8704  digits of `out_int`
+;;   8760  messages of the runtime errors
+
+(import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+(import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+(import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+(global $sp (mut i32) (i32.const 0))                 ;; top of the shadow stack
+(global $heap_top (mut i32) (i32.const 0))           ;; end of the allocated part of the heap
+(global $heap_end (mut i32) (i32.const 0))           ;; end of the memory
+(global $allocated (mut i32) (i32.const 0))          ;; bytes allocated since the last collection
+(global $threshold (mut i32) (i32.const 1048576))    ;; bytes allocated before the next collection
+(global $mark_sp (mut i32) (i32.const 0))            ;; top of the mark stack, above the shadow stack
+(global $mark_overflow (mut i32) (i32.const 0))      ;; set when the mark stack was full
+(global $out_length (mut i32) (i32.const 0))         ;; bytes in the output buffer
+(global $in_position (mut i32) (i32.const 0))        ;; next byte of the input buffer
+(global $in_length (mut i32) (i32.const 0))          ;; bytes in the input buffer
+(global $in_eof (mut i32) (i32.const 0))             ;; set at the end of the input
+(global $cool_location (mut i32) (i32.const 0))      ;; location of the operation running, see `$located_error`
+
+(data (i32.const 8760) "\n")
+(data (i32.const 8768) ": Dispatch to void.")
+(data (i32.const 8832) ": Match on void in case statement.")
+(data (i32.const 8896) ": No match in case statement for Class ")
+(data (i32.const 8960) ": Division by zero.")
+(data (i32.const 9024) "Abort called from class ")
+(data (i32.const 9088) "Index to substr is negative")
+(data (i32.const 9152) "Stack overflow")
+(data (i32.const 9216) "Out of memory")
+(data (i32.const 9280) "Void")
+(data (i32.const 9344) "Length to substr is negative")
+(data (i32.const 9408) "Index to substr is too big")
+(data (i32.const 9472) "Length to substr too long")
+(data (i32.const 9536) ": ")
+
+(func $_start (export "_start")
+  global.get $cool_stack_base
+  global.set $sp
+  global.get $cool_heap_start
+  global.set $heap_top
+  memory.size
+  i32.const 16
+  i32.shl
+  global.set $heap_end
+  call $cool_main
+  call $flush)
+
+;; Output and input
+
+;; Writes all the bytes to the file descriptor, exits if that fails
+(func $write (param $fd i32) (param $start i32) (param $length i32)
+  loop $more
+    local.get $length
+    i32.eqz
+    if
+      return
+    end
+    i32.const 16
+    local.get $start
+    i32.store
+    i32.const 20
+    local.get $length
+    i32.store
+    local.get $fd
+    i32.const 16
+    i32.const 1
+    i32.const 24
+    call $fd_write
+    if
+      i32.const 1
+      call $proc_exit
+    end
+    local.get $start
+    i32.const 24
+    i32.load
+    i32.add
+    local.set $start
+    local.get $length
+    i32.const 24
+    i32.load
+    i32.sub
+    local.set $length
+    br $more
+  end)
+
+(func $flush
+  i32.const 1
+  i32.const 512
+  global.get $out_length
+  call $write
+  i32.const 0
+  global.set $out_length)
+
+;; Buffers bytes for the standard output
+(func $out_bytes (param $start i32) (param $length i32)
+  global.get $out_length
+  local.get $length
+  i32.add
+  i32.const 4096
+  i32.gt_u
+  if
+    call $flush
+  end
+  local.get $length
+  i32.const 4096
+  i32.gt_u
+  if
+    i32.const 1
+    local.get $start
+    local.get $length
+    call $write
+    return
+  end
+  i32.const 512
+  global.get $out_length
+  i32.add
+  local.get $start
+  local.get $length
+  memory.copy
+  global.get $out_length
+  local.get $length
+  i32.add
+  global.set $out_length)
+
+;; Next byte of the standard input, -1 at its end
+(func $read_byte (result i32)
+  (local $byte i32)
+  global.get $in_position
+  global.get $in_length
+  i32.ge_u
+  if
+    global.get $in_eof
+    if
+      i32.const -1
+      return
+    end
+    i32.const 16
+    i32.const 4608
+    i32.store
+    i32.const 20
+    i32.const 4096
+    i32.store
+    i32.const 0
+    i32.const 16
+    i32.const 1
+    i32.const 24
+    call $fd_read
+    ;; an error ends the input
+    if
+      i32.const 0
+      global.set $in_length
+    else
+      i32.const 24
+      i32.load
+      global.set $in_length
+    end
+    i32.const 0
+    global.set $in_position
+    global.get $in_length
+    i32.eqz
+    if
+      i32.const 1
+      global.set $in_eof
+      i32.const -1
+      return
+    end
+  end
+  i32.const 4608
+  global.get $in_position
+  i32.add
+  i32.load8_u
+  global.get $in_position
+  i32.const 1
+  i32.add
+  global.set $in_position)
+
+;; A line of input without its line break, empty at the end of the input
+(func $read_line (result i32)
+  (local $frame i32)
+  (local $string i32)
+  (local $capacity i32)
+  (local $length i32)
+  (local $byte i32)
+  (local $larger i32)
+  call $flush
+  ;; the string being read is a root, it is replaced by a larger one when full
+  global.get $sp
+  local.tee $frame
+  i32.const 0
+  i32.store
+  local.get $frame
+  i32.const 4
+  i32.add
+  global.set $sp
+  call $check_stack
+  i32.const 60
+  local.tee $capacity
+  call $alloc_string
+  local.set $string
+  local.get $frame
+  local.get $string
+  i32.store
+  block $done
+    loop $next
+      call $read_byte
+      local.tee $byte
+      i32.const -1
+      i32.eq
+      br_if $done
+      local.get $byte
+      i32.const 10
+      i32.eq
+      br_if $done
+      local.get $length
+      local.get $capacity
+      i32.eq
+      if
+        local.get $capacity
+        i32.const 1
+        i32.shl
+        local.tee $capacity
+        call $alloc_string
+        local.tee $larger
+        i32.const 16
+        i32.add
+        local.get $string
+        i32.const 16
+        i32.add
+        local.get $length
+        memory.copy
+        local.get $larger
+        local.set $string
+        local.get $frame
+        local.get $string
+        i32.store
+      end
+      local.get $string
+      i32.const 16
+      i32.add
+      local.get $length
+      i32.add
+      local.get $byte
+      i32.store8
+      local.get $length
+      i32.const 1
+      i32.add
+      local.set $length
+      br $next
+    end
+  end
+  ;; a carriage return before the line feed is part of the line break
+  local.get $byte
+  i32.const 10
+  i32.eq
+  local.get $length
+  i32.const 0
+  i32.ne
+  i32.and
+  if
+    local.get $string
+    i32.const 15
+    i32.add
+    local.get $length
+    i32.add
+    i32.load8_u
+    i32.const 13
+    i32.eq
+    if
+      local.get $length
+      i32.const 1
+      i32.sub
+      local.set $length
+    end
+  end
+  local.get $string
+  i32.const 16
+  i32.add
+  local.get $length
+  i32.add
+  i32.const 0
+  i32.store8
+  local.get $string
+  local.get $length
+  i32.store offset=12
+  local.get $frame
+  global.set $sp
+  local.get $string)
+
+;; Runtime errors
+
+;; Prints the message, followed by the detail if its length is not 0, and exits with status 1
+(func $runtime_error (param $message i32) (param $length i32) (param $detail i32) (param $detail_length i32)
+  call $flush
+  i32.const 2
+  local.get $message
+  local.get $length
+  call $write
+  i32.const 2
+  local.get $detail
+  local.get $detail_length
+  call $write
+  i32.const 2
+  i32.const 8760
+  i32.const 1
+  call $write
+  i32.const 1
+  call $proc_exit)
+
+;; Prints the location in `$cool_location` if it is set, then the message, and exits with status 1. Generated code sets
+;; it to the `String` of the location of each operation that may allocate or call a built-in method.
+(func $located_error (param $message i32) (param $length i32)
+  global.get $cool_location
+  if
+    call $flush
+    i32.const 2
+    global.get $cool_location
+    i32.const 16
+    i32.add
+    global.get $cool_location
+    i32.load offset=12
+    call $write
+    i32.const 2
+    i32.const 9536
+    i32.const 2
+    call $write
+  end
+  local.get $message
+  local.get $length
+  i32.const 0
+  i32.const 0
+  call $runtime_error)
+
+;; Name of the class of an object, a `String`
+(func $class_name (param $object i32) (result i32)
+  global.get $cool_class_table
+  local.get $object
+  i32.load
+  i32.const 16
+  i32.mul
+  i32.add
+  i32.load)
+
+;; Reports the error of a `trap` or a failed `check_void`, kinds are in the order of `TrapKind`. `$at` is the
+;; source location of the expression, a `String` like `file.cl:12`.
+(func $cool_trap (param $kind i32) (param $value i32) (param $at i32)
+  (local $name i32)
+  local.get $kind
+  i32.eqz
+  if
+    local.get $at
+    i32.const 16
+    i32.add
+    local.get $at
+    i32.load offset=12
+    i32.const 8768
+    i32.const 19
+    call $runtime_error
+  end
+  local.get $kind
+  i32.const 1
+  i32.eq
+  if
+    local.get $at
+    i32.const 16
+    i32.add
+    local.get $at
+    i32.load offset=12
+    i32.const 8832
+    i32.const 34
+    call $runtime_error
+  end
+  call $flush
+  i32.const 2
+  local.get $at
+  i32.const 16
+  i32.add
+  local.get $at
+  i32.load offset=12
+  call $write
+  local.get $value
+  i32.eqz
+  if
+    i32.const 8896
+    i32.const 39
+    i32.const 9280
+    i32.const 4
+    call $runtime_error
+  end
+  local.get $value
+  call $class_name
+  local.set $name
+  i32.const 8896
+  i32.const 39
+  local.get $name
+  i32.const 16
+  i32.add
+  local.get $name
+  i32.load offset=12
+  call $runtime_error)
+
+(func $cool_division_by_zero (param $at i32)
+  local.get $at
+  i32.const 16
+  i32.add
+  local.get $at
+  i32.load offset=12
+  i32.const 8960
+  i32.const 19
+  call $runtime_error)
+
+;; Called by functions when their frame does not fit in the shadow stack
+(func $cool_stack_overflow
+  i32.const 9152
+  i32.const 14
+  i32.const 0
+  i32.const 0
+  call $runtime_error)
+
+(func $check_stack
+  global.get $sp
+  global.get $cool_stack_limit
+  i32.gt_u
+  if
+    call $cool_stack_overflow
+  end)
+
+(func $out_of_memory
+  i32.const 9216
+  i32.const 13
+  call $located_error)
+
+;; Allocation
+
+;; A zeroed object of the size with the header of the class
+(func $cool_alloc (param $tag i32) (param $size i32) (result i32)
+  (local $object i32)
+  global.get $allocated
+  local.get $size
+  i32.add
+  global.get $threshold
+  i32.gt_u
+  if
+    call $collect
+  end
+  local.get $size
+  call $take_free
+  local.tee $object
+  i32.eqz
+  if
+    global.get $heap_top
+    local.get $size
+    i32.add
+    global.get $heap_end
+    i32.gt_u
+    if
+      local.get $size
+      call $grow
+    end
+    global.get $heap_top
+    local.set $object
+    global.get $heap_top
+    local.get $size
+    i32.add
+    global.set $heap_top
+  end
+  global.get $allocated
+  local.get $size
+  i32.add
+  global.set $allocated
+  local.get $object
+  i32.const 0
+  local.get $size
+  memory.fill
+  local.get $object
+  local.get $tag
+  i32.store
+  local.get $object
+  local.get $size
+  i32.store offset=4
+  local.get $object
+  global.get $cool_class_table
+  local.get $tag
+  i32.const 16
+  i32.mul
+  i32.add
+  i32.load offset=4
+  i32.load offset=8
+  i32.store offset=8
+  local.get $object)
+
+;; Grows the memory so that the size fits after the top of the heap, by at least 1 MiB
+(func $grow (param $size i32)
+  (local $pages i32)
+  global.get $heap_top
+  local.get $size
+  i32.add
+  global.get $heap_end
+  i32.sub
+  i32.const 65535
+  i32.add
+  i32.const 16
+  i32.shr_u
+  local.tee $pages
+  i32.const 16
+  local.get $pages
+  i32.const 16
+  i32.gt_u
+  select
+  memory.grow
+  i32.const -1
+  i32.eq
+  if
+    call $out_of_memory
+  end
+  memory.size
+  i32.const 16
+  i32.shl
+  global.set $heap_end)
+
+;; Address of the head of the free list of blocks of the size
+(func $free_list (param $size i32) (result i32)
+  local.get $size
+  i32.const 32
+  i32.add
+  i32.const 288
+  local.get $size
+  i32.const 256
+  i32.lt_u
+  select)
+
+;; Adds a block to the free lists, marked free by a tag of -1 and linked through its third word
+(func $add_free (param $block i32) (param $size i32)
+  (local $list i32)
+  local.get $block
+  i32.const -1
+  i32.store
+  local.get $block
+  local.get $size
+  i32.store offset=4
+  local.get $block
+  local.get $size
+  call $free_list
+  local.tee $list
+  i32.load
+  i32.store offset=8
+  local.get $list
+  local.get $block
+  i32.store)
+
+;; A free block of the size, or 0. Small blocks come from the list of their size, then larger blocks are split.
+(func $take_free (param $size i32) (result i32)
+  (local $list i32)
+  (local $block i32)
+  (local $link i32)
+  (local $rest i32)
+  local.get $size
+  i32.const 256
+  i32.lt_u
+  if
+    local.get $size
+    call $free_list
+    local.tee $list
+    i32.load
+    local.tee $block
+    if
+      local.get $list
+      local.get $block
+      i32.load offset=8
+      i32.store
+      local.get $block
+      return
+    end
+  end
+  i32.const 288
+  local.set $link
+  loop $next
+    local.get $link
+    i32.load
+    local.tee $block
+    i32.eqz
+    if
+      i32.const 0
+      return
+    end
+    ;; the rest of a split block must hold the header of a free block
+    local.get $block
+    i32.load offset=4
+    local.get $size
+    i32.sub
+    local.tee $rest
+    i32.eqz
+    local.get $rest
+    i32.const 12
+    i32.ge_s
+    i32.or
+    if
+      local.get $link
+      local.get $block
+      i32.load offset=8
+      i32.store
+      local.get $rest
+      if
+        local.get $block
+        local.get $size
+        i32.add
+        local.get $rest
+        call $add_free
+      end
+      local.get $block
+      return
+    end
+    local.get $block
+    i32.const 8
+    i32.add
+    local.set $link
+    br $next
+  end
+  i32.const 0)
+
+;; Garbage collection
+
+;; Marks an object of the heap and pushes it on the mark stack, static objects are never collected
+(func $mark (param $object i32)
+  (local $size i32)
+  local.get $object
+  global.get $cool_heap_start
+  i32.lt_u
+  if
+    return
+  end
+  local.get $object
+  i32.load offset=4
+  local.tee $size
+  i32.const 1
+  i32.and
+  if
+    return
+  end
+  local.get $object
+  local.get $size
+  i32.const 1
+  i32.or
+  i32.store offset=4
+  global.get $mark_sp
+  global.get $cool_stack_limit
+  i32.ge_u
+  if
+    i32.const 1
+    global.set $mark_overflow
+    return
+  end
+  global.get $mark_sp
+  local.get $object
+  i32.store
+  global.get $mark_sp
+  i32.const 4
+  i32.add
+  global.set $mark_sp)
+
+;; Marks the attributes of an object that hold objects, basic objects have none. The fourth word of a class table
+;; entry is the address of a byte per attribute, non-zero if it holds a raw value, or 0 if every attribute holds an
+;; object.
+(func $scan (param $object i32)
+  (local $tag i32)
+  (local $field i32)
+  (local $end i32)
+  (local $raw i32)
+  local.get $object
+  i32.load
+  local.tee $tag
+  global.get $cool_int_tag
+  i32.eq
+  local.get $tag
+  global.get $cool_bool_tag
+  i32.eq
+  i32.or
+  local.get $tag
+  global.get $cool_string_tag
+  i32.eq
+  i32.or
+  if
+    return
+  end
+  global.get $cool_class_table
+  local.get $tag
+  i32.const 16
+  i32.mul
+  i32.add
+  i32.load offset=12
+  local.set $raw
+  local.get $object
+  i32.const 12
+  i32.add
+  local.set $field
+  local.get $object
+  local.get $object
+  i32.load offset=4
+  i32.const -2
+  i32.and
+  i32.add
+  local.set $end
+  block $done
+    loop $next
+      local.get $field
+      local.get $end
+      i32.ge_u
+      br_if $done
+      block $skip
+        block $object
+          local.get $raw
+          i32.eqz
+          br_if $object
+          local.get $raw
+          i32.load8_u
+          local.get $raw
+          i32.const 1
+          i32.add
+          local.set $raw
+          br_if $skip
+        end
+        local.get $field
+        i32.load
+        call $mark
+      end
+      local.get $field
+      i32.const 4
+      i32.add
+      local.set $field
+      br $next
+    end
+  end)
+
+;; Scans the objects of the mark stack until it is empty
+(func $drain
+  block $empty
+    loop $next
+      global.get $mark_sp
+      global.get $sp
+      i32.le_u
+      br_if $empty
+      global.get $mark_sp
+      i32.const 4
+      i32.sub
+      global.set $mark_sp
+      global.get $mark_sp
+      i32.load
+      call $scan
+      br $next
+    end
+  end)
+
+;; Marks the objects reachable from the shadow stack, then sweeps the others into the free lists.
+;; The mark stack uses the free part of the shadow stack. If it fills up, the heap is scanned again for marked
+;; objects whose attributes may not be marked yet.
+(func $collect
+  (local $root i32)
+  (local $object i32)
+  (local $size i32)
+  global.get $sp
+  global.set $mark_sp
+  global.get $cool_stack_base
+  local.set $root
+  block $roots_done
+    loop $roots
+      local.get $root
+      global.get $sp
+      i32.ge_u
+      br_if $roots_done
+      local.get $root
+      i32.load
+      call $mark
+      call $drain
+      local.get $root
+      i32.const 4
+      i32.add
+      local.set $root
+      br $roots
+    end
+  end
+  block $marked
+    loop $rescan
+      global.get $mark_overflow
+      i32.eqz
+      br_if $marked
+      i32.const 0
+      global.set $mark_overflow
+      global.get $cool_heap_start
+      local.set $object
+      block $heap_done
+        loop $heap
+          local.get $object
+          global.get $heap_top
+          i32.ge_u
+          br_if $heap_done
+          local.get $object
+          i32.load offset=4
+          local.tee $size
+          i32.const 1
+          i32.and
+          if
+            local.get $object
+            call $scan
+            call $drain
+          end
+          local.get $object
+          local.get $size
+          i32.const -2
+          i32.and
+          i32.add
+          local.set $object
+          br $heap
+        end
+      end
+      br $rescan
+    end
+  end
+  call $sweep)
+
+;; Unmarks live objects and merges the runs of dead objects and free blocks between them into free blocks. A run
+;; at the end of the heap goes back to the unallocated memory.
+(func $sweep
+  (local $object i32)
+  (local $size i32)
+  (local $free i32)
+  (local $live i32)
+  i32.const 32
+  i32.const 0
+  i32.const 260
+  memory.fill
+  global.get $cool_heap_start
+  local.set $object
+  block $done
+    loop $next
+      local.get $object
+      global.get $heap_top
+      i32.ge_u
+      br_if $done
+      local.get $object
+      i32.load offset=4
+      local.tee $size
+      i32.const 1
+      i32.and
+      if
+        local.get $object
+        local.get $size
+        i32.const -2
+        i32.and
+        local.tee $size
+        i32.store offset=4
+        local.get $live
+        local.get $size
+        i32.add
+        local.set $live
+        local.get $free
+        if
+          local.get $free
+          local.get $object
+          local.get $free
+          i32.sub
+          call $add_free
+          i32.const 0
+          local.set $free
+        end
+      else
+        local.get $free
+        i32.eqz
+        if
+          local.get $object
+          local.set $free
+        end
+      end
+      local.get $object
+      local.get $size
+      i32.add
+      local.set $object
+      br $next
+    end
+  end
+  local.get $free
+  if
+    local.get $free
+    global.set $heap_top
+  end
+  i32.const 0
+  global.set $allocated
+  ;; the heap grows to about twice the live objects before the next collection
+  local.get $live
+  i32.const 1048576
+  local.get $live
+  i32.const 1048576
+  i32.gt_u
+  select
+  global.set $threshold)
+
+;; Objects
+
+;; A copy of the prototype of the class, with default attribute values. The caller runs the initialiser.
+(func $cool_new (param $tag i32) (result i32)
+  (local $prototype i32)
+  (local $object i32)
+  global.get $cool_class_table
+  local.get $tag
+  i32.const 16
+  i32.mul
+  i32.add
+  i32.load offset=4
+  local.set $prototype
+  local.get $tag
+  local.get $prototype
+  i32.load offset=4
+  call $cool_alloc
+  local.tee $object
+  i32.const 12
+  i32.add
+  local.get $prototype
+  i32.const 12
+  i32.add
+  local.get $prototype
+  i32.load offset=4
+  i32.const 12
+  i32.sub
+  memory.copy
+  local.get $object)
+
+;; `new SELF_TYPE`: a new initialised object of the dynamic class of `self`
+(func $cool_new_like (param $self i32) (result i32)
+  (local $object i32)
+  (local $init i32)
+  local.get $self
+  i32.load
+  call $cool_new
+  local.set $object
+  global.get $cool_class_table
+  local.get $self
+  i32.load
+  i32.const 16
+  i32.mul
+  i32.add
+  i32.load offset=8
+  local.tee $init
+  i32.const -1
+  i32.eq
+  if
+    local.get $object
+    return
+  end
+  local.get $object
+  local.get $init
+  call_indirect (type $fn1))
+
+(func $cool_box_int (param $value i32) (result i32)
+  (local $box i32)
+  global.get $cool_int_tag
+  i32.const 16
+  call $cool_alloc
+  local.tee $box
+  local.get $value
+  i32.store offset=12
+  local.get $box)
+
+(func $cool_box_bool (param $value i32) (result i32)
+  (local $box i32)
+  global.get $cool_bool_tag
+  i32.const 16
+  call $cool_alloc
+  local.tee $box
+  local.get $value
+  i32.store offset=12
+  local.get $box)
+
+;; A string of the given length, whose characters are filled in by the caller
+(func $alloc_string (param $length i32) (result i32)
+  (local $string i32)
+  local.get $length
+  i32.const 0x3fffffff
+  i32.gt_u
+  if
+    call $out_of_memory
+  end
+  global.get $cool_string_tag
+  local.get $length
+  i32.const 20
+  i32.add
+  i32.const -4
+  i32.and
+  call $cool_alloc
+  local.tee $string
+  local.get $length
+  i32.store offset=12
+  local.get $string)
+
+;; COOL `=` on objects: the same object, or basic objects with equal values
+(func $cool_equals (param $a i32) (param $b i32) (result i32)
+  (local $tag i32)
+  (local $index i32)
+  local.get $a
+  local.get $b
+  i32.eq
+  if
+    i32.const 1
+    return
+  end
+  local.get $a
+  i32.eqz
+  local.get $b
+  i32.eqz
+  i32.or
+  if
+    i32.const 0
+    return
+  end
+  local.get $a
+  i32.load
+  local.tee $tag
+  local.get $b
+  i32.load
+  i32.ne
+  if
+    i32.const 0
+    return
+  end
+  local.get $tag
+  global.get $cool_int_tag
+  i32.eq
+  local.get $tag
+  global.get $cool_bool_tag
+  i32.eq
+  i32.or
+  if
+    local.get $a
+    i32.load offset=12
+    local.get $b
+    i32.load offset=12
+    i32.eq
+    return
+  end
+  local.get $tag
+  global.get $cool_string_tag
+  i32.ne
+  if
+    i32.const 0
+    return
+  end
+  local.get $a
+  i32.load offset=12
+  local.get $b
+  i32.load offset=12
+  i32.ne
+  if
+    i32.const 0
+    return
+  end
+  block $equal
+    loop $next
+      local.get $index
+      local.get $a
+      i32.load offset=12
+      i32.ge_u
+      br_if $equal
+      local.get $a
+      local.get $index
+      i32.add
+      i32.load8_u offset=16
+      local.get $b
+      local.get $index
+      i32.add
+      i32.load8_u offset=16
+      i32.ne
+      if
+        i32.const 0
+        return
+      end
+      local.get $index
+      i32.const 1
+      i32.add
+      local.set $index
+      br $next
+    end
+  end
+  i32.const 1)
+
+;; Built-in methods
+
+(func $cool_Object_abort (param $self i32) (result i32)
+  (local $name i32)
+  local.get $self
+  call $class_name
+  local.set $name
+  i32.const 9024
+  i32.const 24
+  local.get $name
+  i32.const 16
+  i32.add
+  local.get $name
+  i32.load offset=12
+  call $runtime_error
+  unreachable)
+
+(func $cool_Object_type_name (param $self i32) (result i32)
+  local.get $self
+  call $class_name)
+
+(func $cool_Object_copy (param $self i32) (result i32)
+  (local $copy i32)
+  local.get $self
+  i32.load
+  local.get $self
+  i32.load offset=4
+  call $cool_alloc
+  local.tee $copy
+  i32.const 12
+  i32.add
+  local.get $self
+  i32.const 12
+  i32.add
+  local.get $self
+  i32.load offset=4
+  i32.const 12
+  i32.sub
+  memory.copy
+  local.get $copy)
+
+(func $cool_IO_out_string (param $self i32) (param $string i32) (result i32)
+  local.get $string
+  i32.const 16
+  i32.add
+  local.get $string
+  i32.load offset=12
+  call $out_bytes
+  local.get $self)
+
+;; Digits are written backwards from the end of their buffer, from the magnitude as an unsigned integer so that the
+;; smallest integer has one
+(func $cool_IO_out_int (param $self i32) (param $value i32) (result i32)
+  (local $magnitude i32)
+  (local $position i32)
+  local.get $value
+  local.tee $magnitude
+  i32.const 0
+  i32.lt_s
+  if
+    i32.const 0
+    local.get $magnitude
+    i32.sub
+    local.set $magnitude
+  end
+  i32.const 8720
+  local.set $position
+  loop $digits
+    local.get $position
+    i32.const 1
+    i32.sub
+    local.tee $position
+    local.get $magnitude
+    i32.const 10
+    i32.rem_u
+    i32.const 48
+    i32.add
+    i32.store8
+    local.get $magnitude
+    i32.const 10
+    i32.div_u
+    local.tee $magnitude
+    br_if $digits
+  end
+  local.get $value
+  i32.const 0
+  i32.lt_s
+  if
+    local.get $position
+    i32.const 1
+    i32.sub
+    local.tee $position
+    i32.const 45
+    i32.store8
+  end
+  local.get $position
+  i32.const 8720
+  local.get $position
+  i32.sub
+  call $out_bytes
+  local.get $self)
+
+(func $cool_IO_in_string (param $self i32) (result i32)
+  call $read_line)
+
+;; The integer at the start of the line, after blanks, 0 if there is none or it does not fit in an `Int`
+(func $cool_IO_in_int (param $self i32) (result i32)
+  (local $line i32)
+  (local $chars i32)
+  (local $end i32)
+  (local $negative i32)
+  (local $digit i32)
+  (local $digits i32)
+  (local $value i32)
+  (local $overflow i32)
+  call $read_line
+  local.tee $line
+  i32.const 16
+  i32.add
+  local.tee $chars
+  local.get $line
+  i32.load offset=12
+  i32.add
+  local.set $end
+  block $blanks_done
+    loop $blanks
+      local.get $chars
+      local.get $end
+      i32.ge_u
+      br_if $blanks_done
+      local.get $chars
+      i32.load8_u
+      local.tee $digit
+      i32.const 32
+      i32.eq
+      local.get $digit
+      i32.const 9
+      i32.sub
+      i32.const 4
+      i32.le_u
+      i32.or
+      i32.eqz
+      br_if $blanks_done
+      local.get $chars
+      i32.const 1
+      i32.add
+      local.set $chars
+      br $blanks
+    end
+  end
+  local.get $chars
+  local.get $end
+  i32.lt_u
+  if
+    local.get $chars
+    i32.load8_u
+    i32.const 45
+    i32.eq
+    local.set $negative
+  end
+  local.get $chars
+  local.get $negative
+  i32.add
+  local.set $chars
+  ;; the magnitude is at most 2^31, which only fits negated
+  block $digits_done
+    loop $next
+      local.get $chars
+      local.get $end
+      i32.ge_u
+      br_if $digits_done
+      local.get $chars
+      i32.load8_u
+      i32.const 48
+      i32.sub
+      local.tee $digit
+      i32.const 9
+      i32.gt_u
+      br_if $digits_done
+      local.get $digits
+      i32.const 1
+      i32.add
+      local.set $digits
+      local.get $value
+      i32.const 214748364
+      i32.gt_u
+      local.get $value
+      i32.const 214748364
+      i32.eq
+      local.get $digit
+      i32.const 8
+      i32.gt_u
+      i32.and
+      i32.or
+      if
+        i32.const 1
+        local.set $overflow
+        br $digits_done
+      end
+      local.get $value
+      i32.const 10
+      i32.mul
+      local.get $digit
+      i32.add
+      local.set $value
+      local.get $chars
+      i32.const 1
+      i32.add
+      local.set $chars
+      br $next
+    end
+  end
+  local.get $value
+  i32.const 0x80000000
+  i32.eq
+  local.get $negative
+  i32.eqz
+  i32.and
+  local.get $overflow
+  i32.or
+  local.get $digits
+  i32.eqz
+  i32.or
+  if
+    i32.const 0
+    local.set $value
+  end
+  local.get $negative
+  if
+    i32.const 0
+    local.get $value
+    i32.sub
+    local.set $value
+  end
+  local.get $value)
+
+(func $cool_String_length (param $self i32) (result i32)
+  local.get $self
+  i32.load offset=12)
+
+(func $cool_String_concat (param $self i32) (param $other i32) (result i32)
+  (local $result i32)
+  local.get $self
+  i32.load offset=12
+  local.get $other
+  i32.load offset=12
+  i32.add
+  call $alloc_string
+  local.tee $result
+  i32.const 16
+  i32.add
+  local.get $self
+  i32.const 16
+  i32.add
+  local.get $self
+  i32.load offset=12
+  memory.copy
+  local.get $result
+  i32.const 16
+  i32.add
+  local.get $self
+  i32.load offset=12
+  i32.add
+  local.get $other
+  i32.const 16
+  i32.add
+  local.get $other
+  i32.load offset=12
+  memory.copy
+  local.get $result)
+
+(func $cool_String_substr (param $self i32) (param $start i32) (param $length i32) (result i32)
+  (local $result i32)
+  local.get $start
+  i32.const 0
+  i32.lt_s
+  if
+    i32.const 9088
+    i32.const 27
+    call $located_error
+  end
+  local.get $length
+  i32.const 0
+  i32.lt_s
+  if
+    i32.const 9344
+    i32.const 28
+    call $located_error
+  end
+  local.get $start
+  local.get $self
+  i32.load offset=12
+  i32.gt_s
+  if
+    i32.const 9408
+    i32.const 26
+    call $located_error
+  end
+  local.get $length
+  local.get $self
+  i32.load offset=12
+  local.get $start
+  i32.sub
+  i32.gt_s
+  if
+    i32.const 9472
+    i32.const 25
+    call $located_error
+  end
+  local.get $length
+  call $alloc_string
+  local.tee $result
+  i32.const 16
+  i32.add
+  local.get $self
+  i32.const 16
+  i32.add
+  local.get $start
+  i32.add
+  local.get $length
+  memory.copy
+  local.get $result)
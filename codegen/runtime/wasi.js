@@ -0,0 +1,160 @@
+// The WASI functions imported by COOL programs compiled to WebAssembly, `fd_write`, `fd_read` and `proc_exit`, for
+// browsers and Node.js.
+//
+// In a browser, output is passed to callbacks as text and input is a string, or a function called whenever more
+// input is needed, returning a string or null at the end of the input:
+//
+//   const wasi = new CoolWasi({ stdin: () => prompt("Input"), stdout: text => output.append(text) });
+//   const { instance } = await WebAssembly.instantiateStreaming(fetch("program.wasm"), wasi.imports);
+//   const status = wasi.start(instance);
+//
+// With Node.js, `node wasi.js program.wasm` runs a program on the standard streams and exits with its status.
+"use strict";
+
+const ERRNO_BADF = 8;
+
+// Thrown by `proc_exit` to unwind the program
+class CoolExit {
+  constructor(code) {
+    this.code = code;
+  }
+}
+
+class CoolWasi {
+  // `stdout` and `stderr` receive text, or bytes when `binary` is set. `stdin` is a string, bytes, or a function
+  // returning the next string or bytes of input, null at the end of the input.
+  constructor({ stdin = "", stdout = () => {}, stderr = () => {}, binary = false } = {}) {
+    this.stdin = stdin;
+    this.input = new Uint8Array(0);
+    this.inputEnded = false;
+    this.outputs = { 1: stdout, 2: stderr };
+    this.decoders = binary ? null : { 1: new TextDecoder(), 2: new TextDecoder() };
+    this.memory = null;
+    this.imports = {
+      wasi_snapshot_preview1: {
+        fd_write: (fd, iovs, count, written) => this.fdWrite(fd, iovs, count, written),
+        fd_read: (fd, iovs, count, read) => this.fdRead(fd, iovs, count, read),
+        proc_exit: code => {
+          throw new CoolExit(code);
+        },
+      },
+    };
+  }
+
+  // Runs the program and returns its exit status
+  start(instance) {
+    this.memory = instance.exports.memory;
+    try {
+      instance.exports._start();
+      return 0;
+    } catch (e) {
+      if (e instanceof CoolExit) {
+        return e.code;
+      }
+      throw e;
+    }
+  }
+
+  // Start and length of each buffer of an array of `iovec`
+  buffers(iovs, count) {
+    const view = new DataView(this.memory.buffer);
+    const buffers = [];
+    for (let i = 0; i < count; i++) {
+      buffers.push([view.getUint32(iovs + 8 * i, true), view.getUint32(iovs + 8 * i + 4, true)]);
+    }
+    return buffers;
+  }
+
+  fdWrite(fd, iovs, count, written) {
+    if (fd !== 1 && fd !== 2) {
+      return ERRNO_BADF;
+    }
+    let total = 0;
+    for (const [start, length] of this.buffers(iovs, count)) {
+      const bytes = new Uint8Array(this.memory.buffer, start, length).slice();
+      this.outputs[fd](this.decoders ? this.decoders[fd].decode(bytes, { stream: true }) : bytes);
+      total += length;
+    }
+    new DataView(this.memory.buffer).setUint32(written, total, true);
+    return 0;
+  }
+
+  fdRead(fd, iovs, count, read) {
+    if (fd !== 0) {
+      return ERRNO_BADF;
+    }
+    let total = 0;
+    for (const [start, length] of this.buffers(iovs, count)) {
+      if (this.input.length === 0 && !this.nextInput()) {
+        break;
+      }
+      const bytes = this.input.subarray(0, length);
+      new Uint8Array(this.memory.buffer, start, bytes.length).set(bytes);
+      this.input = this.input.subarray(bytes.length);
+      total += bytes.length;
+      if (bytes.length < length) {
+        break;
+      }
+    }
+    new DataView(this.memory.buffer).setUint32(read, total, true);
+    return 0;
+  }
+
+  // Takes the next part of the input, returns false at its end
+  nextInput() {
+    while (!this.inputEnded && this.input.length === 0) {
+      let input = this.stdin;
+      if (typeof this.stdin === "function") {
+        input = this.stdin();
+      } else {
+        this.inputEnded = true;
+      }
+      if (input === null || input === undefined) {
+        this.inputEnded = true;
+      } else {
+        this.input = typeof input === "string" ? new TextEncoder().encode(input) : new Uint8Array(input);
+      }
+    }
+    return this.input.length > 0;
+  }
+}
+
+if (typeof module !== "undefined") {
+  module.exports = { CoolWasi };
+} else {
+  globalThis.CoolWasi = CoolWasi;
+}
+
+if (typeof require !== "undefined" && typeof module !== "undefined" && require.main === module) {
+  const fs = require("fs");
+  const write = (fd, bytes) => {
+    for (let offset = 0; offset < bytes.length; ) {
+      offset += fs.writeSync(fd, bytes, offset);
+    }
+  };
+  const readInput = () => {
+    const buffer = Buffer.alloc(65536);
+    for (;;) {
+      try {
+        const length = fs.readSync(0, buffer, 0, buffer.length, null);
+        return length === 0 ? null : buffer.subarray(0, length);
+      } catch (e) {
+        if (e.code === "EAGAIN") {
+          continue;
+        }
+        if (e.code === "EOF") {
+          return null;
+        }
+        throw e;
+      }
+    }
+  };
+
+  if (process.argv.length !== 3) {
+    console.error("Usage: node wasi.js <program.wasm>");
+    process.exit(2);
+  }
+  const wasi = new CoolWasi({ stdin: readInput, stdout: bytes => write(1, bytes), stderr: bytes => write(2, bytes), binary: true });
+  const instance = new WebAssembly.Instance(new WebAssembly.Module(fs.readFileSync(process.argv[2])), wasi.imports);
+  process.exitCode = wasi.start(instance);
+}
@@ -0,0 +1,46 @@
+pub mod llvm;
+pub mod mips;
+pub mod target;
+pub mod toolchain;
+pub mod wasm;
+pub mod x86;
+
+#[cfg(test)]
+mod test {
+  use ir::interp::run;
+  use ir::lower::lower_program;
+  use ir::model::module::Module;
+  use parser::programs::{find_programs, TestProgram};
+  use semantic::gen::{analyze_program, CheckOptions};
+  use std::path::Path;
+
+  /// Lowers the test program, with its dependencies
+  pub(crate) fn lower_test_program(test_program: &TestProgram) -> Module {
+    let program = test_program.parse().expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    lower_program(&checked)
+  }
+
+  /// Lowers the program of the file
+  pub(crate) fn lower_file(path: &str) -> Module {
+    lower_test_program(&TestProgram::new(Path::new(path)))
+  }
+
+  /// The programs of `test_resources/programs` and the one exercising the corners of native code generation
+  pub(crate) fn programs() -> Vec<TestProgram> {
+    let mut programs = find_programs("../test_resources/programs");
+    programs.push(TestProgram::new(Path::new("../test_resources/codegen/runtime.cl")));
+    programs
+  }
+
+  /// Output, error output and exit code of the program run by the IR interpreter
+  pub(crate) fn interpret(module: &Module, input: &str) -> (String, String, Option<i32>) {
+    let mut output: Vec<u8> = Vec::new();
+    let (_, result) = run(module, &mut input.as_bytes(), &mut output);
+    let output = String::from_utf8(output).expect("Output must be UTF-8");
+    match result {
+      Ok(()) => (output, String::new(), Some(0)),
+      Err(e) => (output, format!("{e}\n"), Some(1)),
+    }
+  }
+}
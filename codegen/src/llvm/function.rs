@@ -0,0 +1,447 @@
+use crate::llvm::{class_type, ll_type, StringTable};
+use ir::interp::Location;
+use ir::model::function::Function;
+use ir::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use ir::model::module::Module;
+use ir::model::{BlockId, Constant, Pos, Temp, Type};
+use parser::model::class::INT_CLASS_NAME;
+use std::collections::HashMap;
+
+/// Emits the definition of one function with a body.
+///
+/// IR temporaries are already in SSA form and become LLVM values `%tN`, phis become LLVM phis. Copies and constants
+/// have no LLVM instruction, their uses refer to the copied value or the constant. Void checks and division split an
+/// IR block, so phis name the last LLVM block emitted for each predecessor.
+///
+/// The collector may move objects, so each object temporary also has a root in the frame the function links into the
+/// shadow stack, see `runtime/runtime.c`. It is stored there once defined and loaded again at each use, and phis take
+/// the values loaded at the end of their predecessors.
+pub(super) struct FunctionEmitter<'a> {
+  strings: &'a mut StringTable,
+  module: &'a Module,
+  function: &'a Function,
+  definitions: HashMap<Temp, &'a Op>,
+  roots: HashMap<Temp, usize>,                 // index in the frame of each object temporary
+  edge_values: HashMap<(BlockId, Temp), String>, // operand of each phi coming from a predecessor
+  lines: Vec<String>,       // instructions of the current IR block, with the labels of its splits
+  last_labels: Vec<String>, // label of the last LLVM block of each IR block
+  value_count: usize,       // values and labels made besides the temporaries
+}
+
+impl<'a> FunctionEmitter<'a> {
+  pub(super) fn new(strings: &'a mut StringTable, module: &'a Module, function: &'a Function) -> Self {
+    let definitions: HashMap<Temp, &Op> = function.blocks.iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|instruction| instruction.dest.map(|dest| (dest, &instruction.op)))
+        .collect();
+    let roots = (0..function.temps.len() as u32)
+        .map(Temp)
+        .filter(|temp| function.temp_type(*temp).is_object() && !matches!(definitions.get(temp), Some(Op::Copy(_) | Op::Const(_))))
+        .enumerate()
+        .map(|(index, temp)| (temp, index))
+        .collect();
+    FunctionEmitter {
+      strings,
+      module,
+      function,
+      definitions,
+      roots,
+      edge_values: HashMap::new(),
+      lines: Vec::new(),
+      last_labels: Vec::new(),
+      value_count: 0,
+    }
+  }
+
+  pub(super) fn emit(mut self) -> String {
+    let params: Vec<String> = self.function.params.iter()
+        .map(|(_, param)| format!("{} %t{}", ll_type(self.function.temp_type(*param)), param.0))
+        .collect();
+    let mut text = format!("define internal {} @\"{}\"({}) {{\n", ll_type(&self.function.ret_type), self.function.name, params.join(", "));
+
+    let mut bodies: Vec<Vec<String>> = Vec::new();
+    for id in self.function.block_ids() {
+      self.last_labels.push(block_label(id));
+      let block = self.function.block(id);
+      for instruction in block.instructions.iter().filter(|instruction| !instruction.is_phi()) {
+        self.instruction(id, instruction);
+      }
+      self.terminator(id, &block.terminator);
+      bodies.push(std::mem::take(&mut self.lines));
+    }
+
+    for (id, body) in self.function.block_ids().zip(bodies) {
+      text.push_str(&format!("{}:\n", block_label(id)));
+      if id == BlockId::ENTRY {
+        text.push_str(&self.prologue());
+      }
+      let phis: Vec<&Instruction> = self.function.block(id).instructions.iter().take_while(|instruction| instruction.is_phi()).collect();
+      for instruction in &phis {
+        let phi = self.phi(instruction);
+        text.push_str(&format!("  {phi}\n"));
+      }
+      for dest in phis.iter().filter_map(|instruction| instruction.dest) {
+        if let Some(root) = self.roots.get(&dest) {
+          text.push_str(&format!("  store ptr %t{}, ptr %root.{root}\n", dest.0));
+        }
+      }
+      for line in body {
+        text.push_str(&line);
+        text.push('\n');
+      }
+    }
+    text.push_str("}\n");
+    text
+  }
+
+  /// Links a frame holding a root per object temporary, all void, into the shadow stack and stores the parameters
+  fn prologue(&self) -> String {
+    let frame_type = format!("{{ ptr, i64, [{} x ptr] }}", self.roots.len());
+    let mut lines = vec![
+      format!("%frame = alloca {frame_type}"),
+      "%frame.next = load ptr, ptr @cool_shadow_stack".to_string(),
+      "store ptr %frame.next, ptr %frame".to_string(),
+      format!("%frame.count = getelementptr inbounds {frame_type}, ptr %frame, i32 0, i32 1"),
+      format!("store i64 {}, ptr %frame.count", self.roots.len()),
+      format!("%frame.roots = getelementptr inbounds {frame_type}, ptr %frame, i32 0, i32 2"),
+      format!("store [{} x ptr] zeroinitializer, ptr %frame.roots", self.roots.len()),
+      "store ptr %frame, ptr @cool_shadow_stack".to_string(),
+    ];
+    for index in 0..self.roots.len() {
+      lines.push(format!("%root.{index} = getelementptr inbounds [{} x ptr], ptr %frame.roots, i64 0, i64 {index}", self.roots.len()));
+    }
+    for (_, param) in &self.function.params {
+      if let Some(root) = self.roots.get(param) {
+        lines.push(format!("store ptr %t{}, ptr %root.{root}", param.0));
+      }
+    }
+    lines.iter().map(|line| format!("  {line}\n")).collect()
+  }
+
+  /// LLVM operand of a temporary: the value itself, an object loaded from its root, a constant, or the value a copy
+  /// was made of
+  fn value(&mut self, temp: Temp) -> String {
+    match self.definitions.get(&temp) {
+      Some(Op::Copy(source)) => self.value(*source),
+      Some(Op::Const(constant)) => match constant {
+        Constant::Int(value) => value.to_string(),
+        Constant::Bool(value) => value.to_string(),
+        Constant::Str(value) => self.strings.global(value),
+        Constant::Void => "null".to_string(),
+      },
+      _ => match self.roots.get(&temp).copied() {
+        Some(root) => {
+          let value = self.new_value();
+          self.ins(format!("{value} = load ptr, ptr %root.{root}"));
+          value
+        }
+        None => format!("%t{}", temp.0),
+      },
+    }
+  }
+
+  /// Returns `true` if the temporary is a constant or a copy of one, which is never in the heap
+  fn is_constant(&self, temp: Temp) -> bool {
+    match self.definitions.get(&temp) {
+      Some(Op::Copy(source)) => self.is_constant(*source),
+      Some(Op::Const(_)) => true,
+      _ => false,
+    }
+  }
+
+  /// Type and operand of a temporary, e.g. `ptr %t3`
+  fn typed(&mut self, temp: Temp) -> String {
+    format!("{} {}", ll_type(self.function.temp_type(temp)), self.value(temp))
+  }
+
+  fn ins(&mut self, line: String) {
+    self.lines.push(format!("  {line}"));
+  }
+
+  fn new_value(&mut self) -> String {
+    self.value_count += 1;
+    format!("%v{}", self.value_count)
+  }
+
+  /// Starts a new LLVM block inside the IR block, which is its last one until the next split
+  fn start_block(&mut self, id: BlockId, label: &str) {
+    self.lines.push(format!("{label}:"));
+    self.last_labels[id.index()] = label.to_string();
+  }
+
+  fn new_label(&mut self, id: BlockId) -> String {
+    self.value_count += 1;
+    format!("{}.{}", block_label(id), self.value_count)
+  }
+
+  fn phi(&mut self, instruction: &Instruction) -> String {
+    let (Some(dest), Op::Phi(incoming)) = (instruction.dest, &instruction.op) else {
+      panic!("Only phis are emitted as phis");
+    };
+    let incoming: Vec<String> = incoming.iter()
+        .map(|(block, _)| format!("[ {}, %{} ]", self.edge_values[&(*block, dest)], self.last_labels[block.index()]))
+        .collect();
+    format!("%t{} = phi {} {}", dest.0, ll_type(self.function.temp_type(dest)), incoming.join(", "))
+  }
+
+  fn instruction(&mut self, id: BlockId, instruction: &Instruction) {
+    let dest = instruction.dest.map_or_else(|| self.new_value(), |dest| format!("%t{}", dest.0));
+    if instruction.op.enters_runtime() {
+      let location = self.location(instruction.pos);
+      self.ins(format!("store ptr {location}, ptr @cool_location"));
+    }
+    match &instruction.op {
+      Op::Const(_) | Op::Copy(_) => {}
+      Op::Phi(_) => panic!("Phis are emitted at the start of their block"),
+      Op::Unary(UnaryOp::Neg, t) => {
+        let value = self.value(*t);
+        self.ins(format!("{dest} = sub i32 0, {value}"));
+      }
+      Op::Unary(UnaryOp::Not, t) => {
+        let value = self.value(*t);
+        self.ins(format!("{dest} = xor i1 {value}, true"));
+      }
+      Op::Binary(op, a, b) => self.binary(id, &dest, *op, *a, *b, instruction.pos),
+      Op::ObjEq(a, b) => {
+        let (a, b) = (self.value(*a), self.value(*b));
+        let equal = self.new_value();
+        self.ins(format!("{equal} = call i64 @cool_equals(ptr {a}, ptr {b})"));
+        self.ins(format!("{dest} = icmp ne i64 {equal}, 0"));
+      }
+      Op::IsVoid(t) => {
+        let value = self.value(*t);
+        self.ins(format!("{dest} = icmp eq ptr {value}, null"));
+      }
+      Op::InstanceOf(t, class_name) => {
+        // tag - first tag <= last tag - first tag, compared unsigned so smaller tags fail too
+        let class = self.module.class(class_name).expect("Class is verified");
+        let value = self.value(*t);
+        let (tag, offset) = (self.new_value(), self.new_value());
+        self.ins(format!("{tag} = load i64, ptr {value}"));
+        self.ins(format!("{offset} = sub i64 {tag}, {}", class.tag));
+        self.ins(format!("{dest} = icmp ule i64 {offset}, {}", class.max_descendant_tag - class.tag));
+      }
+      Op::Box(t) => {
+        let function = if *self.function.temp_type(*t) == Type::Bool { "cool_box_bool" } else { "cool_box_int" };
+        let word = self.word(*t);
+        self.ins(format!("{dest} = call ptr @{function}(i64 {word})"));
+      }
+      Op::Unbox(t) => {
+        let value = self.value(*t);
+        let field = self.new_value();
+        self.ins(format!("{field} = getelementptr inbounds {}, ptr {value}, i32 0, i32 1", class_type(INT_CLASS_NAME)));
+        self.load_raw(&dest, instruction.dest, &field);
+      }
+      Op::New(class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        match &class.init {
+          Some(init) => {
+            let object = self.new_value();
+            self.ins(format!("{object} = call ptr @cool_new(i64 {})", class.tag));
+            self.ins(format!("{dest} = call ptr @\"{init}\"(ptr {object})"));
+          }
+          None => self.ins(format!("{dest} = call ptr @cool_new(i64 {})", class.tag)),
+        }
+      }
+      Op::NewLike(t) => {
+        let value = self.value(*t);
+        self.ins(format!("{dest} = call ptr @cool_new_like(ptr {value})"));
+      }
+      Op::GetAttr { object, index } => {
+        let field = self.attribute(*object, *index);
+        if instruction.dest.is_some_and(|dest| self.function.temp_type(dest).is_object()) {
+          self.ins(format!("{dest} = load ptr, ptr {field}"));
+        } else {
+          self.load_raw(&dest, instruction.dest, &field);
+        }
+      }
+      Op::SetAttr { object, index, value } => {
+        let field = self.attribute(*object, *index);
+        if self.function.temp_type(*value).is_object() {
+          let constant = self.is_constant(*value);
+          let value = self.value(*value);
+          self.ins(format!("store ptr {value}, ptr {field}"));
+          if !constant {
+            self.write_barrier(id, *object, &value);
+          }
+        } else {
+          let word = self.word(*value);
+          self.ins(format!("store i64 {word}, ptr {field}"));
+        }
+      }
+      Op::CheckVoid(t, kind) => self.check_void(id, *t, *kind, instruction.pos),
+      Op::DebugVar { .. } => {}
+      Op::Call { function, args } => {
+        let callee = self.module.function(function).unwrap_or_else(|| panic!("Function {function} is verified to exist"));
+        let callee = format!("{} {}", ll_type(&callee.ret_type), function_symbol(callee.name.as_str(), callee.is_builtin()));
+        let args: Vec<String> = args.iter().map(|arg| self.typed(*arg)).collect();
+        self.ins(format!("{dest} = call {callee}({})", args.join(", ")));
+      }
+      Op::Dispatch { receiver, slot, args, .. } => {
+        self.check_void(id, *receiver, TrapKind::DispatchOnVoid, instruction.pos);
+        let object = self.value(*receiver);
+        let (table_field, table, entry, target) = (self.new_value(), self.new_value(), self.new_value(), self.new_value());
+        self.ins(format!("{table_field} = getelementptr inbounds %cool.header, ptr {object}, i32 0, i32 2"));
+        self.ins(format!("{table} = load ptr, ptr {table_field}"));
+        self.ins(format!("{entry} = getelementptr inbounds ptr, ptr {table}, i64 {slot}"));
+        self.ins(format!("{target} = load ptr, ptr {entry}"));
+        let ret_type = instruction.dest.map_or("ptr", |dest| ll_type(self.function.temp_type(dest)));
+        let args: Vec<String> = std::iter::once(*receiver).chain(args.iter().copied()).map(|arg| self.typed(arg)).collect();
+        self.ins(format!("{dest} = call {ret_type} {target}({})", args.join(", ")));
+      }
+    }
+    if let Some((dest, root)) = instruction.dest.and_then(|dest| self.roots.get(&dest).map(|root| (dest, *root))) {
+      self.ins(format!("store ptr %t{}, ptr %root.{root}", dest.0));
+    }
+  }
+
+  /// Remembers the object when the value it now points to is in the nursery and the object is not, so the next minor
+  /// collection finds the pointer. Without a generational collector the nursery is empty and the call never runs.
+  fn write_barrier(&mut self, id: BlockId, object: Temp, value: &str) {
+    let object = self.value(object);
+    let (start, size, start_address, address, offset, young) = (self.new_value(), self.new_value(), self.new_value(), self.new_value(), self.new_value(), self.new_value());
+    self.ins(format!("{start} = load ptr, ptr @cool_nursery_start"));
+    self.ins(format!("{size} = load i64, ptr @cool_nursery_size"));
+    self.ins(format!("{start_address} = ptrtoint ptr {start} to i64"));
+    self.ins(format!("{address} = ptrtoint ptr {value} to i64"));
+    self.ins(format!("{offset} = sub i64 {address}, {start_address}"));
+    self.ins(format!("{young} = icmp ult i64 {offset}, {size}"));
+    let (object_address, object_offset, old, remember) = (self.new_value(), self.new_value(), self.new_value(), self.new_value());
+    self.ins(format!("{object_address} = ptrtoint ptr {object} to i64"));
+    self.ins(format!("{object_offset} = sub i64 {object_address}, {start_address}"));
+    self.ins(format!("{old} = icmp uge i64 {object_offset}, {size}"));
+    self.ins(format!("{remember} = and i1 {young}, {old}"));
+    let (call, done) = (self.new_label(id), self.new_label(id));
+    self.ins(format!("br i1 {remember}, label %{call}, label %{done}"));
+    self.lines.push(format!("{call}:"));
+    self.ins(format!("call void @cool_remember(ptr {object})"));
+    self.ins(format!("br label %{done}"));
+    self.start_block(id, &done);
+  }
+
+  /// Raw value extended to the word it is stored in, in boxes and attributes
+  fn word(&mut self, t: Temp) -> String {
+    let extend = if *self.function.temp_type(t) == Type::Bool { "zext i1" } else { "sext i32" };
+    let value = self.value(t);
+    let word = self.new_value();
+    self.ins(format!("{word} = {extend} {value} to i64"));
+    word
+  }
+
+  /// Loads the raw value of the temporary from the word at the address
+  fn load_raw(&mut self, dest: &str, temp: Option<Temp>, address: &str) {
+    let word = self.new_value();
+    self.ins(format!("{word} = load i64, ptr {address}"));
+    match temp.map(|temp| self.function.temp_type(temp)) {
+      Some(Type::Bool) => self.ins(format!("{dest} = icmp ne i64 {word}, 0")),
+      _ => self.ins(format!("{dest} = trunc i64 {word} to i32")),
+    }
+  }
+
+  /// Address of an attribute, in the struct of the static class of the object
+  fn attribute(&mut self, object: Temp, index: usize) -> String {
+    let struct_type = class_type(self.function.temp_type(object).class_name());
+    let value = self.value(object);
+    let field = self.new_value();
+    self.ins(format!("{field} = getelementptr inbounds {struct_type}, ptr {value}, i32 0, i32 {}", index + 1));
+    field
+  }
+
+  fn binary(&mut self, id: BlockId, dest: &str, op: BinaryOp, a: Temp, b: Temp, pos: Pos) {
+    let operand_type = ll_type(self.function.temp_type(a));
+    let (a, b) = (self.value(a), self.value(b));
+    match op {
+      BinaryOp::Add => self.ins(format!("{dest} = add i32 {a}, {b}")),
+      BinaryOp::Sub => self.ins(format!("{dest} = sub i32 {a}, {b}")),
+      BinaryOp::Mul => self.ins(format!("{dest} = mul i32 {a}, {b}")),
+      BinaryOp::Div => {
+        // `sdiv` of the minimum integer by -1 is undefined, it wraps to the negation like the other operations
+        let (is_zero, divide) = (self.new_value(), self.new_label(id));
+        let trap = self.new_label(id);
+        self.ins(format!("{is_zero} = icmp eq i32 {b}, 0"));
+        self.ins(format!("br i1 {is_zero}, label %{trap}, label %{divide}"));
+        self.lines.push(format!("{trap}:"));
+        let location = self.location(pos);
+        self.ins(format!("call void @cool_division_by_zero(ptr {location})"));
+        self.ins("unreachable".to_string());
+        self.start_block(id, &divide);
+
+        let (minus_one, divisor, quotient, negation) = (self.new_value(), self.new_value(), self.new_value(), self.new_value());
+        self.ins(format!("{minus_one} = icmp eq i32 {b}, -1"));
+        self.ins(format!("{divisor} = select i1 {minus_one}, i32 1, i32 {b}"));
+        self.ins(format!("{quotient} = sdiv i32 {a}, {divisor}"));
+        self.ins(format!("{negation} = sub i32 0, {a}"));
+        self.ins(format!("{dest} = select i1 {minus_one}, i32 {negation}, i32 {quotient}"));
+      }
+      BinaryOp::Lt => self.ins(format!("{dest} = icmp slt i32 {a}, {b}")),
+      BinaryOp::Le => self.ins(format!("{dest} = icmp sle i32 {a}, {b}")),
+      BinaryOp::Eq => self.ins(format!("{dest} = icmp eq {operand_type} {a}, {b}")),
+    }
+  }
+
+  /// Branches to a block reporting the error if the value is void, the rest of the IR block follows in a new block
+  fn check_void(&mut self, id: BlockId, t: Temp, kind: TrapKind, pos: Pos) {
+    let value = self.value(t);
+    let (is_void, trap, ok) = (self.new_value(), self.new_label(id), self.new_label(id));
+    self.ins(format!("{is_void} = icmp eq ptr {value}, null"));
+    self.ins(format!("br i1 {is_void}, label %{trap}, label %{ok}"));
+    self.lines.push(format!("{trap}:"));
+    self.trap(kind, t, pos);
+    self.start_block(id, &ok);
+  }
+
+  /// Calls the runtime to report the error, which does not return
+  fn trap(&mut self, kind: TrapKind, value: Temp, pos: Pos) {
+    let kind = match kind {
+      TrapKind::DispatchOnVoid => 0,
+      TrapKind::CaseOnVoid => 1,
+      TrapKind::CaseNoMatch => 2,
+    };
+    let value = self.value(value);
+    let location = self.location(pos);
+    self.ins(format!("call void @cool_trap(i64 {kind}, ptr {value}, ptr {location})"));
+    self.ins("unreachable".to_string());
+  }
+
+  /// Global of the string giving the source location of the position in the runtime errors
+  fn location(&mut self, pos: Pos) -> String {
+    self.strings.global(&Location::of(self.module, self.function, pos).to_string())
+  }
+
+  fn terminator(&mut self, id: BlockId, terminator: &Terminator) {
+    // The operands of the phis of the successors, loaded before leaving the block
+    for target in terminator.successors() {
+      for instruction in self.function.block(target).instructions.iter().take_while(|instruction| instruction.is_phi()) {
+        let (Some(dest), Op::Phi(incoming)) = (instruction.dest, &instruction.op) else {
+          continue;
+        };
+        if let Some((_, value)) = incoming.iter().find(|(block, _)| *block == id) {
+          let value = self.value(*value);
+          self.edge_values.insert((id, dest), value);
+        }
+      }
+    }
+    match terminator {
+      Terminator::Jump(target) => self.ins(format!("br label %{}", block_label(*target))),
+      Terminator::Branch { cond, then_block, else_block } => {
+        let cond = self.value(*cond);
+        self.ins(format!("br i1 {cond}, label %{}, label %{}", block_label(*then_block), block_label(*else_block)));
+      }
+      Terminator::Return(value) => {
+        let value = self.typed(*value);
+        self.ins("store ptr %frame.next, ptr @cool_shadow_stack".to_string());
+        self.ins(format!("ret {value}"));
+      }
+      Terminator::Trap { kind, value, pos } => self.trap(*kind, *value, *pos),
+    }
+  }
+}
+
+fn block_label(id: BlockId) -> String {
+  id.to_string()
+}
+
+/// Global of a function: the runtime implements the built-in methods as `cool_Class_method`
+pub(super) fn function_symbol(name: &str, builtin: bool) -> String {
+  if builtin { format!("@cool_{}", name.replace('.', "_")) } else { format!("@\"{name}\"") }
+}
@@ -0,0 +1,265 @@
+mod function;
+
+use crate::llvm::function::{function_symbol, FunctionEmitter};
+use ir::model::module::{ClassInfo, Module};
+use ir::model::{Constant, Type};
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashMap;
+
+/// Size in bytes of the object header: class tag, object size and dispatch table, see `runtime/runtime.c`
+const HEADER_SIZE: usize = 24;
+
+/// Declarations of the runtime functions called by generated code, besides the built-in methods, of the location of
+/// the operation running that the runtime prints in its errors, and of the globals of the shadow stack and the write
+/// barrier
+const RUNTIME_DECLARATIONS: &str = "\
+declare ptr @cool_new(i64)
+declare ptr @cool_new_like(ptr)
+declare ptr @cool_box_int(i64)
+declare ptr @cool_box_bool(i64)
+declare i64 @cool_equals(ptr, ptr)
+declare void @cool_trap(i64, ptr, ptr) noreturn
+declare void @cool_division_by_zero(ptr) noreturn
+declare void @cool_remember(ptr)
+@cool_location = external global ptr
+@cool_shadow_stack = external global ptr
+@cool_nursery_start = external global ptr
+@cool_nursery_size = external global i64
+";
+
+/// Emits textual LLVM IR, with opaque pointers.
+///
+/// Each class is a struct type `%"class.Name"` made of the object header and one word per attribute, a pointer or
+/// the raw value of an attribute declared `Int` or `Bool`, so attributes are reached with `getelementptr`. Dispatch tables are constant arrays of function pointers, loaded
+/// through the header. The module is linked with the C runtime, like the x86-64 backend, which it shares the object
+/// layout with.
+pub fn emit_module(module: &Module) -> String {
+  let mut strings = StringTable::default();
+  for class in &module.classes {
+    strings.global(&class.name);
+  }
+
+  let mut out = String::new();
+  out.push_str("%cool.header = type { i64, i64, ptr }\n");
+  for class in &module.classes {
+    out.push_str(&format!("{} = type {}\n", class_type(&class.name), struct_body(class)));
+  }
+  out.push('\n');
+  out.push_str(RUNTIME_DECLARATIONS);
+  for function in module.functions.iter().filter(|function| function.is_builtin()) {
+    let params: Vec<&str> = function.params.iter().map(|(_, param)| ll_type(function.temp_type(*param))).collect();
+    out.push_str(&format!("declare {} {}({})\n", ll_type(&function.ret_type), function_symbol(&function.name, true), params.join(", ")));
+  }
+  out.push('\n');
+
+  emit_class_table(&mut out, module, &mut strings);
+  for class in &module.classes {
+    emit_prototype(&mut out, class, &mut strings);
+  }
+  out.push('\n');
+
+  for function in module.functions.iter().filter(|function| !function.is_builtin()) {
+    out.push_str(&FunctionEmitter::new(&mut strings, module, function).emit());
+    out.push('\n');
+  }
+  emit_main(&mut out, module);
+  strings.emit(&mut out, module);
+  out
+}
+
+/// LLVM type of an IR value: raw integers are `i32`, raw booleans `i1` and objects pointers
+pub(crate) fn ll_type(value_type: &Type) -> &'static str {
+  match value_type {
+    Type::Int => "i32",
+    Type::Bool => "i1",
+    Type::Object(_) => "ptr",
+  }
+}
+
+/// Struct type of the objects of a class
+pub(crate) fn class_type(class_name: &str) -> String {
+  format!("%\"class.{class_name}\"")
+}
+
+fn struct_body(class: &ClassInfo) -> String {
+  match class.name.as_str() {
+    INT_CLASS_NAME | BOOL_CLASS_NAME => "{ %cool.header, i64 }".to_string(),
+    STR_CLASS_NAME => "{ %cool.header, i64, [0 x i8] }".to_string(),
+    _ => {
+      let fields: Vec<&str> = std::iter::once("%cool.header").chain(class.attributes.iter().map(|attribute| field_type(&attribute.attr_type))).collect();
+      format!("{{ {} }}", fields.join(", "))
+    }
+  }
+}
+
+/// LLVM type of the field of an attribute: raw values take a whole word, like in the C runtime
+pub(crate) fn field_type(attr_type: &Type) -> &'static str {
+  if attr_type.is_object() { "ptr" } else { "i64" }
+}
+
+fn dispatch_table_global(class: &ClassInfo) -> String {
+  format!("@\"{}._dispatch\"", class.name)
+}
+
+fn prototype_global(class: &ClassInfo) -> String {
+  format!("@\"{}._prototype\"", class.name)
+}
+
+fn header(class: &ClassInfo, size: usize) -> String {
+  format!("%cool.header {{ i64 {}, i64 {size}, ptr {} }}", class.tag, dispatch_table_global(class))
+}
+
+/// Name, prototype, initialiser and raw attributes of each class in tag order, the dispatch tables and the tags of
+/// the basic classes
+fn emit_class_table(out: &mut String, module: &Module, strings: &mut StringTable) {
+  for class in module.classes.iter().filter(|class| class.has_raw_attributes()) {
+    let flags: Vec<String> = class.attributes.iter().map(|attribute| format!("i8 {}", u8::from(!attribute.attr_type.is_object()))).collect();
+    out.push_str(&format!("{} = constant [{} x i8] [{}]\n", raw_attributes_global(class), flags.len(), flags.join(", ")));
+  }
+  let entries: Vec<String> = module.classes.iter()
+      .map(|class| {
+        let init = class.init.as_ref().map_or("null".to_string(), |init| function_symbol(init, false));
+        let raw_attributes = if class.has_raw_attributes() { raw_attributes_global(class) } else { "null".to_string() };
+        format!("{{ ptr, ptr, ptr, ptr }} {{ ptr {}, ptr {}, ptr {init}, ptr {raw_attributes} }}", strings.global(&class.name), prototype_global(class))
+      })
+      .collect();
+  out.push_str(&format!("@cool_class_table = constant [{} x {{ ptr, ptr, ptr, ptr }}] [\n  {}\n]\n", entries.len(), entries.join(",\n  ")));
+
+  for (global, class_name) in [("cool_int_tag", INT_CLASS_NAME), ("cool_bool_tag", BOOL_CLASS_NAME), ("cool_string_tag", STR_CLASS_NAME)] {
+    let tag = module.class(class_name).map_or(usize::MAX, |class| class.tag);
+    out.push_str(&format!("@{global} = constant i64 {tag}\n"));
+  }
+
+  for class in &module.classes {
+    let slots: Vec<String> = class.dispatch_table.iter()
+        .map(|slot| {
+          let builtin = module.function(&slot.function).is_some_and(|function| function.is_builtin());
+          format!("ptr {}", function_symbol(&slot.function, builtin))
+        })
+        .collect();
+    out.push_str(&format!("{} = constant [{} x ptr] [{}]\n", dispatch_table_global(class), slots.len(), slots.join(", ")));
+  }
+}
+
+fn raw_attributes_global(class: &ClassInfo) -> String {
+  format!("@\"{}._raw_attributes\"", class.name)
+}
+
+/// Object copied by `new`, with the default value of each attribute: 0 for raw values, void or the empty string
+fn emit_prototype(out: &mut String, class: &ClassInfo, strings: &mut StringTable) {
+  let (global_type, body) = match class.name.as_str() {
+    INT_CLASS_NAME | BOOL_CLASS_NAME => (class_type(&class.name), format!("{{ {}, i64 0 }}", header(class, HEADER_SIZE + 8))),
+    // Strings are read up to their size, so the empty string has a word of characters for its terminating zero byte
+    STR_CLASS_NAME => ("{ %cool.header, i64, [8 x i8] }".to_string(), format!("{{ {}, i64 0, [8 x i8] zeroinitializer }}", header(class, HEADER_SIZE + 16))),
+    _ => {
+      let fields: Vec<String> = class.attributes.iter()
+          .map(|attribute| match Constant::default_of(attribute.attr_type.class_name()) {
+            Constant::Str(value) => format!("ptr {}", strings.global(&value)),
+            _ if !attribute.attr_type.is_object() => "i64 0".to_string(),
+            _ => "ptr null".to_string(),
+          })
+          .collect();
+      let header = header(class, HEADER_SIZE + 8 * fields.len());
+      (class_type(&class.name), format!("{{ {} }}", std::iter::once(header).chain(fields).collect::<Vec<String>>().join(", ")))
+    }
+  };
+  out.push_str(&format!("{} = global {global_type} {body}\n", prototype_global(class)));
+}
+
+/// `cool_main`, called by the runtime: runs the entry function on a new object of the entry class
+fn emit_main(out: &mut String, module: &Module) {
+  let entry = module.class(&module.entry_class).expect("Entry class is verified");
+  out.push_str("define void @cool_main() {\n");
+  out.push_str(&format!("  %object = call ptr @cool_new(i64 {})\n", entry.tag));
+  let object = match &entry.init {
+    Some(init) => {
+      out.push_str(&format!("  %main = call ptr {}(ptr %object)\n", function_symbol(init, false)));
+      "%main"
+    }
+    None => "%object",
+  };
+  let entry_function = module.function(&module.entry_function).expect("Entry function is verified");
+  out.push_str(&format!("  call {} {}(ptr {object})\n", ll_type(&entry_function.ret_type), function_symbol(&entry_function.name, false)));
+  out.push_str("  ret void\n}\n");
+}
+
+/// String constants of the program, emitted once each as `String` objects
+#[derive(Default)]
+pub(crate) struct StringTable {
+  globals: HashMap<String, usize>,
+  strings: Vec<String>,
+}
+
+impl StringTable {
+  pub(crate) fn global(&mut self, value: &str) -> String {
+    let index = *self.globals.entry(value.to_string()).or_insert_with(|| {
+      self.strings.push(value.to_string());
+      self.strings.len() - 1
+    });
+    format!("@.str.{index}")
+  }
+
+  fn emit(&self, out: &mut String, module: &Module) {
+    let string_class = module.class(STR_CLASS_NAME).expect("String is a basic class");
+    for (index, value) in self.strings.iter().enumerate() {
+      // Characters and terminating zero byte, padded to whole words
+      let chars = (value.len() + 1).next_multiple_of(8);
+      let header = header(string_class, HEADER_SIZE + 8 + chars);
+      out.push_str(&format!(
+        "@.str.{index} = private global {{ %cool.header, i64, [{chars} x i8] }} {{ {header}, i64 {}, [{chars} x i8] c\"{}\" }}, align 8\n",
+        value.len(),
+        escape(value, chars)
+      ));
+    }
+  }
+}
+
+/// Escapes the bytes of a string for an LLVM `c"..."` constant, padded with zero bytes to the length
+fn escape(value: &str, length: usize) -> String {
+  value.bytes()
+      .chain(std::iter::repeat_n(0, length - value.len()))
+      .map(|byte| match byte {
+        b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+        _ => format!("\\{byte:02X}"),
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::llvm::emit_module;
+  use crate::test::lower_test_program;
+  use ir::opt::{OptLevel, PassManager};
+  use parser::programs::find_programs;
+  use std::io::Write;
+  use std::process::{Command, Stdio};
+
+  #[test]
+  fn test_emitted_ir_is_valid() {
+    // llvm-as parses and verifies the module, opaque pointers need a flag before LLVM 15
+    let Ok(version) = Command::new("llvm-as").arg("--version").output() else {
+      eprintln!("Skipping LLVM IR validation, llvm-as not found");
+      return;
+    };
+    let version = String::from_utf8_lossy(&version.stdout).to_string();
+    let opaque_pointers_flag = ["11", "12", "13", "14"].iter().any(|old| version.contains(&format!("LLVM version {old}.")));
+
+    for program in find_programs("../test_resources/programs") {
+      let name = program.name();
+      let mut module = lower_test_program(&program);
+      PassManager::for_level(OptLevel::O2).run(&mut module).unwrap_or_else(|e| panic!("{name}: {e}"));
+      let mut llvm_as = Command::new("llvm-as");
+      if opaque_pointers_flag {
+        llvm_as.arg("-opaque-pointers");
+      }
+      let mut child = llvm_as.args(["-o", "/dev/null", "-"])
+          .stdin(Stdio::piped())
+          .stderr(Stdio::piped())
+          .spawn()
+          .expect("llvm-as must start");
+      child.stdin.take().expect("Input is piped").write_all(emit_module(&module).as_bytes()).expect("Couldn't write IR");
+      let output = child.wait_with_output().expect("llvm-as must finish");
+      assert!(output.status.success(), "{name}: {}", String::from_utf8_lossy(&output.stderr));
+    }
+  }
+}
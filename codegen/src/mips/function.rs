@@ -0,0 +1,360 @@
+use crate::mips::{Assembly, StringTable, Symbols, HEADER_SIZE};
+use ir::interp::Location;
+use ir::model::function::Function;
+use ir::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use ir::model::module::Module;
+use ir::model::{BlockId, Constant, Pos, Temp, Type};
+
+/// Registers of the first arguments, the others are passed on the stack
+const ARG_REGISTERS: [&str; 4] = ["$a0", "$a1", "$a2", "$a3"];
+
+/// Bytes below the frame pointer holding the saved return address and frame pointer, and the number of object slots
+const SAVED_SIZE: usize = 12;
+
+/// Emits the code of one function with a body.
+///
+/// The frame pointer points at the stack arguments passed by the caller. Below it are the saved return address and
+/// frame pointer, the number of slots holding objects, the slots of the temporaries, objects first, then the stack
+/// arguments of the calls the function makes. The collector finds the objects of every frame through the chain of
+/// saved frame pointers, so the object slots start void.
+pub(super) struct FunctionEmitter<'a> {
+  out: &'a mut Assembly,
+  strings: &'a mut StringTable,
+  symbols: &'a Symbols,
+  module: &'a Module,
+  function: &'a Function,
+  index: usize,       // index of the function in the module, to make its local labels unique
+  label_count: usize, // local labels made besides the blocks
+  slots: Vec<usize>,  // slot of each temporary
+  object_slots: usize,
+}
+
+impl<'a> FunctionEmitter<'a> {
+  pub(super) fn new(out: &'a mut Assembly, strings: &'a mut StringTable, symbols: &'a Symbols, module: &'a Module, function: &'a Function, index: usize) -> Self {
+    let (objects, values): (Vec<Temp>, Vec<Temp>) = (0..function.temps.len() as u32).map(Temp).partition(|temp| function.temp_type(*temp).is_object());
+    let mut slots = vec![0; function.temps.len()];
+    for (slot, temp) in objects.iter().chain(&values).enumerate() {
+      slots[temp.index()] = slot;
+    }
+    FunctionEmitter { out, strings, symbols, module, function, index, label_count: 0, slots, object_slots: objects.len() }
+  }
+
+  pub(super) fn emit(&mut self) {
+    let stack_args = self.function.block_ids()
+        .flat_map(|id| self.function.block(id).instructions.iter())
+        .map(|instruction| match &instruction.op {
+          Op::Call { args, .. } => args.len(),
+          Op::Dispatch { args, .. } => args.len() + 1,
+          _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+        .saturating_sub(ARG_REGISTERS.len());
+    let frame_size = (SAVED_SIZE + 4 * self.function.temps.len() + 4 * stack_args).next_multiple_of(8);
+
+    self.out.label(self.symbols.function(&self.function.name));
+    self.out.ins(&format!("addiu $sp, $sp, -{frame_size}"));
+    self.out.ins(&format!("sw $ra, {}($sp)", frame_size - 4));
+    self.out.ins(&format!("sw $fp, {}($sp)", frame_size - 8));
+    self.out.ins(&format!("addiu $fp, $sp, {frame_size}"));
+    self.out.ins(&format!("li $t0, {}", self.object_slots));
+    self.out.ins("sw $t0, -12($fp)");
+    for slot in 0..self.object_slots {
+      self.out.ins(&format!("sw $zero, -{}($fp)", SAVED_SIZE + 4 * (slot + 1)));
+    }
+    for (index, (_, param)) in self.function.params.iter().enumerate() {
+      match ARG_REGISTERS.get(index) {
+        Some(register) => self.store(register, *param),
+        None => {
+          self.out.ins(&format!("lw $t0, {}($fp)", 4 * (index - ARG_REGISTERS.len())));
+          self.store("$t0", *param);
+        }
+      }
+    }
+
+    for id in self.function.block_ids() {
+      self.out.label(&self.block_label(id));
+      let block = self.function.block(id);
+      for instruction in block.instructions.iter().filter(|instruction| !instruction.is_phi()) {
+        self.instruction(instruction);
+      }
+      self.terminator(id, &block.terminator);
+    }
+  }
+
+  fn slot(&self, temp: Temp) -> String {
+    format!("-{}($fp)", SAVED_SIZE + 4 * (self.slots[temp.index()] + 1))
+  }
+
+  fn load(&mut self, temp: Temp, register: &str) {
+    self.out.ins(&format!("lw {register}, {}", self.slot(temp)));
+  }
+
+  fn store(&mut self, register: &str, temp: Temp) {
+    self.out.ins(&format!("sw {register}, {}", self.slot(temp)));
+  }
+
+  /// Stores the register in the destination, if the value is used
+  fn store_result(&mut self, register: &str, instruction: &Instruction) {
+    if let Some(dest) = instruction.dest {
+      self.store(register, dest);
+    }
+  }
+
+  fn block_label(&self, id: BlockId) -> String {
+    format!("_f{}_{id}", self.index)
+  }
+
+  fn new_label(&mut self) -> String {
+    self.label_count += 1;
+    format!("_f{}_{}", self.index, self.label_count)
+  }
+
+  fn instruction(&mut self, instruction: &Instruction) {
+    if instruction.op.enters_runtime() {
+      let location = self.location(instruction.pos);
+      self.out.ins(&format!("la $t0, {location}"));
+      self.out.ins("sw $t0, cool_location");
+    }
+    match &instruction.op {
+      Op::Const(constant) => {
+        match constant {
+          Constant::Int(value) => self.out.ins(&format!("li $t0, {value}")),
+          Constant::Bool(value) => self.out.ins(&format!("li $t0, {}", *value as i32)),
+          Constant::Str(value) => {
+            let label = self.strings.label(value);
+            self.out.ins(&format!("la $t0, {label}"));
+          }
+          Constant::Void => self.out.ins("move $t0, $zero"),
+        }
+        self.store_result("$t0", instruction);
+      }
+      Op::Copy(t) => {
+        self.load(*t, "$t0");
+        self.store_result("$t0", instruction);
+      }
+      Op::Phi(_) => panic!("Phis are assigned on the edges to their block"),
+      Op::Unary(UnaryOp::Neg, t) => {
+        self.load(*t, "$t0");
+        self.out.ins("subu $t0, $zero, $t0");
+        self.store_result("$t0", instruction);
+      }
+      Op::Unary(UnaryOp::Not, t) => {
+        self.load(*t, "$t0");
+        self.out.ins("xori $t0, $t0, 1");
+        self.store_result("$t0", instruction);
+      }
+      Op::Binary(op, a, b) => {
+        self.binary(*op, *a, *b, instruction.pos);
+        self.store_result("$t0", instruction);
+      }
+      Op::ObjEq(a, b) => {
+        self.load(*a, "$a0");
+        self.load(*b, "$a1");
+        self.out.ins("jal cool_equals");
+        self.store_result("$v0", instruction);
+      }
+      Op::IsVoid(t) => {
+        self.load(*t, "$t0");
+        self.out.ins("sltiu $t0, $t0, 1");
+        self.store_result("$t0", instruction);
+      }
+      Op::InstanceOf(t, class_name) => {
+        // tag - first tag < last tag - first tag + 1, compared unsigned so smaller tags fail too
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.load(*t, "$t0");
+        self.out.ins("lw $t0, 0($t0)");
+        self.out.ins(&format!("addiu $t0, $t0, -{}", class.tag));
+        self.out.ins(&format!("sltiu $t0, $t0, {}", class.max_descendant_tag - class.tag + 1));
+        self.store_result("$t0", instruction);
+      }
+      Op::Box(t) => {
+        let function = if *self.function.temp_type(*t) == Type::Bool { "cool_box_bool" } else { "cool_box_int" };
+        self.load(*t, "$a0");
+        self.out.ins(&format!("jal {function}"));
+        self.store_result("$v0", instruction);
+      }
+      Op::Unbox(t) => {
+        self.load(*t, "$t0");
+        self.out.ins(&format!("lw $t0, {HEADER_SIZE}($t0)"));
+        self.store_result("$t0", instruction);
+      }
+      Op::New(class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.out.ins(&format!("li $a0, {}", class.tag));
+        self.out.ins("jal cool_new");
+        if let Some(init) = &class.init {
+          self.out.ins("move $a0, $v0");
+          self.out.ins(&format!("jal {}", self.symbols.function(init)));
+        }
+        self.store_result("$v0", instruction);
+      }
+      Op::NewLike(t) => {
+        self.load(*t, "$a0");
+        self.out.ins("jal cool_new_like");
+        self.store_result("$v0", instruction);
+      }
+      Op::GetAttr { object, index } => {
+        self.load(*object, "$t0");
+        self.out.ins(&format!("lw $t0, {}($t0)", HEADER_SIZE + 4 * index));
+        self.store_result("$t0", instruction);
+      }
+      Op::SetAttr { object, index, value } => {
+        self.load(*object, "$t0");
+        self.load(*value, "$t1");
+        self.out.ins(&format!("sw $t1, {}($t0)", HEADER_SIZE + 4 * index));
+      }
+      Op::CheckVoid(t, kind) => {
+        let ok = self.new_label();
+        self.load(*t, "$t0");
+        self.out.ins(&format!("bnez $t0, {ok}"));
+        self.trap(*kind, *t, instruction.pos);
+        self.out.label(&ok);
+      }
+      Op::DebugVar { .. } => {}
+      Op::Call { function, args } => {
+        let symbol = self.symbols.function(function).to_string();
+        self.pass_args(args);
+        self.out.ins(&format!("jal {symbol}"));
+        self.store_result("$v0", instruction);
+      }
+      Op::Dispatch { receiver, slot, args, .. } => {
+        let ok = self.new_label();
+        self.load(*receiver, "$t0");
+        self.out.ins(&format!("bnez $t0, {ok}"));
+        self.trap(TrapKind::DispatchOnVoid, *receiver, instruction.pos);
+        self.out.label(&ok);
+
+        let args: Vec<Temp> = std::iter::once(*receiver).chain(args.iter().copied()).collect();
+        self.pass_args(&args);
+        self.out.ins("lw $t0, 8($a0)");
+        self.out.ins(&format!("lw $t0, {}($t0)", 4 * slot));
+        self.out.ins("jalr $t0");
+        self.store_result("$v0", instruction);
+      }
+    }
+  }
+
+  /// Computes the operation on raw values in `$t0`, wrapping on overflow
+  fn binary(&mut self, op: BinaryOp, a: Temp, b: Temp, pos: Pos) {
+    self.load(a, "$t0");
+    self.load(b, "$t1");
+    match op {
+      BinaryOp::Add => self.out.ins("addu $t0, $t0, $t1"),
+      BinaryOp::Sub => self.out.ins("subu $t0, $t0, $t1"),
+      BinaryOp::Mul => self.out.ins("mul $t0, $t0, $t1"),
+      BinaryOp::Div => {
+        // The quotient of the minimum integer by -1 is unpredictable, it wraps to itself like its negation
+        let (nonzero, divide, done) = (self.new_label(), self.new_label(), self.new_label());
+        self.out.ins(&format!("bnez $t1, {nonzero}"));
+        let location = self.location(pos);
+        self.out.ins(&format!("la $a0, {location}"));
+        self.out.ins("jal cool_division_by_zero");
+        self.out.label(&nonzero);
+        self.out.ins("li $t2, -1");
+        self.out.ins(&format!("bne $t1, $t2, {divide}"));
+        self.out.ins("subu $t0, $zero, $t0");
+        self.out.ins(&format!("b {done}"));
+        self.out.label(&divide);
+        self.out.ins("div $t0, $t1");
+        self.out.ins("mflo $t0");
+        self.out.label(&done);
+      }
+      BinaryOp::Lt => self.out.ins("slt $t0, $t0, $t1"),
+      BinaryOp::Le => {
+        self.out.ins("slt $t0, $t1, $t0");
+        self.out.ins("xori $t0, $t0, 1");
+      }
+      BinaryOp::Eq => {
+        self.out.ins("xor $t0, $t0, $t1");
+        self.out.ins("sltiu $t0, $t0, 1");
+      }
+    }
+  }
+
+  /// Stores the arguments past the fourth at the bottom of the frame, then loads the first ones into registers
+  fn pass_args(&mut self, args: &[Temp]) {
+    for (index, arg) in args.iter().enumerate().skip(ARG_REGISTERS.len()) {
+      self.load(*arg, "$t0");
+      self.out.ins(&format!("sw $t0, {}($sp)", 4 * (index - ARG_REGISTERS.len())));
+    }
+    for (arg, register) in args.iter().zip(ARG_REGISTERS) {
+      self.load(*arg, register);
+    }
+  }
+
+  /// Calls the runtime to report the error, which does not return
+  fn trap(&mut self, kind: TrapKind, value: Temp, pos: Pos) {
+    let kind = match kind {
+      TrapKind::DispatchOnVoid => 0,
+      TrapKind::CaseOnVoid => 1,
+      TrapKind::CaseNoMatch => 2,
+    };
+    self.out.ins(&format!("li $a0, {kind}"));
+    self.load(value, "$a1");
+    let location = self.location(pos);
+    self.out.ins(&format!("la $a2, {location}"));
+    self.out.ins("jal cool_trap");
+  }
+
+  /// Label of the string giving the source location of the position in the runtime errors
+  fn location(&mut self, pos: Pos) -> String {
+    self.strings.label(&Location::of(self.module, self.function, pos).to_string())
+  }
+
+  fn terminator(&mut self, id: BlockId, terminator: &Terminator) {
+    match terminator {
+      Terminator::Jump(target) => self.jump(id, *target, true),
+      Terminator::Branch { cond, then_block, else_block } => {
+        let else_edge = self.new_label();
+        self.load(*cond, "$t0");
+        self.out.ins(&format!("beqz $t0, {else_edge}"));
+        self.jump(id, *then_block, false);
+        self.out.label(&else_edge);
+        self.jump(id, *else_block, true);
+      }
+      Terminator::Return(value) => {
+        self.load(*value, "$v0");
+        self.out.ins("lw $ra, -4($fp)");
+        self.out.ins("move $sp, $fp");
+        self.out.ins("lw $fp, -8($fp)");
+        self.out.ins("jr $ra");
+      }
+      Terminator::Trap { kind, value, pos } => self.trap(*kind, *value, *pos),
+    }
+  }
+
+  /// Assigns the phis of the target the values coming from the block, then jumps to it unless it may fall through
+  /// to the next block. The values are all read before any phi is written, as a phi may be the operand of another.
+  fn jump(&mut self, from: BlockId, target: BlockId, falls_through: bool) {
+    let moves: Vec<(Temp, Temp)> = self.function.block(target).instructions.iter()
+        .map_while(|instruction| match (&instruction.op, instruction.dest) {
+          (Op::Phi(incoming), Some(dest)) => incoming.iter().find(|(block, _)| *block == from).map(|(_, value)| (dest, *value)),
+          _ => None,
+        })
+        .filter(|(dest, value)| dest != value)
+        .collect();
+
+    match moves.as_slice() {
+      [] => {}
+      [(dest, value)] => {
+        self.load(*value, "$t0");
+        self.store("$t0", *dest);
+      }
+      _ => {
+        // Below the stack pointer, which nothing else uses meanwhile
+        for (index, (_, value)) in moves.iter().enumerate() {
+          self.load(*value, "$t0");
+          self.out.ins(&format!("sw $t0, -{}($sp)", 4 * (index + 1)));
+        }
+        for (index, (dest, _)) in moves.iter().enumerate() {
+          self.out.ins(&format!("lw $t0, -{}($sp)", 4 * (index + 1)));
+          self.store("$t0", *dest);
+        }
+      }
+    }
+    if !(falls_through && target.index() == from.index() + 1) {
+      self.out.ins(&format!("b {}", self.block_label(target)));
+    }
+  }
+}
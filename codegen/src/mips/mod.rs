@@ -0,0 +1,300 @@
+mod function;
+
+use crate::mips::function::FunctionEmitter;
+use crate::toolchain::GcMode;
+use ir::model::module::{ClassInfo, Module};
+use ir::model::Constant;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashMap;
+
+/// Runtime appended to every program, see `runtime/runtime.mips.s`
+pub const RUNTIME_SOURCE: &str = include_str!("../../runtime/runtime.mips.s");
+
+/// Size in bytes of the object header: class tag, object size and dispatch table, see `runtime/runtime.mips.s`
+const HEADER_SIZE: usize = 12;
+
+/// Emits MIPS32 assembly in the syntax of SPIM, with the runtime appended, to run on SPIM or the simulator of the
+/// `mips` crate.
+///
+/// Like the x86-64 backend, every temporary lives in its own stack slot and instructions go through `$t0`-`$t2`
+/// and the argument registers. Functions take their first four arguments in `$a0`-`$a3` and the others on the
+/// stack, and return in `$v0`. Integers are 32 bits wide, so raw values need no extension. The runtime collects
+/// garbage with its mark-sweep collector.
+pub fn emit_module(module: &Module) -> String {
+  emit_module_with_gc(module, GcMode::MarkSweep, false)
+}
+
+/// Emits the program like `emit_module`, with the collector of the runtime, `none` or `mark-sweep`, collecting at
+/// every allocation in stress mode
+pub fn emit_module_with_gc(module: &Module, mode: GcMode, stress: bool) -> String {
+  assert!(mode != GcMode::Generational, "The MIPS runtime has no generational collector");
+  let mut strings = StringTable::default();
+  for class in &module.classes {
+    strings.label(&class.name);
+  }
+  let symbols = Symbols::new(module);
+
+  let mut out = Assembly::default();
+  out.line(".text");
+  for (index, function) in module.functions.iter().enumerate().filter(|(_, function)| !function.is_builtin()) {
+    FunctionEmitter::new(&mut out, &mut strings, &symbols, module, function, index).emit();
+  }
+  emit_main(&mut out, module, &symbols);
+
+  out.line(".data");
+  out.line(".align 2");
+  out.label("cool_gc");
+  out.ins(&format!(".word {}", (mode == GcMode::MarkSweep) as i32));
+  out.label("cool_gc_stress");
+  out.ins(&format!(".word {}", stress as i32));
+  emit_class_table(&mut out, module, &mut strings, &symbols);
+  for class in &module.classes {
+    emit_prototype(&mut out, class, &mut strings);
+  }
+  strings.emit(&mut out, module);
+  out.line("");
+  out.text + RUNTIME_SOURCE
+}
+
+/// Text of the assembly file
+#[derive(Default)]
+pub(crate) struct Assembly {
+  text: String,
+}
+
+impl Assembly {
+  /// Adds a directive, or an instruction when indented by the caller
+  pub(crate) fn line(&mut self, line: &str) {
+    self.text.push_str(line);
+    self.text.push('\n');
+  }
+
+  /// Adds an indented instruction
+  pub(crate) fn ins(&mut self, instruction: &str) {
+    self.text.push_str("  ");
+    self.line(instruction);
+  }
+
+  pub(crate) fn label(&mut self, label: &str) {
+    self.text.push_str(label);
+    self.line(":");
+  }
+}
+
+/// Assembly symbols of the functions. Functions with a body keep their IR name, e.g. `Main.main`, which cannot
+/// clash with a label of the runtime. The runtime implements the built-in methods as `cool_Class_method`.
+pub(crate) struct Symbols {
+  functions: HashMap<String, String>,
+}
+
+impl Symbols {
+  fn new(module: &Module) -> Self {
+    let functions = module.functions.iter()
+        .map(|function| {
+          let symbol = if function.is_builtin() { format!("cool_{}", function.name.replace('.', "_")) } else { function.name.clone() };
+          (function.name.clone(), symbol)
+        })
+        .collect();
+    Symbols { functions }
+  }
+
+  pub(crate) fn function(&self, name: &str) -> &str {
+    self.functions.get(name).unwrap_or_else(|| panic!("Function {name} is verified to exist"))
+  }
+}
+
+fn dispatch_table_symbol(class: &ClassInfo) -> String {
+  format!("{}._dispatch", class.name)
+}
+
+fn prototype_symbol(class: &ClassInfo) -> String {
+  format!("{}._prototype", class.name)
+}
+
+fn raw_attributes_symbol(class: &ClassInfo) -> String {
+  format!("{}._raw", class.name)
+}
+
+/// Tag of a basic class, which the runtime reads to box values and compare objects
+fn class_tag(module: &Module, class_name: &str) -> usize {
+  module.class(class_name).map_or(usize::MAX, |class| class.tag)
+}
+
+/// `cool_main`, called by the runtime: runs the entry function on a new object of the entry class
+fn emit_main(out: &mut Assembly, module: &Module, symbols: &Symbols) {
+  let entry = module.class(&module.entry_class).expect("Entry class is verified");
+  out.line(".globl cool_main");
+  out.label("cool_main");
+  out.ins("addiu $sp, $sp, -8");
+  out.ins("sw $ra, 4($sp)");
+  out.ins(&format!("li $a0, {}", entry.tag));
+  out.ins("jal cool_new");
+  if let Some(init) = &entry.init {
+    out.ins("move $a0, $v0");
+    out.ins(&format!("jal {}", symbols.function(init)));
+  }
+  out.ins("move $a0, $v0");
+  out.ins(&format!("jal {}", symbols.function(&module.entry_function)));
+  out.ins("lw $ra, 4($sp)");
+  out.ins("addiu $sp, $sp, 8");
+  out.ins("jr $ra");
+}
+
+/// Name, prototype, initialiser and raw attributes of each class in tag order, the dispatch tables and the tags of
+/// the basic classes. The raw attributes are a byte per attribute, non-zero if it holds a raw value, or 0 if every
+/// attribute holds an object.
+fn emit_class_table(out: &mut Assembly, module: &Module, strings: &mut StringTable, symbols: &Symbols) {
+  out.label("cool_class_table");
+  for class in &module.classes {
+    let init = class.init.as_deref().map_or("0", |init| symbols.function(init));
+    let raw_attributes = if class.has_raw_attributes() { raw_attributes_symbol(class) } else { "0".to_string() };
+    out.ins(&format!(".word {}, {}, {init}, {raw_attributes}", strings.label(&class.name), prototype_symbol(class)));
+  }
+
+  for (symbol, class_name) in [("cool_int_tag", INT_CLASS_NAME), ("cool_bool_tag", BOOL_CLASS_NAME), ("cool_string_tag", STR_CLASS_NAME)] {
+    out.label(symbol);
+    out.ins(&format!(".word {}", class_tag(module, class_name)));
+  }
+
+  for class in &module.classes {
+    out.label(&dispatch_table_symbol(class));
+    for slot in &class.dispatch_table {
+      out.ins(&format!(".word {}", symbols.function(&slot.function)));
+    }
+  }
+
+  for class in module.classes.iter().filter(|class| class.has_raw_attributes()) {
+    let flags: Vec<&str> = class.attributes.iter().map(|attribute| if attribute.attr_type.is_object() { "0" } else { "1" }).collect();
+    out.label(&raw_attributes_symbol(class));
+    out.ins(&format!(".byte {}", flags.join(", ")));
+    out.line(".align 2");
+  }
+}
+
+/// Object copied by `new`, with the default value of each attribute: 0 for raw values and void, or the empty string
+fn emit_prototype(out: &mut Assembly, class: &ClassInfo, strings: &mut StringTable) {
+  let fields: Vec<String> = match class.name.as_str() {
+    INT_CLASS_NAME | BOOL_CLASS_NAME => vec!["0".to_string()],
+    // Length and terminating zero byte of the empty string
+    STR_CLASS_NAME => vec!["0".to_string(), "0".to_string()],
+    _ => class.attributes.iter()
+        .map(|attribute| match Constant::default_of(attribute.attr_type.class_name()) {
+          Constant::Str(value) => strings.label(&value),
+          _ => "0".to_string(),
+        })
+        .collect(),
+  };
+  out.line(".align 2");
+  out.label(&prototype_symbol(class));
+  out.ins(&format!(".word {}, {}, {}", class.tag, HEADER_SIZE + 4 * fields.len(), dispatch_table_symbol(class)));
+  for field in fields {
+    out.ins(&format!(".word {field}"));
+  }
+}
+
+/// String constants of the program, emitted once each as `String` objects
+#[derive(Default)]
+pub(crate) struct StringTable {
+  labels: HashMap<String, usize>,
+  strings: Vec<String>,
+}
+
+impl StringTable {
+  pub(crate) fn label(&mut self, value: &str) -> String {
+    let index = *self.labels.entry(value.to_string()).or_insert_with(|| {
+      self.strings.push(value.to_string());
+      self.strings.len() - 1
+    });
+    format!("_str{index}")
+  }
+
+  fn emit(&self, out: &mut Assembly, module: &Module) {
+    let string_class = module.class(STR_CLASS_NAME).expect("String is a basic class");
+    for (index, value) in self.strings.iter().enumerate() {
+      // Header, length, characters and terminating zero byte, rounded up to whole words
+      let size = (HEADER_SIZE + 4 + value.len() + 1).next_multiple_of(4);
+      out.line(".align 2");
+      out.label(&format!("_str{index}"));
+      out.ins(&format!(".word {}, {size}, {}, {}", string_class.tag, dispatch_table_symbol(string_class), value.len()));
+      // SPIM only knows a few escapes, so strings with other characters are written as bytes
+      if value.bytes().all(|byte| (b' '..=b'~').contains(&byte)) {
+        out.ins(&format!(".asciiz \"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")));
+      } else {
+        let bytes: Vec<String> = value.bytes().chain([0]).map(|byte| byte.to_string()).collect();
+        out.ins(&format!(".byte {}", bytes.join(", ")));
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::mips::{emit_module, emit_module_with_gc};
+  use crate::test::{interpret, lower_file, lower_test_program, programs};
+  use crate::toolchain::GcMode;
+  use ir::opt::{OptLevel, PassManager};
+  use mips::asm::assemble;
+  use mips::machine::run;
+  use parser::programs::TestProgram;
+  use std::path::Path;
+
+  #[test]
+  fn test_programs_match_interpreter() {
+    for test_program in programs() {
+      let module = lower_test_program(&test_program);
+      for level in [OptLevel::O0, OptLevel::O2] {
+        let mut optimised = module.clone();
+        PassManager::for_level(level).run(&mut optimised).unwrap_or_else(|e| panic!("{}: {e}", test_program.name()));
+        let (stdout, stderr, code) = interpret(&optimised, &test_program.input);
+        let name = format!("{}-{level:?}", test_program.name());
+
+        let program = assemble(&emit_module(&optimised)).unwrap_or_else(|e| panic!("{name}: {e}"));
+        let mut output = Vec::new();
+        let (_, result) = run(&program, &mut test_program.input.as_bytes(), &mut output);
+        let result = result.unwrap_or_else(|e| panic!("{name}: {e}"));
+        // Runtime errors are part of the output
+        let actual = (String::from_utf8_lossy(&output).to_string(), Some(result));
+        assert_eq!(actual, (stdout + &stderr, code), "{name} behaves differently when run by the MIPS simulator");
+      }
+    }
+  }
+
+  #[test]
+  fn test_garbage_collectors() {
+    // Collecting at every allocation sweeps the whole heap, too slow in the simulator for the many live objects of
+    // gc.cl, which allocates enough to collect several times anyway
+    let mut runs: Vec<(TestProgram, GcMode, bool)> = programs().into_iter()
+        .flat_map(|program| [(program.clone(), GcMode::None, false), (program, GcMode::MarkSweep, true)])
+        .collect();
+    runs.push((TestProgram::new(Path::new("../test_resources/codegen/gc.cl")), GcMode::MarkSweep, false));
+    for (test_program, mode, stress) in runs {
+      let mut module = lower_test_program(&test_program);
+      PassManager::for_level(OptLevel::O2).run(&mut module).unwrap_or_else(|e| panic!("{}: {e}", test_program.name()));
+      let (stdout, stderr, code) = interpret(&module, &test_program.input);
+
+      let name = test_program.name();
+      let program = assemble(&emit_module_with_gc(&module, mode, stress)).unwrap_or_else(|e| panic!("{name}: {e}"));
+      let mut output = Vec::new();
+      let (_, result) = run(&program, &mut test_program.input.as_bytes(), &mut output);
+      let result = result.unwrap_or_else(|e| panic!("{name}: {e}"));
+      let actual = (String::from_utf8_lossy(&output).to_string(), Some(result));
+      assert_eq!(actual, (stdout + &stderr, code), "{name} behaves differently with the garbage collector {mode}, stress {stress}");
+    }
+  }
+
+  #[test]
+  fn test_runtime_errors_match_interpreter() {
+    let mut module = lower_file("../test_resources/ir/errors.cl");
+    module.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+    let program = assemble(&emit_module(&module)).expect("Program must assemble");
+
+    // Running out of memory depends on the heap of each backend
+    for choice in ["1", "2", "3", "4", "5", "6"] {
+      let (stdout, stderr, code) = interpret(&module, choice);
+      let mut output = Vec::new();
+      let (_, result) = run(&program, &mut choice.as_bytes(), &mut output);
+      let actual = (String::from_utf8_lossy(&output).to_string(), result.ok());
+      assert_eq!(actual, (stdout + &stderr, code), "Choice {choice} fails differently when run by the MIPS simulator");
+    }
+  }
+}
@@ -0,0 +1,75 @@
+use crate::{llvm, mips, wasm, x86};
+use ir::model::module::Module;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Machine the program is compiled for
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Target {
+  /// Linux on x86-64, System V calling convention, assembled with GNU as
+  #[default]
+  X86_64,
+  /// Textual LLVM IR, compiled with clang or llc for the host
+  Llvm,
+  /// WebAssembly with WASI, assembled to the binary format by the backend itself
+  Wasm,
+  /// MIPS32 assembly for SPIM, with the runtime included, run by the simulator of the `mips` crate
+  Mips,
+}
+
+impl FromStr for Target {
+  type Err = String;
+
+  /// Parses the target of a `--target` flag
+  fn from_str(target: &str) -> Result<Self, Self::Err> {
+    match target {
+      "x86-64" => Ok(Target::X86_64),
+      "llvm" => Ok(Target::Llvm),
+      "wasm" => Ok(Target::Wasm),
+      "mips" => Ok(Target::Mips),
+      _ => Err(format!("Unknown target {target}, expected x86-64, llvm, wasm or mips")),
+    }
+  }
+}
+
+impl Display for Target {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      Target::X86_64 => "x86-64",
+      Target::Llvm => "llvm",
+      Target::Wasm => "wasm",
+      Target::Mips => "mips",
+    })
+  }
+}
+
+impl Target {
+  /// Assembly of the whole program, linked with the runtime to make an executable, or the WebAssembly text format.
+  /// MIPS assembly already includes its runtime.
+  pub fn emit(self, module: &Module) -> String {
+    match self {
+      Target::X86_64 => x86::emit_module(module),
+      Target::Llvm => llvm::emit_module(module),
+      Target::Wasm => wasm::emit_module(module),
+      Target::Mips => mips::emit_module(module),
+    }
+  }
+
+  /// Extension of the files written by `emit`
+  pub fn extension(self) -> &'static str {
+    match self {
+      Target::X86_64 | Target::Mips => "s",
+      Target::Llvm => "ll",
+      Target::Wasm => "wat",
+    }
+  }
+
+  /// Extension of the files written by `build_executable`, none for native executables
+  pub fn executable_extension(self) -> &'static str {
+    match self {
+      Target::X86_64 | Target::Llvm => "",
+      Target::Wasm => "wasm",
+      Target::Mips => "s",
+    }
+  }
+}
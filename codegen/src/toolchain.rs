@@ -0,0 +1,341 @@
+use crate::target::Target;
+use crate::{mips, wasm};
+use ir::model::module::Module;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// C runtime linked with every program, see `runtime/runtime.c`
+pub const RUNTIME_SOURCE: &str = include_str!("../runtime/runtime.c");
+
+/// Garbage collector of the runtime, see `runtime/runtime.c`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GcMode {
+  /// Never frees memory
+  None,
+  /// Non-moving mark and sweep into free lists
+  MarkSweep,
+  /// Copying nursery promoting to a mark-compact old generation
+  Generational,
+}
+
+impl FromStr for GcMode {
+  type Err = String;
+
+  /// Parses the collector of a `--gc` flag
+  fn from_str(mode: &str) -> Result<Self, Self::Err> {
+    match mode {
+      "none" => Ok(GcMode::None),
+      "mark-sweep" => Ok(GcMode::MarkSweep),
+      "generational" => Ok(GcMode::Generational),
+      _ => Err(format!("Unknown garbage collector {mode}, expected none, mark-sweep or generational")),
+    }
+  }
+}
+
+impl Display for GcMode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      GcMode::None => "none",
+      GcMode::MarkSweep => "mark-sweep",
+      GcMode::Generational => "generational",
+    })
+  }
+}
+
+/// Garbage collection options of a build
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct GcOptions {
+  /// Collector of the runtime, the default of the target when `None`
+  pub mode: Option<GcMode>,
+  /// Collects at every allocation, to find missing roots
+  pub stress: bool,
+}
+
+impl GcOptions {
+  /// Checks the options are supported by the target and returns the collector. The x86-64 backend emits the stack
+  /// maps all collectors need and the LLVM backend a shadow stack, both use the generational one by default. The
+  /// WebAssembly runtime has its own mark-sweep collector, and the MIPS runtime a mark-sweep collector that can be
+  /// turned off.
+  pub fn resolve(self, target: Target) -> Result<GcMode, String> {
+    let (default, supported): (GcMode, &[GcMode]) = match target {
+      Target::X86_64 | Target::Llvm => (GcMode::Generational, &[GcMode::None, GcMode::MarkSweep, GcMode::Generational]),
+      Target::Mips => (GcMode::MarkSweep, &[GcMode::None, GcMode::MarkSweep]),
+      Target::Wasm => (GcMode::MarkSweep, &[GcMode::MarkSweep]),
+    };
+    let mode = self.mode.unwrap_or(default);
+    if !supported.contains(&mode) {
+      return Err(format!("The {target} target does not support the {mode} garbage collector"));
+    }
+    if self.stress && target == Target::Wasm {
+      return Err(format!("The {target} target does not support --gc-stress"));
+    }
+    Ok(mode)
+  }
+
+  /// Macros selecting the collector when compiling the runtime, and where it finds the roots of generated code
+  fn defines(self, target: Target, mode: GcMode) -> Vec<String> {
+    let mode = match mode {
+      GcMode::None => 0,
+      GcMode::MarkSweep => 1,
+      GcMode::Generational => 2,
+    };
+    let shadow_stack = target == Target::Llvm;
+    vec![format!("-DCOOL_GC={mode}"), format!("-DCOOL_GC_STRESS={}", self.stress as i32), format!("-DCOOL_SHADOW_STACK={}", shadow_stack as i32)]
+  }
+}
+
+/// Number of build directories made by this process, so parallel builds do not share one
+static BUILD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// C compiler driver used to assemble and link, `cc` unless the `CC` environment variable names another
+pub fn compiler() -> String {
+  env::var("CC").unwrap_or_else(|_| "cc".to_string())
+}
+
+/// Returns `true` if the C compiler can be run, so tests can be skipped on machines without one
+pub fn has_compiler() -> bool {
+  has_tool(&compiler())
+}
+
+/// Compiles the module for the target and links it with the runtime into an executable, or a WebAssembly module
+/// in the binary format to run with `runtime/wasi.js` or any WASI runtime. MIPS programs are the assembly itself,
+/// which SPIM loads.
+pub fn build_executable(module: &Module, target: Target, gc: GcOptions, output: &Path) -> Result<(), String> {
+  let mode = gc.resolve(target)?;
+  match target {
+    Target::Wasm => {
+      let binary = wasm::assemble(&target.emit(module))?;
+      return fs::write(output, binary).map_err(|e| format!("Unable to write {}: {e}", output.display()));
+    }
+    Target::Mips => return write(output, &mips_program(module, gc)?),
+    _ => {}
+  }
+
+  let dir = BuildDir::new()?;
+  let program = dir.path.join(format!("program.{}", target.extension()));
+  let runtime = dir.path.join("runtime.c");
+  write(&program, &target.emit(module))?;
+  write(&runtime, RUNTIME_SOURCE)?;
+
+  // Without clang, llc turns LLVM IR into assembly for the C compiler
+  let program = match target {
+    Target::Llvm if !has_tool("clang") => {
+      let assembly = dir.path.join("program.s");
+      let mut llc = Command::new("llc");
+      if llvm_version("llc").is_some_and(|version| version < 15) {
+        llc.arg("-opaque-pointers");
+      }
+      run(llc.arg("-O2").arg("-relocation-model=pic").arg("-o").arg(&assembly).arg(&program))?;
+      assembly
+    }
+    _ => program,
+  };
+
+  let mut compiler = match target {
+    Target::Llvm if has_tool("clang") => {
+      let mut clang = Command::new("clang");
+      if llvm_version("clang").is_some_and(|version| version < 15) {
+        clang.args(["-Xclang", "-opaque-pointers"]);
+      }
+      clang
+    }
+    _ => Command::new(compiler()),
+  };
+  // The collector walks the stack through the frame pointers of the runtime functions too
+  compiler.args(gc.defines(target, mode)).args(["-fno-omit-frame-pointer", "-mno-omit-leaf-frame-pointer"]);
+  run(compiler.arg("-O2").arg("-o").arg(output).arg(&program).arg(&runtime))
+}
+
+/// MIPS assembly of the program with its runtime, which SPIM or the simulator runs, using the collector of the options
+pub fn mips_program(module: &Module, gc: GcOptions) -> Result<String, String> {
+  let mode = gc.resolve(Target::Mips)?;
+  Ok(mips::emit_module_with_gc(module, mode, gc.stress))
+}
+
+/// Returns `true` if the tool can be run
+fn has_tool(tool: &str) -> bool {
+  Command::new(tool).arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+/// Major version of an LLVM tool, which takes opaque pointers by default from version 15
+fn llvm_version(tool: &str) -> Option<u32> {
+  let output = Command::new(tool).arg("--version").output().ok()?;
+  let text = String::from_utf8_lossy(&output.stdout);
+  let version = text.split(" version ").nth(1)?;
+  version.split('.').next()?.trim().parse().ok()
+}
+
+/// Returns `true` if LLVM IR can be compiled, with clang or llc
+pub fn has_llvm() -> bool {
+  has_tool("clang") || has_tool("llc")
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+  let program = command.get_program().to_string_lossy().to_string();
+  let result = command.output().map_err(|e| format!("Unable to run {program}: {e}"))?;
+  if !result.status.success() {
+    return Err(format!("{program} failed:\n{}", String::from_utf8_lossy(&result.stderr)));
+  }
+  Ok(())
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+  fs::write(path, contents).map_err(|e| format!("Unable to write {}: {e}", path.display()))
+}
+
+/// Temporary directory for the files of a build, removed when dropped
+struct BuildDir {
+  path: PathBuf,
+}
+
+impl BuildDir {
+  fn new() -> Result<Self, String> {
+    let count = BUILD_COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("cool-build-{}-{count}", std::process::id()));
+    fs::create_dir_all(&path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+    Ok(BuildDir { path })
+  }
+}
+
+impl Drop for BuildDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::target::Target;
+  use crate::test::{interpret, lower_file, lower_test_program, programs};
+  use crate::toolchain::{build_executable, has_compiler, has_llvm, GcMode, GcOptions};
+  use ir::model::module::Module;
+  use ir::opt::{OptLevel, PassManager};
+  use parser::programs::TestProgram;
+  use std::env;
+  use std::fs;
+  use std::io::Write;
+  use std::path::Path;
+  use std::process::{Command, Stdio};
+
+  /// Builds the program, runs it on the input and returns its output, error output and exit code
+  fn run_native(module: &Module, target: Target, gc: GcOptions, name: &str, input: &str) -> (String, String, Option<i32>) {
+    let executable = env::temp_dir().join(format!("cool-test-{}-{name}-{target:?}-{:?}-{}", std::process::id(), gc.mode, gc.stress));
+    build_executable(module, target, gc, &executable).unwrap_or_else(|e| panic!("{name}: {e}"));
+    let mut child = Command::new(&executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Executable must start");
+    child.stdin.take().expect("Input is piped").write_all(input.as_bytes()).expect("Couldn't write input");
+    let output = child.wait_with_output().expect("Executable must finish");
+    let _ = fs::remove_file(&executable);
+    (String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string(), output.status.code())
+  }
+
+  #[test]
+  fn test_programs_match_interpreter() {
+    if !has_compiler() {
+      eprintln!("Skipping native code tests, no C compiler found");
+      return;
+    }
+    let mut targets = vec![Target::X86_64];
+    if has_llvm() {
+      targets.push(Target::Llvm);
+    } else {
+      eprintln!("Skipping the LLVM target, neither clang nor llc found");
+    }
+
+    for program in programs() {
+      let module = lower_test_program(&program);
+      for level in [OptLevel::O0, OptLevel::O2] {
+        let mut optimised = module.clone();
+        PassManager::for_level(level).run(&mut optimised).unwrap_or_else(|e| panic!("{}: {e}", program.name()));
+        let expected = interpret(&optimised, &program.input);
+
+        let name = format!("{}-{level:?}", program.name());
+        for target in &targets {
+          let result = run_native(&optimised, *target, GcOptions::default(), &name, &program.input);
+          assert_eq!(result, expected, "{name} behaves differently when compiled for {target:?}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_runtime_errors_match_interpreter() {
+    if !has_compiler() {
+      eprintln!("Skipping native code tests, no C compiler found");
+      return;
+    }
+    let mut targets = vec![Target::X86_64];
+    if has_llvm() {
+      targets.push(Target::Llvm);
+    }
+    let mut module = lower_file("../test_resources/ir/errors.cl");
+    module.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+
+    // Running out of memory depends on the heap of each backend
+    for choice in ["1", "2", "3", "4", "5", "6"] {
+      let expected = interpret(&module, choice);
+      for target in &targets {
+        let result = run_native(&module, *target, GcOptions::default(), &format!("errors{choice}"), choice);
+        assert_eq!(result, expected, "Choice {choice} fails differently when compiled for {target:?}");
+      }
+    }
+  }
+
+  #[test]
+  fn test_garbage_collectors() {
+    if !has_compiler() {
+      eprintln!("Skipping native code tests, no C compiler found");
+      return;
+    }
+    let collectors = [
+      GcOptions { mode: Some(GcMode::None), stress: false },
+      GcOptions { mode: Some(GcMode::MarkSweep), stress: false },
+      GcOptions { mode: Some(GcMode::MarkSweep), stress: true },
+      GcOptions { mode: Some(GcMode::Generational), stress: true },
+    ];
+    let mut targets = vec![Target::X86_64];
+    if has_llvm() {
+      targets.push(Target::Llvm);
+    }
+    let mut programs = programs();
+    programs.push(TestProgram::new(Path::new("../test_resources/codegen/gc.cl")));
+    for program in programs {
+      let mut module = lower_test_program(&program);
+      PassManager::for_level(OptLevel::O2).run(&mut module).unwrap_or_else(|e| panic!("{}: {e}", program.name()));
+      let expected = interpret(&module, &program.input);
+
+      let name = program.name();
+      for gc in collectors {
+        for &target in &targets {
+          let result = run_native(&module, target, gc, &name, &program.input);
+          assert_eq!(result, expected, "{name} behaves differently on {target} with the garbage collector {gc:?}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_gc_options() {
+    let stress = GcOptions { mode: None, stress: true };
+    assert_eq!(GcOptions::default().resolve(Target::X86_64), Ok(GcMode::Generational));
+    assert_eq!(stress.resolve(Target::X86_64), Ok(GcMode::Generational));
+    assert_eq!(GcOptions::default().resolve(Target::Llvm), Ok(GcMode::Generational));
+    assert_eq!(stress.resolve(Target::Llvm), Ok(GcMode::Generational));
+    assert_eq!(GcOptions::default().resolve(Target::Wasm), Ok(GcMode::MarkSweep));
+    assert_eq!(GcOptions::default().resolve(Target::Mips), Ok(GcMode::MarkSweep));
+    assert_eq!(stress.resolve(Target::Mips), Ok(GcMode::MarkSweep));
+    let generational = GcOptions { mode: Some(GcMode::Generational), stress: false };
+    assert_eq!(generational.resolve(Target::Mips), Err("The mips target does not support the generational garbage collector".to_string()));
+    assert_eq!(stress.resolve(Target::Wasm), Err("The wasm target does not support --gc-stress".to_string()));
+    assert_eq!("copying".parse::<GcMode>(), Err("Unknown garbage collector copying, expected none, mark-sweep or generational".to_string()));
+  }
+}
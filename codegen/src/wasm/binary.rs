@@ -0,0 +1,495 @@
+use crate::wasm::model::{BlockType, Data, Element, Export, ExportKind, Func, FuncType, Global, Import, Instr, MemArg, Module, NumOp};
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+const I32: u8 = 0x7F;
+const FUNC_REF: u8 = 0x70;
+
+/// Encodes the module in the WebAssembly binary format, with function names in the `name` custom section
+pub fn encode(module: &Module) -> Vec<u8> {
+  let mut out = Vec::new();
+  out.extend_from_slice(MAGIC);
+  out.extend_from_slice(VERSION);
+
+  section(&mut out, 1, &module.types, |out, func_type| {
+    out.push(0x60);
+    vector(out, &vec![I32; func_type.params], |out, value_type| out.push(*value_type));
+    vector(out, &vec![I32; func_type.results], |out, value_type| out.push(*value_type));
+  });
+  section(&mut out, 2, &module.imports, |out, import| {
+    name(out, &import.module);
+    name(out, &import.name);
+    out.push(0x00);
+    unsigned(out, import.type_index);
+  });
+  section(&mut out, 3, &module.functions, |out, function| unsigned(out, function.type_index));
+  section(&mut out, 4, module.table.as_slice(), |out, size| {
+    out.push(FUNC_REF);
+    out.push(0x00);
+    unsigned(out, *size);
+  });
+  section(&mut out, 5, module.memory.as_slice(), |out, pages| {
+    out.push(0x00);
+    unsigned(out, *pages);
+  });
+  section(&mut out, 6, &module.globals, |out, global| {
+    out.push(I32);
+    out.push(global.mutable as u8);
+    constant(out, global.init);
+  });
+  section(&mut out, 7, &module.exports, |out, export| {
+    name(out, &export.name);
+    out.push(match export.kind {
+      ExportKind::Func => 0x00,
+      ExportKind::Memory => 0x02,
+    });
+    unsigned(out, export.index);
+  });
+  section(&mut out, 9, &module.elements, |out, element| {
+    out.push(0x00);
+    constant(out, element.offset as i32);
+    vector(out, &element.functions, |out, function| unsigned(out, *function));
+  });
+  section(&mut out, 10, &module.functions, |out, function| {
+    let mut code = Vec::new();
+    if function.locals > 0 {
+      unsigned(&mut code, 1);
+      unsigned(&mut code, function.locals as u32);
+      code.push(I32);
+    } else {
+      unsigned(&mut code, 0);
+    }
+    for instruction in &function.body {
+      encode_instruction(&mut code, instruction);
+    }
+    unsigned(out, code.len() as u32);
+    out.extend_from_slice(&code);
+  });
+  section(&mut out, 11, &module.data, |out, data| {
+    out.push(0x00);
+    constant(out, data.offset as i32);
+    unsigned(out, data.bytes.len() as u32);
+    out.extend_from_slice(&data.bytes);
+  });
+
+  if !module.names.is_empty() {
+    let mut names = Vec::new();
+    name(&mut names, "name");
+    let mut functions = Vec::new();
+    vector(&mut functions, &module.names.iter().enumerate().collect::<Vec<_>>(), |out, (index, function)| {
+      unsigned(out, *index as u32);
+      name(out, function);
+    });
+    names.push(1);
+    unsigned(&mut names, functions.len() as u32);
+    names.extend_from_slice(&functions);
+    out.push(0);
+    unsigned(&mut out, names.len() as u32);
+    out.extend_from_slice(&names);
+  }
+  out
+}
+
+/// Appends a section with its items, or nothing if it has none
+fn section<T>(out: &mut Vec<u8>, id: u8, items: &[T], item: impl FnMut(&mut Vec<u8>, &T)) {
+  if items.is_empty() {
+    return;
+  }
+  let mut contents = Vec::new();
+  vector(&mut contents, items, item);
+  out.push(id);
+  unsigned(out, contents.len() as u32);
+  out.extend_from_slice(&contents);
+}
+
+fn vector<T>(out: &mut Vec<u8>, items: &[T], mut item: impl FnMut(&mut Vec<u8>, &T)) {
+  unsigned(out, items.len() as u32);
+  for value in items {
+    item(out, value);
+  }
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+  unsigned(out, name.len() as u32);
+  out.extend_from_slice(name.as_bytes());
+}
+
+/// Constant expression `i32.const value; end`
+fn constant(out: &mut Vec<u8>, value: i32) {
+  out.push(0x41);
+  signed(out, value);
+  out.push(0x0B);
+}
+
+/// Unsigned LEB128
+fn unsigned(out: &mut Vec<u8>, mut value: u32) {
+  loop {
+    let byte = (value & 0x7F) as u8;
+    value >>= 7;
+    if value == 0 {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+/// Signed LEB128
+fn signed(out: &mut Vec<u8>, mut value: i32) {
+  loop {
+    let byte = (value & 0x7F) as u8;
+    value >>= 7;
+    if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+fn block_type(block_type: BlockType) -> u8 {
+  match block_type {
+    BlockType::Empty => 0x40,
+    BlockType::I32 => I32,
+  }
+}
+
+fn memarg(out: &mut Vec<u8>, opcode: u8, memarg: MemArg) {
+  out.push(opcode);
+  unsigned(out, memarg.align);
+  unsigned(out, memarg.offset);
+}
+
+fn encode_instruction(out: &mut Vec<u8>, instruction: &Instr) {
+  match instruction {
+    Instr::Unreachable => out.push(0x00),
+    Instr::Nop => out.push(0x01),
+    Instr::Block(result) => out.extend_from_slice(&[0x02, block_type(*result)]),
+    Instr::Loop(result) => out.extend_from_slice(&[0x03, block_type(*result)]),
+    Instr::If(result) => out.extend_from_slice(&[0x04, block_type(*result)]),
+    Instr::Else => out.push(0x05),
+    Instr::End => out.push(0x0B),
+    Instr::Br(depth) => {
+      out.push(0x0C);
+      unsigned(out, *depth);
+    }
+    Instr::BrIf(depth) => {
+      out.push(0x0D);
+      unsigned(out, *depth);
+    }
+    Instr::BrTable(targets, default) => {
+      out.push(0x0E);
+      vector(out, targets, |out, target| unsigned(out, *target));
+      unsigned(out, *default);
+    }
+    Instr::Return => out.push(0x0F),
+    Instr::Call(function) => {
+      out.push(0x10);
+      unsigned(out, *function);
+    }
+    Instr::CallIndirect(type_index) => {
+      out.push(0x11);
+      unsigned(out, *type_index);
+      out.push(0x00);
+    }
+    Instr::Drop => out.push(0x1A),
+    Instr::Select => out.push(0x1B),
+    Instr::LocalGet(index) | Instr::LocalSet(index) | Instr::LocalTee(index) | Instr::GlobalGet(index) | Instr::GlobalSet(index) => {
+      out.push(match instruction {
+        Instr::LocalGet(_) => 0x20,
+        Instr::LocalSet(_) => 0x21,
+        Instr::LocalTee(_) => 0x22,
+        Instr::GlobalGet(_) => 0x23,
+        _ => 0x24,
+      });
+      unsigned(out, *index);
+    }
+    Instr::I32Load(argument) => memarg(out, 0x28, *argument),
+    Instr::I32Load8U(argument) => memarg(out, 0x2D, *argument),
+    Instr::I32Store(argument) => memarg(out, 0x36, *argument),
+    Instr::I32Store8(argument) => memarg(out, 0x3A, *argument),
+    Instr::MemorySize => out.extend_from_slice(&[0x3F, 0x00]),
+    Instr::MemoryGrow => out.extend_from_slice(&[0x40, 0x00]),
+    Instr::MemoryCopy => out.extend_from_slice(&[0xFC, 10, 0x00, 0x00]),
+    Instr::MemoryFill => out.extend_from_slice(&[0xFC, 11, 0x00]),
+    Instr::I32Const(value) => {
+      out.push(0x41);
+      signed(out, *value);
+    }
+    Instr::I32(op) => out.push(op.opcode()),
+  }
+}
+
+/// Decodes a module in the binary format, rejecting what `encode` never writes, e.g. values other than `i32`
+pub fn decode(bytes: &[u8]) -> Result<Module, String> {
+  let mut reader = Reader { bytes, position: 0 };
+  if reader.take(4)? != MAGIC || reader.take(4)? != VERSION {
+    return Err("not a WebAssembly module".to_string());
+  }
+
+  let mut module = Module::default();
+  let mut function_types = Vec::new();
+  while reader.position < bytes.len() {
+    let id = reader.byte()?;
+    let size = reader.unsigned()? as usize;
+    let mut section = Reader { bytes: reader.take(size)?, position: 0 };
+    match id {
+      0 => {
+        if section.name()? == "name" {
+          module.names = decode_names(&mut section)?;
+        }
+        continue;
+      }
+      1 => {
+        module.types = section.vector(|reader| {
+          reader.expect(0x60, "function type")?;
+          let params = reader.vector(|reader| reader.expect(I32, "value type"))?.len();
+          let results = reader.vector(|reader| reader.expect(I32, "value type"))?.len();
+          Ok(FuncType { params, results })
+        })?
+      }
+      2 => {
+        module.imports = section.vector(|reader| {
+          let (module, name) = (reader.name()?, reader.name()?);
+          reader.expect(0x00, "function import")?;
+          Ok(Import { module, name, type_index: reader.unsigned()? })
+        })?
+      }
+      3 => function_types = section.vector(Reader::unsigned)?,
+      4 => {
+        let tables = section.vector(|reader| {
+          reader.expect(FUNC_REF, "funcref table")?;
+          reader.expect(0x00, "table limits")?;
+          reader.unsigned()
+        })?;
+        module.table = tables.first().copied();
+      }
+      5 => {
+        let memories = section.vector(|reader| {
+          reader.expect(0x00, "memory limits")?;
+          reader.unsigned()
+        })?;
+        module.memory = memories.first().copied();
+      }
+      6 => {
+        module.globals = section.vector(|reader| {
+          reader.expect(I32, "value type")?;
+          let mutable = reader.byte()? == 1;
+          Ok(Global { mutable, init: reader.constant()? })
+        })?
+      }
+      7 => {
+        module.exports = section.vector(|reader| {
+          let name = reader.name()?;
+          let kind = match reader.byte()? {
+            0x00 => ExportKind::Func,
+            0x02 => ExportKind::Memory,
+            kind => return Err(format!("unsupported export kind {kind}")),
+          };
+          Ok(Export { name, kind, index: reader.unsigned()? })
+        })?
+      }
+      9 => {
+        module.elements = section.vector(|reader| {
+          reader.expect(0x00, "active element segment")?;
+          let offset = reader.constant()? as u32;
+          Ok(Element { offset, functions: reader.vector(Reader::unsigned)? })
+        })?
+      }
+      10 => {
+        let bodies = section.vector(|reader| {
+          let size = reader.unsigned()? as usize;
+          let mut code = Reader { bytes: reader.take(size)?, position: 0 };
+          let mut locals = 0;
+          for (count, _) in code.vector(|reader| Ok((reader.unsigned()?, reader.expect(I32, "value type")?)))? {
+            locals += count as usize;
+          }
+          let mut body = Vec::new();
+          while code.position < code.bytes.len() {
+            body.push(code.instruction()?);
+          }
+          Ok((locals, body))
+        })?;
+        if bodies.len() != function_types.len() {
+          return Err("function and code sections differ in length".to_string());
+        }
+        module.functions = function_types.iter().zip(bodies).map(|(type_index, (locals, body))| Func { type_index: *type_index, locals, body }).collect();
+      }
+      11 => {
+        module.data = section.vector(|reader| {
+          reader.expect(0x00, "active data segment")?;
+          let offset = reader.constant()? as u32;
+          let length = reader.unsigned()? as usize;
+          Ok(Data { offset, bytes: reader.take(length)?.to_vec() })
+        })?
+      }
+      _ => return Err(format!("unsupported section {id}")),
+    }
+    if section.position != section.bytes.len() {
+      return Err(format!("section {id} has trailing bytes"));
+    }
+  }
+  Ok(module)
+}
+
+/// Function names of the `name` custom section
+fn decode_names(section: &mut Reader) -> Result<Vec<String>, String> {
+  let mut names = Vec::new();
+  while section.position < section.bytes.len() {
+    let id = section.byte()?;
+    let size = section.unsigned()? as usize;
+    let mut subsection = Reader { bytes: section.take(size)?, position: 0 };
+    if id == 1 {
+      for (index, name) in subsection.vector(|reader| Ok((reader.unsigned()?, reader.name()?)))? {
+        if index as usize != names.len() {
+          return Err("function names are not in order".to_string());
+        }
+        names.push(name);
+      }
+    }
+  }
+  Ok(names)
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+    let bytes = self.bytes.get(self.position..self.position + length).ok_or("unexpected end of module")?;
+    self.position += length;
+    Ok(bytes)
+  }
+
+  fn byte(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn expect(&mut self, expected: u8, what: &str) -> Result<(), String> {
+    match self.byte()? {
+      byte if byte == expected => Ok(()),
+      byte => Err(format!("expected {what}, found byte {byte:#04x} at {}", self.position - 1)),
+    }
+  }
+
+  fn unsigned(&mut self) -> Result<u32, String> {
+    let mut value: u64 = 0;
+    for shift in (0..35).step_by(7) {
+      let byte = self.byte()?;
+      value |= ((byte & 0x7F) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return u32::try_from(value).map_err(|_| "integer too large".to_string());
+      }
+    }
+    Err("integer too long".to_string())
+  }
+
+  fn signed(&mut self) -> Result<i32, String> {
+    let mut value: i64 = 0;
+    for shift in (0..35).step_by(7) {
+      let byte = self.byte()?;
+      value |= ((byte & 0x7F) as i64) << shift;
+      if byte & 0x80 == 0 {
+        if byte & 0x40 != 0 {
+          value |= -1 << (shift + 7);
+        }
+        return i32::try_from(value).map_err(|_| "integer too large".to_string());
+      }
+    }
+    Err("integer too long".to_string())
+  }
+
+  fn name(&mut self) -> Result<String, String> {
+    let length = self.unsigned()? as usize;
+    String::from_utf8(self.take(length)?.to_vec()).map_err(|_| "names must be UTF-8".to_string())
+  }
+
+  fn vector<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let length = self.unsigned()?;
+    (0..length).map(|_| item(self)).collect()
+  }
+
+  fn constant(&mut self) -> Result<i32, String> {
+    self.expect(0x41, "i32.const")?;
+    let value = self.signed()?;
+    self.expect(0x0B, "end of constant expression")?;
+    Ok(value)
+  }
+
+  fn block_type(&mut self) -> Result<BlockType, String> {
+    match self.byte()? {
+      0x40 => Ok(BlockType::Empty),
+      I32 => Ok(BlockType::I32),
+      byte => Err(format!("unsupported block type {byte:#04x}")),
+    }
+  }
+
+  fn memarg(&mut self) -> Result<MemArg, String> {
+    Ok(MemArg { align: self.unsigned()?, offset: self.unsigned()? })
+  }
+
+  fn instruction(&mut self) -> Result<Instr, String> {
+    let opcode = self.byte()?;
+    let instruction = match opcode {
+      0x00 => Instr::Unreachable,
+      0x01 => Instr::Nop,
+      0x02 => Instr::Block(self.block_type()?),
+      0x03 => Instr::Loop(self.block_type()?),
+      0x04 => Instr::If(self.block_type()?),
+      0x05 => Instr::Else,
+      0x0B => Instr::End,
+      0x0C => Instr::Br(self.unsigned()?),
+      0x0D => Instr::BrIf(self.unsigned()?),
+      0x0E => {
+        let targets = self.vector(Reader::unsigned)?;
+        Instr::BrTable(targets, self.unsigned()?)
+      }
+      0x0F => Instr::Return,
+      0x10 => Instr::Call(self.unsigned()?),
+      0x11 => {
+        let type_index = self.unsigned()?;
+        self.expect(0x00, "table 0")?;
+        Instr::CallIndirect(type_index)
+      }
+      0x1A => Instr::Drop,
+      0x1B => Instr::Select,
+      0x20 => Instr::LocalGet(self.unsigned()?),
+      0x21 => Instr::LocalSet(self.unsigned()?),
+      0x22 => Instr::LocalTee(self.unsigned()?),
+      0x23 => Instr::GlobalGet(self.unsigned()?),
+      0x24 => Instr::GlobalSet(self.unsigned()?),
+      0x28 => Instr::I32Load(self.memarg()?),
+      0x2D => Instr::I32Load8U(self.memarg()?),
+      0x36 => Instr::I32Store(self.memarg()?),
+      0x3A => Instr::I32Store8(self.memarg()?),
+      0x3F => {
+        self.expect(0x00, "memory 0")?;
+        Instr::MemorySize
+      }
+      0x40 => {
+        self.expect(0x00, "memory 0")?;
+        Instr::MemoryGrow
+      }
+      0x41 => Instr::I32Const(self.signed()?),
+      0xFC => match self.unsigned()? {
+        10 => {
+          self.expect(0x00, "memory 0")?;
+          self.expect(0x00, "memory 0")?;
+          Instr::MemoryCopy
+        }
+        11 => {
+          self.expect(0x00, "memory 0")?;
+          Instr::MemoryFill
+        }
+        code => return Err(format!("unsupported instruction 0xfc {code}")),
+      },
+      _ => match NumOp::ALL.into_iter().find(|op| op.opcode() == opcode) {
+        Some(op) => Instr::I32(op),
+        None => return Err(format!("unsupported instruction {opcode:#04x} at {}", self.position - 1)),
+      },
+    };
+    Ok(instruction)
+  }
+}
@@ -0,0 +1,340 @@
+use crate::wasm::{function_symbol, Layout, HEADER_SIZE};
+use ir::interp::Location;
+use ir::model::function::Function;
+use ir::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use ir::model::module::Module;
+use ir::model::{BlockId, Constant, Pos, Temp, Type};
+use std::collections::HashMap;
+
+/// Emits one function with a body.
+///
+/// Temporaries are locals `$tN`. Object values are also stored in the function's frame on the shadow stack when they
+/// are defined, so the collector finds them. Blocks are laid out in order inside a loop: a jump to the next block falls
+/// through, other jumps set `$block` and restart the loop, whose `br_table` branches to the target.
+pub(super) struct FunctionEmitter<'a> {
+  layout: &'a Layout,
+  module: &'a Module,
+  function: &'a Function,
+  slots: HashMap<Temp, u32>, // offset in the frame of each object temporary
+  text: String,
+  depth: usize, // blocks open, for indentation
+}
+
+impl<'a> FunctionEmitter<'a> {
+  pub(super) fn new(layout: &'a Layout, module: &'a Module, function: &'a Function) -> Self {
+    let slots = (0..function.temps.len() as u32)
+        .map(Temp)
+        .filter(|temp| matches!(function.temp_type(*temp), Type::Object(_)))
+        .enumerate()
+        .map(|(index, temp)| (temp, 4 * index as u32))
+        .collect();
+    FunctionEmitter { layout, module, function, slots, text: String::new(), depth: 2 }
+  }
+
+  pub(super) fn emit(mut self) -> String {
+    let params: Vec<String> = self.function.params.iter().map(|(_, param)| format!(" (param $t{} i32)", param.0)).collect();
+    self.text.push_str(&format!("  (func {}{} (result i32)\n", function_symbol(self.function), params.concat()));
+    for temp in (0..self.function.temps.len() as u32).map(Temp) {
+      if !self.function.params.iter().any(|(_, param)| *param == temp) {
+        self.ins(&format!("(local $t{} i32)", temp.0));
+      }
+    }
+    self.ins("(local $frame i32)");
+    self.ins("(local $block i32)");
+
+    let frame_size = 4 * self.slots.len();
+    if frame_size > 0 {
+      for line in ["global.get $sp", "local.tee $frame", &format!("i32.const {frame_size}"), "i32.add", "global.set $sp", "call $check_stack"] {
+        self.ins(line);
+      }
+      for line in ["local.get $frame", "i32.const 0", &format!("i32.const {frame_size}"), "memory.fill"] {
+        self.ins(line);
+      }
+      for (_, param) in &self.function.params {
+        self.root(*param);
+      }
+    }
+
+    let ids: Vec<BlockId> = self.function.block_ids().collect();
+    let dispatch = ids.iter().any(|id| self.function.block(*id).terminator.successors().iter().any(|target| target.index() != id.index() + 1));
+    if dispatch {
+      self.ins("loop $dispatch");
+      for id in ids.iter().rev() {
+        self.ins(&format!("block $b{}", id.0));
+      }
+      let targets: Vec<String> = ids.iter().map(|id| format!("$b{}", id.0)).collect();
+      self.ins("local.get $block");
+      self.ins(&format!("br_table {} $b0", targets.join(" ")));
+    }
+    for id in ids {
+      if dispatch {
+        self.ins("end");
+      }
+      let block = self.function.block(id);
+      for instruction in block.instructions.iter().filter(|instruction| !instruction.is_phi()) {
+        self.instruction(instruction);
+      }
+      self.terminator(id, &block.terminator);
+    }
+    if dispatch {
+      self.ins("end");
+    }
+    self.ins("unreachable)");
+    self.text
+  }
+
+  /// Adds a line, indented by the blocks it is in
+  fn ins(&mut self, line: &str) {
+    if line == "end" || line == "else" {
+      self.depth -= 1;
+    }
+    self.text.push_str(&"  ".repeat(self.depth));
+    self.text.push_str(line);
+    self.text.push('\n');
+    if line.starts_with("block") || line.starts_with("loop") || line.starts_with("if") || line == "else" {
+      self.depth += 1;
+    }
+  }
+
+  fn get(&mut self, temp: Temp) {
+    self.ins(&format!("local.get $t{}", temp.0));
+  }
+
+  /// Stores an object temporary in the frame, where the collector finds it
+  fn root(&mut self, temp: Temp) {
+    if let Some(offset) = self.slots.get(&temp).copied() {
+      self.ins("local.get $frame");
+      self.get(temp);
+      self.ins(&format!("i32.store offset={offset}"));
+    }
+  }
+
+  /// Sets the destination to the value on the stack, or drops it if the value is unused
+  fn set(&mut self, dest: Option<Temp>) {
+    match dest {
+      Some(dest) => {
+        self.ins(&format!("local.set $t{}", dest.0));
+        self.root(dest);
+      }
+      None => self.ins("drop"),
+    }
+  }
+
+  fn instruction(&mut self, instruction: &Instruction) {
+    if instruction.op.enters_runtime() {
+      self.ins(&format!("i32.const {}", self.location(instruction.pos)));
+      self.ins("global.set $cool_location");
+    }
+    match &instruction.op {
+      Op::Const(constant) => {
+        let value = match constant {
+          Constant::Int(value) => *value,
+          Constant::Bool(value) => *value as i32,
+          Constant::Str(value) => self.layout.string(value) as i32,
+          Constant::Void => 0,
+        };
+        self.ins(&format!("i32.const {value}"));
+      }
+      Op::Copy(t) => self.get(*t),
+      Op::Phi(_) => panic!("Phis are assigned on the edges to their block"),
+      Op::Unary(UnaryOp::Neg, t) => {
+        self.ins("i32.const 0");
+        self.get(*t);
+        self.ins("i32.sub");
+      }
+      Op::Unary(UnaryOp::Not, t) | Op::IsVoid(t) => {
+        self.get(*t);
+        self.ins("i32.eqz");
+      }
+      Op::Binary(op, a, b) => self.binary(*op, *a, *b, instruction.pos),
+      Op::ObjEq(a, b) => {
+        self.get(*a);
+        self.get(*b);
+        self.ins("call $cool_equals");
+      }
+      Op::InstanceOf(t, class_name) => {
+        // tag - first tag <= last tag - first tag, compared unsigned so smaller tags fail too
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.get(*t);
+        self.ins("i32.load");
+        self.ins(&format!("i32.const {}", class.tag));
+        self.ins("i32.sub");
+        self.ins(&format!("i32.const {}", class.max_descendant_tag - class.tag));
+        self.ins("i32.le_u");
+      }
+      Op::Box(t) => {
+        self.get(*t);
+        self.ins(if *self.function.temp_type(*t) == Type::Bool { "call $cool_box_bool" } else { "call $cool_box_int" });
+      }
+      Op::Unbox(t) => {
+        self.get(*t);
+        self.ins(&format!("i32.load offset={HEADER_SIZE}"));
+      }
+      Op::New(class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.ins(&format!("i32.const {}", class.tag));
+        self.ins("call $cool_new");
+        if let Some(init) = &class.init {
+          self.call(init);
+        }
+      }
+      Op::NewLike(t) => {
+        self.get(*t);
+        self.ins("call $cool_new_like");
+      }
+      Op::GetAttr { object, index } => {
+        self.get(*object);
+        self.ins(&format!("i32.load offset={}", HEADER_SIZE + 4 * *index as u32));
+      }
+      Op::SetAttr { object, index, value } => {
+        self.get(*object);
+        self.get(*value);
+        self.ins(&format!("i32.store offset={}", HEADER_SIZE + 4 * *index as u32));
+        return;
+      }
+      Op::CheckVoid(t, kind) => {
+        self.check_void(*t, *kind, instruction.pos);
+        return;
+      }
+      Op::DebugVar { .. } => return,
+      Op::Call { function, args } => {
+        for arg in args {
+          self.get(*arg);
+        }
+        self.call(function);
+      }
+      Op::Dispatch { receiver, slot, args, .. } => {
+        self.check_void(*receiver, TrapKind::DispatchOnVoid, instruction.pos);
+        self.get(*receiver);
+        for arg in args {
+          self.get(*arg);
+        }
+        self.get(*receiver);
+        self.ins("i32.load offset=8");
+        self.ins(&format!("i32.load offset={}", 4 * slot));
+        self.ins(&format!("call_indirect (type $fn{})", args.len() + 1));
+      }
+    }
+    self.set(instruction.dest);
+  }
+
+  fn call(&mut self, function: &str) {
+    let symbol = function_symbol(self.module.function(function).expect("Function is verified"));
+    self.ins(&format!("call {symbol}"));
+  }
+
+  fn check_void(&mut self, t: Temp, kind: TrapKind, pos: Pos) {
+    self.get(t);
+    self.ins("i32.eqz");
+    self.ins("if");
+    self.trap(kind, t, pos);
+    self.ins("end");
+  }
+
+  /// Calls the runtime to report the error, which does not return
+  fn trap(&mut self, kind: TrapKind, value: Temp, pos: Pos) {
+    let kind = match kind {
+      TrapKind::DispatchOnVoid => 0,
+      TrapKind::CaseOnVoid => 1,
+      TrapKind::CaseNoMatch => 2,
+    };
+    self.ins(&format!("i32.const {kind}"));
+    self.get(value);
+    self.ins(&format!("i32.const {}", self.location(pos)));
+    self.ins("call $cool_trap");
+  }
+
+  /// Address of the string giving the source location of the position in the runtime errors, see `error_locations`
+  fn location(&self, pos: Pos) -> u32 {
+    self.layout.string(&Location::of(self.module, self.function, pos).to_string())
+  }
+
+  /// Pushes the result of the operation on raw values, which wraps on overflow like COOL integers
+  fn binary(&mut self, op: BinaryOp, a: Temp, b: Temp, pos: Pos) {
+    if op == BinaryOp::Div {
+      // `i32.div_s` traps on the minimum integer divided by -1, which wraps to itself like its negation
+      self.get(b);
+      self.ins("i32.eqz");
+      self.ins("if");
+      self.ins(&format!("i32.const {}", self.location(pos)));
+      self.ins("call $cool_division_by_zero");
+      self.ins("end");
+      self.get(b);
+      self.ins("i32.const -1");
+      self.ins("i32.eq");
+      self.ins("if (result i32)");
+      self.ins("i32.const 0");
+      self.get(a);
+      self.ins("i32.sub");
+      self.ins("else");
+      self.get(a);
+      self.get(b);
+      self.ins("i32.div_s");
+      self.ins("end");
+      return;
+    }
+    self.get(a);
+    self.get(b);
+    self.ins(match op {
+      BinaryOp::Add => "i32.add",
+      BinaryOp::Sub => "i32.sub",
+      BinaryOp::Mul => "i32.mul",
+      BinaryOp::Lt => "i32.lt_s",
+      BinaryOp::Le => "i32.le_s",
+      BinaryOp::Eq => "i32.eq",
+      BinaryOp::Div => unreachable!("Division is handled above"),
+    });
+  }
+
+  fn terminator(&mut self, id: BlockId, terminator: &Terminator) {
+    match terminator {
+      Terminator::Jump(target) => self.jump(id, *target),
+      Terminator::Branch { cond, then_block, else_block } => {
+        self.get(*cond);
+        self.ins("if");
+        self.jump(id, *then_block);
+        self.ins("else");
+        self.jump(id, *else_block);
+        self.ins("end");
+      }
+      Terminator::Return(value) => {
+        self.get(*value);
+        if !self.slots.is_empty() {
+          self.ins("local.get $frame");
+          self.ins("global.set $sp");
+        }
+        self.ins("return");
+      }
+      Terminator::Trap { kind, value, pos } => {
+        self.trap(*kind, *value, *pos);
+        self.ins("unreachable");
+      }
+    }
+  }
+
+  /// Assigns the phis of the target the values coming from the block, then jumps to it unless it is the next block.
+  /// The values are all pushed before any phi is set, as a phi may be the operand of another.
+  fn jump(&mut self, from: BlockId, target: BlockId) {
+    let moves: Vec<(Temp, Temp)> = self.function.block(target).instructions.iter()
+        .map_while(|instruction| match (&instruction.op, instruction.dest) {
+          (Op::Phi(incoming), Some(dest)) => incoming.iter().find(|(block, _)| *block == from).map(|(_, value)| (dest, *value)),
+          _ => None,
+        })
+        .filter(|(dest, value)| dest != value)
+        .collect();
+    for (_, value) in &moves {
+      self.get(*value);
+    }
+    for (dest, _) in moves.iter().rev() {
+      self.ins(&format!("local.set $t{}", dest.0));
+    }
+    for (dest, _) in &moves {
+      self.root(*dest);
+    }
+    if target.index() != from.index() + 1 {
+      self.ins(&format!("i32.const {}", target.0));
+      self.ins("local.set $block");
+      self.ins("br $dispatch");
+    }
+  }
+}
@@ -0,0 +1,477 @@
+use crate::wasm::model::{BlockType, ExportKind, Func, Instr, MemArg, Module, NumOp};
+use std::io::{Read, Write};
+
+/// Size of a memory page
+const PAGE_SIZE: usize = 65536;
+
+/// Largest memory, in pages, like the 1 GiB default limit of browsers
+const MAX_PAGES: usize = 16384;
+
+/// Deepest call stack before the module traps, about what engines allow for the small frames of COOL methods
+const MAX_FRAMES: usize = 200_000;
+
+/// WASI error code of a bad file descriptor
+const ERRNO_BADF: i32 = 8;
+
+/// WASI error code of an input or output error
+const ERRNO_IO: i32 = 29;
+
+/// Runs the `_start` export of a module, with the WASI functions the backend imports on the given streams.
+///
+/// This is a plain interpreter of the subset in `model`, to run compiled programs where no WebAssembly engine is
+/// installed. Returns the exit code, 0 when `_start` returns, or the message of a trap.
+pub fn run(module: &Module, stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<i32, String> {
+  let start = module.exports.iter()
+      .find(|export| export.name == "_start" && export.kind == ExportKind::Func)
+      .ok_or("module has no _start function")?;
+  let mut machine = Machine::new(module, Wasi { stdin, stdout, stderr })?;
+  let result = match machine.call(start.index) {
+    Ok(()) => Ok(0),
+    Err(Stop::Exit(code)) => Ok(code),
+    Err(Stop::Trap(message)) => Err(message),
+  };
+  machine.wasi.stdout.flush().map_err(|e| e.to_string())?;
+  result
+}
+
+/// Why a module stopped before `_start` returned
+enum Stop {
+  Exit(i32),
+  Trap(String),
+}
+
+fn trap<T>(message: &str) -> Result<T, Stop> {
+  Err(Stop::Trap(message.to_string()))
+}
+
+/// The streams of the WASI functions
+struct Wasi<'a> {
+  stdin: &'a mut dyn Read,
+  stdout: &'a mut dyn Write,
+  stderr: &'a mut dyn Write,
+}
+
+/// Imported function, by name in `wasi_snapshot_preview1`
+#[derive(Clone, Copy)]
+enum HostFunction {
+  FdWrite,
+  FdRead,
+  ProcExit,
+}
+
+/// Target of a branch to an enclosing block
+#[derive(Clone, Copy)]
+struct Label {
+  arity: usize,        // values kept by a branch
+  height: usize,       // height of the value stack when the block was entered
+  continuation: usize, // position a branch continues at
+  is_loop: bool,       // branches to a loop restart it, so it keeps its label
+}
+
+struct Frame {
+  function: usize, // index in the functions with a body
+  position: usize,
+  locals: usize, // start of the locals in `Machine::locals`
+  labels: usize, // labels of enclosing frames
+  height: usize, // height of the value stack when called
+}
+
+struct Machine<'a> {
+  module: &'a Module,
+  wasi: Wasi<'a>,
+  host_functions: Vec<HostFunction>,
+  ends: Vec<Vec<usize>>, // for each instruction starting a block, the position of its `end`, and of its `end` for an `else`
+  elses: Vec<Vec<Option<usize>>>,
+  memory: Vec<u8>,
+  globals: Vec<i32>,
+  table: Vec<Option<u32>>,
+  stack: Vec<i32>,
+  locals: Vec<i32>,
+  labels: Vec<Label>,
+  frames: Vec<Frame>,
+}
+
+impl<'a> Machine<'a> {
+  fn new(module: &'a Module, wasi: Wasi<'a>) -> Result<Self, String> {
+    let host_functions = module.imports.iter()
+        .map(|import| match (import.module.as_str(), import.name.as_str()) {
+          ("wasi_snapshot_preview1", "fd_write") => Ok(HostFunction::FdWrite),
+          ("wasi_snapshot_preview1", "fd_read") => Ok(HostFunction::FdRead),
+          ("wasi_snapshot_preview1", "proc_exit") => Ok(HostFunction::ProcExit),
+          (module, name) => Err(format!("unknown import {module}.{name}")),
+        })
+        .collect::<Result<Vec<HostFunction>, String>>()?;
+
+    let mut memory = vec![0; module.memory.unwrap_or(0) as usize * PAGE_SIZE];
+    for data in &module.data {
+      let start = data.offset as usize;
+      memory.get_mut(start..start + data.bytes.len()).ok_or("data segment out of bounds")?.copy_from_slice(&data.bytes);
+    }
+    let mut table = vec![None; module.table.unwrap_or(0) as usize];
+    for element in &module.elements {
+      for (index, function) in element.functions.iter().enumerate() {
+        *table.get_mut(element.offset as usize + index).ok_or("element segment out of bounds")? = Some(*function);
+      }
+    }
+
+    let (ends, elses) = module.functions.iter().map(block_ends).collect::<Result<Vec<_>, String>>()?.into_iter().unzip();
+    Ok(Machine {
+      module,
+      wasi,
+      host_functions,
+      ends,
+      elses,
+      memory,
+      globals: module.globals.iter().map(|global| global.init).collect(),
+      table,
+      stack: Vec::new(),
+      locals: Vec::new(),
+      labels: Vec::new(),
+      frames: Vec::new(),
+    })
+  }
+
+  /// Calls a function without arguments and runs until it returns
+  fn call(&mut self, function: u32) -> Result<(), Stop> {
+    self.enter(function)?;
+    while !self.frames.is_empty() {
+      self.step()?;
+    }
+    Ok(())
+  }
+
+  /// Calls a function with its arguments on the stack, running imported functions right away
+  fn enter(&mut self, function: u32) -> Result<(), Stop> {
+    let func_type = self.module.func_type(function);
+    if let Some(host) = self.host_functions.get(function as usize).copied() {
+      let args = self.stack.split_off(self.stack.len() - func_type.params);
+      let result = self.host_call(host, &args)?;
+      self.stack.push(result);
+      return Ok(());
+    }
+    if self.frames.len() >= MAX_FRAMES {
+      return trap("call stack exhausted");
+    }
+    let index = function as usize - self.module.imports.len();
+    let locals = self.locals.len();
+    self.locals.extend(self.stack.drain(self.stack.len() - func_type.params..));
+    self.locals.extend(std::iter::repeat_n(0, self.module.functions[index].locals));
+    self.frames.push(Frame { function: index, position: 0, locals, labels: self.labels.len(), height: self.stack.len() });
+    Ok(())
+  }
+
+  /// Returns from the running function, keeping its results on the stack
+  fn leave(&mut self) {
+    let frame = self.frames.pop().expect("A function is running");
+    let results = self.module.types[self.module.functions[frame.function].type_index as usize].results;
+    let first = self.stack.len() - results;
+    self.stack.drain(frame.height..first);
+    self.locals.truncate(frame.locals);
+    self.labels.truncate(frame.labels);
+  }
+
+  fn pop(&mut self) -> i32 {
+    self.stack.pop().expect("Stack is validated")
+  }
+
+  /// Branches to the enclosing block at the depth, or returns if the depth is that of the function body
+  fn branch(&mut self, depth: u32) {
+    let frame = self.frames.last().expect("A function is running");
+    let frame_labels = self.labels.len() - frame.labels;
+    if depth as usize == frame_labels {
+      self.leave();
+      return;
+    }
+    let index = self.labels.len() - 1 - depth as usize;
+    let label = self.labels[index];
+    let first = self.stack.len() - label.arity;
+    self.stack.drain(label.height..first);
+    self.labels.truncate(if label.is_loop { index + 1 } else { index });
+    self.jump(label.continuation);
+  }
+
+  /// Continues the running function at the position
+  fn jump(&mut self, position: usize) {
+    self.frames.last_mut().expect("A function is running").position = position;
+  }
+
+  fn address(&mut self, memarg: MemArg, size: usize) -> Result<usize, Stop> {
+    let address = self.pop() as u32 as usize + memarg.offset as usize;
+    if address + size > self.memory.len() {
+      return trap("out of bounds memory access");
+    }
+    Ok(address)
+  }
+
+  fn load(&self, address: usize) -> i32 {
+    i32::from_le_bytes(self.memory[address..address + 4].try_into().expect("Four bytes"))
+  }
+
+  fn step(&mut self) -> Result<(), Stop> {
+    let module = self.module;
+    let frame = self.frames.last_mut().expect("A function is running");
+    let (index, position, locals, frame_labels) = (frame.function, frame.position, frame.locals, frame.labels);
+    frame.position += 1;
+    let function: &Func = &module.functions[index];
+
+    match &function.body[position] {
+      Instr::Unreachable => return trap("unreachable"),
+      Instr::Nop => {}
+      Instr::Block(block_type) | Instr::Loop(block_type) => {
+        let is_loop = matches!(function.body[position], Instr::Loop(_));
+        let label = Label {
+          arity: if is_loop { 0 } else { arity(*block_type) },
+          height: self.stack.len(),
+          continuation: if is_loop { position + 1 } else { self.ends[index][position] + 1 },
+          is_loop,
+        };
+        self.labels.push(label);
+      }
+      Instr::If(block_type) => {
+        let end = self.ends[index][position];
+        let otherwise = self.elses[index][position];
+        let condition = self.pop();
+        self.labels.push(Label { arity: arity(*block_type), height: self.stack.len(), continuation: end + 1, is_loop: false });
+        if condition == 0 {
+          // The `end` of the `if` pops its label
+          self.jump(otherwise.map_or(end, |otherwise| otherwise + 1));
+        }
+      }
+      Instr::Else => {
+        // The `then` branch is done
+        self.jump(self.ends[index][position]);
+      }
+      Instr::End => {
+        if self.labels.len() > frame_labels {
+          self.labels.pop();
+        } else {
+          self.leave();
+        }
+      }
+      Instr::Br(depth) => self.branch(*depth),
+      Instr::BrIf(depth) => {
+        if self.pop() != 0 {
+          self.branch(*depth);
+        }
+      }
+      Instr::BrTable(targets, default) => {
+        let index = self.pop() as u32 as usize;
+        self.branch(*targets.get(index).unwrap_or(default));
+      }
+      Instr::Return => self.leave(),
+      Instr::Call(function) => self.enter(*function)?,
+      Instr::CallIndirect(type_index) => {
+        let index = self.pop() as u32 as usize;
+        let Some(Some(function)) = self.table.get(index).copied() else {
+          return trap("uninitialized table element");
+        };
+        if module.func_type(function) != module.types[*type_index as usize] {
+          return trap("indirect call signature mismatch");
+        }
+        self.enter(function)?;
+      }
+      Instr::Drop => {
+        self.pop();
+      }
+      Instr::Select => {
+        let condition = self.pop();
+        let second = self.pop();
+        let first = self.pop();
+        self.stack.push(if condition != 0 { first } else { second });
+      }
+      Instr::LocalGet(index) => self.stack.push(self.locals[locals + *index as usize]),
+      Instr::LocalSet(index) => self.locals[locals + *index as usize] = self.pop(),
+      Instr::LocalTee(index) => self.locals[locals + *index as usize] = *self.stack.last().expect("Stack is validated"),
+      Instr::GlobalGet(index) => self.stack.push(self.globals[*index as usize]),
+      Instr::GlobalSet(index) => self.globals[*index as usize] = self.pop(),
+      Instr::I32Load(memarg) => {
+        let address = self.address(*memarg, 4)?;
+        self.stack.push(self.load(address));
+      }
+      Instr::I32Load8U(memarg) => {
+        let address = self.address(*memarg, 1)?;
+        self.stack.push(self.memory[address] as i32);
+      }
+      Instr::I32Store(memarg) => {
+        let value = self.pop();
+        let address = self.address(*memarg, 4)?;
+        self.memory[address..address + 4].copy_from_slice(&value.to_le_bytes());
+      }
+      Instr::I32Store8(memarg) => {
+        let value = self.pop();
+        let address = self.address(*memarg, 1)?;
+        self.memory[address] = value as u8;
+      }
+      Instr::MemorySize => self.stack.push((self.memory.len() / PAGE_SIZE) as i32),
+      Instr::MemoryGrow => {
+        let pages = self.memory.len() / PAGE_SIZE;
+        let delta = self.pop() as u32 as usize;
+        if pages + delta > MAX_PAGES {
+          self.stack.push(-1);
+        } else {
+          self.memory.resize((pages + delta) * PAGE_SIZE, 0);
+          self.stack.push(pages as i32);
+        }
+      }
+      Instr::MemoryCopy => {
+        let length = self.pop() as u32 as usize;
+        let source = self.pop() as u32 as usize;
+        let destination = self.pop() as u32 as usize;
+        if source + length > self.memory.len() || destination + length > self.memory.len() {
+          return trap("out of bounds memory access");
+        }
+        self.memory.copy_within(source..source + length, destination);
+      }
+      Instr::MemoryFill => {
+        let length = self.pop() as u32 as usize;
+        let value = self.pop() as u8;
+        let destination = self.pop() as u32 as usize;
+        let bytes = self.memory.get_mut(destination..destination + length).map_or_else(|| trap("out of bounds memory access"), Ok)?;
+        bytes.fill(value);
+      }
+      Instr::I32Const(value) => self.stack.push(*value),
+      Instr::I32(NumOp::Eqz) => {
+        let value = self.pop();
+        self.stack.push((value == 0) as i32);
+      }
+      Instr::I32(op) => {
+        let b = self.pop();
+        let a = self.pop();
+        let result = numeric(*op, a, b).map_or_else(trap, Ok)?;
+        self.stack.push(result);
+      }
+    }
+    Ok(())
+  }
+
+  fn host_call(&mut self, host: HostFunction, args: &[i32]) -> Result<i32, Stop> {
+    match host {
+      HostFunction::ProcExit => Err(Stop::Exit(args[0])),
+      HostFunction::FdWrite => {
+        let (fd, iovs, count, written) = (args[0], args[1] as u32 as usize, args[2] as usize, args[3] as u32 as usize);
+        let mut total = 0;
+        for iov in 0..count {
+          let (start, length) = self.iov(iovs + 8 * iov)?;
+          let bytes = &self.memory[start..start + length];
+          let result = match fd {
+            1 => self.wasi.stdout.write_all(bytes),
+            2 => self.wasi.stdout.flush().and_then(|_| self.wasi.stderr.write_all(bytes)),
+            _ => return Ok(ERRNO_BADF),
+          };
+          if result.is_err() {
+            return Ok(ERRNO_IO);
+          }
+          total += length;
+        }
+        self.store(written, total as i32)?;
+        Ok(0)
+      }
+      HostFunction::FdRead => {
+        let (fd, iovs, count, read) = (args[0], args[1] as u32 as usize, args[2] as usize, args[3] as u32 as usize);
+        if fd != 0 {
+          return Ok(ERRNO_BADF);
+        }
+        let mut total = 0;
+        for iov in 0..count {
+          let (start, length) = self.iov(iovs + 8 * iov)?;
+          let Ok(bytes) = self.wasi.stdin.read(&mut self.memory[start..start + length]) else {
+            return Ok(ERRNO_IO);
+          };
+          total += bytes;
+          if bytes < length {
+            break;
+          }
+        }
+        self.store(read, total as i32)?;
+        Ok(0)
+      }
+    }
+  }
+
+  /// Start and length of the buffer described by a WASI `iovec`
+  fn iov(&self, address: usize) -> Result<(usize, usize), Stop> {
+    if address + 8 > self.memory.len() {
+      return trap("out of bounds memory access");
+    }
+    let (start, length) = (self.load(address) as u32 as usize, self.load(address + 4) as u32 as usize);
+    if start + length > self.memory.len() {
+      return trap("out of bounds memory access");
+    }
+    Ok((start, length))
+  }
+
+  fn store(&mut self, address: usize, value: i32) -> Result<(), Stop> {
+    let bytes = self.memory.get_mut(address..address + 4).map_or_else(|| trap("out of bounds memory access"), Ok)?;
+    bytes.copy_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+}
+
+fn arity(block_type: BlockType) -> usize {
+  match block_type {
+    BlockType::Empty => 0,
+    BlockType::I32 => 1,
+  }
+}
+
+/// Positions of the `end` of each block and of the `else` of each `if`, indexed by the position of the block
+fn block_ends(function: &Func) -> Result<(Vec<usize>, Vec<Option<usize>>), String> {
+  let mut ends = vec![0; function.body.len()];
+  let mut elses = vec![None; function.body.len()];
+  let mut open: Vec<usize> = Vec::new();
+  for (position, instruction) in function.body.iter().enumerate() {
+    match instruction {
+      Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => open.push(position),
+      Instr::Else => {
+        let start = *open.last().ok_or("else outside of an if")?;
+        elses[start] = Some(position);
+      }
+      Instr::End => {
+        if let Some(start) = open.pop() {
+          ends[start] = position;
+          if let Some(otherwise) = elses[start] {
+            ends[otherwise] = position;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  if !open.is_empty() || function.body.last() != Some(&Instr::End) {
+    return Err("function body is not terminated".to_string());
+  }
+  Ok((ends, elses))
+}
+
+/// Result of a binary operation, or the trap it causes
+fn numeric(op: NumOp, a: i32, b: i32) -> Result<i32, &'static str> {
+  let (ua, ub) = (a as u32, b as u32);
+  Ok(match op {
+    NumOp::Eqz => (b == 0) as i32,
+    NumOp::Eq => (a == b) as i32,
+    NumOp::Ne => (a != b) as i32,
+    NumOp::LtS => (a < b) as i32,
+    NumOp::LtU => (ua < ub) as i32,
+    NumOp::GtS => (a > b) as i32,
+    NumOp::GtU => (ua > ub) as i32,
+    NumOp::LeS => (a <= b) as i32,
+    NumOp::LeU => (ua <= ub) as i32,
+    NumOp::GeS => (a >= b) as i32,
+    NumOp::GeU => (ua >= ub) as i32,
+    NumOp::Add => a.wrapping_add(b),
+    NumOp::Sub => a.wrapping_sub(b),
+    NumOp::Mul => a.wrapping_mul(b),
+    NumOp::DivS | NumOp::DivU | NumOp::RemS | NumOp::RemU if b == 0 => return Err("integer divide by zero"),
+    NumOp::DivS if a == i32::MIN && b == -1 => return Err("integer overflow"),
+    NumOp::DivS => a / b,
+    NumOp::DivU => (ua / ub) as i32,
+    NumOp::RemS => a.wrapping_rem(b),
+    NumOp::RemU => (ua % ub) as i32,
+    NumOp::And => a & b,
+    NumOp::Or => a | b,
+    NumOp::Xor => a ^ b,
+    NumOp::Shl => a.wrapping_shl(ub),
+    NumOp::ShrS => a.wrapping_shr(ub),
+    NumOp::ShrU => ua.wrapping_shr(ub) as i32,
+  })
+}
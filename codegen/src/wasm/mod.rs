@@ -0,0 +1,355 @@
+pub mod binary;
+mod function;
+pub mod interp;
+pub mod model;
+pub mod text;
+
+use crate::wasm::function::FunctionEmitter;
+use ir::interp::Location;
+use ir::model::function::Function;
+use ir::model::instruction::{BinaryOp, Op, Terminator};
+use ir::model::module::{ClassInfo, Module};
+use ir::model::Constant;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashMap;
+
+/// Runtime spliced into every module, see `runtime/runtime.wat`
+pub const RUNTIME_SOURCE: &str = include_str!("../../runtime/runtime.wat");
+
+/// WASI functions for browsers and Node.js, to run the programs without a WebAssembly runtime, see `runtime/wasi.js`
+pub const WASI_SHIM_SOURCE: &str = include_str!("../../runtime/wasi.js");
+
+/// Size in bytes of the object header: class tag, object size and dispatch table, see `runtime/runtime.wat`
+const HEADER_SIZE: u32 = 12;
+
+/// Start of the data of the program, the runtime uses the memory below
+const DATA_START: u32 = 16384;
+
+/// Size in bytes of the shadow stack, which holds the object values of the running functions
+const STACK_SIZE: u32 = 1 << 20;
+
+/// Size in bytes of the heap when the program starts, it grows as needed
+const INITIAL_HEAP_SIZE: u32 = 1 << 20;
+
+/// Emits a WebAssembly module in the text format, which `assemble` turns into the binary format.
+///
+/// Objects live in linear memory, managed by the runtime's mark-sweep collector. Every function of the module is in
+/// the function table, at its index in the IR module, so dispatch tables and initialisers are table indices called
+/// with `call_indirect`. Methods take and return `i32` values, raw integers and booleans or object addresses.
+pub fn emit_module(module: &Module) -> String {
+  let layout = Layout::new(module);
+  let mut out = String::from("(module\n");
+  let max_params = module.functions.iter().map(|function| function.params.len()).max().unwrap_or(0).max(1);
+  for params in 1..=max_params {
+    out.push_str(&format!("  (type $fn{params} (func{} (result i32)))\n", " (param i32)".repeat(params)));
+  }
+
+  let pages = (layout.heap_start + INITIAL_HEAP_SIZE).div_ceil(65536);
+  out.push_str(&format!("  (memory (export \"memory\") {pages})\n"));
+  let globals = [
+    ("cool_class_table", DATA_START as usize),
+    ("cool_int_tag", class_tag(module, INT_CLASS_NAME)),
+    ("cool_bool_tag", class_tag(module, BOOL_CLASS_NAME)),
+    ("cool_string_tag", class_tag(module, STR_CLASS_NAME)),
+    ("cool_stack_base", layout.stack_base as usize),
+    ("cool_stack_limit", (layout.stack_base + STACK_SIZE) as usize),
+    ("cool_heap_start", layout.heap_start as usize),
+  ];
+  for (name, value) in globals {
+    out.push_str(&format!("  (global ${name} i32 (i32.const {value}))\n"));
+  }
+  let functions: Vec<String> = module.functions.iter().map(function_symbol).collect();
+  out.push_str(&format!("  (table {} funcref)\n", functions.len()));
+  out.push_str(&format!("  (elem (i32.const 0) {})\n", functions.join(" ")));
+  out.push_str(&format!("  (data (i32.const {DATA_START}) \"{}\")\n", escape(&layout.data)));
+
+  for function in module.functions.iter().filter(|function| !function.is_builtin()) {
+    out.push_str(&FunctionEmitter::new(&layout, module, function).emit());
+  }
+  emit_main(&mut out, module);
+  out.push_str(RUNTIME_SOURCE);
+  out.push_str(")\n");
+  out
+}
+
+/// Assembles the text of `emit_module` into the binary format
+pub fn assemble(text: &str) -> Result<Vec<u8>, String> {
+  Ok(binary::encode(&text::parse(text)?))
+}
+
+/// Name of a function in the text format: its IR name, or `cool_Class_method` for a built-in method of the runtime
+pub(crate) fn function_symbol(function: &Function) -> String {
+  if function.is_builtin() {
+    format!("$cool_{}", function.name.replace('.', "_"))
+  } else {
+    format!("${}", function.name)
+  }
+}
+
+/// Tag of a basic class, which the runtime reads to box values and compare objects
+fn class_tag(module: &Module, class_name: &str) -> usize {
+  module.class(class_name).map_or(usize::MAX, |class| class.tag)
+}
+
+/// `$cool_main`, called by the runtime: runs the entry function on a new object of the entry class
+fn emit_main(out: &mut String, module: &Module) {
+  let entry = module.class(&module.entry_class).expect("Entry class is verified");
+  let function = |name: &str| function_symbol(module.function(name).expect("Function is verified"));
+  out.push_str(&format!("  (func $cool_main\n    i32.const {}\n    call $cool_new\n", entry.tag));
+  if let Some(init) = &entry.init {
+    out.push_str(&format!("    call {}\n", function(init)));
+  }
+  out.push_str(&format!("    call {}\n    drop)\n", function(&module.entry_function)));
+}
+
+/// Static data of the program: the class table, dispatch tables, prototypes and string constants, all in linear memory
+/// from `DATA_START`, followed by the shadow stack and the heap
+pub(crate) struct Layout {
+  data: Vec<u8>,
+  strings: HashMap<String, u32>,
+  stack_base: u32,
+  heap_start: u32,
+}
+
+impl Layout {
+  fn new(module: &Module) -> Self {
+    let string_class = module.class(STR_CLASS_NAME).expect("String is a basic class");
+    let mut constants: Vec<&str> = module.classes.iter().map(|class| class.name.as_str()).collect();
+    for function in &module.functions {
+      for block in &function.blocks {
+        for instruction in &block.instructions {
+          if let Op::Const(Constant::Str(value)) = &instruction.op {
+            constants.push(value);
+          }
+        }
+      }
+    }
+    constants.push("");
+    let locations = error_locations(module);
+    constants.extend(locations.iter().map(String::as_str));
+
+    // Class table entries are the name, the prototype, the table index of the initialiser, or -1, and the flags of
+    // the attributes holding raw values, or 0
+    let class_table_size = 16 * module.classes.len() as u32;
+    let mut dispatch_tables = Vec::new();
+    let mut address = DATA_START + class_table_size;
+    for class in &module.classes {
+      dispatch_tables.push(address);
+      address += 4 * class.dispatch_table.len() as u32;
+    }
+    let mut raw_attributes = Vec::new();
+    for class in &module.classes {
+      if class.has_raw_attributes() {
+        raw_attributes.push(address);
+        address += (class.attributes.len() as u32).next_multiple_of(4);
+      } else {
+        raw_attributes.push(0);
+      }
+    }
+    let mut prototypes = Vec::new();
+    for class in &module.classes {
+      prototypes.push(address);
+      address += prototype_size(class);
+    }
+    let mut strings = HashMap::new();
+    let mut string_order = Vec::new();
+    for value in constants {
+      if !strings.contains_key(value) {
+        strings.insert(value.to_string(), address);
+        string_order.push(value);
+        address += string_size(value);
+      }
+    }
+
+    let function_index: HashMap<&str, i32> = module.functions.iter().enumerate().map(|(index, function)| (function.name.as_str(), index as i32)).collect();
+    let mut data = Data::default();
+    for ((class, prototype), raw) in module.classes.iter().zip(&prototypes).zip(&raw_attributes) {
+      data.word(strings[&class.name] as i32);
+      data.word(*prototype as i32);
+      data.word(class.init.as_ref().map_or(-1, |init| function_index[init.as_str()]));
+      data.word(*raw as i32);
+    }
+    for class in &module.classes {
+      for slot in &class.dispatch_table {
+        data.word(function_index[slot.function.as_str()]);
+      }
+    }
+    for (class, raw) in module.classes.iter().zip(&raw_attributes).filter(|(_, raw)| **raw != 0) {
+      data.bytes.extend(class.attributes.iter().map(|attribute| !attribute.attr_type.is_object() as u8));
+      data.align(*raw + class.attributes.len() as u32 - DATA_START);
+    }
+    for (class, prototype) in module.classes.iter().zip(&prototypes) {
+      data.word(class.tag as i32);
+      data.word(prototype_size(class) as i32);
+      data.word(dispatch_tables[class.tag] as i32);
+      match class.name.as_str() {
+        // The prototypes of the basic classes are also the default values of their attributes: 0, false and ""
+        INT_CLASS_NAME | BOOL_CLASS_NAME => data.word(0),
+        STR_CLASS_NAME => data.word(0),
+        _ => {
+          for attribute in &class.attributes {
+            let default = match Constant::default_of(attribute.attr_type.class_name()) {
+              Constant::Str(value) => strings[&value],
+              _ => 0,
+            };
+            data.word(default as i32);
+          }
+        }
+      }
+      data.align(*prototype + prototype_size(class) - DATA_START);
+    }
+    for value in string_order {
+      data.word(string_class.tag as i32);
+      data.word(string_size(value) as i32);
+      data.word(dispatch_tables[string_class.tag] as i32);
+      data.word(value.len() as i32);
+      data.bytes.extend_from_slice(value.as_bytes());
+      data.align(data.bytes.len() as u32 + 1);
+    }
+
+    let stack_base = (DATA_START + data.bytes.len() as u32).next_multiple_of(16);
+    Layout { data: data.bytes, strings, stack_base, heap_start: stack_base + STACK_SIZE }
+  }
+
+  /// Address of the `String` object of a constant
+  pub(crate) fn string(&self, value: &str) -> u32 {
+    self.strings[value]
+  }
+}
+
+/// Source locations of the instructions that can stop the program with an error, which the runtime prints
+fn error_locations(module: &Module) -> Vec<String> {
+  let mut locations = Vec::new();
+  for function in &module.functions {
+    for block in &function.blocks {
+      for instruction in &block.instructions {
+        if instruction.op.enters_runtime() || matches!(instruction.op, Op::CheckVoid(..) | Op::Binary(BinaryOp::Div, ..)) {
+          locations.push(Location::of(module, function, instruction.pos).to_string());
+        }
+      }
+      if let Terminator::Trap { pos, .. } = block.terminator {
+        locations.push(Location::of(module, function, pos).to_string());
+      }
+    }
+  }
+  locations
+}
+
+/// Size in bytes of a prototype, the empty string of `String` has a word for its terminating zero byte
+fn prototype_size(class: &ClassInfo) -> u32 {
+  match class.name.as_str() {
+    INT_CLASS_NAME | BOOL_CLASS_NAME => HEADER_SIZE + 4,
+    STR_CLASS_NAME => HEADER_SIZE + 8,
+    _ => HEADER_SIZE + 4 * class.attributes.len() as u32,
+  }
+}
+
+/// Size in bytes of a `String` object: header, length, characters and terminating zero byte, rounded up to words
+fn string_size(value: &str) -> u32 {
+  (HEADER_SIZE + 4 + value.len() as u32 + 1).next_multiple_of(4)
+}
+
+/// Bytes of the data segment, little-endian words
+#[derive(Default)]
+struct Data {
+  bytes: Vec<u8>,
+}
+
+impl Data {
+  fn word(&mut self, value: i32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  /// Pads with zero bytes to at least the length, then to whole words
+  fn align(&mut self, length: u32) {
+    let length = length.next_multiple_of(4) as usize;
+    self.bytes.resize(length.max(self.bytes.len()), 0);
+  }
+}
+
+/// Escapes bytes for a string of the text format, using hexadecimal escapes for anything but printable ASCII
+fn escape(bytes: &[u8]) -> String {
+  bytes.iter()
+      .map(|byte| match byte {
+        b'"' | b'\\' => format!("\\{}", *byte as char),
+        b' '..=b'~' => (*byte as char).to_string(),
+        _ => format!("\\{byte:02x}"),
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test::{interpret, lower_file, lower_test_program, programs};
+  use crate::wasm::{binary, emit_module, interp, text};
+  use ir::opt::{OptLevel, PassManager};
+  use std::env;
+  use std::fs;
+  use std::io::Write;
+  use std::process::{Command, Stdio};
+
+  /// Runs the module with `runtime/wasi.js` under Node.js, returning its output, error output and exit code
+  fn run_node(binary: &[u8], name: &str, input: &str) -> (String, String, Option<i32>) {
+    let path = env::temp_dir().join(format!("cool-test-{}-{name}.wasm", std::process::id()));
+    fs::write(&path, binary).expect("Couldn't write module");
+    let mut child = Command::new("node")
+        .arg("runtime/wasi.js")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Node.js must start");
+    child.stdin.take().expect("Input is piped").write_all(input.as_bytes()).expect("Couldn't write input");
+    let output = child.wait_with_output().expect("Node.js must finish");
+    let _ = fs::remove_file(&path);
+    (String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string(), output.status.code())
+  }
+
+  #[test]
+  fn test_programs_match_interpreter() {
+    // The in-repo interpreter always runs the modules, Node.js also does when installed
+    let has_node = Command::new("node").arg("--version").output().is_ok_and(|output| output.status.success());
+    if !has_node {
+      eprintln!("Skipping Node.js, only the WebAssembly interpreter runs the programs");
+    }
+
+    for program in programs() {
+      let module = lower_test_program(&program);
+      for level in [OptLevel::O0, OptLevel::O2] {
+        let mut optimised = module.clone();
+        PassManager::for_level(level).run(&mut optimised).unwrap_or_else(|e| panic!("{}: {e}", program.name()));
+        let expected = interpret(&optimised, &program.input);
+        let name = format!("{}-{level:?}", program.name());
+
+        let parsed = text::parse(&emit_module(&optimised)).unwrap_or_else(|e| panic!("{name}: {e}"));
+        let encoded = binary::encode(&parsed);
+        let decoded = binary::decode(&encoded).unwrap_or_else(|e| panic!("{name}: {e}"));
+        assert_eq!(decoded, parsed, "{name} changes through the binary format");
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let code = interp::run(&decoded, &mut program.input.as_bytes(), &mut stdout, &mut stderr).unwrap_or_else(|e| panic!("{name} trapped: {e}"));
+        let actual = (String::from_utf8_lossy(&stdout).to_string(), String::from_utf8_lossy(&stderr).to_string(), Some(code));
+        assert_eq!(actual, expected, "{name} behaves differently when run by the WebAssembly interpreter");
+        if has_node {
+          assert_eq!(run_node(&encoded, &name, &program.input), expected, "{name} behaves differently when run by Node.js");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_runtime_errors_match_interpreter() {
+    let mut module = lower_file("../test_resources/ir/errors.cl");
+    module.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+    let decoded = text::parse(&emit_module(&module)).expect("Module must parse");
+
+    // Running out of memory depends on the heap of each backend
+    for choice in ["1", "2", "3", "4", "5", "6"] {
+      let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+      let code = interp::run(&decoded, &mut choice.as_bytes(), &mut stdout, &mut stderr).unwrap_or_else(|e| panic!("Choice {choice} trapped: {e}"));
+      let actual = (String::from_utf8_lossy(&stdout).to_string(), String::from_utf8_lossy(&stderr).to_string(), Some(code));
+      assert_eq!(actual, interpret(&module, choice), "Choice {choice} fails differently when run by the WebAssembly interpreter");
+    }
+  }
+}
@@ -0,0 +1,250 @@
+/// A WebAssembly module, limited to what the backend and its runtime use: every value is an `i32`, there is one
+/// memory and one table of functions, and functions are imported from WASI only.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module {
+  pub types: Vec<FuncType>,
+  pub imports: Vec<Import>,
+  pub functions: Vec<Func>,
+  pub table: Option<u32>,  // number of elements
+  pub memory: Option<u32>, // initial number of 64 KiB pages
+  pub globals: Vec<Global>,
+  pub exports: Vec<Export>,
+  pub elements: Vec<Element>,
+  pub data: Vec<Data>,
+  pub names: Vec<String>, // name of each function, imports first, written to the `name` custom section
+}
+
+/// Signature of a function, as numbers of `i32` parameters and results
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FuncType {
+  pub params: usize,
+  pub results: usize,
+}
+
+/// Function imported from the host, e.g. `wasi_snapshot_preview1.fd_write`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+  pub module: String,
+  pub name: String,
+  pub type_index: u32,
+}
+
+/// Function with a body. Locals come after the parameters and the body ends with `End`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Func {
+  pub type_index: u32,
+  pub locals: usize,
+  pub body: Vec<Instr>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Global {
+  pub mutable: bool,
+  pub init: i32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExportKind {
+  Func,
+  Memory,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Export {
+  pub name: String,
+  pub kind: ExportKind,
+  pub index: u32,
+}
+
+/// Functions stored in the table from an offset, indices counting imports first
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+  pub offset: u32,
+  pub functions: Vec<u32>,
+}
+
+/// Bytes stored in memory from an offset when the module is instantiated
+#[derive(Debug, PartialEq, Clone)]
+pub struct Data {
+  pub offset: u32,
+  pub bytes: Vec<u8>,
+}
+
+/// Result of a block, `if` or `loop`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlockType {
+  Empty,
+  I32,
+}
+
+/// Alignment and offset of a memory access. The alignment is the log2 of the number of bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemArg {
+  pub align: u32,
+  pub offset: u32,
+}
+
+/// An instruction of the binary format, branches refer to enclosing blocks by depth
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instr {
+  Unreachable,
+  Nop,
+  Block(BlockType),
+  Loop(BlockType),
+  If(BlockType),
+  Else,
+  End,
+  Br(u32),
+  BrIf(u32),
+  BrTable(Vec<u32>, u32),
+  Return,
+  Call(u32),
+  CallIndirect(u32), // type index, on table 0
+  Drop,
+  Select,
+  LocalGet(u32),
+  LocalSet(u32),
+  LocalTee(u32),
+  GlobalGet(u32),
+  GlobalSet(u32),
+  I32Load(MemArg),
+  I32Load8U(MemArg),
+  I32Store(MemArg),
+  I32Store8(MemArg),
+  MemorySize,
+  MemoryGrow,
+  MemoryCopy,
+  MemoryFill,
+  I32Const(i32),
+  I32(NumOp),
+}
+
+/// Numeric `i32` instructions, unary ones take one operand and the others two
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumOp {
+  Eqz,
+  Eq,
+  Ne,
+  LtS,
+  LtU,
+  GtS,
+  GtU,
+  LeS,
+  LeU,
+  GeS,
+  GeU,
+  Add,
+  Sub,
+  Mul,
+  DivS,
+  DivU,
+  RemS,
+  RemU,
+  And,
+  Or,
+  Xor,
+  Shl,
+  ShrS,
+  ShrU,
+}
+
+impl NumOp {
+  pub const ALL: [NumOp; 24] = [
+    NumOp::Eqz,
+    NumOp::Eq,
+    NumOp::Ne,
+    NumOp::LtS,
+    NumOp::LtU,
+    NumOp::GtS,
+    NumOp::GtU,
+    NumOp::LeS,
+    NumOp::LeU,
+    NumOp::GeS,
+    NumOp::GeU,
+    NumOp::Add,
+    NumOp::Sub,
+    NumOp::Mul,
+    NumOp::DivS,
+    NumOp::DivU,
+    NumOp::RemS,
+    NumOp::RemU,
+    NumOp::And,
+    NumOp::Or,
+    NumOp::Xor,
+    NumOp::Shl,
+    NumOp::ShrS,
+    NumOp::ShrU,
+  ];
+
+  /// Name in the text format, without the `i32.` prefix
+  pub fn name(self) -> &'static str {
+    match self {
+      NumOp::Eqz => "eqz",
+      NumOp::Eq => "eq",
+      NumOp::Ne => "ne",
+      NumOp::LtS => "lt_s",
+      NumOp::LtU => "lt_u",
+      NumOp::GtS => "gt_s",
+      NumOp::GtU => "gt_u",
+      NumOp::LeS => "le_s",
+      NumOp::LeU => "le_u",
+      NumOp::GeS => "ge_s",
+      NumOp::GeU => "ge_u",
+      NumOp::Add => "add",
+      NumOp::Sub => "sub",
+      NumOp::Mul => "mul",
+      NumOp::DivS => "div_s",
+      NumOp::DivU => "div_u",
+      NumOp::RemS => "rem_s",
+      NumOp::RemU => "rem_u",
+      NumOp::And => "and",
+      NumOp::Or => "or",
+      NumOp::Xor => "xor",
+      NumOp::Shl => "shl",
+      NumOp::ShrS => "shr_s",
+      NumOp::ShrU => "shr_u",
+    }
+  }
+
+  /// Opcode in the binary format
+  pub fn opcode(self) -> u8 {
+    match self {
+      NumOp::Eqz => 0x45,
+      NumOp::Eq => 0x46,
+      NumOp::Ne => 0x47,
+      NumOp::LtS => 0x48,
+      NumOp::LtU => 0x49,
+      NumOp::GtS => 0x4A,
+      NumOp::GtU => 0x4B,
+      NumOp::LeS => 0x4C,
+      NumOp::LeU => 0x4D,
+      NumOp::GeS => 0x4E,
+      NumOp::GeU => 0x4F,
+      NumOp::Add => 0x6A,
+      NumOp::Sub => 0x6B,
+      NumOp::Mul => 0x6C,
+      NumOp::DivS => 0x6D,
+      NumOp::DivU => 0x6E,
+      NumOp::RemS => 0x6F,
+      NumOp::RemU => 0x70,
+      NumOp::And => 0x71,
+      NumOp::Or => 0x72,
+      NumOp::Xor => 0x73,
+      NumOp::Shl => 0x74,
+      NumOp::ShrS => 0x75,
+      NumOp::ShrU => 0x76,
+    }
+  }
+}
+
+impl Module {
+  /// Type of a function, counting imports first
+  pub fn func_type(&self, index: u32) -> FuncType {
+    let index = index as usize;
+    let type_index = match self.imports.get(index) {
+      Some(import) => import.type_index,
+      None => self.functions[index - self.imports.len()].type_index,
+    };
+    self.types[type_index as usize]
+  }
+}
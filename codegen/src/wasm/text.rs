@@ -0,0 +1,668 @@
+use crate::wasm::model::{BlockType, Data, Element, Export, ExportKind, Func, FuncType, Global, Import, Instr, MemArg, Module, NumOp};
+use std::collections::HashMap;
+
+/// Parses a module in the WebAssembly text format.
+///
+/// Only the subset written by the backend and `runtime/runtime.wat` is supported: instructions are in the flat
+/// (not folded) form, every value is an `i32` and types, functions, globals and labels may be named or numbered.
+pub fn parse(text: &str) -> Result<Module, String> {
+  let tokens = tokenize(text)?;
+  let mut position = 0;
+  let tree = read_sexp(&tokens, &mut position)?;
+  if position < tokens.len() {
+    return Err(format!("line {}: unexpected text after the module", tokens[position].line));
+  }
+  let fields = match &tree {
+    Sexp::List(items, _) if items.first().is_some_and(|item| item.is_atom("module")) => &items[1..],
+    _ => return Err(format!("line {}: expected (module ...)", tree.line())),
+  };
+  Assembler::default().assemble(fields)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+  Open,
+  Close,
+  Atom(String),
+  Str(Vec<u8>),
+}
+
+struct Located {
+  token: Token,
+  line: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Located>, String> {
+  let bytes = text.as_bytes();
+  let mut tokens = Vec::new();
+  let mut line = 1;
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'\n' => {
+        line += 1;
+        i += 1;
+      }
+      b' ' | b'\t' | b'\r' => i += 1,
+      b';' if bytes.get(i + 1) == Some(&b';') => {
+        while i < bytes.len() && bytes[i] != b'\n' {
+          i += 1;
+        }
+      }
+      b'(' if bytes.get(i + 1) == Some(&b';') => {
+        let start = line;
+        i += 2;
+        while i < bytes.len() && !(bytes[i] == b';' && bytes.get(i + 1) == Some(&b')')) {
+          line += (bytes[i] == b'\n') as usize;
+          i += 1;
+        }
+        if i >= bytes.len() {
+          return Err(format!("line {start}: unterminated block comment"));
+        }
+        i += 2;
+      }
+      b'(' => {
+        tokens.push(Located { token: Token::Open, line });
+        i += 1;
+      }
+      b')' => {
+        tokens.push(Located { token: Token::Close, line });
+        i += 1;
+      }
+      b'"' => {
+        let (string, end) = read_string(bytes, i + 1).map_err(|e| format!("line {line}: {e}"))?;
+        tokens.push(Located { token: Token::Str(string), line });
+        i = end;
+      }
+      _ => {
+        let start = i;
+        while i < bytes.len() && !matches!(bytes[i], b' ' | b'\t' | b'\r' | b'\n' | b'(' | b')' | b'"' | b';') {
+          i += 1;
+        }
+        tokens.push(Located { token: Token::Atom(text[start..i].to_string()), line });
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+/// Reads the bytes of a string from after its opening quote, returning them and the index after the closing quote
+fn read_string(bytes: &[u8], mut i: usize) -> Result<(Vec<u8>, usize), String> {
+  let mut string = Vec::new();
+  loop {
+    match bytes.get(i) {
+      None | Some(b'\n') => return Err("unterminated string".to_string()),
+      Some(b'"') => return Ok((string, i + 1)),
+      Some(b'\\') => {
+        let escape = *bytes.get(i + 1).ok_or("unterminated string")?;
+        i += 2;
+        match escape {
+          b'n' => string.push(b'\n'),
+          b't' => string.push(b'\t'),
+          b'r' => string.push(b'\r'),
+          b'"' | b'\'' | b'\\' => string.push(escape),
+          _ => {
+            let high = (escape as char).to_digit(16);
+            let low = bytes.get(i).and_then(|low| (*low as char).to_digit(16));
+            match (high, low) {
+              (Some(high), Some(low)) => string.push((high * 16 + low) as u8),
+              _ => return Err(format!("unknown escape \\{}", escape as char)),
+            }
+            i += 1;
+          }
+        }
+      }
+      Some(byte) => {
+        string.push(*byte);
+        i += 1;
+      }
+    }
+  }
+}
+
+/// A parenthesised list, an atom or a string
+#[derive(Debug)]
+enum Sexp {
+  List(Vec<Sexp>, usize),
+  Atom(String, usize),
+  Str(Vec<u8>, usize),
+}
+
+impl Sexp {
+  fn line(&self) -> usize {
+    match self {
+      Sexp::List(_, line) | Sexp::Atom(_, line) | Sexp::Str(_, line) => *line,
+    }
+  }
+
+  fn is_atom(&self, atom: &str) -> bool {
+    matches!(self, Sexp::Atom(value, _) if value == atom)
+  }
+
+  /// The items of a list starting with the keyword
+  fn list(&self, keyword: &str) -> Option<&[Sexp]> {
+    match self {
+      Sexp::List(items, _) if items.first().is_some_and(|item| item.is_atom(keyword)) => Some(&items[1..]),
+      _ => None,
+    }
+  }
+
+  fn error<T>(&self, message: &str) -> Result<T, String> {
+    Err(format!("line {}: {message}", self.line()))
+  }
+}
+
+fn read_sexp(tokens: &[Located], position: &mut usize) -> Result<Sexp, String> {
+  let Some(located) = tokens.get(*position) else {
+    return Err("unexpected end of text".to_string());
+  };
+  *position += 1;
+  match &located.token {
+    Token::Atom(atom) => Ok(Sexp::Atom(atom.clone(), located.line)),
+    Token::Str(string) => Ok(Sexp::Str(string.clone(), located.line)),
+    Token::Close => Err(format!("line {}: unexpected )", located.line)),
+    Token::Open => {
+      let mut items = Vec::new();
+      loop {
+        match tokens.get(*position) {
+          None => return Err(format!("line {}: unclosed (", located.line)),
+          Some(Located { token: Token::Close, .. }) => {
+            *position += 1;
+            return Ok(Sexp::List(items, located.line));
+          }
+          Some(_) => items.push(read_sexp(tokens, position)?),
+        }
+      }
+    }
+  }
+}
+
+/// Parses an integer in decimal or hexadecimal, with an optional sign and `_` separators
+fn parse_int(text: &str) -> Option<i64> {
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text.strip_prefix('+').unwrap_or(text)),
+  };
+  let digits = digits.replace('_', "");
+  let value = match digits.strip_prefix("0x") {
+    Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+    None => digits.parse::<i64>().ok()?,
+  };
+  Some(if negative { -value } else { value })
+}
+
+/// Indices of the names of one index space
+#[derive(Default)]
+struct Names {
+  indices: HashMap<String, u32>,
+}
+
+impl Names {
+  fn define(&mut self, item: &Sexp, index: u32) -> Result<(), String> {
+    if let Sexp::Atom(name, _) = item {
+      if name.starts_with('$') && self.indices.insert(name.clone(), index).is_some() {
+        return item.error(&format!("{name} is defined twice"));
+      }
+    }
+    Ok(())
+  }
+
+  /// Index of a name or a number
+  fn resolve(&self, item: &Sexp) -> Result<u32, String> {
+    match item {
+      Sexp::Atom(name, _) if name.starts_with('$') => self.indices.get(name).copied().map_or_else(|| item.error(&format!("unknown {name}")), Ok),
+      Sexp::Atom(number, _) => parse_int(number).and_then(|index| u32::try_from(index).ok()).map_or_else(|| item.error(&format!("expected an index, found {number}")), Ok),
+      _ => item.error("expected an index"),
+    }
+  }
+}
+
+#[derive(Default)]
+struct Assembler {
+  module: Module,
+  types: Names,
+  functions: Names,
+  globals: Names,
+}
+
+impl Assembler {
+  fn assemble(mut self, fields: &[Sexp]) -> Result<Module, String> {
+    // Imported functions come first in the index space, whatever the order of the fields
+    for field in fields {
+      if let Some(items) = field.list("type") {
+        let name = items.first().filter(|item| matches!(item, Sexp::Atom(..)));
+        let signature = items.get(name.is_some() as usize).and_then(|item| item.list("func")).map_or_else(|| field.error("expected (type (func ...))"), Ok)?;
+        let (func_type, _) = self.signature(signature, false)?;
+        if let Some(name) = name {
+          self.types.define(name, self.module.types.len() as u32)?;
+        }
+        self.module.types.push(func_type);
+      }
+    }
+    let mut function_count = 0;
+    for field in fields {
+      if let Some([Sexp::Str(..), Sexp::Str(..), function]) = field.list("import") {
+        let items = function.list("func").map_or_else(|| function.error("only functions can be imported"), Ok)?;
+        self.define_function(items, &mut function_count)?;
+      }
+    }
+    for field in fields {
+      if let Some(items) = field.list("func") {
+        self.define_function(items, &mut function_count)?;
+      }
+    }
+    for (index, items) in fields.iter().filter_map(|field| field.list("global")).enumerate() {
+      if let Some(name) = items.first() {
+        self.globals.define(name, index as u32)?;
+      }
+    }
+
+    let mut bodies = Vec::new();
+    for field in fields {
+      let Sexp::List(items, _) = field else {
+        return field.error("expected a module field");
+      };
+      let keyword = match items.first() {
+        Some(Sexp::Atom(keyword, _)) => keyword.as_str(),
+        _ => return field.error("expected a module field"),
+      };
+      let items = &items[1..];
+      match keyword {
+        "type" => {}
+        "import" => self.import(field, items)?,
+        "func" => bodies.push(items),
+        "memory" => self.memory(field, items)?,
+        "table" => self.table(field, items)?,
+        "global" => self.global(field, items)?,
+        "export" => self.export(field, items)?,
+        "elem" => self.element(field, items)?,
+        "data" => self.data(field, items)?,
+        _ => return field.error(&format!("unsupported module field {keyword}")),
+      }
+    }
+    for items in bodies {
+      let function = self.function(items)?;
+      self.module.functions.push(function);
+    }
+    Ok(self.module)
+  }
+
+  fn define_function(&mut self, items: &[Sexp], count: &mut u32) -> Result<(), String> {
+    let name = match items.first() {
+      Some(Sexp::Atom(name, _)) if name.starts_with('$') => name.clone(),
+      _ => format!("f{count}"),
+    };
+    if let Some(item) = items.first() {
+      self.functions.define(item, *count)?;
+    }
+    self.module.names.push(name.trim_start_matches('$').to_string());
+    *count += 1;
+    Ok(())
+  }
+
+  /// Parameters and results of a signature, with the names of the parameters in order
+  fn signature(&self, items: &[Sexp], allow_names: bool) -> Result<(FuncType, Vec<Option<String>>), String> {
+    let mut func_type = FuncType { params: 0, results: 0 };
+    let mut names = Vec::new();
+    for item in items {
+      if let Some(params) = item.list("param") {
+        match params {
+          [Sexp::Atom(name, _), value_type] if name.starts_with('$') && allow_names => {
+            expect_i32(value_type)?;
+            names.push(Some(name.clone()));
+          }
+          _ => {
+            for value_type in params {
+              expect_i32(value_type)?;
+              names.push(None);
+            }
+          }
+        }
+        func_type.params = names.len();
+      } else if let Some(results) = item.list("result") {
+        for value_type in results {
+          expect_i32(value_type)?;
+        }
+        func_type.results += results.len();
+      }
+    }
+    if func_type.results > 1 {
+      return items[0].error("functions return at most one value");
+    }
+    Ok((func_type, names))
+  }
+
+  /// Index of a function type, added to the module if no type has the signature
+  fn type_index(&mut self, func_type: FuncType) -> u32 {
+    match self.module.types.iter().position(|existing| *existing == func_type) {
+      Some(index) => index as u32,
+      None => {
+        self.module.types.push(func_type);
+        self.module.types.len() as u32 - 1
+      }
+    }
+  }
+
+  /// Type of a function from its `(type ...)` use or its inline signature
+  fn function_type(&mut self, items: &[Sexp]) -> Result<(u32, Vec<Option<String>>), String> {
+    let (func_type, names) = self.signature(items, true)?;
+    match items.iter().find_map(|item| item.list("type")) {
+      Some([type_use]) => {
+        let index = self.types.resolve(type_use)?;
+        let declared = *self.module.types.get(index as usize).map_or_else(|| type_use.error("unknown type"), Ok)?;
+        if !items.iter().any(|item| item.list("param").is_some() || item.list("result").is_some()) {
+          return Ok((index, vec![None; declared.params]));
+        }
+        if declared != func_type {
+          return type_use.error("signature does not match the type");
+        }
+        Ok((index, names))
+      }
+      Some(_) => items[0].error("expected (type index)"),
+      None => Ok((self.type_index(func_type), names)),
+    }
+  }
+
+  fn import(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let [Sexp::Str(module, _), Sexp::Str(name, _), function] = items else {
+      return field.error("expected (import \"module\" \"name\" (func ...))");
+    };
+    let signature = function.list("func").map_or_else(|| function.error("only functions can be imported"), Ok)?;
+    let (type_index, _) = self.function_type(signature)?;
+    self.module.imports.push(Import { module: utf8(module, field)?, name: utf8(name, field)?, type_index });
+    Ok(())
+  }
+
+  fn memory(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let mut pages = None;
+    for item in items {
+      match item {
+        Sexp::Atom(name, _) if name.starts_with('$') => {}
+        Sexp::Atom(number, _) => pages = parse_int(number).and_then(|pages| u32::try_from(pages).ok()),
+        _ => {
+          let [Sexp::Str(name, _)] = item.list("export").map_or_else(|| item.error("expected (export \"name\")"), Ok)? else {
+            return item.error("expected (export \"name\")");
+          };
+          self.module.exports.push(Export { name: utf8(name, item)?, kind: ExportKind::Memory, index: 0 });
+        }
+      }
+    }
+    self.module.memory = Some(pages.map_or_else(|| field.error("expected the number of pages"), Ok)?);
+    Ok(())
+  }
+
+  fn table(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let items: Vec<&Sexp> = items.iter().filter(|item| !matches!(item, Sexp::Atom(name, _) if name.starts_with('$'))).collect();
+    match items.as_slice() {
+      [Sexp::Atom(size, _), reference] if reference.is_atom("funcref") => {
+        self.module.table = Some(parse_int(size).and_then(|size| u32::try_from(size).ok()).map_or_else(|| field.error("expected the table size"), Ok)?);
+        Ok(())
+      }
+      _ => field.error("expected (table size funcref)"),
+    }
+  }
+
+  fn global(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let items = match items.first() {
+      Some(Sexp::Atom(name, _)) if name.starts_with('$') => &items[1..],
+      _ => items,
+    };
+    let [value_type, init] = items else {
+      return field.error("expected (global type (i32.const value))");
+    };
+    let mutable = match value_type.list("mut") {
+      Some([value_type]) => {
+        expect_i32(value_type)?;
+        true
+      }
+      _ => {
+        expect_i32(value_type)?;
+        false
+      }
+    };
+    let init = constant(init)? as i32;
+    self.module.globals.push(Global { mutable, init });
+    Ok(())
+  }
+
+  fn export(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let [Sexp::Str(name, _), Sexp::List(target, _)] = items else {
+      return field.error("expected (export \"name\" (func index))");
+    };
+    let (kind, index) = match target.as_slice() {
+      [kind, index] if kind.is_atom("func") => (ExportKind::Func, self.functions.resolve(index)?),
+      [kind, _] if kind.is_atom("memory") => (ExportKind::Memory, 0),
+      _ => return field.error("only functions and the memory can be exported"),
+    };
+    self.module.exports.push(Export { name: utf8(name, field)?, kind, index });
+    Ok(())
+  }
+
+  fn element(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let Some((offset, functions)) = items.split_first() else {
+      return field.error("expected (elem (i32.const offset) functions)");
+    };
+    let functions = functions.iter()
+        .filter(|item| !item.is_atom("func"))
+        .map(|function| self.functions.resolve(function))
+        .collect::<Result<Vec<u32>, String>>()?;
+    self.module.elements.push(Element { offset: constant(offset)? as u32, functions });
+    Ok(())
+  }
+
+  fn data(&mut self, field: &Sexp, items: &[Sexp]) -> Result<(), String> {
+    let Some((offset, strings)) = items.split_first() else {
+      return field.error("expected (data (i32.const offset) strings)");
+    };
+    let mut bytes = Vec::new();
+    for string in strings {
+      match string {
+        Sexp::Str(string, _) => bytes.extend_from_slice(string),
+        _ => return string.error("expected a string"),
+      }
+    }
+    self.module.data.push(Data { offset: constant(offset)? as u32, bytes });
+    Ok(())
+  }
+
+  fn function(&mut self, items: &[Sexp]) -> Result<Func, String> {
+    let items = match items.first() {
+      Some(Sexp::Atom(name, _)) if name.starts_with('$') => &items[1..],
+      _ => items,
+    };
+    let header = items.iter().take_while(|item| matches!(item, Sexp::List(..))).count();
+    let (header, body) = items.split_at(header);
+
+    let index = self.module.imports.len() + self.module.functions.len();
+    for item in header {
+      if let Some(export) = item.list("export") {
+        let [Sexp::Str(name, _)] = export else {
+          return item.error("expected (export \"name\")");
+        };
+        self.module.exports.push(Export { name: utf8(name, item)?, kind: ExportKind::Func, index: index as u32 });
+      }
+    }
+    let (type_index, params) = self.function_type(header)?;
+
+    let mut locals = Names::default();
+    let mut count = 0;
+    for name in &params {
+      if let Some(name) = name {
+        locals.indices.insert(name.clone(), count);
+      }
+      count += 1;
+    }
+    for item in header {
+      if let Some(declared) = item.list("local") {
+        match declared {
+          [name @ Sexp::Atom(id, _), value_type] if id.starts_with('$') => {
+            expect_i32(value_type)?;
+            locals.define(name, count)?;
+            count += 1;
+          }
+          _ => {
+            for value_type in declared {
+              expect_i32(value_type)?;
+              count += 1;
+            }
+          }
+        }
+      }
+    }
+
+    let body = self.instructions(body, &locals)?;
+    Ok(Func { type_index, locals: count as usize - params.len(), body })
+  }
+
+  fn instructions(&mut self, items: &[Sexp], locals: &Names) -> Result<Vec<Instr>, String> {
+    let mut body = Vec::new();
+    let mut labels: Vec<Option<String>> = Vec::new();
+    let mut i = 0;
+    let next = |i: &mut usize| -> Option<&Sexp> {
+      *i += 1;
+      items.get(*i - 1)
+    };
+    while let Some(item) = next(&mut i) {
+      let Sexp::Atom(name, _) = item else {
+        return item.error("folded instructions are not supported");
+      };
+      let immediate = |i: &mut usize| next(i).map_or_else(|| item.error(&format!("{name} needs an operand")), Ok);
+      let instruction = match name.as_str() {
+        "unreachable" => Instr::Unreachable,
+        "nop" => Instr::Nop,
+        "block" | "loop" | "if" => {
+          let label = match items.get(i) {
+            Some(Sexp::Atom(label, _)) if label.starts_with('$') => {
+              i += 1;
+              Some(label.clone())
+            }
+            _ => None,
+          };
+          let block_type = match items.get(i).and_then(|item| item.list("result")) {
+            Some(results) => {
+              i += 1;
+              match results {
+                [value_type] => expect_i32(value_type).map(|_| BlockType::I32)?,
+                _ => return item.error("blocks return one value at most"),
+              }
+            }
+            None => BlockType::Empty,
+          };
+          labels.push(label);
+          match name.as_str() {
+            "block" => Instr::Block(block_type),
+            "loop" => Instr::Loop(block_type),
+            _ => Instr::If(block_type),
+          }
+        }
+        "else" => Instr::Else,
+        "end" => {
+          if labels.pop().is_none() {
+            return item.error("end without a block");
+          }
+          Instr::End
+        }
+        "br" => Instr::Br(label_depth(&labels, immediate(&mut i)?)?),
+        "br_if" => Instr::BrIf(label_depth(&labels, immediate(&mut i)?)?),
+        "br_table" => {
+          let mut targets = Vec::new();
+          while let Some(Ok(depth)) = items.get(i).map(|target| label_depth(&labels, target)) {
+            targets.push(depth);
+            i += 1;
+          }
+          let default = targets.pop().map_or_else(|| item.error("br_table needs a default label"), Ok)?;
+          Instr::BrTable(targets, default)
+        }
+        "return" => Instr::Return,
+        "call" => Instr::Call(self.functions.resolve(immediate(&mut i)?)?),
+        "call_indirect" => {
+          let type_use = immediate(&mut i)?;
+          match type_use.list("type") {
+            Some([index]) => Instr::CallIndirect(self.types.resolve(index)?),
+            _ => return type_use.error("expected (type index)"),
+          }
+        }
+        "drop" => Instr::Drop,
+        "select" => Instr::Select,
+        "local.get" => Instr::LocalGet(locals.resolve(immediate(&mut i)?)?),
+        "local.set" => Instr::LocalSet(locals.resolve(immediate(&mut i)?)?),
+        "local.tee" => Instr::LocalTee(locals.resolve(immediate(&mut i)?)?),
+        "global.get" => Instr::GlobalGet(self.globals.resolve(immediate(&mut i)?)?),
+        "global.set" => Instr::GlobalSet(self.globals.resolve(immediate(&mut i)?)?),
+        "i32.load" | "i32.load8_u" | "i32.store" | "i32.store8" => {
+          let natural = if name.contains('8') { 0 } else { 2 };
+          let mut memarg = MemArg { align: natural, offset: 0 };
+          while let Some(Sexp::Atom(argument, _)) = items.get(i) {
+            if let Some(offset) = argument.strip_prefix("offset=") {
+              memarg.offset = parse_int(offset).and_then(|offset| u32::try_from(offset).ok()).map_or_else(|| item.error("bad offset"), Ok)?;
+            } else if let Some(align) = argument.strip_prefix("align=") {
+              let align = parse_int(align).filter(|align| [1, 2, 4].contains(align)).map_or_else(|| item.error("bad alignment"), Ok)?;
+              memarg.align = align.trailing_zeros();
+            } else {
+              break;
+            }
+            i += 1;
+          }
+          match name.as_str() {
+            "i32.load" => Instr::I32Load(memarg),
+            "i32.load8_u" => Instr::I32Load8U(memarg),
+            "i32.store" => Instr::I32Store(memarg),
+            _ => Instr::I32Store8(memarg),
+          }
+        }
+        "memory.size" => Instr::MemorySize,
+        "memory.grow" => Instr::MemoryGrow,
+        "memory.copy" => Instr::MemoryCopy,
+        "memory.fill" => Instr::MemoryFill,
+        "i32.const" => {
+          let value = immediate(&mut i)?;
+          match value {
+            Sexp::Atom(number, _) => Instr::I32Const(parse_int(number).filter(|value| (i32::MIN as i64..=u32::MAX as i64).contains(value)).map_or_else(|| value.error("bad i32 constant"), Ok)? as i32),
+            _ => return value.error("expected a number"),
+          }
+        }
+        _ => match name.strip_prefix("i32.").and_then(|op| NumOp::ALL.into_iter().find(|candidate| candidate.name() == op)) {
+          Some(op) => Instr::I32(op),
+          None => return item.error(&format!("unknown instruction {name}")),
+        },
+      };
+      body.push(instruction);
+    }
+    if !labels.is_empty() {
+      return Err("block without end".to_string());
+    }
+    body.push(Instr::End);
+    Ok(body)
+  }
+}
+
+/// Depth of a branch target, a label name or a number
+fn label_depth(labels: &[Option<String>], item: &Sexp) -> Result<u32, String> {
+  match item {
+    Sexp::Atom(name, _) if name.starts_with('$') => labels.iter().rev()
+        .position(|label| label.as_deref() == Some(name.as_str()))
+        .map_or_else(|| item.error(&format!("unknown label {name}")), |depth| Ok(depth as u32)),
+    Sexp::Atom(number, _) => parse_int(number).and_then(|depth| u32::try_from(depth).ok()).map_or_else(|| item.error("expected a label"), Ok),
+    _ => item.error("expected a label"),
+  }
+}
+
+/// Value of an `(i32.const value)` expression
+fn constant(item: &Sexp) -> Result<i64, String> {
+  match item.list("i32.const") {
+    Some([Sexp::Atom(number, _)]) => parse_int(number).map_or_else(|| item.error("bad i32 constant"), Ok),
+    _ => item.error("expected (i32.const value)"),
+  }
+}
+
+fn expect_i32(item: &Sexp) -> Result<(), String> {
+  if item.is_atom("i32") {
+    Ok(())
+  } else {
+    item.error("only i32 values are supported")
+  }
+}
+
+fn utf8(bytes: &[u8], item: &Sexp) -> Result<String, String> {
+  String::from_utf8(bytes.to_vec()).map_or_else(|_| item.error("names must be UTF-8"), Ok)
+}
@@ -0,0 +1,416 @@
+use crate::x86::{Assembly, StackMaps, StringTable, Symbols, HEADER_SIZE};
+use ir::analysis::liveness::Liveness;
+use ir::interp::Location;
+use ir::model::function::Function;
+use ir::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use ir::model::module::Module;
+use ir::model::{BlockId, Constant, Pos, Temp, Type};
+use std::collections::HashSet;
+
+/// Registers of the first arguments in the System V calling convention, the others are passed on the stack
+const ARG_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Offset of the stack slot of the temporary from the frame pointer
+fn slot_offset(temp: Temp) -> i64 {
+  -8 * (temp.index() as i64 + 1)
+}
+
+/// Emits the code of one function with a body
+pub(super) struct FunctionEmitter<'a> {
+  out: &'a mut Assembly,
+  strings: &'a mut StringTable,
+  stack_maps: &'a mut StackMaps,
+  symbols: &'a Symbols,
+  module: &'a Module,
+  function: &'a Function,
+  index: usize,                   // index of the function in the module, to make its local labels unique
+  label_count: usize,             // local labels made besides the blocks
+  live_after: Vec<HashSet<Temp>>, // temporaries live after each instruction of the current block
+}
+
+impl<'a> FunctionEmitter<'a> {
+  pub(super) fn new(
+    out: &'a mut Assembly,
+    strings: &'a mut StringTable,
+    stack_maps: &'a mut StackMaps,
+    symbols: &'a Symbols,
+    module: &'a Module,
+    function: &'a Function,
+    index: usize,
+  ) -> Self {
+    FunctionEmitter { out, strings, stack_maps, symbols, module, function, index, label_count: 0, live_after: Vec::new() }
+  }
+
+  pub(super) fn emit(&mut self) {
+    let frame_size = (8 * self.function.temps.len()).next_multiple_of(16);
+    self.out.label(self.symbols.function(&self.function.name));
+    self.out.ins("pushq %rbp");
+    self.out.ins("movq %rsp, %rbp");
+    if frame_size > 0 {
+      self.out.ins(&format!("subq ${frame_size}, %rsp"));
+    }
+    for (index, (_, param)) in self.function.params.iter().enumerate() {
+      match ARG_REGISTERS.get(index) {
+        Some(register) => self.store(register, *param),
+        None => {
+          // Above the saved frame pointer and the return address
+          self.out.ins(&format!("movq {}(%rbp), %rax", 16 + 8 * (index - ARG_REGISTERS.len())));
+          self.store("%rax", *param);
+        }
+      }
+    }
+
+    let liveness = Liveness::new(self.function);
+    for id in self.function.block_ids() {
+      self.out.label(&self.block_label(id));
+      self.live_after = liveness.live_after(self.function, id);
+      let block = self.function.block(id);
+      for (position, instruction) in block.instructions.iter().enumerate().filter(|(_, instruction)| !instruction.is_phi()) {
+        self.instruction(instruction, position);
+      }
+      self.terminator(id, &block.terminator);
+    }
+  }
+
+  fn slot(&self, temp: Temp) -> String {
+    format!("{}(%rbp)", slot_offset(temp))
+  }
+
+  fn load(&mut self, temp: Temp, register: &str) {
+    self.out.ins(&format!("movq {}, {register}", self.slot(temp)));
+  }
+
+  fn store(&mut self, register: &str, temp: Temp) {
+    self.out.ins(&format!("movq {register}, {}", self.slot(temp)));
+  }
+
+  /// Stores `%rax` in the destination, if the value is used
+  fn store_result(&mut self, instruction: &Instruction) {
+    if let Some(dest) = instruction.dest {
+      self.store("%rax", dest);
+    }
+  }
+
+  fn block_label(&self, id: BlockId) -> String {
+    format!(".Lf{}_{id}", self.index)
+  }
+
+  fn new_label(&mut self) -> String {
+    self.label_count += 1;
+    format!(".Lf{}_{}", self.index, self.label_count)
+  }
+
+  /// Labels the return address of the call just emitted, which may collect garbage, and records the stack slots of
+  /// the objects live across it. The result of the call is not stored yet, so its slot is left out.
+  fn safepoint(&mut self, instruction: &Instruction, position: usize) {
+    let label = self.new_label();
+    self.out.label(&label);
+    let mut roots: Vec<Temp> = self.live_after[position].iter()
+        .copied()
+        .filter(|temp| Some(*temp) != instruction.dest && self.function.temp_type(*temp).is_object())
+        .collect();
+    roots.sort();
+    self.stack_maps.add(label, roots.into_iter().map(slot_offset).collect());
+  }
+
+  fn instruction(&mut self, instruction: &Instruction, position: usize) {
+    if instruction.op.enters_runtime() {
+      let location = self.location(instruction.pos);
+      self.out.ins(&format!("leaq {location}(%rip), %rax"));
+      self.out.ins("movq %rax, cool_location(%rip)");
+    }
+    match &instruction.op {
+      Op::Const(constant) => {
+        match constant {
+          Constant::Int(value) => self.out.ins(&format!("movq ${value}, %rax")),
+          Constant::Bool(value) => self.out.ins(&format!("movq ${}, %rax", *value as i32)),
+          Constant::Str(value) => {
+            let label = self.strings.label(value);
+            self.out.ins(&format!("leaq {label}(%rip), %rax"));
+          }
+          Constant::Void => self.out.ins("xorl %eax, %eax"),
+        }
+        self.store_result(instruction);
+      }
+      Op::Copy(t) => {
+        self.load(*t, "%rax");
+        self.store_result(instruction);
+      }
+      Op::Phi(_) => panic!("Phis are assigned on the edges to their block"),
+      Op::Unary(UnaryOp::Neg, t) => {
+        self.load(*t, "%rax");
+        self.out.ins("negl %eax");
+        self.out.ins("movslq %eax, %rax");
+        self.store_result(instruction);
+      }
+      Op::Unary(UnaryOp::Not, t) => {
+        self.load(*t, "%rax");
+        self.out.ins("xorq $1, %rax");
+        self.store_result(instruction);
+      }
+      Op::Binary(op, a, b) => {
+        self.binary(*op, *a, *b, instruction.pos);
+        self.store_result(instruction);
+      }
+      Op::ObjEq(a, b) => {
+        self.load(*a, "%rdi");
+        self.load(*b, "%rsi");
+        self.out.ins("call cool_equals");
+        self.store_result(instruction);
+      }
+      Op::IsVoid(t) => {
+        self.out.ins(&format!("cmpq $0, {}", self.slot(*t)));
+        self.set_flag("e");
+        self.store_result(instruction);
+      }
+      Op::InstanceOf(t, class_name) => {
+        // tag - first tag <= last tag - first tag, compared unsigned so smaller tags fail too
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.load(*t, "%rax");
+        self.out.ins("movq (%rax), %rax");
+        self.out.ins(&format!("subq ${}, %rax", class.tag));
+        self.out.ins(&format!("cmpq ${}, %rax", class.max_descendant_tag - class.tag));
+        self.set_flag("be");
+        self.store_result(instruction);
+      }
+      Op::Box(t) => {
+        let function = if *self.function.temp_type(*t) == Type::Bool { "cool_box_bool" } else { "cool_box_int" };
+        self.load(*t, "%rdi");
+        self.out.ins(&format!("call {function}"));
+        self.safepoint(instruction, position);
+        self.store_result(instruction);
+      }
+      Op::Unbox(t) => {
+        self.load(*t, "%rax");
+        self.out.ins(&format!("movq {HEADER_SIZE}(%rax), %rax"));
+        self.store_result(instruction);
+      }
+      Op::New(class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        self.out.ins(&format!("movq ${}, %rdi", class.tag));
+        self.out.ins("call cool_new");
+        self.safepoint(instruction, position);
+        if let Some(init) = &class.init {
+          self.out.ins("movq %rax, %rdi");
+          self.out.ins(&format!("call {}", self.symbols.function(init)));
+          self.safepoint(instruction, position);
+        }
+        self.store_result(instruction);
+      }
+      Op::NewLike(t) => {
+        self.load(*t, "%rdi");
+        self.out.ins("call cool_new_like");
+        self.safepoint(instruction, position);
+        self.store_result(instruction);
+      }
+      Op::GetAttr { object, index } => {
+        self.load(*object, "%rax");
+        self.out.ins(&format!("movq {}(%rax), %rax", HEADER_SIZE + 8 * index));
+        self.store_result(instruction);
+      }
+      Op::SetAttr { object, index, value } => {
+        self.load(*object, "%rax");
+        self.load(*value, "%rcx");
+        self.out.ins(&format!("movq %rcx, {}(%rax)", HEADER_SIZE + 8 * index));
+        if self.function.temp_type(*value).is_object() {
+          self.write_barrier();
+        }
+      }
+      Op::CheckVoid(t, kind) => {
+        let ok = self.new_label();
+        self.out.ins(&format!("cmpq $0, {}", self.slot(*t)));
+        self.out.ins(&format!("jne {ok}"));
+        self.trap(*kind, *t, instruction.pos);
+        self.out.label(&ok);
+      }
+      Op::DebugVar { .. } => {}
+      Op::Call { function, args } => {
+        let symbol = self.symbols.function(function).to_string();
+        self.call(&format!("call {symbol}"), args, |_| {}, instruction, position);
+        self.store_result(instruction);
+      }
+      Op::Dispatch { receiver, slot, args, .. } => {
+        let ok = self.new_label();
+        self.out.ins(&format!("cmpq $0, {}", self.slot(*receiver)));
+        self.out.ins(&format!("jne {ok}"));
+        self.trap(TrapKind::DispatchOnVoid, *receiver, instruction.pos);
+        self.out.label(&ok);
+
+        let args: Vec<Temp> = std::iter::once(*receiver).chain(args.iter().copied()).collect();
+        let (receiver, slot) = (self.slot(*receiver), *slot);
+        self.call("call *%r11", &args, |out| {
+          out.ins(&format!("movq {receiver}, %rax"));
+          out.ins("movq 16(%rax), %rax");
+          out.ins(&format!("movq {}(%rax), %r11", 8 * slot));
+        }, instruction, position);
+        self.store_result(instruction);
+      }
+    }
+  }
+
+  /// Remembers the object in `%rax` when the value in `%rcx` it now points to is in the nursery and the object is
+  /// not, so the next minor collection finds the pointer. Without a generational collector the nursery is empty and
+  /// the first comparison always skips the call.
+  fn write_barrier(&mut self) {
+    let done = self.new_label();
+    for (register, skip) in [("%rcx", "jae"), ("%rax", "jb")] {
+      self.out.ins(&format!("movq {register}, %rdx"));
+      self.out.ins("subq cool_nursery_start(%rip), %rdx");
+      self.out.ins("cmpq cool_nursery_size(%rip), %rdx");
+      self.out.ins(&format!("{skip} {done}"));
+    }
+    self.out.ins("movq %rax, %rdi");
+    self.out.ins("call cool_remember");
+    self.out.label(&done);
+  }
+
+  /// Sets `%rax` to 1 if the condition of the last comparison holds, 0 otherwise
+  fn set_flag(&mut self, condition: &str) {
+    self.out.ins(&format!("set{condition} %al"));
+    self.out.ins("movzbq %al, %rax");
+  }
+
+  /// Computes the operation on raw values in `%rax`, wrapping on overflow like 32-bit integers
+  fn binary(&mut self, op: BinaryOp, a: Temp, b: Temp, pos: Pos) {
+    let (a, b) = (self.slot(a), self.slot(b));
+    match op {
+      BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+        let instruction = match op {
+          BinaryOp::Add => "addl",
+          BinaryOp::Sub => "subl",
+          _ => "imull",
+        };
+        self.out.ins(&format!("movl {a}, %eax"));
+        self.out.ins(&format!("{instruction} {b}, %eax"));
+        self.out.ins("movslq %eax, %rax");
+      }
+      BinaryOp::Div => {
+        // `idiv` faults on the minimum integer divided by -1, which wraps to itself like its negation
+        let (nonzero, divide, done) = (self.new_label(), self.new_label(), self.new_label());
+        self.out.ins(&format!("movl {b}, %ecx"));
+        self.out.ins("testl %ecx, %ecx");
+        self.out.ins(&format!("jne {nonzero}"));
+        let location = self.location(pos);
+        self.out.ins(&format!("leaq {location}(%rip), %rdi"));
+        self.out.ins("call cool_division_by_zero");
+        self.out.label(&nonzero);
+        self.out.ins(&format!("movl {a}, %eax"));
+        self.out.ins("cmpl $-1, %ecx");
+        self.out.ins(&format!("jne {divide}"));
+        self.out.ins("negl %eax");
+        self.out.ins(&format!("jmp {done}"));
+        self.out.label(&divide);
+        self.out.ins("cltd");
+        self.out.ins("idivl %ecx");
+        self.out.label(&done);
+        self.out.ins("movslq %eax, %rax");
+      }
+      BinaryOp::Lt | BinaryOp::Le | BinaryOp::Eq => {
+        self.out.ins(&format!("movq {a}, %rax"));
+        self.out.ins(&format!("cmpq {b}, %rax"));
+        self.set_flag(match op {
+          BinaryOp::Lt => "l",
+          BinaryOp::Le => "le",
+          _ => "e",
+        });
+      }
+    }
+  }
+
+  /// Passes the arguments and emits the call of the instruction, `load_target` loads the address of an indirect call
+  /// into `%r11`. The return address is a safepoint, before the arguments on the stack are popped.
+  fn call(&mut self, call: &str, args: &[Temp], load_target: impl FnOnce(&mut Assembly), instruction: &Instruction, position: usize) {
+    // Arguments on the stack are pushed last to first, keeping the stack aligned to 16 bytes
+    let stack_args = args.len().saturating_sub(ARG_REGISTERS.len());
+    let padding = if stack_args % 2 == 1 { 8 } else { 0 };
+    if padding > 0 {
+      self.out.ins(&format!("subq ${padding}, %rsp"));
+    }
+    for arg in args.iter().skip(ARG_REGISTERS.len()).rev() {
+      self.out.ins(&format!("pushq {}", self.slot(*arg)));
+    }
+    load_target(self.out);
+    for (arg, register) in args.iter().zip(ARG_REGISTERS) {
+      self.load(*arg, register);
+    }
+    self.out.ins(call);
+    self.safepoint(instruction, position);
+    if stack_args > 0 {
+      self.out.ins(&format!("addq ${}, %rsp", 8 * stack_args + padding));
+    }
+    // The built-in methods of the runtime return an `Int` in `%eax` only
+    if instruction.dest.is_some_and(|dest| *self.function.temp_type(dest) == Type::Int) {
+      self.out.ins("movslq %eax, %rax");
+    }
+  }
+
+  /// Calls the runtime to report the error, which does not return
+  fn trap(&mut self, kind: TrapKind, value: Temp, pos: Pos) {
+    let kind = match kind {
+      TrapKind::DispatchOnVoid => 0,
+      TrapKind::CaseOnVoid => 1,
+      TrapKind::CaseNoMatch => 2,
+    };
+    self.out.ins(&format!("movq ${kind}, %rdi"));
+    self.load(value, "%rsi");
+    let location = self.location(pos);
+    self.out.ins(&format!("leaq {location}(%rip), %rdx"));
+    self.out.ins("call cool_trap");
+  }
+
+  /// Label of the string giving the source location of the position in the runtime errors
+  fn location(&mut self, pos: Pos) -> String {
+    self.strings.label(&Location::of(self.module, self.function, pos).to_string())
+  }
+
+  fn terminator(&mut self, id: BlockId, terminator: &Terminator) {
+    match terminator {
+      Terminator::Jump(target) => self.jump(id, *target, true),
+      Terminator::Branch { cond, then_block, else_block } => {
+        let else_edge = self.new_label();
+        self.out.ins(&format!("cmpq $0, {}", self.slot(*cond)));
+        self.out.ins(&format!("je {else_edge}"));
+        self.jump(id, *then_block, false);
+        self.out.label(&else_edge);
+        self.jump(id, *else_block, true);
+      }
+      Terminator::Return(value) => {
+        self.load(*value, "%rax");
+        self.out.ins("leave");
+        self.out.ins("ret");
+      }
+      Terminator::Trap { kind, value, pos } => self.trap(*kind, *value, *pos),
+    }
+  }
+
+  /// Assigns the phis of the target the values coming from the block, then jumps to it unless it may fall through
+  /// to the next block. The values are all read before any phi is written, as a phi may be the operand of another.
+  fn jump(&mut self, from: BlockId, target: BlockId, falls_through: bool) {
+    let moves: Vec<(Temp, Temp)> = self.function.block(target).instructions.iter()
+        .map_while(|instruction| match (&instruction.op, instruction.dest) {
+          (Op::Phi(incoming), Some(dest)) => incoming.iter().find(|(block, _)| *block == from).map(|(_, value)| (dest, *value)),
+          _ => None,
+        })
+        .filter(|(dest, value)| dest != value)
+        .collect();
+
+    match moves.as_slice() {
+      [] => {}
+      [(dest, value)] => {
+        self.load(*value, "%rax");
+        self.store("%rax", *dest);
+      }
+      _ => {
+        for (_, value) in &moves {
+          self.out.ins(&format!("pushq {}", self.slot(*value)));
+        }
+        for (dest, _) in moves.iter().rev() {
+          self.out.ins(&format!("popq {}", self.slot(*dest)));
+        }
+      }
+    }
+    if !(falls_through && target.index() == from.index() + 1) {
+      self.out.ins(&format!("jmp {}", self.block_label(target)));
+    }
+  }
+}
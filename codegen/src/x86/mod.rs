@@ -0,0 +1,263 @@
+mod function;
+
+use crate::x86::function::FunctionEmitter;
+use ir::model::module::{ClassInfo, Module};
+use ir::model::Constant;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashMap;
+
+/// Size in bytes of the object header: class tag, object size and dispatch table, see `runtime/runtime.c`
+const HEADER_SIZE: usize = 24;
+
+/// Emits GNU assembly for x86-64 Linux.
+///
+/// Every temporary lives in its own stack slot and instructions go through `%rax`, `%rcx` and the argument
+/// registers, so no register allocation is needed. Functions follow the System V calling convention, which lets the
+/// runtime implement the built-in methods and call attribute initialisers. Raw integers and booleans are kept
+/// sign-extended to 64 bits.
+///
+/// As no register holds an object across a call, the stack slots are the only roots the garbage collector needs
+/// from generated code. Each call that may collect is followed by a label, and the stack maps tell the runtime which
+/// slots of the frame hold live objects at that return address.
+pub fn emit_module(module: &Module) -> String {
+  let mut strings = StringTable::default();
+  for class in &module.classes {
+    strings.label(&class.name);
+  }
+  let symbols = Symbols::new(module);
+  let mut stack_maps = StackMaps::default();
+
+  let mut out = Assembly::default();
+  out.line(".text");
+  for (index, function) in module.functions.iter().enumerate().filter(|(_, function)| !function.is_builtin()) {
+    FunctionEmitter::new(&mut out, &mut strings, &mut stack_maps, &symbols, module, function, index).emit();
+  }
+  emit_main(&mut out, module, &symbols);
+
+  out.line(".data");
+  emit_class_table(&mut out, module, &mut strings, &symbols);
+  stack_maps.emit(&mut out);
+  for class in &module.classes {
+    emit_prototype(&mut out, class, &mut strings);
+  }
+  strings.emit(&mut out, module);
+  out.line(".section .note.GNU-stack,\"\",@progbits");
+  out.text
+}
+
+/// Text of the assembly file
+#[derive(Default)]
+pub(crate) struct Assembly {
+  text: String,
+}
+
+impl Assembly {
+  /// Adds a directive, or an instruction when indented by the caller
+  pub(crate) fn line(&mut self, line: &str) {
+    self.text.push_str(line);
+    self.text.push('\n');
+  }
+
+  /// Adds an indented instruction
+  pub(crate) fn ins(&mut self, instruction: &str) {
+    self.text.push_str("  ");
+    self.line(instruction);
+  }
+
+  pub(crate) fn label(&mut self, label: &str) {
+    self.text.push_str(label);
+    self.line(":");
+  }
+}
+
+/// Assembly symbols of the functions. Functions with a body keep their IR name, e.g. `Main.main`, which cannot
+/// clash with a C symbol. The runtime implements the built-in methods as `cool_Class_method`.
+pub(crate) struct Symbols {
+  functions: HashMap<String, String>,
+}
+
+impl Symbols {
+  fn new(module: &Module) -> Self {
+    let functions = module.functions.iter()
+        .map(|function| {
+          let symbol = if function.is_builtin() { format!("cool_{}", function.name.replace('.', "_")) } else { function.name.clone() };
+          (function.name.clone(), symbol)
+        })
+        .collect();
+    Symbols { functions }
+  }
+
+  pub(crate) fn function(&self, name: &str) -> &str {
+    self.functions.get(name).unwrap_or_else(|| panic!("Function {name} is verified to exist"))
+  }
+}
+
+/// Stack slots holding live objects at the return address of each call that may collect garbage, in the order of
+/// the code so the runtime can binary search them. Calls with no live objects are left out.
+#[derive(Default)]
+pub(crate) struct StackMaps {
+  entries: Vec<(String, Vec<i64>)>,
+}
+
+impl StackMaps {
+  /// Records the offsets from the frame pointer of the live slots at the return address
+  pub(crate) fn add(&mut self, label: String, offsets: Vec<i64>) {
+    if !offsets.is_empty() {
+      self.entries.push((label, offsets));
+    }
+  }
+
+  /// `cool_stack_maps` holds the return address, first slot and slot count of each entry, `cool_stack_map_slots` the
+  /// offsets of all entries one after the other
+  fn emit(&self, out: &mut Assembly) {
+    out.line(".balign 8");
+    out.line(".globl cool_stack_map_count");
+    out.label("cool_stack_map_count");
+    out.ins(&format!(".quad {}", self.entries.len()));
+    out.line(".globl cool_stack_maps");
+    out.label("cool_stack_maps");
+    let mut first = 0;
+    for (label, offsets) in &self.entries {
+      out.ins(&format!(".quad {label}, {first}, {}", offsets.len()));
+      first += offsets.len();
+    }
+    out.line(".globl cool_stack_map_slots");
+    out.label("cool_stack_map_slots");
+    for (_, offsets) in &self.entries {
+      let offsets: Vec<String> = offsets.iter().map(i64::to_string).collect();
+      out.ins(&format!(".quad {}", offsets.join(", ")));
+    }
+  }
+}
+
+fn dispatch_table_symbol(class: &ClassInfo) -> String {
+  format!("{}._dispatch", class.name)
+}
+
+fn prototype_symbol(class: &ClassInfo) -> String {
+  format!("{}._prototype", class.name)
+}
+
+/// Tag of a basic class, which the runtime reads to box values and compare objects
+fn class_tag(module: &Module, class_name: &str) -> usize {
+  module.class(class_name).map_or(usize::MAX, |class| class.tag)
+}
+
+/// `cool_main`, called by the runtime: runs the entry function on a new object of the entry class
+fn emit_main(out: &mut Assembly, module: &Module, symbols: &Symbols) {
+  let entry = module.class(&module.entry_class).expect("Entry class is verified");
+  out.line(".globl cool_main");
+  out.label("cool_main");
+  out.ins("pushq %rbp");
+  out.ins("movq %rsp, %rbp");
+  out.ins(&format!("movq ${}, %rdi", entry.tag));
+  out.ins("call cool_new");
+  if let Some(init) = &entry.init {
+    out.ins("movq %rax, %rdi");
+    out.ins(&format!("call {}", symbols.function(init)));
+  }
+  out.ins("movq %rax, %rdi");
+  out.ins(&format!("call {}", symbols.function(&module.entry_function)));
+  out.ins("leave");
+  out.ins("ret");
+}
+
+/// Name, prototype, initialiser and raw attributes of each class in tag order, the dispatch tables and the tags of
+/// the basic classes
+fn emit_class_table(out: &mut Assembly, module: &Module, strings: &mut StringTable, symbols: &Symbols) {
+  out.line(".balign 8");
+  out.line(".globl cool_class_table");
+  out.label("cool_class_table");
+  for class in &module.classes {
+    let init = class.init.as_deref().map_or("0", |init| symbols.function(init));
+    let raw_attributes = if class.has_raw_attributes() { raw_attributes_symbol(class) } else { "0".to_string() };
+    out.ins(&format!(".quad {}, {}, {init}, {raw_attributes}", strings.label(&class.name), prototype_symbol(class)));
+  }
+
+  for (symbol, class_name) in [("cool_int_tag", INT_CLASS_NAME), ("cool_bool_tag", BOOL_CLASS_NAME), ("cool_string_tag", STR_CLASS_NAME)] {
+    out.line(&format!(".globl {symbol}"));
+    out.label(symbol);
+    out.ins(&format!(".quad {}", class_tag(module, class_name)));
+  }
+
+  for class in &module.classes {
+    out.label(&dispatch_table_symbol(class));
+    for slot in &class.dispatch_table {
+      out.ins(&format!(".quad {}", symbols.function(&slot.function)));
+    }
+  }
+
+  // One byte per attribute, which the collector reads to skip raw values
+  for class in module.classes.iter().filter(|class| class.has_raw_attributes()) {
+    let flags: Vec<String> = class.attributes.iter().map(|attribute| u8::from(!attribute.attr_type.is_object()).to_string()).collect();
+    out.label(&raw_attributes_symbol(class));
+    out.ins(&format!(".byte {}", flags.join(", ")));
+  }
+}
+
+fn raw_attributes_symbol(class: &ClassInfo) -> String {
+  format!("{}._raw_attributes", class.name)
+}
+
+/// Object copied by `new`, with the default value of each attribute: 0 for raw values and void, or the empty string
+fn emit_prototype(out: &mut Assembly, class: &ClassInfo, strings: &mut StringTable) {
+  let fields: Vec<String> = match class.name.as_str() {
+    INT_CLASS_NAME | BOOL_CLASS_NAME => vec!["0".to_string()],
+    // Length and terminating zero byte of the empty string
+    STR_CLASS_NAME => vec!["0".to_string(), "0".to_string()],
+    _ => class.attributes.iter()
+        .map(|attribute| match Constant::default_of(attribute.attr_type.class_name()) {
+          Constant::Str(value) => strings.label(&value),
+          _ => "0".to_string(),
+        })
+        .collect(),
+  };
+  out.line(".balign 8");
+  out.label(&prototype_symbol(class));
+  out.ins(&format!(".quad {}, {}, {}", class.tag, HEADER_SIZE + 8 * fields.len(), dispatch_table_symbol(class)));
+  for field in fields {
+    out.ins(&format!(".quad {field}"));
+  }
+}
+
+/// String constants of the program, emitted once each as `String` objects
+#[derive(Default)]
+pub(crate) struct StringTable {
+  labels: HashMap<String, usize>,
+  strings: Vec<String>,
+}
+
+impl StringTable {
+  pub(crate) fn label(&mut self, value: &str) -> String {
+    let index = *self.labels.entry(value.to_string()).or_insert_with(|| {
+      self.strings.push(value.to_string());
+      self.strings.len() - 1
+    });
+    format!(".Lstr{index}")
+  }
+
+  fn emit(&self, out: &mut Assembly, module: &Module) {
+    let string_class = module.class(STR_CLASS_NAME).expect("String is a basic class");
+    for (index, value) in self.strings.iter().enumerate() {
+      // Header, length, characters and terminating zero byte, rounded up to whole words
+      let size = (HEADER_SIZE + 8 + value.len() + 1).next_multiple_of(8);
+      out.line(".balign 8");
+      out.label(&format!(".Lstr{index}"));
+      out.ins(&format!(".quad {}, {size}, {}", string_class.tag, dispatch_table_symbol(string_class)));
+      out.ins(&format!(".quad {}", value.len()));
+      out.ins(&format!(".ascii \"{}\"", escape(value)));
+      out.ins(".byte 0");
+    }
+  }
+}
+
+/// Escapes the bytes of a string for `.ascii`, using octal escapes for anything but printable ASCII
+fn escape(value: &str) -> String {
+  value.bytes()
+      .map(|byte| match byte {
+        b'"' | b'\\' => format!("\\{}", byte as char),
+        b' '..=b'~' => (byte as char).to_string(),
+        _ => format!("\\{byte:03o}"),
+      })
+      .collect()
+}
@@ -0,0 +1,116 @@
+use crate::analysis::reverse_post_order;
+use crate::model::function::Function;
+use crate::model::BlockId;
+
+/// Dominator tree of the blocks reachable from the entry block,
+/// computed with the iterative algorithm of Cooper, Harvey and Kennedy
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dominators {
+  idom: Vec<Option<BlockId>>, // immediate dominator of each block, the entry block is its own
+  rpo_index: Vec<usize>,      // `usize::MAX` for unreachable blocks
+}
+
+impl Dominators {
+  pub fn new(function: &Function) -> Dominators {
+    let rpo = reverse_post_order(function);
+    let predecessors = function.predecessors();
+    let mut rpo_index = vec![usize::MAX; function.blocks.len()];
+    for (index, block) in rpo.iter().enumerate() {
+      rpo_index[block.index()] = index;
+    }
+
+    let mut idom: Vec<Option<BlockId>> = vec![None; function.blocks.len()];
+    if let Some(&entry) = rpo.first() {
+      idom[entry.index()] = Some(entry);
+    }
+
+    let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+      while a != b {
+        while rpo_index[a.index()] > rpo_index[b.index()] {
+          a = idom[a.index()].expect("Processed block must have a dominator");
+        }
+        while rpo_index[b.index()] > rpo_index[a.index()] {
+          b = idom[b.index()].expect("Processed block must have a dominator");
+        }
+      }
+      a
+    };
+
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for &block in rpo.iter().skip(1) {
+        let mut new_idom: Option<BlockId> = None;
+        for &predecessor in &predecessors[block.index()] {
+          if idom[predecessor.index()].is_none() {
+            continue;
+          }
+          new_idom = Some(match new_idom {
+            Some(current) => intersect(&idom, predecessor, current),
+            None => predecessor,
+          });
+        }
+        if new_idom != idom[block.index()] {
+          idom[block.index()] = new_idom;
+          changed = true;
+        }
+      }
+    }
+
+    Dominators { idom, rpo_index }
+  }
+
+  pub fn is_reachable(&self, block: BlockId) -> bool {
+    self.rpo_index[block.index()] != usize::MAX
+  }
+
+  /// Immediate dominator of the block, none for the entry block and unreachable blocks
+  pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+    self.idom[block.index()].filter(|&idom| idom != block)
+  }
+
+  /// Returns `true` if every path from the entry block to `b` goes through `a`. A block dominates itself.
+  pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+    if !self.is_reachable(a) || !self.is_reachable(b) {
+      return false;
+    }
+    let mut current = b;
+    loop {
+      if current == a {
+        return true;
+      }
+      match self.immediate_dominator(current) {
+        Some(idom) if self.rpo_index[idom.index()] >= self.rpo_index[a.index()] => current = idom,
+        _ => return false,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::analysis::dominators::Dominators;
+  use crate::analysis::reverse_post_order;
+  use crate::lower::lower_program;
+  use crate::model::BlockId;
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_case_dominators() {
+    let program = get_ast_from_file_path("../test_resources/ir/lowering.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    let function = module.function("Main.describe").expect("Function must exist");
+
+    assert_eq!(reverse_post_order(function), (0..8).map(BlockId).collect::<Vec<BlockId>>());
+
+    let dominators = Dominators::new(function);
+    let idoms: Vec<Option<u32>> = function.block_ids().map(|block| dominators.immediate_dominator(block).map(|b| b.0)).collect();
+    assert_eq!(idoms, vec![None, Some(0), Some(0), Some(2), Some(2), Some(4), Some(0), Some(4)]);
+
+    assert!(dominators.dominates(BlockId(2), BlockId(7)));
+    assert!(dominators.dominates(BlockId(5), BlockId(5)));
+    assert!(!dominators.dominates(BlockId(3), BlockId(6)));
+  }
+}
@@ -0,0 +1,120 @@
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::{BlockId, Temp};
+use std::collections::HashSet;
+
+/// Temporaries live at the end of each block, computed with the usual backward data flow.
+/// A phi reads its operand at the end of the predecessor it comes from and defines its value at the start of its block.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Liveness {
+  live_out: Vec<HashSet<Temp>>,
+}
+
+impl Liveness {
+  pub fn new(function: &Function) -> Liveness {
+    let count = function.blocks.len();
+    let mut live_in: Vec<HashSet<Temp>> = vec![HashSet::new(); count];
+    let mut live_out: Vec<HashSet<Temp>> = vec![HashSet::new(); count];
+
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for id in (0..count as u32).rev().map(BlockId) {
+        let mut out = HashSet::new();
+        for successor in function.block(id).terminator.successors() {
+          out.extend(live_in[successor.index()].iter().copied());
+          out.extend(phi_operands(function, id, successor));
+        }
+
+        let mut live = out.clone();
+        live.extend(function.block(id).terminator.operands());
+        for instruction in function.block(id).instructions.iter().rev() {
+          if let Some(dest) = instruction.dest {
+            live.remove(&dest);
+          }
+          if !instruction.is_phi() {
+            live.extend(instruction.op.operands());
+          }
+        }
+
+        if out != live_out[id.index()] || live != live_in[id.index()] {
+          live_out[id.index()] = out;
+          live_in[id.index()] = live;
+          changed = true;
+        }
+      }
+    }
+    Liveness { live_out }
+  }
+
+  pub fn live_out(&self, block: BlockId) -> &HashSet<Temp> {
+    &self.live_out[block.index()]
+  }
+
+  /// Temporaries live right after each instruction of the block, whose value is used later, in instruction order
+  pub fn live_after(&self, function: &Function, block: BlockId) -> Vec<HashSet<Temp>> {
+    let block_ref = function.block(block);
+    let mut live = self.live_out(block).clone();
+    live.extend(block_ref.terminator.operands());
+    let mut result = vec![HashSet::new(); block_ref.instructions.len()];
+    for (index, instruction) in block_ref.instructions.iter().enumerate().rev() {
+      result[index] = live.clone();
+      if let Some(dest) = instruction.dest {
+        live.remove(&dest);
+      }
+      if !instruction.is_phi() {
+        live.extend(instruction.op.operands());
+      }
+    }
+    result
+  }
+}
+
+/// Values the phis of the successor take when coming from the block
+fn phi_operands(function: &Function, block: BlockId, successor: BlockId) -> Vec<Temp> {
+  function.block(successor).instructions.iter()
+      .map_while(|instruction| match &instruction.op {
+        Op::Phi(incoming) => Some(incoming.iter().filter(|(from, _)| *from == block).map(|(_, value)| *value).collect::<Vec<Temp>>()),
+        _ => None,
+      })
+      .flatten()
+      .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::analysis::liveness::Liveness;
+  use crate::model::{BlockId, Temp};
+  use crate::parse::parse_function;
+  use std::collections::HashSet;
+
+  #[test]
+  fn test_loop_liveness() {
+    let function = parse_function("\
+fn Main.count(self %0 : Main) : Int {
+bb0:
+  %1 : int = const 0
+  %2 : int = const 10
+  jump bb1
+bb1:
+  %3 : int = phi [bb0: %1, bb2: %4]
+  %5 : bool = lt %3, %2
+  branch %5, bb2, bb3
+bb2:
+  %6 : int = const 1
+  %4 : int = add %3, %6
+  jump bb1
+bb3:
+  %7 : Int = box %3
+  ret %7
+}").expect("Function must parse");
+    let liveness = Liveness::new(&function);
+
+    let set = |temps: &[u32]| temps.iter().map(|t| Temp(*t)).collect::<HashSet<Temp>>();
+    assert_eq!(liveness.live_out(BlockId(0)), &set(&[1, 2]));
+    assert_eq!(liveness.live_out(BlockId(1)), &set(&[2, 3]));
+    assert_eq!(liveness.live_out(BlockId(2)), &set(&[2, 4]));
+    assert_eq!(liveness.live_out(BlockId(3)), &set(&[]));
+    assert_eq!(liveness.live_after(&function, BlockId(2)), vec![set(&[2, 3, 6]), set(&[2, 4])]);
+  }
+}
@@ -0,0 +1,37 @@
+pub mod dominators;
+pub mod liveness;
+
+use crate::model::function::Function;
+use crate::model::BlockId;
+
+/// Blocks reachable from the entry block in reverse post-order, so each block comes before its successors
+/// except along back edges
+pub fn reverse_post_order(function: &Function) -> Vec<BlockId> {
+  let mut visited = vec![false; function.blocks.len()];
+  let mut post_order: Vec<BlockId> = Vec::new();
+  if function.blocks.is_empty() {
+    return post_order;
+  }
+
+  // Iterative DFS keeping the index of the next successor to visit. Successors are visited last to first,
+  // so the first successor of a branch comes first in the order.
+  let mut stack: Vec<(BlockId, usize)> = vec![(BlockId::ENTRY, 0)];
+  visited[BlockId::ENTRY.index()] = true;
+  while let Some((block, next)) = stack.pop() {
+    let mut successors = function.block(block).terminator.successors();
+    successors.reverse();
+    match successors.get(next) {
+      Some(&successor) => {
+        stack.push((block, next + 1));
+        if !visited[successor.index()] {
+          visited[successor.index()] = true;
+          stack.push((successor, 0));
+        }
+      }
+      None => post_order.push(block),
+    }
+  }
+
+  post_order.reverse();
+  post_order
+}
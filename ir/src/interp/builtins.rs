@@ -0,0 +1,69 @@
+use crate::interp::{Interpreter, ObjectData, RuntimeError, SubstrError, Value};
+use crate::model::function::Function;
+use crate::runtime::{parse_int, read_line};
+
+impl Interpreter<'_> {
+  /// Runs a method of a basic class, the receiver is the first argument
+  pub(super) fn call_builtin(&mut self, function: &Function, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let receiver = &args[0];
+    let io_error = |e: std::io::Error| RuntimeError::Io(e.to_string());
+    match function.name.as_str() {
+      "Object.abort" => {
+        let tag = receiver.tag().expect("Receiver is checked for void");
+        Err(RuntimeError::Abort { class_name: self.module.classes[tag].name.clone() })
+      }
+      "Object.type_name" => {
+        let tag = receiver.tag().expect("Receiver is checked for void");
+        let name = self.module.classes[tag].name.clone();
+        Ok(self.string(name))
+      }
+      "Object.copy" => {
+        let Value::Object(object) = receiver else {
+          panic!("Receiver is checked for void");
+        };
+        let (tag, data) = {
+          let object = object.borrow();
+          (object.tag, object.data.clone())
+        };
+        Ok(self.new_value(tag, data))
+      }
+      "IO.out_string" => {
+        write!(self.output, "{}", args[1].as_str()).map_err(io_error)?;
+        Ok(receiver.clone())
+      }
+      "IO.out_int" => {
+        write!(self.output, "{}", args[1].as_int()).map_err(io_error)?;
+        Ok(receiver.clone())
+      }
+      "IO.in_string" => {
+        let line = read_line(self.input, self.output)?;
+        Ok(self.string(line))
+      }
+      "IO.in_int" => {
+        let line = read_line(self.input, self.output)?;
+        Ok(Value::Int(parse_int(&line)))
+      }
+      "String.length" => {
+        Ok(Value::Int(receiver.as_str().chars().count() as i32))
+      }
+      "String.concat" => {
+        let value = receiver.as_str() + &args[1].as_str();
+        Ok(self.string(value))
+      }
+      "String.substr" => {
+        let chars: Vec<char> = receiver.as_str().chars().collect();
+        let (start, length) = (args[1].as_int(), args[2].as_int());
+        if let Some(error) = SubstrError::check(start, length, chars.len()) {
+          return Err(RuntimeError::Substr { error, at: None });
+        }
+        let value = chars[start as usize..(start + length) as usize].iter().collect();
+        Ok(self.string(value))
+      }
+      name => panic!("Unknown built-in method {name}"),
+    }
+  }
+
+  fn string(&mut self, value: String) -> Value {
+    self.new_value(self.string_tag, ObjectData::Str(value))
+  }
+}
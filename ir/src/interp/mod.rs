@@ -0,0 +1,567 @@
+mod builtins;
+mod profile;
+mod value;
+
+pub use profile::{BranchHits, LineHits, MethodProfile, Profile};
+pub use value::{Object, ObjectData, ObjectRef, Value};
+pub use crate::runtime::{Location, RuntimeError, SubstrError, MAX_OBJECTS};
+
+use crate::model::function::Function;
+use crate::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use crate::model::module::Module;
+use crate::model::{BlockId, Constant, Pos, Temp, Type};
+use crate::runtime::{Heap, MAX_FRAMES};
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+
+/// Counters of the work done by a program, to compare optimisations
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Stats {
+  pub instructions: u64, // instructions and terminators executed
+  pub calls: u64,        // calls of functions with a body, including initialisers
+  pub allocations: u64,  // objects allocated, including boxes and strings built at runtime
+}
+
+impl Display for Stats {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} instructions, {} calls, {} allocations", self.instructions, self.calls, self.allocations)
+  }
+}
+
+/// Runs the entry function of the module on a new object of the entry class
+pub fn run(module: &Module, input: &mut dyn BufRead, output: &mut dyn Write) -> (Stats, Result<(), RuntimeError>) {
+  let mut interpreter = Interpreter::new(module, input, output);
+  let result = interpreter.run();
+  (interpreter.stats, result)
+}
+
+/// Runs the program like `run`, profiling its methods, allocations and coverage
+pub fn run_profiled(module: &Module, input: &mut dyn BufRead, output: &mut dyn Write) -> (Profile, Result<(), RuntimeError>) {
+  let mut interpreter = Interpreter::new(module, input, output);
+  interpreter.enable_profile();
+  let result = interpreter.run();
+  (interpreter.take_profile().expect("Profiling is enabled"), result)
+}
+
+/// Activation of a function with a body
+pub struct Frame<'a> {
+  function: &'a Function,
+  temps: Vec<Value>,
+  block: BlockId,
+  position: usize,                      // index of the next instruction, the terminator after the last one
+  dest: Option<Temp>,                   // temporary of the caller receiving the result
+  variables: Vec<(usize, &'a str, Temp)>, // source variables in scope, innermost last, see `Op::DebugVar`
+}
+
+impl<'a> Frame<'a> {
+  pub fn function(&self) -> &'a Function {
+    self.function
+  }
+
+  /// Instruction run next, none before the terminator of a block
+  pub fn next_instruction(&self) -> Option<&'a Instruction> {
+    self.function.block(self.block).instructions.get(self.position)
+  }
+
+  /// Position of the instruction run next, or of the last instruction of the block before its terminator
+  pub fn pos(&self) -> Pos {
+    let instructions = &self.function.block(self.block).instructions;
+    instructions.get(self.position).or(instructions.last()).map_or(self.function.pos, |instruction| instruction.pos)
+  }
+
+  /// Position of the call a frame below the innermost one is waiting for
+  pub fn call_pos(&self) -> Pos {
+    let instructions = &self.function.block(self.block).instructions;
+    self.position.checked_sub(1).and_then(|index| instructions.get(index)).map_or(self.function.pos, |instruction| instruction.pos)
+  }
+
+  /// `self`, the first parameter
+  pub fn receiver(&self) -> &Value {
+    &self.temps[self.function.params[0].1.index()]
+  }
+
+  /// Source variables in scope with their static types and values, innermost last. Only functions lowered with
+  /// debug information have some.
+  pub fn variables(&self) -> Vec<(&'a str, &'a Type, &Value)> {
+    self.variables.iter()
+        .map(|(_, name, temp)| (*name, self.function.temp_type(*temp), &self.temps[temp.index()]))
+        .collect()
+  }
+}
+
+/// Frames of a call run one step at a time, see `Interpreter::start`
+pub struct Execution<'a> {
+  frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Execution<'a> {
+  /// Frames of the functions running, innermost last
+  pub fn frames(&self) -> &[Frame<'a>] {
+    &self.frames
+  }
+}
+
+/// What an instruction asks the interpreter to do next
+enum Action<'a> {
+  Next,
+  Call { function: &'a Function, args: Vec<Value>, dest: Option<Temp> },
+}
+
+/// Executes IR directly, one instruction at a time
+pub struct Interpreter<'a> {
+  module: &'a Module,
+  functions: HashMap<&'a str, &'a Function>,
+  input: &'a mut dyn BufRead,
+  output: &'a mut dyn Write,
+  int_tag: usize,
+  bool_tag: usize,
+  string_tag: usize,
+  stats: Stats,
+  profile: Option<Profile>,
+  heap: Heap<RefCell<Object>>,
+}
+
+impl<'a> Interpreter<'a> {
+  pub fn new(module: &'a Module, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self {
+    let functions = module.functions.iter().map(|function| (function.name.as_str(), function)).collect();
+    let tag = |class_name: &str| module.class(class_name).map_or(usize::MAX, |class| class.tag);
+    Interpreter {
+      module,
+      functions,
+      input,
+      output,
+      int_tag: tag(INT_CLASS_NAME),
+      bool_tag: tag(BOOL_CLASS_NAME),
+      string_tag: tag(STR_CLASS_NAME),
+      stats: Stats::default(),
+      profile: None,
+      heap: Heap::new(MAX_OBJECTS),
+    }
+  }
+
+  /// Most objects alive at once before the program runs out of memory, `MAX_OBJECTS` by default
+  pub fn set_heap_limit(&mut self, objects: usize) {
+    self.heap.limit = objects;
+  }
+
+  pub fn stats(&self) -> Stats {
+    self.stats
+  }
+
+  /// Profiles the calls run from now on
+  pub fn enable_profile(&mut self) {
+    self.profile = Some(Profile::new(self.module));
+  }
+
+  /// The profile collected since `enable_profile`, ending the calls still running
+  pub fn take_profile(&mut self) -> Option<Profile> {
+    let mut profile = self.profile.take()?;
+    profile.finish();
+    Some(profile)
+  }
+
+  pub fn run(&mut self) -> Result<(), RuntimeError> {
+    let entry = self.module.class(&self.module.entry_class).expect("Entry class is verified");
+    let main = self.allocate(entry.tag);
+    let main = match &entry.init {
+      Some(init) => self.call_to_end(init, vec![main])?,
+      None => main,
+    };
+    let entry_function = self.module.entry_function.clone();
+    self.call_to_end(&entry_function, vec![main])?;
+    self.output.flush().map_err(|e| RuntimeError::Io(e.to_string()))
+  }
+
+  /// Calls the function and runs until it returns
+  pub fn call_to_end(&mut self, name: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let function = self.function(name);
+    if function.is_builtin() {
+      return self.run_builtin(function, args);
+    }
+
+    let mut execution = self.start(name, args);
+    loop {
+      if let Some(value) = self.step(&mut execution)? {
+        return Ok(value);
+      }
+    }
+  }
+
+  /// Calls a function with a body, without running it: `step` runs it
+  pub fn start(&mut self, name: &str, args: Vec<Value>) -> Execution<'a> {
+    let function = self.function(name);
+    assert!(!function.is_builtin(), "Built-in function {name} cannot be run one step at a time");
+    Execution { frames: vec![self.enter(function, args, None)] }
+  }
+
+  /// Runs the next instruction or terminator of the innermost frame. Returns the value of the call once its
+  /// outermost frame returns.
+  pub fn step(&mut self, execution: &mut Execution<'a>) -> Result<Option<Value>, RuntimeError> {
+    let frames = &mut execution.frames;
+    let frame = frames.last_mut().expect("A function is running");
+    let block = frame.function.block(frame.block);
+    self.stats.instructions += 1;
+
+    if let Some(instruction) = block.instructions.get(frame.position) {
+      frame.position += 1;
+      if let Op::DebugVar { name, var, value } = &instruction.op {
+        frame.variables.retain(|(other, _, _)| other != var);
+        if let Some(value) = value {
+          frame.variables.push((*var, name, *value));
+        }
+        return Ok(None);
+      }
+      if let Some(profile) = &mut self.profile {
+        profile.line(&frame.function.class_name, instruction.pos.0);
+      }
+      let action = self.execute(&mut frame.temps, instruction)
+          .and_then(|action| if self.heap.exhausted { Err(RuntimeError::OutOfMemory { at: None }) } else { Ok(action) })
+          .map_err(|error| error.located(|| Some(self.location(frame.function, instruction.pos))))?;
+      if let Action::Call { function, args, dest } = action {
+        if frames.len() >= MAX_FRAMES {
+          return Err(RuntimeError::StackOverflow);
+        }
+        frames.push(self.enter(function, args, dest));
+      }
+      return Ok(None);
+    }
+
+    match &block.terminator {
+      Terminator::Jump(target) => self.jump(frame, *target),
+      Terminator::Branch { cond, then_block, else_block } => {
+        let then = frame.temps[cond.index()].as_bool();
+        if let Some(profile) = &mut self.profile {
+          profile.branch(&frame.function.name, frame.block, then);
+        }
+        self.jump(frame, if then { *then_block } else { *else_block });
+      }
+      Terminator::Return(value) => {
+        if let Some(profile) = &mut self.profile {
+          profile.exit();
+        }
+        let value = frame.temps[value.index()].clone();
+        let dest = frame.dest;
+        frames.pop();
+        match frames.last_mut() {
+          Some(caller) => {
+            if let Some(dest) = dest {
+              caller.temps[dest.index()] = value;
+            }
+          }
+          None => return Ok(Some(value)),
+        }
+      }
+      Terminator::Trap { kind, value, pos } => {
+        let error = self.trap(*kind, &frame.temps[value.index()]);
+        return Err(error.located(|| Some(self.location(frame.function, *pos))));
+      }
+    }
+    Ok(None)
+  }
+
+  fn function(&self, name: &str) -> &'a Function {
+    self.functions.get(name).copied().unwrap_or_else(|| panic!("Function {name} is verified to exist"))
+  }
+
+  fn enter(&mut self, function: &'a Function, args: Vec<Value>, dest: Option<Temp>) -> Frame<'a> {
+    self.stats.calls += 1;
+    if let Some(profile) = &mut self.profile {
+      profile.enter(&function.name);
+    }
+    let mut temps = vec![Value::Void; function.temps.len()];
+    for ((_, param), arg) in function.params.iter().zip(args) {
+      temps[param.index()] = arg;
+    }
+    Frame { function, temps, block: BlockId::ENTRY, position: 0, dest, variables: Vec::new() }
+  }
+
+  /// Moves to the target block, assigning its phis the values coming from the current block
+  fn jump(&mut self, frame: &mut Frame, target: BlockId) {
+    if let Some(profile) = self.profile.as_mut().filter(|_| target.index() <= frame.block.index()) {
+      profile.jump_back();
+    }
+    let instructions = &frame.function.block(target).instructions;
+    let values: Vec<(Temp, Value)> = instructions.iter()
+        .map_while(|instruction| match (&instruction.op, instruction.dest) {
+          (Op::Phi(incoming), Some(dest)) => {
+            let (_, value) = incoming.iter().find(|(block, _)| *block == frame.block).expect("Phis have a value for each predecessor");
+            Some((dest, frame.temps[value.index()].clone()))
+          }
+          _ => None,
+        })
+        .collect();
+
+    frame.position = values.len();
+    for (dest, value) in values {
+      frame.temps[dest.index()] = value;
+    }
+    frame.block = target;
+  }
+
+  fn execute(&mut self, temps: &mut [Value], instruction: &'a Instruction) -> Result<Action<'a>, RuntimeError> {
+    let value = |t: &Temp| temps[t.index()].clone();
+    let result = match &instruction.op {
+      Op::Const(constant) => match constant {
+        Constant::Int(v) => Value::Int(*v),
+        Constant::Bool(v) => Value::Bool(*v),
+        Constant::Str(v) => Value::object(self.string_tag, ObjectData::Str(v.clone())),
+        Constant::Void => Value::Void,
+      },
+      Op::Copy(t) => value(t),
+      Op::Phi(_) => panic!("Phis are assigned when jumping to their block"),
+      Op::Unary(UnaryOp::Neg, t) => Value::Int(value(t).as_int().wrapping_neg()),
+      Op::Unary(UnaryOp::Not, t) => Value::Bool(!value(t).as_bool()),
+      Op::Binary(op, a, b) => binary(*op, &value(a), &value(b))?,
+      Op::ObjEq(a, b) => Value::Bool(value(a).equals(&value(b))),
+      Op::IsVoid(t) => Value::Bool(value(t).is_void()),
+      Op::InstanceOf(t, class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        let tag = value(t).tag().expect("Class tests are done on objects");
+        Value::Bool(class.tag <= tag && tag <= class.max_descendant_tag)
+      }
+      Op::Box(t) => match value(t) {
+        Value::Int(v) => self.new_value(self.int_tag, ObjectData::Int(v)),
+        Value::Bool(v) => self.new_value(self.bool_tag, ObjectData::Bool(v)),
+        other => panic!("Only raw values are boxed, found {other:?}"),
+      },
+      Op::Unbox(t) => match value(t) {
+        Value::Object(object) => match object.borrow().data {
+          ObjectData::Int(v) => Value::Int(v),
+          ObjectData::Bool(v) => Value::Bool(v),
+          ref data => panic!("Only Int and Bool objects are unboxed, found {data:?}"),
+        },
+        other => panic!("Only objects are unboxed, found {other:?}"),
+      },
+      Op::New(class_name) => {
+        let class = self.module.class(class_name).expect("Class is verified");
+        return Ok(self.new_object(class.tag, instruction.dest, temps));
+      }
+      Op::NewLike(t) => {
+        let tag = value(t).tag().expect("new SELF_TYPE is done on self");
+        return Ok(self.new_object(tag, instruction.dest, temps));
+      }
+      Op::GetAttr { object, index } => match value(object) {
+        Value::Object(object) => match &object.borrow().data {
+          ObjectData::Attributes(attributes) => attributes[*index].clone(),
+          data => panic!("Only objects with attributes have attributes, found {data:?}"),
+        },
+        other => panic!("Attributes are read from self, found {other:?}"),
+      },
+      Op::SetAttr { object, index, value: new_value } => {
+        let new_value = value(new_value);
+        match value(object) {
+          Value::Object(object) => match &mut object.borrow_mut().data {
+            ObjectData::Attributes(attributes) => attributes[*index] = new_value,
+            data => panic!("Only objects with attributes have attributes, found {data:?}"),
+          },
+          other => panic!("Attributes are written to self, found {other:?}"),
+        }
+        return Ok(Action::Next);
+      }
+      Op::CheckVoid(t, kind) => {
+        let checked = value(t);
+        if checked.is_void() {
+          return Err(self.trap(*kind, &checked));
+        }
+        return Ok(Action::Next);
+      }
+      Op::Call { function, args } => {
+        let args = args.iter().map(value).collect();
+        return self.call(self.function(function), args, instruction.dest, temps);
+      }
+      Op::Dispatch { receiver, slot, args, .. } => {
+        let receiver = value(receiver);
+        let tag = receiver.tag().ok_or(RuntimeError::DispatchOnVoid { at: None })?;
+        let target = &self.module.classes[tag].dispatch_table[*slot].function;
+        let args = std::iter::once(receiver).chain(args.iter().map(value)).collect();
+        return self.call(self.function(target), args, instruction.dest, temps);
+      }
+      Op::DebugVar { .. } => panic!("Debug information is read by `step`"),
+    };
+
+    if let Some(dest) = instruction.dest {
+      temps[dest.index()] = result;
+    }
+    Ok(Action::Next)
+  }
+
+  /// Built-in methods run at once, other functions get a new frame
+  fn call(&mut self, function: &'a Function, args: Vec<Value>, dest: Option<Temp>, temps: &mut [Value]) -> Result<Action<'a>, RuntimeError> {
+    if !function.is_builtin() {
+      return Ok(Action::Call { function, args, dest });
+    }
+    let result = self.run_builtin(function, args)?;
+    if let Some(dest) = dest {
+      temps[dest.index()] = result;
+    }
+    Ok(Action::Next)
+  }
+
+  fn run_builtin(&mut self, function: &'a Function, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if let Some(profile) = &mut self.profile {
+      profile.enter(&function.name);
+    }
+    let result = self.call_builtin(function, args)?;
+    if let Some(profile) = &mut self.profile {
+      profile.exit();
+    }
+    Ok(result)
+  }
+
+  /// Allocates the object, then runs the initialiser of its class which returns it
+  fn new_object(&mut self, tag: usize, dest: Option<Temp>, temps: &mut [Value]) -> Action<'a> {
+    let object = self.allocate(tag);
+    match &self.module.classes[tag].init {
+      Some(init) => Action::Call { function: self.function(init), args: vec![object], dest },
+      None => {
+        if let Some(dest) = dest {
+          temps[dest.index()] = object;
+        }
+        Action::Next
+      }
+    }
+  }
+
+  /// A new object of the class with default attribute values
+  pub fn allocate(&mut self, tag: usize) -> Value {
+    let data = match tag {
+      _ if tag == self.int_tag => ObjectData::Int(0),
+      _ if tag == self.bool_tag => ObjectData::Bool(false),
+      _ if tag == self.string_tag => ObjectData::Str(String::new()),
+      _ => {
+        let attributes = self.module.classes[tag].attributes.iter()
+            .map(|attribute| match Constant::default_of(attribute.attr_type.class_name()) {
+              Constant::Int(v) => Value::Int(v),
+              Constant::Bool(v) => Value::Bool(v),
+              Constant::Str(v) => Value::object(self.string_tag, ObjectData::Str(v)),
+              Constant::Void => Value::Void,
+            })
+            .collect();
+        ObjectData::Attributes(attributes)
+      }
+    };
+    self.new_value(tag, data)
+  }
+
+  /// A new object allocated by the program, counted in the statistics, the profile and the heap
+  fn new_value(&mut self, tag: usize, data: ObjectData) -> Value {
+    self.stats.allocations += 1;
+    if let Some(profile) = &mut self.profile {
+      profile.allocation(tag);
+    }
+    let value = Value::object(tag, data);
+    if let Value::Object(object) = &value {
+      self.heap.register(object);
+    }
+    value
+  }
+
+  fn location(&self, function: &Function, pos: Pos) -> Location {
+    Location::of(self.module, function, pos)
+  }
+
+  fn trap(&self, kind: TrapKind, value: &Value) -> RuntimeError {
+    match kind {
+      TrapKind::DispatchOnVoid => RuntimeError::DispatchOnVoid { at: None },
+      TrapKind::CaseOnVoid => RuntimeError::CaseOnVoid { at: None },
+      TrapKind::CaseNoMatch => {
+        let class_name = value.tag().map_or("Void".to_string(), |tag| self.module.classes[tag].name.clone());
+        RuntimeError::CaseNoMatch { class_name, at: None }
+      }
+    }
+  }
+}
+
+fn binary(op: BinaryOp, a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+  if op == BinaryOp::Eq {
+    return Ok(Value::Bool(a.equals(b)));
+  }
+  let (a, b) = (a.as_int(), b.as_int());
+  Ok(match op {
+    BinaryOp::Add => Value::Int(a.wrapping_add(b)),
+    BinaryOp::Sub => Value::Int(a.wrapping_sub(b)),
+    BinaryOp::Mul => Value::Int(a.wrapping_mul(b)),
+    BinaryOp::Div if b == 0 => return Err(RuntimeError::DivisionByZero { at: None }),
+    BinaryOp::Div => Value::Int(a.wrapping_div(b)),
+    BinaryOp::Lt => Value::Bool(a < b),
+    BinaryOp::Le => Value::Bool(a <= b),
+    BinaryOp::Eq => unreachable!("Handled above"),
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use crate::interp::{run, run_profiled, BranchHits, Interpreter, LineHits, RuntimeError};
+  use crate::lower::lower_program;
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  fn run_file(path: &str, input: &str) -> (String, Result<(), RuntimeError>) {
+    let program = get_ast_from_file_path(path).expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    let mut output: Vec<u8> = Vec::new();
+    let (_, result) = run(&module, &mut input.as_bytes(), &mut output);
+    (String::from_utf8(output).expect("Output must be UTF-8"), result)
+  }
+
+  #[test]
+  fn test_run_programs() {
+    assert_eq!(run_file("../test_resources/programs/hello_world.cl", ""), ("Hello, World.\n".to_string(), Ok(())));
+    assert_eq!(run_file("../test_resources/ir/lowering.cl", ""), ("55".to_string(), Ok(())));
+
+    let (output, result) = run_file("../test_resources/programs/palindrome.cl", "racecar\n");
+    assert_eq!(output, "enter a string\nthat was a palindrome\n");
+    assert_eq!(result, Ok(()));
+  }
+
+  #[test]
+  fn test_runtime_errors() {
+    let program = get_ast_from_file_path("../test_resources/ir/errors.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let mut module = lower_program(&checked);
+    module.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+
+    let error = |choice: &str| {
+      let (mut input, mut output) = (choice.as_bytes(), Vec::new());
+      let mut interpreter = Interpreter::new(&module, &mut input, &mut output);
+      interpreter.set_heap_limit(10_000);
+      interpreter.run().expect_err("Program must fail").to_string()
+    };
+    assert_eq!(error("1"), "errors.cl:18: Dispatch to void.");
+    assert_eq!(error("2"), "errors.cl:19: Match on void in case statement.");
+    assert_eq!(error("3"), "errors.cl:20: No match in case statement for Class Int");
+    assert_eq!(error("4"), "errors.cl:21: Length to substr too long");
+    assert_eq!(error("5"), "errors.cl:22: Index to substr is negative");
+    assert_eq!(error("6"), "errors.cl:23: Division by zero.");
+    assert_eq!(error("7"), "errors.cl:24: Out of memory");
+  }
+
+  #[test]
+  fn test_profile() {
+    let program = get_ast_from_file_path("../test_resources/ir/profile.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    let (mut input, mut output) = ("".as_bytes(), Vec::new());
+    let (profile, result) = run_profiled(&module, &mut input, &mut output);
+    assert_eq!(result, Ok(()));
+    assert_eq!(String::from_utf8(output).expect("Output must be UTF-8"), "5\nMain");
+
+    let calls: Vec<(&str, u64)> = profile.methods().iter().map(|method| (method.function, method.calls)).collect();
+    assert!(calls.contains(&("Main.fib", 15)) && calls.contains(&("IO.out_int", 1)) && calls.len() == 7, "{calls:?}");
+    assert_eq!(profile.allocations(), [("String", 2), ("Main", 1)]);
+
+    let line = |line, hits| LineHits { class_name: "Main", line, hits };
+    assert_eq!(profile.lines(&module), [line(3, 15), line(7, 0), line(12, 1), line(13, 1)]);
+    assert_eq!(profile.branches(&module), [BranchHits { class_name: "Main", function: "Main.fib", line: 3, taken: [8, 7] }]);
+
+    let folded = profile.folded();
+    let stacks: Vec<&str> = folded.lines().filter_map(|line| line.rsplit_once(' ')).map(|(stack, _)| stack).collect();
+    assert_eq!(stacks[..3], ["Main._init", "Main.main", "Main.main;Main.fib"]);
+    assert!(stacks.contains(&"Main.main;Main.fib;Main.fib;Main.fib;Main.fib;Main.fib"));
+    assert!(stacks.contains(&"Main.main;String.concat"));
+  }
+}
@@ -0,0 +1,251 @@
+use crate::model::function::Function;
+use crate::model::instruction::Terminator;
+use crate::model::module::Module;
+use crate::model::BlockId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// A function called from the functions on the path from the root of the call tree
+struct CallNode {
+  function: String, // empty for the root
+  parent: usize,
+  children: Vec<usize>,
+  calls: u64,
+  time: Duration, // including the time of the children
+}
+
+/// A call running
+struct Activation {
+  node: usize,
+  start: Instant,
+  line: u32, // line last counted, 0 after a jump back
+}
+
+/// Calls, time, allocations and coverage of a run, collected once `Interpreter::enable_profile` is called
+pub struct Profile {
+  class_names: Vec<String>, // by tag
+  nodes: Vec<CallNode>,     // call tree, the root first
+  stack: Vec<Activation>,
+  allocations: Vec<u64>,                               // objects allocated, by class tag
+  lines: HashMap<String, HashMap<u32, u64>>,           // times each line of a class started running
+  branches: HashMap<String, HashMap<BlockId, [u64; 2]>>, // times the branch ending a block of a function went each way
+}
+
+/// Calls and time of a method over the run
+#[derive(Debug, PartialEq, Clone)]
+pub struct MethodProfile<'a> {
+  pub function: &'a str,
+  pub calls: u64,
+  pub inclusive: Duration, // time of the outermost calls, so recursion is counted once
+  pub exclusive: Duration, // time not spent in the methods it called
+}
+
+/// Times a line with code started running
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LineHits<'a> {
+  pub class_name: &'a str,
+  pub line: u32,
+  pub hits: u64,
+}
+
+/// Times a branch went each way, both 0 if it was never reached
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BranchHits<'a> {
+  pub class_name: &'a str,
+  pub function: &'a str,
+  pub line: u32, // line of the condition
+  pub taken: [u64; 2],
+}
+
+impl Profile {
+  pub fn new(module: &Module) -> Self {
+    let root = CallNode { function: String::new(), parent: 0, children: Vec::new(), calls: 0, time: Duration::ZERO };
+    Profile {
+      class_names: module.classes.iter().map(|class| class.name.clone()).collect(),
+      nodes: vec![root],
+      stack: Vec::new(),
+      allocations: vec![0; module.classes.len()],
+      lines: HashMap::new(),
+      branches: HashMap::new(),
+    }
+  }
+
+  pub(super) fn enter(&mut self, function: &str) {
+    let parent = self.stack.last().map_or(0, |activation| activation.node);
+    let existing = self.nodes[parent].children.iter().copied().find(|&child| self.nodes[child].function == function);
+    let node = existing.unwrap_or_else(|| {
+      let node = self.nodes.len();
+      self.nodes.push(CallNode { function: function.to_string(), parent, children: Vec::new(), calls: 0, time: Duration::ZERO });
+      self.nodes[parent].children.push(node);
+      node
+    });
+    self.nodes[node].calls += 1;
+    self.stack.push(Activation { node, start: Instant::now(), line: 0 });
+  }
+
+  pub(super) fn exit(&mut self) {
+    let activation = self.stack.pop().expect("A call is running");
+    self.nodes[activation.node].time += activation.start.elapsed();
+  }
+
+  /// Ends the calls still running, after a runtime error
+  pub(super) fn finish(&mut self) {
+    while !self.stack.is_empty() {
+      self.exit();
+    }
+  }
+
+  /// Counts the line when the function moves to it from another line, or from the end of a loop
+  pub(super) fn line(&mut self, class_name: &str, line: u32) {
+    let activation = self.stack.last_mut().expect("A call is running");
+    if line > 0 && line != activation.line {
+      *counts_of(&mut self.lines, class_name).entry(line).or_default() += 1;
+    }
+    activation.line = line;
+  }
+
+  /// A jump back to an earlier block, the start of a loop
+  pub(super) fn jump_back(&mut self) {
+    if let Some(activation) = self.stack.last_mut() {
+      activation.line = 0;
+    }
+  }
+
+  pub(super) fn branch(&mut self, function: &str, block: BlockId, then: bool) {
+    counts_of(&mut self.branches, function).entry(block).or_default()[usize::from(!then)] += 1;
+  }
+
+  pub(super) fn allocation(&mut self, tag: usize) {
+    self.allocations[tag] += 1;
+  }
+
+  /// Methods called, the most time spent in them first
+  pub fn methods(&self) -> Vec<MethodProfile<'_>> {
+    let mut methods: BTreeMap<&str, MethodProfile> = BTreeMap::new();
+    for (index, node) in self.nodes.iter().enumerate().skip(1) {
+      let method = methods.entry(&node.function).or_insert(MethodProfile {
+        function: &node.function,
+        calls: 0,
+        inclusive: Duration::ZERO,
+        exclusive: Duration::ZERO,
+      });
+      method.calls += node.calls;
+      let children: Duration = node.children.iter().map(|&child| self.nodes[child].time).sum();
+      method.exclusive += node.time.saturating_sub(children);
+      if !self.is_recursive(index) {
+        method.inclusive += node.time;
+      }
+    }
+    let mut methods: Vec<MethodProfile> = methods.into_values().collect();
+    methods.sort_by(|a, b| b.exclusive.cmp(&a.exclusive).then(a.function.cmp(b.function)));
+    methods
+  }
+
+  /// Whether the function of the node is also called further up its path
+  fn is_recursive(&self, index: usize) -> bool {
+    let function = &self.nodes[index].function;
+    let mut ancestor = self.nodes[index].parent;
+    while ancestor != 0 {
+      if &self.nodes[ancestor].function == function {
+        return true;
+      }
+      ancestor = self.nodes[ancestor].parent;
+    }
+    false
+  }
+
+  /// Objects allocated by class, the most first
+  pub fn allocations(&self) -> Vec<(&str, u64)> {
+    let mut allocations: Vec<(&str, u64)> = self.class_names.iter()
+        .zip(&self.allocations)
+        .filter(|(_, count)| **count > 0)
+        .map(|(class_name, count)| (class_name.as_str(), *count))
+        .collect();
+    allocations.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    allocations
+  }
+
+  /// Every line with code of the functions with a body of the module profiled, by class and line
+  pub fn lines<'a>(&self, module: &'a Module) -> Vec<LineHits<'a>> {
+    let mut lines = BTreeSet::new();
+    for function in functions(module) {
+      let positions = function.blocks.iter().flat_map(|block| &block.instructions).map(|instruction| instruction.pos.0);
+      lines.extend(positions.filter(|line| *line > 0).map(|line| (function.class_name.as_str(), line)));
+    }
+    lines.into_iter()
+        .map(|(class_name, line)| {
+          let hits = self.lines.get(class_name).and_then(|lines| lines.get(&line)).copied().unwrap_or(0);
+          LineHits { class_name, line, hits }
+        })
+        .collect()
+  }
+
+  /// Every branch of the functions with a body of the module profiled, in the order of the functions and their blocks
+  pub fn branches<'a>(&self, module: &'a Module) -> Vec<BranchHits<'a>> {
+    let mut branches = Vec::new();
+    for function in functions(module) {
+      for (index, block) in function.blocks.iter().enumerate() {
+        if let Terminator::Branch { .. } = block.terminator {
+          let line = block.instructions.last().map_or(function.pos.0, |instruction| instruction.pos.0);
+          let taken = self.branches.get(&function.name).and_then(|blocks| blocks.get(&BlockId(index as u32))).copied();
+          let taken = taken.unwrap_or_default();
+          branches.push(BranchHits { class_name: &function.class_name, function: &function.name, line, taken });
+        }
+      }
+    }
+    branches
+  }
+
+  /// Call stacks in the folded format of flame graph tools: the functions from the outermost one separated by `;`,
+  /// then the time spent in the innermost one in microseconds
+  pub fn folded(&self) -> String {
+    let mut text = String::new();
+    for node in self.nodes.iter().skip(1) {
+      let children: Duration = node.children.iter().map(|&child| self.nodes[child].time).sum();
+      let mut path = vec![node.function.as_str()];
+      let mut ancestor = node.parent;
+      while ancestor != 0 {
+        path.push(&self.nodes[ancestor].function);
+        ancestor = self.nodes[ancestor].parent;
+      }
+      path.reverse();
+      text += &format!("{} {}\n", path.join(";"), node.time.saturating_sub(children).as_micros());
+    }
+    text
+  }
+}
+
+/// Counts of a class or function, added on its first count
+fn counts_of<'m, K: Eq + Hash, V>(counts: &'m mut HashMap<String, HashMap<K, V>>, name: &str) -> &'m mut HashMap<K, V> {
+  if !counts.contains_key(name) {
+    counts.insert(name.to_string(), HashMap::new());
+  }
+  counts.get_mut(name).expect("Counts were just added")
+}
+
+fn functions(module: &Module) -> impl Iterator<Item = &Function> {
+  module.functions.iter().filter(|function| !function.is_builtin())
+}
+
+impl Display for Profile {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let methods = self.methods();
+    let width = methods.iter().map(|method| method.function.len()).chain(["Method".len()]).max().unwrap_or(0);
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    writeln!(f, "{:width$}  {:>10}  {:>12}  {:>12}", "Method", "Calls", "Inclusive ms", "Exclusive ms")?;
+    for method in &methods {
+      writeln!(f, "{:width$}  {:>10}  {:>12.3}  {:>12.3}", method.function, method.calls, millis(method.inclusive), millis(method.exclusive))?;
+    }
+
+    let allocations = self.allocations();
+    let width = allocations.iter().map(|(class_name, _)| class_name.len()).chain(["Class".len()]).max().unwrap_or(0);
+    writeln!(f)?;
+    writeln!(f, "{:width$}  {:>11}", "Class", "Allocations")?;
+    for (class_name, count) in allocations {
+      writeln!(f, "{class_name:width$}  {count:>11}")?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A runtime value: a raw integer or boolean, or a reference to an object
+#[derive(Debug, Clone)]
+pub enum Value {
+  Void,
+  Int(i32),
+  Bool(bool),
+  Object(ObjectRef),
+}
+
+pub type ObjectRef = Rc<RefCell<Object>>;
+
+#[derive(Debug, Clone)]
+pub struct Object {
+  pub tag: usize, // tag of the dynamic class
+  pub data: ObjectData,
+}
+
+#[derive(Debug, Clone)]
+pub enum ObjectData {
+  Attributes(Vec<Value>),
+  Int(i32),
+  Bool(bool),
+  Str(String),
+}
+
+impl Value {
+  pub fn object(tag: usize, data: ObjectData) -> Value {
+    Value::Object(Rc::new(RefCell::new(Object { tag, data })))
+  }
+
+  pub fn is_void(&self) -> bool {
+    matches!(self, Value::Void)
+  }
+
+  /// Tag of the dynamic class of an object, none for void and raw values
+  pub fn tag(&self) -> Option<usize> {
+    match self {
+      Value::Object(object) => Some(object.borrow().tag),
+      _ => None,
+    }
+  }
+
+  pub fn as_int(&self) -> i32 {
+    match self {
+      Value::Int(value) => *value,
+      Value::Object(object) => match object.borrow().data {
+        ObjectData::Int(value) => value,
+        ref data => panic!("Expected an Int object, found {data:?}"),
+      },
+      _ => panic!("Expected an int, found {self:?}"),
+    }
+  }
+
+  pub fn as_bool(&self) -> bool {
+    match self {
+      Value::Bool(value) => *value,
+      Value::Object(object) => match object.borrow().data {
+        ObjectData::Bool(value) => value,
+        ref data => panic!("Expected a Bool object, found {data:?}"),
+      },
+      _ => panic!("Expected a bool, found {self:?}"),
+    }
+  }
+
+  pub fn as_str(&self) -> String {
+    match self {
+      Value::Object(object) => match &object.borrow().data {
+        ObjectData::Str(value) => value.clone(),
+        data => panic!("Expected a String object, found {data:?}"),
+      },
+      _ => panic!("Expected a String object, found {self:?}"),
+    }
+  }
+
+  /// COOL `=`: the same object, or basic objects with equal values
+  pub fn equals(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Void, Value::Void) => true,
+      (Value::Int(a), Value::Int(b)) => a == b,
+      (Value::Bool(a), Value::Bool(b)) => a == b,
+      (Value::Object(a), Value::Object(b)) => {
+        Rc::ptr_eq(a, b) || match (&a.borrow().data, &b.borrow().data) {
+          (ObjectData::Int(a), ObjectData::Int(b)) => a == b,
+          (ObjectData::Bool(a), ObjectData::Bool(b)) => a == b,
+          (ObjectData::Str(a), ObjectData::Str(b)) => a == b,
+          _ => false,
+        }
+      }
+      _ => false,
+    }
+  }
+}
+
+impl Drop for Object {
+  /// Drops the objects only this one refers to one after the other, as dropping them recursively would overflow the
+  /// stack on long chains of objects like lists
+  fn drop(&mut self) {
+    let ObjectData::Attributes(attributes) = &mut self.data else {
+      return;
+    };
+    let mut pending = std::mem::take(attributes);
+    while let Some(value) = pending.pop() {
+      if let Value::Object(object) = value {
+        if let Ok(object) = Rc::try_unwrap(object) {
+          if let ObjectData::Attributes(attributes) = &mut object.borrow_mut().data {
+            pending.append(attributes);
+          }
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,8 @@
+pub mod analysis;
+pub mod interp;
+pub mod lower;
+pub mod model;
+pub mod opt;
+pub mod parse;
+pub mod runtime;
+pub mod verify;
@@ -0,0 +1,275 @@
+use crate::model::function::{Block, Function};
+use crate::model::instruction::{Instruction, Op, Terminator};
+use crate::model::{BlockId, Pos, Temp, Type};
+use std::collections::HashMap;
+
+/// A mutable variable of the source program: a formal, or a `let` or `case` binding
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) struct VarId(usize);
+
+impl VarId {
+  pub(crate) fn index(self) -> usize {
+    self.0
+  }
+}
+
+#[derive(Debug, Default)]
+struct PendingBlock {
+  instructions: Vec<Instruction>,
+  terminator: Option<Terminator>,
+}
+
+/// Builds a function in SSA form from structured control flow, placing phis for variables on demand with the
+/// algorithm of Braun et al., "Simple and Efficient Construction of Static Single Assignment Form".
+/// A block is sealed once all its predecessors are known. Reading a variable in an unsealed block creates
+/// an incomplete phi that gets its operands when the block is sealed.
+pub(crate) struct FunctionBuilder {
+  function: Function,
+  blocks: Vec<PendingBlock>,
+  current: BlockId,
+  pos: Pos,
+  predecessors: Vec<Vec<BlockId>>,
+  sealed: Vec<bool>,
+  var_types: Vec<Type>,
+  definitions: HashMap<(VarId, BlockId), Temp>,
+  incomplete_phis: HashMap<BlockId, Vec<(VarId, Temp)>>,
+  aliases: HashMap<Temp, Temp>, // trivial phis replaced by their only operand
+}
+
+impl FunctionBuilder {
+  /// Starts a function with one temporary per parameter, in the sealed entry block
+  pub(crate) fn new(name: String, class_name: String, params: Vec<(String, Type)>, ret_type: Type, pos: Pos) -> FunctionBuilder {
+    let temps: Vec<Type> = params.iter().map(|(_, param_type)| param_type.clone()).collect();
+    let params: Vec<(String, Temp)> = params.into_iter().enumerate().map(|(index, (name, _))| (name, Temp(index as u32))).collect();
+    let function = Function { name, class_name, params, ret_type, temps, blocks: Vec::new(), pos };
+
+    let mut builder = FunctionBuilder {
+      function,
+      blocks: Vec::new(),
+      current: BlockId::ENTRY,
+      pos,
+      predecessors: Vec::new(),
+      sealed: Vec::new(),
+      var_types: Vec::new(),
+      definitions: HashMap::new(),
+      incomplete_phis: HashMap::new(),
+      aliases: HashMap::new(),
+    };
+    let entry = builder.new_block();
+    builder.seal(entry);
+    builder
+  }
+
+  pub(crate) fn param(&self, index: usize) -> Temp {
+    self.function.params[index].1
+  }
+
+  pub(crate) fn temp_type(&self, temp: Temp) -> &Type {
+    self.function.temp_type(temp)
+  }
+
+  pub(crate) fn set_pos(&mut self, pos: Pos) {
+    if pos != (0, 0) {
+      self.pos = pos;
+    }
+  }
+
+  pub(crate) fn new_block(&mut self) -> BlockId {
+    self.blocks.push(PendingBlock::default());
+    self.predecessors.push(Vec::new());
+    self.sealed.push(false);
+    BlockId(self.blocks.len() as u32 - 1)
+  }
+
+  pub(crate) fn current_block(&self) -> BlockId {
+    self.current
+  }
+
+  pub(crate) fn switch_to(&mut self, block: BlockId) {
+    self.current = block;
+  }
+
+  /// Appends an instruction that produces a value to the current block
+  pub(crate) fn value(&mut self, op: Op, value_type: Type) -> Temp {
+    let dest = self.function.new_temp(value_type);
+    self.blocks[self.current.index()].instructions.push(Instruction { dest: Some(dest), op, pos: self.pos });
+    dest
+  }
+
+  /// Appends an instruction that only has an effect to the current block
+  pub(crate) fn effect(&mut self, op: Op) {
+    self.blocks[self.current.index()].instructions.push(Instruction { dest: None, op, pos: self.pos });
+  }
+
+  /// Adds a phi at the start of the current block
+  pub(crate) fn phi(&mut self, incoming: Vec<(BlockId, Temp)>, value_type: Type) -> Temp {
+    let block = self.current;
+    self.insert_phi(block, incoming, value_type)
+  }
+
+  /// Ends the current block
+  pub(crate) fn terminate(&mut self, terminator: Terminator) {
+    let block = self.current;
+    for successor in terminator.successors() {
+      self.predecessors[successor.index()].push(block);
+    }
+    let pending = &mut self.blocks[block.index()];
+    assert!(pending.terminator.is_none(), "Block {block} is already terminated");
+    pending.terminator = Some(terminator);
+  }
+
+  /// Declares that all predecessors of the block are known, completing the phis created while it was unsealed
+  pub(crate) fn seal(&mut self, block: BlockId) {
+    for (var, phi) in self.incomplete_phis.remove(&block).unwrap_or_default() {
+      self.add_phi_operands(var, block, phi);
+    }
+    self.sealed[block.index()] = true;
+  }
+
+  pub(crate) fn declare_variable(&mut self, var_type: Type) -> VarId {
+    self.var_types.push(var_type);
+    VarId(self.var_types.len() - 1)
+  }
+
+  pub(crate) fn variable_type(&self, var: VarId) -> &Type {
+    &self.var_types[var.0]
+  }
+
+  pub(crate) fn write_variable(&mut self, var: VarId, value: Temp) {
+    self.definitions.insert((var, self.current), value);
+  }
+
+  pub(crate) fn read_variable(&mut self, var: VarId) -> Temp {
+    let block = self.current;
+    self.read_variable_in(var, block)
+  }
+
+  fn read_variable_in(&mut self, var: VarId, block: BlockId) -> Temp {
+    if let Some(&value) = self.definitions.get(&(var, block)) {
+      return self.resolve(value);
+    }
+
+    let var_type = self.var_types[var.0].clone();
+    let value = if !self.sealed[block.index()] {
+      let phi = self.insert_phi(block, Vec::new(), var_type);
+      self.incomplete_phis.entry(block).or_default().push((var, phi));
+      phi
+    } else if let [predecessor] = self.predecessors[block.index()][..] {
+      self.read_variable_in(var, predecessor)
+    } else {
+      // Breaks cycles in loops: the phi is the definition while its operands are read
+      let phi = self.insert_phi(block, Vec::new(), var_type);
+      self.definitions.insert((var, block), phi);
+      self.add_phi_operands(var, block, phi)
+    };
+
+    self.definitions.insert((var, block), value);
+    value
+  }
+
+  fn insert_phi(&mut self, block: BlockId, incoming: Vec<(BlockId, Temp)>, value_type: Type) -> Temp {
+    let dest = self.function.new_temp(value_type);
+    let instructions = &mut self.blocks[block.index()].instructions;
+    let index = instructions.iter().take_while(|instruction| instruction.is_phi()).count();
+    instructions.insert(index, Instruction { dest: Some(dest), op: Op::Phi(incoming), pos: self.pos });
+    dest
+  }
+
+  fn add_phi_operands(&mut self, var: VarId, block: BlockId, phi: Temp) -> Temp {
+    let predecessors = self.predecessors[block.index()].clone();
+    let incoming: Vec<(BlockId, Temp)> = predecessors.into_iter().map(|predecessor| (predecessor, self.read_variable_in(var, predecessor))).collect();
+
+    let instruction = self.blocks[block.index()].instructions.iter_mut()
+        .find(|instruction| instruction.dest == Some(phi))
+        .expect("Phi must be in its block");
+    instruction.op = Op::Phi(incoming);
+
+    self.try_remove_trivial_phi(block, phi)
+  }
+
+  /// Replaces a phi whose operands are all the same value, or the phi itself, by that value
+  fn try_remove_trivial_phi(&mut self, block: BlockId, phi: Temp) -> Temp {
+    let Some(Op::Phi(incoming)) = self.blocks[block.index()].instructions.iter().find(|i| i.dest == Some(phi)).map(|i| &i.op) else {
+      return phi;
+    };
+
+    let mut same: Option<Temp> = None;
+    for &(_, operand) in incoming {
+      let operand = self.resolve(operand);
+      if Some(operand) == same || operand == phi {
+        continue;
+      }
+      if same.is_some() {
+        return phi;
+      }
+      same = Some(operand);
+    }
+
+    match same {
+      Some(same) => {
+        self.aliases.insert(phi, same);
+        same
+      }
+      None => phi, // only reachable from itself
+    }
+  }
+
+  fn resolve(&self, mut temp: Temp) -> Temp {
+    while let Some(&alias) = self.aliases.get(&temp) {
+      temp = alias;
+    }
+    temp
+  }
+
+  /// Returns the function without a body, e.g. a built-in method provided by the runtime
+  pub(crate) fn declaration(self) -> Function {
+    self.function
+  }
+
+  /// Removes the trivial phis and returns the function, with blocks and temporaries renumbered
+  pub(crate) fn finish(mut self) -> Function {
+    assert!(self.incomplete_phis.is_empty(), "All blocks must be sealed");
+    self.remove_trivial_phis();
+
+    let mut blocks: Vec<Block> = Vec::new();
+    for (index, pending) in std::mem::take(&mut self.blocks).into_iter().enumerate() {
+      let mut terminator = pending.terminator.unwrap_or_else(|| panic!("Block bb{index} of {} is not terminated", self.function.name));
+      let mut instructions: Vec<Instruction> = Vec::new();
+      for mut instruction in pending.instructions {
+        if instruction.dest.is_some_and(|dest| self.aliases.contains_key(&dest)) {
+          continue;
+        }
+        for operand in instruction.op.operands_mut() {
+          *operand = self.resolve(*operand);
+        }
+        instructions.push(instruction);
+      }
+      for operand in terminator.operands_mut() {
+        *operand = self.resolve(*operand);
+      }
+      blocks.push(Block { instructions, terminator });
+    }
+
+    self.function.blocks = blocks;
+    self.function.renumber();
+    self.function
+  }
+
+  /// Phis that became trivial after their operands were replaced, e.g. nested loops, are removed until none is left
+  fn remove_trivial_phis(&mut self) {
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for block in 0..self.blocks.len() {
+        let phis: Vec<Temp> = self.blocks[block].instructions.iter()
+            .filter(|instruction| instruction.is_phi())
+            .filter_map(|instruction| instruction.dest)
+            .filter(|dest| !self.aliases.contains_key(dest))
+            .collect();
+        for phi in phis {
+          changed |= self.try_remove_trivial_phi(BlockId(block as u32), phi) != phi;
+        }
+      }
+    }
+  }
+}
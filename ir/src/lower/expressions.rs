@@ -0,0 +1,325 @@
+use crate::lower::{object_type, value_type, Lowerer};
+use crate::model::instruction::{BinaryOp, Op, Terminator, TrapKind, UnaryOp};
+use crate::model::{BlockId, Constant, Temp, Type};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::OBJECT_CLASS_NAME;
+use parser::model::expressions::{CaseBranch, Expression};
+use semantic::environments::SELF_OBJECT_NAME;
+
+impl Lowerer<'_, '_> {
+  /// Lowers the expression to a value in its natural representation: raw for literals, arithmetic, comparisons and
+  /// `not`, and for variables, attributes, dispatches and conditionals of type `Int` or `Bool`, an object otherwise
+  pub(crate) fn lower(&mut self, expr: &Expression) -> Temp {
+    self.builder.set_pos(expr.get_pos());
+
+    match expr {
+      Expression::PartialAssign { .. } |
+      Expression::PartialDispatch { .. } |
+      Expression::PartialCastDispatch { .. } |
+      Expression::PartialBinary { .. } => unreachable!("Partial expressions are rejected by the semantic checks"),
+
+      Expression::IntExpr { value, .. } => self.builder.value(Op::Const(Constant::Int(*value)), Type::Int),
+      Expression::BoolExpr { value, .. } => self.builder.value(Op::Const(Constant::Bool(*value)), Type::Bool),
+      Expression::StringExpr { value, .. } => self.builder.value(Op::Const(Constant::Str(value.clone())), self.static_type(expr)),
+      Expression::SelfExpr | Expression::SelfTypeExpr { .. } => self.self_temp,
+
+      Expression::IdentExpr { name } => {
+        let name = name.get_name();
+        if name == SELF_OBJECT_NAME {
+          return self.self_temp;
+        }
+        if let Some(var) = self.lookup_variable(&name) {
+          return self.builder.read_variable(var);
+        }
+        let (index, attr_type) = self.attribute(&name);
+        let object = self.self_temp;
+        self.builder.value(Op::GetAttr { object, index }, attr_type)
+      }
+
+      Expression::Assign { name, expr } => {
+        let name = name.get_name();
+        match self.lookup_variable(&name) {
+          Some(var) => {
+            let var_type = self.builder.variable_type(var).clone();
+            let value = self.lower_as(expr, &var_type);
+            self.assign(var, value);
+            value
+          }
+          None => {
+            let (index, attr_type) = self.attribute(&name);
+            let value = self.lower_as(expr, &attr_type);
+            let object = self.self_temp;
+            self.builder.effect(Op::SetAttr { object, index, value });
+            value
+          }
+        }
+      }
+
+      Expression::New { type_name } => {
+        let type_name = type_name.get_name();
+        if type_name == KEYWORD_SELF_TYPE {
+          let self_temp = self.self_temp;
+          self.builder.value(Op::NewLike(self_temp), Type::object(&self.class_name))
+        } else {
+          self.builder.value(Op::New(type_name.clone()), Type::object(&type_name))
+        }
+      }
+
+      Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } => {
+        // Every implementation of the method has the signature of the class dispatched on
+        let method = fn_name.get_name();
+        let dispatch_class = match cast_type {
+          Some(cast_type) => cast_type.get_name(),
+          None => self.static_type(calling_expr).class_name().to_string(),
+        };
+        let checked = self.checked;
+        let signature = checked.env.lookup_method(&dispatch_class, &method).expect("Method is checked by the semantic pass");
+
+        // The arguments are evaluated before the receiver
+        let args: Vec<Temp> = param_list.iter().zip(&signature.formals)
+            .map(|(param, (_, formal_type))| self.lower_as(param, &value_type(formal_type, &dispatch_class)))
+            .collect();
+        let receiver = self.lower_object(calling_expr);
+        let result_type = if signature.ret_type == KEYWORD_SELF_TYPE {
+          self.static_type(expr)
+        } else {
+          value_type(&signature.ret_type, &dispatch_class)
+        };
+
+        self.builder.set_pos(fn_name.get_pos());
+        self.builder.effect(Op::CheckVoid(receiver, TrapKind::DispatchOnVoid));
+
+        match cast_type {
+          Some(cast_type) => {
+            let layout = &self.checked.layouts[&cast_type.get_name()];
+            let slot = layout.method_index(&method).expect("Method is checked by the semantic pass");
+            let function = format!("{}.{method}", layout.dispatch_table[slot].class_name);
+            let args = std::iter::once(receiver).chain(args).collect();
+            self.builder.value(Op::Call { function, args }, result_type)
+          }
+          None => {
+            let receiver_class = self.builder.temp_type(receiver).class_name().to_string();
+            let slot = self.checked.layouts[&receiver_class].method_index(&method).expect("Method is checked by the semantic pass");
+            self.builder.value(Op::Dispatch { receiver, method, slot, args }, result_type)
+          }
+        }
+      }
+
+      Expression::Conditional { predicate, then_expr, else_expr } => {
+        let cond = self.lower_raw(predicate, Type::Bool);
+        let (then_block, else_block, join) = (self.builder.new_block(), self.builder.new_block(), self.builder.new_block());
+        self.builder.terminate(Terminator::Branch { cond, then_block, else_block });
+        self.builder.seal(then_block);
+        self.builder.seal(else_block);
+
+        let result_type = self.natural_type(expr);
+        let mut incoming: Vec<(BlockId, Temp)> = Vec::new();
+        for (block, arm) in [(then_block, then_expr), (else_block, else_expr)] {
+          self.builder.switch_to(block);
+          let value = self.lower_as(arm, &result_type);
+          incoming.push((self.builder.current_block(), value));
+          self.builder.terminate(Terminator::Jump(join));
+        }
+
+        self.builder.seal(join);
+        self.builder.switch_to(join);
+        self.builder.phi(incoming, result_type)
+      }
+
+      Expression::Loop { predicate, body } => {
+        let header = self.builder.new_block();
+        self.builder.terminate(Terminator::Jump(header));
+        self.builder.switch_to(header);
+
+        let cond = self.lower_raw(predicate, Type::Bool);
+        let (body_block, exit) = (self.builder.new_block(), self.builder.new_block());
+        self.builder.terminate(Terminator::Branch { cond, then_block: body_block, else_block: exit });
+        self.builder.seal(body_block);
+        self.builder.seal(exit);
+
+        self.builder.switch_to(body_block);
+        self.lower(body);
+        self.builder.terminate(Terminator::Jump(header));
+        self.builder.seal(header);
+
+        // The value of the loop is at the loop, not at the end of its body
+        self.builder.switch_to(exit);
+        self.builder.set_pos(expr.get_pos());
+        self.builder.value(Op::Const(Constant::Void), Type::object(OBJECT_CLASS_NAME))
+      }
+
+      Expression::Block { expr_list } => {
+        let mut value = None;
+        for expr in expr_list {
+          value = Some(self.lower(expr));
+        }
+        value.expect("Blocks have at least one expression")
+      }
+
+      Expression::Let { let_init, in_expr } => {
+        for init in let_init {
+          let var_type = value_type(&init.id_type.get_name(), &self.class_name);
+          let value = match &init.expr {
+            Some(init_expr) => self.lower_as(init_expr, &var_type),
+            None => {
+              self.builder.set_pos(init.id.get_pos());
+              self.builder.value(Op::Const(Constant::default_of(var_type.class_name())), var_type.clone())
+            }
+          };
+          self.bind(init.id.get_name(), var_type, value);
+        }
+
+        let value = self.lower(in_expr);
+        self.unbind(let_init.len());
+        value
+      }
+
+      Expression::Case { switch_expression, branches } => self.lower_case(expr, switch_expression, branches),
+
+      Expression::Plus { left, right } => self.binary(BinaryOp::Add, left, right, Type::Int),
+      Expression::Minus { left, right } => self.binary(BinaryOp::Sub, left, right, Type::Int),
+      Expression::Multiply { left, right } => self.binary(BinaryOp::Mul, left, right, Type::Int),
+      Expression::Divide { left, right } => self.binary(BinaryOp::Div, left, right, Type::Int),
+      Expression::LessThan { left, right } => self.binary(BinaryOp::Lt, left, right, Type::Int),
+      Expression::LessThanOrEqual { left, right } => self.binary(BinaryOp::Le, left, right, Type::Int),
+
+      Expression::Equal { left, right } => {
+        let operand_type = self.natural_type(left);
+        if operand_type.is_object() {
+          let left = self.lower_object(left);
+          let right = self.lower_object(right);
+          self.builder.value(Op::ObjEq(left, right), Type::Bool)
+        } else {
+          self.binary(BinaryOp::Eq, left, right, operand_type)
+        }
+      }
+
+      Expression::Negate { expr } => {
+        let value = self.lower_raw(expr, Type::Int);
+        self.builder.value(Op::Unary(UnaryOp::Neg, value), Type::Int)
+      }
+      Expression::Not { expr } => {
+        let value = self.lower_raw(expr, Type::Bool);
+        self.builder.value(Op::Unary(UnaryOp::Not, value), Type::Bool)
+      }
+      Expression::IsVoid { expr } => {
+        let value = self.lower(expr);
+        if self.builder.temp_type(value).is_object() {
+          self.builder.value(Op::IsVoid(value), Type::Bool)
+        } else {
+          self.builder.value(Op::Const(Constant::Bool(false)), Type::Bool)
+        }
+      }
+    }
+  }
+
+  /// Lowers the expression to an object, boxing raw values
+  pub(crate) fn lower_object(&mut self, expr: &Expression) -> Temp {
+    let value = self.lower(expr);
+    self.box_value(value)
+  }
+
+  /// Lowers the expression to a value of the type, raw or an object
+  pub(crate) fn lower_as(&mut self, expr: &Expression, value_type: &Type) -> Temp {
+    if value_type.is_object() {
+      self.lower_object(expr)
+    } else {
+      self.lower_raw(expr, value_type.clone())
+    }
+  }
+
+  /// Lowers an `Int` or `Bool` expression to a raw value, unboxing objects
+  fn lower_raw(&mut self, expr: &Expression, raw: Type) -> Temp {
+    let value = self.lower(expr);
+    if self.builder.temp_type(value).is_object() {
+      self.builder.value(Op::Unbox(value), raw)
+    } else {
+      value
+    }
+  }
+
+  fn box_value(&mut self, value: Temp) -> Temp {
+    let value_type = self.builder.temp_type(value);
+    if value_type.is_object() {
+      return value;
+    }
+    let boxed = Type::object(value_type.class_name());
+    self.builder.value(Op::Box(value), boxed)
+  }
+
+  /// Operation on two raw operands of the given type
+  fn binary(&mut self, op: BinaryOp, left: &Expression, right: &Expression, operand_type: Type) -> Temp {
+    let pos = left.get_pos();
+    let left = self.lower_raw(left, operand_type.clone());
+    let right = self.lower_raw(right, operand_type);
+    self.builder.set_pos(pos);
+    let result_type = match op {
+      BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => Type::Int,
+      BinaryOp::Lt | BinaryOp::Le | BinaryOp::Eq => Type::Bool,
+    };
+    self.builder.value(Op::Binary(op, left, right), result_type)
+  }
+
+  /// Tests the branches from the most to the least specific type, so the first branch whose type the dynamic class
+  /// of the value conforms to is the one with the closest ancestor
+  fn lower_case(&mut self, expr: &Expression, switch_expression: &Expression, branches: &[CaseBranch]) -> Temp {
+    let value = self.lower_object(switch_expression);
+    let pos = switch_expression.get_pos();
+    self.builder.set_pos(pos);
+    self.builder.effect(Op::CheckVoid(value, TrapKind::CaseOnVoid));
+
+    let hierarchy = &self.checked.env.hierarchy;
+    let mut ordered: Vec<&CaseBranch> = branches.iter().collect();
+    ordered.sort_by_key(|branch| std::cmp::Reverse(hierarchy.depth(&branch.id_type.get_name()).unwrap_or(0)));
+
+    let result_type = self.natural_type(expr);
+    let join = self.builder.new_block();
+    let mut incoming: Vec<(BlockId, Temp)> = Vec::new();
+    for branch in ordered {
+      let branch_type = branch.id_type.get_name();
+      self.builder.set_pos(branch.id_type.get_pos());
+      let matches = self.builder.value(Op::InstanceOf(value, branch_type.clone()), Type::Bool);
+      let (arm, next) = (self.builder.new_block(), self.builder.new_block());
+      self.builder.terminate(Terminator::Branch { cond: matches, then_block: arm, else_block: next });
+      self.builder.seal(arm);
+      self.builder.seal(next);
+
+      self.builder.switch_to(arm);
+      let var_type = value_type(&branch_type, &self.class_name);
+      let bound = if var_type.is_object() { Op::Copy(value) } else { Op::Unbox(value) };
+      let bound = self.builder.value(bound, var_type.clone());
+      self.bind(branch.id.get_name(), var_type, bound);
+      let result = self.lower_as(&branch.expr, &result_type);
+      self.unbind(1);
+      incoming.push((self.builder.current_block(), result));
+      self.builder.terminate(Terminator::Jump(join));
+
+      self.builder.switch_to(next);
+    }
+    self.builder.terminate(Terminator::Trap { kind: TrapKind::CaseNoMatch, value, pos });
+
+    self.builder.seal(join);
+    self.builder.switch_to(join);
+    self.builder.phi(incoming, result_type)
+  }
+
+  /// Static type of the expression inferred by the type checker, as an object type
+  fn static_type(&self, expr: &Expression) -> Type {
+    let type_name = self.checked.types.get(expr).expect("Every expression is typed by the semantic pass");
+    object_type(type_name, &self.class_name)
+  }
+
+  /// Static type of the expression as the type of its values, raw for `Int` and `Bool`
+  fn natural_type(&self, expr: &Expression) -> Type {
+    let type_name = self.checked.types.get(expr).expect("Every expression is typed by the semantic pass");
+    value_type(type_name, &self.class_name)
+  }
+
+  /// Index and type of an attribute of the class
+  pub(crate) fn attribute(&self, name: &str) -> (usize, Type) {
+    let layout = &self.checked.layouts[&self.class_name];
+    let index = layout.attribute_index(name).expect("Identifiers are checked by the semantic pass");
+    let slot = &layout.attributes[index];
+    (index, value_type(&slot.attr_type, &slot.class_name))
+  }
+}
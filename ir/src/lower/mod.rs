@@ -0,0 +1,341 @@
+mod builder;
+mod expressions;
+
+use crate::lower::builder::{FunctionBuilder, VarId};
+use crate::model::function::Function;
+use crate::model::instruction::{Op, Terminator};
+use crate::model::module::{AttributeInfo, ClassInfo, Module, SlotInfo, INIT_METHOD_NAME};
+use crate::model::{Temp, Type};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::{ParseClass, BOOL_CLASS_NAME, INT_CLASS_NAME};
+use parser::model::feature::{Method, ParseFeature};
+use semantic::environments::SELF_OBJECT_NAME;
+use semantic::models::class::PRIMITIVE_TYPES;
+use semantic::models::layout::ClassLayout;
+use semantic::models::program::CheckedProgram;
+
+/// How a program is lowered
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct LowerOptions {
+  pub debug_info: bool, // emit `Op::DebugVar` so the debugger can show the source variables
+}
+
+/// Lowers a checked program to IR in SSA form.
+///
+/// Attributes, variables, parameters and return values declared `Int` or `Bool` hold raw values, the others objects.
+/// `Int` and `Bool` values are only boxed where an object is expected: stored with a more general type, used as a
+/// dispatch receiver or matched by `case`. Dispatch is preceded by an explicit void check, and `new` runs the
+/// attribute initialiser of the class, a function named `Class._init`.
+pub fn lower_program(checked: &CheckedProgram) -> Module {
+  lower_program_with_options(checked, &LowerOptions::default())
+}
+
+pub fn lower_program_with_options(checked: &CheckedProgram, options: &LowerOptions) -> Module {
+  let mut layouts: Vec<&ClassLayout> = checked.layouts.values().collect();
+  layouts.sort_by_key(|layout| layout.tag);
+
+  let classes: Vec<ClassInfo> = layouts.iter().map(|layout| lower_class_info(checked, layout)).collect();
+
+  let mut functions: Vec<Function> = Vec::new();
+  for layout in &layouts {
+    if PRIMITIVE_TYPES.contains(&layout.class_name.as_str()) {
+      functions.extend(declare_builtins(checked, &layout.class_name));
+    }
+  }
+  for class in checked.program.classes() {
+    functions.push(lower_init(checked, options, class));
+    for feature in class.features.iter().flatten() {
+      if let ParseFeature::Method { method } = feature {
+        functions.push(lower_method(checked, options, class, method));
+      }
+    }
+  }
+
+  let entry_class = checked.entry.class_name.clone();
+  let entry_layout = &checked.layouts[&entry_class];
+  let entry_slot = entry_layout.method_index(&checked.entry.method_name).expect("Entry point is checked by the semantic pass");
+  let entry_function = format!("{}.{}", entry_layout.dispatch_table[entry_slot].class_name, checked.entry.method_name);
+
+  Module { classes, functions, entry_class, entry_function }
+}
+
+/// Object type of a declared type, with `SELF_TYPE` standing for the class it occurs in
+fn object_type(type_name: &str, class_name: &str) -> Type {
+  if type_name == KEYWORD_SELF_TYPE { Type::object(class_name) } else { Type::object(type_name) }
+}
+
+/// Type of the values of a declared type: raw for `Int` and `Bool`, an object otherwise
+fn value_type(type_name: &str, class_name: &str) -> Type {
+  match type_name {
+    INT_CLASS_NAME => Type::Int,
+    BOOL_CLASS_NAME => Type::Bool,
+    _ => object_type(type_name, class_name),
+  }
+}
+
+fn lower_class_info(checked: &CheckedProgram, layout: &ClassLayout) -> ClassInfo {
+  let name = layout.class_name.clone();
+  let parent = checked.env.class_map.get(&name)
+      .map(|node| node.parent.to_string())
+      .filter(|parent| checked.env.class_map.contains_key(parent));
+
+  let attributes = layout.attributes.iter()
+      .map(|slot| AttributeInfo { name: slot.name.clone(), attr_type: value_type(&slot.attr_type, &slot.class_name) })
+      .collect();
+  let dispatch_table = layout.dispatch_table.iter()
+      .map(|slot| SlotInfo { method: slot.name.clone(), function: format!("{}.{}", slot.class_name, slot.name) })
+      .collect();
+  let init = (!PRIMITIVE_TYPES.contains(&name.as_str())).then(|| format!("{name}.{INIT_METHOD_NAME}"));
+
+  // The checked program does not know the files of the classes, the driver adds them
+  ClassInfo { name, parent, tag: layout.tag, max_descendant_tag: layout.max_descendant_tag, attributes, dispatch_table, init, file: None }
+}
+
+/// Declarations of the methods a basic class implements, which are provided by the runtime
+fn declare_builtins(checked: &CheckedProgram, class_name: &str) -> Vec<Function> {
+  checked.env.methods.methods_of(class_name).iter()
+      .map(|method| {
+        let mut params = vec![(SELF_OBJECT_NAME.to_string(), Type::object(class_name))];
+        params.extend(method.formals.iter().map(|(name, formal_type)| (name.clone(), value_type(formal_type, class_name))));
+        let ret_type = value_type(&method.ret_type, class_name);
+        let name = format!("{class_name}.{}", method.name);
+        FunctionBuilder::new(name, class_name.to_string(), params, ret_type, (0, 0)).declaration()
+      })
+      .collect()
+}
+
+/// `Class._init(self)`: runs the initialiser of the parent class, then the initialisers of the attributes
+/// declared in the class, in declaration order
+fn lower_init(checked: &CheckedProgram, options: &LowerOptions, class: &ParseClass) -> Function {
+  let class_name = class.get_name();
+  let self_type = Type::object(&class_name);
+  let params = vec![(SELF_OBJECT_NAME.to_string(), self_type.clone())];
+  let builder = FunctionBuilder::new(format!("{class_name}.{INIT_METHOD_NAME}"), class_name.clone(), params, self_type, (class.line_num, class.line_pos));
+  let mut lowerer = Lowerer::new(checked, options, class_name.clone(), builder);
+
+  let parent = class.parent_type.get_name();
+  if !PRIMITIVE_TYPES.contains(&parent.as_str()) {
+    let self_temp = lowerer.self_temp;
+    let parent_type = Type::object(&parent);
+    lowerer.builder.value(Op::Call { function: format!("{parent}.{INIT_METHOD_NAME}"), args: vec![self_temp] }, parent_type);
+  }
+
+  for feature in class.features.iter().flatten() {
+    let ParseFeature::Attribute { attribute } = feature else {
+      continue;
+    };
+    let Some(expr) = &attribute.expr else {
+      continue;
+    };
+    let (index, attr_type) = lowerer.attribute(&attribute.get_name());
+    let value = lowerer.lower_as(expr, &attr_type);
+    lowerer.builder.set_pos(attribute.name.get_pos());
+    let object = lowerer.self_temp;
+    lowerer.builder.effect(Op::SetAttr { object, index, value });
+  }
+
+  let self_temp = lowerer.self_temp;
+  lowerer.builder.terminate(Terminator::Return(self_temp));
+  lowerer.builder.finish()
+}
+
+fn lower_method(checked: &CheckedProgram, options: &LowerOptions, class: &ParseClass, method: &Method) -> Function {
+  let class_name = class.get_name();
+  let mut params = vec![(SELF_OBJECT_NAME.to_string(), Type::object(&class_name))];
+  for formal in method.formals.iter().flatten() {
+    params.push((formal.formal_name.get_name(), value_type(&formal.formal_type.get_name(), &class_name)));
+  }
+
+  let name = format!("{class_name}.{}", method.get_name());
+  let ret_type = value_type(&method.return_type.get_name(), &class_name);
+  let builder = FunctionBuilder::new(name, class_name.clone(), params.clone(), ret_type.clone(), method.name.get_pos());
+  let mut lowerer = Lowerer::new(checked, options, class_name, builder);
+
+  // Formals can be assigned, so they are variables initialised with the parameters
+  for (index, (formal_name, formal_type)) in params.into_iter().enumerate().skip(1) {
+    let param = lowerer.builder.param(index);
+    lowerer.bind(formal_name, formal_type, param);
+  }
+
+  let result = lowerer.lower_as(&method.expr, &ret_type);
+  lowerer.builder.terminate(Terminator::Return(result));
+  lowerer.builder.finish()
+}
+
+/// Lowers the expressions of one function
+struct Lowerer<'a, 'p> {
+  checked: &'a CheckedProgram<'p>,
+  debug_info: bool,
+  class_name: String,
+  builder: FunctionBuilder,
+  self_temp: Temp,
+  scopes: Vec<(String, VarId)>, // variables in scope, innermost last
+}
+
+impl<'a, 'p> Lowerer<'a, 'p> {
+  fn new(checked: &'a CheckedProgram<'p>, options: &LowerOptions, class_name: String, builder: FunctionBuilder) -> Self {
+    let self_temp = builder.param(0);
+    Lowerer { checked, debug_info: options.debug_info, class_name, builder, self_temp, scopes: Vec::new() }
+  }
+
+  /// Declares a variable holding the value and brings it in scope
+  fn bind(&mut self, name: String, var_type: Type, value: Temp) {
+    let var = self.builder.declare_variable(var_type);
+    self.scopes.push((name, var));
+    self.assign(var, value);
+  }
+
+  fn assign(&mut self, var: VarId, value: Temp) {
+    self.builder.write_variable(var, value);
+    self.debug_var(var, Some(value));
+  }
+
+  /// Takes the innermost variables out of scope
+  fn unbind(&mut self, count: usize) {
+    for _ in 0..count {
+      let (_, var) = *self.scopes.last().expect("Variables are unbound in scope");
+      self.debug_var(var, None);
+      self.scopes.pop();
+    }
+  }
+
+  fn debug_var(&mut self, var: VarId, value: Option<Temp>) {
+    if self.debug_info {
+      let (name, _) = self.scopes.iter().rev().find(|(_, other)| *other == var).expect("Variable is in scope");
+      self.builder.effect(Op::DebugVar { name: name.clone(), var: var.index(), value });
+    }
+  }
+
+  fn lookup_variable(&self, name: &str) -> Option<VarId> {
+    self.scopes.iter().rev().find(|(var_name, _)| var_name == name).map(|(_, var)| *var)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lower::{lower_program, lower_program_with_options, LowerOptions};
+  use crate::verify::{verify_module, Form};
+  use parser::get_ast_from_file_path;
+  use parser::programs::find_programs;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_lower_all_programs() {
+    for test_program in find_programs("../test_resources/programs") {
+      let program = test_program.parse().expect("Couldn't parse file");
+      let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+      let module = lower_program(&checked);
+      if let Err(errors) = verify_module(&module, Form::Ssa) {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        panic!("Invalid IR for {}:\n{}\n\n{module}", test_program.name(), errors.join("\n"));
+      }
+    }
+  }
+
+  #[test]
+  fn test_lowering() {
+    let program = get_ast_from_file_path("../test_resources/ir/lowering.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    verify_module(&module, Form::Ssa).expect("IR must be valid");
+
+    let dump = |name: &str| module.function(name).expect("Function must exist").to_string();
+    println!("{module}");
+    assert_eq!(module.entry_function, "Main.main");
+    assert_eq!(dump("Main.sum"), "\
+fn Main.sum(self %0 : Main, n %1 : int) : int {
+bb0:
+  %2 : int = const 0
+  jump bb1
+bb1:
+  %3 : int = phi [bb0: %1, bb2: %9]
+  %4 : int = phi [bb0: %2, bb2: %7]
+  %5 : int = const 0
+  %6 : bool = lt %5, %3
+  branch %6, bb2, bb3
+bb2:
+  %7 : int = add %4, %3
+  %8 : int = const 1
+  %9 : int = sub %3, %8
+  jump bb1
+bb3:
+  %10 : Object = const void
+  ret %4
+}
+");
+
+    // Branches are tested from the most specific type, `Object` matches any other value. An `Int` is bound raw.
+    assert_eq!(dump("Main.describe"), "\
+fn Main.describe(self %0 : Main, x %1 : Object) : String {
+bb0:
+  check_void %1, case_on_void
+  %2 : bool = instance_of %1, Int
+  branch %2, bb1, bb2
+bb1:
+  %3 : int = unbox %1
+  %4 : String = const \"int\"
+  jump bb6
+bb2:
+  %5 : bool = instance_of %1, String
+  branch %5, bb3, bb4
+bb3:
+  %6 : String = copy %1
+  jump bb6
+bb4:
+  %7 : bool = instance_of %1, Object
+  branch %7, bb5, bb7
+bb5:
+  %8 : Object = copy %1
+  check_void %8, dispatch_on_void
+  %9 : String = dispatch %8.type_name#1()
+  jump bb6
+bb6:
+  %10 : String = phi [bb1: %4, bb3: %6, bb5: %9]
+  ret %10
+bb7:
+  trap case_no_match %1
+}
+");
+
+    let init = &module.class("Main").expect("Class must exist").init;
+    assert_eq!(init.as_deref(), Some("Main._init"));
+    assert_eq!(module.function("IO.out_int").map(|f| f.to_string()), Some("declare fn IO.out_int(self %0 : IO, x %1 : int) : IO\n".to_string()));
+  }
+
+  #[test]
+  fn test_debug_info() {
+    let program = get_ast_from_file_path("../test_resources/ir/lowering.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program_with_options(&checked, &LowerOptions { debug_info: true });
+    verify_module(&module, Form::Ssa).expect("IR must be valid");
+
+    // Assignments rebind the variables, which stay in scope until the end of their `let`
+    let dump = module.function("Main.sum").expect("Function must exist").to_string();
+    assert_eq!(dump, "\
+fn Main.sum(self %0 : Main, n %1 : int) : int {
+bb0:
+  debug_var n#0, %1
+  %2 : int = const 0
+  debug_var total#1, %2
+  jump bb1
+bb1:
+  %3 : int = phi [bb0: %1, bb2: %9]
+  %4 : int = phi [bb0: %2, bb2: %7]
+  %5 : int = const 0
+  %6 : bool = lt %5, %3
+  branch %6, bb2, bb3
+bb2:
+  %7 : int = add %4, %3
+  debug_var total#1, %7
+  %8 : int = const 1
+  %9 : int = sub %3, %8
+  debug_var n#0, %9
+  jump bb1
+bb3:
+  %10 : Object = const void
+  debug_var total#1, end
+  ret %4
+}
+");
+  }
+}
@@ -0,0 +1,157 @@
+use crate::analysis::reverse_post_order;
+use crate::model::instruction::{Instruction, Op, Terminator};
+use crate::model::{BlockId, Pos, Temp, Type};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+  pub instructions: Vec<Instruction>,
+  pub terminator: Terminator,
+}
+
+/// A method implementation, or the attribute initialiser of a class.
+/// Blocks are indexed by their id and `bb0` is the entry block. Built-in methods have no blocks.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+  pub name: String, // `Class.method`
+  pub class_name: String,
+  pub params: Vec<(String, Temp)>, // `self` first
+  pub ret_type: Type,
+  pub temps: Vec<Type>, // type of each temporary
+  pub blocks: Vec<Block>,
+  pub pos: Pos,
+}
+
+impl Function {
+  pub fn is_builtin(&self) -> bool {
+    self.blocks.is_empty()
+  }
+
+  pub fn temp_type(&self, temp: Temp) -> &Type {
+    &self.temps[temp.index()]
+  }
+
+  pub fn new_temp(&mut self, temp_type: Type) -> Temp {
+    self.temps.push(temp_type);
+    Temp(self.temps.len() as u32 - 1)
+  }
+
+  pub fn block(&self, id: BlockId) -> &Block {
+    &self.blocks[id.index()]
+  }
+
+  pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+    &mut self.blocks[id.index()]
+  }
+
+  pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+    (0..self.blocks.len() as u32).map(BlockId)
+  }
+
+  /// Predecessors of each block, in block order
+  pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+    let mut predecessors: Vec<Vec<BlockId>> = vec![Vec::new(); self.blocks.len()];
+    for id in self.block_ids() {
+      for successor in self.block(id).terminator.successors() {
+        if !predecessors[successor.index()].contains(&id) {
+          predecessors[successor.index()].push(id);
+        }
+      }
+    }
+    predecessors
+  }
+
+  /// Drops unreachable blocks, phi values of removed edges and unused temporaries, then numbers blocks in reverse post-order and temporaries
+  /// in order of definition, parameters first
+  pub fn renumber(&mut self) {
+    if self.is_builtin() {
+      return;
+    }
+
+    let order = reverse_post_order(self);
+    let mut block_map: HashMap<BlockId, BlockId> = HashMap::new();
+    for (index, block) in order.iter().enumerate() {
+      block_map.insert(*block, BlockId(index as u32));
+    }
+
+    let mut old_blocks: Vec<Option<Block>> = std::mem::take(&mut self.blocks).into_iter().map(Some).collect();
+    self.blocks = order.iter().map(|block| old_blocks[block.index()].take().expect("Blocks are visited once")).collect();
+    for block in &mut self.blocks {
+      for successor in block.terminator.successors_mut() {
+        *successor = block_map[successor];
+      }
+    }
+
+    // Phis keep the values of edges that still exist, some may have been removed by folding a branch
+    let predecessors = self.predecessors();
+    for (block, predecessors) in self.blocks.iter_mut().zip(&predecessors) {
+      for instruction in &mut block.instructions {
+        if let Op::Phi(incoming) = &mut instruction.op {
+          incoming.retain(|(predecessor, _)| block_map.get(predecessor).is_some_and(|new_id| predecessors.contains(new_id)));
+          for (predecessor, _) in incoming {
+            *predecessor = block_map[predecessor];
+          }
+        }
+      }
+    }
+
+    let mut temp_map: HashMap<Temp, Temp> = HashMap::new();
+    let mut temps: Vec<Type> = Vec::new();
+    let definitions = self.params.iter().map(|(_, param)| *param)
+        .chain(self.blocks.iter().flat_map(|block| block.instructions.iter().filter_map(|instruction| instruction.dest)));
+    for temp in definitions {
+      temp_map.entry(temp).or_insert_with(|| {
+        temps.push(self.temps[temp.index()].clone());
+        Temp(temps.len() as u32 - 1)
+      });
+    }
+
+    // Temporaries used without a definition are kept, so the verifier can report them
+    let mut map = |temp: &mut Temp| {
+      *temp = *temp_map.entry(*temp).or_insert_with(|| {
+        temps.push(self.temps[temp.index()].clone());
+        Temp(temps.len() as u32 - 1)
+      });
+    };
+    for (_, param) in &mut self.params {
+      map(param);
+    }
+    for block in &mut self.blocks {
+      for instruction in &mut block.instructions {
+        instruction.op.operands_mut().into_iter().for_each(&mut map);
+        instruction.dest.iter_mut().for_each(&mut map);
+      }
+      block.terminator.operands_mut().into_iter().for_each(&mut map);
+    }
+    self.temps = temps;
+  }
+
+  /// Number of instructions, counting terminators
+  pub fn instruction_count(&self) -> usize {
+    self.blocks.iter().map(|block| block.instructions.len() + 1).sum()
+  }
+}
+
+impl Display for Function {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let params: Vec<String> = self.params.iter().map(|(name, t)| format!("{name} {t} : {}", self.temp_type(*t))).collect();
+    let signature = format!("{}({}) : {}", self.name, params.join(", "), self.ret_type);
+    if self.is_builtin() {
+      return writeln!(f, "declare fn {signature}");
+    }
+
+    writeln!(f, "fn {signature} {{")?;
+    for (id, block) in self.block_ids().zip(&self.blocks) {
+      writeln!(f, "{id}:")?;
+      for instruction in &block.instructions {
+        match instruction.dest {
+          Some(dest) => writeln!(f, "  {dest} : {} = {}", self.temp_type(dest), instruction.op)?,
+          None => writeln!(f, "  {}", instruction.op)?,
+        }
+      }
+      writeln!(f, "  {}", block.terminator)?;
+    }
+    writeln!(f, "}}")
+  }
+}
@@ -0,0 +1,243 @@
+use crate::model::{BlockId, Constant, Pos, Temp};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+  Neg,
+  Not,
+}
+
+/// Operations on raw values. `Eq` compares two integers or two booleans, objects are compared with `Op::ObjEq`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Lt,
+  Le,
+  Eq,
+}
+
+/// Runtime errors raised by generated code
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TrapKind {
+  DispatchOnVoid,
+  CaseOnVoid,
+  CaseNoMatch,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Op {
+  Const(Constant),
+  Copy(Temp),
+  /// Value of the temporary coming from each predecessor block, only at the start of a block
+  Phi(Vec<(BlockId, Temp)>),
+  Unary(UnaryOp, Temp),
+  Binary(BinaryOp, Temp, Temp),
+  /// COOL `=` on objects: the same object, or two `Int`, `Bool` or `String` objects with equal values
+  ObjEq(Temp, Temp),
+  IsVoid(Temp),
+  /// `true` if the dynamic class of the non-void object conforms to the class
+  InstanceOf(Temp, String),
+  Box(Temp),
+  Unbox(Temp),
+  /// Allocates an object with default attribute values and runs the attribute initialisers of the class
+  New(String),
+  /// Same as `New`, with the dynamic class of the non-void object, i.e. `new SELF_TYPE`
+  NewLike(Temp),
+  GetAttr { object: Temp, index: usize },
+  SetAttr { object: Temp, index: usize, value: Temp },
+  CheckVoid(Temp, TrapKind),
+  /// Direct call of a method implementation, the receiver is the first argument
+  Call { function: String, args: Vec<Temp> },
+  /// Dynamic dispatch through the dispatch table slot of the method in the receiver's class
+  Dispatch { receiver: Temp, method: String, slot: usize, args: Vec<Temp> },
+  /// Debug information for the interpreter: the source variable, numbered to tell shadowed variables apart, now holds
+  /// the value, or goes out of scope without one. Only emitted when lowering with `LowerOptions::debug_info`.
+  DebugVar { name: String, var: usize, value: Option<Temp> },
+}
+
+impl Op {
+  /// Temporaries read by the operation, in evaluation order
+  pub fn operands(&self) -> Vec<Temp> {
+    match self {
+      Op::Const(_) | Op::New(_) => Vec::new(),
+      Op::Copy(t) | Op::Unary(_, t) | Op::IsVoid(t) | Op::InstanceOf(t, _) | Op::Box(t) | Op::Unbox(t) | Op::NewLike(t) | Op::CheckVoid(t, _) => vec![*t],
+      Op::GetAttr { object, .. } => vec![*object],
+      Op::Phi(incoming) => incoming.iter().map(|(_, t)| *t).collect(),
+      Op::Binary(_, a, b) | Op::ObjEq(a, b) => vec![*a, *b],
+      Op::SetAttr { object, value, .. } => vec![*object, *value],
+      Op::Call { args, .. } => args.clone(),
+      Op::Dispatch { receiver, args, .. } => std::iter::once(*receiver).chain(args.iter().copied()).collect(),
+      Op::DebugVar { value, .. } => value.iter().copied().collect(),
+    }
+  }
+
+  pub fn operands_mut(&mut self) -> Vec<&mut Temp> {
+    match self {
+      Op::Const(_) | Op::New(_) => Vec::new(),
+      Op::Copy(t) | Op::Unary(_, t) | Op::IsVoid(t) | Op::InstanceOf(t, _) | Op::Box(t) | Op::Unbox(t) | Op::NewLike(t) | Op::CheckVoid(t, _) => vec![t],
+      Op::GetAttr { object, .. } => vec![object],
+      Op::Phi(incoming) => incoming.iter_mut().map(|(_, t)| t).collect(),
+      Op::Binary(_, a, b) | Op::ObjEq(a, b) => vec![a, b],
+      Op::SetAttr { object, value, .. } => vec![object, value],
+      Op::Call { args, .. } => args.iter_mut().collect(),
+      Op::Dispatch { receiver, args, .. } => std::iter::once(receiver).chain(args.iter_mut()).collect(),
+      Op::DebugVar { value, .. } => value.iter_mut().collect(),
+    }
+  }
+
+  /// Returns `true` if the operation has an effect besides computing its value, or may trap,
+  /// so it cannot be removed when its value is unused
+  pub fn has_side_effects(&self) -> bool {
+    matches!(self,
+      Op::Binary(BinaryOp::Div, _, _)
+      | Op::New(_)
+      | Op::NewLike(_)
+      | Op::SetAttr { .. }
+      | Op::CheckVoid(..)
+      | Op::Call { .. }
+      | Op::Dispatch { .. }
+      | Op::DebugVar { .. }
+    )
+  }
+
+  /// Returns `true` if the operation allocates, or calls a method that may allocate or fail like `String.substr`.
+  /// The native backends tell their runtime the location of these operations, which it prints in its errors.
+  pub fn enters_runtime(&self) -> bool {
+    matches!(self, Op::Box(_) | Op::New(_) | Op::NewLike(_) | Op::Call { .. } | Op::Dispatch { .. })
+  }
+}
+
+impl Display for UnaryOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      UnaryOp::Neg => write!(f, "neg"),
+      UnaryOp::Not => write!(f, "not"),
+    }
+  }
+}
+
+impl Display for BinaryOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      BinaryOp::Add => write!(f, "add"),
+      BinaryOp::Sub => write!(f, "sub"),
+      BinaryOp::Mul => write!(f, "mul"),
+      BinaryOp::Div => write!(f, "div"),
+      BinaryOp::Lt => write!(f, "lt"),
+      BinaryOp::Le => write!(f, "le"),
+      BinaryOp::Eq => write!(f, "eq"),
+    }
+  }
+}
+
+impl Display for TrapKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TrapKind::DispatchOnVoid => write!(f, "dispatch_on_void"),
+      TrapKind::CaseOnVoid => write!(f, "case_on_void"),
+      TrapKind::CaseNoMatch => write!(f, "case_no_match"),
+    }
+  }
+}
+
+fn join(temps: &[Temp]) -> String {
+  temps.iter().map(Temp::to_string).collect::<Vec<String>>().join(", ")
+}
+
+impl Display for Op {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Op::Const(constant) => write!(f, "const {constant}"),
+      Op::Copy(t) => write!(f, "copy {t}"),
+      Op::Phi(incoming) => {
+        let incoming: Vec<String> = incoming.iter().map(|(block, t)| format!("{block}: {t}")).collect();
+        write!(f, "phi [{}]", incoming.join(", "))
+      }
+      Op::Unary(op, t) => write!(f, "{op} {t}"),
+      Op::Binary(op, a, b) => write!(f, "{op} {a}, {b}"),
+      Op::ObjEq(a, b) => write!(f, "obj_eq {a}, {b}"),
+      Op::IsVoid(t) => write!(f, "is_void {t}"),
+      Op::InstanceOf(t, class_name) => write!(f, "instance_of {t}, {class_name}"),
+      Op::Box(t) => write!(f, "box {t}"),
+      Op::Unbox(t) => write!(f, "unbox {t}"),
+      Op::New(class_name) => write!(f, "new {class_name}"),
+      Op::NewLike(t) => write!(f, "new_like {t}"),
+      Op::GetAttr { object, index } => write!(f, "get_attr {object}, {index}"),
+      Op::SetAttr { object, index, value } => write!(f, "set_attr {object}, {index}, {value}"),
+      Op::CheckVoid(t, kind) => write!(f, "check_void {t}, {kind}"),
+      Op::Call { function, args } => write!(f, "call {function}({})", join(args)),
+      Op::Dispatch { receiver, method, slot, args } => write!(f, "dispatch {receiver}.{method}#{slot}({})", join(args)),
+      Op::DebugVar { name, var, value: Some(value) } => write!(f, "debug_var {name}#{var}, {value}"),
+      Op::DebugVar { name, var, value: None } => write!(f, "debug_var {name}#{var}, end"),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction {
+  pub dest: Option<Temp>,
+  pub op: Op,
+  pub pos: Pos,
+}
+
+impl Instruction {
+  pub fn is_phi(&self) -> bool {
+    matches!(self.op, Op::Phi(_))
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Terminator {
+  Jump(BlockId),
+  Branch { cond: Temp, then_block: BlockId, else_block: BlockId },
+  Return(Temp),
+  Trap { kind: TrapKind, value: Temp, pos: Pos },
+}
+
+impl Terminator {
+  pub fn successors(&self) -> Vec<BlockId> {
+    match self {
+      Terminator::Jump(target) => vec![*target],
+      Terminator::Branch { then_block, else_block, .. } => vec![*then_block, *else_block],
+      Terminator::Return(_) | Terminator::Trap { .. } => Vec::new(),
+    }
+  }
+
+  pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+    match self {
+      Terminator::Jump(target) => vec![target],
+      Terminator::Branch { then_block, else_block, .. } => vec![then_block, else_block],
+      Terminator::Return(_) | Terminator::Trap { .. } => Vec::new(),
+    }
+  }
+
+  pub fn operands(&self) -> Vec<Temp> {
+    match self {
+      Terminator::Jump(_) => Vec::new(),
+      Terminator::Branch { cond, .. } => vec![*cond],
+      Terminator::Return(t) | Terminator::Trap { value: t, .. } => vec![*t],
+    }
+  }
+
+  pub fn operands_mut(&mut self) -> Vec<&mut Temp> {
+    match self {
+      Terminator::Jump(_) => Vec::new(),
+      Terminator::Branch { cond, .. } => vec![cond],
+      Terminator::Return(t) | Terminator::Trap { value: t, .. } => vec![t],
+    }
+  }
+}
+
+impl Display for Terminator {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Terminator::Jump(target) => write!(f, "jump {target}"),
+      Terminator::Branch { cond, then_block, else_block } => write!(f, "branch {cond}, {then_block}, {else_block}"),
+      Terminator::Return(t) => write!(f, "ret {t}"),
+      Terminator::Trap { kind, value, .. } => write!(f, "trap {kind} {value}"),
+    }
+  }
+}
@@ -0,0 +1,117 @@
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::fmt::{Display, Formatter};
+
+pub mod function;
+pub mod instruction;
+pub mod module;
+
+/// Type of an IR value. `Int` and `Bool` are raw machine words, every other value is a reference to an object,
+/// possibly void, whose static class is known. Boxed integers and booleans are objects of class `Int` and `Bool`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Type {
+  Int,
+  Bool,
+  Object(String),
+}
+
+impl Type {
+  pub fn object(class_name: &str) -> Type {
+    Type::Object(class_name.to_string())
+  }
+
+  pub fn is_object(&self) -> bool {
+    matches!(self, Type::Object(_))
+  }
+
+  /// Static class of the value, the class of the box for raw values
+  pub fn class_name(&self) -> &str {
+    match self {
+      Type::Int => INT_CLASS_NAME,
+      Type::Bool => BOOL_CLASS_NAME,
+      Type::Object(class_name) => class_name,
+    }
+  }
+
+  /// Returns `true` if values of both types have the same machine representation
+  pub fn same_kind(&self, other: &Type) -> bool {
+    matches!((self, other), (Type::Int, Type::Int) | (Type::Bool, Type::Bool) | (Type::Object(_), Type::Object(_)))
+  }
+}
+
+impl Display for Type {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Type::Int => write!(f, "int"),
+      Type::Bool => write!(f, "bool"),
+      Type::Object(class_name) => write!(f, "{class_name}"),
+    }
+  }
+}
+
+/// A temporary of a function, written `%n`
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Temp(pub u32);
+
+impl Temp {
+  pub fn index(self) -> usize {
+    self.0 as usize
+  }
+}
+
+impl Display for Temp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "%{}", self.0)
+  }
+}
+
+/// A basic block of a function, written `bbn`
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct BlockId(pub u32);
+
+impl BlockId {
+  pub const ENTRY: BlockId = BlockId(0);
+
+  pub fn index(self) -> usize {
+    self.0 as usize
+  }
+}
+
+impl Display for BlockId {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "bb{}", self.0)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Constant {
+  Int(i32),
+  Bool(bool),
+  Str(String),
+  Void,
+}
+
+impl Constant {
+  /// Default value of an attribute or a `let` binding of the class, before it is initialised
+  pub fn default_of(class_name: &str) -> Constant {
+    match class_name {
+      INT_CLASS_NAME => Constant::Int(0),
+      BOOL_CLASS_NAME => Constant::Bool(false),
+      STR_CLASS_NAME => Constant::Str(String::new()),
+      _ => Constant::Void,
+    }
+  }
+}
+
+impl Display for Constant {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Constant::Int(value) => write!(f, "{value}"),
+      Constant::Bool(value) => write!(f, "{value}"),
+      Constant::Str(value) => write!(f, "{value:?}"),
+      Constant::Void => write!(f, "void"),
+    }
+  }
+}
+
+/// Line number and position of the expression an instruction was lowered from, `(0, 0)` if none
+pub type Pos = (u32, u32);
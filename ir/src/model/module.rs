@@ -0,0 +1,111 @@
+use crate::model::function::Function;
+use crate::model::Type;
+use std::fmt::{Display, Formatter};
+
+/// Suffix of the function that runs the attribute initialisers of a class, e.g. `Main._init`.
+/// COOL method names cannot start with an underscore, so it never clashes with a method.
+pub const INIT_METHOD_NAME: &str = "_init";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AttributeInfo {
+  pub name: String,
+  pub attr_type: Type,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlotInfo {
+  pub method: String,
+  pub function: String, // implementation dispatched to, `Class.method`
+}
+
+/// Runtime layout of a class, see `semantic::models::layout::ClassLayout`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassInfo {
+  pub name: String,
+  pub parent: Option<String>,
+  pub tag: usize,
+  pub max_descendant_tag: usize,
+  pub attributes: Vec<AttributeInfo>,
+  pub dispatch_table: Vec<SlotInfo>,
+  pub init: Option<String>, // attribute initialiser, none for the basic classes
+  pub file: Option<String>, // source file of the class, for runtime errors, none if unknown
+}
+
+impl ClassInfo {
+  pub fn slot(&self, method: &str) -> Option<usize> {
+    self.dispatch_table.iter().position(|slot| slot.method == method)
+  }
+
+  /// Returns `true` if an attribute holds a raw `Int` or `Bool` value, which a collector must not follow
+  pub fn has_raw_attributes(&self) -> bool {
+    self.attributes.iter().any(|attribute| !attribute.attr_type.is_object())
+  }
+}
+
+/// A whole program in IR. Classes are ordered by tag, so `classes[tag]` is the class with that tag.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Module {
+  pub classes: Vec<ClassInfo>,
+  pub functions: Vec<Function>,
+  pub entry_class: String,
+  pub entry_function: String,
+}
+
+impl Module {
+  pub fn class(&self, name: &str) -> Option<&ClassInfo> {
+    self.classes.iter().find(|class| class.name == name)
+  }
+
+  pub fn function(&self, name: &str) -> Option<&Function> {
+    self.functions.iter().find(|function| function.name == name)
+  }
+
+  pub fn function_mut(&mut self, name: &str) -> Option<&mut Function> {
+    self.functions.iter_mut().find(|function| function.name == name)
+  }
+
+  /// Returns `true` if the class `sub_class` conforms to `super_class`
+  pub fn conforms(&self, sub_class: &str, super_class: &str) -> bool {
+    match (self.class(sub_class), self.class(super_class)) {
+      (Some(sub), Some(sup)) => sup.tag <= sub.tag && sub.tag <= sup.max_descendant_tag,
+      _ => false,
+    }
+  }
+
+  pub fn instruction_count(&self) -> usize {
+    self.functions.iter().map(Function::instruction_count).sum()
+  }
+}
+
+impl Display for ClassInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.parent {
+      Some(parent) => writeln!(f, "class {} : {parent} [tag {}..={}]", self.name, self.tag, self.max_descendant_tag)?,
+      None => writeln!(f, "class {} [tag {}..={}]", self.name, self.tag, self.max_descendant_tag)?,
+    }
+    for (index, attribute) in self.attributes.iter().enumerate() {
+      writeln!(f, "  attr {index} {} : {}", attribute.name, attribute.attr_type)?;
+    }
+    for (index, slot) in self.dispatch_table.iter().enumerate() {
+      writeln!(f, "  slot {index} {} = {}", slot.method, slot.function)?;
+    }
+    if let Some(init) = &self.init {
+      writeln!(f, "  init {init}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for Module {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    for class in &self.classes {
+      writeln!(f, "{class}")?;
+    }
+    writeln!(f, "entry {} {}", self.entry_class, self.entry_function)?;
+    for function in &self.functions {
+      writeln!(f)?;
+      write!(f, "{function}")?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,213 @@
+use crate::analysis::reverse_post_order;
+use crate::model::function::Function;
+use crate::model::instruction::{BinaryOp, Op, Terminator, UnaryOp};
+use crate::model::module::Module;
+use crate::model::{Constant, Temp, Type};
+use crate::opt::Pass;
+use std::collections::HashMap;
+
+/// Evaluates operations on constants at compile time, with the 32-bit wraparound of COOL integers, and replaces
+/// branches on constant conditions by jumps. Division by zero is left to trap at runtime.
+/// Class tests of a value whose static class conforms to the tested class are always true.
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+  fn name(&self) -> &'static str {
+    "const-fold"
+  }
+
+  fn run_on_function(&self, module: &Module, function: &mut Function) -> bool {
+    let mut constants: HashMap<Temp, Constant> = HashMap::new();
+    let mut boxed: HashMap<Temp, Constant> = HashMap::new(); // boxes of raw constants
+    let mut changed = false;
+
+    // In reverse post-order, operands are seen before their uses except through loop phis
+    for id in reverse_post_order(function) {
+      let Function { blocks, temps, .. } = &mut *function;
+      let block = &mut blocks[id.index()];
+      for instruction in &mut block.instructions {
+        let folded = match &instruction.op {
+          // The operand is not void, so it is an instance of any class its static class conforms to
+          Op::InstanceOf(t, class_name) => module.conforms(temps[t.index()].class_name(), class_name).then_some(Constant::Bool(true)),
+          // A `case` branch which is never selected may unbox an `Int` as a `Bool` or the reverse, which is left as is
+          Op::Unbox(t) => boxed.get(t)
+              .filter(|constant| matches!((constant, instruction.dest.map(|dest| &temps[dest.index()])), (Constant::Int(_), Some(Type::Int)) | (Constant::Bool(_), Some(Type::Bool))))
+              .cloned(),
+          op => fold(op, &constants, &boxed),
+        };
+        if let Some(constant) = folded {
+          instruction.op = Op::Const(constant);
+          changed = true;
+        }
+        let Some(dest) = instruction.dest else {
+          continue;
+        };
+        match &instruction.op {
+          Op::Const(constant) => {
+            constants.insert(dest, constant.clone());
+          }
+          Op::Box(value) => {
+            if let Some(constant) = constants.get(value) {
+              boxed.insert(dest, constant.clone());
+            }
+          }
+          _ => {}
+        }
+      }
+
+      // A folded phi may be followed by other phis, which must stay at the start of the block
+      block.instructions.sort_by_key(|instruction| !instruction.is_phi());
+
+      if let Terminator::Branch { cond, then_block, else_block } = block.terminator {
+        if let Some(Constant::Bool(value)) = constants.get(&cond) {
+          block.terminator = Terminator::Jump(if *value { then_block } else { else_block });
+          changed = true;
+        }
+      }
+    }
+
+    if changed {
+      function.renumber();
+    }
+    changed
+  }
+}
+
+fn fold(op: &Op, constants: &HashMap<Temp, Constant>, boxed: &HashMap<Temp, Constant>) -> Option<Constant> {
+  // Value of an object operand: a string or void constant, or a boxed constant
+  let object = |t: &Temp| constants.get(t).or_else(|| boxed.get(t));
+  match op {
+    Op::Unary(UnaryOp::Neg, t) => match constants.get(t)? {
+      Constant::Int(value) => Some(Constant::Int(value.wrapping_neg())),
+      _ => None,
+    },
+    Op::Unary(UnaryOp::Not, t) => match constants.get(t)? {
+      Constant::Bool(value) => Some(Constant::Bool(!value)),
+      _ => None,
+    },
+    Op::Binary(op, a, b) => fold_binary(*op, constants.get(a)?, constants.get(b)?),
+    Op::IsVoid(t) => object(t).map(|value| Constant::Bool(*value == Constant::Void)),
+    // Boxed integers, booleans and strings are equal if their values are
+    Op::ObjEq(a, b) => Some(Constant::Bool(object(a)? == object(b)?)),
+    Op::Phi(incoming) => {
+      let (_, first) = incoming.first()?;
+      let constant = constants.get(first)?;
+      incoming.iter().all(|(_, t)| constants.get(t) == Some(constant)).then(|| constant.clone())
+    }
+    _ => None,
+  }
+}
+
+fn fold_binary(op: BinaryOp, a: &Constant, b: &Constant) -> Option<Constant> {
+  match (a, b) {
+    (Constant::Int(a), Constant::Int(b)) => Some(match op {
+      BinaryOp::Add => Constant::Int(a.wrapping_add(*b)),
+      BinaryOp::Sub => Constant::Int(a.wrapping_sub(*b)),
+      BinaryOp::Mul => Constant::Int(a.wrapping_mul(*b)),
+      BinaryOp::Div if *b == 0 => return None,
+      BinaryOp::Div => Constant::Int(a.wrapping_div(*b)),
+      BinaryOp::Lt => Constant::Bool(a < b),
+      BinaryOp::Le => Constant::Bool(a <= b),
+      BinaryOp::Eq => Constant::Bool(a == b),
+    }),
+    (Constant::Bool(a), Constant::Bool(b)) if op == BinaryOp::Eq => Some(Constant::Bool(a == b)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::ConstantFolding;
+
+  #[test]
+  fn test_constant_folding() {
+    // The sum overflows, the division by zero is kept and the branch becomes a jump
+    check_pass(&ConstantFolding, "\
+fn Main.f(self %0 : Main) : Int {
+bb0:
+  %1 : int = const 2147483647
+  %2 : int = const 1
+  %3 : int = add %1, %2
+  %4 : int = const 0
+  %5 : int = div %3, %4
+  %6 : bool = lt %3, %4
+  %7 : bool = not %6
+  branch %7, bb1, bb2
+bb1:
+  %8 : Int = box %5
+  jump bb3
+bb2:
+  %9 : Int = box %3
+  jump bb3
+bb3:
+  %10 : Int = phi [bb1: %8, bb2: %9]
+  ret %10
+}
+", "\
+fn Main.f(self %0 : Main) : Int {
+bb0:
+  %1 : int = const 2147483647
+  %2 : int = const 1
+  %3 : int = const -2147483648
+  %4 : int = const 0
+  %5 : int = div %3, %4
+  %6 : bool = const true
+  %7 : bool = const false
+  jump bb1
+bb1:
+  %8 : Int = box %3
+  jump bb2
+bb2:
+  %9 : Int = phi [bb1: %8]
+  ret %9
+}
+");
+
+    // Boxed constants are seen through by unbox, is_void and obj_eq, self is always an IO but may not be an Int. The
+    // integer unboxed as a boolean, by a `case` branch which is never selected, is not folded.
+    check_pass(&ConstantFolding, "\
+fn Main.g(self %0 : Main) : Bool {
+bb0:
+  %1 : int = const -7
+  %2 : int = const 2
+  %3 : int = div %1, %2
+  %4 : Int = box %3
+  %5 : int = unbox %4
+  %6 : int = mul %5, %5
+  %7 : String = const \"a\\n\"
+  %8 : String = const \"a\\n\"
+  %9 : bool = obj_eq %7, %8
+  %10 : bool = is_void %4
+  %11 : bool = eq %9, %10
+  %12 : Bool = box %11
+  %13 : bool = instance_of %12, Object
+  %14 : bool = instance_of %0, IO
+  %15 : bool = instance_of %0, Int
+  %16 : bool = unbox %4
+  ret %12
+}
+", "\
+fn Main.g(self %0 : Main) : Bool {
+bb0:
+  %1 : int = const -7
+  %2 : int = const 2
+  %3 : int = const -3
+  %4 : Int = box %3
+  %5 : int = const -3
+  %6 : int = const 9
+  %7 : String = const \"a\\n\"
+  %8 : String = const \"a\\n\"
+  %9 : bool = const true
+  %10 : bool = const false
+  %11 : bool = const false
+  %12 : Bool = box %11
+  %13 : bool = const true
+  %14 : bool = const true
+  %15 : bool = instance_of %0, Int
+  %16 : bool = unbox %4
+  ret %12
+}
+");
+  }
+}
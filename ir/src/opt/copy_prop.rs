@@ -0,0 +1,142 @@
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::module::Module;
+use crate::model::Temp;
+use crate::opt::{replace_uses, type_conforms, Pass};
+use std::collections::HashMap;
+
+/// Replaces the uses of copies, and of phis merging a single value, by the copied value.
+/// Copies that narrow the static class, like the variable bound by a `case` branch, are kept so dispatch and
+/// attribute accesses still see the class they were checked against.
+pub struct CopyPropagation;
+
+impl Pass for CopyPropagation {
+  fn name(&self) -> &'static str {
+    "copy-prop"
+  }
+
+  fn run_on_function(&self, module: &Module, function: &mut Function) -> bool {
+    let copies: HashMap<Temp, Temp> = function.blocks.iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|instruction| match (instruction.dest, &instruction.op) {
+          (Some(dest), Op::Copy(source)) => Some((dest, *source)),
+          _ => None,
+        })
+        .collect();
+    let mut replacements: HashMap<Temp, Temp> = HashMap::new();
+    // Replacing a copy can make a phi trivial, so this runs until no more copies are found
+    loop {
+      let mut found = false;
+      for block in &function.blocks {
+        for instruction in &block.instructions {
+          let Some(dest) = instruction.dest else {
+            continue;
+          };
+          if replacements.contains_key(&dest) {
+            continue;
+          }
+          let Some(mut source) = copied_value(&instruction.op, dest, &replacements) else {
+            continue;
+          };
+          // Through a narrowing copy, the original value may have a class the destination accepts
+          let dest_type = function.temp_type(dest);
+          while let Some(&next) = copies.get(&source).filter(|next| type_conforms(module, function.temp_type(**next), dest_type)) {
+            source = next;
+          }
+          if type_conforms(module, function.temp_type(source), dest_type) {
+            replacements.insert(dest, source);
+            found = true;
+          }
+        }
+      }
+      if !found {
+        break;
+      }
+    }
+
+    if replacements.is_empty() {
+      return false;
+    }
+    replace_uses(function, &replacements);
+    for block in &mut function.blocks {
+      block.instructions.retain(|instruction| instruction.dest.is_none_or(|dest| !replacements.contains_key(&dest)));
+    }
+    function.renumber();
+    true
+  }
+}
+
+/// The value a copy or a phi stands for, if it is always the same temporary
+fn copied_value(op: &Op, dest: Temp, replacements: &HashMap<Temp, Temp>) -> Option<Temp> {
+  let resolve = |mut temp: Temp| {
+    while let Some(&replacement) = replacements.get(&temp) {
+      temp = replacement;
+    }
+    temp
+  };
+
+  match op {
+    Op::Copy(source) => Some(resolve(*source)),
+    // A phi can refer to itself along a loop back edge
+    Op::Phi(incoming) => {
+      let mut values = incoming.iter().map(|(_, t)| resolve(*t)).filter(|t| *t != dest);
+      let first = values.next()?;
+      values.all(|t| t == first).then_some(first)
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::CopyPropagation;
+
+  #[test]
+  fn test_copy_propagation() {
+    // The phis only merge copies of %1 and become %1, the downcast to Int is kept
+    check_pass(&CopyPropagation, "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : Object = copy %1
+  %3 : bool = instance_of %1, Int
+  branch %3, bb1, bb2
+bb1:
+  %4 : Int = copy %1
+  %5 : Object = copy %4
+  jump bb3
+bb2:
+  %6 : Object = copy %2
+  jump bb3
+bb3:
+  %7 : Object = phi [bb1: %5, bb2: %6, bb4: %8]
+  %9 : bool = is_void %7
+  branch %9, bb4, bb5
+bb4:
+  %8 : Object = phi [bb3: %7]
+  jump bb3
+bb5:
+  %10 : IO = copy %0
+  ret %10
+}
+", "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : bool = instance_of %1, Int
+  branch %2, bb1, bb2
+bb1:
+  %3 : Int = copy %1
+  jump bb3
+bb2:
+  jump bb3
+bb3:
+  %4 : bool = is_void %1
+  branch %4, bb4, bb5
+bb4:
+  jump bb3
+bb5:
+  ret %0
+}
+");
+  }
+}
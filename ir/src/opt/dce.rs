@@ -0,0 +1,96 @@
+use crate::model::function::Function;
+use crate::model::module::Module;
+use crate::model::Temp;
+use crate::opt::{definitions, Pass};
+use std::collections::HashSet;
+
+/// Removes instructions whose value is never used and which have no side effect, including phis that are only
+/// used by each other around a loop
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+  fn name(&self) -> &'static str {
+    "dce"
+  }
+
+  fn run_on_function(&self, _module: &Module, function: &mut Function) -> bool {
+    // Values are live if a side effect or a terminator uses them, or if they are used to compute a live value
+    let mut worklist: Vec<Temp> = Vec::new();
+    for block in &function.blocks {
+      for instruction in block.instructions.iter().filter(|instruction| instruction.op.has_side_effects()) {
+        worklist.extend(instruction.op.operands());
+      }
+      worklist.extend(block.terminator.operands());
+    }
+
+    let definitions = definitions(function);
+    let mut live: HashSet<Temp> = HashSet::new();
+    while let Some(temp) = worklist.pop() {
+      if live.insert(temp) {
+        if let Some(op) = definitions.get(&temp) {
+          worklist.extend(op.operands());
+        }
+      }
+    }
+
+    let mut changed = false;
+    for block in &mut function.blocks {
+      let count = block.instructions.len();
+      block.instructions.retain(|instruction| {
+        instruction.op.has_side_effects() || instruction.dest.is_some_and(|dest| live.contains(&dest))
+      });
+      changed |= block.instructions.len() != count;
+    }
+    if changed {
+      function.renumber();
+    }
+    changed
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::DeadCodeElimination;
+
+  #[test]
+  fn test_dead_code_elimination() {
+    // The loop phis only feed each other, the unused call and division may have effects and are kept
+    check_pass(&DeadCodeElimination, "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = const 0
+  %3 : int = unbox %1
+  %4 : int = div %3, %2
+  %5 : int = call Main.sum(%0, %3)
+  %6 : String = const \"unused\"
+  jump bb1
+bb1:
+  %7 : Int = phi [bb0: %1, bb2: %9]
+  %8 : bool = lt %2, %3
+  branch %8, bb2, bb3
+bb2:
+  %9 : Int = copy %7
+  jump bb1
+bb3:
+  ret %1
+}
+", "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = const 0
+  %3 : int = unbox %1
+  %4 : int = div %3, %2
+  %5 : int = call Main.sum(%0, %3)
+  jump bb1
+bb1:
+  %6 : bool = lt %2, %3
+  branch %6, bb2, bb3
+bb2:
+  jump bb1
+bb3:
+  ret %1
+}
+");
+  }
+}
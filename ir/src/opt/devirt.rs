@@ -0,0 +1,101 @@
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::module::Module;
+use crate::model::{Constant, Temp};
+use crate::opt::Pass;
+use parser::model::class::STR_CLASS_NAME;
+use std::collections::{BTreeSet, HashMap};
+
+/// Turns dynamic dispatch into direct calls using class-hierarchy analysis.
+/// Tags number the class graph in pre-order, so the classes a receiver can have are the tags from its static class
+/// to the last of its descendants. If all of them share the implementation in the slot, or the receiver was just
+/// created with `new`, the dispatch always reaches the same function.
+pub struct Devirtualize;
+
+impl Pass for Devirtualize {
+  fn name(&self) -> &'static str {
+    "devirt"
+  }
+
+  fn run_on_function(&self, module: &Module, function: &mut Function) -> bool {
+    // Temporaries whose dynamic class is known exactly
+    let mut exact: HashMap<Temp, String> = HashMap::new();
+    for instruction in function.blocks.iter().flat_map(|block| &block.instructions) {
+      let class_name = match &instruction.op {
+        Op::New(class_name) => class_name.as_str(),
+        Op::Box(_) => function.temp_type(instruction.dest.expect("Boxes have a destination")).class_name(),
+        Op::Const(Constant::Str(_)) => STR_CLASS_NAME,
+        _ => continue,
+      };
+      if let Some(dest) = instruction.dest {
+        exact.insert(dest, class_name.to_string());
+      }
+    }
+
+    let mut changed = false;
+    let temps = &function.temps;
+    for instruction in function.blocks.iter_mut().flat_map(|block| &mut block.instructions) {
+      let Op::Dispatch { receiver, slot, args, .. } = &instruction.op else {
+        continue;
+      };
+      let class_name = exact.get(receiver).map_or(temps[receiver.index()].class_name(), String::as_str);
+      if let Some(target) = single_target(module, class_name, *slot, exact.contains_key(receiver)) {
+        let args = std::iter::once(*receiver).chain(args.iter().copied()).collect();
+        instruction.op = Op::Call { function: target, args };
+        changed = true;
+      }
+    }
+    changed
+  }
+}
+
+/// The function a dispatch through the slot reaches for every class the receiver can have
+fn single_target(module: &Module, class_name: &str, slot: usize, exact: bool) -> Option<String> {
+  let class = module.class(class_name)?;
+  let last_tag = if exact { class.tag } else { class.max_descendant_tag };
+  let targets: BTreeSet<&str> = module.classes[class.tag..=last_tag].iter()
+      .map(|class| class.dispatch_table.get(slot).map_or("", |target| target.function.as_str()))
+      .collect();
+  match targets.into_iter().collect::<Vec<&str>>().as_slice() {
+    [target] if !target.is_empty() => Some(target.to_string()),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::Devirtualize;
+
+  #[test]
+  fn test_devirtualize() {
+    // `describe` is overridden by Shouter so it stays dynamic on a Main, unless the object was just created
+    check_pass(&Devirtualize, "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : String = dispatch %1.type_name#1()
+  %3 : String = dispatch %0.describe#8(%1)
+  %4 : Object = dispatch %0.main#9()
+  %5 : Main = new Main
+  %6 : String = dispatch %5.describe#8(%2)
+  %7 : Shouter = new Shouter
+  %8 : String = dispatch %7.describe#8(%2)
+  %9 : String = dispatch %2.concat#4(%3)
+  ret %9
+}
+", "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : String = call Object.type_name(%1)
+  %3 : String = dispatch %0.describe#8(%1)
+  %4 : Object = call Main.main(%0)
+  %5 : Main = new Main
+  %6 : String = call Main.describe(%5, %2)
+  %7 : Shouter = new Shouter
+  %8 : String = call Shouter.describe(%7, %2)
+  %9 : String = call String.concat(%2, %3)
+  ret %9
+}
+");
+  }
+}
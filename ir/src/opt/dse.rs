@@ -0,0 +1,104 @@
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::module::Module;
+use crate::model::Temp;
+use crate::opt::Pass;
+use std::collections::{HashMap, HashSet};
+
+/// Removes attribute stores within a block that are overwritten before anything can read them, and stores of
+/// the value just loaded from the same attribute.
+/// Objects are only known to be the same if they are the same temporary, so a load of the attribute index from
+/// any object, or running any other code, ends a store's lifetime.
+pub struct DeadStoreElimination;
+
+impl Pass for DeadStoreElimination {
+  fn name(&self) -> &'static str {
+    "dse"
+  }
+
+  fn run_on_function(&self, _module: &Module, function: &mut Function) -> bool {
+    let mut changed = false;
+    for block in &mut function.blocks {
+      let mut dead = vec![false; block.instructions.len()];
+
+      // Backwards, attributes that are stored again before they are read
+      let mut overwritten: HashSet<(Temp, usize)> = HashSet::new();
+      for (position, instruction) in block.instructions.iter().enumerate().rev() {
+        match &instruction.op {
+          Op::SetAttr { object, index, .. } => dead[position] = !overwritten.insert((*object, *index)),
+          Op::GetAttr { index, .. } => overwritten.retain(|(_, stored)| stored != index),
+          op if runs_code(op) => overwritten.clear(),
+          _ => {}
+        }
+      }
+
+      // Forwards, the value each attribute is known to hold
+      let mut known: HashMap<(Temp, usize), Temp> = HashMap::new();
+      for (position, instruction) in block.instructions.iter().enumerate() {
+        if dead[position] {
+          continue;
+        }
+        match (&instruction.op, instruction.dest) {
+          (Op::GetAttr { object, index }, Some(dest)) => {
+            known.insert((*object, *index), dest);
+          }
+          (Op::SetAttr { object, index, value }, _) => {
+            if known.get(&(*object, *index)) == Some(value) {
+              dead[position] = true;
+            } else {
+              known.retain(|(_, stored), _| stored != index);
+              known.insert((*object, *index), *value);
+            }
+          }
+          (op, _) if runs_code(op) => known.clear(),
+          _ => {}
+        }
+      }
+
+      if dead.contains(&true) {
+        let mut dead = dead.into_iter();
+        block.instructions.retain(|_| !dead.next().unwrap_or(false));
+        changed = true;
+      }
+    }
+    changed
+  }
+}
+
+/// Returns `true` if the operation runs code that can read or write any attribute
+fn runs_code(op: &Op) -> bool {
+  matches!(op, Op::New(_) | Op::NewLike(_) | Op::Call { .. } | Op::Dispatch { .. })
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::DeadStoreElimination;
+
+  #[test]
+  fn test_dead_store_elimination() {
+    // The first store is overwritten, the second is read by the call and the last one stores the loaded value
+    check_pass(&DeadStoreElimination, "\
+fn Main.f(self %0 : Main, n %1 : int) : int {
+bb0:
+  %2 : int = get_attr %0, 0
+  set_attr %0, 0, %1
+  set_attr %0, 0, %2
+  %3 : Object = call Main.main(%0)
+  set_attr %0, 0, %1
+  %4 : int = get_attr %0, 0
+  set_attr %0, 0, %4
+  ret %4
+}
+", "\
+fn Main.f(self %0 : Main, n %1 : int) : int {
+bb0:
+  %2 : int = get_attr %0, 0
+  %3 : Object = call Main.main(%0)
+  set_attr %0, 0, %1
+  %4 : int = get_attr %0, 0
+  ret %4
+}
+");
+  }
+}
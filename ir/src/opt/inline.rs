@@ -0,0 +1,165 @@
+use crate::model::function::{Block, Function};
+use crate::model::instruction::{Instruction, Op, Terminator};
+use crate::model::module::Module;
+use crate::model::{BlockId, Temp};
+use crate::opt::Pass;
+use std::collections::HashMap;
+
+/// Largest callee inlined, in instructions including terminators
+const DEFAULT_MAX_INSTRUCTIONS: usize = 12;
+
+/// Replaces direct calls of small leaf functions, which do not call, dispatch or allocate objects with
+/// initialisers, by a copy of their body
+pub struct Inline {
+  pub max_instructions: usize,
+}
+
+impl Default for Inline {
+  fn default() -> Self {
+    Inline { max_instructions: DEFAULT_MAX_INSTRUCTIONS }
+  }
+}
+
+impl Pass for Inline {
+  fn name(&self) -> &'static str {
+    "inline"
+  }
+
+  fn run_on_function(&self, module: &Module, function: &mut Function) -> bool {
+    let mut changed = false;
+    // Inlined bodies have no calls, so each call site is visited once
+    let mut id = 0;
+    while id < function.blocks.len() {
+      let call_site = function.blocks[id].instructions.iter().enumerate().find_map(|(position, instruction)| match &instruction.op {
+        Op::Call { function: name, .. } => module.function(name).filter(|callee| self.is_candidate(callee, function)).map(|callee| (position, callee)),
+        _ => None,
+      });
+      match call_site {
+        Some((position, callee)) => {
+          inline_call(function, BlockId(id as u32), position, callee);
+          changed = true;
+        }
+        None => id += 1,
+      }
+    }
+
+    if changed {
+      function.renumber();
+    }
+    changed
+  }
+}
+
+impl Inline {
+  fn is_candidate(&self, callee: &Function, caller: &Function) -> bool {
+    !callee.is_builtin()
+        && callee.name != caller.name
+        && callee.instruction_count() <= self.max_instructions
+        && callee.blocks.iter().flat_map(|block| &block.instructions).all(|instruction| {
+          !matches!(instruction.op, Op::Call { .. } | Op::Dispatch { .. } | Op::New(_) | Op::NewLike(_))
+        })
+  }
+}
+
+/// Splits the block after the call, copies the arguments to the parameters of a copy of the callee's blocks
+/// and joins their return values in a phi at the start of the rest of the block
+fn inline_call(function: &mut Function, id: BlockId, position: usize, callee: &Function) {
+  let first_block = function.blocks.len() as u32;
+  let continuation = BlockId(first_block + callee.blocks.len() as u32);
+  let temps: HashMap<Temp, Temp> = (0..callee.temps.len() as u32).map(Temp)
+      .map(|temp| (temp, function.new_temp(callee.temp_type(temp).clone())))
+      .collect();
+
+  let block = function.block_mut(id);
+  let rest = block.instructions.split_off(position + 1);
+  let call = block.instructions.pop().expect("Call is in the block");
+  let Op::Call { args, .. } = &call.op else {
+    panic!("Only calls are inlined");
+  };
+  for ((_, param), arg) in callee.params.iter().zip(args) {
+    block.instructions.push(Instruction { dest: Some(temps[param]), op: Op::Copy(*arg), pos: call.pos });
+  }
+  let terminator = std::mem::replace(&mut block.terminator, Terminator::Jump(BlockId(first_block)));
+
+  let mut returns: Vec<(BlockId, Temp)> = Vec::new();
+  for (index, callee_block) in callee.blocks.iter().enumerate() {
+    let mut block = callee_block.clone();
+    for instruction in &mut block.instructions {
+      instruction.op.operands_mut().into_iter().for_each(|t| *t = temps[t]);
+      instruction.dest.iter_mut().for_each(|t| *t = temps[t]);
+      if let Op::Phi(incoming) = &mut instruction.op {
+        incoming.iter_mut().for_each(|(block, _)| *block = BlockId(first_block + block.0));
+      }
+    }
+    block.terminator.operands_mut().into_iter().for_each(|t| *t = temps[t]);
+    block.terminator.successors_mut().into_iter().for_each(|block| *block = BlockId(first_block + block.0));
+    if let Terminator::Return(value) = block.terminator {
+      returns.push((BlockId(first_block + index as u32), value));
+      block.terminator = Terminator::Jump(continuation);
+    }
+    function.blocks.push(block);
+  }
+
+  // Successors of the call's block now come after the continuation
+  for successor in terminator.successors() {
+    for instruction in &mut function.block_mut(successor).instructions {
+      if let Op::Phi(incoming) = &mut instruction.op {
+        incoming.iter_mut().filter(|(block, _)| *block == id).for_each(|(block, _)| *block = continuation);
+      }
+    }
+  }
+
+  let mut instructions: Vec<Instruction> = Vec::new();
+  if let Some(dest) = call.dest {
+    instructions.push(Instruction { dest: Some(dest), op: Op::Phi(returns), pos: call.pos });
+  }
+  instructions.extend(rest);
+  function.blocks.push(Block { instructions, terminator });
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::Inline;
+
+  #[test]
+  fn test_inline() {
+    // Shouter.describe is a leaf and is inlined, Main.describe dispatches and is kept
+    check_pass(&Inline::default(), "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : Shouter = new Shouter
+  %3 : bool = is_void %1
+  branch %3, bb1, bb2
+bb1:
+  %4 : String = call Shouter.describe(%2, %1)
+  %5 : String = call Main.describe(%0, %4)
+  jump bb2
+bb2:
+  %6 : Object = phi [bb0: %1, bb1: %5]
+  ret %6
+}
+", "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : Shouter = new Shouter
+  %3 : bool = is_void %1
+  branch %3, bb1, bb4
+bb1:
+  %4 : Shouter = copy %2
+  %5 : Object = copy %1
+  jump bb2
+bb2:
+  %6 : String = const \"LOUD\"
+  jump bb3
+bb3:
+  %7 : String = phi [bb2: %6]
+  %8 : String = call Main.describe(%0, %7)
+  jump bb4
+bb4:
+  %9 : Object = phi [bb0: %1, bb3: %8]
+  ret %9
+}
+");
+  }
+}
@@ -0,0 +1,290 @@
+mod const_fold;
+mod copy_prop;
+mod dce;
+mod devirt;
+mod dse;
+mod inline;
+mod simplify_cfg;
+mod unbox;
+mod void_checks;
+
+pub use const_fold::ConstantFolding;
+pub use copy_prop::CopyPropagation;
+pub use dce::DeadCodeElimination;
+pub use devirt::Devirtualize;
+pub use dse::DeadStoreElimination;
+pub use inline::Inline;
+pub use simplify_cfg::SimplifyCfg;
+pub use unbox::Unbox;
+pub use void_checks::RedundantVoidChecks;
+
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::module::Module;
+use crate::model::{Temp, Type};
+use crate::verify::{verify_module, Form};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Upper bound on the number of times the passes are repeated while one of them changes the module
+const MAX_ROUNDS: usize = 8;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum OptLevel {
+  /// No optimisation, the IR is printed or compiled as lowered
+  #[default]
+  O0,
+  /// Unboxing of integers and booleans and local simplifications that never make the code larger
+  O1,
+  /// Everything in `O1`, plus devirtualisation, inlining, dead-store elimination and block merging
+  O2,
+}
+
+impl FromStr for OptLevel {
+  type Err = String;
+
+  /// Parses the level of an `-O<level>` flag
+  fn from_str(level: &str) -> Result<Self, Self::Err> {
+    match level {
+      "0" => Ok(OptLevel::O0),
+      "1" => Ok(OptLevel::O1),
+      "2" => Ok(OptLevel::O2),
+      _ => Err(format!("Unknown optimisation level {level}, expected 0, 1 or 2")),
+    }
+  }
+}
+
+/// A transformation of the module preserving the behaviour of the program
+pub trait Pass {
+  fn name(&self) -> &'static str;
+
+  /// Runs the pass on each function with a body, returns `true` if the module changed
+  fn run(&self, module: &mut Module) -> bool {
+    let mut changed = false;
+    for index in 0..module.functions.len() {
+      if module.functions[index].is_builtin() {
+        continue;
+      }
+      // The function is taken out so the pass can look at the rest of the module
+      let mut function = module.functions[index].clone();
+      if self.run_on_function(module, &mut function) {
+        module.functions[index] = function;
+        changed = true;
+      }
+    }
+    changed
+  }
+
+  /// Returns `true` if the function changed
+  fn run_on_function(&self, module: &Module, function: &mut Function) -> bool;
+}
+
+/// Runs a list of passes in order, repeating them while they find something to change
+pub struct PassManager {
+  passes: Vec<Box<dyn Pass>>,
+  verify_each: bool,
+}
+
+impl PassManager {
+  pub fn new() -> Self {
+    PassManager { passes: Vec::new(), verify_each: cfg!(debug_assertions) }
+  }
+
+  pub fn for_level(level: OptLevel) -> Self {
+    let mut manager = PassManager::new();
+    if level >= OptLevel::O2 {
+      manager.add(Devirtualize).add(Inline::default());
+    }
+    if level >= OptLevel::O1 {
+      manager.add(Unbox).add(ConstantFolding).add(CopyPropagation).add(RedundantVoidChecks).add(DeadCodeElimination);
+    }
+    if level >= OptLevel::O2 {
+      manager.add(DeadStoreElimination).add(SimplifyCfg);
+    }
+    manager
+  }
+
+  pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+    self.passes.push(Box::new(pass));
+    self
+  }
+
+  /// Verifies the module after each pass that changed it, on by default in debug builds
+  pub fn verify_each(&mut self, verify_each: bool) -> &mut Self {
+    self.verify_each = verify_each;
+    self
+  }
+
+  pub fn pass_names(&self) -> Vec<&'static str> {
+    self.passes.iter().map(|pass| pass.name()).collect()
+  }
+
+  /// Optimises the module, fails if a pass produced invalid IR
+  pub fn run(&self, module: &mut Module) -> Result<(), String> {
+    for _ in 0..MAX_ROUNDS {
+      let mut changed = false;
+      for pass in &self.passes {
+        if !pass.run(module) {
+          continue;
+        }
+        changed = true;
+        if self.verify_each {
+          if let Err(errors) = verify_module(module, Form::Ssa) {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(format!("Invalid IR after {}:\n{}", pass.name(), errors.join("\n")));
+          }
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Default for PassManager {
+  fn default() -> Self {
+    PassManager::new()
+  }
+}
+
+/// Operation defining each temporary of the function, parameters have none
+fn definitions(function: &Function) -> HashMap<Temp, Op> {
+  function.blocks.iter()
+      .flat_map(|block| &block.instructions)
+      .filter_map(|instruction| instruction.dest.map(|dest| (dest, instruction.op.clone())))
+      .collect()
+}
+
+/// Replaces the uses of temporaries, following chains of replacements
+fn replace_uses(function: &mut Function, replacements: &HashMap<Temp, Temp>) {
+  let resolve = |mut temp: Temp| {
+    while let Some(&replacement) = replacements.get(&temp) {
+      temp = replacement;
+    }
+    temp
+  };
+
+  for block in &mut function.blocks {
+    for instruction in &mut block.instructions {
+      instruction.op.operands_mut().into_iter().for_each(|t| *t = resolve(*t));
+    }
+    block.terminator.operands_mut().into_iter().for_each(|t| *t = resolve(*t));
+  }
+}
+
+/// Returns `true` if a value of type `from` can be used where a value of type `to` is expected
+fn type_conforms(module: &Module, from: &Type, to: &Type) -> bool {
+  match (from, to) {
+    (Type::Object(from), Type::Object(to)) => from == to || module.conforms(from, to),
+    _ => from == to,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::interp::{run, run_profiled, RuntimeError, Stats};
+  use crate::lower::lower_program;
+  use crate::model::module::Module;
+  use crate::opt::{OptLevel, Pass, PassManager};
+  use crate::parse::parse_function;
+  use crate::verify::{verify_module, Form};
+  use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME};
+  use parser::programs::{find_programs, TestProgram};
+  use semantic::gen::{analyze_program, CheckOptions};
+  use std::path::Path;
+
+  fn lower_test_program(test_program: &TestProgram) -> Module {
+    let program = test_program.parse().expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    lower_program(&checked)
+  }
+
+  /// Runs the pass on a function written in IR, added to the module of `test_resources/ir/lowering.cl`,
+  /// and compares the result with the expected IR
+  pub(crate) fn check_pass(pass: &dyn Pass, before: &str, after: &str) {
+    let mut module = lower_test_program(&TestProgram::new(Path::new("../test_resources/ir/lowering.cl")));
+    let function = parse_function(before).unwrap_or_else(|e| panic!("Invalid test function: {e}"));
+    let name = function.name.clone();
+    module.functions.retain(|f| f.name != name);
+    module.functions.push(function);
+    verify_module(&module, Form::Ssa).unwrap_or_else(|e| panic!("Invalid test function: {e:?}"));
+
+    let changed = pass.run(&mut module);
+    verify_module(&module, Form::Ssa).unwrap_or_else(|e| panic!("Invalid IR after {}: {e:?}", pass.name()));
+    let result = module.function(&name).expect("Function must exist").to_string();
+    assert_eq!(result, after, "{} produced:\n{result}", pass.name());
+    assert_eq!(changed, before != after, "{} must report whether it changed the function", pass.name());
+  }
+
+  #[test]
+  fn test_levels() {
+    assert_eq!("2".parse::<OptLevel>(), Ok(OptLevel::O2));
+    assert_eq!("3".parse::<OptLevel>(), Err("Unknown optimisation level 3, expected 0, 1 or 2".to_string()));
+    assert!(PassManager::for_level(OptLevel::O0).pass_names().is_empty());
+    assert_eq!(PassManager::for_level(OptLevel::O1).pass_names(), vec!["unbox", "const-fold", "copy-prop", "void-checks", "dce"]);
+  }
+
+  #[test]
+  fn test_optimise_all_programs() {
+    for program in find_programs("../test_resources/programs") {
+      let name = program.name();
+      let module = lower_test_program(&program);
+      for level in [OptLevel::O1, OptLevel::O2] {
+        let mut optimised = module.clone();
+        PassManager::for_level(level).verify_each(true).run(&mut optimised).unwrap_or_else(|e| panic!("{name}: {e}"));
+        // Inlining may make the code larger
+        if level == OptLevel::O1 {
+          assert!(optimised.instruction_count() < module.instruction_count(), "{name} is not smaller at {level:?}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_optimisation_reduces_work() {
+    let programs = ["list.cl", "sort_list.cl", "cells.cl", "primes.cl", "hairyscary.cl", "book_list.cl", "arith.cl"];
+    for name in programs {
+      let program = TestProgram::new(&Path::new("../test_resources/programs").join(name));
+      let module = lower_test_program(&program);
+      let mut results: Vec<(String, Result<(), RuntimeError>, Stats)> = Vec::new();
+      for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let mut optimised = module.clone();
+        PassManager::for_level(level).run(&mut optimised).unwrap_or_else(|e| panic!("{name}: {e}"));
+        let mut output: Vec<u8> = Vec::new();
+        let (stats, result) = run(&optimised, &mut program.input.as_bytes(), &mut output);
+        println!("{name} {level:?}: {stats}");
+        results.push((String::from_utf8(output).expect("Output must be UTF-8"), result, stats));
+      }
+
+      let (output, result, _) = &results[0];
+      for (level, (level_output, level_result, _)) in results.iter().enumerate().skip(1) {
+        assert_eq!((level_output, level_result), (output, result), "{name} behaves differently at O{level}");
+      }
+      assert!(results[1].2.instructions < results[0].2.instructions, "{name} runs as many instructions at O1");
+      assert!(results[2].2.instructions < results[1].2.instructions, "{name} runs as many instructions at O2");
+      assert!(results[2].2.calls <= results[0].2.calls, "{name} makes more calls at O2");
+      if name.contains("list") {
+        assert!(results[2].2.calls < results[0].2.calls, "{name} makes as many calls at O2");
+      }
+      assert!(results[1].2.allocations <= results[0].2.allocations, "{name} allocates more at O1");
+      assert!(results[2].2.allocations <= results[0].2.allocations, "{name} allocates more at O2");
+    }
+  }
+
+  #[test]
+  fn test_int_and_bool_stay_raw() {
+    // Attributes, variables, arguments and results declared `Int` or `Bool` are raw, and these programs never store
+    // an integer or a boolean with a more general type, so they box none
+    for name in ["primes.cl", "arith.cl"] {
+      let program = TestProgram::new(&Path::new("../test_resources/programs").join(name));
+      let mut module = lower_test_program(&program);
+      PassManager::for_level(OptLevel::O2).run(&mut module).unwrap_or_else(|e| panic!("{name}: {e}"));
+      let (profile, _) = run_profiled(&module, &mut program.input.as_bytes(), &mut Vec::new());
+      let allocations = profile.allocations();
+      let boxes = allocations.iter().filter(|(class_name, _)| [INT_CLASS_NAME, BOOL_CLASS_NAME].contains(class_name));
+      assert_eq!(boxes.count(), 0, "{name} boxes values: {allocations:?}");
+    }
+  }
+}
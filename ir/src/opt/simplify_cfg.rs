@@ -0,0 +1,118 @@
+use crate::model::function::Function;
+use crate::model::instruction::{Op, Terminator};
+use crate::model::module::Module;
+use crate::model::BlockId;
+use crate::opt::Pass;
+
+/// Merges a block into its only predecessor when that predecessor jumps to it, and turns branches to the same
+/// block on both sides into jumps
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+  fn name(&self) -> &'static str {
+    "simplify-cfg"
+  }
+
+  fn run_on_function(&self, _module: &Module, function: &mut Function) -> bool {
+    let mut changed = false;
+    for block in &mut function.blocks {
+      if let Terminator::Branch { then_block, else_block, .. } = block.terminator {
+        if then_block == else_block {
+          block.terminator = Terminator::Jump(then_block);
+          changed = true;
+        }
+      }
+    }
+
+    while let Some((predecessor, block)) = mergeable(function) {
+      merge(function, predecessor, block);
+      // Drops the merged block, which is no longer reachable
+      function.renumber();
+      changed = true;
+    }
+    if changed {
+      function.renumber();
+    }
+    changed
+  }
+}
+
+/// A block and its only predecessor, which jumps to it
+fn mergeable(function: &Function) -> Option<(BlockId, BlockId)> {
+  let predecessors = function.predecessors();
+  function.block_ids().skip(1).find_map(|id| match predecessors[id.index()].as_slice() {
+    [predecessor] if *predecessor != id && function.block(*predecessor).terminator == Terminator::Jump(id) => Some((*predecessor, id)),
+    _ => None,
+  })
+}
+
+fn merge(function: &mut Function, predecessor: BlockId, id: BlockId) {
+  let mut instructions = std::mem::take(&mut function.block_mut(id).instructions);
+  // With a single predecessor, phis have a single value
+  for instruction in &mut instructions {
+    if let Op::Phi(incoming) = &instruction.op {
+      instruction.op = Op::Copy(incoming[0].1);
+    }
+  }
+  let terminator = function.block(id).terminator.clone();
+
+  for successor in terminator.successors() {
+    for instruction in &mut function.block_mut(successor).instructions {
+      if let Op::Phi(incoming) = &mut instruction.op {
+        for (block, _) in incoming.iter_mut().filter(|(block, _)| *block == id) {
+          *block = predecessor;
+        }
+      }
+    }
+  }
+
+  let target = function.block_mut(predecessor);
+  target.instructions.extend(instructions);
+  target.terminator = terminator;
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::SimplifyCfg;
+
+  #[test]
+  fn test_simplify_cfg() {
+    // The branch to bb1 on both sides becomes a jump, then the straight line of blocks is a single block
+    check_pass(&SimplifyCfg, "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = unbox %1
+  %3 : bool = is_void %1
+  branch %3, bb1, bb1
+bb1:
+  %4 : Int = phi [bb0: %1]
+  jump bb2
+bb2:
+  %5 : int = const 0
+  %6 : bool = lt %5, %2
+  branch %6, bb3, bb4
+bb3:
+  jump bb4
+bb4:
+  %7 : Int = phi [bb2: %1, bb3: %4]
+  ret %7
+}
+", "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = unbox %1
+  %3 : bool = is_void %1
+  %4 : Int = copy %1
+  %5 : int = const 0
+  %6 : bool = lt %5, %2
+  branch %6, bb1, bb2
+bb1:
+  jump bb2
+bb2:
+  %7 : Int = phi [bb0: %1, bb1: %4]
+  ret %7
+}
+");
+  }
+}
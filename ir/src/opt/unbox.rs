@@ -0,0 +1,340 @@
+use crate::model::function::Function;
+use crate::model::instruction::{BinaryOp, Instruction, Op, Terminator};
+use crate::model::module::Module;
+use crate::model::{BlockId, Pos, Temp, Type};
+use crate::opt::Pass;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME};
+use std::collections::{HashMap, HashSet};
+
+/// Keeps `Int` and `Bool` values raw inside a function.
+/// Lowering already keeps the variables, attributes, parameters and return values declared `Int` or `Bool` raw, so phis
+/// of boxes come from IR built otherwise, like the IR text format. A phi whose value is only unboxed, compared with
+/// `=`, merged again or returned merges the raw values instead: `unbox` of a known box reads the raw value and `=` on
+/// two integers or booleans compares raw values. A returned phi is boxed again, once per call, so it is only converted
+/// when all the boxes it merges are then unused, at least one of which ran before the return. Phis passed as arguments,
+/// stored in attributes, used as dispatch receivers or matched by `case` stay boxed, since boxing them at each use
+/// could allocate more than before.
+pub struct Unbox;
+
+impl Pass for Unbox {
+  fn name(&self) -> &'static str {
+    "unbox"
+  }
+
+  fn run_on_function(&self, _module: &Module, function: &mut Function) -> bool {
+    // Raw value of boxed temporaries: the operand of a box, or the raw phi replacing a phi of boxes
+    let mut raw: HashMap<Temp, Temp> = HashMap::new();
+    for instruction in function.blocks.iter().flat_map(|block| &block.instructions) {
+      if let (Some(dest), Op::Box(value)) = (instruction.dest, &instruction.op) {
+        raw.insert(dest, *value);
+      }
+    }
+    let converted = unboxed_phis(function);
+    for phi in &converted {
+      let raw_type = raw_type(function.temp_type(*phi)).expect("Only phis of raw values are converted");
+      raw.insert(*phi, function.new_temp(raw_type));
+    }
+
+    let mut rewriter = Rewriter { function, raw, converted, changed: false, at_block_end: Vec::new() };
+    rewriter.rewrite();
+    let Rewriter { changed, at_block_end, .. } = rewriter;
+    for (block, instruction) in at_block_end {
+      function.block_mut(block).instructions.push(instruction);
+    }
+    if changed {
+      function.renumber();
+    }
+    changed
+  }
+}
+
+/// Raw type of a boxed integer or boolean
+fn raw_type(value_type: &Type) -> Option<Type> {
+  match value_type.class_name() {
+    INT_CLASS_NAME if value_type.is_object() => Some(Type::Int),
+    BOOL_CLASS_NAME if value_type.is_object() => Some(Type::Bool),
+    _ => None,
+  }
+}
+
+/// Returns `true` if the instruction unboxes an `Int` as an integer or a `Bool` as a boolean. A `case` branch which is
+/// never selected may unbox an `Int` as a boolean or the reverse, which keeps reading the box.
+fn unboxes_raw(function: &Function, instruction: &Instruction) -> bool {
+  match (instruction.dest, &instruction.op) {
+    (Some(dest), Op::Unbox(value)) => raw_type(function.temp_type(*value)).as_ref() == Some(function.temp_type(dest)),
+    _ => false,
+  }
+}
+
+/// Phis of boxed integers or booleans whose value never has to be an object, except when it is returned and can be
+/// boxed again. Starts from all of them and drops those with another use until the remaining phis only feed each other.
+fn unboxed_phis(function: &Function) -> HashSet<Temp> {
+  let phis: HashMap<Temp, Vec<Temp>> = function.blocks.iter()
+      .flat_map(|block| &block.instructions)
+      .filter_map(|instruction| match (instruction.dest, &instruction.op) {
+        (Some(dest), Op::Phi(incoming)) if raw_type(function.temp_type(dest)).is_some() => Some((dest, incoming.iter().map(|(_, value)| *value).collect())),
+        _ => None,
+      })
+      .collect();
+  let mut candidates: HashSet<Temp> = phis.keys().copied().collect();
+
+  loop {
+    let mut boxed: HashSet<Temp> = HashSet::new();
+    let mut returned: Vec<Temp> = Vec::new();
+    for block in &function.blocks {
+      for instruction in &block.instructions {
+        match &instruction.op {
+          Op::Unbox(_) if unboxes_raw(function, instruction) => {}
+          Op::ObjEq(a, b) if raw_type(function.temp_type(*a)).is_some() && raw_type(function.temp_type(*a)) == raw_type(function.temp_type(*b)) => {}
+          Op::Phi(_) if instruction.dest.is_some_and(|dest| candidates.contains(&dest)) => {}
+          op => boxed.extend(op.operands()),
+        }
+      }
+      match block.terminator {
+        Terminator::Trap { value, .. } => {
+          boxed.insert(value);
+        }
+        Terminator::Return(value) => returned.push(value),
+        _ => {}
+      }
+    }
+    let reboxed = reboxed_phis(function, &phis, &candidates);
+    boxed.extend(returned.into_iter().filter(|value| !reboxed.contains(value)));
+
+    let count = candidates.len();
+    candidates.retain(|phi| !boxed.contains(phi));
+    if candidates.len() == count {
+      return candidates;
+    }
+  }
+}
+
+/// Candidate phis which may be boxed again when returned without allocating more than before: those merging only
+/// boxes left unused by the rewrite and phis like them. A call which returns one of them ran one of these boxes.
+fn reboxed_phis(function: &Function, phis: &HashMap<Temp, Vec<Temp>>, candidates: &HashSet<Temp>) -> HashSet<Temp> {
+  let dropped = dropped_boxes(function, candidates);
+  let mut reboxed = candidates.clone();
+  loop {
+    let count = reboxed.len();
+    let kept = reboxed.clone();
+    reboxed.retain(|phi| phis[phi].iter().all(|value| dropped.contains(value) || kept.contains(value)));
+    if reboxed.len() == count {
+      return reboxed;
+    }
+  }
+}
+
+/// Boxes which are only unboxed, merged by candidate phis or compared with them, so are unused once these are raw
+fn dropped_boxes(function: &Function, candidates: &HashSet<Temp>) -> HashSet<Temp> {
+  let mut dropped: HashSet<Temp> = function.blocks.iter()
+      .flat_map(|block| &block.instructions)
+      .filter(|instruction| matches!(instruction.op, Op::Box(_)))
+      .filter_map(|instruction| instruction.dest)
+      .collect();
+  for block in &function.blocks {
+    for instruction in &block.instructions {
+      match &instruction.op {
+        Op::Unbox(_) if unboxes_raw(function, instruction) => {}
+        Op::Phi(_) if instruction.dest.is_some_and(|dest| candidates.contains(&dest)) => {}
+        Op::ObjEq(a, b) if candidates.contains(a) || candidates.contains(b) => {}
+        op => {
+          for operand in op.operands() {
+            dropped.remove(&operand);
+          }
+        }
+      }
+    }
+    for operand in block.terminator.operands() {
+      dropped.remove(&operand);
+    }
+  }
+  dropped
+}
+
+struct Rewriter<'a> {
+  function: &'a mut Function,
+  raw: HashMap<Temp, Temp>,
+  converted: HashSet<Temp>,                   // phis of boxes, which are no longer defined
+  changed: bool,
+  at_block_end: Vec<(BlockId, Instruction)>, // unboxing of phi operands, added after the rewrite
+}
+
+impl Rewriter<'_> {
+  fn rewrite(&mut self) {
+    for id in self.function.block_ids().collect::<Vec<BlockId>>() {
+      let instructions = std::mem::take(&mut self.function.block_mut(id).instructions);
+      let mut rewritten: Vec<Instruction> = Vec::new();
+
+      for mut instruction in instructions {
+        let pos = instruction.pos;
+        let unboxes_raw = unboxes_raw(self.function, &instruction);
+        match &mut instruction.op {
+          Op::Phi(incoming) if instruction.dest.is_some_and(|dest| self.converted.contains(&dest)) => {
+            let dest = instruction.dest.expect("Phis have a destination");
+            for (block, value) in incoming.iter_mut() {
+              *value = self.raw_at_end(*block, *value, pos);
+            }
+            instruction.dest = Some(self.raw[&dest]);
+            self.changed = true;
+          }
+          Op::Unbox(value) if unboxes_raw && self.raw.contains_key(value) => {
+            instruction.op = Op::Copy(self.raw[value]);
+            self.changed = true;
+          }
+          Op::ObjEq(a, b) if self.converted.contains(a) || self.converted.contains(b) => {
+            let (a, b) = (*a, *b);
+            let a = self.raw_before(&mut rewritten, a, pos);
+            let b = self.raw_before(&mut rewritten, b, pos);
+            instruction.op = Op::Binary(BinaryOp::Eq, a, b);
+            self.changed = true;
+          }
+          _ => {}
+        }
+        rewritten.push(instruction);
+      }
+
+      if let Terminator::Return(value) = self.function.block(id).terminator {
+        if self.converted.contains(&value) {
+          let boxed = self.function.new_temp(self.function.temp_type(value).clone());
+          rewritten.push(Instruction { dest: Some(boxed), op: Op::Box(self.raw[&value]), pos: (0, 0) });
+          self.function.block_mut(id).terminator = Terminator::Return(boxed);
+        }
+      }
+      self.function.block_mut(id).instructions = rewritten;
+    }
+  }
+
+  /// Raw value of a boxed integer or boolean at the end of a predecessor, unboxing it there if it is not known
+  fn raw_at_end(&mut self, block: BlockId, value: Temp, pos: Pos) -> Temp {
+    if let Some(raw) = self.raw.get(&value) {
+      return *raw;
+    }
+    let raw_type = raw_type(self.function.temp_type(value)).expect("Only boxes of raw values are unboxed");
+    let raw = self.function.new_temp(raw_type);
+    self.at_block_end.push((block, Instruction { dest: Some(raw), op: Op::Unbox(value), pos }));
+    raw
+  }
+
+  /// Raw value of a boxed integer or boolean, unboxing it before the current instruction if it is not known
+  fn raw_before(&mut self, rewritten: &mut Vec<Instruction>, value: Temp, pos: Pos) -> Temp {
+    if let Some(raw) = self.raw.get(&value) {
+      return *raw;
+    }
+    let raw_type = raw_type(self.function.temp_type(value)).expect("Only boxes of raw values are unboxed");
+    let raw = self.function.new_temp(raw_type);
+    rewritten.push(Instruction { dest: Some(raw), op: Op::Unbox(value), pos });
+    raw
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::Unbox;
+
+  #[test]
+  fn test_unbox() {
+    // The loop merges raw integers, the total is boxed once when it is returned
+    check_pass(&Unbox, "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = const 0
+  %3 : Int = box %2
+  jump bb1
+bb1:
+  %4 : Int = phi [bb0: %1, bb2: %16]
+  %5 : Int = phi [bb0: %3, bb2: %12]
+  %6 : int = const 0
+  %7 : int = unbox %4
+  %8 : bool = lt %6, %7
+  branch %8, bb2, bb3
+bb2:
+  %9 : int = unbox %5
+  %10 : int = unbox %4
+  %11 : int = add %9, %10
+  %12 : Int = box %11
+  %13 : int = unbox %4
+  %14 : int = const 1
+  %15 : int = sub %13, %14
+  %16 : Int = box %15
+  jump bb1
+bb3:
+  %17 : bool = obj_eq %5, %1
+  ret %5
+}
+", "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = const 0
+  %3 : Int = box %2
+  %4 : int = unbox %1
+  jump bb1
+bb1:
+  %5 : int = phi [bb0: %4, bb2: %16]
+  %6 : int = phi [bb0: %2, bb2: %12]
+  %7 : int = const 0
+  %8 : int = copy %5
+  %9 : bool = lt %7, %8
+  branch %9, bb2, bb3
+bb2:
+  %10 : int = copy %6
+  %11 : int = copy %5
+  %12 : int = add %10, %11
+  %13 : Int = box %12
+  %14 : int = copy %5
+  %15 : int = const 1
+  %16 : int = sub %14, %15
+  %17 : Int = box %16
+  jump bb1
+bb3:
+  %18 : int = unbox %1
+  %19 : bool = eq %6, %18
+  %20 : Int = box %6
+  ret %20
+}
+");
+
+    // The phi merges the parameter, already a box: boxing the phi again when it is returned would allocate once more
+    // per call than the box of the other branch
+    let unchanged = "\
+fn Main.f(self %0 : Main, n %1 : Int) : Int {
+bb0:
+  %2 : int = unbox %1
+  %3 : int = const 0
+  %4 : bool = lt %3, %2
+  branch %4, bb1, bb2
+bb1:
+  jump bb3
+bb2:
+  %5 : Int = box %3
+  jump bb3
+bb3:
+  %6 : Int = phi [bb1: %1, bb2: %5]
+  %7 : int = unbox %6
+  ret %6
+}
+";
+    check_pass(&Unbox, unchanged, unchanged);
+
+    // A `case` branch which is never selected unboxes the integer as a boolean, which keeps reading the box
+    check_pass(&Unbox, "\
+fn Main.f(self %0 : Main) : bool {
+bb0:
+  %1 : int = const 10
+  %2 : Int = box %1
+  %3 : int = unbox %2
+  %4 : bool = unbox %2
+  ret %4
+}
+", "\
+fn Main.f(self %0 : Main) : bool {
+bb0:
+  %1 : int = const 10
+  %2 : Int = box %1
+  %3 : int = copy %1
+  %4 : bool = unbox %2
+  ret %4
+}
+");
+  }
+}
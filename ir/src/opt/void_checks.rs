@@ -0,0 +1,151 @@
+use crate::analysis::dominators::Dominators;
+use crate::analysis::reverse_post_order;
+use crate::model::function::Function;
+use crate::model::instruction::Op;
+use crate::model::module::Module;
+use crate::model::{BlockId, Constant, Temp};
+use crate::opt::Pass;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashSet;
+
+/// Removes void checks of values that cannot be void: `self`, new objects, boxes, values of the basic classes
+/// `Int`, `Bool` and `String`, and values already checked on every path. `is_void` of such values becomes `false`.
+pub struct RedundantVoidChecks;
+
+impl Pass for RedundantVoidChecks {
+  fn name(&self) -> &'static str {
+    "void-checks"
+  }
+
+  fn run_on_function(&self, _module: &Module, function: &mut Function) -> bool {
+    let non_void = non_void_temps(function);
+    let dominators = Dominators::new(function);
+    let mut checked: Vec<(Temp, BlockId)> = Vec::new();
+    let mut changed = false;
+
+    // A block comes after its dominators in reverse post-order
+    for id in reverse_post_order(function) {
+      let block = function.block_mut(id);
+      let mut redundant = vec![false; block.instructions.len()];
+      for (position, instruction) in block.instructions.iter_mut().enumerate() {
+        match &instruction.op {
+          Op::CheckVoid(t, _) => {
+            if non_void.contains(t) || checked.iter().any(|(c, block)| c == t && dominators.dominates(*block, id)) {
+              redundant[position] = true;
+            } else {
+              checked.push((*t, id));
+            }
+          }
+          Op::IsVoid(t) if non_void.contains(t) => {
+            instruction.op = Op::Const(Constant::Bool(false));
+            changed = true;
+          }
+          _ => {}
+        }
+      }
+
+      if redundant.contains(&true) {
+        let mut redundant = redundant.into_iter();
+        block.instructions.retain(|_| !redundant.next().unwrap_or(false));
+        changed = true;
+      }
+    }
+    changed
+  }
+}
+
+/// Temporaries that always hold an object. Copies and phis are assumed to be non-void until one of their operands
+/// may be void, so values passed around a loop are found.
+fn non_void_temps(function: &Function) -> HashSet<Temp> {
+  let mut non_void: HashSet<Temp> = function.params.first().map(|(_, self_temp)| *self_temp).into_iter().collect();
+  // Variables and attributes of the basic classes start with a default value instead of void
+  non_void.extend((0..function.temps.len() as u32).map(Temp).filter(|temp| {
+    let temp_type = function.temp_type(*temp);
+    temp_type.is_object() && [INT_CLASS_NAME, BOOL_CLASS_NAME, STR_CLASS_NAME].contains(&temp_type.class_name())
+  }));
+  let mut assumed: Vec<(Temp, Vec<Temp>)> = Vec::new();
+  for instruction in function.blocks.iter().flat_map(|block| &block.instructions) {
+    let Some(dest) = instruction.dest else {
+      continue;
+    };
+    match &instruction.op {
+      Op::New(_) | Op::NewLike(_) | Op::Box(_) | Op::Const(Constant::Str(_)) => {
+        non_void.insert(dest);
+      }
+      Op::Copy(_) | Op::Phi(_) => {
+        non_void.insert(dest);
+        assumed.push((dest, instruction.op.operands()));
+      }
+      _ => {}
+    }
+  }
+
+  loop {
+    let count = non_void.len();
+    for (temp, operands) in &assumed {
+      if !operands.iter().all(|operand| non_void.contains(operand)) {
+        non_void.remove(temp);
+      }
+    }
+    if non_void.len() == count {
+      return non_void;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::opt::test::check_pass;
+  use crate::opt::RedundantVoidChecks;
+
+  #[test]
+  fn test_redundant_void_checks() {
+    // Only the first check of the parameter is needed, the loop phi holds new objects or self
+    check_pass(&RedundantVoidChecks, "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  check_void %0, dispatch_on_void
+  %2 : Object = dispatch %0.abort#0()
+  %3 : Main = new Main
+  check_void %3, dispatch_on_void
+  %4 : Object = call Object.abort(%3)
+  check_void %1, case_on_void
+  jump bb1
+bb1:
+  %5 : Main = phi [bb0: %0, bb2: %6]
+  %7 : bool = is_void %5
+  %8 : bool = is_void %1
+  check_void %1, dispatch_on_void
+  branch %8, bb2, bb3
+bb2:
+  %6 : Main = new_like %5
+  jump bb1
+bb3:
+  %9 : Object = phi [bb1: %1]
+  check_void %9, dispatch_on_void
+  ret %5
+}
+", "\
+fn Main.f(self %0 : Main, x %1 : Object) : Object {
+bb0:
+  %2 : Object = dispatch %0.abort#0()
+  %3 : Main = new Main
+  %4 : Object = call Object.abort(%3)
+  check_void %1, case_on_void
+  jump bb1
+bb1:
+  %5 : Main = phi [bb0: %0, bb2: %6]
+  %7 : bool = const false
+  %8 : bool = is_void %1
+  branch %8, bb2, bb3
+bb2:
+  %6 : Main = new_like %5
+  jump bb1
+bb3:
+  %9 : Object = phi [bb1: %1]
+  check_void %9, dispatch_on_void
+  ret %5
+}
+");
+  }
+}
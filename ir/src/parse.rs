@@ -0,0 +1,461 @@
+use crate::model::function::{Block, Function};
+use crate::model::instruction::{BinaryOp, Instruction, Op, Terminator, TrapKind, UnaryOp};
+use crate::model::{BlockId, Constant, Temp, Type};
+
+/// Parses a function in the textual format of its `Display` implementation, e.g. to write IR by hand in tests.
+/// Errors are prefixed with the line number.
+pub fn parse_function(text: &str) -> Result<Function, String> {
+  let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())).filter(|(_, line)| !line.is_empty());
+
+  let (line_num, header) = lines.next().ok_or("Empty function")?;
+  let at_line = |line_num: usize| move |e: String| format!("{line_num}: {e}");
+  let mut function = parse_header(header).map_err(at_line(line_num))?;
+  if function.is_builtin() {
+    return Ok(function.finish());
+  }
+
+  let mut current: Option<(Vec<Instruction>, u32)> = None;
+  let mut closed = false;
+  for (line_num, line) in lines {
+    let mut parse_line = || -> Result<(), String> {
+      if closed {
+        return Err(format!("Unexpected `{line}` after the end of the function"));
+      }
+      if line == "}" {
+        closed = true;
+        return match current {
+          Some(_) => Err("Block is not terminated".to_string()),
+          None => Ok(()),
+        };
+      }
+
+      if let Some(label) = line.strip_suffix(':') {
+        let id = Cursor::new(label).block()?;
+        if current.is_some() {
+          return Err("Block is not terminated".to_string());
+        }
+        if id.index() != function.blocks.len() {
+          return Err(format!("Expected block bb{}, found {id}", function.blocks.len()));
+        }
+        current = Some((Vec::new(), id.0));
+        return Ok(());
+      }
+
+      let (instructions, _) = current.as_mut().ok_or("Instruction outside of a block")?;
+      let mut cursor = Cursor::new(line);
+      if let Some(terminator) = cursor.terminator()? {
+        cursor.end()?;
+        let (instructions, _) = current.take().expect("Block is open");
+        function.blocks.push(Block { instructions, terminator });
+        return Ok(());
+      }
+
+      let instruction = cursor.instruction(&mut function.temps)?;
+      cursor.end()?;
+      instructions.push(instruction);
+      Ok(())
+    };
+    parse_line().map_err(at_line(line_num))?;
+  }
+
+  if !closed {
+    return Err("Missing `}` at the end of the function".to_string());
+  }
+  let uses = function.blocks.iter()
+      .flat_map(|block| block.instructions.iter().flat_map(|instruction| instruction.op.operands()).chain(block.terminator.operands()));
+  for temp in uses {
+    if function.temps.get(temp.index()).is_none_or(Option::is_none) {
+      return Err(format!("Temporary {temp} is never defined"));
+    }
+  }
+  Ok(function.finish())
+}
+
+/// Function being parsed, temporaries get their type when they are defined
+struct PartialFunction {
+  name: String,
+  class_name: String,
+  params: Vec<(String, Temp)>,
+  ret_type: Type,
+  temps: Vec<Option<Type>>,
+  blocks: Vec<Block>,
+  builtin: bool,
+}
+
+impl PartialFunction {
+  fn is_builtin(&self) -> bool {
+    self.builtin
+  }
+
+  fn finish(self) -> Function {
+    // Numbers skipped in the text are unused temporaries
+    let temps = self.temps.into_iter().map(|t| t.unwrap_or(Type::Bool)).collect();
+    Function { name: self.name, class_name: self.class_name, params: self.params, ret_type: self.ret_type, temps, blocks: self.blocks, pos: (0, 0) }
+  }
+}
+
+/// `[declare] fn Class.method(self %0 : Class, x %1 : Int) : Type [{]`
+fn parse_header(header: &str) -> Result<PartialFunction, String> {
+  let mut cursor = Cursor::new(header);
+  let builtin = cursor.keyword("declare");
+  if !cursor.keyword("fn") {
+    return Err(format!("Expected a function, found `{header}`"));
+  }
+
+  let name = cursor.word()?;
+  let class_name = name.split_once('.').map(|(class_name, _)| class_name.to_string()).ok_or(format!("Function name {name} is not `Class.method`"))?;
+  cursor.punct("(")?;
+  let mut params: Vec<(String, Temp)> = Vec::new();
+  let mut temps: Vec<Option<Type>> = Vec::new();
+  while !cursor.try_punct(")") {
+    if !params.is_empty() {
+      cursor.punct(",")?;
+    }
+    let param_name = cursor.word()?;
+    let temp = cursor.temp()?;
+    cursor.punct(":")?;
+    let param_type = cursor.value_type()?;
+    define(&mut temps, temp, param_type)?;
+    params.push((param_name, temp));
+  }
+  cursor.punct(":")?;
+  let ret_type = cursor.value_type()?;
+  if !builtin {
+    cursor.punct("{")?;
+  }
+  cursor.end()?;
+
+  Ok(PartialFunction { name, class_name, params, ret_type, temps, blocks: Vec::new(), builtin })
+}
+
+fn define(temps: &mut Vec<Option<Type>>, temp: Temp, temp_type: Type) -> Result<(), String> {
+  if temps.len() <= temp.index() {
+    temps.resize(temp.index() + 1, None);
+  }
+  // Temporaries defined twice, i.e. outside of SSA form, must have the same type
+  match &temps[temp.index()] {
+    Some(existing) if *existing != temp_type => Err(format!("Temporary {temp} is defined with types {existing} and {temp_type}")),
+    _ => {
+      temps[temp.index()] = Some(temp_type);
+      Ok(())
+    }
+  }
+}
+
+struct Cursor<'a> {
+  rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+  fn new(line: &'a str) -> Self {
+    Cursor { rest: line.trim() }
+  }
+
+  fn skip_spaces(&mut self) {
+    self.rest = self.rest.trim_start();
+  }
+
+  fn end(&mut self) -> Result<(), String> {
+    self.skip_spaces();
+    if self.rest.is_empty() { Ok(()) } else { Err(format!("Unexpected `{}`", self.rest)) }
+  }
+
+  fn try_punct(&mut self, punct: &str) -> bool {
+    self.skip_spaces();
+    match self.rest.strip_prefix(punct) {
+      Some(rest) => {
+        self.rest = rest;
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn punct(&mut self, punct: &str) -> Result<(), String> {
+    if self.try_punct(punct) { Ok(()) } else { Err(format!("Expected `{punct}`, found `{}`", self.rest)) }
+  }
+
+  /// Identifiers, including dotted function names and the underscore of `_init`
+  fn word(&mut self) -> Result<String, String> {
+    self.skip_spaces();
+    let end = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.')).unwrap_or(self.rest.len());
+    if end == 0 {
+      return Err(format!("Expected a name, found `{}`", self.rest));
+    }
+    let (word, rest) = self.rest.split_at(end);
+    self.rest = rest;
+    Ok(word.to_string())
+  }
+
+  fn keyword(&mut self, keyword: &str) -> bool {
+    let saved = self.rest;
+    match self.word() {
+      Ok(word) if word == keyword => true,
+      _ => {
+        self.rest = saved;
+        false
+      }
+    }
+  }
+
+  fn number(&mut self) -> Result<i64, String> {
+    self.skip_spaces();
+    let negative = self.rest.starts_with('-');
+    let digits = if negative { &self.rest[1..] } else { self.rest };
+    let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    let value: i64 = digits[..end].parse().map_err(|_| format!("Expected a number, found `{}`", self.rest))?;
+    self.rest = &digits[end..];
+    Ok(if negative { -value } else { value })
+  }
+
+  fn index(&mut self) -> Result<usize, String> {
+    let value = self.number()?;
+    usize::try_from(value).map_err(|_| format!("Expected an index, found {value}"))
+  }
+
+  fn temp(&mut self) -> Result<Temp, String> {
+    self.punct("%")?;
+    let index = self.index()?;
+    Ok(Temp(index as u32))
+  }
+
+  fn block(&mut self) -> Result<BlockId, String> {
+    self.punct("bb")?;
+    let index = self.index()?;
+    Ok(BlockId(index as u32))
+  }
+
+  fn value_type(&mut self) -> Result<Type, String> {
+    Ok(match self.word()?.as_str() {
+      "int" => Type::Int,
+      "bool" => Type::Bool,
+      class_name => Type::object(class_name),
+    })
+  }
+
+  fn temps(&mut self) -> Result<Vec<Temp>, String> {
+    self.punct("(")?;
+    let mut temps: Vec<Temp> = Vec::new();
+    while !self.try_punct(")") {
+      if !temps.is_empty() {
+        self.punct(",")?;
+      }
+      temps.push(self.temp()?);
+    }
+    Ok(temps)
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.punct("\"")?;
+    let mut value = String::new();
+    let mut chars = self.rest.char_indices();
+    while let Some((index, c)) = chars.next() {
+      match c {
+        '"' => {
+          self.rest = &self.rest[index + 1..];
+          return Ok(value);
+        }
+        '\\' => value.push(match chars.next().map(|(_, c)| c) {
+          Some('n') => '\n',
+          Some('t') => '\t',
+          Some('r') => '\r',
+          Some('0') => '\0',
+          Some(c @ ('\\' | '"' | '\'')) => c,
+          other => return Err(format!("Unknown escape sequence \\{}", other.unwrap_or(' '))),
+        }),
+        c => value.push(c),
+      }
+    }
+    Err("Unterminated string".to_string())
+  }
+
+  fn terminator(&mut self) -> Result<Option<Terminator>, String> {
+    let saved = self.rest;
+    let terminator = match self.word().unwrap_or_default().as_str() {
+      "jump" => Terminator::Jump(self.block()?),
+      "branch" => {
+        let cond = self.temp()?;
+        self.punct(",")?;
+        let then_block = self.block()?;
+        self.punct(",")?;
+        let else_block = self.block()?;
+        Terminator::Branch { cond, then_block, else_block }
+      }
+      "ret" => Terminator::Return(self.temp()?),
+      "trap" => {
+        let kind = self.trap_kind()?;
+        Terminator::Trap { kind, value: self.temp()?, pos: (0, 0) }
+      }
+      _ => {
+        self.rest = saved;
+        return Ok(None);
+      }
+    };
+    Ok(Some(terminator))
+  }
+
+  fn trap_kind(&mut self) -> Result<TrapKind, String> {
+    match self.word()?.as_str() {
+      "dispatch_on_void" => Ok(TrapKind::DispatchOnVoid),
+      "case_on_void" => Ok(TrapKind::CaseOnVoid),
+      "case_no_match" => Ok(TrapKind::CaseNoMatch),
+      kind => Err(format!("Unknown trap {kind}")),
+    }
+  }
+
+  fn instruction(&mut self, temps: &mut Vec<Option<Type>>) -> Result<Instruction, String> {
+    self.skip_spaces();
+    let dest = if self.rest.starts_with('%') {
+      let dest = self.temp()?;
+      self.punct(":")?;
+      let dest_type = self.value_type()?;
+      self.punct("=")?;
+      define(temps, dest, dest_type)?;
+      Some(dest)
+    } else {
+      None
+    };
+
+    let op = self.op()?;
+    Ok(Instruction { dest, op, pos: (0, 0) })
+  }
+
+  fn op(&mut self) -> Result<Op, String> {
+    let name = self.word()?;
+    let binary = |op: BinaryOp, cursor: &mut Cursor| -> Result<Op, String> {
+      let left = cursor.temp()?;
+      cursor.punct(",")?;
+      Ok(Op::Binary(op, left, cursor.temp()?))
+    };
+
+    Ok(match name.as_str() {
+      "const" => {
+        self.skip_spaces();
+        let constant = if self.rest.starts_with('"') {
+          Constant::Str(self.string()?)
+        } else if self.keyword("true") {
+          Constant::Bool(true)
+        } else if self.keyword("false") {
+          Constant::Bool(false)
+        } else if self.keyword("void") {
+          Constant::Void
+        } else {
+          let value = self.number()?;
+          Constant::Int(i32::try_from(value).map_err(|_| format!("Constant {value} does not fit in an Int"))?)
+        };
+        Op::Const(constant)
+      }
+      "copy" => Op::Copy(self.temp()?),
+      "phi" => {
+        self.punct("[")?;
+        let mut incoming: Vec<(BlockId, Temp)> = Vec::new();
+        while !self.try_punct("]") {
+          if !incoming.is_empty() {
+            self.punct(",")?;
+          }
+          let block = self.block()?;
+          self.punct(":")?;
+          incoming.push((block, self.temp()?));
+        }
+        Op::Phi(incoming)
+      }
+      "neg" => Op::Unary(UnaryOp::Neg, self.temp()?),
+      "not" => Op::Unary(UnaryOp::Not, self.temp()?),
+      "add" => binary(BinaryOp::Add, self)?,
+      "sub" => binary(BinaryOp::Sub, self)?,
+      "mul" => binary(BinaryOp::Mul, self)?,
+      "div" => binary(BinaryOp::Div, self)?,
+      "lt" => binary(BinaryOp::Lt, self)?,
+      "le" => binary(BinaryOp::Le, self)?,
+      "eq" => binary(BinaryOp::Eq, self)?,
+      "obj_eq" => {
+        let left = self.temp()?;
+        self.punct(",")?;
+        Op::ObjEq(left, self.temp()?)
+      }
+      "is_void" => Op::IsVoid(self.temp()?),
+      "instance_of" => {
+        let value = self.temp()?;
+        self.punct(",")?;
+        Op::InstanceOf(value, self.word()?)
+      }
+      "box" => Op::Box(self.temp()?),
+      "unbox" => Op::Unbox(self.temp()?),
+      "new" => Op::New(self.word()?),
+      "new_like" => Op::NewLike(self.temp()?),
+      "get_attr" => {
+        let object = self.temp()?;
+        self.punct(",")?;
+        Op::GetAttr { object, index: self.index()? }
+      }
+      "set_attr" => {
+        let object = self.temp()?;
+        self.punct(",")?;
+        let index = self.index()?;
+        self.punct(",")?;
+        Op::SetAttr { object, index, value: self.temp()? }
+      }
+      "check_void" => {
+        let value = self.temp()?;
+        self.punct(",")?;
+        Op::CheckVoid(value, self.trap_kind()?)
+      }
+      "call" => {
+        let function = self.word()?;
+        Op::Call { function, args: self.temps()? }
+      }
+      "dispatch" => {
+        let receiver = self.temp()?;
+        self.punct(".")?;
+        let method = self.word()?;
+        self.punct("#")?;
+        let slot = self.index()?;
+        Op::Dispatch { receiver, method, slot, args: self.temps()? }
+      }
+      "debug_var" => {
+        let name = self.word()?;
+        self.punct("#")?;
+        let var = self.index()?;
+        self.punct(",")?;
+        let value = if self.keyword("end") { None } else { Some(self.temp()?) };
+        Op::DebugVar { name, var, value }
+      }
+      _ => return Err(format!("Unknown instruction {name}")),
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lower::{lower_program_with_options, LowerOptions};
+  use crate::parse::parse_function;
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_parse_round_trip() {
+    for path in ["../test_resources/ir/lowering.cl", "../test_resources/programs/hairyscary.cl"] {
+      let program = get_ast_from_file_path(path).expect("Couldn't parse file");
+      let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+      for debug_info in [false, true] {
+        let module = lower_program_with_options(&checked, &LowerOptions { debug_info });
+
+        for function in &module.functions {
+          let text = function.to_string();
+          let parsed = parse_function(&text).unwrap_or_else(|e| panic!("Couldn't parse {}: {e}\n{text}", function.name));
+          assert_eq!(parsed.to_string(), text);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let error = |text: &str| parse_function(text).expect_err("Function must be invalid");
+    assert_eq!(error("fn Main.f(self %0 : Main) : Int {\nbb0:\n  %1 : int = mod %0, %0\n  ret %1\n}"), "3: Unknown instruction mod");
+    assert_eq!(error("fn Main.f(self %0 : Main) : Int {\nbb0:\n  ret %0\nbb2:\n  ret %0\n}"), "4: Expected block bb1, found bb2");
+    assert_eq!(error("fn Main.f(self %0 : Main) : Int {\nbb0:\n  ret %3\n}"), "Temporary %3 is never defined");
+    assert_eq!(error("fn Main.f(self %0 : Main) : Int {\nbb0:\n  %1 : int = const 1\n}"), "4: Block is not terminated");
+    assert_eq!(error("fn f() : Int {"), "1: Function name f is not `Class.method`");
+  }
+}
@@ -0,0 +1,177 @@
+//! What the IR interpreter and the bytecode VM share to run programs alike: their errors, the limits they stop
+//! programs at and the built-ins reading input.
+
+use crate::model::function::Function;
+use crate::model::module::Module;
+use crate::model::Pos;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+use std::rc::{Rc, Weak};
+
+/// Deepest call stack before the program is stopped, COOL programs only loop through recursion or `while`
+pub const MAX_FRAMES: usize = 1_000_000;
+
+/// Most objects alive at once before the program runs out of memory, about a gigabyte of small objects
+pub const MAX_OBJECTS: usize = 10_000_000;
+
+/// Source position of the expression a runtime error comes from
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Location {
+  pub file: Option<String>, // file of the class of the method running, none if unknown
+  pub pos: Pos,
+}
+
+impl Location {
+  /// Location of a position in a function, in the file of its class. The native backends print it in their errors.
+  pub fn of(module: &Module, function: &Function, pos: Pos) -> Location {
+    let file = module.class(&function.class_name).and_then(|class| class.file.clone());
+    Location { file, pos }
+  }
+}
+
+impl Display for Location {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.file {
+      Some(file) => write!(f, "{file}:{}", self.pos.0),
+      None => write!(f, "line {}", self.pos.0),
+    }
+  }
+}
+
+/// Errors stopping a program, with the messages of the reference COOL runtime. The errors of an expression are
+/// located by the loop running the program, which knows the instruction running.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuntimeError {
+  Abort { class_name: String },
+  DispatchOnVoid { at: Option<Location> },
+  CaseOnVoid { at: Option<Location> },
+  CaseNoMatch { class_name: String, at: Option<Location> },
+  DivisionByZero { at: Option<Location> },
+  Substr { error: SubstrError, at: Option<Location> },
+  StackOverflow,
+  OutOfMemory { at: Option<Location> },
+  Io(String),
+}
+
+/// Arguments of `String.substr` out of the string
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SubstrError {
+  NegativeIndex,
+  NegativeLength,
+  IndexTooBig,
+  LengthTooLong,
+}
+
+impl SubstrError {
+  /// What is wrong with the arguments, none if the substring is in the string
+  pub fn check(start: i32, length: i32, string_length: usize) -> Option<SubstrError> {
+    if start < 0 {
+      Some(SubstrError::NegativeIndex)
+    } else if length < 0 {
+      Some(SubstrError::NegativeLength)
+    } else if start as usize > string_length {
+      Some(SubstrError::IndexTooBig)
+    } else if start as usize + length as usize > string_length {
+      Some(SubstrError::LengthTooLong)
+    } else {
+      None
+    }
+  }
+}
+
+impl RuntimeError {
+  /// Where the error comes from, none for the errors of the whole program and before it is located
+  pub fn location(&self) -> Option<&Location> {
+    match self {
+      RuntimeError::DispatchOnVoid { at } |
+      RuntimeError::CaseOnVoid { at } |
+      RuntimeError::CaseNoMatch { at, .. } |
+      RuntimeError::DivisionByZero { at } |
+      RuntimeError::Substr { at, .. } |
+      RuntimeError::OutOfMemory { at } => at.as_ref(),
+      _ => None,
+    }
+  }
+
+  /// The error, coming from the expression at the location if it was not located yet
+  pub fn located(mut self, location: impl FnOnce() -> Option<Location>) -> Self {
+    if let RuntimeError::DispatchOnVoid { at } |
+        RuntimeError::CaseOnVoid { at } |
+        RuntimeError::CaseNoMatch { at, .. } |
+        RuntimeError::DivisionByZero { at } |
+        RuntimeError::Substr { at, .. } |
+        RuntimeError::OutOfMemory { at } = &mut self {
+      if at.is_none() {
+        *at = location();
+      }
+    }
+    self
+  }
+}
+
+impl Display for RuntimeError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let prefix = |at: &Option<Location>| at.as_ref().map_or(String::new(), |at| format!("{at}: "));
+    match self {
+      RuntimeError::Abort { class_name } => write!(f, "Abort called from class {class_name}"),
+      RuntimeError::DispatchOnVoid { at } => write!(f, "{}Dispatch to void.", prefix(at)),
+      RuntimeError::CaseOnVoid { at } => write!(f, "{}Match on void in case statement.", prefix(at)),
+      RuntimeError::CaseNoMatch { class_name, at } => write!(f, "{}No match in case statement for Class {class_name}", prefix(at)),
+      RuntimeError::DivisionByZero { at } => write!(f, "{}Division by zero.", prefix(at)),
+      RuntimeError::Substr { error: SubstrError::NegativeIndex, at } => write!(f, "{}Index to substr is negative", prefix(at)),
+      RuntimeError::Substr { error: SubstrError::NegativeLength, at } => write!(f, "{}Length to substr is negative", prefix(at)),
+      RuntimeError::Substr { error: SubstrError::IndexTooBig, at } => write!(f, "{}Index to substr is too big", prefix(at)),
+      RuntimeError::Substr { error: SubstrError::LengthTooLong, at } => write!(f, "{}Length to substr too long", prefix(at)),
+      RuntimeError::StackOverflow => write!(f, "Stack overflow"),
+      RuntimeError::OutOfMemory { at } => write!(f, "{}Out of memory", prefix(at)),
+      RuntimeError::Io(e) => write!(f, "I/O error: {e}"),
+    }
+  }
+}
+
+/// Objects allocated by the program that may still be alive, counted against the limit from time to time
+pub struct Heap<T> {
+  objects: Vec<Weak<T>>,
+  pub limit: usize,
+  next_check: usize, // number of objects at which the dead ones are dropped and the live ones counted
+  pub exhausted: bool,
+}
+
+impl<T> Heap<T> {
+  pub fn new(limit: usize) -> Self {
+    Heap { objects: Vec::new(), limit, next_check: 1024, exhausted: false }
+  }
+
+  pub fn register(&mut self, object: &Rc<T>) {
+    self.objects.push(Rc::downgrade(object));
+    if self.objects.len() >= self.next_check {
+      self.objects.retain(|object| object.strong_count() > 0);
+      self.exhausted = self.objects.len() > self.limit;
+      self.next_check = (self.objects.len() * 2).max(1024);
+    }
+  }
+}
+
+/// A line of input without its line break, empty at the end of the input. The output is flushed first, for the
+/// prompts written before.
+pub fn read_line(input: &mut dyn BufRead, output: &mut dyn Write) -> Result<String, RuntimeError> {
+  output.flush().map_err(|e| RuntimeError::Io(e.to_string()))?;
+  let mut line = String::new();
+  input.read_line(&mut line).map_err(|e| RuntimeError::Io(e.to_string()))?;
+  if line.ends_with('\n') {
+    line.pop();
+    if line.ends_with('\r') {
+      line.pop();
+    }
+  }
+  Ok(line)
+}
+
+/// The integer at the start of the line, after blanks, 0 if there is none or it does not fit in an `Int`
+pub fn parse_int(line: &str) -> i32 {
+  let line = line.trim_start();
+  let digits = line.strip_prefix('-').unwrap_or(line);
+  let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+  let number = &line[..line.len() - digits.len() + end];
+  number.parse().unwrap_or(0)
+}
@@ -0,0 +1,496 @@
+use crate::analysis::dominators::Dominators;
+use crate::model::function::Function;
+use crate::model::instruction::{BinaryOp, Op, Terminator, UnaryOp};
+use crate::model::module::Module;
+use crate::model::{BlockId, Constant, Temp, Type};
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+/// Whether temporaries must be in SSA form, or may be assigned several times once phis have been replaced by copies
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Form {
+  Ssa,
+  Conventional,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerifyError {
+  pub function: Option<String>,
+  pub block: Option<BlockId>,
+  pub message: String,
+}
+
+impl Display for VerifyError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match (&self.function, self.block) {
+      (Some(function), Some(block)) => write!(f, "{function} {block}: {}", self.message),
+      (Some(function), None) => write!(f, "{function}: {}", self.message),
+      _ => write!(f, "{}", self.message),
+    }
+  }
+}
+
+/// Checks that the module is well formed: class tables and functions are consistent, every block of every function
+/// is terminated by a jump to existing blocks, temporaries are defined before they are used and operands have the
+/// types operations expect
+pub fn verify_module(module: &Module, form: Form) -> Result<(), Vec<VerifyError>> {
+  let mut errors: Vec<VerifyError> = Vec::new();
+  let mut module_error = |message: String| errors.push(VerifyError { function: None, block: None, message });
+
+  for (tag, class) in module.classes.iter().enumerate() {
+    if class.tag != tag {
+      module_error(format!("Class {} has tag {} at index {tag}", class.name, class.tag));
+    }
+    if class.max_descendant_tag < class.tag || class.max_descendant_tag >= module.classes.len() {
+      module_error(format!("Class {} has invalid descendant tags {}..={}", class.name, class.tag, class.max_descendant_tag));
+    }
+    if let Some(parent) = &class.parent {
+      if !module.conforms(&class.name, parent) {
+        module_error(format!("Class {} does not conform to its parent {parent}", class.name));
+      }
+    }
+    for slot in &class.dispatch_table {
+      if module.function(&slot.function).is_none() {
+        module_error(format!("Slot {} of class {} refers to undefined function {}", slot.method, class.name, slot.function));
+      }
+    }
+    if let Some(init) = &class.init {
+      if module.function(init).is_none() {
+        module_error(format!("Class {} has undefined initialiser {init}", class.name));
+      }
+    }
+  }
+
+  let mut names: HashSet<&str> = HashSet::new();
+  for function in &module.functions {
+    if !names.insert(&function.name) {
+      module_error(format!("Function {} is defined more than once", function.name));
+    }
+  }
+  if module.class(&module.entry_class).is_none() {
+    module_error(format!("Entry class {} is not defined", module.entry_class));
+  }
+  if module.function(&module.entry_function).is_none() {
+    module_error(format!("Entry function {} is not defined", module.entry_function));
+  }
+
+  for function in &module.functions {
+    errors.extend(verify_function(module, function, form));
+  }
+
+  if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+pub fn verify_function(module: &Module, function: &Function, form: Form) -> Vec<VerifyError> {
+  let mut verifier = FunctionVerifier { module, function, form, errors: Vec::new() };
+  verifier.verify();
+  verifier.errors
+}
+
+struct FunctionVerifier<'a> {
+  module: &'a Module,
+  function: &'a Function,
+  form: Form,
+  errors: Vec<VerifyError>,
+}
+
+/// Where a temporary is defined: a parameter, or the instruction at an index of a block
+#[derive(Debug, Clone, Copy)]
+enum Definition {
+  Param,
+  Instruction(BlockId, usize),
+}
+
+impl FunctionVerifier<'_> {
+  fn error(&mut self, block: Option<BlockId>, message: String) {
+    self.errors.push(VerifyError { function: Some(self.function.name.clone()), block, message });
+  }
+
+  fn verify(&mut self) {
+    let function = self.function;
+    let temp_count = function.temps.len();
+    if function.params.iter().any(|(_, param)| param.index() >= temp_count) {
+      self.error(None, "Parameter has no type".to_string());
+      return;
+    }
+    if function.is_builtin() {
+      return;
+    }
+
+    if !self.verify_structure() {
+      return;
+    }
+    let Some(definitions) = self.collect_definitions() else {
+      return;
+    };
+    self.verify_uses(&definitions);
+
+    for id in function.block_ids() {
+      let block = function.block(id);
+      for instruction in &block.instructions {
+        let dest_type = instruction.dest.map(|dest| function.temp_type(dest));
+        self.verify_op(id, &instruction.op, dest_type);
+      }
+      self.verify_terminator(id, &block.terminator);
+    }
+  }
+
+  /// Checks block targets, temporary numbers and the placement of phis, returns `false` if other checks cannot run
+  fn verify_structure(&mut self) -> bool {
+    let function = self.function;
+    let mut valid = true;
+    for id in function.block_ids() {
+      let block = function.block(id);
+      for successor in block.terminator.successors() {
+        if successor.index() >= function.blocks.len() {
+          self.error(Some(id), format!("Jump to undefined block {successor}"));
+          valid = false;
+        }
+      }
+
+      let temps = block.instructions.iter().flat_map(|i| i.op.operands().into_iter().chain(i.dest)).chain(block.terminator.operands());
+      if let Some(temp) = temps.into_iter().find(|temp| temp.index() >= function.temps.len()) {
+        self.error(Some(id), format!("Temporary {temp} has no type"));
+        valid = false;
+      }
+
+      let phi_count = block.instructions.iter().take_while(|i| i.is_phi()).count();
+      if block.instructions.iter().skip(phi_count).any(|i| i.is_phi()) {
+        self.error(Some(id), "Phi after a non-phi instruction".to_string());
+      }
+      if phi_count > 0 && (self.form == Form::Conventional || id == BlockId::ENTRY) {
+        self.error(Some(id), "Phi in a block without predecessors or outside of SSA form".to_string());
+      }
+
+      for instruction in &block.instructions {
+        let produces_value = !matches!(instruction.op, Op::SetAttr { .. } | Op::CheckVoid(..) | Op::DebugVar { .. });
+        if instruction.dest.is_some() != produces_value {
+          self.error(Some(id), format!("{} must {}have a destination", instruction.op, if produces_value { "" } else { "not " }));
+        }
+      }
+    }
+
+    if valid && !function.predecessors()[BlockId::ENTRY.index()].is_empty() {
+      self.error(Some(BlockId::ENTRY), "Entry block has predecessors".to_string());
+    }
+    valid
+  }
+
+  fn collect_definitions(&mut self) -> Option<Vec<Option<Definition>>> {
+    let function = self.function;
+    let mut definitions: Vec<Option<Definition>> = vec![None; function.temps.len()];
+    let mut valid = true;
+
+    for (_, param) in &function.params {
+      definitions[param.index()] = Some(Definition::Param);
+    }
+    for id in function.block_ids() {
+      for (index, instruction) in function.block(id).instructions.iter().enumerate() {
+        let Some(dest) = instruction.dest else {
+          continue;
+        };
+        if definitions[dest.index()].is_some() && self.form == Form::Ssa {
+          self.error(Some(id), format!("Temporary {dest} is defined more than once"));
+          valid = false;
+        }
+        definitions[dest.index()] = Some(Definition::Instruction(id, index));
+      }
+    }
+
+    valid.then_some(definitions)
+  }
+
+  fn verify_uses(&mut self, definitions: &[Option<Definition>]) {
+    let function = self.function;
+    let dominators = Dominators::new(function);
+    let predecessors = function.predecessors();
+
+    for id in function.block_ids() {
+      if !dominators.is_reachable(id) {
+        continue;
+      }
+      let block = function.block(id);
+
+      for (index, instruction) in block.instructions.iter().enumerate() {
+        if let Op::Phi(incoming) = &instruction.op {
+          let mut incoming_blocks: Vec<BlockId> = incoming.iter().map(|(block, _)| *block).collect();
+          let mut expected = predecessors[id.index()].clone();
+          incoming_blocks.sort();
+          expected.sort();
+          if incoming_blocks != expected {
+            self.error(Some(id), format!("{} does not have one value for each predecessor", instruction.op));
+          }
+          for &(predecessor, value) in incoming {
+            // A phi operand is used at the end of its predecessor
+            let end = function.blocks.get(predecessor.index()).map_or(0, |b| b.instructions.len());
+            self.verify_use(definitions, &dominators, value, predecessor, end);
+          }
+          continue;
+        }
+        for operand in instruction.op.operands() {
+          self.verify_use(definitions, &dominators, operand, id, index);
+        }
+      }
+      for operand in block.terminator.operands() {
+        self.verify_use(definitions, &dominators, operand, id, block.instructions.len());
+      }
+    }
+  }
+
+  /// Checks that the temporary is defined, and in SSA form that its definition dominates the use
+  fn verify_use(&mut self, definitions: &[Option<Definition>], dominators: &Dominators, temp: Temp, block: BlockId, index: usize) {
+    match definitions[temp.index()] {
+      None => self.error(Some(block), format!("Temporary {temp} is used but never defined")),
+      Some(Definition::Param) => {}
+      Some(Definition::Instruction(def_block, def_index)) => {
+        if self.form == Form::Conventional || !dominators.is_reachable(block) {
+          return;
+        }
+        let dominated = if def_block == block { def_index < index } else { dominators.dominates(def_block, block) };
+        if !dominated {
+          self.error(Some(block), format!("Definition of {temp} in {def_block} does not dominate its use"));
+        }
+      }
+    }
+  }
+
+  fn temp_type(&self, temp: Temp) -> &Type {
+    self.function.temp_type(temp)
+  }
+
+  fn expect(&mut self, block: BlockId, temp: Temp, expected: &Type, context: &str) {
+    if !self.temp_type(temp).same_kind(expected) {
+      let message = format!("{context} expects {} but {temp} has type {}", kind_name(expected), self.temp_type(temp));
+      self.error(Some(block), message);
+    }
+  }
+
+  fn expect_object(&mut self, block: BlockId, temp: Temp, context: &str) {
+    self.expect(block, temp, &Type::object(""), context);
+  }
+
+  fn expect_class(&mut self, block: BlockId, class_name: &str) {
+    if self.module.class(class_name).is_none() {
+      self.error(Some(block), format!("Class {class_name} is not defined"));
+    }
+  }
+
+  fn verify_op(&mut self, block: BlockId, op: &Op, dest_type: Option<&Type>) {
+    let context = op.to_string();
+    let context = context.as_str();
+    let result: Option<Type> = match op {
+      Op::Const(constant) => Some(match constant {
+        Constant::Int(_) => Type::Int,
+        Constant::Bool(_) => Type::Bool,
+        Constant::Str(_) => Type::object(STR_CLASS_NAME),
+        Constant::Void => Type::object(""),
+      }),
+      Op::Copy(t) => Some(self.temp_type(*t).clone()),
+      Op::Phi(incoming) => {
+        if let Some(dest_type) = dest_type {
+          for (_, value) in incoming {
+            self.expect(block, *value, dest_type, context);
+          }
+        }
+        dest_type.cloned()
+      }
+      Op::Unary(op, t) => {
+        let operand_type = if *op == UnaryOp::Neg { Type::Int } else { Type::Bool };
+        self.expect(block, *t, &operand_type, context);
+        Some(operand_type)
+      }
+      Op::Binary(op, a, b) => {
+        let operand_type = if *op == BinaryOp::Eq { self.temp_type(*a).clone() } else { Type::Int };
+        if operand_type.is_object() {
+          self.error(Some(block), format!("{context} compares objects, which needs obj_eq"));
+        }
+        self.expect(block, *a, &operand_type, context);
+        self.expect(block, *b, &operand_type, context);
+        Some(match op {
+          BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => Type::Int,
+          BinaryOp::Lt | BinaryOp::Le | BinaryOp::Eq => Type::Bool,
+        })
+      }
+      Op::ObjEq(a, b) => {
+        self.expect_object(block, *a, context);
+        self.expect_object(block, *b, context);
+        Some(Type::Bool)
+      }
+      Op::IsVoid(t) => {
+        self.expect_object(block, *t, context);
+        Some(Type::Bool)
+      }
+      Op::InstanceOf(t, class_name) => {
+        self.expect_object(block, *t, context);
+        self.expect_class(block, class_name);
+        Some(Type::Bool)
+      }
+      Op::Box(t) => {
+        let boxed = match self.temp_type(*t) {
+          Type::Int => INT_CLASS_NAME,
+          Type::Bool => BOOL_CLASS_NAME,
+          Type::Object(_) => {
+            self.error(Some(block), format!("{context} expects a raw value"));
+            ""
+          }
+        };
+        Some(Type::object(boxed))
+      }
+      Op::Unbox(t) => {
+        self.expect_object(block, *t, context);
+        if dest_type.is_some_and(Type::is_object) {
+          self.error(Some(block), format!("{context} must produce a raw value"));
+        }
+        dest_type.cloned()
+      }
+      Op::New(class_name) => {
+        self.expect_class(block, class_name);
+        Some(Type::object(class_name))
+      }
+      Op::NewLike(t) => {
+        self.expect_object(block, *t, context);
+        Some(Type::object(""))
+      }
+      Op::GetAttr { object, index } => self.verify_attribute(block, *object, *index, context),
+      Op::SetAttr { object, index, value } => {
+        if let Some(attr_type) = self.verify_attribute(block, *object, *index, context) {
+          self.expect(block, *value, &attr_type, context);
+        }
+        None
+      }
+      Op::CheckVoid(t, _) => {
+        self.expect_object(block, *t, context);
+        None
+      }
+      Op::DebugVar { .. } => None,
+      Op::Call { function, args } => {
+        match self.module.function(function) {
+          Some(callee) => self.verify_args(block, callee, args, context),
+          None => self.error(Some(block), format!("Call of undefined function {function}")),
+        }
+        self.module.function(function).map(|callee| callee.ret_type.clone())
+      }
+      Op::Dispatch { receiver, method, slot, args } => {
+        self.expect_object(block, *receiver, context);
+        let class_name = self.temp_type(*receiver).class_name().to_string();
+        let target = self.module.class(&class_name).and_then(|class| class.dispatch_table.get(*slot));
+        match target {
+          Some(target) if target.method == *method => {
+            let callee = self.module.function(&target.function);
+            if let Some(callee) = callee {
+              let all_args: Vec<Temp> = std::iter::once(*receiver).chain(args.iter().copied()).collect();
+              self.verify_args(block, callee, &all_args, context);
+            }
+            callee.map(|callee| callee.ret_type.clone())
+          }
+          _ => {
+            self.error(Some(block), format!("{context} does not match slot {slot} of class {class_name}"));
+            None
+          }
+        }
+      }
+    };
+
+    if let (Some(result), Some(dest_type)) = (result, dest_type) {
+      if !result.same_kind(dest_type) {
+        self.error(Some(block), format!("{context} produces {} but its destination has type {dest_type}", kind_name(&result)));
+      }
+    }
+  }
+
+  /// Type of the attribute, none if the object has no such attribute
+  fn verify_attribute(&mut self, block: BlockId, object: Temp, index: usize, context: &str) -> Option<Type> {
+    self.expect_object(block, object, context);
+    let class_name = self.temp_type(object).class_name().to_string();
+    match self.module.class(&class_name) {
+      Some(class) if index < class.attributes.len() => return Some(class.attributes[index].attr_type.clone()),
+      Some(_) => self.error(Some(block), format!("{context} is out of the attributes of class {class_name}")),
+      None => self.expect_class(block, &class_name),
+    }
+    None
+  }
+
+  fn verify_args(&mut self, block: BlockId, callee: &Function, args: &[Temp], context: &str) {
+    if callee.params.len() != args.len() {
+      self.error(Some(block), format!("{context} passes {} arguments to {} which takes {}", args.len(), callee.name, callee.params.len()));
+      return;
+    }
+    for (arg, (_, param)) in args.iter().zip(&callee.params) {
+      self.expect(block, *arg, callee.temp_type(*param), context);
+    }
+  }
+
+  fn verify_terminator(&mut self, block: BlockId, terminator: &Terminator) {
+    let context = terminator.to_string();
+    match terminator {
+      Terminator::Jump(_) => {}
+      Terminator::Branch { cond, .. } => self.expect(block, *cond, &Type::Bool, &context),
+      Terminator::Return(value) => {
+        let ret_type = self.function.ret_type.clone();
+        self.expect(block, *value, &ret_type, &context);
+      }
+      Terminator::Trap { value, .. } => self.expect_object(block, *value, &context),
+    }
+  }
+}
+
+fn kind_name(value_type: &Type) -> &'static str {
+  match value_type {
+    Type::Int => "int",
+    Type::Bool => "bool",
+    Type::Object(_) => "an object",
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lower::lower_program;
+  use crate::model::instruction::{Op, Terminator};
+  use crate::model::{BlockId, Temp, Type};
+  use crate::verify::{verify_module, Form};
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_verify_errors() {
+    let program = get_ast_from_file_path("../test_resources/ir/lowering.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    verify_module(&module, Form::Ssa).expect("IR must be valid");
+
+    let errors = |edit: &dyn Fn(&mut crate::model::function::Function)| -> Vec<String> {
+      let mut module = module.clone();
+      edit(module.function_mut("Main.sum").expect("Function must exist"));
+      verify_module(&module, Form::Ssa).expect_err("IR must be invalid").iter().map(|e| e.to_string()).collect()
+    };
+
+    // `%6 : bool = lt %5, %3` returned by the loop exit
+    assert_eq!(errors(&|f| f.block_mut(BlockId(3)).terminator = Terminator::Return(Temp(6))), vec![
+      "Main.sum bb3: ret %6 expects int but %6 has type bool",
+    ]);
+    assert_eq!(errors(&|f| f.block_mut(BlockId(2)).instructions[0].op = Op::Binary(crate::model::instruction::BinaryOp::Add, Temp(4), Temp(0))), vec![
+      "Main.sum bb2: add %4, %0 expects int but %0 has type Main",
+    ]);
+    assert_eq!(errors(&|f| f.block_mut(BlockId(1)).instructions.swap(2, 3)), vec![
+      "Main.sum bb1: Definition of %5 in bb1 does not dominate its use",
+    ]);
+    assert_eq!(errors(&|f| f.block_mut(BlockId(1)).instructions[1].op = Op::Phi(vec![(BlockId(0), Temp(2))])), vec![
+      "Main.sum bb1: phi [bb0: %2] does not have one value for each predecessor",
+    ]);
+    assert_eq!(errors(&|f| f.block_mut(BlockId(2)).terminator = Terminator::Jump(BlockId(9))), vec![
+      "Main.sum bb2: Jump to undefined block bb9",
+    ]);
+    assert_eq!(errors(&|f| { f.block_mut(BlockId(2)).instructions[1].dest = Some(Temp(5)); }), vec![
+      "Main.sum bb2: Temporary %5 is defined more than once",
+    ]);
+    assert_eq!(errors(&|f| f.temps[10] = Type::Int), vec![
+      "Main.sum bb3: const void produces an object but its destination has type int",
+    ]);
+
+    let mut module = module.clone();
+    module.entry_function = "Main.start".to_string();
+    let errors = verify_module(&module, Form::Ssa).expect_err("IR must be invalid");
+    assert_eq!(errors[0].to_string(), "Entry function Main.start is not defined");
+  }
+}
@@ -5,21 +5,46 @@ use crate::model::constants::{
     PLUS, SEMI_COLON, STAR, TILDE,
 };
 use crate::model::token::Token;
+use std::fmt::{Debug, Formatter};
 use std::fs::File;
-use std::io::{BufReader, Bytes, Read, Seek, SeekFrom};
+use std::io::{BufReader, Bytes, Cursor, Read, Seek, SeekFrom};
 use std::iter::Peekable;
 
-#[derive(Debug)]
+/// Source of the program text, either a file or an in-memory program
+trait Source: Read + Seek {}
+impl<T: Read + Seek> Source for T {}
+
 pub(crate) struct CharIter {
-    bytes_iter: Peekable<Bytes<BufReader<File>>>,
+    bytes_iter: Peekable<Bytes<BufReader<Box<dyn Source>>>>,
     curr_char: char,
     line_num: u32,
     line_pos: u32,
 }
 
+impl Debug for CharIter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CharIter")
+            .field("curr_char", &self.curr_char)
+            .field("line_num", &self.line_num)
+            .field("line_pos", &self.line_pos)
+            .finish()
+    }
+}
+
 impl From<File> for CharIter {
     fn from(value: File) -> Self {
-        let mut buf_reader: BufReader<File> = BufReader::new(value);
+        CharIter::from_source(Box::new(value))
+    }
+}
+
+impl CharIter {
+    /// Reads the program from memory rather than from a file
+    pub(crate) fn from_program_text(program: &str) -> Self {
+        CharIter::from_source(Box::new(Cursor::new(program.as_bytes().to_vec())))
+    }
+
+    fn from_source(value: Box<dyn Source>) -> Self {
+        let mut buf_reader: BufReader<Box<dyn Source>> = BufReader::new(value);
 
         // Ignore byte order marker, if present. UTF-8 byte-order marker is first 3 bytes of file = [0xEF 0xBB 0xBF]
         let mut read_byte = [0; 3]; // Buffer to hold 3 bytes
@@ -38,7 +63,7 @@ impl From<File> for CharIter {
             }
         }
 
-        let bytes_iter: Peekable<Bytes<BufReader<File>>> = buf_reader.bytes().peekable();
+        let bytes_iter: Peekable<Bytes<BufReader<Box<dyn Source>>>> = buf_reader.bytes().peekable();
 
         CharIter {
             curr_char: NULL_CHAR,
@@ -184,7 +209,12 @@ impl Iterator for CharIter {
                 }
 
                 _ => {
-                    panic!("Unexpected char {c} at line {line_num} position {line_pos}")
+                    token = Token::Error {
+                        value: format!("Unexpected character {char_at}"),
+                        line_num,
+                        line_pos,
+                    };
+                    break;
                 }
             }
         }
@@ -245,7 +275,7 @@ impl CharIter {
                 }
 
                 Ok(byte) => {
-                    let char_at = byte as char;
+                    let char_at = self.decode_utf8(byte);
 
                     self.line_pos += 1;
                     self.curr_char = char_at;
@@ -270,6 +300,33 @@ impl CharIter {
         }
     }
 
+    /// The character starting with the byte, reading the rest of its UTF-8 encoding. Bytes which are not UTF-8 are
+    /// read as the replacement character.
+    fn decode_utf8(&mut self, first: u8) -> char {
+        let length = match first {
+            0x00..=0x7F => return first as char,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return char::REPLACEMENT_CHARACTER,
+        };
+
+        let mut bytes = vec![first];
+        while bytes.len() < length {
+            match self.bytes_iter.peek() {
+                Some(Ok(byte)) if byte & 0xC0 == 0x80 => {
+                    bytes.push(*byte);
+                    let _ = self.bytes_iter.next();
+                }
+                _ => break,
+            }
+        }
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
     // Returns the current position of iterator in the file, along with the last read character
     pub(crate) fn get_cur_pos(&self) -> (char, u32, u32) {
         (self.curr_char, self.line_num, self.line_pos)
@@ -383,17 +440,24 @@ impl CharIter {
 
     fn get_int(&mut self) -> Token {
         let (initial_digit, line_num, line_pos) = self.get_cur_pos();
-        let mut int_val = initial_digit as i32 - '0' as i32;
+        let mut int_val = Some(initial_digit as i32 - '0' as i32);
 
         while self.peek_is_digit() {
             let Some(ProgramChar { char_at, .. }) = self.next_char() else {
                 unreachable!()
             };
             let t = char_at as i32 - '0' as i32;
-            int_val *= 10;
-            int_val += t;
+            int_val = int_val.and_then(|v| v.checked_mul(10)).and_then(|v| v.checked_add(t));
         }
 
+        let Some(int_val) = int_val else {
+            return Token::Error {
+                value: String::from("Integer constant too large"),
+                line_num,
+                line_pos,
+            };
+        };
+
         Token::Int {
             value: int_val,
             line_num,
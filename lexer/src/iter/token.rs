@@ -91,13 +91,9 @@ pub trait BaseTokenIter: Iterator {
                 break; // reached the real end, accounted for all matching brackets
             }
 
-            let token = match self.next_token() {
-                None => {
-                    assert_eq!(seen_open_curl, 0);
-                    assert_eq!(seen_open_paren, 0);
-                    break;
-                }
-                Some(t) => t,
+            // Unmatched brackets are left to the parser, which finds the closing token missing
+            let Some(token) = self.next_token() else {
+                break;
             };
 
             match token {
@@ -117,7 +113,7 @@ pub trait BaseTokenIter: Iterator {
 
                 Token::EndCase { .. } => seen_start_case -= 1,
 
-                Token::Loop { .. } => seen_start_loop += 1,
+                Token::While { .. } => seen_start_loop += 1,
 
                 Token::EndLoop { .. } => seen_start_loop -= 1,
 
@@ -218,6 +214,17 @@ impl From<String> for TokenIter {
     }
 }
 
+impl TokenIter {
+    /// Lexes a program held in memory. `TokenIter::from(String)` reads the program from a file path instead.
+    pub fn from_program_text(program: &str) -> Self {
+        TokenIter {
+            char_iter: CharIter::from_program_text(program).peekable(),
+            last_line_num: 0,
+            last_line_pos: 0,
+        }
+    }
+}
+
 impl From<File> for TokenIter {
     fn from(value: File) -> Self {
         TokenIter {
@@ -0,0 +1,687 @@
+use crate::model::{AluOp, BranchCond, ImmOp, Instruction, LoadOp, MulDivOp, Program, Reg, ShiftOp, StoreOp, DATA_BASE, TEXT_BASE};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Assembles a program in the syntax of SPIM.
+///
+/// The text segment takes the instructions of MIPS32 without floating point, with the usual pseudo-instructions
+/// `li`, `la`, `move`, `b`, `beqz`, `bnez`, `blt`, `bgt`, `ble`, `bge`, `neg`, `negu`, `not`, `nop`, and `div` or
+/// `rem` with three operands. Branches have no delay slot, like SPIM by default. The data segment takes `.word`,
+/// `.half`, `.byte`, `.ascii`, `.asciiz`, `.space` and `.align`; `.word` and `.half` align their data and labels.
+/// Loads and stores also take a label as their address.
+///
+/// The program starts at `__start` if it defines it. Otherwise, like with SPIM's trap handler, a start routine is
+/// added that calls `main` and exits when it returns.
+pub fn assemble(source: &str) -> Result<Program, String> {
+  let mut assembler = Assembler::default();
+  for (index, line) in source.lines().enumerate() {
+    assembler.line(index + 1, line).map_err(|e| format!("Line {}: {e}", index + 1))?;
+  }
+  assembler.define_pending();
+  assembler.finish()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+enum Segment {
+  #[default]
+  Text,
+  Data,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Operand {
+  Reg(Reg),
+  Imm(i64),
+  /// A label plus an offset
+  Label(String, i64),
+  /// `offset(base)`
+  Mem(i64, Reg),
+}
+
+impl Display for Operand {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Operand::Reg(reg) => write!(f, "{reg}"),
+      Operand::Imm(value) => write!(f, "{value}"),
+      Operand::Label(label, 0) => write!(f, "{label}"),
+      Operand::Label(label, addend) => write!(f, "{label}{addend:+}"),
+      Operand::Mem(offset, base) => write!(f, "{offset}({base})"),
+    }
+  }
+}
+
+/// An instruction of the source, expanded once all labels are known
+struct TextItem {
+  line: usize,
+  mnemonic: String,
+  operands: Vec<Operand>,
+}
+
+/// A word or half word of the data segment holding the address of a label
+struct Fixup {
+  line: usize,
+  offset: usize,
+  size: usize,
+  label: String,
+  addend: i64,
+}
+
+#[derive(Default)]
+struct Assembler {
+  segment: Segment,
+  items: Vec<TextItem>,
+  text_size: u32, // instructions in the text segment, pseudo-instructions expanded
+  data: Vec<u8>,
+  fixups: Vec<Fixup>,
+  symbols: HashMap<String, u32>,
+  pending_labels: Vec<String>, // labels defined at the next instruction or data, once aligned
+}
+
+impl Assembler {
+  fn line(&mut self, line_number: usize, line: &str) -> Result<(), String> {
+    let mut rest = strip_comment(line).trim();
+    while let Some((label, after)) = split_label(rest) {
+      if self.symbols.contains_key(label) || self.pending_labels.iter().any(|pending| pending == label) {
+        return Err(format!("Label {label} is defined twice"));
+      }
+      self.pending_labels.push(label.to_string());
+      rest = after.trim_start();
+    }
+    if rest.is_empty() {
+      return Ok(());
+    }
+
+    let (name, arguments) = rest.split_once(char::is_whitespace).map_or((rest, ""), |(name, arguments)| (name, arguments.trim()));
+    if name.starts_with('.') {
+      self.directive(line_number, name, arguments)
+    } else {
+      self.instruction(line_number, name, arguments)
+    }
+  }
+
+  /// Defines the pending labels at the current position of the segment
+  fn define_pending(&mut self) {
+    let address = match self.segment {
+      Segment::Text => TEXT_BASE + 4 * self.text_size,
+      Segment::Data => DATA_BASE + self.data.len() as u32,
+    };
+    for label in self.pending_labels.drain(..) {
+      self.symbols.insert(label, address);
+    }
+  }
+
+  fn align(&mut self, alignment: usize) {
+    while !self.data.len().is_multiple_of(alignment) {
+      self.data.push(0);
+    }
+  }
+
+  fn data_segment(&self, directive: &str) -> Result<(), String> {
+    match self.segment {
+      Segment::Data => Ok(()),
+      Segment::Text => Err(format!("{directive} is only allowed in the data segment")),
+    }
+  }
+
+  fn directive(&mut self, line: usize, name: &str, arguments: &str) -> Result<(), String> {
+    match name {
+      ".text" | ".data" => {
+        self.define_pending();
+        self.segment = if name == ".text" { Segment::Text } else { Segment::Data };
+      }
+      ".globl" | ".extern" => {}
+      ".align" => {
+        let power = parse_integer(arguments).filter(|power| (0..=12).contains(power)).ok_or_else(|| format!("Invalid alignment {arguments}"))?;
+        if self.segment == Segment::Data {
+          self.align(1 << power);
+        }
+        self.define_pending();
+      }
+      ".word" | ".half" | ".byte" => {
+        self.data_segment(name)?;
+        let size = match name {
+          ".word" => 4,
+          ".half" => 2,
+          _ => 1,
+        };
+        self.align(size);
+        self.define_pending();
+        for value in split_operands(arguments) {
+          match parse_operand(&value)? {
+            Operand::Imm(value) => {
+              let bits = 8 * size as u32;
+              if value < -(1 << (bits - 1)) || value >= 1 << bits {
+                return Err(format!("Value {value} does not fit in {name}"));
+              }
+              self.data.extend(&(value as u32).to_le_bytes()[..size]);
+            }
+            Operand::Label(label, addend) if size > 1 => {
+              self.fixups.push(Fixup { line, offset: self.data.len(), size, label, addend });
+              self.data.extend(&[0, 0, 0, 0][..size]);
+            }
+            _ => return Err(format!("Invalid value {value} for {name}")),
+          }
+        }
+      }
+      ".ascii" | ".asciiz" => {
+        self.data_segment(name)?;
+        self.define_pending();
+        let string = parse_string(arguments)?;
+        self.data.extend(string);
+        if name == ".asciiz" {
+          self.data.push(0);
+        }
+      }
+      ".space" => {
+        self.data_segment(name)?;
+        self.define_pending();
+        let size = parse_integer(arguments).filter(|size| (0..1 << 24).contains(size)).ok_or_else(|| format!("Invalid size {arguments}"))?;
+        self.data.resize(self.data.len() + size as usize, 0);
+      }
+      _ => return Err(format!("Unknown directive {name}")),
+    }
+    Ok(())
+  }
+
+  fn instruction(&mut self, line: usize, mnemonic: &str, arguments: &str) -> Result<(), String> {
+    if self.segment != Segment::Text {
+      return Err(format!("Instruction {mnemonic} in the data segment"));
+    }
+    self.define_pending();
+    let operands = split_operands(arguments).iter().map(|operand| parse_operand(operand)).collect::<Result<Vec<Operand>, String>>()?;
+    let size = instruction_size(mnemonic, &operands);
+    self.items.push(TextItem { line, mnemonic: mnemonic.to_string(), operands });
+    self.text_size += size;
+    Ok(())
+  }
+
+  fn finish(mut self) -> Result<Program, String> {
+    if !self.symbols.contains_key("__start") {
+      let main = *self.symbols.get("main").ok_or("The program defines neither __start nor main")?;
+      let start = TEXT_BASE + 4 * self.text_size;
+      self.symbols.insert("__start".to_string(), start);
+      let items = [("jal", vec![Operand::Imm(main as i64)]), ("li", vec![Operand::Reg(Reg::V0), Operand::Imm(10)]), ("syscall", vec![])];
+      for (mnemonic, operands) in items {
+        self.text_size += instruction_size(mnemonic, &operands);
+        self.items.push(TextItem { line: 0, mnemonic: mnemonic.to_string(), operands });
+      }
+    }
+
+    for fixup in &self.fixups {
+      let address = resolve(&self.symbols, &fixup.label, fixup.addend).map_err(|e| format!("Line {}: {e}", fixup.line))?;
+      self.data[fixup.offset..fixup.offset + fixup.size].copy_from_slice(&address.to_le_bytes()[..fixup.size]);
+    }
+
+    let mut text = Vec::with_capacity(self.text_size as usize);
+    for item in &self.items {
+      let expanded = expand(item, &self.symbols).map_err(|e| format!("Line {}: {e}", item.line))?;
+      debug_assert_eq!(expanded.len() as u32, instruction_size(&item.mnemonic, &item.operands), "{} expands to its size", item.mnemonic);
+      text.extend(expanded);
+    }
+    let entry = self.symbols["__start"];
+    Ok(Program { text, data: self.data, entry, symbols: self.symbols })
+  }
+}
+
+/// Number of machine instructions of the instruction once expanded
+fn instruction_size(mnemonic: &str, operands: &[Operand]) -> u32 {
+  match (mnemonic, operands) {
+    ("li", [_, Operand::Imm(value)]) => if fits_signed(*value) || (0..=0xffff).contains(value) { 1 } else { 2 },
+    ("la", _) | ("blt" | "bgt" | "ble" | "bge", _) => 2,
+    ("div" | "divu" | "rem" | "remu", [_, _, _]) => 2,
+    ("lw" | "lh" | "lhu" | "lb" | "lbu" | "sw" | "sh" | "sb", [_, Operand::Label(..)]) => 2,
+    _ => 1,
+  }
+}
+
+fn fits_signed(value: i64) -> bool {
+  (-0x8000..=0x7fff).contains(&value)
+}
+
+fn resolve(symbols: &HashMap<String, u32>, label: &str, addend: i64) -> Result<u32, String> {
+  let address = symbols.get(label).ok_or_else(|| format!("Undefined label {label}"))?;
+  Ok((*address as i64 + addend) as u32)
+}
+
+/// The machine instructions of an instruction of the source
+fn expand(item: &TextItem, symbols: &HashMap<String, u32>) -> Result<Vec<Instruction>, String> {
+  let operands = Operands { operands: &item.operands, symbols };
+  let mnemonic = item.mnemonic.as_str();
+  let count = item.operands.len();
+  let expected = |n: usize| if count == n { Ok(()) } else { Err(format!("{mnemonic} takes {n} operands, found {count}")) };
+
+  let alu = match mnemonic {
+    "add" => Some(AluOp::Add),
+    "addu" => Some(AluOp::Addu),
+    "sub" => Some(AluOp::Sub),
+    "subu" => Some(AluOp::Subu),
+    "mul" => Some(AluOp::Mul),
+    "and" => Some(AluOp::And),
+    "or" => Some(AluOp::Or),
+    "xor" => Some(AluOp::Xor),
+    "nor" => Some(AluOp::Nor),
+    "slt" => Some(AluOp::Slt),
+    "sltu" => Some(AluOp::Sltu),
+    _ => None,
+  };
+  if let Some(op) = alu {
+    expected(3)?;
+    return Ok(vec![Instruction::Alu(op, operands.reg(0)?, operands.reg(1)?, operands.reg(2)?)]);
+  }
+
+  let imm = match mnemonic {
+    "addi" => Some((ImmOp::Addi, true)),
+    "addiu" => Some((ImmOp::Addiu, true)),
+    "slti" => Some((ImmOp::Slti, true)),
+    "sltiu" => Some((ImmOp::Sltiu, true)),
+    "andi" => Some((ImmOp::Andi, false)),
+    "ori" => Some((ImmOp::Ori, false)),
+    "xori" => Some((ImmOp::Xori, false)),
+    _ => None,
+  };
+  if let Some((op, signed)) = imm {
+    expected(3)?;
+    let value = if signed { operands.signed16(2)? } else { operands.unsigned16(2)? as i32 };
+    return Ok(vec![Instruction::AluImm(op, operands.reg(0)?, operands.reg(1)?, value)]);
+  }
+
+  let load = match mnemonic {
+    "lw" => Some(LoadOp::Lw),
+    "lh" => Some(LoadOp::Lh),
+    "lhu" => Some(LoadOp::Lhu),
+    "lb" => Some(LoadOp::Lb),
+    "lbu" => Some(LoadOp::Lbu),
+    _ => None,
+  };
+  if let Some(op) = load {
+    expected(2)?;
+    if let Operand::Label(..) = item.operands[1] {
+      let (upper, offset) = split_address(operands.address(1)?);
+      return Ok(vec![Instruction::Lui(Reg::AT, upper), Instruction::Load(op, operands.reg(0)?, offset, Reg::AT)]);
+    }
+    let (offset, base) = operands.mem(1)?;
+    return Ok(vec![Instruction::Load(op, operands.reg(0)?, offset, base)]);
+  }
+
+  let store = match mnemonic {
+    "sw" => Some(StoreOp::Sw),
+    "sh" => Some(StoreOp::Sh),
+    "sb" => Some(StoreOp::Sb),
+    _ => None,
+  };
+  if let Some(op) = store {
+    expected(2)?;
+    if let Operand::Label(..) = item.operands[1] {
+      let (upper, offset) = split_address(operands.address(1)?);
+      return Ok(vec![Instruction::Lui(Reg::AT, upper), Instruction::Store(op, operands.reg(0)?, offset, Reg::AT)]);
+    }
+    let (offset, base) = operands.mem(1)?;
+    return Ok(vec![Instruction::Store(op, operands.reg(0)?, offset, base)]);
+  }
+
+  let instructions = match mnemonic {
+    "sll" | "srl" | "sra" => {
+      expected(3)?;
+      let op = match mnemonic {
+        "sll" => ShiftOp::Sll,
+        "srl" => ShiftOp::Srl,
+        _ => ShiftOp::Sra,
+      };
+      let amount = operands.imm(2)?;
+      if !(0..32).contains(&amount) {
+        return Err(format!("Invalid shift amount {amount}"));
+      }
+      vec![Instruction::Shift(op, operands.reg(0)?, operands.reg(1)?, amount as u8)]
+    }
+    "lui" => {
+      expected(2)?;
+      vec![Instruction::Lui(operands.reg(0)?, operands.unsigned16(1)?)]
+    }
+    "mult" | "multu" | "div" | "divu" | "rem" | "remu" => {
+      let op = match mnemonic {
+        "mult" => MulDivOp::Mult,
+        "multu" => MulDivOp::Multu,
+        "div" | "rem" => MulDivOp::Div,
+        _ => MulDivOp::Divu,
+      };
+      match count {
+        2 if !mnemonic.starts_with("rem") => vec![Instruction::MulDiv(op, operands.reg(0)?, operands.reg(1)?)],
+        3 if !mnemonic.starts_with("mult") => {
+          let result = if mnemonic.starts_with("rem") { Instruction::Mfhi(operands.reg(0)?) } else { Instruction::Mflo(operands.reg(0)?) };
+          vec![Instruction::MulDiv(op, operands.reg(1)?, operands.reg(2)?), result]
+        }
+        _ => return Err(format!("{mnemonic} takes {} operands, found {count}", if mnemonic.starts_with("mult") { 2 } else { 3 })),
+      }
+    }
+    "mfhi" | "mflo" => {
+      expected(1)?;
+      vec![if mnemonic == "mfhi" { Instruction::Mfhi(operands.reg(0)?) } else { Instruction::Mflo(operands.reg(0)?) }]
+    }
+    "beq" | "bne" => {
+      expected(3)?;
+      let cond = if mnemonic == "beq" { BranchCond::Eq } else { BranchCond::Ne };
+      vec![Instruction::Branch(cond, operands.reg(0)?, operands.reg(1)?, operands.address(2)?)]
+    }
+    "blez" | "bgtz" | "bltz" | "bgez" | "beqz" | "bnez" => {
+      expected(2)?;
+      let cond = match mnemonic {
+        "blez" => BranchCond::Lez,
+        "bgtz" => BranchCond::Gtz,
+        "bltz" => BranchCond::Ltz,
+        "bgez" => BranchCond::Gez,
+        "beqz" => BranchCond::Eq,
+        _ => BranchCond::Ne,
+      };
+      vec![Instruction::Branch(cond, operands.reg(0)?, Reg::ZERO, operands.address(1)?)]
+    }
+    "blt" | "bgt" | "ble" | "bge" => {
+      expected(3)?;
+      let (a, b, target) = (operands.reg(0)?, operands.reg(1)?, operands.address(2)?);
+      let (left, right) = if matches!(mnemonic, "blt" | "bge") { (a, b) } else { (b, a) };
+      let cond = if matches!(mnemonic, "blt" | "bgt") { BranchCond::Ne } else { BranchCond::Eq };
+      vec![Instruction::Alu(AluOp::Slt, Reg::AT, left, right), Instruction::Branch(cond, Reg::AT, Reg::ZERO, target)]
+    }
+    "b" | "j" | "jal" => {
+      expected(1)?;
+      let target = operands.address(0)?;
+      match mnemonic {
+        "b" => vec![Instruction::Branch(BranchCond::Eq, Reg::ZERO, Reg::ZERO, target)],
+        "j" => vec![Instruction::Jump(target)],
+        _ => vec![Instruction::Jal(target)],
+      }
+    }
+    "jr" => {
+      expected(1)?;
+      vec![Instruction::Jr(operands.reg(0)?)]
+    }
+    "jalr" => match count {
+      1 => vec![Instruction::Jalr(Reg::RA, operands.reg(0)?)],
+      _ => {
+        expected(2)?;
+        vec![Instruction::Jalr(operands.reg(0)?, operands.reg(1)?)]
+      }
+    },
+    "syscall" | "break" | "nop" => {
+      expected(0)?;
+      vec![match mnemonic {
+        "syscall" => Instruction::Syscall,
+        "break" => Instruction::Break,
+        _ => Instruction::Shift(ShiftOp::Sll, Reg::ZERO, Reg::ZERO, 0),
+      }]
+    }
+    "li" => {
+      expected(2)?;
+      let (rd, value) = (operands.reg(0)?, operands.imm(1)?);
+      if !(-(1 << 31)..1 << 32).contains(&value) {
+        return Err(format!("Value {value} does not fit in a register"));
+      }
+      if fits_signed(value) {
+        vec![Instruction::AluImm(ImmOp::Addiu, rd, Reg::ZERO, value as i32)]
+      } else if (0..=0xffff).contains(&value) {
+        vec![Instruction::AluImm(ImmOp::Ori, rd, Reg::ZERO, value as i32)]
+      } else {
+        load_upper(rd, value as u32)
+      }
+    }
+    "la" => {
+      expected(2)?;
+      load_upper(operands.reg(0)?, operands.address(1)?)
+    }
+    "move" => {
+      expected(2)?;
+      vec![Instruction::Alu(AluOp::Addu, operands.reg(0)?, operands.reg(1)?, Reg::ZERO)]
+    }
+    "neg" | "negu" => {
+      expected(2)?;
+      let op = if mnemonic == "neg" { AluOp::Sub } else { AluOp::Subu };
+      vec![Instruction::Alu(op, operands.reg(0)?, Reg::ZERO, operands.reg(1)?)]
+    }
+    "not" => {
+      expected(2)?;
+      vec![Instruction::Alu(AluOp::Nor, operands.reg(0)?, operands.reg(1)?, Reg::ZERO)]
+    }
+    _ => return Err(format!("Unknown instruction {mnemonic}")),
+  };
+  Ok(instructions)
+}
+
+/// `lui` and `ori` loading a 32-bit value
+fn load_upper(rd: Reg, value: u32) -> Vec<Instruction> {
+  vec![Instruction::Lui(rd, (value >> 16) as u16), Instruction::AluImm(ImmOp::Ori, rd, rd, (value & 0xffff) as i32)]
+}
+
+/// Upper half and signed offset adding up to the address, for a load or store through `$at`
+fn split_address(address: u32) -> (u16, i32) {
+  let offset = address as u16 as i16 as i32;
+  ((address.wrapping_sub(offset as u32) >> 16) as u16, offset)
+}
+
+/// Operands of an instruction, read with the kind the instruction expects
+struct Operands<'a> {
+  operands: &'a [Operand],
+  symbols: &'a HashMap<String, u32>,
+}
+
+impl Operands<'_> {
+  fn reg(&self, index: usize) -> Result<Reg, String> {
+    match &self.operands[index] {
+      Operand::Reg(reg) => Ok(*reg),
+      operand => Err(format!("Expected a register, found {operand}")),
+    }
+  }
+
+  fn imm(&self, index: usize) -> Result<i64, String> {
+    match &self.operands[index] {
+      Operand::Imm(value) => Ok(*value),
+      operand => Err(format!("Expected an immediate, found {operand}")),
+    }
+  }
+
+  fn signed16(&self, index: usize) -> Result<i32, String> {
+    let value = self.imm(index)?;
+    if !fits_signed(value) {
+      return Err(format!("Immediate {value} does not fit in 16 signed bits"));
+    }
+    Ok(value as i32)
+  }
+
+  fn unsigned16(&self, index: usize) -> Result<u16, String> {
+    let value = self.imm(index)?;
+    u16::try_from(value).map_err(|_| format!("Immediate {value} does not fit in 16 unsigned bits"))
+  }
+
+  /// An address given as a label, or as an immediate
+  fn address(&self, index: usize) -> Result<u32, String> {
+    match &self.operands[index] {
+      Operand::Label(label, addend) => resolve(self.symbols, label, *addend),
+      Operand::Imm(value) => Ok(*value as u32),
+      operand => Err(format!("Expected a label, found {operand}")),
+    }
+  }
+
+  fn mem(&self, index: usize) -> Result<(i32, Reg), String> {
+    match &self.operands[index] {
+      Operand::Mem(offset, base) if fits_signed(*offset) => Ok((*offset as i32, *base)),
+      Operand::Mem(offset, _) => Err(format!("Offset {offset} does not fit in 16 signed bits")),
+      operand => Err(format!("Expected an address like 4($sp), found {operand}")),
+    }
+  }
+}
+
+/// The line without its comment, which starts with `#` outside string and character literals
+fn strip_comment(line: &str) -> &str {
+  let mut quote = None;
+  let mut escaped = false;
+  for (index, c) in line.char_indices() {
+    match quote {
+      Some(_) if escaped => escaped = false,
+      Some(_) if c == '\\' => escaped = true,
+      Some(q) if c == q => quote = None,
+      Some(_) => {}
+      None if c == '"' || c == '\'' => quote = Some(c),
+      None if c == '#' => return &line[..index],
+      None => {}
+    }
+  }
+  line
+}
+
+fn is_identifier(text: &str) -> bool {
+  let mut chars = text.chars();
+  chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+      && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+/// A label at the start of the line and the rest of the line
+fn split_label(line: &str) -> Option<(&str, &str)> {
+  let (label, rest) = line.split_once(':')?;
+  is_identifier(label.trim_end()).then_some((label.trim_end(), rest))
+}
+
+/// Operands separated by commas, outside string and character literals
+fn split_operands(arguments: &str) -> Vec<String> {
+  let mut operands = Vec::new();
+  let mut current = String::new();
+  let mut quote = None;
+  let mut escaped = false;
+  for c in arguments.chars() {
+    match quote {
+      Some(_) if escaped => escaped = false,
+      Some(_) if c == '\\' => escaped = true,
+      Some(q) if c == q => quote = None,
+      Some(_) => {}
+      None if c == '"' || c == '\'' => quote = Some(c),
+      None if c == ',' => {
+        operands.push(current.trim().to_string());
+        current.clear();
+        continue;
+      }
+      None => {}
+    }
+    current.push(c);
+  }
+  if !current.trim().is_empty() || !operands.is_empty() {
+    operands.push(current.trim().to_string());
+  }
+  operands
+}
+
+/// A decimal, hexadecimal or character literal
+fn parse_integer(text: &str) -> Option<i64> {
+  let text = text.trim();
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text),
+  };
+  let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+    i64::from_str_radix(hex, 16).ok()?
+  } else if digits.starts_with('\'') && digits.ends_with('\'') && digits.len() >= 3 {
+    match parse_escapes(&digits[1..digits.len() - 1]).ok()?.as_slice() {
+      [byte] => *byte as i64,
+      _ => return None,
+    }
+  } else if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+    digits.parse().ok()?
+  } else {
+    return None;
+  };
+  Some(if negative { -value } else { value })
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+  if let Some(name) = text.strip_prefix('$') {
+    return Reg::parse(name).map(Operand::Reg).ok_or_else(|| format!("Unknown register {text}"));
+  }
+  if let Some((offset, base)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) {
+    let offset = if offset.trim().is_empty() { 0 } else { parse_integer(offset).ok_or_else(|| format!("Invalid offset {offset}"))? };
+    let base = base.trim().strip_prefix('$').and_then(Reg::parse).ok_or_else(|| format!("Invalid base register {base}"))?;
+    return Ok(Operand::Mem(offset, base));
+  }
+  if let Some(value) = parse_integer(text) {
+    return Ok(Operand::Imm(value));
+  }
+  let (label, addend) = match text.find(['+', '-']) {
+    Some(index) => {
+      let addend = parse_integer(&text[index + 1..]).ok_or_else(|| format!("Invalid operand {text}"))?;
+      (text[..index].trim(), if text[index..].starts_with('-') { -addend } else { addend })
+    }
+    None => (text, 0),
+  };
+  if !is_identifier(label) {
+    return Err(format!("Invalid operand {text}"));
+  }
+  Ok(Operand::Label(label.to_string(), addend))
+}
+
+/// The bytes of a string literal in double quotes
+fn parse_string(text: &str) -> Result<Vec<u8>, String> {
+  let inner = text.strip_prefix('"').and_then(|text| text.strip_suffix('"')).ok_or_else(|| format!("Expected a string literal, found {text}"))?;
+  parse_escapes(inner)
+}
+
+/// The bytes of the text of a literal, with the escapes `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\'`
+fn parse_escapes(text: &str) -> Result<Vec<u8>, String> {
+  let mut bytes = Vec::new();
+  let mut chars = text.bytes();
+  while let Some(byte) = chars.next() {
+    if byte != b'\\' {
+      bytes.push(byte);
+      continue;
+    }
+    bytes.push(match chars.next() {
+      Some(b'n') => b'\n',
+      Some(b't') => b'\t',
+      Some(b'r') => b'\r',
+      Some(b'0') => 0,
+      Some(escaped @ (b'\\' | b'"' | b'\'')) => escaped,
+      Some(other) => return Err(format!("Unknown escape \\{}", other as char)),
+      None => return Err("Unterminated escape".to_string()),
+    });
+  }
+  Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::asm::assemble;
+  use crate::model::{AluOp, BranchCond, ImmOp, Instruction, Reg, DATA_BASE, TEXT_BASE};
+
+  #[test]
+  fn test_assemble() {
+    let source = r#"
+        .data
+    greeting: .asciiz "hi, \"you\"\n"   # a comment
+    table:  .word main, greeting+1, -1
+        .text
+    main:
+        li $t0, 70000
+        la $a0, greeting
+        blt $t0, $zero, main
+        jr $ra
+    "#;
+    let program = assemble(source).expect("Program must assemble");
+    assert_eq!(program.symbols["greeting"], DATA_BASE);
+    assert_eq!(program.symbols["table"], DATA_BASE + 12);
+    assert_eq!(&program.data[..12], b"hi, \"you\"\n\0\0");
+    let words: Vec<u32> = program.data[12..].chunks(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect();
+    assert_eq!(words, vec![TEXT_BASE, DATA_BASE + 1, u32::MAX]);
+
+    let t0 = Reg(8);
+    assert_eq!(program.text[..2], [Instruction::Lui(t0, 1), Instruction::AluImm(ImmOp::Ori, t0, t0, 4464)]);
+    assert_eq!(program.text[4..6], [Instruction::Alu(AluOp::Slt, Reg::AT, t0, Reg::ZERO), Instruction::Branch(BranchCond::Ne, Reg::AT, Reg::ZERO, TEXT_BASE)]);
+    // The start routine calls main and exits
+    assert_eq!(program.entry, TEXT_BASE + 4 * 7);
+    assert_eq!(program.text[7..], [Instruction::Jal(TEXT_BASE), Instruction::AluImm(ImmOp::Addiu, Reg::V0, Reg::ZERO, 10), Instruction::Syscall]);
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(assemble("main:\n  frob $t0"), Err("Line 2: Unknown instruction frob".to_string()));
+    assert_eq!(assemble("main:\n  j nowhere"), Err("Line 2: Undefined label nowhere".to_string()));
+    assert_eq!(assemble("main:\n  addiu $t0, $t0, 40000"), Err("Line 2: Immediate 40000 does not fit in 16 signed bits".to_string()));
+    assert_eq!(assemble("main:\n  lw $t0, $t1"), Err("Line 2: Expected an address like 4($sp), found $t1".to_string()));
+    assert_eq!(assemble("main:\nmain:"), Err("Line 2: Label main is defined twice".to_string()));
+    assert_eq!(assemble("  .text\n  .word 1"), Err("Line 2: .word is only allowed in the data segment".to_string()));
+    assert_eq!(assemble("start:\n  nop"), Err("The program defines neither __start nor main".to_string()));
+  }
+}
@@ -0,0 +1,438 @@
+use crate::model::{AluOp, BranchCond, ImmOp, Instruction, LoadOp, MulDivOp, Program, Reg, ShiftOp, StoreOp, DATA_BASE, GLOBAL_POINTER, STACK_TOP};
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+
+/// Size of the stack, accesses below it are bad addresses
+const STACK_SIZE: u32 = 256 << 20;
+
+/// Start of the memory the program can use below the data segment, for the global pointer
+const STATIC_BASE: u32 = 0x1000_0000;
+
+const PAGE_BITS: u32 = 16;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+/// Counters of the work done by a program
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Stats {
+  pub instructions: u64, // machine instructions executed, pseudo-instructions count as their expansion
+  pub syscalls: u64,
+}
+
+impl Display for Stats {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} instructions, {} syscalls", self.instructions, self.syscalls)
+  }
+}
+
+/// An error stopping the program, at the address of the instruction that caused it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Exception {
+  pub pc: u32,
+  pub message: String,
+}
+
+impl Display for Exception {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Exception at 0x{:08x}: {}", self.pc, self.message)
+  }
+}
+
+/// Runs the program from its entry until it exits, and returns its exit code.
+///
+/// The syscalls of SPIM are supported: `print_int` (1), `print_string` (4), `read_int` (5), `read_string` (8),
+/// `sbrk` (9), `exit` (10), `print_char` (11), `read_char` (12) and `exit2` (17).
+pub fn run(program: &Program, input: &mut dyn BufRead, output: &mut dyn Write) -> (Stats, Result<i32, Exception>) {
+  let mut machine = Machine::new(program, input, output);
+  let result = machine.run();
+  (machine.stats, result)
+}
+
+/// Memory of the program, in pages allocated when first used
+struct Memory {
+  pages: Vec<Option<Box<[u8]>>>,
+}
+
+impl Memory {
+  fn new() -> Self {
+    Memory { pages: vec![None; 1 << (32 - PAGE_BITS)] }
+  }
+
+  fn page(&mut self, address: u32) -> &mut [u8] {
+    self.pages[(address >> PAGE_BITS) as usize].get_or_insert_with(|| vec![0; PAGE_SIZE].into_boxed_slice())
+  }
+
+  /// Reads `N` bytes, which are in one page as the address is aligned
+  fn read<const N: usize>(&mut self, address: u32) -> [u8; N] {
+    let offset = address as usize % PAGE_SIZE;
+    self.page(address)[offset..offset + N].try_into().expect("Aligned accesses stay in a page")
+  }
+
+  fn write(&mut self, address: u32, bytes: &[u8]) {
+    let offset = address as usize % PAGE_SIZE;
+    self.page(address)[offset..offset + bytes.len()].copy_from_slice(bytes);
+  }
+}
+
+struct Machine<'a> {
+  program: &'a Program,
+  input: &'a mut dyn BufRead,
+  output: &'a mut dyn Write,
+  registers: [u32; 32],
+  hi: u32,
+  lo: u32,
+  pc: u32,
+  memory: Memory,
+  brk: u32, // end of the heap, moved by `sbrk`
+  stats: Stats,
+}
+
+impl<'a> Machine<'a> {
+  fn new(program: &'a Program, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self {
+    let mut memory = Memory::new();
+    for (index, chunk) in program.data.chunks(PAGE_SIZE).enumerate() {
+      memory.write(DATA_BASE + (index * PAGE_SIZE) as u32, chunk);
+    }
+    let mut registers = [0; 32];
+    registers[Reg::SP.index()] = STACK_TOP;
+    registers[Reg::GP.index()] = GLOBAL_POINTER;
+    let brk = (DATA_BASE + program.data.len() as u32).next_multiple_of(8);
+    Machine { program, input, output, registers, hi: 0, lo: 0, pc: program.entry, memory, brk, stats: Stats::default() }
+  }
+
+  fn exception(&self, message: impl Into<String>) -> Exception {
+    Exception { pc: self.pc, message: message.into() }
+  }
+
+  fn get(&self, reg: Reg) -> u32 {
+    self.registers[reg.index()]
+  }
+
+  fn set(&mut self, reg: Reg, value: u32) {
+    self.registers[reg.index()] = value;
+  }
+
+  fn run(&mut self) -> Result<i32, Exception> {
+    loop {
+      let instruction = *self.program.instruction(self.pc).ok_or_else(|| self.exception("Bad instruction address"))?;
+      self.stats.instructions += 1;
+      let mut next = self.pc.wrapping_add(4);
+      match instruction {
+        Instruction::Alu(op, rd, rs, rt) => {
+          let value = self.alu(op, self.get(rs), self.get(rt))?;
+          self.set(rd, value);
+        }
+        Instruction::AluImm(op, rt, rs, immediate) => {
+          let (a, b) = (self.get(rs), immediate as u32);
+          let value = match op {
+            ImmOp::Addi => self.alu(AluOp::Add, a, b)?,
+            ImmOp::Addiu => a.wrapping_add(b),
+            ImmOp::Andi => a & b,
+            ImmOp::Ori => a | b,
+            ImmOp::Xori => a ^ b,
+            ImmOp::Slti => ((a as i32) < (b as i32)) as u32,
+            ImmOp::Sltiu => (a < b) as u32,
+          };
+          self.set(rt, value);
+        }
+        Instruction::Shift(op, rd, rt, amount) => {
+          let value = self.get(rt);
+          let value = match op {
+            ShiftOp::Sll => value << amount,
+            ShiftOp::Srl => value >> amount,
+            ShiftOp::Sra => ((value as i32) >> amount) as u32,
+          };
+          self.set(rd, value);
+        }
+        Instruction::Lui(rt, immediate) => self.set(rt, (immediate as u32) << 16),
+        Instruction::MulDiv(op, rs, rt) => {
+          let (a, b) = (self.get(rs), self.get(rt));
+          match op {
+            MulDivOp::Mult => self.set_hi_lo((a as i32 as i64 * b as i32 as i64) as u64),
+            MulDivOp::Multu => self.set_hi_lo(a as u64 * b as u64),
+            // The result of a division by zero is unpredictable on MIPS, the registers are left unchanged
+            MulDivOp::Div if b != 0 => {
+              self.lo = (a as i32).wrapping_div(b as i32) as u32;
+              self.hi = (a as i32).wrapping_rem(b as i32) as u32;
+            }
+            MulDivOp::Divu if b != 0 => {
+              self.lo = a / b;
+              self.hi = a % b;
+            }
+            MulDivOp::Div | MulDivOp::Divu => {}
+          }
+        }
+        Instruction::Mfhi(rd) => self.set(rd, self.hi),
+        Instruction::Mflo(rd) => self.set(rd, self.lo),
+        Instruction::Load(op, rt, offset, base) => {
+          let address = self.get(base).wrapping_add(offset as u32);
+          let value = match op {
+            LoadOp::Lw => u32::from_le_bytes(self.read(address)?),
+            LoadOp::Lh => i16::from_le_bytes(self.read(address)?) as u32,
+            LoadOp::Lhu => u16::from_le_bytes(self.read(address)?) as u32,
+            LoadOp::Lb => self.read::<1>(address)?[0] as i8 as u32,
+            LoadOp::Lbu => self.read::<1>(address)?[0] as u32,
+          };
+          self.set(rt, value);
+        }
+        Instruction::Store(op, rt, offset, base) => {
+          let address = self.get(base).wrapping_add(offset as u32);
+          let value = self.get(rt).to_le_bytes();
+          match op {
+            StoreOp::Sw => self.write(address, &value)?,
+            StoreOp::Sh => self.write(address, &value[..2])?,
+            StoreOp::Sb => self.write(address, &value[..1])?,
+          }
+        }
+        Instruction::Branch(cond, rs, rt, target) => {
+          let (a, b) = (self.get(rs), self.get(rt));
+          let taken = match cond {
+            BranchCond::Eq => a == b,
+            BranchCond::Ne => a != b,
+            BranchCond::Lez => a as i32 <= 0,
+            BranchCond::Gtz => a as i32 > 0,
+            BranchCond::Ltz => (a as i32) < 0,
+            BranchCond::Gez => a as i32 >= 0,
+          };
+          if taken {
+            next = target;
+          }
+        }
+        Instruction::Jump(target) => next = target,
+        Instruction::Jal(target) => {
+          self.set(Reg::RA, next);
+          next = target;
+        }
+        Instruction::Jr(rs) => next = self.get(rs),
+        Instruction::Jalr(rd, rs) => {
+          let target = self.get(rs);
+          self.set(rd, next);
+          next = target;
+        }
+        Instruction::Syscall => {
+          self.stats.syscalls += 1;
+          if let Some(code) = self.syscall()? {
+            self.output.flush().map_err(|e| self.exception(format!("I/O error: {e}")))?;
+            return Ok(code);
+          }
+        }
+        Instruction::Break => return Err(self.exception("Break")),
+      }
+      self.registers[0] = 0;
+      self.pc = next;
+    }
+  }
+
+  fn alu(&self, op: AluOp, a: u32, b: u32) -> Result<u32, Exception> {
+    let overflow = || self.exception("Arithmetic overflow");
+    Ok(match op {
+      AluOp::Add => (a as i32).checked_add(b as i32).ok_or_else(overflow)? as u32,
+      AluOp::Addu => a.wrapping_add(b),
+      AluOp::Sub => (a as i32).checked_sub(b as i32).ok_or_else(overflow)? as u32,
+      AluOp::Subu => a.wrapping_sub(b),
+      AluOp::Mul => a.wrapping_mul(b),
+      AluOp::And => a & b,
+      AluOp::Or => a | b,
+      AluOp::Xor => a ^ b,
+      AluOp::Nor => !(a | b),
+      AluOp::Slt => ((a as i32) < (b as i32)) as u32,
+      AluOp::Sltu => (a < b) as u32,
+    })
+  }
+
+  fn set_hi_lo(&mut self, value: u64) {
+    self.hi = (value >> 32) as u32;
+    self.lo = value as u32;
+  }
+
+  /// Checks the address is aligned and in the data segment, the heap or the stack
+  fn check(&self, address: u32, size: u32) -> Result<(), Exception> {
+    if !address.is_multiple_of(size) {
+      return Err(self.exception(format!("Unaligned address 0x{address:08x}")));
+    }
+    let in_data = (STATIC_BASE..self.brk).contains(&address);
+    let in_stack = (STACK_TOP - STACK_SIZE..0x8000_0000).contains(&address);
+    if !in_data && !in_stack {
+      return Err(self.exception(format!("Bad address 0x{address:08x}")));
+    }
+    Ok(())
+  }
+
+  fn read<const N: usize>(&mut self, address: u32) -> Result<[u8; N], Exception> {
+    self.check(address, N as u32)?;
+    Ok(self.memory.read(address))
+  }
+
+  fn write(&mut self, address: u32, bytes: &[u8]) -> Result<(), Exception> {
+    self.check(address, bytes.len() as u32)?;
+    self.memory.write(address, bytes);
+    Ok(())
+  }
+
+  /// Runs the syscall numbered by `$v0`, returns the exit code if the program exits
+  fn syscall(&mut self) -> Result<Option<i32>, Exception> {
+    let a0 = self.get(Reg::A0);
+    let io_error = |machine: &Self, e: std::io::Error| machine.exception(format!("I/O error: {e}"));
+    match self.get(Reg::V0) {
+      1 => write!(self.output, "{}", a0 as i32).map_err(|e| io_error(self, e))?,
+      4 => {
+        let mut bytes = Vec::new();
+        let mut address = a0;
+        loop {
+          let [byte] = self.read::<1>(address)?;
+          if byte == 0 {
+            break;
+          }
+          bytes.push(byte);
+          address = address.wrapping_add(1);
+        }
+        self.output.write_all(&bytes).map_err(|e| io_error(self, e))?;
+      }
+      5 => {
+        let line = self.read_line(usize::MAX).map_err(|e| io_error(self, e))?;
+        let value = parse_int(&String::from_utf8_lossy(&line));
+        self.set(Reg::V0, value as u32);
+      }
+      8 => {
+        // At most length - 1 bytes, up to and including the line break, then a zero byte
+        let length = self.get(Reg(5)) as i32;
+        if length >= 1 {
+          let line = self.read_line(length as usize - 1).map_err(|e| io_error(self, e))?;
+          for (index, byte) in line.iter().chain([&0]).enumerate() {
+            self.write(a0.wrapping_add(index as u32), &[*byte])?;
+          }
+        }
+      }
+      9 => {
+        // -1 when the heap would run into the stack, like `sbrk` on Unix, so the program reports the error itself
+        let brk = self.brk.checked_add((a0 as i32).max(0) as u32).filter(|brk| *brk < STACK_TOP - STACK_SIZE);
+        match brk {
+          Some(brk) => {
+            self.set(Reg::V0, self.brk);
+            self.brk = brk.next_multiple_of(8);
+          }
+          None => self.set(Reg::V0, u32::MAX),
+        }
+      }
+      10 => return Ok(Some(0)),
+      11 => self.output.write_all(&[a0 as u8]).map_err(|e| io_error(self, e))?,
+      12 => {
+        let byte = self.output.flush().and_then(|_| self.read_byte()).map_err(|e| io_error(self, e))?;
+        self.set(Reg::V0, byte.map_or(u32::MAX, u32::from));
+      }
+      17 => return Ok(Some(a0 as i32)),
+      number => return Err(self.exception(format!("Unknown syscall {number}"))),
+    }
+    Ok(None)
+  }
+
+  fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+    let byte = self.input.fill_buf()?.first().copied();
+    if byte.is_some() {
+      self.input.consume(1);
+    }
+    Ok(byte)
+  }
+
+  /// Reads up to `limit` bytes of the input, stopping after a line break
+  fn read_line(&mut self, limit: usize) -> std::io::Result<Vec<u8>> {
+    self.output.flush()?;
+    let mut line = Vec::new();
+    while line.len() < limit {
+      let Some(byte) = self.read_byte()? else {
+        break;
+      };
+      line.push(byte);
+      if byte == b'\n' {
+        break;
+      }
+    }
+    Ok(line)
+  }
+}
+
+/// The integer at the start of the line after blanks, 0 if there is none or it does not fit in 32 bits
+fn parse_int(line: &str) -> i32 {
+  let line = line.trim_start();
+  let end = line.char_indices()
+      .find(|(index, c)| !(c.is_ascii_digit() || (*index == 0 && (*c == '-' || *c == '+'))))
+      .map_or(line.len(), |(index, _)| index);
+  line[..end].parse().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::asm::assemble;
+  use crate::machine::{run, Exception};
+  use crate::model::TEXT_BASE;
+
+  fn run_source(source: &str, input: &str) -> (String, Result<i32, Exception>) {
+    let program = assemble(source).expect("Program must assemble");
+    let mut output = Vec::new();
+    let (_, result) = run(&program, &mut input.as_bytes(), &mut output);
+    (String::from_utf8(output).expect("Output must be UTF-8"), result)
+  }
+
+  #[test]
+  fn test_recursion_and_syscalls() {
+    // Reads n and prints n!, computed recursively, then echoes a line
+    let source = r#"
+        .data
+    prompt: .asciiz "n! = "
+    buffer: .space 16
+        .text
+    main:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        li $v0, 5
+        syscall
+        move $a0, $v0
+        jal factorial
+        move $t0, $v0
+        la $a0, prompt
+        li $v0, 4
+        syscall
+        move $a0, $t0
+        li $v0, 1
+        syscall
+        li $a0, '\n'
+        li $v0, 11
+        syscall
+        la $a0, buffer
+        li $a1, 16
+        li $v0, 8
+        syscall
+        li $v0, 4
+        syscall
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+
+    factorial:
+        bgtz $a0, recurse
+        li $v0, 1
+        jr $ra
+    recurse:
+        addiu $sp, $sp, -8
+        sw $ra, 4($sp)
+        sw $a0, 0($sp)
+        addiu $a0, $a0, -1
+        jal factorial
+        lw $a0, 0($sp)
+        mul $v0, $v0, $a0
+        lw $ra, 4($sp)
+        addiu $sp, $sp, 8
+        jr $ra
+    "#;
+    assert_eq!(run_source(source, "  10 apples\nsecond line\n"), ("n! = 3628800\nsecond line\n".to_string(), Ok(0)));
+  }
+
+  #[test]
+  fn test_exceptions() {
+    let (_, result) = run_source("main:\n  lw $t0, 0($zero)", "");
+    assert_eq!(result, Err(Exception { pc: TEXT_BASE, message: "Bad address 0x00000000".to_string() }));
+    let (_, result) = run_source("main:\n  li $t0, 0x7fffffff\n  addi $t0, $t0, 1", "");
+    assert_eq!(result.unwrap_err().message, "Arithmetic overflow");
+    let (output, result) = run_source("main:\n  li $a0, 65\n  li $v0, 11\n  syscall\n  li $a0, 3\n  li $v0, 17\n  syscall", "");
+    assert_eq!((output.as_str(), result), ("A", Ok(3)));
+  }
+}
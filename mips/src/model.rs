@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Address of the first instruction, as in SPIM
+pub const TEXT_BASE: u32 = 0x0040_0000;
+
+/// Address of the data segment, the heap grows from its end with `sbrk`
+pub const DATA_BASE: u32 = 0x1001_0000;
+
+/// Initial stack pointer, the stack grows down
+pub const STACK_TOP: u32 = 0x7fff_effc;
+
+/// Initial global pointer
+pub const GLOBAL_POINTER: u32 = 0x1000_8000;
+
+/// One of the 32 general purpose registers
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Reg(pub u8);
+
+/// Conventional names of the registers, in number order
+pub const REGISTER_NAMES: [&str; 32] = [
+  "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+  "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+impl Reg {
+  pub const ZERO: Reg = Reg(0);
+  pub const AT: Reg = Reg(1);
+  pub const V0: Reg = Reg(2);
+  pub const A0: Reg = Reg(4);
+  pub const GP: Reg = Reg(28);
+  pub const SP: Reg = Reg(29);
+  pub const FP: Reg = Reg(30);
+  pub const RA: Reg = Reg(31);
+
+  /// Parses a register without its `$`, by name like `t0` or by number like `8`. `s8` is another name of `fp`.
+  pub fn parse(name: &str) -> Option<Reg> {
+    if let Ok(number) = name.parse::<u8>() {
+      return (number < 32).then_some(Reg(number));
+    }
+    if name == "s8" {
+      return Some(Reg::FP);
+    }
+    REGISTER_NAMES.iter().position(|register| *register == name).map(|number| Reg(number as u8))
+  }
+
+  pub fn index(self) -> usize {
+    self.0 as usize
+  }
+}
+
+impl Display for Reg {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "${}", REGISTER_NAMES[self.index()])
+  }
+}
+
+/// Operations on two registers into a third, `rd = rs op rt`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AluOp {
+  Add,
+  Addu,
+  Sub,
+  Subu,
+  Mul,
+  And,
+  Or,
+  Xor,
+  Nor,
+  Slt,
+  Sltu,
+}
+
+/// Operations on a register and an immediate, `rt = rs op immediate`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImmOp {
+  Addi,
+  Addiu,
+  Andi,
+  Ori,
+  Xori,
+  Slti,
+  Sltiu,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ShiftOp {
+  Sll,
+  Srl,
+  Sra,
+}
+
+/// Multiplications and divisions into `hi` and `lo`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MulDivOp {
+  Mult,
+  Multu,
+  Div,
+  Divu,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LoadOp {
+  Lw,
+  Lh,
+  Lhu,
+  Lb,
+  Lbu,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StoreOp {
+  Sw,
+  Sh,
+  Sb,
+}
+
+/// Conditions of branches, comparing two registers or one register with zero
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BranchCond {
+  Eq,
+  Ne,
+  Lez,
+  Gtz,
+  Ltz,
+  Gez,
+}
+
+/// A machine instruction, after the assembler expanded the pseudo-instructions. Branch and jump targets are
+/// addresses. Immediates are already sign or zero extended, as their instruction reads them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction {
+  Alu(AluOp, Reg, Reg, Reg),
+  AluImm(ImmOp, Reg, Reg, i32),
+  Shift(ShiftOp, Reg, Reg, u8),
+  Lui(Reg, u16),
+  MulDiv(MulDivOp, Reg, Reg),
+  Mfhi(Reg),
+  Mflo(Reg),
+  Load(LoadOp, Reg, i32, Reg),
+  Store(StoreOp, Reg, i32, Reg),
+  Branch(BranchCond, Reg, Reg, u32),
+  Jump(u32),
+  Jal(u32),
+  Jr(Reg),
+  Jalr(Reg, Reg),
+  Syscall,
+  Break,
+}
+
+/// An assembled program: the instructions from `TEXT_BASE`, the initial data from `DATA_BASE` and the addresses
+/// of the labels
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Program {
+  pub text: Vec<Instruction>,
+  pub data: Vec<u8>,
+  pub entry: u32,
+  pub symbols: HashMap<String, u32>,
+}
+
+impl Program {
+  /// The instruction at the address, if it is in the text segment
+  pub fn instruction(&self, address: u32) -> Option<&Instruction> {
+    if !address.is_multiple_of(4) {
+      return None;
+    }
+    self.text.get((address.wrapping_sub(TEXT_BASE) / 4) as usize)
+  }
+}
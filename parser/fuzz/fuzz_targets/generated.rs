@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parser::fuzz::grammar::ProgramGenerator;
+use parser::fuzz::{check_parse, check_valid, mutate};
+
+// The input is the seed of a generated program, which must parse, then of broken copies of it, which must not make
+// the parser panic
+fuzz_target!(|data: &[u8]| {
+    let mut seed = [0; 8];
+    let length = data.len().min(seed.len());
+    seed[..length].copy_from_slice(&data[..length]);
+
+    let mut generator = ProgramGenerator::new(u64::from_le_bytes(seed));
+    let tokens = generator.program_tokens();
+    let text = generator.layout(&tokens);
+    if let Err(e) = check_valid(&text) {
+        panic!("{e}\n{text}");
+    }
+    for _ in 0..data.len().saturating_sub(seed.len()).min(16) {
+        let mutant = mutate(generator.rng(), &tokens);
+        let text = generator.layout(&mutant);
+        if let Err(e) = check_parse(&text) {
+            panic!("{e}\n{text}");
+        }
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parser::fuzz::check_parse;
+
+// Any text: the lexer and the parser must not panic, and a program which parses must be printed as one which parses
+// back to it
+fuzz_target!(|data: &[u8]| {
+    if let Err(e) = check_parse(&String::from_utf8_lossy(data)) {
+        panic!("{e}");
+    }
+});
@@ -0,0 +1,308 @@
+use crate::fuzz::rng::Rng;
+use std::mem::take;
+
+const CLASSES: [&str; 4] = ["A", "B", "C", "Main"];
+const PARENTS: [&str; 4] = ["A", "B", "IO", "Object"];
+const TYPES: [&str; 8] = ["Int", "Bool", "String", "Object", "IO", "SELF_TYPE", "A", "Main"];
+const NAMES: [&str; 6] = ["x", "y", "n", "count", "a_1", "b2"];
+const METHODS: [&str; 6] = ["f", "g", "init", "out_int", "length", "type_name"];
+const OPERATORS: [&str; 7] = ["+", "-", "*", "/", "<", "<=", "="];
+const STRING_PARTS: [&str; 14] = ["a", "Z", " ", "0", "\\n", "\\t", "\\\"", "\\\\", "\\b", "-- x", "(*", "*)", ";", "é"];
+
+/// How deep the expressions of a program nest
+const MAX_DEPTH: usize = 5;
+
+/// Generates random programs following the grammar the parser implements.
+///
+/// Expressions are made the way the parser reads them: the operands of the binary operators are expressions of a
+/// single operand with the dispatches on it, an expression which runs to the end of the enclosing expression, `let`,
+/// `not` or an assignment, is only an operand in parentheses. Keywords are in random case and the tokens are separated
+/// by random whitespace and comments, see `layout`.
+pub struct ProgramGenerator {
+    rng: Rng,
+    tokens: Vec<String>,
+    depth: usize, // levels of expressions the current expression may still nest
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        ProgramGenerator { rng: Rng::new(seed), tokens: Vec::new(), depth: MAX_DEPTH }
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// The source text of a random program
+    pub fn program(&mut self) -> String {
+        let tokens = self.program_tokens();
+        self.layout(&tokens)
+    }
+
+    /// The tokens of a random program
+    pub fn program_tokens(&mut self) -> Vec<String> {
+        for _ in 0..1 + self.rng.below(3) {
+            self.class();
+        }
+        take(&mut self.tokens)
+    }
+
+    /// Joins the tokens with whitespace, sometimes with new lines, tabs or comments
+    pub fn layout(&mut self, tokens: &[String]) -> String {
+        let mut text = String::new();
+        for token in tokens {
+            text.push_str(token);
+            text.push_str(match self.rng.below(20) {
+                0..=11 => " ",
+                12..=15 => "\n",
+                16 => "\t",
+                17 => "\n    ",
+                18 => " -- comment\n",
+                _ => " (* comment\n*) ",
+            });
+        }
+        text
+    }
+
+    fn push(&mut self, token: &str) {
+        self.tokens.push(token.to_string());
+    }
+
+    /// Adds the keyword, in lower case most of the time, which the lexer does not require
+    fn keyword(&mut self, keyword: &str) {
+        let token = match self.rng.below(10) {
+            // The lexer only reads `true` and `false` starting with a lower case letter
+            0 if keyword != "true" && keyword != "false" => keyword.to_uppercase(),
+            1 => keyword.chars().enumerate().map(|(i, c)| if i % 2 == 1 { c.to_ascii_uppercase() } else { c }).collect(),
+            _ => keyword.to_string(),
+        };
+        self.tokens.push(token);
+    }
+
+    fn pick(&mut self, items: &[&str]) {
+        let item = *self.rng.pick(items);
+        self.push(item);
+    }
+
+    /// Calls `item` between 0 and `max` times, with `separator` between the calls
+    fn list(&mut self, min: usize, max: usize, separator: &str, mut item: impl FnMut(&mut Self)) {
+        for index in 0..min + self.rng.below(max - min + 1) {
+            if index > 0 {
+                self.push(separator);
+            }
+            item(self);
+        }
+    }
+
+    fn class(&mut self) {
+        self.keyword("class");
+        self.pick(&CLASSES);
+        if self.rng.chance(50) {
+            self.keyword("inherits");
+            self.pick(&PARENTS);
+        }
+        self.push("{");
+        for _ in 0..self.rng.below(5) {
+            self.feature();
+            self.push(";");
+        }
+        self.push("}");
+        self.push(";");
+    }
+
+    fn feature(&mut self) {
+        if self.rng.chance(40) {
+            self.pick(&NAMES);
+            self.push(":");
+            self.pick(&TYPES);
+            if self.rng.chance(50) {
+                self.push("<-");
+                self.expression();
+            }
+        } else {
+            self.pick(&METHODS);
+            self.push("(");
+            self.list(0, 3, ",", |generator| {
+                generator.pick(&NAMES);
+                generator.push(":");
+                generator.pick(&TYPES);
+            });
+            self.push(")");
+            self.push(":");
+            self.pick(&TYPES);
+            self.push("{");
+            self.expression();
+            self.push("}");
+        }
+    }
+
+    /// An expression which may run to the end of the enclosing expression
+    fn expression(&mut self) {
+        if self.depth == 0 {
+            return self.operand();
+        }
+        self.depth -= 1;
+        match self.rng.below(10) {
+            0 => {
+                self.pick(&NAMES);
+                self.push("<-");
+                self.expression();
+            }
+            1 => {
+                self.keyword("not");
+                self.expression();
+            }
+            2 => {
+                self.keyword("let");
+                self.list(1, 3, ",", |generator| {
+                    generator.pick(&NAMES);
+                    generator.push(":");
+                    generator.pick(&TYPES);
+                    if generator.rng.chance(50) {
+                        generator.push("<-");
+                        generator.expression();
+                    }
+                });
+                self.keyword("in");
+                self.expression();
+            }
+            _ => {
+                self.operand();
+                for _ in 0..self.rng.below(4) {
+                    self.pick(&OPERATORS);
+                    self.operand();
+                }
+            }
+        }
+        self.depth += 1;
+    }
+
+    /// An operand of the binary operators
+    fn operand(&mut self) {
+        if self.depth == 0 {
+            return self.atom();
+        }
+        self.depth -= 1;
+        match self.rng.below(14) {
+            0 => {
+                self.push("(");
+                self.expression();
+                self.push(")");
+            }
+            1 | 2 => self.dispatch(),
+            3 => {
+                self.push("~");
+                self.operand();
+            }
+            4 => {
+                self.keyword("isvoid");
+                self.operand();
+            }
+            5 => {
+                self.keyword("new");
+                self.pick(&TYPES);
+            }
+            6 => {
+                self.keyword("if");
+                self.expression();
+                self.keyword("then");
+                self.expression();
+                self.keyword("else");
+                self.expression();
+                self.keyword("fi");
+            }
+            7 => {
+                self.keyword("while");
+                self.expression();
+                self.keyword("loop");
+                self.expression();
+                self.keyword("pool");
+            }
+            8 => {
+                self.keyword("case");
+                self.expression();
+                self.keyword("of");
+                for _ in 0..1 + self.rng.below(3) {
+                    self.pick(&NAMES);
+                    self.push(":");
+                    self.pick(&TYPES);
+                    self.push("=>");
+                    self.expression();
+                    self.push(";");
+                }
+                self.keyword("esac");
+            }
+            9 => {
+                self.push("{");
+                for _ in 0..1 + self.rng.below(3) {
+                    self.expression();
+                    self.push(";");
+                }
+                self.push("}");
+            }
+            _ => self.atom(),
+        }
+        self.depth += 1;
+    }
+
+    /// `f(args)` or `receiver{@T}.f(args)`, followed by more dispatches
+    fn dispatch(&mut self) {
+        if self.rng.chance(40) {
+            self.call();
+        } else {
+            if self.rng.chance(70) {
+                self.atom();
+            } else {
+                self.push("(");
+                self.expression();
+                self.push(")");
+            }
+            self.method_call();
+        }
+        for _ in 0..self.rng.below(3) {
+            self.method_call();
+        }
+    }
+
+    /// `{@T}.f(args)`
+    fn method_call(&mut self) {
+        if self.rng.chance(25) {
+            self.push("@");
+            self.pick(&PARENTS);
+        }
+        self.push(".");
+        self.call();
+    }
+
+    /// `f(args)`
+    fn call(&mut self) {
+        self.pick(&METHODS);
+        self.push("(");
+        self.list(0, 3, ",", Self::expression);
+        self.push(")");
+    }
+
+    fn atom(&mut self) {
+        match self.rng.below(6) {
+            0 | 1 => {
+                let value = if self.rng.chance(20) { self.rng.below(i32::MAX as usize + 1) } else { self.rng.below(100) };
+                self.push(&value.to_string());
+            }
+            2 => {
+                let mut literal = String::from('"');
+                for _ in 0..self.rng.below(8) {
+                    let part = *self.rng.pick(&STRING_PARTS);
+                    literal.push_str(part);
+                }
+                literal.push('"');
+                self.tokens.push(literal);
+            }
+            3 => {
+                let value = if self.rng.chance(50) { "true" } else { "false" };
+                self.keyword(value);
+            }
+            4 => self.push("self"),
+            _ => self.pick(&NAMES),
+        }
+    }
+}
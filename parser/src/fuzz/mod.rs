@@ -0,0 +1,181 @@
+//! Random testing of the lexer and the parser.
+//!
+//! `ProgramGenerator` makes programs following the grammar, which must parse, and `mutate` breaks them, which must
+//! not make the parser panic. Every program which parses is printed with `print_program` and parsed again, which must
+//! give the same program. `parser/fuzz` runs the same checks with `cargo fuzz`, `cool fuzz` without it.
+
+pub mod grammar;
+pub mod rng;
+
+use crate::fuzz::grammar::ProgramGenerator;
+use crate::fuzz::rng::Rng;
+use crate::get_ast_from_program_text;
+use crate::printer::print_program;
+use std::panic::catch_unwind;
+
+/// Tokens inserted by `mutate`: those of the language, and some which the lexer does not expect
+const MUTATION_TOKENS: [&str; 48] = [
+    "class", "inherits", "if", "then", "else", "fi", "while", "loop", "pool", "let", "in", "case", "of", "esac", "new",
+    "isvoid", "not", "true", "false", "self", "SELF_TYPE", "x", "Int", "0", "\"s\"", "{", "}", "(", ")", ";", ":",
+    ",", ".", "@", "<-", "=>", "+", "-", "*", "/", "~", "<", "<=", "=", "2147483648", "\"open", "$", "(*",
+];
+
+/// How many broken copies of each generated program `fuzz` parses
+const MUTANTS: usize = 8;
+
+/// An input which made a check fail
+#[derive(Debug)]
+pub struct Failure {
+    pub input: String,
+    pub message: String,
+}
+
+/// Parses the text, then checks that the program it parses to, if any, is printed as COOL which parses back to it
+pub fn check_parse(text: &str) -> Result<(), String> {
+    let Ok(program) = get_ast_from_program_text(text) else {
+        return Ok(());
+    };
+    let printed = print_program(&program);
+    let reparsed = get_ast_from_program_text(&printed).map_err(|e| format!("The printed program doesn't parse: {e}\n{printed}"))?;
+    let reprinted = print_program(&reparsed);
+    if reprinted != printed {
+        return Err(format!("The printed program parses to another program:\n{printed}\nprinted again:\n{reprinted}"));
+    }
+    Ok(())
+}
+
+/// Checks a program following the grammar: it must parse, and print as a program which parses back to it
+pub fn check_valid(text: &str) -> Result<(), String> {
+    get_ast_from_program_text(text).map_err(|e| format!("The program doesn't parse: {e}"))?;
+    check_parse(text)
+}
+
+/// The tokens with 1 to 4 random changes: a token removed, repeated, swapped with the next, joined to the next,
+/// replaced or inserted, or the program cut short
+pub fn mutate(rng: &mut Rng, tokens: &[String]) -> Vec<String> {
+    let mut tokens = tokens.to_vec();
+    for _ in 0..1 + rng.below(4) {
+        if tokens.is_empty() {
+            break;
+        }
+        let index = rng.below(tokens.len());
+        match rng.below(7) {
+            0 => {
+                tokens.remove(index);
+            }
+            1 => tokens.insert(index, tokens[index].clone()),
+            2 if index + 1 < tokens.len() => tokens.swap(index, index + 1),
+            3 => tokens[index] = rng.pick(&MUTATION_TOKENS).to_string(),
+            4 => tokens.truncate(index),
+            5 if index + 1 < tokens.len() => {
+                let next = tokens.remove(index + 1);
+                tokens[index].push_str(&next);
+            }
+            _ => tokens.insert(index, rng.pick(&MUTATION_TOKENS).to_string()),
+        }
+    }
+    tokens
+}
+
+/// Runs the checks on `runs` generated programs, from the seed, and on broken copies of them. A panic of the lexer or
+/// the parser is a failure, with the message of the panic.
+pub fn fuzz(seed: u64, runs: u64) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    let mut check = |input: String, valid: bool| {
+        let result = catch_unwind(|| if valid { check_valid(&input) } else { check_parse(&input) });
+        let message = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(message)) => message,
+            Err(panic) => {
+                let message = panic.downcast_ref::<&str>().map(|message| message.to_string());
+                format!("The parser panicked: {}", message.or_else(|| panic.downcast_ref::<String>().cloned()).unwrap_or_default())
+            }
+        };
+        failures.push(Failure { input, message });
+    };
+
+    for run in 0..runs {
+        let mut generator = ProgramGenerator::new(seed.wrapping_add(run));
+        let tokens = generator.program_tokens();
+        let text = generator.layout(&tokens);
+        check(text, true);
+        for _ in 0..MUTANTS {
+            let mutant = mutate(generator.rng(), &tokens);
+            check(generator.layout(&mutant), false);
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fuzz::grammar::ProgramGenerator;
+    use crate::fuzz::{check_parse, fuzz};
+    use crate::get_ast_from_program_text;
+    use std::thread;
+
+    #[test]
+    fn test_generated_programs() {
+        let failures = fuzz(0, 300);
+        if let Some(failure) = failures.first() {
+            panic!("{} inputs failed, the first with: {}\n{}", failures.len(), failure.message, failure.input);
+        }
+    }
+
+    #[test]
+    fn test_generator_is_deterministic() {
+        assert_eq!(ProgramGenerator::new(7).program(), ProgramGenerator::new(7).program());
+        assert_ne!(ProgramGenerator::new(7).program(), ProgramGenerator::new(8).program());
+    }
+
+    #[test]
+    fn test_malformed_programs() {
+        // Inputs which made the lexer or the parser panic, loop or mangle the program
+        let inputs = [
+            ";",
+            "class Main { main( : Object { 1 }; };",
+            "class Main { main() : Object { { 1; 2 } }; };",
+            "class Main { x : Int <- ; };",
+            "class Main { f() : Int { { } }; };",
+            "class Main { f() : Int { case x of esac }; };",
+            "class Main { f() : Int { let in 1 }; };",
+            "class Main { f() : Int { + 1 }; };",
+            "class Main { f() : Int { 1 2 }; };",
+            "class Main { f() : Int { f() <- 2 }; };",
+            "class Main { f() : Int { x then }; };",
+            "class Main { f() : Int { 99999999999 }; };",
+            "class Main { f() : Int { # }; };",
+            "class Main { f() : Int { \"open }; };",
+            "class Main { f() : String { \"é\" }; };",
+            "class Main { f() : Int { while while x loop 1 pool loop 1 pool }; };",
+            "class Main { f() : Int { 1 }; }; -- the end",
+        ];
+        for input in inputs {
+            check_parse(input).unwrap_or_else(|e| panic!("{input}: {e}"));
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let patterns = [
+            format!("{}1{}", "(".repeat(5000), ")".repeat(5000)),
+            format!("{}1", "~".repeat(5000)),
+            format!("{}1", "not ".repeat(5000)),
+            format!("{}1", "let x : Int in ".repeat(5000)),
+            format!("{}1{}", "case 1 of x : Int => ".repeat(5000), "; esac".repeat(5000)),
+            format!("{}1{}", "f(".repeat(5000), ")".repeat(5000)),
+            format!("a{}", ".f()".repeat(5000)),
+            format!("1{}", " + 1".repeat(5000)),
+        ];
+        // The parser is recursive: the limit keeps it within the stack of the main thread
+        let parse = thread::Builder::new().stack_size(8 << 20).spawn(move || {
+            for body in patterns {
+                let text = format!("class A {{ f() : Int {{ {body} }}; }};");
+                let e = get_ast_from_program_text(&text).expect_err("The expression must be too deep");
+                assert!(e.contains("Expression nested too deeply"), "{e}");
+            }
+        });
+        parse.expect("Couldn't start the thread").join().expect("The parser must not overflow the stack");
+        check_parse(&format!("class A {{ f() : Int {{ {}1{} }}; }};", "(".repeat(100), ")".repeat(100))).unwrap();
+    }
+}
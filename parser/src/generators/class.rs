@@ -20,7 +20,7 @@ pub(super) fn gen_class(iter: &mut BufferedTokenIter) -> Result<ParseClass, Stri
     else {
         unreachable!()
     };
-    let class_type: Type = Type::from(value);
+    let class_type: Type = Type::from((value, line_num, line_pos));
 
     let parent_type: Option<Type> = if iter.peek_eq(&INHERITS_TYPE) {
         match iter.consume_required(&INHERITS_TYPE) {
@@ -28,11 +28,11 @@ pub(super) fn gen_class(iter: &mut BufferedTokenIter) -> Result<ParseClass, Stri
             Err(e) => errors.push_str(&e),
         };
 
-        let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+        let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
             unreachable!()
         };
 
-        let inherits_from: Type = Type::from(value);
+        let inherits_from: Type = Type::from((value, line_num, line_pos));
         Some(inherits_from)
     } else {
         None
@@ -44,8 +44,7 @@ pub(super) fn gen_class(iter: &mut BufferedTokenIter) -> Result<ParseClass, Stri
         None
     } else {
         let mut feature_iter = iter.gen_iter_till(&CLOSE_CURL_TYPE);
-        let gen_features = gen_features(&mut feature_iter)?;
-        gen_features
+        gen_features(&mut feature_iter)?
     };
 
     match iter.consume_required(&CLOSE_CURL_TYPE) {
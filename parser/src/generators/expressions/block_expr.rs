@@ -21,10 +21,10 @@ pub(in crate::generators) fn gen_block_expr(
         block_expr_list.push(expr);
     }
 
-    assert!(
-        !block_expr_list.is_empty(),
-        "Block expression must contain at least one expression"
-    );
+    if block_expr_list.is_empty() {
+        let (line_num, line_pos) = iter.get_last_pos();
+        return Err(format!("{line_num}:{line_pos} Block must contain at least one expression"));
+    }
     iter.consume_required(&CLOSE_CURL_TYPE)?;
 
     Ok(Expression::Block {
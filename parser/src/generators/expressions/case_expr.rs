@@ -31,24 +31,27 @@ fn gen_case_branch_list(iter: &mut BufferedTokenIter) -> Result<Vec<CaseBranch>,
         case_branch_list.push(gen_case_branch(iter)?);
     }
 
-    assert!(!case_branch_list.is_empty()); // case expression must have at least one branch
+    if case_branch_list.is_empty() {
+        let (line_num, line_pos) = iter.get_last_pos();
+        return Err(format!("{line_num}:{line_pos} Case must have at least one branch"));
+    }
 
     Ok(case_branch_list)
 }
 
 /// `ID` : `TYPE` => `expr` ;
 fn gen_case_branch(iter: &mut BufferedTokenIter) -> Result<CaseBranch, String> {
-    let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let id: Ident = Ident::from(value);
+    let id: Ident = Ident::from((value, line_num, line_pos));
 
     iter.consume_required(&COLON_TYPE)?;
 
-    let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let id_type: Type = Type::from(value);
+    let id_type: Type = Type::from((value, line_num, line_pos));
 
     iter.consume_required(&CASE_BRANCH_TYPE)?;
 
@@ -11,10 +11,10 @@ pub(super) fn gen_partial_dispatch_expr(
     ident_token: Token,
     iter: &mut BufferedTokenIter,
 ) -> Result<Expression, String> {
-    let Token::Ident { value, .. } = ident_token else {
+    let Token::Ident { value, line_num, line_pos } = ident_token else {
         unreachable!()
     };
-    let fn_name = Ident::from(value);
+    let fn_name = Ident::from((value, line_num, line_pos));
     let param_list = gen_fn_param_list(iter)?;
 
     Ok(Expression::PartialDispatch {
@@ -32,18 +32,18 @@ pub(super) fn gen_partial_cast_dispatch(
     if iter.peek_eq(&AT_TYPE) {
         iter.consume_required(&AT_TYPE)?;
 
-        let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+        let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
             unreachable!()
         };
-        cast_type = Some(Type::from(value));
+        cast_type = Some(Type::from((value, line_num, line_pos)));
     }
 
     iter.consume_required(&DOT_TYPE)?;
 
-    let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let fn_name = Ident::from(value);
+    let fn_name = Ident::from((value, line_num, line_pos));
 
     let param_list = gen_fn_param_list(iter)?;
 
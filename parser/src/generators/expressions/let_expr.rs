@@ -37,27 +37,27 @@ fn gen_let_init_list(iter: &mut BufferedTokenIter) -> Result<Vec<LetInit>, Strin
         iter.consume_next_if_eq(&COMMA_TYPE);
     }
 
-    assert!(
-        !init_list.is_empty(),
-        "Let expression initialisation list is empty"
-    );
+    if init_list.is_empty() {
+        let (line_num, line_pos) = iter.get_last_pos();
+        return Err(format!("{line_num}:{line_pos} Let must declare at least one variable"));
+    }
 
     Ok(init_list)
 }
 
 /// `Id` : `Type` {{ <- expr }}
 fn gen_let_init(iter: &mut BufferedTokenIter) -> Result<LetInit, String> {
-    let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let id: Ident = Ident::from(value);
+    let id: Ident = Ident::from((value, line_num, line_pos));
 
     iter.consume_required(&COLON_TYPE)?;
 
-    let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let id_type: Type = Type::from(value);
+    let id_type: Type = Type::from((value, line_num, line_pos));
 
     let mut expr: Option<Expression> = None;
 
@@ -15,39 +15,91 @@ use dispatch_expr::gen_partial_cast_dispatch;
 use let_expr::gen_let_expression;
 use lexer::iter::token::{BaseTokenIter, BufferedTokenIter};
 use lexer::model::constants::{
-  ASSIGN_TYPE, CLOSE_CURL_TYPE, CLOSE_PAREN_TYPE, END_CASE_TYPE, END_IF_TYPE, END_LOOP_TYPE,
+  ASSIGN_TYPE, CLOSE_PAREN_TYPE,
   IDENT_TYPE, NEW_TYPE, OPEN_PAREN_TYPE,
 };
 use lexer::model::token::Token;
 use loop_expr::gen_loop_expression;
+use std::cell::Cell;
 use std::collections::VecDeque;
 use std::mem::replace;
 
+/// Expressions nest at most this deep, so that the recursion of the parser, and of the passes over the program after
+/// it, does not overflow the stack
+const MAX_NESTING: usize = 150;
+
+thread_local! {
+    /// Levels of expressions around the one being parsed
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Levels of nesting of the expression being parsed, given back when it is dropped
+struct NestingGuard(usize);
+
+impl NestingGuard {
+    fn enter(levels: usize, (line_num, line_pos): (u32, u32)) -> Result<NestingGuard, String> {
+        let outer = NESTING.get();
+        if outer + levels > MAX_NESTING {
+            return Err(format!("{line_num}:{line_pos} Expression nested too deeply, the limit is {MAX_NESTING} levels"));
+        }
+        NESTING.set(outer + levels);
+        Ok(NestingGuard(outer))
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        NESTING.set(self.0);
+    }
+}
+
 pub(super) fn gen_expression(
     iter: &mut BufferedTokenIter,
     read_till_token: &Token,
 ) -> Result<Expression, String> {
     let mut expression_token_iter = iter.gen_iter_till(read_till_token);
 
-    let partial_expressions = gen_partial_expressions(&mut expression_token_iter, read_till_token)?;
+    let partial_expressions = gen_partial_expressions(&mut expression_token_iter, read_till_token, false)?;
+    if partial_expressions.is_empty() {
+        let (line_num, line_pos) = iter.get_last_pos();
+        return Err(format!("{line_num}:{line_pos} Missing expression"));
+    }
     let expr = reduce_expression_list(partial_expressions)?;
     Ok(expr)
 }
 
+/// Operand of a binary operator, `~` or `isvoid`: a single expression with the dispatches applied to it
+pub(super) fn gen_operand_expression(
+    iter: &mut BufferedTokenIter,
+    read_till_token: &Token,
+) -> Result<Expression, String> {
+    let partial_expressions = gen_partial_expressions(iter, read_till_token, true)?;
+    if partial_expressions.is_empty() {
+        let (line_num, line_pos) = iter.get_last_pos();
+        return Err(format!("{line_num}:{line_pos} Missing operand"));
+    }
+    reduce_expression_list(partial_expressions)
+}
+
+/// Reads expressions till the end of the iterator or, when `single_operand` is set, till the end of the first
+/// expression and the dispatches on it.
 fn gen_partial_expressions(
     iter: &mut BufferedTokenIter,
     read_till_token: &Token,
+    single_operand: bool,
 ) -> Result<VecDeque<Expression>, String> {
     let mut expr_list: VecDeque<Expression> = VecDeque::new();
     while iter.has_next() {
         let Some(peek) = iter.peek() else {
             return Err(String::from("gen_partial_expressions: Unexpected EOF"));
         };
+        if single_operand && !expr_list.is_empty() && !matches!(peek, Token::At { .. } | Token::Dot { .. }) {
+            break;
+        }
+        // Each expression of the list takes the previous ones as its operand or receiver once reduced
+        let _nesting = NestingGuard::enter(expr_list.len() + 1, peek.get_pos())?;
         match peek {
-            Token::Empty | Token::Error { .. } | Token::Comment { .. } => {
-                dbg!("get_expression_helper: Unexpected token {:?}", &peek);
-                panic!("Unexpected token {peek}");
-            }
+            Token::Error { value, line_num, line_pos } => return Err(format!("{line_num}:{line_pos} {value}")),
 
             Token::Ident { .. } => {
                 let ident_token = iter.get_required(&IDENT_TYPE)?;
@@ -89,29 +141,26 @@ fn gen_partial_expressions(
             Token::New { .. } => {
                 iter.consume_required(&NEW_TYPE)?;
 
-                let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+                let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
                     unreachable!()
                 };
                 expr_list.push_back(Expression::New {
-                    type_name: Type::from(value),
+                    type_name: Type::from((value, line_num, line_pos)),
                 });
             }
 
             Token::If { .. } => {
-                let mut cond_expr_iter = iter.gen_iter_till(&END_IF_TYPE);
-                let conditional_expr = gen_conditional_expression(&mut cond_expr_iter)?;
+                let conditional_expr = gen_conditional_expression(iter)?;
                 expr_list.push_back(conditional_expr);
             }
 
             Token::While { .. } => {
-                let mut loop_expr_iter = iter.gen_iter_till(&END_LOOP_TYPE);
-                let loop_expr = gen_loop_expression(&mut loop_expr_iter)?;
+                let loop_expr = gen_loop_expression(iter)?;
                 expr_list.push_back(loop_expr);
             }
 
             Token::Case { .. } => {
-                let mut case_expr_iter = iter.gen_iter_till(&END_CASE_TYPE);
-                let case_expr = gen_case_expression(&mut case_expr_iter)?;
+                let case_expr = gen_case_expression(iter)?;
                 expr_list.push_back(case_expr);
             }
 
@@ -121,14 +170,12 @@ fn gen_partial_expressions(
             }
 
             Token::OpenParen { .. } => {
-                let mut single_expr_iter = iter.gen_iter_till(&CLOSE_PAREN_TYPE);
-                let single_expr = gen_single_expr_within_paren(&mut single_expr_iter)?;
+                let single_expr = gen_single_expr_within_paren(iter)?;
                 expr_list.push_back(single_expr);
             }
 
             Token::OpenCurl { .. } => {
-                let mut block_expr_iter = iter.gen_iter_till(&CLOSE_CURL_TYPE);
-                let block_expr = block_expr::gen_block_expr(&mut block_expr_iter)?;
+                let block_expr = block_expr::gen_block_expr(iter)?;
                 expr_list.push_back(block_expr);
             }
 
@@ -161,17 +208,11 @@ fn gen_partial_expressions(
                 expr_list.push_back(partial_cast_dispatch);
             }
 
-            // Should never encounter these expressions, since no expression starts with these tokens
-            Token::Then { .. } | Token::Else { .. } | Token::EndIf { .. } => {
-                panic!("Unexpected conditional branch {peek}")
-            }
-            Token::Loop { .. } | Token::EndLoop { .. } => panic!("Unexpected loop branch {peek}"),
-            Token::CaseBranch { .. } | Token::Of { .. } | Token::EndCase { .. } => {
-                panic!("Unexpected case branch {peek}")
+            // No expression starts with the other tokens, such as `then`, `of` or `;`
+            _ => {
+                let (line_num, line_pos) = peek.get_pos();
+                return Err(format!("{line_num}:{line_pos} Unexpected {}", peek.get_key()));
             }
-            Token::In { .. } => panic!("Unexpected let branch {peek}"),
-
-            _ => panic!("Unexpected token {peek}"),
         }
     }
 
@@ -180,116 +221,138 @@ fn gen_partial_expressions(
 
 /// Collapse a list of expressions into a single expression; error otherwise
 fn reduce_expression_list(mut expressions: VecDeque<Expression>) -> Result<Expression, String> {
-    assert!(
-        !expressions.is_empty(),
-        "The expression list cannot be reduced on empty list"
-    );
+    let Some(first) = expressions.front() else {
+        return Err(String::from("Missing expression"));
+    };
+    let (line_num, line_pos) = first.get_pos();
+    match first {
+        Expression::PartialBinary { binary_token, .. } => {
+            let (line_num, line_pos) = binary_token.get_pos();
+            return Err(format!("{line_num}:{line_pos} Missing left operand of {}", binary_token.get_key()));
+        }
+        Expression::PartialAssign { .. } => return Err(format!("{line_num}:{line_pos} Missing the identifier assigned")),
+        Expression::PartialCastDispatch { fn_name, .. } => {
+            return Err(format!("{line_num}:{line_pos} Missing the receiver of the dispatch to {}", fn_name.get_name()));
+        }
+        _ => (),
+    }
 
     if expressions.len() == 1 {
         let e = expressions.pop_front().unwrap();
         if let Expression::PartialDispatch { .. } = e {
             return Ok(e.convert_to_dispatch());
         }
-
-        assert!(!e.is_partial(), "List with a single partial expression");
         return Ok(e);
     }
 
-    let reduce: Expression;
-
-    let first = expressions.pop_front().unwrap();
-    let second = expressions.get(0).unwrap().clone();
-
-    assert!(second.is_partial(), "Last expression must be partial");
-
-    match second {
-        Expression::PartialBinary {
-            binary_token,
-            right_expr,
-        } => match binary_token {
-            Token::Plus { .. } => {
-                reduce = Expression::Plus {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
-            Token::Minus { .. } => {
-                reduce = Expression::Minus {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
-            Token::Star { .. } => {
-                reduce = Expression::Multiply {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
-            Token::ForwardSlash { .. } => {
-                reduce = Expression::Divide {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
+    // Dispatches and assignment bind to the first expression, so a list with only binary operators left
+    // is reduced by operator precedence
+    if expressions.iter().skip(1).all(|e| matches!(e, Expression::PartialBinary { .. })) {
+        return reduce_binary_expressions(expressions);
+    }
 
-            Token::Less { .. } => {
-                reduce = Expression::LessThan {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
-            Token::LessOrEqual { .. } => {
-                reduce = Expression::LessThanOrEqual {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
-            Token::Equal { .. } => {
-                reduce = Expression::Equal {
-                    left: Box::from(first),
-                    right: right_expr,
-                }
-            }
+    let first = match expressions.pop_front().unwrap() {
+        e @ Expression::PartialDispatch { .. } => e.convert_to_dispatch(), // dispatch on `self` used as an operand
+        e => e,
+    };
+    let second = expressions.front().unwrap().clone();
 
-            _ => return Err(format!("Unexpected token {binary_token}")),
-        },
+    let reduce = match second {
         Expression::PartialAssign { expr } => {
             let Expression::IdentExpr { name, .. } = first else {
-                return Err(format!("PartialAssign: join expression is not ident"));
+                let (line_num, line_pos) = first.get_pos();
+                return Err(format!("{line_num}:{line_pos} Only an identifier can be assigned"));
             };
-            reduce = Expression::Assign { name, expr };
+            Expression::Assign { name, expr }
         }
         Expression::PartialCastDispatch {
             fn_name,
             cast_type,
             param_list,
-        } => {
-            reduce = Expression::Dispatch {
-                calling_expr: Box::from(first),
-                cast_type,
-                fn_name,
-                param_list,
-            };
-        }
+        } => Expression::Dispatch {
+            calling_expr: Box::from(first),
+            cast_type,
+            fn_name,
+            param_list,
+        },
         Expression::PartialDispatch {
             fn_name,
             param_list,
-        } => {
-            reduce = Expression::Dispatch {
-                calling_expr: Box::from(first),
-                cast_type: None,
-                fn_name,
-                param_list,
-            };
+        } => Expression::Dispatch {
+            calling_expr: Box::from(first),
+            cast_type: None,
+            fn_name,
+            param_list,
+        },
+        // A binary operator after an expression which doesn't take one, or two expressions in a row
+        _ => {
+            let (line_num, line_pos) = second.get_pos();
+            return Err(format!("{line_num}:{line_pos} Unexpected {} after an expression", second.get_type()));
         }
-        _ => panic!("Incorrect expression {second}"),
-    }
+    };
 
     let _ = replace(&mut expressions[0], reduce);
     reduce_expression_list(expressions)
 }
 
-/// ...previously seen expression.. + {`+` | `-` | `*`| `/`| `<`| `<=`| `=`} expr
+/// Reduces `expr (op expr)*`, where `*` and `/` bind tighter than `+` and `-`, which bind tighter than the
+/// comparisons. Operators of the same precedence are left associative.
+fn reduce_binary_expressions(mut expressions: VecDeque<Expression>) -> Result<Expression, String> {
+    let first = match expressions.pop_front().unwrap() {
+        e @ Expression::PartialDispatch { .. } => e.convert_to_dispatch(),
+        e => e,
+    };
+
+    let mut operands: Vec<Expression> = vec![first];
+    let mut operators: Vec<Token> = Vec::new();
+
+    for expr in expressions {
+        let Expression::PartialBinary { binary_token, right_expr } = expr else {
+            unreachable!("Only binary expressions are reduced by precedence")
+        };
+
+        while operators.last().is_some_and(|op| precedence(op) >= precedence(&binary_token)) {
+            apply_binary_operator(&mut operands, operators.pop().unwrap())?;
+        }
+        operators.push(binary_token);
+        operands.push(*right_expr);
+    }
+
+    while let Some(op) = operators.pop() {
+        apply_binary_operator(&mut operands, op)?;
+    }
+
+    Ok(operands.pop().unwrap())
+}
+
+fn precedence(binary_token: &Token) -> u8 {
+    match binary_token {
+        Token::Star { .. } | Token::ForwardSlash { .. } => 3,
+        Token::Plus { .. } | Token::Minus { .. } => 2,
+        _ => 1,
+    }
+}
+
+fn apply_binary_operator(operands: &mut Vec<Expression>, binary_token: Token) -> Result<(), String> {
+    let right = Box::from(operands.pop().unwrap());
+    let left = Box::from(operands.pop().unwrap());
+
+    let expr = match binary_token {
+        Token::Plus { .. } => Expression::Plus { left, right },
+        Token::Minus { .. } => Expression::Minus { left, right },
+        Token::Star { .. } => Expression::Multiply { left, right },
+        Token::ForwardSlash { .. } => Expression::Divide { left, right },
+        Token::Less { .. } => Expression::LessThan { left, right },
+        Token::LessOrEqual { .. } => Expression::LessThanOrEqual { left, right },
+        Token::Equal { .. } => Expression::Equal { left, right },
+        _ => return Err(format!("Unexpected token {binary_token}")),
+    };
+
+    operands.push(expr);
+    Ok(())
+}
+
+/// ...previously seen expression.. + {`+` | `-` | `*`| `/`| `<`| `<=`| `=`} operand
 fn gen_partial_binary_expr(
     token_iter: &mut BufferedTokenIter,
     read_till_tokens: &Token,
@@ -297,7 +360,7 @@ fn gen_partial_binary_expr(
     let binary_token = token_iter
         .next()
         .unwrap_or_else(|| panic!("get_expression_helper: Error reading binary token"));
-    let right = gen_expression(token_iter, read_till_tokens)?;
+    let right = gen_operand_expression(token_iter, read_till_tokens)?;
     let partial_binary_expr = Expression::PartialBinary {
         binary_token,
         right_expr: Box::new(right),
@@ -315,3 +378,46 @@ fn gen_single_expr_within_paren(iter: &mut BufferedTokenIter) -> Result<Expressi
 
     Ok(expr)
 }
+
+#[cfg(test)]
+mod test {
+    use crate::generators::expressions::gen_expression;
+    use crate::model::expressions::Expression;
+    use crate::test::get_buffered_iter;
+    use lexer::model::token::Token;
+    use std::fs::File;
+
+    #[test]
+    fn test_binary_precedence() {
+        let file = File::open("../test_resources/expressions/expr.binary").expect("file not found");
+        let mut iter = get_buffered_iter(file);
+        let expr = gen_expression(&mut iter, &Token::EOF).expect("expression generation failure");
+
+        // (((1 + (2 * 3)) - 4) < ((5 + 6) * ~7)) = ((if .. fi) + ((isvoid x.f()) * 10))
+        let Expression::Equal { left, right } = expr else {
+            panic!("Expected an equality at the top level, got {expr}");
+        };
+
+        let Expression::LessThan { left: less_left, right: less_right } = *left else {
+            panic!("Expected a comparison, got {left}");
+        };
+        let Expression::Minus { left: minus_left, .. } = *less_left else {
+            panic!("Expected a subtraction, got {less_left}");
+        };
+        assert!(matches!(*minus_left, Expression::Plus { ref right, .. } if matches!(**right, Expression::Multiply { .. })));
+        let Expression::Multiply { left: mul_left, right: mul_right } = *less_right else {
+            panic!("Expected a multiplication, got {less_right}");
+        };
+        assert!(matches!(*mul_left, Expression::Plus { .. }));
+        assert!(matches!(*mul_right, Expression::Negate { .. }));
+
+        let Expression::Plus { left: plus_left, right: plus_right } = *right else {
+            panic!("Expected an addition, got {right}");
+        };
+        assert!(matches!(*plus_left, Expression::Conditional { .. }));
+        let Expression::Multiply { left: mul_left, .. } = *plus_right else {
+            panic!("Expected a multiplication, got {plus_right}");
+        };
+        assert!(matches!(*mul_left, Expression::IsVoid { ref expr } if matches!(**expr, Expression::Dispatch { .. })));
+    }
+}
@@ -14,7 +14,12 @@ pub(in crate::generators) fn gen_unary_expr(
         .next()
         .unwrap_or_else(|| panic!("get_expression_helper: Error reading unary token"));
 
-    let sub_expr = expressions::gen_expression(iter, read_till_tokens)?;
+    // `not` has a lower precedence than all binary operators, `~` and `isvoid` apply to a single operand
+    let sub_expr = if unary_token == NOT_TYPE {
+        expressions::gen_expression(iter, read_till_tokens)?
+    } else {
+        expressions::gen_operand_expression(iter, read_till_tokens)?
+    };
 
     let unary_expr = if unary_token == NOT_TYPE {
         Expression::Not {
@@ -33,10 +33,10 @@ pub(super) fn gen_features(iter: &mut BufferedTokenIter) -> Result<Option<Vec<Pa
 
 fn gen_feature(iter: &mut BufferedTokenIter, read_till_token: &Token) -> Result<ParseFeature, String> {
   //Feature starts with ID
-  let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+  let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
     unreachable!()
   };
-  let ident_name = Ident::from(value);
+  let ident_name = Ident::from((value, line_num, line_pos));
 
   let feature: ParseFeature = match iter.peek() {
     Some(peeked_token) if *peeked_token == COLON_TYPE => {
@@ -70,10 +70,10 @@ fn gen_method_feature(ident_name: Ident, iter: &mut BufferedTokenIter) -> Result
   iter.consume_required(&CLOSE_PAREN_TYPE)?;
   iter.consume_required(&COLON_TYPE)?;
 
-  let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+  let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
     unreachable!()
   };
-  let method_return_type = Type::from(value);
+  let method_return_type = Type::from((value, line_num, line_pos));
 
   iter.consume_required(&OPEN_CURL_TYPE)?;
 
@@ -94,10 +94,10 @@ fn gen_attribute_feature(
 ) -> Result<Attribute, String> {
   iter.consume_required(&COLON_TYPE)?;
 
-  let Token::Ident { value, .. } = iter.get_required(&IDENT_TYPE)? else {
+  let Token::Ident { value, line_num, line_pos } = iter.get_required(&IDENT_TYPE)? else {
     unreachable!()
   };
-  let method_return_type = Type::from(value);
+  let method_return_type = Type::from((value, line_num, line_pos));
 
   let attribute = if iter.peek_eq(&ASSIGN_TYPE) {
     iter.consume_required(&ASSIGN_TYPE)?;
@@ -177,7 +177,7 @@ mod test {
     assert!(matches!(feature, ParseFeature::Method { .. }));
 
     match feature {
-      ParseFeature::Attribute {..} => assert!(false),
+      ParseFeature::Attribute {..} => panic!("Expected a method feature"),
       ParseFeature::Method { method } => {
         let Method { name: feature_name, formals, return_type, expr } = method;
 
@@ -213,7 +213,7 @@ mod test {
 
         assert!(expr.is_none());
       }
-      ParseFeature::Method { .. } => assert!(false),
+      ParseFeature::Method { .. } => panic!("Expected an attribute feature"),
     }
   }
 
@@ -255,7 +255,7 @@ mod test {
         let Some(feature_expr) = expr else { unreachable!();};
         assert_eq!(feature_expr.get_type(), "New");
       },
-      ParseFeature::Method { .. } => assert!(false),
+      ParseFeature::Method { .. } => panic!("Expected an attribute feature"),
     }
   }
 }
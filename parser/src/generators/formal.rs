@@ -22,17 +22,17 @@ pub(super) fn gen_formals(iter: &mut BufferedTokenIter) -> Result<Vec<Formal>, S
 
 /// Formal |-> ID : TYPE
 fn gen_formal(token_iter: &mut BufferedTokenIter) -> Result<Formal, String> {
-    let Token::Ident { value, .. } = token_iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = token_iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let formal_name = Ident::from(value);
+    let formal_name = Ident::from((value, line_num, line_pos));
 
     token_iter.consume_required(&COLON_TYPE)?; // consume colon
 
-    let Token::Ident { value, .. } = token_iter.get_required(&IDENT_TYPE)? else {
+    let Token::Ident { value, line_num, line_pos } = token_iter.get_required(&IDENT_TYPE)? else {
         unreachable!()
     };
-    let formal_type: Type = Type::from(value);
+    let formal_type: Type = Type::from((value, line_num, line_pos));
 
     Ok((formal_name, formal_type).into())
 }
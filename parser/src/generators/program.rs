@@ -9,8 +9,12 @@ pub(crate) fn gen_program_from_file(file: File) -> Result<ParseProgram, String>
     gen_program(&mut token_iter)
 }
 
-/// Program is a list of semicolon separated classes
+pub(crate) fn gen_program_from_text(program: &str) -> Result<ParseProgram, String> {
+    let mut token_iter: TokenIter = TokenIter::from_program_text(program);
+    gen_program(&mut token_iter)
+}
 
+/// Program is a list of semicolon separated classes
 fn gen_program(iter: &mut TokenIter) -> Result<ParseProgram, String> {
     let mut program: ParseProgram = ParseProgram::new();
     let mut errors = String::new();
@@ -18,8 +22,9 @@ fn gen_program(iter: &mut TokenIter) -> Result<ParseProgram, String> {
     while iter.has_next() {
         let program_tokens = iter.collect_till(&SEMI_COLON_TYPE);
 
-        if program_tokens.is_empty() {
-            continue;
+        // Only comments after the last class
+        if program_tokens.is_empty() && !iter.has_next() {
+            break;
         }
 
         let mut buffered_iter = BufferedTokenIter::from(program_tokens);
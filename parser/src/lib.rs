@@ -1,9 +1,12 @@
-use crate::generators::program::gen_program_from_file;
+use crate::generators::program::{gen_program_from_file, gen_program_from_text};
 use crate::model::program::ParseProgram;
 use std::fs::File;
 
 pub(crate) mod generators;
+pub mod fuzz;
 pub mod model;
+pub mod printer;
+pub mod programs;
 
 pub fn get_ast_from_file_path(file_path: &str) -> Result<ParseProgram, String> {
     let f = File::open(file_path).expect("Unable to open file");
@@ -13,6 +16,11 @@ pub fn get_ast(file: File) -> Result<ParseProgram, String> {
     gen_program_from_file(file)
 }
 
+/// Parses a program given as source text rather than as a file
+pub fn get_ast_from_program_text(program: &str) -> Result<ParseProgram, String> {
+    gen_program_from_text(program)
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use lexer::iter::token::{BufferedTokenIter, TokenIter};
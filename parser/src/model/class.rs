@@ -19,8 +19,8 @@ pub const BOOL_CLASS_NAME: &str = "Bool";
 pub const VOID_CLASS_NAME: &str = "Void";
 
 pub const OBJECT: ParseClass = ParseClass {
-    name: Type(Cow::Borrowed(OBJECT_CLASS_NAME)),
-    parent_type: Type(Cow::Borrowed("BASE_OBJECT")),
+    name: Type(Cow::Borrowed(OBJECT_CLASS_NAME), 0, 0),
+    parent_type: Type(Cow::Borrowed("BASE_OBJECT"), 0, 0),
     features: None,
     line_num: 0,
     line_pos: 0,
@@ -38,11 +38,7 @@ impl ParseClass {
         line_num: u32,
         line_pos: u32,
     ) -> Self {
-        let parent: Type = if parent_type.is_some() {
-            parent_type.unwrap()
-        } else {
-            OBJECT.name.clone()
-        };
+        let parent: Type = parent_type.unwrap_or_else(|| OBJECT.name.clone());
 
         ParseClass {
             name: class_type,
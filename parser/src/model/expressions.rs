@@ -157,10 +157,148 @@ impl Expression {
         )
     }
 
+    /// Position of the first token of the expression that carries one, `(0, 0)` if none does
+    pub fn get_pos(&self) -> (u32, u32) {
+        match self {
+            Expression::Assign { name, .. } | Expression::IdentExpr { name } => name.get_pos(),
+
+            Expression::PartialDispatch { fn_name, .. } | Expression::PartialCastDispatch { fn_name, .. } => fn_name.get_pos(),
+            Expression::Dispatch { calling_expr, fn_name, .. } => match calling_expr.get_pos() {
+                (0, 0) => fn_name.get_pos(),
+                pos => pos,
+            },
+
+            Expression::Conditional { predicate, .. } | Expression::Loop { predicate, .. } => predicate.get_pos(),
+            Expression::Case { switch_expression, .. } => switch_expression.get_pos(),
+            Expression::Block { expr_list } => expr_list.first().map_or((0, 0), |e| e.get_pos()),
+            Expression::Let { let_init, in_expr } => let_init.first().map_or_else(|| in_expr.get_pos(), |init| init.id.get_pos()),
+
+            Expression::Plus { left, .. }
+            | Expression::Minus { left, .. }
+            | Expression::Multiply { left, .. }
+            | Expression::Divide { left, .. }
+            | Expression::LessThan { left, .. }
+            | Expression::Equal { left, .. }
+            | Expression::LessThanOrEqual { left, .. } => left.get_pos(),
+
+            Expression::PartialAssign { expr }
+            | Expression::Negate { expr }
+            | Expression::Not { expr }
+            | Expression::IsVoid { expr } => expr.get_pos(),
+            Expression::PartialBinary { right_expr, .. } => right_expr.get_pos(),
+
+            Expression::IntExpr { line_num, line_pos, .. }
+            | Expression::BoolExpr { line_num, line_pos, .. }
+            | Expression::StringExpr { line_num, line_pos, .. }
+            | Expression::SelfTypeExpr { line_num, line_pos } => (*line_num, *line_pos),
+
+            Expression::New { type_name } => type_name.get_pos(),
+            Expression::SelfExpr => (0, 0),
+        }
+    }
+
+    /// Direct sub-expressions, in evaluation order
+    pub fn sub_expressions(&self) -> Vec<&Expression> {
+        match self {
+            Expression::PartialAssign { expr }
+            | Expression::Assign { expr, .. }
+            | Expression::Negate { expr }
+            | Expression::Not { expr }
+            | Expression::IsVoid { expr } => vec![expr],
+
+            Expression::PartialDispatch { param_list, .. } | Expression::PartialCastDispatch { param_list, .. } => param_list.iter().collect(),
+            Expression::Dispatch { calling_expr, param_list, .. } => {
+                let mut sub_expressions = vec![calling_expr.as_ref()];
+                sub_expressions.extend(param_list);
+                sub_expressions
+            }
+
+            Expression::Conditional { predicate, then_expr, else_expr } => vec![predicate, then_expr, else_expr],
+            Expression::Loop { predicate, body } => vec![predicate, body],
+            Expression::Case { switch_expression, branches } => {
+                let mut sub_expressions = vec![switch_expression.as_ref()];
+                sub_expressions.extend(branches.iter().map(|branch| &branch.expr));
+                sub_expressions
+            }
+            Expression::Block { expr_list } => expr_list.iter().collect(),
+            Expression::Let { let_init, in_expr } => {
+                let mut sub_expressions: Vec<&Expression> = let_init.iter().filter_map(|init| init.expr.as_ref()).collect();
+                sub_expressions.push(in_expr);
+                sub_expressions
+            }
+
+            Expression::PartialBinary { right_expr, .. } => vec![right_expr],
+            Expression::Plus { left, right }
+            | Expression::Minus { left, right }
+            | Expression::Multiply { left, right }
+            | Expression::Divide { left, right }
+            | Expression::LessThan { left, right }
+            | Expression::Equal { left, right }
+            | Expression::LessThanOrEqual { left, right } => vec![left, right],
+
+            Expression::IdentExpr { .. }
+            | Expression::IntExpr { .. }
+            | Expression::BoolExpr { .. }
+            | Expression::StringExpr { .. }
+            | Expression::SelfTypeExpr { .. }
+            | Expression::SelfExpr
+            | Expression::New { .. } => Vec::new(),
+        }
+    }
+
+    /// Direct sub-expressions, in the order of `sub_expressions`, to rewrite them
+    pub fn sub_expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::PartialAssign { expr }
+            | Expression::Assign { expr, .. }
+            | Expression::Negate { expr }
+            | Expression::Not { expr }
+            | Expression::IsVoid { expr } => vec![expr],
+
+            Expression::PartialDispatch { param_list, .. } | Expression::PartialCastDispatch { param_list, .. } => param_list.iter_mut().collect(),
+            Expression::Dispatch { calling_expr, param_list, .. } => {
+                let mut sub_expressions = vec![calling_expr.as_mut()];
+                sub_expressions.extend(param_list);
+                sub_expressions
+            }
+
+            Expression::Conditional { predicate, then_expr, else_expr } => vec![predicate, then_expr, else_expr],
+            Expression::Loop { predicate, body } => vec![predicate, body],
+            Expression::Case { switch_expression, branches } => {
+                let mut sub_expressions = vec![switch_expression.as_mut()];
+                sub_expressions.extend(branches.iter_mut().map(|branch| &mut branch.expr));
+                sub_expressions
+            }
+            Expression::Block { expr_list } => expr_list.iter_mut().collect(),
+            Expression::Let { let_init, in_expr } => {
+                let mut sub_expressions: Vec<&mut Expression> = let_init.iter_mut().filter_map(|init| init.expr.as_mut()).collect();
+                sub_expressions.push(in_expr);
+                sub_expressions
+            }
+
+            Expression::PartialBinary { right_expr, .. } => vec![right_expr],
+            Expression::Plus { left, right }
+            | Expression::Minus { left, right }
+            | Expression::Multiply { left, right }
+            | Expression::Divide { left, right }
+            | Expression::LessThan { left, right }
+            | Expression::Equal { left, right }
+            | Expression::LessThanOrEqual { left, right } => vec![left, right],
+
+            Expression::IdentExpr { .. }
+            | Expression::IntExpr { .. }
+            | Expression::BoolExpr { .. }
+            | Expression::StringExpr { .. }
+            | Expression::SelfTypeExpr { .. }
+            | Expression::SelfExpr
+            | Expression::New { .. } => Vec::new(),
+        }
+    }
+
     pub fn get_type(&self) -> String {
         match self {
             // Expression::NoExpr => String::from("NoExpr"),
-            Expression::SelfExpr { .. } => String::from("Self"),
+            Expression::SelfExpr => String::from("Self"),
             Expression::SelfTypeExpr { .. } => String::from("SelfType"),
             Expression::PartialAssign { .. } => String::from("PartialAssign"),
             Expression::Assign { .. } => String::from("Assign"),
@@ -202,8 +340,8 @@ impl From<Token> for Expression {
     /// - [`Token::SelfType`]
     fn from(token: Token) -> Self {
         match token {
-            Token::Ident { value, .. } => Expression::IdentExpr {
-                name: Ident::from(value),
+            Token::Ident { value, line_num, line_pos } => Expression::IdentExpr {
+                name: Ident::from((value, line_num, line_pos)),
             },
 
             Token::Int {
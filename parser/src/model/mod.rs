@@ -7,8 +7,9 @@ pub mod feature;
 pub mod formal;
 pub mod program;
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct Ident(pub Cow<'static, str>);
+/// Identifier with its line number and position in the program file
+#[derive(Debug, Clone)]
+pub struct Ident(pub Cow<'static, str>, pub u32, pub u32);
 impl Display for Ident {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Ident [ {} ]", self.0)
@@ -17,12 +18,26 @@ impl Display for Ident {
 
 impl From<String> for Ident {
     fn from(value: String) -> Self {
-        Ident(Cow::Owned(value))
+        Ident(Cow::Owned(value), 0, 0)
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct Type(pub Cow<'static, str>);
+impl From<(String, u32, u32)> for Ident {
+    fn from((value, line_num, line_pos): (String, u32, u32)) -> Self {
+        Ident(Cow::Owned(value), line_num, line_pos)
+    }
+}
+
+/// Identifiers are equal if their names are equal, irrespective of where they occur
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// Type name with its line number and position in the program file
+#[derive(Debug, Clone)]
+pub struct Type(pub Cow<'static, str>, pub u32, pub u32);
 
 impl Display for Type {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -32,7 +47,20 @@ impl Display for Type {
 
 impl From<String> for Type {
     fn from(value: String) -> Self {
-        Type(Cow::Owned(value))
+        Type(Cow::Owned(value), 0, 0)
+    }
+}
+
+impl From<(String, u32, u32)> for Type {
+    fn from((value, line_num, line_pos): (String, u32, u32)) -> Self {
+        Type(Cow::Owned(value), line_num, line_pos)
+    }
+}
+
+/// Types are equal if their names are equal, irrespective of where they occur
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
     }
 }
 
@@ -40,10 +68,18 @@ impl Type {
     pub fn get_name(&self) -> String {
         String::from(self.0.as_ref())
     }
+
+    pub fn get_pos(&self) -> (u32, u32) {
+        (self.1, self.2)
+    }
 }
 
 impl Ident {
     pub fn get_name(&self) -> String {
         String::from(self.0.as_ref())
     }
+
+    pub fn get_pos(&self) -> (u32, u32) {
+        (self.1, self.2)
+    }
 }
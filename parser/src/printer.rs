@@ -0,0 +1,304 @@
+use crate::model::class::ParseClass;
+use crate::model::expressions::Expression;
+use crate::model::feature::ParseFeature;
+use crate::model::program::ParseProgram;
+
+const INDENT: &str = "    ";
+
+/// Prints the program as COOL source that parses back to the same program.
+///
+/// Operands of the operators, and receivers of dispatches, are parenthesised unless they are a single token or a
+/// block, so the text shows how the parser grouped the expressions. Blocks and the branches of a case are on lines
+/// of their own, everything else is on the line of the expression it is part of.
+pub fn print_program(program: &ParseProgram) -> String {
+    let mut printer = Printer::default();
+    for class in program.classes() {
+        printer.class(class);
+    }
+    printer.text
+}
+
+/// Prints the expression as COOL source, continuation lines indented by `depth` levels
+pub fn print_expression(expr: &Expression, depth: usize) -> String {
+    let mut printer = Printer { text: String::new(), depth };
+    printer.expression(expr);
+    printer.text
+}
+
+#[derive(Default)]
+struct Printer {
+    text: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn class(&mut self, class: &ParseClass) {
+        self.text.push_str(&format!("class {}", class.name.get_name()));
+        let parent = class.parent_type.get_name();
+        if parent != "Object" {
+            self.text.push_str(&format!(" inherits {parent}"));
+        }
+        self.text.push_str(" {\n");
+        self.depth += 1;
+        for feature in class.features.iter().flatten() {
+            self.indent();
+            self.feature(feature);
+            self.text.push_str(";\n");
+        }
+        self.depth -= 1;
+        self.text.push_str("};\n\n");
+    }
+
+    fn feature(&mut self, feature: &ParseFeature) {
+        match feature {
+            ParseFeature::Attribute { attribute } => {
+                self.text.push_str(&format!("{} : {}", attribute.get_name(), attribute.return_type.get_name()));
+                if let Some(expr) = &attribute.expr {
+                    self.text.push_str(" <- ");
+                    self.expression(expr);
+                }
+            }
+            ParseFeature::Method { method } => {
+                let formals: Vec<String> = method.formals.iter().flatten()
+                    .map(|formal| format!("{} : {}", formal.formal_name.get_name(), formal.formal_type.get_name()))
+                    .collect();
+                self.text.push_str(&format!("{}({}) : {} {{\n", method.get_name(), formals.join(", "), method.return_type.get_name()));
+                self.depth += 1;
+                self.indent();
+                self.expression(&method.expr);
+                self.text.push('\n');
+                self.depth -= 1;
+                self.indent();
+                self.text.push('}');
+            }
+        }
+    }
+
+    fn indent(&mut self) {
+        self.text.push_str(&INDENT.repeat(self.depth));
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::IntExpr { value, .. } if *value < 0 => self.text.push_str(&format!("~{}", value.unsigned_abs())),
+            Expression::IntExpr { value, .. } => self.text.push_str(&value.to_string()),
+            Expression::BoolExpr { value, .. } => self.text.push_str(&value.to_string()),
+            Expression::StringExpr { value, .. } => self.text.push_str(&quote(value)),
+            Expression::IdentExpr { name } => self.text.push_str(&name.get_name()),
+            Expression::SelfExpr => self.text.push_str("self"),
+            Expression::SelfTypeExpr { .. } => self.text.push_str("SELF_TYPE"),
+            Expression::New { type_name } => self.text.push_str(&format!("new {}", type_name.get_name())),
+
+            Expression::Assign { name, expr } => {
+                self.text.push_str(&format!("{} <- ", name.get_name()));
+                self.expression(expr);
+            }
+            Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } => {
+                if **calling_expr != Expression::SelfExpr || cast_type.is_some() {
+                    self.operand(calling_expr);
+                    if let Some(cast_type) = cast_type {
+                        self.text.push_str(&format!("@{}", cast_type.get_name()));
+                    }
+                    self.text.push('.');
+                }
+                self.call(&fn_name.get_name(), param_list);
+            }
+            Expression::Conditional { predicate, then_expr, else_expr } => {
+                self.text.push_str("if ");
+                self.expression(predicate);
+                self.text.push_str(" then ");
+                self.expression(then_expr);
+                self.text.push_str(" else ");
+                self.expression(else_expr);
+                self.text.push_str(" fi");
+            }
+            Expression::Loop { predicate, body } => {
+                self.text.push_str("while ");
+                self.expression(predicate);
+                self.text.push_str(" loop ");
+                self.expression(body);
+                self.text.push_str(" pool");
+            }
+            Expression::Case { switch_expression, branches } => {
+                self.text.push_str("case ");
+                self.expression(switch_expression);
+                self.text.push_str(" of\n");
+                self.depth += 1;
+                for branch in branches {
+                    self.indent();
+                    self.text.push_str(&format!("{} : {} => ", branch.id.get_name(), branch.id_type.get_name()));
+                    self.expression(&branch.expr);
+                    self.text.push_str(";\n");
+                }
+                self.depth -= 1;
+                self.indent();
+                self.text.push_str("esac");
+            }
+            Expression::Block { expr_list } => {
+                self.text.push_str("{\n");
+                self.depth += 1;
+                for expr in expr_list {
+                    self.indent();
+                    self.expression(expr);
+                    self.text.push_str(";\n");
+                }
+                self.depth -= 1;
+                self.indent();
+                self.text.push('}');
+            }
+            Expression::Let { let_init, in_expr } => {
+                self.text.push_str("let ");
+                for (index, init) in let_init.iter().enumerate() {
+                    if index > 0 {
+                        self.text.push_str(", ");
+                    }
+                    self.text.push_str(&format!("{} : {}", init.id.get_name(), init.id_type.get_name()));
+                    if let Some(expr) = &init.expr {
+                        self.text.push_str(" <- ");
+                        // The body of a nested `let` would take the next variables
+                        if matches!(expr, Expression::Let { .. }) {
+                            self.parenthesised(expr);
+                        } else {
+                            self.expression(expr);
+                        }
+                    }
+                }
+                self.text.push_str(" in ");
+                self.expression(in_expr);
+            }
+
+            Expression::Plus { left, right } => self.binary(left, "+", right),
+            Expression::Minus { left, right } => self.binary(left, "-", right),
+            Expression::Multiply { left, right } => self.binary(left, "*", right),
+            Expression::Divide { left, right } => self.binary(left, "/", right),
+            Expression::LessThan { left, right } => self.binary(left, "<", right),
+            Expression::LessThanOrEqual { left, right } => self.binary(left, "<=", right),
+            Expression::Equal { left, right } => self.binary(left, "=", right),
+            Expression::Negate { expr } => self.unary("~", expr),
+            Expression::Not { expr } => self.unary("not ", expr),
+            Expression::IsVoid { expr } => self.unary("isvoid ", expr),
+
+            // Partial expressions only exist while the parser builds the complete ones
+            Expression::PartialAssign { expr } => {
+                self.text.push_str("<- ");
+                self.expression(expr);
+            }
+            Expression::PartialDispatch { fn_name, param_list } => self.call(&fn_name.get_name(), param_list),
+            Expression::PartialCastDispatch { cast_type, fn_name, param_list } => {
+                if let Some(cast_type) = cast_type {
+                    self.text.push_str(&format!("@{}", cast_type.get_name()));
+                }
+                self.text.push('.');
+                self.call(&fn_name.get_name(), param_list);
+            }
+            Expression::PartialBinary { binary_token, right_expr } => {
+                self.text.push_str(&format!("{} ", binary_token.get_key().trim_matches(['[', ' ', ']'])));
+                self.operand(right_expr);
+            }
+        }
+    }
+
+    fn call(&mut self, fn_name: &str, param_list: &[Expression]) {
+        self.text.push_str(&format!("{fn_name}("));
+        for (index, param) in param_list.iter().enumerate() {
+            if index > 0 {
+                self.text.push_str(", ");
+            }
+            self.expression(param);
+        }
+        self.text.push(')');
+    }
+
+    fn binary(&mut self, left: &Expression, operator: &str, right: &Expression) {
+        self.operand(left);
+        self.text.push_str(&format!(" {operator} "));
+        self.operand(right);
+    }
+
+    fn unary(&mut self, operator: &str, expr: &Expression) {
+        self.text.push_str(operator);
+        self.operand(expr);
+    }
+
+    /// Prints the operand of an operator or a dispatch, in parentheses unless it cannot be split
+    fn operand(&mut self, expr: &Expression) {
+        match expr {
+            Expression::IntExpr { value, .. } if *value < 0 => self.parenthesised(expr),
+            Expression::IntExpr { .. }
+            | Expression::BoolExpr { .. }
+            | Expression::StringExpr { .. }
+            | Expression::IdentExpr { .. }
+            | Expression::SelfExpr
+            | Expression::SelfTypeExpr { .. }
+            | Expression::Block { .. } => self.expression(expr),
+            _ => self.parenthesised(expr),
+        }
+    }
+
+    fn parenthesised(&mut self, expr: &Expression) {
+        self.text.push('(');
+        self.expression(expr);
+        self.text.push(')');
+    }
+}
+
+/// The string as a COOL string literal
+fn quote(value: &str) -> String {
+    let mut literal = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\x0C' => literal.push_str("\\f"),
+            '\x0B' => literal.push_str("\\v"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod test {
+    use crate::get_ast_from_program_text;
+    use crate::printer::print_program;
+    use std::fs;
+
+    #[test]
+    fn test_print_program() {
+        let source = "class Main inherits IO { x : Int <- 1 + 2 * 3; main() : Object { { out_string(\"a\\n\"); let y : Int <- x in if y < 3 then self@IO.out_int(~y) else not true fi; } }; };";
+        let program = get_ast_from_program_text(source).expect("Program must parse");
+        let expected = "\
+class Main inherits IO {
+    x : Int <- 1 + (2 * 3);
+    main() : Object {
+        {
+            out_string(\"a\\n\");
+            let y : Int <- x in if y < 3 then self@IO.out_int(~y) else not true fi;
+        }
+    };
+};
+
+";
+        assert_eq!(print_program(&program), expected);
+    }
+
+    #[test]
+    fn test_printed_programs_parse_back() {
+        for entry in fs::read_dir("../test_resources/programs").expect("Couldn't read the programs") {
+            let path = entry.expect("Couldn't read the programs").path();
+            if path.extension().is_none_or(|extension| extension != "cl") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).expect("Couldn't read the program");
+            let program = get_ast_from_program_text(&source).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            let printed = print_program(&program);
+            let reparsed = get_ast_from_program_text(&printed).unwrap_or_else(|e| panic!("{}: {e}\n{printed}", path.display()));
+            // Literals keep their positions, which differ in the printed text, so the programs are compared printed
+            assert_eq!(print_program(&reparsed), printed, "{} parses differently once printed", path.display());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! The test programs of `test_resources/programs`, as the golden tests lay them out.
+//!
+//! A program made of several files lists the others in `X.deps`, one per line, and a program reading input has it in
+//! `X.in`. The tests of every phase find the programs with `find_programs`, so a new program needs no change to them.
+
+use crate::get_ast_from_program_text;
+use crate::model::program::ParseProgram;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A program and the input it is run on
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestProgram {
+    /// Its own file first, then those it depends on
+    pub files: Vec<PathBuf>,
+    pub input: String,
+}
+
+impl TestProgram {
+    /// The program of the file, with the dependencies and the input given next to it
+    pub fn new(path: &Path) -> Self {
+        let mut files = vec![path.to_path_buf()];
+        files.extend(dependencies(path));
+        let input = fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+        TestProgram { files, input }
+    }
+
+    /// The name of its own file, without the extension
+    pub fn name(&self) -> String {
+        self.files[0].file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string())
+    }
+
+    /// The classes of all its files
+    pub fn parse(&self) -> Result<ParseProgram, String> {
+        let mut program = parse_file(&self.files[0])?;
+        for file in &self.files[1..] {
+            program.classes.extend(parse_file(file)?.classes);
+        }
+        Ok(program)
+    }
+}
+
+fn parse_file(path: &Path) -> Result<ParseProgram, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    get_ast_from_program_text(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// The files the program lists in its `.deps` file, none without one
+pub fn dependencies(path: &Path) -> Vec<PathBuf> {
+    let deps = fs::read_to_string(path.with_extension("deps")).unwrap_or_default();
+    deps.lines().map(|file| path.with_file_name(file)).collect()
+}
+
+/// The programs of the directory, not of its subdirectories, by name. The files other programs depend on are only
+/// parts of them and are left out.
+pub fn find_programs(dir: impl AsRef<Path>) -> Vec<TestProgram> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Couldn't read the programs")
+        .map(|entry| entry.expect("Couldn't read the programs").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cl"))
+        .collect();
+    paths.sort();
+    let dependencies: Vec<PathBuf> = paths.iter().flat_map(|path| dependencies(path)).collect();
+    paths.retain(|path| !dependencies.contains(path));
+    paths.iter().map(|path| TestProgram::new(path)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::programs::find_programs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_find_programs() {
+        let programs = find_programs("../test_resources/programs");
+        let atoi_test = programs.iter().find(|program| program.name() == "atoi_test").expect("atoi_test.cl must be found");
+        let files = ["atoi_test.cl", "atoi.cl"].map(|file| PathBuf::from("../test_resources/programs").join(file));
+        assert_eq!(atoi_test.files, files);
+        assert_eq!(atoi_test.input, "42\n-17\nstop\n");
+        assert!(atoi_test.parse().is_ok_and(|program| program.classes.iter().any(|class| class.get_name() == "A2I")));
+        assert!(!programs.iter().any(|program| program.name() == "atoi"), "atoi.cl is part of atoi_test.cl");
+        assert!(!programs.iter().any(|program| program.files[0].starts_with("../test_resources/programs/errors")));
+    }
+}
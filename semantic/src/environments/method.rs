@@ -0,0 +1,103 @@
+use crate::models::symbols::SymbolType;
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::{INT_CLASS_NAME, IO_CLASS_NAME, OBJECT_CLASS_NAME, STR_CLASS_NAME};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Class, name, formals and return type of a method of a basic class
+type BuiltInMethod = (&'static str, &'static str, &'static [(&'static str, &'static str)], &'static str);
+
+const BUILT_IN_METHODS: [BuiltInMethod; 10] = [
+  (OBJECT_CLASS_NAME, "abort", &[], OBJECT_CLASS_NAME),
+  (OBJECT_CLASS_NAME, "type_name", &[], STR_CLASS_NAME),
+  (OBJECT_CLASS_NAME, "copy", &[], KEYWORD_SELF_TYPE),
+  (IO_CLASS_NAME, "out_string", &[("x", STR_CLASS_NAME)], KEYWORD_SELF_TYPE),
+  (IO_CLASS_NAME, "out_int", &[("x", INT_CLASS_NAME)], KEYWORD_SELF_TYPE),
+  (IO_CLASS_NAME, "in_string", &[], STR_CLASS_NAME),
+  (IO_CLASS_NAME, "in_int", &[], INT_CLASS_NAME),
+  (STR_CLASS_NAME, "length", &[], INT_CLASS_NAME),
+  (STR_CLASS_NAME, "concat", &[("s", STR_CLASS_NAME)], STR_CLASS_NAME),
+  (STR_CLASS_NAME, "substr", &[("i", INT_CLASS_NAME), ("l", INT_CLASS_NAME)], STR_CLASS_NAME),
+];
+
+/// Signature of a method, as declared in `class_name`
+#[derive(PartialEq, Debug, Clone)]
+pub struct MethodSignature {
+  pub name: String,
+  pub class_name: String,
+  pub formals: Vec<(String, SymbolType)>,
+  pub ret_type: SymbolType,
+  pub line_num: u32,
+  pub line_pos: u32,
+}
+
+impl MethodSignature {
+  /// Returns `true` if both methods take the same formal types and return the same type
+  pub fn same_signature(&self, other: &MethodSignature) -> bool {
+    self.ret_type == other.ret_type
+        && self.formals.len() == other.formals.len()
+        && self.formals.iter().zip(&other.formals).all(|((_, a), (_, b))| a == b)
+  }
+}
+
+impl Display for MethodSignature {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let formals: Vec<String> = self.formals.iter().map(|(name, f_type)| format!("{name} : {f_type}")).collect();
+    write!(f, "{}.{}({}) : {}", self.class_name, self.name, formals.join(", "), self.ret_type)
+  }
+}
+
+/// Method environment: the methods declared directly in each class, in declaration order
+#[derive(PartialEq, Debug, Clone)]
+pub struct MethodTable {
+  methods: HashMap<String, Vec<MethodSignature>>,
+}
+
+impl Default for MethodTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl MethodTable {
+  /// Creates the method table with the methods of the basic classes
+  pub fn new() -> Self {
+    let mut table = MethodTable { methods: HashMap::new() };
+
+    for (class_name, name, formals, ret_type) in BUILT_IN_METHODS {
+      table.put(MethodSignature {
+        name: name.to_string(),
+        class_name: class_name.to_string(),
+        formals: formals.iter().map(|(f_name, f_type)| (f_name.to_string(), f_type.to_string())).collect(),
+        ret_type: ret_type.to_string(),
+        line_num: 0,
+        line_pos: 0,
+      });
+    }
+
+    table
+  }
+
+  /// Adds the method to its class, unless the class already declares a method with the same name.
+  /// Returns the previously declared method in that case.
+  pub fn put(&mut self, signature: MethodSignature) -> Option<&MethodSignature> {
+    let class_methods = self.methods.entry(signature.class_name.clone()).or_default();
+    match class_methods.iter().position(|m| m.name == signature.name) {
+      Some(index) => Some(&class_methods[index]),
+      None => {
+        class_methods.push(signature);
+        None
+      }
+    }
+  }
+
+  /// Returns the method declared directly in the class
+  pub fn get(&self, class_name: &str, method_name: &str) -> Option<&MethodSignature> {
+    self.methods.get(class_name)?.iter().find(|m| m.name == method_name)
+  }
+
+  /// Returns the methods declared directly in the class, in declaration order
+  pub fn methods_of(&self, class_name: &str) -> &[MethodSignature] {
+    self.methods.get(class_name).map_or(&[], |methods| methods.as_slice())
+  }
+}
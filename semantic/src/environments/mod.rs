@@ -0,0 +1,75 @@
+pub mod method;
+
+use crate::environments::method::{MethodSignature, MethodTable};
+use crate::models::class::ClassNode;
+use crate::models::hierarchy::ClassHierarchy;
+use crate::models::symbols::{EnvType, Symbol, SymbolTable};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use std::collections::HashMap;
+
+pub const SELF_OBJECT_NAME: &str = "self";
+
+/// The environments of a program:
+/// - type names, i.e. the class map with the inheritance graph, indexed by the class hierarchy
+/// - object identifiers declared as attributes of each class
+/// - method signatures declared in each class
+#[derive(Debug, Clone)]
+pub struct Environment {
+  pub class_map: HashMap<String, ClassNode>,
+  pub hierarchy: ClassHierarchy,
+  pub attributes: HashMap<String, Vec<Symbol>>, // attributes declared by each class, in declaration order
+  pub methods: MethodTable,
+}
+
+impl Environment {
+  pub fn new(class_map: HashMap<String, ClassNode>) -> Self {
+    let hierarchy = ClassHierarchy::new(&class_map);
+    Environment { class_map, hierarchy, attributes: HashMap::new(), methods: MethodTable::new() }
+  }
+
+  /// Returns `true` if `type_name` is a declared class, or `SELF_TYPE` when allowed
+  pub fn is_type_defined(&self, type_name: &str, allow_self_type: bool) -> bool {
+    (allow_self_type && type_name == KEYWORD_SELF_TYPE) || self.class_map.contains_key(type_name)
+  }
+
+  /// Returns the class and all its ancestors, starting with the class itself and ending with `Object`
+  pub fn ancestors(&self, class_name: &str) -> Vec<String> {
+    self.hierarchy.ancestors(class_name)
+  }
+
+  /// Looks up an attribute declared in the class or inherited from one of its ancestors
+  pub fn lookup_attribute(&self, class_name: &str, attribute_name: &str) -> Option<&Symbol> {
+    self.ancestors(class_name).iter()
+        .filter_map(|class| self.attributes.get(class))
+        .find_map(|attributes| attributes.iter().find(|attribute| attribute.name == attribute_name))
+  }
+
+  /// Looks up a method declared in the class or inherited from one of its ancestors
+  pub fn lookup_method(&self, class_name: &str, method_name: &str) -> Option<&MethodSignature> {
+    self.ancestors(class_name).iter().find_map(|class| self.methods.get(class, method_name))
+  }
+
+  /// Generates the object environment visible inside the class: one scope per ancestor, starting with `Object`,
+  /// holding its attributes. `self` is bound in the innermost scope.
+  pub fn object_env(&self, class_name: &str) -> SymbolTable {
+    let mut table = SymbolTable::new();
+
+    for class in self.ancestors(class_name).iter().rev() {
+      table.enter_scope();
+      for attribute in self.attributes.get(class).into_iter().flatten() {
+        table.put(attribute.clone());
+      }
+    }
+
+    let (line_num, line_pos) = self.class_map.get(class_name).map_or((0, 0), |node| (node.line_num, node.line_pos));
+    table.put(Symbol {
+      name: SELF_OBJECT_NAME.to_string(),
+      env_type: EnvType::Class,
+      sym_type: KEYWORD_SELF_TYPE.to_string(),
+      line_num,
+      line_pos,
+    });
+
+    table
+  }
+}
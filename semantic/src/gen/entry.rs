@@ -0,0 +1,107 @@
+use crate::environments::Environment;
+use crate::models::class::PRIMITIVE_TYPES;
+use crate::models::diagnostic::Diagnostic;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+pub const MAIN_CLASS_NAME: &str = "Main";
+pub const MAIN_METHOD_NAME: &str = "main";
+
+/// The method that starts the program: `Main.main` unless another `Class.method` is chosen, e.g. to run a test class
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntryPoint {
+  pub class_name: String,
+  pub method_name: String,
+}
+
+impl Default for EntryPoint {
+  fn default() -> Self {
+    EntryPoint { class_name: MAIN_CLASS_NAME.to_string(), method_name: MAIN_METHOD_NAME.to_string() }
+  }
+}
+
+impl FromStr for EntryPoint {
+  type Err = String;
+
+  /// Parses `Class.method`
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("Invalid entry point `{value}`, expected `Class.method`");
+    let (class_name, method_name) = value.split_once('.').ok_or_else(invalid)?;
+
+    let is_name = |name: &str, first_upper: bool| {
+      name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() && c.is_ascii_uppercase() == first_upper)
+          && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !is_name(class_name, true) || !is_name(method_name, false) {
+      return Err(invalid());
+    }
+
+    Ok(EntryPoint { class_name: class_name.to_string(), method_name: method_name.to_string() })
+  }
+}
+
+impl Display for EntryPoint {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}.{}", self.class_name, self.method_name)
+  }
+}
+
+/// Checks that the entry class is a user-defined class that declares or inherits the entry method,
+/// and that the method takes no arguments
+pub fn check_entry_point(env: &Environment, entry: &EntryPoint) -> Vec<Diagnostic> {
+  let EntryPoint { class_name, method_name } = entry;
+
+  let Some(class) = env.class_map.get(class_name) else {
+    return vec![Diagnostic::program_error(format!("Class {class_name} is not defined, the program has no entry point {entry}"))];
+  };
+  if PRIMITIVE_TYPES.contains(&class_name.as_str()) {
+    return vec![Diagnostic::program_error(format!("Basic class {class_name} cannot be the entry class"))];
+  }
+
+  let Some(method) = env.lookup_method(class_name, method_name) else {
+    return vec![Diagnostic::error(class.line_num, class.line_pos, format!("Class {class_name} has no method {method_name}, the program has no entry point {entry}"))];
+  };
+  if !method.formals.is_empty() {
+    let message = format!("Entry method {}.{method_name} must take no arguments, found {}", method.class_name, method.formals.len());
+    return vec![Diagnostic::error(method.line_num, method.line_pos, message)];
+  }
+
+  Vec::new()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gen::entry::{check_entry_point, EntryPoint};
+  use crate::gen::gen_class_map;
+  use crate::gen::symbol_table::gen_symbol_table;
+  use parser::get_ast_from_file_path;
+
+  #[test]
+  fn test_parse_entry_point() {
+    let entry: EntryPoint = "ListTest.run".parse().expect("Entry point must parse");
+    assert_eq!(entry, EntryPoint { class_name: "ListTest".to_string(), method_name: "run".to_string() });
+    assert_eq!(EntryPoint::default().to_string(), "Main.main");
+
+    for invalid in ["Main", "main.main", "Main.Main", "Main.", ".main", "Main.ma-in"] {
+      assert!(invalid.parse::<EntryPoint>().is_err(), "{invalid}");
+    }
+  }
+
+  #[test]
+  fn test_entry_point_errors() {
+    let program = get_ast_from_file_path("../test_resources/semantic/entry_points.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let env = gen_symbol_table(&program, &class_map).expect("Couldn't generate environment");
+
+    let check = |entry: &str| -> Vec<String> {
+      check_entry_point(&env, &entry.parse().unwrap()).iter().map(|d| d.to_string()).collect()
+    };
+
+    assert_eq!(check("Main.main"), vec!["Error: Class Main is not defined, the program has no entry point Main.main"]);
+    assert_eq!(check("Test.run"), Vec::<String>::new());
+    assert_eq!(check("SubTest.run"), Vec::<String>::new()); // inherited entry method
+    assert_eq!(check("Test.main"), vec!["Error: 1:7 Class Test has no method main, the program has no entry point Test.main"]);
+    assert_eq!(check("Test.check"), vec!["Error: 3:3 Entry method Test.check must take no arguments, found 1"]);
+    assert_eq!(check("Int.copy"), vec!["Error: Basic class Int cannot be the entry class"]);
+  }
+}
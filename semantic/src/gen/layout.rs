@@ -0,0 +1,101 @@
+use crate::environments::Environment;
+use crate::models::layout::{AttributeSlot, ClassLayout, MethodSlot};
+use parser::model::class::OBJECT_CLASS_NAME;
+use std::collections::HashMap;
+
+/// Computes the object layout and dispatch table of every class, walking the inheritance tree from `Object`
+pub fn gen_layouts(env: &Environment) -> HashMap<String, ClassLayout> {
+  let mut layouts: HashMap<String, ClassLayout> = HashMap::new();
+  let mut next_tag = 0;
+  gen_layout(env, OBJECT_CLASS_NAME, None, &mut next_tag, &mut layouts);
+
+  layouts
+}
+
+fn gen_layout(env: &Environment, class_name: &str, parent: Option<&ClassLayout>, next_tag: &mut usize, layouts: &mut HashMap<String, ClassLayout>) {
+  let Some(node) = env.class_map.get(class_name) else {
+    return;
+  };
+
+  let (attributes, dispatch_table) = match parent {
+    Some(parent) => (parent.attributes.clone(), parent.dispatch_table.clone()),
+    None => (Vec::new(), Vec::new()),
+  };
+  let mut layout = ClassLayout { class_name: class_name.to_string(), tag: *next_tag, max_descendant_tag: *next_tag, attributes, dispatch_table };
+  *next_tag += 1;
+
+  for attribute in env.attributes.get(class_name).into_iter().flatten() {
+    layout.attributes.push(AttributeSlot { name: attribute.name.clone(), attr_type: attribute.sym_type.clone(), class_name: class_name.to_string() });
+  }
+
+  // Overriding methods take the slot of the overridden method, new methods are appended
+  for method in env.methods.methods_of(class_name) {
+    match layout.method_index(&method.name) {
+      Some(index) => layout.dispatch_table[index].class_name = class_name.to_string(),
+      None => layout.dispatch_table.push(MethodSlot { name: method.name.clone(), class_name: class_name.to_string() }),
+    }
+  }
+
+  for child in &node.children {
+    gen_layout(env, child, Some(&layout), next_tag, layouts);
+  }
+
+  layout.max_descendant_tag = *next_tag - 1;
+  layouts.insert(class_name.to_string(), layout);
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gen::gen_class_map;
+  use crate::gen::layout::gen_layouts;
+  use crate::gen::symbol_table::gen_symbol_table;
+  use parser::get_ast_from_file_path;
+
+  #[test]
+  fn test_list_layout() {
+    let program = get_ast_from_file_path("../test_resources/programs/list.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let env = gen_symbol_table(&program, &class_map).expect("Couldn't generate environment");
+    let layouts = gen_layouts(&env);
+    assert_eq!(layouts.len(), env.class_map.len());
+
+    let list = &layouts["List"];
+    let cons = &layouts["Cons"];
+    println!("{list}{cons}");
+
+    let attributes: Vec<(&str, &str)> = cons.attributes.iter().map(|a| (a.name.as_str(), a.class_name.as_str())).collect();
+    assert_eq!(attributes, vec![("car", "Cons"), ("cdr", "Cons")]);
+
+    let methods: Vec<String> = cons.dispatch_table.iter().map(|m| format!("{}.{}", m.class_name, m.name)).collect();
+    assert_eq!(methods, vec![
+      "Object.abort", "Object.type_name", "Object.copy",
+      "Cons.isNil", "Cons.head", "Cons.tail", "List.cons",
+      "Cons.init",
+    ]);
+
+    // overridden methods keep the slot of the parent
+    for (index, slot) in list.dispatch_table.iter().enumerate() {
+      assert_eq!(cons.method_index(&slot.name), Some(index));
+    }
+
+    assert!(list.tag < cons.tag && cons.tag <= list.max_descendant_tag);
+    assert_eq!(layouts["Object"].tag, 0);
+    assert_eq!(layouts["Object"].max_descendant_tag, layouts.len() - 1);
+  }
+
+  #[test]
+  fn test_inherited_attribute_layout() {
+    let program = get_ast_from_file_path("../test_resources/programs/io.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let env = gen_symbol_table(&program, &class_map).expect("Couldn't generate environment");
+    let layouts = gen_layouts(&env);
+
+    for (parent, child) in [("A", "B"), ("IO", "C"), ("C", "D")] {
+      let (parent, child) = (&layouts[parent], &layouts[child]);
+      assert_eq!(child.attributes[..parent.attributes.len()], parent.attributes[..]);
+      assert_eq!(child.dispatch_table[..parent.dispatch_table.len()], parent.dispatch_table[..]);
+    }
+    assert_eq!(layouts["B"].attribute_index("io"), Some(0));
+    assert!(layouts["D"].method_index("out_c") < layouts["D"].method_index("out_d"));
+  }
+}
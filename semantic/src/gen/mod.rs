@@ -1,211 +1,260 @@
-﻿mod symbol_table;
-mod class;
-
-use crate::models::class::{ClassNode, BASE_NODE_IO};
-use crate::models::program::ProgramNode;
-use parser::get_ast;
-use parser::model::class::OBJECT_CLASS_NAME;
+pub mod entry;
+pub mod layout;
+pub mod symbol_table;
+pub mod type_check;
+
+use crate::gen::entry::{check_entry_point, EntryPoint};
+use crate::gen::layout::gen_layouts;
+use crate::gen::symbol_table::gen_symbol_table;
+use crate::gen::type_check::infer_types;
+use crate::lint::config::LintConfig;
+use crate::lint::run_lints;
+use crate::lint::suppression::Suppressions;
+use crate::models::class::{ClassNode, BASE_NODE_BOOL, BASE_NODE_INT, BASE_NODE_IO, BASE_NODE_STR, NO_INHERIT, PRIMITIVE_TYPES};
+use crate::models::diagnostic::Diagnostic;
+use crate::models::program::{CheckedProgram, ProgramNode};
 use parser::model::program::ParseProgram;
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-
-pub fn check_program(program: ParseProgram) -> Result<ProgramNode, String> {
-  let mut class_map = gen_class_map(program);
-
-  let classes: Vec<ClassNode> = class_map.values().cloned().collect();
-  let program = ProgramNode { classes };
-  
-  let mut seen_nodes: Vec<String> = Vec::new();
-  match check_if_dag(&mut class_map, &mut seen_nodes, OBJECT_CLASS_NAME) {
-    Ok(None) => Ok(program),
-    Ok(Some(err)) => Err(err),
-    Err(e) => panic!("Cannot proceed. Fatal error: {}", e),
-  }
+use std::collections::HashMap;
+
+/// Options of the semantic checks that are not part of the program itself
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+  pub lint_config: LintConfig,
+  pub suppressions: Suppressions,
+  pub entry: EntryPoint,
 }
 
+/// Runs all semantic checks on the program. Warnings are returned with the checked program, or along with the errors.
+pub fn check_program(program: &ParseProgram) -> Result<ProgramNode, Vec<Diagnostic>> {
+  check_program_with(program, &CheckOptions::default())
+}
+
+/// Same as `check_program`, validating the chosen entry point and running the lints at the configured levels
+/// on a well-typed program
+pub fn check_program_with(program: &ParseProgram, options: &CheckOptions) -> Result<ProgramNode, Vec<Diagnostic>> {
+  let checked = analyze_program(program, options)?;
+  let classes: Vec<ClassNode> = checked.env.class_map.values().cloned().collect();
+  Ok(ProgramNode { classes, warnings: checked.warnings })
+}
 
-fn check_if_dag(node_map: &mut HashMap<String, ClassNode>, seen_nodes: &mut Vec<String>, start_class_name: &str) -> Result<Option<String>, String> {
-  let gen_cycle_err = |nodes: Vec<String>| -> String {
-    let chain = nodes.clone().join(" -> ");
-    return format!("There is a cycle in the inheritance graph via {chain}");
-  };
+/// Runs all semantic checks like `check_program_with`, keeping what code generation needs:
+/// the environments, the static type of every expression and the class layouts
+pub fn analyze_program<'a>(program: &'a ParseProgram, options: &CheckOptions) -> Result<CheckedProgram<'a>, Vec<Diagnostic>> {
+  let class_map = gen_class_map(program)?;
+  let env = gen_symbol_table(program, &class_map)?;
 
-  if node_map.is_empty() {
-    return Ok(None); // empty node_map => all nodes are seen
+  let (types, mut diagnostics) = infer_types(program, &env);
+  diagnostics.extend(check_entry_point(&env, &options.entry));
+  if !diagnostics.iter().any(Diagnostic::is_error) {
+    diagnostics.extend(run_lints(program, &env, &types, &options.lint_config, &options.suppressions, &options.entry));
   }
 
-  if seen_nodes.contains(&start_class_name.to_string()) {
-    return Ok(Some(gen_cycle_err(seen_nodes.clone()))); // node is seen before; cycle in the graph
+  diagnostics.sort_by_key(|d| d.position);
+  if diagnostics.iter().any(Diagnostic::is_error) {
+    return Err(diagnostics);
   }
 
-  let node = match node_map.remove(start_class_name) {
-    Some(n) => n,
-    None if !node_map.contains_key(start_class_name) => {
-      let chain = seen_nodes.clone().join(" -> ");
-      return Ok(Some(format!("Class {start_class_name} is not declared in the chain {chain}")));
-    }
-    None => return Err(format!("Could not remove {start_class_name} from the node_map")),
-  };
+  let layouts = gen_layouts(&env);
+  Ok(CheckedProgram { program, env, types, layouts, entry: options.entry.clone(), warnings: diagnostics })
+}
 
-  let node_name = node.name;
-  seen_nodes.push(node_name.into());
+/// Returns every cycle in the inheritance graph, following the `parent` link of each class.
+/// Each cycle is reported once, starting from the class in the cycle that is declared first.
+pub fn check_if_dag(node_map: &HashMap<String, ClassNode>) -> Vec<Diagnostic> {
+  let mut errors: Vec<Diagnostic> = Vec::new();
+  let mut visited: HashMap<&str, bool> = HashMap::new(); // `false` while the node is on the current path
+
+  let mut nodes: Vec<&ClassNode> = node_map.values().collect();
+  nodes.sort_by(|a, b| (a.line_num, a.line_pos, &a.name).cmp(&(b.line_num, b.line_pos, &b.name)));
+
+  for node in nodes {
+    let mut path: Vec<&ClassNode> = Vec::new();
+    let mut current = Some(node);
+
+    while let Some(class) = current {
+      match visited.get(class.name.as_ref()) {
+        Some(true) => break, // reached a node whose ancestry is already checked
+        Some(false) => {
+          let start = path.iter().position(|n| n.name == class.name).unwrap_or(0);
+          errors.push(gen_cycle_err(&path[start..]));
+          break;
+        }
+        None => {
+          visited.insert(class.name.as_ref(), false);
+          path.push(class);
+          current = node_map.get(class.parent.as_ref());
+        }
+      }
+    }
 
-  let children = node.children;
-  for child in children {
-    let child_result = check_if_dag(node_map, seen_nodes, &*child)?;
-    if child_result.is_some() { // seen a cycle, stop the loop, and return the result
-      return Ok(child_result);
+    for class in path {
+      visited.insert(class.name.as_ref(), true);
     }
   }
 
-  seen_nodes.pop();
-  Ok(None) // no cycle seen
+  errors
+}
+
+fn gen_cycle_err(cycle: &[&ClassNode]) -> Diagnostic {
+  let first = (0..cycle.len()).min_by_key(|&i| (cycle[i].line_num, cycle[i].line_pos)).unwrap_or(0);
+  let ClassNode { name, line_num, line_pos, .. } = cycle[first];
+
+  if cycle.len() == 1 {
+    return Diagnostic::error(*line_num, *line_pos, format!("Class {name} cannot inherit from itself"));
+  }
+
+  let mut chain: Vec<&str> = cycle[first..].iter().chain(cycle[..first].iter()).map(|n| n.name.as_ref()).collect();
+  chain.push(name);
+  let chain = chain.join(" -> ");
+  Diagnostic::error(*line_num, *line_pos, format!("There is a cycle in the inheritance graph via {chain}"))
 }
 
-fn gen_class_map<'a>(program: ParseProgram) -> HashMap<String, ClassNode> {
+/// Builds the inheritance graph of the program, with each class linked to its parent.
+/// All errors in class declarations and in the inheritance graph are reported together, ordered by position.
+pub fn gen_class_map(program: &ParseProgram) -> Result<HashMap<String, ClassNode>, Vec<Diagnostic>> {
   let mut class_map: HashMap<String, ClassNode> = HashMap::new();
-  let mut parent_set: HashSet<Cow<'a, str>> = HashSet::new();
+  let mut errors: Vec<Diagnostic> = Vec::new();
+
   let base_object = ClassNode::get_base_object();
   class_map.insert(base_object.name.to_string(), base_object);
-  class_map.insert(BASE_NODE_IO.name.to_string(), BASE_NODE_IO.clone());
+  for base_node in [BASE_NODE_IO, BASE_NODE_INT, BASE_NODE_STR, BASE_NODE_BOOL] {
+    class_map.insert(base_node.name.to_string(), base_node);
+  }
 
   // First pass extracts all class names and parents and put it in the HashMap
-  let classes = program.classes;
-  for class in &classes {
+  let mut declared: Vec<String> = Vec::new();
+  for class in program.classes() {
     let node = ClassNode::from(class);
+    let class_name = node.name.to_string();
+    let (line_num, line_pos) = (class.line_num, class.line_pos);
 
-    let class_name = class.name.get_name();
-    let line_num = class.line_num;
-    let line_pos = class.line_pos;
-    if crate::models::class::PRIMITIVE_TYPES.contains(&class_name.as_ref()) {
-      panic!("Error: {line_num}:{line_pos} attempt to inherit from sealed class via {class_name}")
+    if PRIMITIVE_TYPES.contains(&class_name.as_str()) {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Redefinition of basic class {class_name}")));
+      continue;
     }
 
-    let parent_name = class.parent_type.get_name();
-    if crate::models::class::NO_INHERIT.contains(&parent_name.as_ref()) {
-      panic!("Error: {line_num}:{line_pos} attempt to inherit from sealed class via {parent_name}");
+    if let Some(existing) = class_map.get(&class_name) {
+      let message = format!("Class {class_name} was previously defined at {}:{}", existing.line_num, existing.line_pos);
+      errors.push(Diagnostic::error(line_num, line_pos, message));
+      continue;
     }
 
-    if class_name == parent_name {
-      panic!("Error: {line_num}: {line_pos} class {class_name} attempted to inherit from itself");
-    }
-
-    parent_set.insert(node.parent.clone());
-    class_map.insert(node.name.to_string(), node);
+    class_map.insert(class_name.clone(), node);
+    declared.push(class_name);
   }
 
   // In the second pass, link all children to parents
-  for class in classes {
-    let Some(class_node) = class_map.remove(&class.name.get_name()) else {
-      unreachable!("Missing entry in class_map for {}", class.name.get_name())
-    };
-    let parent_name = &class_node.parent.to_string();
-    let Some(mut parent) = class_map.remove(parent_name) else {
-      unreachable!("Missing entry in class_map for parent {}", parent_name);
-    };
-    parent.add_child(Cow::from(class_node.name.clone()));
-    class_map.insert(parent.name.to_string(), parent);
-    class_map.insert(class.name.get_name(), class_node);
-  }
+  for class_name in declared {
+    let ClassNode { parent, line_num, line_pos, .. } = &class_map[&class_name];
+    let (parent_name, line_num, line_pos) = (parent.to_string(), *line_num, *line_pos);
 
-  class_map
-}
+    if NO_INHERIT.contains(&parent_name.as_str()) {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Class {class_name} cannot inherit from sealed class {parent_name}")));
+      continue;
+    }
 
-fn gen_graph<'a>(file: File) -> Result<HashMap<String, ClassNode>, String> {
-  let program = match get_ast(file) {
-    Ok(pgm) => pgm,
-    Err(e) => panic!("{e}"),
-  };
+    if parent_name == class_name {
+      continue; // reported by the cycle check
+    }
+
+    let Some(parent) = class_map.get_mut(&parent_name) else {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Class {class_name} inherits from undefined class {parent_name}")));
+      continue;
+    };
+    parent.add_child(Cow::from(class_name));
+  }
 
-  let map = gen_class_map(program);
+  errors.extend(check_if_dag(&class_map));
 
-  match check_if_dag(&mut map.clone(), &mut Vec::new(), OBJECT_CLASS_NAME) {
-    Ok(None) => Ok(map),
-    Ok(Some(semantic_error)) => Err(semantic_error),
-    Err(fatal_error) => panic!("{fatal_error}"),
+  if errors.is_empty() {
+    Ok(class_map)
+  } else {
+    errors.sort_by_key(|e| e.position);
+    Err(errors)
   }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::gen::{check_if_dag, gen_graph};
-    use crate::models::class::ClassNode;
-    use parser::model::class::OBJECT_CLASS_NAME;
-    use std::borrow::Cow;
-    use std::collections::HashMap;
-    use std::fs::File;
-
-    #[test]
+  use crate::gen::{check_if_dag, gen_class_map};
+  use crate::models::class::ClassNode;
+  use parser::get_ast_from_file_path;
+  use parser::model::class::OBJECT_CLASS_NAME;
+  use std::borrow::Cow;
+  use std::collections::HashMap;
+
+  fn gen_node(name: &'static str, parent: &'static str, line_num: u32) -> ClassNode {
+    ClassNode { name: Cow::Borrowed(name), parent: Cow::Borrowed(parent), children: Vec::new(), features: Vec::new(), line_num, line_pos: 1 }
+  }
+
+  #[test]
   fn test_gen_inheritance_graph() {
-    let file = File::open("../test_resources/programs/arith.cl").expect("Couldn't open file");
-    let graph_result = gen_graph(file);
+    let program = get_ast_from_file_path("../test_resources/programs/arith.cl").expect("Couldn't parse file");
+    let graph_result = gen_class_map(&program);
     if graph_result.is_err() {
       panic!("Test failed: {:#?}", graph_result.err().unwrap());
     }
-    assert!(graph_result.is_ok());
     let graph = graph_result.unwrap();
 
-    for (class, _) in &graph {
-      print!("{class} ");
-    }
-
     for (class, node) in &graph {
       println!("{class} : {node}");
     }
+
+    assert!(graph[OBJECT_CLASS_NAME].children.contains(&Cow::Borrowed("A")));
+    assert!(graph["A"].children.contains(&Cow::Borrowed("B")));
   }
 
   #[test]
   fn test_cycle_positive() {
-    let file = File::open("../test_resources/programs/arith.cl").expect("Couldn't open file");
-    let graph_result = gen_graph(file);
-    if graph_result.is_err() {
-      panic!("Test failed: {:#?}", graph_result.err().unwrap());
-    }
-    assert!(graph_result.is_ok());
-    let mut graph = graph_result.unwrap();
-
-    let mut seen_nodes: Vec<String> = Vec::new();
-    let cycle = check_if_dag(&mut graph, &mut seen_nodes, OBJECT_CLASS_NAME);
-    if cycle.is_err() {
-      println!("{:#?}", cycle.unwrap_err());
-      assert!(false);
-      return;
-    }
+    let program = get_ast_from_file_path("../test_resources/programs/arith.cl").expect("Couldn't parse file");
+    let graph = gen_class_map(&program).expect("Couldn't generate graph");
 
-    assert!(cycle.is_ok());
-    assert!(cycle.unwrap().is_none());
+    let cycles = check_if_dag(&graph);
+    assert!(cycles.is_empty(), "{cycles:#?}");
   }
 
   #[test]
   fn test_cycle_negative() {
     // Hierarchy:
-    // `Object` -> `A`
-    // `A` -> `B`, `C`
-    // `B` -> `C`
-    // `C` -> `A`
-    // `C` -> `D`
+    // `A` -> `C` -> `B` -> `A`
+    // `D` -> `C`
+    // `E` -> `F` -> `E`
+    // `G` -> `G`
 
     let mut graph = HashMap::new();
-    let base_object = ClassNode::get_base_object();
-    graph.insert(OBJECT_CLASS_NAME.to_string(), base_object);
-    graph.insert(String::from("A"), ClassNode { name: Cow::Borrowed("A"), parent: Cow::Borrowed(OBJECT_CLASS_NAME), children: vec![Cow::Borrowed("B"), Cow::Borrowed("C")], features: Vec::new() });
-    graph.insert(String::from("B"), ClassNode { name: Cow::Borrowed("B"), parent: Cow::Borrowed("A"), children: vec![Cow::Borrowed("C")], features: Vec::new() });
-    graph.insert(String::from("C"), ClassNode { name: Cow::Borrowed("C"), parent: Cow::Borrowed("B"), children: vec![Cow::Borrowed("A")], features: Vec::new() });
-    graph.insert(String::from("D"), ClassNode { name: Cow::Borrowed("D"), parent: Cow::Borrowed("C"), children: Vec::new(), features: Vec::new() });
-    let mut seen_nodes: Vec<String> = Vec::new();
-    let cycle = check_if_dag(&mut graph, &mut seen_nodes, OBJECT_CLASS_NAME);
-    if cycle.is_err() {
-      println!("{:#?}", cycle.unwrap_err());
-      assert!(false);
-      return;
-    }
+    graph.insert(OBJECT_CLASS_NAME.to_string(), ClassNode::get_base_object());
+    graph.insert(String::from("A"), gen_node("A", "C", 1));
+    graph.insert(String::from("B"), gen_node("B", "A", 2));
+    graph.insert(String::from("C"), gen_node("C", "B", 3));
+    graph.insert(String::from("D"), gen_node("D", "C", 4));
+    graph.insert(String::from("E"), gen_node("E", "F", 5));
+    graph.insert(String::from("F"), gen_node("F", "E", 6));
+    graph.insert(String::from("G"), gen_node("G", "G", 7));
+
+    let mut cycles = check_if_dag(&graph);
+    cycles.sort_by_key(|e| e.position);
+    let messages: Vec<String> = cycles.into_iter().map(|e| e.message).collect();
+    assert_eq!(messages, vec![
+      "There is a cycle in the inheritance graph via A -> C -> B -> A",
+      "There is a cycle in the inheritance graph via E -> F -> E",
+      "Class G cannot inherit from itself",
+    ]);
+  }
 
-    assert!(cycle.is_ok());
-    let result = cycle.unwrap();
-    assert!(result.is_some());
-    let err = result.unwrap();
-    println!("{err:?}");
-    assert_eq!(err, "There is a cycle in the inheritance graph via Object -> A -> B -> C");
+  #[test]
+  fn test_inheritance_errors() {
+    let program = get_ast_from_file_path("../test_resources/semantic/inheritance_errors.cl").expect("Couldn't parse file");
+    let errors = gen_class_map(&program).expect_err("Program must have inheritance errors");
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(errors, vec![
+      "Error: 1:7 Class Main inherits from undefined class Mian",
+      "Error: 3:7 Class Number cannot inherit from sealed class Int",
+      "Error: 5:7 Redefinition of basic class String",
+      "Error: 7:7 There is a cycle in the inheritance graph via A -> B -> A",
+      "Error: 11:7 Class Self cannot inherit from itself",
+      "Error: 13:7 Class A was previously defined at 7:7",
+    ]);
   }
 }
@@ -1,167 +1,221 @@
-﻿use crate::models::symbols::{EnvType, Symbol, SymbolTable, SymbolType};
+use crate::environments::method::MethodSignature;
+use crate::environments::{Environment, SELF_OBJECT_NAME};
+use crate::models::class::ClassNode;
+use crate::models::diagnostic::Diagnostic;
+use crate::models::symbols::{EnvType, Symbol, SymbolTable};
 use lexer::model::constants::KEYWORD_SELF_TYPE;
 use parser::model::class::ParseClass;
-use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, IO_CLASS_NAME, OBJECT_CLASS_NAME, STR_CLASS_NAME};
 use parser::model::expressions::Expression;
 use parser::model::feature::{Attribute, Method, ParseFeature};
 use parser::model::program::ParseProgram;
+use std::collections::{HashMap, HashSet};
 
-fn gen_symbol_table(program: ParseProgram) -> Result<SymbolTable, String> {
-  let mut symbol_table = SymbolTable::new();
-  symbol_table.enter_scope();
+/// Generates the attribute and method environments of the program and checks the scoping rules of all
+/// object identifiers: attributes, formals, `let` and `case` bindings, and `self`.
+pub fn gen_symbol_table(program: &ParseProgram, class_map: &HashMap<String, ClassNode>) -> Result<Environment, Vec<Diagnostic>> {
+  let mut env = Environment::new(class_map.clone());
+  let mut errors: Vec<Diagnostic> = Vec::new();
 
-  fill_built_in_classes(&mut symbol_table);
-
-  // fill the symbol table with all the class names
+  // First, populate all class-level features
   for class in program.classes() {
-    let class_symbol: Symbol = Symbol { name: class.name.get_name(), env_type: EnvType::Program, sym_type: "Class" };
-    symbol_table.put(class_symbol)
+    fill_class_features(class, &mut env, &mut errors);
   }
 
-  for class in program.classes {
-    fill_symbol_table_class(&class, &mut symbol_table)?;
+  // Features redefined in subclasses can only be checked once all classes are seen
+  for class in program.classes() {
+    check_inherited_features(class, &env, &mut errors);
   }
 
-  Ok(symbol_table)
-}
-
-fn fill_built_in_classes(symbol_table: &mut SymbolTable) {
-  symbol_table.put(Symbol { name: OBJECT_CLASS_NAME.to_string(), env_type: EnvType::Program, sym_type: String:: from("Class"), ret_type: String::from("Void") });
-  symbol_table.put(Symbol { name: IO_CLASS_NAME.to_string(), env_type: EnvType::Program, sym_type: String:: from("Class"), ret_type: String::from("Void") });
-  symbol_table.put(Symbol { name: INT_CLASS_NAME.to_string(), env_type: EnvType::Program, sym_type: String:: from("Class"), ret_type: String::from("Void") });
-  symbol_table.put(Symbol { name: STR_CLASS_NAME.to_string(), env_type: EnvType::Program, sym_type: String:: from("Class"), ret_type: String::from("Void") });
-  symbol_table.put(Symbol { name: BOOL_CLASS_NAME.to_string(), env_type: EnvType::Program, sym_type: String:: from("Class"), ret_type: String::from("Void") });
-}
-
-fn fill_symbol_table_class(class: &ParseClass, symbol_table: &mut SymbolTable) -> Result<(), String> {
-  symbol_table.enter_scope();
-  let mut error_message = String::new();
-
-  let parent_name_binding = class.parent_type.get_name();
-  let parent_name = parent_name_binding.as_str();
-  if !symbol_table.lookup_symbol_by_env_type(parent_name, EnvType::Class) {
-    error_message.push_str(format!("Class {parent_name} does not exist").as_str());
+  // Continue with the scope checking each of the attribute initialisers and method bodies
+  for class in program.classes() {
+    let class_name = class.get_name();
+    let mut symbol_table = env.object_env(&class_name);
+
+    for feature in class.features.iter().flatten() {
+      match feature {
+        ParseFeature::Attribute { attribute } => {
+          if let Some(expr) = &attribute.expr {
+            check_expr(expr, &mut symbol_table, &env, &mut errors);
+          }
+        }
+        ParseFeature::Method { method } => {
+          symbol_table.enter_scope();
+          for formal in method.formals.iter().flatten() {
+            let (line_num, line_pos) = formal.formal_name.get_pos();
+            symbol_table.put(Symbol {
+              name: formal.formal_name.get_name(),
+              env_type: EnvType::Method,
+              sym_type: formal.formal_type.get_name(),
+              line_num,
+              line_pos,
+            });
+          }
+          check_expr(&method.expr, &mut symbol_table, &env, &mut errors);
+          symbol_table.exit_scope();
+        }
+      }
+    }
   }
 
-  if class.features.is_none() {
-    return if error_message.is_empty() {
-      Ok(())
-    } else {
-      Err(error_message)
-    };
+  if errors.is_empty() {
+    Ok(env)
+  } else {
+    errors.sort_by_key(|e| e.position);
+    Err(errors)
   }
+}
 
-  let self_type = Symbol { name: class.get_name(), env_type: EnvType::Class, sym_type: String::from("Self"), ret_type: String::from("Void") };
-  let features = class.features.as_ref().unwrap();
+fn fill_class_features(class: &ParseClass, env: &mut Environment, errors: &mut Vec<Diagnostic>) {
+  let class_name = class.get_name();
+  let mut attributes: Vec<Symbol> = Vec::new();
 
-  // First, populate all class-level features
-  for feature in features {
+  for feature in class.features.iter().flatten() {
     match feature {
       ParseFeature::Attribute { attribute } => {
-        let attribute_name = attribute.get_name();
-        let ret_type = attribute.return_type.get_name();
-
-        if !symbol_table.lookup_symbol_by_env_type(&ret_type, EnvType::Class) {
-          error_message.push_str(format!("Class {}: attribute {attribute_name} with return type {ret_type} does not exist", class.get_name()).as_str());
+        if let Some(symbol) = gen_attribute_symbol(&class_name, attribute, &attributes, env, errors) {
+          attributes.push(symbol);
         }
-
-        let attribute_symbol = Symbol { name: attribute_name, env_type: EnvType::Class, sym_type: SymbolType::Ident { ident_type: ret_type } };
-        symbol_table.put(attribute_symbol);
       }
-
       ParseFeature::Method { method } => {
-        let ret_type = if method.return_type.get_name() != KEYWORD_SELF_TYPE {
-          method.return_type.get_name()
-        } else {
-          class.get_name()
-        };
-
-        // Check if the return type is in the symbol table
-        if !symbol_table.lookup_symbol_by_env_type(&ret_type, EnvType::Class) {
-          error_message.push_str(format!("Class {}: method {} with return type {ret_type} does not exist", class.get_name(), method.get_name()).as_str());
+        let signature = gen_method_signature(&class_name, method, env, errors);
+        if let Some(existing) = env.methods.put(signature) {
+          let (line_num, line_pos) = method.name.get_pos();
+          let message = format!("Method {} is multiply defined in class {class_name}, previously at {}:{}", method.get_name(), existing.line_num, existing.line_pos);
+          errors.push(Diagnostic::error(line_num, line_pos, message));
         }
-
-        let method_symbol = Symbol { name: method.get_name(), env_type: EnvType::Class, sym_type: SymbolType::Ident { ident_type: ret_type } };
-        symbol_table.put(method_symbol);
       }
     }
   }
 
-  // Continue with the semantic checking each of the methods
-  for feature in features {
-    match feature {
-      ParseFeature::Attribute { attribute } => {
-        match fill_symbol_table_attribute(attribute, symbol_table) {
-          Ok(_) => {}
-          Err(err) => { error_message.push_str(err.as_str()) }
-        }
-      }
-      ParseFeature::Method { method } => {
-        match fill_symbol_table_method(method, symbol_table) {
-          Ok(_) => {}
-          Err(err) => error_message.push_str(err.as_str()),
-        }
-      }
-    }
+  env.attributes.insert(class_name, attributes);
+}
+
+fn gen_attribute_symbol(class_name: &str, attribute: &Attribute, attributes: &[Symbol], env: &Environment, errors: &mut Vec<Diagnostic>) -> Option<Symbol> {
+  let attr_name = attribute.get_name();
+  let attr_type = attribute.return_type.get_name();
+  let (line_num, line_pos) = attribute.name.get_pos();
+
+  if attr_name == SELF_OBJECT_NAME {
+    errors.push(Diagnostic::error(line_num, line_pos, format!("'self' cannot be the name of an attribute in class {class_name}")));
+    return None;
   }
 
-  symbol_table.exit_scope();
+  if !env.is_type_defined(&attr_type, true) {
+    let (line_num, line_pos) = attribute.return_type.get_pos();
+    errors.push(Diagnostic::error(line_num, line_pos, format!("Class {attr_type} of attribute {attr_name} is undefined")));
+  }
 
-  if error_message.is_empty() {
-    Ok(())
-  } else {
-    Err(error_message)
+  if let Some(existing) = attributes.iter().find(|a| a.name == attr_name) {
+    let message = format!("Attribute {attr_name} is multiply defined in class {class_name}, previously at {}:{}", existing.line_num, existing.line_pos);
+    errors.push(Diagnostic::error(line_num, line_pos, message));
+    return None;
   }
+
+  Some(Symbol { name: attr_name, env_type: EnvType::Class, sym_type: attr_type, line_num, line_pos })
 }
 
-fn fill_symbol_table_attribute(attribute: &Attribute, symbol_table: &mut SymbolTable) -> Result<(), String> {
-  let mut error_message = String::new();
+fn gen_method_signature(class_name: &str, method: &Method, env: &Environment, errors: &mut Vec<Diagnostic>) -> MethodSignature {
+  let method_name = method.get_name();
+  let mut formals: Vec<(String, String)> = Vec::new();
+  let mut seen_formals: HashSet<String> = HashSet::new();
 
-  let attr_name = attribute.get_name();
-  let attr_ret_type = attribute.return_type.get_name();
+  for formal in method.formals.iter().flatten() {
+    let formal_name = formal.formal_name.get_name();
+    let formal_type = formal.formal_type.get_name();
+    let (line_num, line_pos) = formal.formal_name.get_pos();
 
-  if !symbol_table.lookup_symbol_by_env_type(&attr_ret_type, EnvType::Class) {
-    error_message.push_str(format!("Return type {attr_ret_type} not defined for attribute {attr_name}").as_str());
+    if formal_name == SELF_OBJECT_NAME {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("'self' cannot be the name of a formal parameter in method {method_name}")));
+    } else if !seen_formals.insert(formal_name.clone()) {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Formal parameter {formal_name} is multiply defined in method {method_name}")));
+    }
+
+    if formal_type == KEYWORD_SELF_TYPE {
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Formal parameter {formal_name} cannot have type SELF_TYPE")));
+    } else if !env.is_type_defined(&formal_type, false) {
+      let (line_num, line_pos) = formal.formal_type.get_pos();
+      errors.push(Diagnostic::error(line_num, line_pos, format!("Class {formal_type} of formal parameter {formal_name} is undefined")));
+    }
+
+    formals.push((formal_name, formal_type));
   }
 
-  symbol_table.put(Symbol { name: attr_name, env_type: EnvType::Class, sym_type: SymbolType::Ident { ident_type: attr_ret_type } });
+  let ret_type = method.return_type.get_name();
+  if !env.is_type_defined(&ret_type, true) {
+    let (line_num, line_pos) = method.return_type.get_pos();
+    errors.push(Diagnostic::error(line_num, line_pos, format!("Undefined return type {ret_type} in method {method_name}")));
+  }
 
-  if attribute.expr.is_none() {
-    if error_message.is_empty() {
-      return Ok(());
-    } else {
-      return Err(error_message);
+  let (line_num, line_pos) = method.name.get_pos();
+  MethodSignature { name: method_name, class_name: class_name.to_string(), formals, ret_type, line_num, line_pos }
+}
+
+/// Attributes cannot be redefined in a subclass, and overriding methods must keep the signature of the parent
+fn check_inherited_features(class: &ParseClass, env: &Environment, errors: &mut Vec<Diagnostic>) {
+  let class_name = class.get_name();
+  let ancestors = env.ancestors(&class_name);
+  let Some((_, parents)) = ancestors.split_first() else {
+    return;
+  };
+
+  for attribute in env.attributes.get(&class_name).into_iter().flatten() {
+    let inherited = parents.iter().find(|parent| env.attributes.get(*parent).is_some_and(|attrs| attrs.iter().any(|a| a.name == attribute.name)));
+    if let Some(parent) = inherited {
+      let message = format!("Attribute {} of class {class_name} is already defined in ancestor class {parent}", attribute.name);
+      errors.push(Diagnostic::error(attribute.line_num, attribute.line_pos, message));
     }
   }
 
-  let Some(expr) = attribute.expr.as_ref() else { unreachable!(); };
-
-  Ok(())
+  for method in env.methods.methods_of(&class_name) {
+    let Some(overridden) = parents.iter().find_map(|parent| env.methods.get(parent, &method.name)) else {
+      continue;
+    };
+    if !method.same_signature(overridden) {
+      let message = format!("Method {} of class {class_name} overrides {overridden} with a different signature", method.name);
+      errors.push(Diagnostic::error(method.line_num, method.line_pos, message));
+    }
+  }
 }
 
-fn fill_symbol_table_method(method: &Method, symbol_table: &mut SymbolTable) -> Result<(), String> {
-  Ok(())
+fn bind(symbol_table: &mut SymbolTable, name: &str, sym_type: &str, env_type: EnvType, pos: (u32, u32), errors: &mut Vec<Diagnostic>) {
+  let (line_num, line_pos) = pos;
+  if name == SELF_OBJECT_NAME {
+    let binder = if env_type == EnvType::Case { "'case' branch" } else { "'let' expression" };
+    errors.push(Diagnostic::error(line_num, line_pos, format!("'self' cannot be bound in a {binder}")));
+  }
+  symbol_table.put(Symbol { name: name.to_string(), env_type, sym_type: sym_type.to_string(), line_num, line_pos });
 }
 
-fn fill_symbol_table_expr(expr: &Expression, symbol_table: &mut SymbolTable, env_type: EnvType) -> Result<SymbolType, String> {
-  symbol_table.enter_scope();
-  let mut error_message = String::new();
+fn check_expr(expr: &Expression, symbol_table: &mut SymbolTable, env: &Environment, errors: &mut Vec<Diagnostic>) {
   match expr {
     Expression::PartialAssign { .. } |
     Expression::PartialDispatch { .. } |
     Expression::PartialCastDispatch { .. } |
-    Expression::PartialBinary { .. } => panic!("Unexpected intermediate expression: {:?}", expr),
+    Expression::PartialBinary { .. } => errors.push(Diagnostic::program_error(format!("Unexpected intermediate expression: {}", expr.get_type()))),
 
-    // No type check needed
+    // No identifiers to check
     Expression::SelfTypeExpr { .. } |
     Expression::SelfExpr |
     Expression::StringExpr { .. } |
     Expression::IntExpr { .. } |
     Expression::BoolExpr { .. } => {}
 
+    Expression::IdentExpr { name } => {
+      if symbol_table.lookup_symbol(&name.get_name()).is_none() {
+        let (line_num, line_pos) = name.get_pos();
+        errors.push(Diagnostic::error(line_num, line_pos, format!("Undeclared identifier {}", name.get_name())));
+      }
+    }
+
     Expression::Assign { name, expr } => {
-      fill_symbol_table_expr(expr, symbol_table, env_type)?;
-      symbol_table.put(Symbol{name: name.get_name(), env_type, sym_type: SymbolType::Ident {}});
+      let (line_num, line_pos) = name.get_pos();
+      let var_name = name.get_name();
+      if var_name == SELF_OBJECT_NAME {
+        errors.push(Diagnostic::error(line_num, line_pos, "Cannot assign to 'self'".to_string()));
+      } else if symbol_table.lookup_symbol(&var_name).is_none() {
+        errors.push(Diagnostic::error(line_num, line_pos, format!("Assignment to undeclared variable {var_name}")));
+      }
+      check_expr(expr, symbol_table, env, errors);
     }
 
     Expression::Plus { left, right } |
@@ -170,32 +224,145 @@ fn fill_symbol_table_expr(expr: &Expression, symbol_table: &mut SymbolTable, env
     Expression::Divide { left, right } |
     Expression::LessThanOrEqual { left, right } |
     Expression::Equal { left, right } |
-    Expression::LessThan { left, right } => {}
-
-    Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } => {}
-    Expression::Conditional { .. } => {}
-    Expression::Loop { .. } => {}
-    Expression::Case { .. } => {}
-    Expression::Block { .. } => {}
-    Expression::Let { .. } => {}
+    Expression::LessThan { left, right } => {
+      check_expr(left, symbol_table, env, errors);
+      check_expr(right, symbol_table, env, errors);
+    }
 
     Expression::Negate { expr } |
     Expression::Not { expr } |
-    Expression::IsVoid { expr } => {}
+    Expression::IsVoid { expr } => check_expr(expr, symbol_table, env, errors),
+
+    Expression::Dispatch { calling_expr, cast_type, param_list, .. } => {
+      check_expr(calling_expr, symbol_table, env, errors);
+      if let Some(cast_type) = cast_type {
+        if !env.is_type_defined(&cast_type.get_name(), false) {
+          let (line_num, line_pos) = cast_type.get_pos();
+          errors.push(Diagnostic::error(line_num, line_pos, format!("Static dispatch to undefined class {}", cast_type.get_name())));
+        }
+      }
+      for param in param_list {
+        check_expr(param, symbol_table, env, errors);
+      }
+    }
+
+    Expression::Conditional { predicate, then_expr, else_expr } => {
+      check_expr(predicate, symbol_table, env, errors);
+      check_expr(then_expr, symbol_table, env, errors);
+      check_expr(else_expr, symbol_table, env, errors);
+    }
 
-    Expression::IdentExpr { name } => {}
+    Expression::Loop { predicate, body } => {
+      check_expr(predicate, symbol_table, env, errors);
+      check_expr(body, symbol_table, env, errors);
+    }
+
+    Expression::Block { expr_list } => {
+      for expr in expr_list {
+        check_expr(expr, symbol_table, env, errors);
+      }
+    }
+
+    Expression::Let { let_init, in_expr } => {
+      // Each binding is in scope for the initialisers that follow it and for the body
+      for init in let_init {
+        if let Some(init_expr) = &init.expr {
+          check_expr(init_expr, symbol_table, env, errors);
+        }
+
+        let id_type = init.id_type.get_name();
+        if !env.is_type_defined(&id_type, true) {
+          let (line_num, line_pos) = init.id_type.get_pos();
+          errors.push(Diagnostic::error(line_num, line_pos, format!("Class {id_type} of let-bound identifier {} is undefined", init.id.get_name())));
+        }
+
+        symbol_table.enter_scope();
+        bind(symbol_table, &init.id.get_name(), &id_type, EnvType::Let, init.id.get_pos(), errors);
+      }
+
+      check_expr(in_expr, symbol_table, env, errors);
+
+      for _ in let_init {
+        symbol_table.exit_scope();
+      }
+    }
+
+    Expression::Case { switch_expression, branches } => {
+      check_expr(switch_expression, symbol_table, env, errors);
+
+      for branch in branches {
+        let id_type = branch.id_type.get_name();
+        if !env.is_type_defined(&id_type, false) {
+          let (line_num, line_pos) = branch.id_type.get_pos();
+          errors.push(Diagnostic::error(line_num, line_pos, format!("Class {id_type} of case branch is undefined")));
+        }
+
+        symbol_table.enter_scope();
+        bind(symbol_table, &branch.id.get_name(), &id_type, EnvType::Case, branch.id.get_pos(), errors);
+        check_expr(&branch.expr, symbol_table, env, errors);
+        symbol_table.exit_scope();
+      }
+    }
 
     Expression::New { type_name } => {
-      if !symbol_table.lookup_symbol_by_env_type(type_name.get_name().as_str(), EnvType::Class) {
-        error_message.push_str(format!("Type {type_name} not defined").as_str());
+      if !env.is_type_defined(&type_name.get_name(), true) {
+        let (line_num, line_pos) = type_name.get_pos();
+        errors.push(Diagnostic::error(line_num, line_pos, format!("'new' used with undefined class {}", type_name.get_name())));
       }
     }
   }
-  symbol_table.exit_scope();
+}
 
-  if error_message.is_empty() {
-    Ok(())
-  } else {
-    Err(error_message)
+#[cfg(test)]
+mod test {
+  use crate::gen::gen_class_map;
+  use crate::gen::symbol_table::gen_symbol_table;
+  use parser::get_ast_from_file_path;
+  use parser::programs::find_programs;
+
+  #[test]
+  fn test_all_programs() {
+    for test_program in find_programs("../test_resources/programs") {
+      let program = test_program.parse().expect("Couldn't parse file");
+      let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+      let result = gen_symbol_table(&program, &class_map);
+      assert!(result.is_ok(), "{}: {:#?}", test_program.name(), result.err());
+    }
+  }
+
+  #[test]
+  fn test_inherited_lookup() {
+    let program = get_ast_from_file_path("../test_resources/programs/list.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let env = gen_symbol_table(&program, &class_map).expect("Couldn't generate environment");
+
+    assert_eq!(env.lookup_method("Cons", "isNil").unwrap().class_name, "Cons");
+    assert_eq!(env.lookup_method("Cons", "cons").unwrap().class_name, "List");
+    assert_eq!(env.lookup_method("Main", "out_string").unwrap().class_name, "IO");
+    assert_eq!(env.lookup_attribute("Cons", "car").unwrap().sym_type, "Int");
+
+    let object_env = env.object_env("Cons");
+    assert_eq!(object_env.lookup_symbol("cdr").unwrap().sym_type, "List");
+    assert_eq!(object_env.lookup_symbol("self").unwrap().sym_type, "SELF_TYPE");
+  }
+
+  #[test]
+  fn test_scope_errors() {
+    let program = get_ast_from_file_path("../test_resources/semantic/scope_errors.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let errors = gen_symbol_table(&program, &class_map).expect_err("Program must have scope errors");
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(errors, vec![
+      "Error: 3:3 Attribute x is multiply defined in class A, previously at 2:3",
+      "Error: 4:14 Formal parameter a is multiply defined in method f",
+      "Error: 5:5 'self' cannot be the name of a formal parameter in method g",
+      "Error: 5:28 Undeclared identifier y",
+      "Error: 9:3 Attribute x of class B is already defined in ancestor class A",
+      "Error: 10:3 Method f of class B overrides A.f(a : Int, a : Bool) : Int with a different signature",
+      "Error: 10:22 Undeclared identifier z",
+      "Error: 11:22 'self' cannot be bound in a 'let' expression",
+      "Error: 11:50 Undeclared identifier w",
+    ]);
   }
 }
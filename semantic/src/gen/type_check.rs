@@ -0,0 +1,400 @@
+use crate::environments::{Environment, SELF_OBJECT_NAME};
+use crate::models::diagnostic::Diagnostic;
+use crate::models::symbols::{EnvType, Symbol, SymbolTable};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, OBJECT_CLASS_NAME, STR_CLASS_NAME};
+use parser::model::expressions::{CaseBranch, Expression};
+use parser::model::feature::ParseFeature;
+use parser::model::program::ParseProgram;
+use parser::model::Ident;
+use std::collections::HashMap;
+
+/// Static types inferred for the expressions of a program, keyed by the address of the expression in the AST.
+/// Only valid for the program it was inferred from.
+#[derive(Debug, Default)]
+pub struct TypeTable {
+  types: HashMap<usize, String>,
+}
+
+impl TypeTable {
+  pub fn get(&self, expr: &Expression) -> Option<&str> {
+    self.types.get(&(expr as *const Expression as usize)).map(String::as_str)
+  }
+
+  fn insert(&mut self, expr: &Expression, expr_type: String) {
+    self.types.insert(expr as *const Expression as usize, expr_type);
+  }
+}
+
+/// Infers the static type of every expression of the program and checks it against the declared types.
+/// Expects a program that passed `gen_symbol_table`, so all identifiers are declared and all types are defined.
+/// Returns type errors along with warnings about `case` expressions.
+pub fn type_check(program: &ParseProgram, env: &Environment) -> Vec<Diagnostic> {
+  infer_types(program, env).1
+}
+
+/// Same as `type_check`, also returning the static type of every expression
+pub fn infer_types(program: &ParseProgram, env: &Environment) -> (TypeTable, Vec<Diagnostic>) {
+  let mut types = TypeTable::default();
+  let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+  for class in program.classes() {
+    let class_name = class.get_name();
+    let symbol_table = env.object_env(&class_name);
+    let mut checker = TypeChecker { env, class_name, symbol_table, types: &mut types, diagnostics: &mut diagnostics };
+
+    for feature in class.features.iter().flatten() {
+      match feature {
+        ParseFeature::Attribute { attribute } => {
+          let Some(expr) = &attribute.expr else {
+            continue;
+          };
+          let (attr_name, declared) = (attribute.get_name(), attribute.return_type.get_name());
+          let inferred = checker.type_of(expr);
+          if !checker.conforms(&inferred, &declared) {
+            let message = format!("Inferred type {inferred} of initialization of attribute {attr_name} does not conform to declared type {declared}");
+            checker.error(attribute.name.get_pos(), message);
+          }
+        }
+        ParseFeature::Method { method } => {
+          checker.symbol_table.enter_scope();
+          for formal in method.formals.iter().flatten() {
+            checker.bind(&formal.formal_name, &formal.formal_type.get_name(), EnvType::Method);
+          }
+
+          let (method_name, declared) = (method.get_name(), method.return_type.get_name());
+          let inferred = checker.type_of(&method.expr);
+          if !checker.conforms(&inferred, &declared) {
+            let message = format!("Inferred return type {inferred} of method {method_name} does not conform to declared return type {declared}");
+            checker.error(method.name.get_pos(), message);
+          }
+          checker.symbol_table.exit_scope();
+        }
+      }
+    }
+  }
+
+  (types, diagnostics)
+}
+
+struct TypeChecker<'a> {
+  env: &'a Environment,
+  class_name: String,
+  symbol_table: SymbolTable,
+  types: &'a mut TypeTable,
+  diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl TypeChecker<'_> {
+  fn error(&mut self, pos: (u32, u32), message: String) {
+    self.diagnostics.push(Diagnostic::error(pos.0, pos.1, message));
+  }
+
+  fn warning(&mut self, pos: (u32, u32), message: String) {
+    self.diagnostics.push(Diagnostic::warning(pos.0, pos.1, message));
+  }
+
+  fn bind(&mut self, name: &Ident, sym_type: &str, env_type: EnvType) {
+    let (line_num, line_pos) = name.get_pos();
+    self.symbol_table.put(Symbol { name: name.get_name(), env_type, sym_type: sym_type.to_string(), line_num, line_pos });
+  }
+
+  /// Replaces `SELF_TYPE` by the class being checked
+  fn resolve(&self, type_name: &str) -> String {
+    if type_name == KEYWORD_SELF_TYPE { self.class_name.clone() } else { type_name.to_string() }
+  }
+
+  fn conforms(&self, sub_type: &str, super_type: &str) -> bool {
+    self.env.hierarchy.conforms_in_class(sub_type, super_type, &self.class_name)
+  }
+
+  fn lub(&self, a: &str, b: &str) -> String {
+    self.env.hierarchy.lub_in_class(a, b, &self.class_name).unwrap_or_else(|| OBJECT_CLASS_NAME.to_string())
+  }
+
+  fn expect_type(&mut self, expr: &Expression, expected: &str, context: &str) {
+    let inferred = self.type_of(expr);
+    if inferred != expected {
+      self.error(expr.get_pos(), format!("{context} has type {inferred} instead of {expected}"));
+    }
+  }
+
+  fn type_of(&mut self, expr: &Expression) -> String {
+    let expr_type = self.infer(expr);
+    self.types.insert(expr, expr_type.clone());
+    expr_type
+  }
+
+  fn infer(&mut self, expr: &Expression) -> String {
+    match expr {
+      // Reported by the scope checker
+      Expression::PartialAssign { .. } |
+      Expression::PartialDispatch { .. } |
+      Expression::PartialCastDispatch { .. } |
+      Expression::PartialBinary { .. } => OBJECT_CLASS_NAME.to_string(),
+
+      Expression::IntExpr { .. } => INT_CLASS_NAME.to_string(),
+      Expression::BoolExpr { .. } => BOOL_CLASS_NAME.to_string(),
+      Expression::StringExpr { .. } => STR_CLASS_NAME.to_string(),
+      Expression::SelfTypeExpr { .. } | Expression::SelfExpr => KEYWORD_SELF_TYPE.to_string(),
+
+      Expression::IdentExpr { name } => {
+        let name = name.get_name();
+        if name == SELF_OBJECT_NAME {
+          return KEYWORD_SELF_TYPE.to_string();
+        }
+        self.symbol_table.lookup_symbol(&name).map_or_else(|| OBJECT_CLASS_NAME.to_string(), |symbol| symbol.sym_type.clone())
+      }
+
+      Expression::Assign { name, expr } => {
+        let inferred = self.type_of(expr);
+        if let Some(declared) = self.symbol_table.lookup_symbol(&name.get_name()).map(|symbol| symbol.sym_type.clone()) {
+          if !self.conforms(&inferred, &declared) {
+            let message = format!("Type {inferred} of assigned expression does not conform to declared type {declared} of identifier {}", name.get_name());
+            self.error(name.get_pos(), message);
+          }
+        }
+        inferred
+      }
+
+      Expression::New { type_name } => type_name.get_name(),
+
+      Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } => {
+        let caller_type = self.type_of(calling_expr);
+        let arg_types: Vec<String> = param_list.iter().map(|param| self.type_of(param)).collect();
+        let method_name = fn_name.get_name();
+
+        let dispatch_class = match cast_type {
+          Some(cast_type) => {
+            let cast_name = cast_type.get_name();
+            if !self.conforms(&caller_type, &cast_name) {
+              let message = format!("Expression type {caller_type} does not conform to declared static dispatch type {cast_name}");
+              self.error(cast_type.get_pos(), message);
+            }
+            cast_name
+          }
+          None => self.resolve(&caller_type),
+        };
+
+        let Some(method) = self.env.lookup_method(&dispatch_class, &method_name) else {
+          self.error(fn_name.get_pos(), format!("Dispatch to undefined method {method_name} of class {dispatch_class}"));
+          return OBJECT_CLASS_NAME.to_string();
+        };
+
+        if method.formals.len() != arg_types.len() {
+          let message = format!("Method {method_name} called with {} arguments instead of {}", arg_types.len(), method.formals.len());
+          self.error(fn_name.get_pos(), message);
+        }
+        for ((param, arg_type), (formal_name, formal_type)) in param_list.iter().zip(&arg_types).zip(&method.formals) {
+          if !self.conforms(arg_type, formal_type) {
+            let message = format!("In call of method {method_name}, type {arg_type} of parameter {formal_name} does not conform to declared type {formal_type}");
+            self.error(param.get_pos(), message);
+          }
+        }
+
+        if method.ret_type == KEYWORD_SELF_TYPE { caller_type } else { method.ret_type.clone() }
+      }
+
+      Expression::Conditional { predicate, then_expr, else_expr } => {
+        self.expect_type(predicate, BOOL_CLASS_NAME, "Predicate of 'if'");
+        let then_type = self.type_of(then_expr);
+        let else_type = self.type_of(else_expr);
+        self.lub(&then_type, &else_type)
+      }
+
+      Expression::Loop { predicate, body } => {
+        self.expect_type(predicate, BOOL_CLASS_NAME, "Loop condition");
+        self.type_of(body);
+        OBJECT_CLASS_NAME.to_string()
+      }
+
+      Expression::Block { expr_list } => {
+        let mut block_type = OBJECT_CLASS_NAME.to_string();
+        for expr in expr_list {
+          block_type = self.type_of(expr);
+        }
+        block_type
+      }
+
+      Expression::Let { let_init, in_expr } => {
+        for init in let_init {
+          let declared = init.id_type.get_name();
+          if let Some(init_expr) = &init.expr {
+            let inferred = self.type_of(init_expr);
+            if !self.conforms(&inferred, &declared) {
+              let message = format!("Inferred type {inferred} of initialization of {} does not conform to identifier's declared type {declared}", init.id.get_name());
+              self.error(init.id.get_pos(), message);
+            }
+          }
+          self.symbol_table.enter_scope();
+          self.bind(&init.id, &declared, EnvType::Let);
+        }
+
+        let let_type = self.type_of(in_expr);
+        for _ in let_init {
+          self.symbol_table.exit_scope();
+        }
+        let_type
+      }
+
+      Expression::Case { switch_expression, branches } => {
+        let switch_type = self.type_of(switch_expression);
+        self.check_case_branches(&switch_type, branches, switch_expression.get_pos());
+
+        let mut case_type: Option<String> = None;
+        for branch in branches {
+          self.symbol_table.enter_scope();
+          self.bind(&branch.id, &branch.id_type.get_name(), EnvType::Case);
+          let branch_type = self.type_of(&branch.expr);
+          self.symbol_table.exit_scope();
+
+          case_type = Some(match case_type {
+            Some(case_type) => self.lub(&case_type, &branch_type),
+            None => branch_type,
+          });
+        }
+        case_type.unwrap_or_else(|| OBJECT_CLASS_NAME.to_string())
+      }
+
+      Expression::Plus { left, right } => self.arithmetic(left, right, "+"),
+      Expression::Minus { left, right } => self.arithmetic(left, right, "-"),
+      Expression::Multiply { left, right } => self.arithmetic(left, right, "*"),
+      Expression::Divide { left, right } => self.arithmetic(left, right, "/"),
+      Expression::LessThan { left, right } => self.comparison(left, right, "<"),
+      Expression::LessThanOrEqual { left, right } => self.comparison(left, right, "<="),
+
+      Expression::Equal { left, right } => {
+        let left_type = self.type_of(left);
+        let right_type = self.type_of(right);
+        let is_basic = |t: &str| [INT_CLASS_NAME, STR_CLASS_NAME, BOOL_CLASS_NAME].contains(&t);
+        if (is_basic(&left_type) || is_basic(&right_type)) && left_type != right_type {
+          self.error(left.get_pos(), format!("Illegal comparison of {left_type} with {right_type}"));
+        }
+        BOOL_CLASS_NAME.to_string()
+      }
+
+      Expression::Negate { expr } => {
+        self.expect_type(expr, INT_CLASS_NAME, "Argument of '~'");
+        INT_CLASS_NAME.to_string()
+      }
+      Expression::Not { expr } => {
+        self.expect_type(expr, BOOL_CLASS_NAME, "Argument of 'not'");
+        BOOL_CLASS_NAME.to_string()
+      }
+      Expression::IsVoid { expr } => {
+        self.type_of(expr);
+        BOOL_CLASS_NAME.to_string()
+      }
+    }
+  }
+
+  fn arithmetic(&mut self, left: &Expression, right: &Expression, op: &str) -> String {
+    self.check_int_operands(left, right, op);
+    INT_CLASS_NAME.to_string()
+  }
+
+  fn comparison(&mut self, left: &Expression, right: &Expression, op: &str) -> String {
+    self.check_int_operands(left, right, op);
+    BOOL_CLASS_NAME.to_string()
+  }
+
+  fn check_int_operands(&mut self, left: &Expression, right: &Expression, op: &str) {
+    let left_type = self.type_of(left);
+    let right_type = self.type_of(right);
+    if left_type != INT_CLASS_NAME || right_type != INT_CLASS_NAME {
+      self.error(left.get_pos(), format!("Non-Int arguments: {left_type} {op} {right_type}"));
+    }
+  }
+
+  /// Checks the branch types of a `case` against each other and against the static type of the scrutinee:
+  /// - duplicate branch types are an error
+  /// - a branch for an ancestor of the scrutinee type is never selected if another branch is for a type between them,
+  ///   as the branch of the closest ancestor of the runtime type is selected, whatever the order of the branches
+  /// - a branch can never be selected if its type is unrelated to the scrutinee type
+  /// - the `case` can fail at runtime if no branch type is an ancestor of the scrutinee type, e.g. without an `Object` branch
+  fn check_case_branches(&mut self, switch_type: &str, branches: &[CaseBranch], case_pos: (u32, u32)) {
+    let env = self.env;
+    let hierarchy = &env.hierarchy;
+    let scrutinee = self.resolve(switch_type);
+    let mut seen: Vec<String> = Vec::new();
+    let mut exhaustive = false;
+
+    for branch in branches {
+      let branch_type = branch.id_type.get_name();
+      let pos = branch.id_type.get_pos();
+      if !hierarchy.contains(&branch_type) {
+        continue; // reported by the scope checker
+      }
+
+      if seen.contains(&branch_type) {
+        self.error(pos, format!("Duplicate branch {branch_type} in case statement"));
+        continue;
+      }
+
+      let closer = branches.iter()
+          .map(|other| other.id_type.get_name())
+          .find(|other| *other != branch_type && hierarchy.contains(other) && hierarchy.conforms(&scrutinee, other) && hierarchy.conforms(other, &branch_type));
+      if let Some(closer) = closer {
+        self.warning(pos, format!("Branch of type {branch_type} can never be selected: the branch of type {closer} is closer to the scrutinee type {scrutinee}"));
+      } else if !hierarchy.conforms(&scrutinee, &branch_type) && !hierarchy.conforms(&branch_type, &scrutinee) {
+        self.warning(pos, format!("Branch of type {branch_type} can never be selected: it is unrelated to the scrutinee type {scrutinee}"));
+      }
+
+      exhaustive |= hierarchy.conforms(&scrutinee, &branch_type);
+      seen.push(branch_type);
+    }
+
+    if !exhaustive {
+      let message = format!("Case on type {scrutinee} has no {OBJECT_CLASS_NAME} branch and fails at runtime when no branch matches");
+      self.warning(case_pos, message);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gen::check_program;
+  use crate::models::diagnostic::Severity;
+  use parser::get_ast_from_file_path;
+  use parser::programs::find_programs;
+
+  #[test]
+  fn test_all_programs() {
+    for test_program in find_programs("../test_resources/programs") {
+      let program = test_program.parse().expect("Couldn't parse file");
+      if let Err(errors) = check_program(&program) {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        panic!("Type errors in {}:\n{}", test_program.name(), errors.join("\n"));
+      }
+    }
+  }
+
+  #[test]
+  fn test_type_errors() {
+    let program = get_ast_from_file_path("../test_resources/semantic/type_errors.cl").expect("Couldn't parse file");
+    let diagnostics = check_program(&program).expect_err("Program must have type errors");
+    let errors: Vec<(u32, u32)> = diagnostics.iter().filter(|d| d.is_error()).filter_map(|d| d.position).collect();
+    for diagnostic in &diagnostics {
+      println!("{diagnostic}");
+    }
+
+    assert_eq!(errors, vec![(3, 3), (5, 9), (6, 5), (7, 8), (8, 27), (9, 10), (10, 5), (11, 15), (12, 16)]);
+  }
+
+  #[test]
+  fn test_case_analysis() {
+    let program = get_ast_from_file_path("../test_resources/semantic/case_analysis.cl").expect("Couldn't parse file");
+    let diagnostics = check_program(&program).expect_err("Program has a duplicate branch");
+    for diagnostic in &diagnostics {
+      println!("{diagnostic}");
+    }
+
+    let positions: Vec<(Severity, u32, u32)> = diagnostics.iter().filter_map(|d| d.position.map(|(line, column)| (d.severity, line, column))).collect();
+    assert_eq!(positions, vec![
+      (Severity::Warning, 12, 10), // no branch for all of `A`
+      (Severity::Warning, 14, 11), // `C` unrelated to `A`
+      (Severity::Warning, 17, 11), // `Object`, for which `A` is closer
+      (Severity::Error, 19, 11),   // duplicate `A`
+    ]);
+  }
+}
@@ -1,3 +1,4 @@
+pub mod environments;
 pub mod models;
-mod environments;
 pub mod gen;
+pub mod lint;
@@ -0,0 +1,241 @@
+use crate::environments::{Environment, SELF_OBJECT_NAME};
+use crate::gen::entry::EntryPoint;
+use crate::gen::type_check::TypeTable;
+use crate::lint::{Lint, DEAD_CODE_AFTER_ABORT, ISVOID_BASIC_CLASS, SHADOWED_ATTRIBUTE, UNREACHABLE_METHOD, UNREAD_ATTRIBUTE, UNUSED_VARIABLE, WHILE_FALSE};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, OBJECT_CLASS_NAME, STR_CLASS_NAME};
+use parser::model::expressions::Expression;
+use parser::model::feature::ParseFeature;
+use parser::model::program::ParseProgram;
+use parser::model::Ident;
+use std::collections::{HashMap, HashSet};
+
+pub(super) type Report<'r> = dyn FnMut(&'static Lint, &str, (u32, u32), String) + 'r; // lint, class, position, message
+
+const ABORT_METHOD_NAME: &str = "abort";
+
+pub(super) fn check_program(program: &ParseProgram, env: &Environment, types: &TypeTable, entry: &EntryPoint, report: &mut Report) {
+  let mut attribute_reads: HashSet<(String, String)> = HashSet::new(); // declaring class and attribute name
+
+  for class in program.classes() {
+    let class_name = class.get_name();
+    let mut walker = Walker { env, types, class_name, scopes: Vec::new(), attribute_reads: &mut attribute_reads, report };
+
+    for feature in class.features.iter().flatten() {
+      match feature {
+        ParseFeature::Attribute { attribute } => {
+          if let Some(expr) = &attribute.expr {
+            walker.walk(expr);
+          }
+        }
+        ParseFeature::Method { method } => {
+          walker.scopes.push(method.formals.iter().flatten().map(|formal| Binding::new(&formal.formal_name, "Formal parameter", true)).collect());
+          walker.walk(&method.expr);
+          walker.exit_scope();
+        }
+      }
+    }
+  }
+
+  for class in program.classes() {
+    let class_name = class.get_name();
+    for attribute in env.attributes.get(&class_name).into_iter().flatten() {
+      if !attribute_reads.contains(&(class_name.clone(), attribute.name.clone())) {
+        let message = format!("Attribute {} of class {class_name} is never read", attribute.name);
+        report(&UNREAD_ATTRIBUTE, &class_name, (attribute.line_num, attribute.line_pos), message);
+      }
+    }
+  }
+
+  check_reachable_methods(program, env, entry, report);
+}
+
+/// A `let`, `case` or formal binding, with whether it was read
+struct Binding {
+  name: String,
+  kind: &'static str,
+  pos: (u32, u32),
+  check_unused: bool,
+  read: bool,
+}
+
+impl Binding {
+  fn new(name: &Ident, kind: &'static str, check_unused: bool) -> Self {
+    Binding { name: name.get_name(), kind, pos: name.get_pos(), check_unused, read: false }
+  }
+}
+
+struct Walker<'a, 'r> {
+  env: &'a Environment,
+  types: &'a TypeTable,
+  class_name: String,
+  scopes: Vec<Vec<Binding>>,
+  attribute_reads: &'a mut HashSet<(String, String)>,
+  report: &'a mut Report<'r>,
+}
+
+impl Walker<'_, '_> {
+  fn exit_scope(&mut self) {
+    for binding in self.scopes.pop().into_iter().flatten() {
+      if binding.check_unused && !binding.read {
+        (self.report)(&UNUSED_VARIABLE, &self.class_name, binding.pos, format!("{} {} is never read", binding.kind, binding.name));
+      }
+    }
+  }
+
+  fn enter_binding(&mut self, name: &Ident, kind: &'static str, check_unused: bool) {
+    let var_name = name.get_name();
+    if let Some(declaring_class) = self.declaring_class(&var_name) {
+      let message = format!("{kind} {var_name} shadows attribute {var_name} of class {declaring_class}");
+      (self.report)(&SHADOWED_ATTRIBUTE, &self.class_name, name.get_pos(), message);
+    }
+    self.scopes.push(vec![Binding::new(name, kind, check_unused)]);
+  }
+
+  /// Returns the class declaring the attribute visible in the current class
+  fn declaring_class(&self, attribute_name: &str) -> Option<String> {
+    self.env.ancestors(&self.class_name).into_iter()
+        .find(|class| self.env.attributes.get(class).is_some_and(|attrs| attrs.iter().any(|a| a.name == attribute_name)))
+  }
+
+  fn read(&mut self, name: &str) {
+    if name == SELF_OBJECT_NAME {
+      return;
+    }
+    if let Some(binding) = self.scopes.iter_mut().rev().flat_map(|scope| scope.iter_mut()).find(|b| b.name == name) {
+      binding.read = true;
+    } else if let Some(declaring_class) = self.declaring_class(name) {
+      self.attribute_reads.insert((declaring_class, name.to_string()));
+    }
+  }
+
+  /// Returns `true` if the expression is a call to `Object.abort`, which never returns
+  fn is_abort_call(&self, expr: &Expression) -> bool {
+    let Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } = expr else {
+      return false;
+    };
+    if fn_name.get_name() != ABORT_METHOD_NAME || !param_list.is_empty() {
+      return false;
+    }
+
+    let dispatch_class = match cast_type {
+      Some(cast_type) => cast_type.get_name(),
+      None => match self.types.get(calling_expr) {
+        Some(KEYWORD_SELF_TYPE) | None => self.class_name.clone(),
+        Some(caller_type) => caller_type.to_string(),
+      },
+    };
+    // Overrides in subclasses of the static type may return
+    let overridden = self.env.class_map.keys()
+        .any(|class| class != OBJECT_CLASS_NAME && self.env.hierarchy.conforms(class, &dispatch_class) && self.env.methods.get(class, ABORT_METHOD_NAME).is_some());
+    !overridden && self.env.lookup_method(&dispatch_class, ABORT_METHOD_NAME).is_some_and(|m| m.class_name == OBJECT_CLASS_NAME)
+  }
+
+  fn walk(&mut self, expr: &Expression) {
+    match expr {
+      Expression::IdentExpr { name } => self.read(&name.get_name()),
+
+      Expression::Let { let_init, in_expr } => {
+        for init in let_init {
+          if let Some(init_expr) = &init.expr {
+            self.walk(init_expr);
+          }
+          self.enter_binding(&init.id, "Let binding", true);
+        }
+        self.walk(in_expr);
+        for _ in let_init {
+          self.exit_scope();
+        }
+      }
+
+      Expression::Case { switch_expression, branches } => {
+        self.walk(switch_expression);
+        for branch in branches {
+          self.enter_binding(&branch.id, "Case binding", false);
+          self.walk(&branch.expr);
+          self.exit_scope();
+        }
+      }
+
+      Expression::Block { expr_list } => {
+        if let Some(index) = expr_list.iter().position(|e| self.is_abort_call(e)) {
+          // `{ abort(); 0; }` is the idiom giving the block the type the method returns
+          let dead = &expr_list[index + 1..];
+          if !matches!(dead, [] | [Expression::IntExpr { .. } | Expression::BoolExpr { .. } | Expression::StringExpr { .. } | Expression::New { .. } | Expression::SelfExpr | Expression::IdentExpr { .. }]) {
+            (self.report)(&DEAD_CODE_AFTER_ABORT, &self.class_name, dead[0].get_pos(), "Unreachable expression after call to abort()".to_string());
+          }
+        }
+        for expr in expr_list {
+          self.walk(expr);
+        }
+      }
+
+      Expression::Loop { predicate, body } => {
+        if let Expression::BoolExpr { value: false, line_num, line_pos } = predicate.as_ref() {
+          (self.report)(&WHILE_FALSE, &self.class_name, (*line_num, *line_pos), "Loop body never runs: the predicate is always false".to_string());
+        }
+        self.walk(predicate);
+        self.walk(body);
+      }
+
+      Expression::IsVoid { expr: operand } => {
+        if let Some(operand_type) = self.types.get(operand).filter(|t| [INT_CLASS_NAME, BOOL_CLASS_NAME, STR_CLASS_NAME].contains(t)) {
+          let message = format!("isvoid on {operand_type} is always false: {operand_type} values are never void");
+          (self.report)(&ISVOID_BASIC_CLASS, &self.class_name, operand.get_pos(), message);
+        }
+        self.walk(operand);
+      }
+
+      _ => {
+        for sub_expr in expr.sub_expressions() {
+          self.walk(sub_expr);
+        }
+      }
+    }
+  }
+}
+
+/// Reports the methods that cannot be called from the entry point. Dispatch is resolved by method name only, so a
+/// method is reachable when any method with the same name is called from reachable code.
+fn check_reachable_methods(program: &ParseProgram, env: &Environment, entry: &EntryPoint, report: &mut Report) {
+  if env.lookup_method(&entry.class_name, &entry.method_name).is_none() {
+    return; // reported by the entry point check
+  }
+
+  let mut bodies: HashMap<String, Vec<&Expression>> = HashMap::new();
+  let mut roots: Vec<&Expression> = Vec::new();
+
+  for class in program.classes() {
+    for feature in class.features.iter().flatten() {
+      match feature {
+        // Attribute initialisers run whenever an object is created
+        ParseFeature::Attribute { attribute } => roots.extend(&attribute.expr),
+        ParseFeature::Method { method } => bodies.entry(method.get_name()).or_default().push(&method.expr),
+      }
+    }
+  }
+
+  let mut reached: HashSet<String> = HashSet::from([entry.method_name.clone()]);
+  let mut pending: Vec<&Expression> = roots;
+  pending.extend(bodies.get(&entry.method_name).into_iter().flatten());
+
+  while let Some(expr) = pending.pop() {
+    if let Expression::Dispatch { fn_name, .. } = expr {
+      if reached.insert(fn_name.get_name()) {
+        pending.extend(bodies.get(&fn_name.get_name()).into_iter().flatten());
+      }
+    }
+    pending.extend(expr.sub_expressions());
+  }
+
+  for class in program.classes() {
+    for feature in class.features.iter().flatten() {
+      if let ParseFeature::Method { method } = feature {
+        if !reached.contains(&method.get_name()) {
+          let message = format!("Method {}.{} is never called from {entry}", class.get_name(), method.get_name());
+          report(&UNREACHABLE_METHOD, &class.get_name(), method.name.get_pos(), message);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,128 @@
+use crate::lint::{find_lint, Lint, LintLevel};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE_NAME: &str = "cool.toml";
+const LINTS_SECTION: &str = "lints";
+
+/// Level of each lint, read from the `[lints]` table of `cool.toml`:
+/// ```toml
+/// [lints]
+/// unused_variable = "allow"
+/// while_false = "deny"
+/// ```
+/// Lints missing from the table keep their default level. Other tables are ignored.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LintConfig {
+  levels: HashMap<&'static str, LintLevel>,
+}
+
+impl LintConfig {
+  pub fn from_file(path: &Path) -> Result<LintConfig, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+    LintConfig::parse(&content).map_err(|e| format!("{}:{e}", path.display()))
+  }
+
+  /// Parses the subset of TOML used by `cool.toml`: tables, `key = "value"` pairs and `#` comments.
+  /// Errors are prefixed with the line number.
+  pub fn parse(content: &str) -> Result<LintConfig, String> {
+    let mut config = LintConfig::default();
+    let mut section = String::new();
+
+    for (index, line) in content.lines().enumerate() {
+      let line_num = index + 1;
+      let line = strip_comment(line).trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      if let Some(header) = line.strip_prefix('[') {
+        let Some(name) = header.strip_suffix(']') else {
+          return Err(format!("{line_num}: Unterminated table header `{line}`"));
+        };
+        section = name.trim().to_string();
+        continue;
+      }
+
+      let Some((key, value)) = line.split_once('=') else {
+        return Err(format!("{line_num}: Expected `key = value`, found `{line}`"));
+      };
+      if section != LINTS_SECTION {
+        continue;
+      }
+
+      let (key, value) = (key.trim(), value.trim());
+      let Some(level) = unquote(value) else {
+        return Err(format!("{line_num}: Expected a quoted lint level for `{key}`, found `{value}`"));
+      };
+      let level = LintLevel::try_from(level).map_err(|e| format!("{line_num}: {e}"))?;
+      config.set_level(key, level).map_err(|e| format!("{line_num}: {e}"))?;
+    }
+
+    Ok(config)
+  }
+
+  pub fn set_level(&mut self, lint_name: &str, level: LintLevel) -> Result<(), String> {
+    let Some(lint) = find_lint(lint_name) else {
+      return Err(format!("Unknown lint `{lint_name}`"));
+    };
+    self.levels.insert(lint.name, level);
+    Ok(())
+  }
+
+  pub fn level(&self, lint: &Lint) -> LintLevel {
+    self.levels.get(lint.name).copied().unwrap_or(lint.default_level)
+  }
+}
+
+/// Removes a `#` comment, unless it is within a quoted string
+fn strip_comment(line: &str) -> &str {
+  let mut quote: Option<char> = None;
+  for (index, c) in line.char_indices() {
+    match (quote, c) {
+      (None, '#') => return &line[..index],
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(q), _) if q == c => quote = None,
+      _ => {}
+    }
+  }
+  line
+}
+
+fn unquote(value: &str) -> Option<&str> {
+  ['"', '\''].iter().find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lint::config::LintConfig;
+  use crate::lint::{LintLevel, UNREACHABLE_METHOD, UNUSED_VARIABLE, WHILE_FALSE};
+
+  #[test]
+  fn test_parse_config() {
+    let config = LintConfig::parse(r#"
+      # project settings
+      [package]
+      name = "list"
+
+      [lints]
+      unused_variable = "allow"   # formals of overridden methods
+      while_false = 'deny'
+    "#).expect("Config must parse");
+
+    assert_eq!(config.level(&UNUSED_VARIABLE), LintLevel::Allow);
+    assert_eq!(config.level(&WHILE_FALSE), LintLevel::Deny);
+    assert_eq!(config.level(&UNREACHABLE_METHOD), UNREACHABLE_METHOD.default_level);
+  }
+
+  #[test]
+  fn test_config_errors() {
+    assert_eq!(LintConfig::parse("[lints]\nunused = \"allow\"").unwrap_err(), "2: Unknown lint `unused`");
+    assert_eq!(
+      LintConfig::parse("[lints]\n\nwhile_false = \"error\"").unwrap_err(),
+      "3: Unknown lint level `error`, expected one of `allow`, `warn` or `deny`"
+    );
+    assert_eq!(LintConfig::parse("[lints]\nwhile_false = deny").unwrap_err(), "2: Expected a quoted lint level for `while_false`, found `deny`");
+  }
+}
@@ -0,0 +1,174 @@
+pub mod config;
+mod checks;
+pub mod suppression;
+
+use crate::environments::Environment;
+use crate::gen::entry::EntryPoint;
+use crate::gen::type_check::TypeTable;
+use crate::lint::config::LintConfig;
+use crate::lint::suppression::Suppressions;
+use crate::models::diagnostic::{Diagnostic, Severity};
+use parser::model::program::ParseProgram;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LintLevel {
+  Allow,
+  Warn,
+  Deny,
+}
+
+impl TryFrom<&str> for LintLevel {
+  type Error = String;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    match value {
+      "allow" => Ok(LintLevel::Allow),
+      "warn" => Ok(LintLevel::Warn),
+      "deny" => Ok(LintLevel::Deny),
+      _ => Err(format!("Unknown lint level `{value}`, expected one of `allow`, `warn` or `deny`")),
+    }
+  }
+}
+
+impl Display for LintLevel {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LintLevel::Allow => write!(f, "allow"),
+      LintLevel::Warn => write!(f, "warn"),
+      LintLevel::Deny => write!(f, "deny"),
+    }
+  }
+}
+
+/// A lint with a stable name, used in `cool.toml` and in suppression comments
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Lint {
+  pub name: &'static str,
+  pub default_level: LintLevel,
+  pub description: &'static str,
+}
+
+pub const UNUSED_VARIABLE: Lint = Lint {
+  name: "unused_variable",
+  default_level: LintLevel::Warn,
+  description: "`let` binding or formal parameter that is never read",
+};
+
+pub const UNREAD_ATTRIBUTE: Lint = Lint {
+  name: "unread_attribute",
+  default_level: LintLevel::Warn,
+  description: "attribute that is never read in its class or its subclasses",
+};
+
+pub const SHADOWED_ATTRIBUTE: Lint = Lint {
+  name: "shadowed_attribute",
+  default_level: LintLevel::Warn,
+  description: "`let` or `case` binding that hides an attribute of the class",
+};
+
+// A last constant, variable, `self` or `new` is not reported, as `{ abort(); 0; }` gives the block its type
+pub const DEAD_CODE_AFTER_ABORT: Lint = Lint {
+  name: "dead_code_after_abort",
+  default_level: LintLevel::Warn,
+  description: "expression of a block that follows a call to `abort()`",
+};
+
+pub const WHILE_FALSE: Lint = Lint {
+  name: "while_false",
+  default_level: LintLevel::Warn,
+  description: "`while` loop whose predicate is the constant `false`",
+};
+
+// COOL has no `void` literal and `=` only compares a basic class with itself, so `isvoid` is the only way to compare
+// an `Int` with void
+pub const ISVOID_BASIC_CLASS: Lint = Lint {
+  name: "isvoid_basic_class",
+  default_level: LintLevel::Warn,
+  description: "`isvoid` on an `Int`, `Bool` or `String`, which is never void",
+};
+
+pub const UNREACHABLE_METHOD: Lint = Lint {
+  name: "unreachable_method",
+  default_level: LintLevel::Allow,
+  description: "method that is never called, directly or indirectly, from the entry point, `Main.main` by default",
+};
+
+pub const LINTS: [Lint; 7] = [
+  UNUSED_VARIABLE,
+  UNREAD_ATTRIBUTE,
+  SHADOWED_ATTRIBUTE,
+  DEAD_CODE_AFTER_ABORT,
+  WHILE_FALSE,
+  ISVOID_BASIC_CLASS,
+  UNREACHABLE_METHOD,
+];
+
+pub fn find_lint(name: &str) -> Option<&'static Lint> {
+  LINTS.iter().find(|lint| lint.name == name)
+}
+
+/// Runs all lints that are not allowed in the configuration or suppressed by a comment on a type checked program.
+/// Lints at level `deny` are reported as errors.
+pub fn run_lints(program: &ParseProgram, env: &Environment, types: &TypeTable, config: &LintConfig, suppressions: &Suppressions, entry: &EntryPoint) -> Vec<Diagnostic> {
+  let mut reported: Vec<(&'static Lint, u32, u32, String)> = Vec::new();
+  checks::check_program(program, env, types, entry, &mut |lint, class_name, (line_num, line_pos), message| {
+    if !suppressions.is_suppressed(lint.name, class_name, line_num) {
+      reported.push((lint, line_num, line_pos, message));
+    }
+  });
+
+  reported.into_iter()
+      .filter_map(|(lint, line_num, line_pos, message)| {
+        let severity = match config.level(lint) {
+          LintLevel::Allow => return None,
+          LintLevel::Warn => Severity::Warning,
+          LintLevel::Deny => Severity::Error,
+        };
+        Some(Diagnostic { severity, position: Some((line_num, line_pos)), message, lint: Some(lint.name) })
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gen::{check_program_with, CheckOptions};
+  use crate::lint::config::LintConfig;
+  use crate::lint::suppression::Suppressions;
+  use crate::lint::{LintLevel, WHILE_FALSE};
+  use crate::models::diagnostic::Severity;
+  use parser::get_ast_from_file_path;
+  use std::fs;
+
+  const LINTS_PROGRAM: &str = "../test_resources/semantic/lints.cl";
+
+  #[test]
+  fn test_lints() {
+    let program = get_ast_from_file_path(LINTS_PROGRAM).expect("Couldn't parse file");
+    let source = fs::read_to_string(LINTS_PROGRAM).expect("Couldn't read file");
+    let lint_config = LintConfig::parse("[lints]\nunreachable_method = \"warn\"").expect("Config must parse");
+    let mut options = CheckOptions { lint_config, suppressions: Suppressions::from_source(&source), ..CheckOptions::default() };
+    let checked = check_program_with(&program, &options).expect("Program must type check");
+
+    let warnings: Vec<(&str, u32, u32)> = checked.warnings.iter().filter_map(|w| w.position.map(|(line, column)| (w.lint.unwrap(), line, column))).collect();
+    for warning in &checked.warnings {
+      println!("{warning}");
+    }
+    assert_eq!(warnings, vec![
+      ("unread_attribute", 3, 3),
+      ("unused_variable", 4, 7),
+      ("shadowed_attribute", 10, 9),
+      ("unused_variable", 10, 23),
+      ("while_false", 12, 11),
+      ("isvoid_basic_class", 13, 15),
+      ("dead_code_after_abort", 14, 16),
+      ("unreachable_method", 19, 3),
+    ]);
+
+    options.lint_config.set_level(WHILE_FALSE.name, LintLevel::Deny).expect("Lint must exist");
+    options.suppressions = Suppressions::default();
+    let errors = check_program_with(&program, &options).expect_err("Denied lint must fail the check");
+    let errors: Vec<(&str, u32)> = errors.iter().filter(|e| e.severity == Severity::Error).filter_map(|e| e.position.map(|(line, _)| (e.lint.unwrap(), line))).collect();
+    assert_eq!(errors, vec![("while_false", 12)]);
+  }
+}
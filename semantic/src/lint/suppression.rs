@@ -0,0 +1,95 @@
+use lexer::iter::token::TokenIter;
+use lexer::model::token::Token;
+use std::collections::{HashMap, HashSet};
+
+const SUPPRESSION_PREFIX: &str = "cool-lint:";
+
+/// Lints suppressed by comments of the form `-- cool-lint: allow(unused_variable, while_false)`.
+/// A comment suppresses the lints on its own line and on the line that follows it, in the classes of its file when the
+/// program spans several files.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Suppressions {
+  files: Vec<FileSuppressions>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct FileSuppressions {
+  lines: HashMap<u32, Vec<String>>,
+  classes: Option<HashSet<String>>, // classes of the file, `None` for all classes
+}
+
+impl Suppressions {
+  /// Collects the suppression comments of the program source
+  pub fn from_source(source: &str) -> Suppressions {
+    let mut lines: HashMap<u32, Vec<String>> = HashMap::new();
+
+    for token in TokenIter::from_program_text(source) {
+      let Token::Comment { value, line_num, .. } = token else {
+        continue;
+      };
+      let Some(lints) = parse_suppression(&value) else {
+        continue;
+      };
+
+      let last_line = line_num + value.matches('\n').count() as u32;
+      for line in [line_num, last_line + 1] {
+        lines.entry(line).or_default().extend(lints.iter().cloned());
+      }
+    }
+
+    Suppressions { files: vec![FileSuppressions { lines, classes: None }] }
+  }
+
+  /// Limits the suppressions to the classes of their file, whose lines are only those of the file
+  pub fn for_classes(mut self, classes: impl IntoIterator<Item = String>) -> Suppressions {
+    let classes: HashSet<String> = classes.into_iter().collect();
+    for file in &mut self.files {
+      file.classes = Some(classes.clone());
+    }
+    self
+  }
+
+  /// Adds the suppressions of another file, used when a program spans several files
+  pub fn extend(&mut self, other: Suppressions) {
+    self.files.extend(other.files);
+  }
+
+  /// Whether a comment suppresses the lint on the line of the class
+  pub fn is_suppressed(&self, lint: &str, class_name: &str, line_num: u32) -> bool {
+    self.files.iter()
+        .filter(|file| file.classes.as_ref().is_none_or(|classes| classes.contains(class_name)))
+        .any(|file| file.lines.get(&line_num).is_some_and(|lints| lints.iter().any(|l| l == lint)))
+  }
+}
+
+/// Returns the lint names in `cool-lint: allow(a, b)`
+fn parse_suppression(comment: &str) -> Option<Vec<String>> {
+  let directive = comment.trim().strip_prefix(SUPPRESSION_PREFIX)?.trim();
+  let names = directive.strip_prefix("allow")?.trim().strip_prefix('(')?.strip_suffix(')')?;
+  Some(names.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lint::suppression::Suppressions;
+
+  #[test]
+  fn test_suppression_comments() {
+    let source = "class Main {\n  -- cool-lint: allow(unused_variable, while_false)\n  main() : Object { 0 }; (* cool-lint: allow(unread_attribute) *)\n};\n";
+    let suppressions = Suppressions::from_source(source);
+
+    assert!(suppressions.is_suppressed("unused_variable", "Main", 3));
+    assert!(suppressions.is_suppressed("while_false", "Main", 2));
+    assert!(suppressions.is_suppressed("unread_attribute", "Main", 4));
+    assert!(!suppressions.is_suppressed("unused_variable", "Main", 4));
+  }
+
+  #[test]
+  fn test_suppressions_of_several_files() {
+    let mut suppressions = Suppressions::from_source("class A {\n  -- cool-lint: allow(unused_variable)\n};\n").for_classes(["A".to_string()]);
+    suppressions.extend(Suppressions::from_source("class B {\n};\n").for_classes(["B".to_string()]));
+
+    assert!(suppressions.is_suppressed("unused_variable", "A", 3));
+    assert!(!suppressions.is_suppressed("unused_variable", "B", 3));
+  }
+}
@@ -13,6 +13,8 @@ pub struct ClassNode {
     pub parent: Cow<'static, str>,
     pub(crate) children: Vec<Cow<'static, str>>,
     pub(crate) features: Vec<FeatureNode>,
+    pub line_num: u32,
+    pub line_pos: u32,
 }
 
 impl Node for ClassNode {}
@@ -21,7 +23,7 @@ impl ClassNode {
     pub(crate) fn add_child(&mut self, child: Cow<'static, str>) {
         self.children.push(child);
     }
-    fn get_children(&self) -> Vec<Cow<'static, str>> { self.children.clone() }
+    pub fn get_children(&self) -> Vec<Cow<'static, str>> { self.children.clone() }
 
     pub fn get_base_object() -> ClassNode {
         let mut base_object = BASE_NODE_OBJECT.clone();
@@ -36,29 +38,29 @@ impl ClassNode {
 
 impl From<ParseClass> for ClassNode {
     fn from(value: ParseClass) -> Self {
-        let ParseClass { name, parent_type, .. } = value;
+        let ParseClass { name, parent_type, line_num, line_pos, .. } = value;
         let class_name = name.get_name();
         let parent = parent_type.get_name();
         let children = Vec::new();
 
-        ClassNode { name: Cow::from(class_name), parent: Cow::from(parent), children, features: Vec::new() }
+        ClassNode { name: Cow::from(class_name), parent: Cow::from(parent), children, features: Vec::new(), line_num, line_pos }
     }
 }
 
 impl From<&ParseClass> for ClassNode {
     fn from(value: &ParseClass) -> Self {
-        let ParseClass { name, parent_type, .. } = value;
+        let ParseClass { name, parent_type, line_num, line_pos, .. } = value;
         let class_name = name.get_name();
         let parent = parent_type.get_name();
         let children = Vec::new();
 
-        ClassNode { name: Cow::from(class_name), parent: Cow::from(parent), children, features: Vec::new() }
+        ClassNode { name: Cow::from(class_name), parent: Cow::from(parent), children, features: Vec::new(), line_num: *line_num, line_pos: *line_pos }
     }
 }
 
 impl Display for ClassNode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let children: String = if self.children.len() > 0 {
+        let children: String = if !self.children.is_empty() {
             self.children.join(",")
         } else {
             " None".to_string()
@@ -77,6 +79,8 @@ const BASE_NODE_OBJECT: ClassNode = ClassNode {
     parent: Cow::Borrowed(""),
     children: Vec::new(),
     features: Vec::new(),
+    line_num: 0,
+    line_pos: 0,
 };
 
 pub const BASE_NODE_IO: ClassNode = ClassNode {
@@ -84,6 +88,8 @@ pub const BASE_NODE_IO: ClassNode = ClassNode {
     parent: Cow::Borrowed(OBJECT_CLASS_NAME),
     children: Vec::new(),
     features: Vec::new(),
+    line_num: 0,
+    line_pos: 0,
 };
 
 pub const BASE_NODE_INT: ClassNode = ClassNode {
@@ -91,6 +97,8 @@ pub const BASE_NODE_INT: ClassNode = ClassNode {
     parent: Cow::Borrowed(OBJECT_CLASS_NAME),
     children: Vec::new(),
     features: Vec::new(),
+    line_num: 0,
+    line_pos: 0,
 };
 
 pub const BASE_NODE_STR: ClassNode = ClassNode {
@@ -98,6 +106,8 @@ pub const BASE_NODE_STR: ClassNode = ClassNode {
     parent: Cow::Borrowed(OBJECT_CLASS_NAME),
     children: Vec::new(),
     features: Vec::new(),
+    line_num: 0,
+    line_pos: 0,
 };
 
 pub const BASE_NODE_BOOL: ClassNode = ClassNode {
@@ -105,4 +115,6 @@ pub const BASE_NODE_BOOL: ClassNode = ClassNode {
     parent: Cow::Borrowed(OBJECT_CLASS_NAME),
     children: Vec::new(),
     features: Vec::new(),
+    line_num: 0,
+    line_pos: 0,
 };
@@ -0,0 +1,53 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+/// A semantic error or warning tied to a position in the source program, or to the whole program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub position: Option<(u32, u32)>, // line and column, none for the whole program
+  pub message: String,
+  pub lint: Option<&'static str>, // name of the lint that reported the diagnostic
+}
+
+impl Diagnostic {
+  pub fn error(line_num: u32, line_pos: u32, message: String) -> Self {
+    Diagnostic { severity: Severity::Error, position: Some((line_num, line_pos)), message, lint: None }
+  }
+
+  /// An error of the whole program rather than of a part of it, such as a missing entry point
+  pub fn program_error(message: String) -> Self {
+    Diagnostic { severity: Severity::Error, position: None, message, lint: None }
+  }
+
+  pub fn warning(line_num: u32, line_pos: u32, message: String) -> Self {
+    Diagnostic { severity: Severity::Warning, position: Some((line_num, line_pos)), message, lint: None }
+  }
+
+  pub fn is_error(&self) -> bool {
+    self.severity == Severity::Error
+  }
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let severity = match self.severity {
+      Severity::Error => "Error",
+      Severity::Warning => "Warning",
+    };
+    write!(f, "{severity}: ")?;
+    if let Some((line_num, line_pos)) = self.position {
+      write!(f, "{line_num}:{line_pos} ")?;
+    }
+    write!(f, "{}", self.message)?;
+    if let Some(lint) = self.lint {
+      write!(f, " [{lint}]")?;
+    }
+    Ok(())
+  }
+}
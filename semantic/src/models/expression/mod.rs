@@ -19,13 +19,13 @@ pub enum  ExpressionNode {
 impl Node for Expression {}
 
 impl From<Expression> for ExpressionNode {
-    fn from(value: Expression) -> Self {
+    fn from(_value: Expression) -> Self {
         todo!()
     }
 }
 
 impl From<Box<Expression>> for ExpressionNode {
-    fn from(value: Box<Expression>) -> Self {
+    fn from(_value: Box<Expression>) -> Self {
         todo!()
     }
 }
\ No newline at end of file
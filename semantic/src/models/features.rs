@@ -2,7 +2,7 @@ use crate::models::expression::ExpressionNode;
 use crate::models::formals::FormalNode;
 use crate::models::Node;
 use parser::model::feature::{Attribute, Method, ParseFeature};
-use std::borrow::{Borrow, Cow};
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 
 // #[derive(Clone)]
@@ -41,15 +41,12 @@ pub struct AttributeNode {
 
 impl From<Attribute> for AttributeNode {
   fn from(value: Attribute) -> Self {
-    let expr = match value.expr {
-      None => None,
-      Some(e) => Some(ExpressionNode::from(e)),
-    };
+    let expr = value.expr.map(ExpressionNode::from);
     AttributeNode { ident: value.name.0, f_type: value.return_type.0, exp: expr }
   }
 }
 impl Display for AttributeNode {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+  fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
     todo!()
   }
 }
@@ -65,14 +62,14 @@ pub struct MethodNode {
 }
 
 impl From<Method> for MethodNode {
-  fn from(value: Method) -> Self {
+  fn from(_value: Method) -> Self {
     todo!()
   }
 }
 impl Node for MethodNode {}
 
 impl Display for MethodNode {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+  fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
     todo!()
   }
 }
@@ -0,0 +1,271 @@
+use crate::models::class::ClassNode;
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::OBJECT_CLASS_NAME;
+use std::collections::HashMap;
+
+/// Inheritance tree of a program, indexed for conformance and least-upper-bound queries.
+///
+/// Each class gets a pre-order interval, so `conforms` is answered in constant time, and a table of
+/// `2^k`-th ancestors, so `lub` climbs the tree in logarithmic time regardless of the depth of the hierarchy.
+/// Only classes reachable from `Object` are part of the hierarchy; queries on other names return `false` or `None`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ClassHierarchy {
+  names: Vec<String>,
+  index: HashMap<String, usize>,
+  depth: Vec<usize>,
+  up: Vec<Vec<usize>>, // up[k][c] is the 2^k-th ancestor of `c`, `Object` is its own ancestor
+  enter: Vec<usize>,   // pre-order index of the class
+  exit: Vec<usize>,    // largest pre-order index in the subtree of the class
+}
+
+impl From<&HashMap<String, ClassNode>> for ClassHierarchy {
+  fn from(class_map: &HashMap<String, ClassNode>) -> Self {
+    ClassHierarchy::new(class_map)
+  }
+}
+
+impl ClassHierarchy {
+  /// Builds the hierarchy from the class map generated by `gen_class_map`, following the `children` links from `Object`
+  pub fn new(class_map: &HashMap<String, ClassNode>) -> Self {
+    let mut hierarchy = ClassHierarchy {
+      names: Vec::new(),
+      index: HashMap::new(),
+      depth: Vec::new(),
+      up: vec![Vec::new()],
+      enter: Vec::new(),
+      exit: Vec::new(),
+    };
+
+    if !class_map.contains_key(OBJECT_CLASS_NAME) {
+      return hierarchy;
+    }
+
+    // Iterative walk, so deep hierarchies cannot overflow the stack
+    let mut stack: Vec<usize> = vec![hierarchy.add(OBJECT_CLASS_NAME, None)];
+    while let Some(class) = stack.pop() {
+      let Some(node) = class_map.get(&hierarchy.names[class]) else {
+        continue;
+      };
+      for child in &node.children {
+        if class_map.contains_key(child.as_ref()) && !hierarchy.index.contains_key(child.as_ref()) {
+          stack.push(hierarchy.add(child, Some(class)));
+        }
+      }
+    }
+
+    hierarchy.number();
+    hierarchy.fill_ancestor_table();
+    hierarchy
+  }
+
+  fn add(&mut self, name: &str, parent: Option<usize>) -> usize {
+    let class = self.names.len();
+    self.names.push(name.to_string());
+    self.index.insert(name.to_string(), class);
+    self.depth.push(parent.map_or(0, |p| self.depth[p] + 1));
+    self.up[0].push(parent.unwrap_or(class));
+    self.enter.push(0);
+    self.exit.push(0);
+    class
+  }
+
+  fn number(&mut self) {
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.names.len()];
+    for class in 1..self.names.len() {
+      children[self.up[0][class]].push(class);
+    }
+
+    let mut counter = 0;
+    let mut stack: Vec<(usize, bool)> = vec![(0, false)];
+    while let Some((class, done)) = stack.pop() {
+      if done {
+        self.exit[class] = counter - 1; // all descendants are numbered
+        continue;
+      }
+      self.enter[class] = counter;
+      counter += 1;
+      stack.push((class, true));
+      stack.extend(children[class].iter().rev().map(|&child| (child, false)));
+    }
+  }
+
+  fn fill_ancestor_table(&mut self) {
+    let max_depth = self.depth.iter().copied().max().unwrap_or(0);
+    let mut step = 1;
+    while step <= max_depth {
+      let prev = self.up.last().expect("ancestor table has at least one level");
+      let next: Vec<usize> = prev.iter().map(|&ancestor| prev[ancestor]).collect();
+      self.up.push(next);
+      step *= 2;
+    }
+  }
+
+  /// Returns `true` if the class is part of the hierarchy
+  pub fn contains(&self, class_name: &str) -> bool {
+    self.index.contains_key(class_name)
+  }
+
+  /// Returns the number of inheritance links between the class and `Object`
+  pub fn depth(&self, class_name: &str) -> Option<usize> {
+    self.index.get(class_name).map(|&class| self.depth[class])
+  }
+
+  /// Returns the class and all its ancestors, starting with the class itself and ending with `Object`
+  pub fn ancestors(&self, class_name: &str) -> Vec<String> {
+    let Some(&(mut class)) = self.index.get(class_name) else {
+      return Vec::new();
+    };
+
+    let mut ancestors = vec![self.names[class].clone()];
+    while self.depth[class] > 0 {
+      class = self.up[0][class];
+      ancestors.push(self.names[class].clone());
+    }
+
+    ancestors
+  }
+
+  /// Returns `true` if `sub_type` is `super_type` or one of its descendants
+  pub fn conforms(&self, sub_type: &str, super_type: &str) -> bool {
+    match (self.index.get(sub_type), self.index.get(super_type)) {
+      (Some(&sub), Some(&sup)) => self.enter[sup] <= self.enter[sub] && self.enter[sub] <= self.exit[sup],
+      _ => false,
+    }
+  }
+
+  /// Returns the least upper bound of both classes, i.e. their closest common ancestor
+  pub fn lub(&self, a: &str, b: &str) -> Option<String> {
+    let (mut a, mut b) = (*self.index.get(a)?, *self.index.get(b)?);
+
+    if self.depth[a] < self.depth[b] {
+      std::mem::swap(&mut a, &mut b);
+    }
+    let mut diff = self.depth[a] - self.depth[b];
+    let mut level = 0;
+    while diff > 0 {
+      if diff & 1 == 1 {
+        a = self.up[level][a];
+      }
+      diff >>= 1;
+      level += 1;
+    }
+
+    if a != b {
+      for level in (0..self.up.len()).rev() {
+        if self.up[level][a] != self.up[level][b] {
+          a = self.up[level][a];
+          b = self.up[level][b];
+        }
+      }
+      a = self.up[0][a];
+    }
+
+    Some(self.names[a].clone())
+  }
+
+  /// Same as `ancestors`, with `SELF_TYPE` standing for `SELF_TYPE` of `current_class`.
+  /// `SELF_TYPE` itself comes first, followed by the ancestors of `current_class`.
+  pub fn ancestors_in_class(&self, class_name: &str, current_class: &str) -> Vec<String> {
+    if class_name != KEYWORD_SELF_TYPE {
+      return self.ancestors(class_name);
+    }
+
+    let mut ancestors = vec![KEYWORD_SELF_TYPE.to_string()];
+    ancestors.extend(self.ancestors(current_class));
+    ancestors
+  }
+
+  /// Same as `conforms`, with `SELF_TYPE` standing for `SELF_TYPE` of `current_class`:
+  /// - `SELF_TYPE <= SELF_TYPE`
+  /// - `SELF_TYPE <= T` if `current_class <= T`
+  /// - `T <= SELF_TYPE` never holds for a class `T`, as `SELF_TYPE` may be any subclass of `current_class`
+  pub fn conforms_in_class(&self, sub_type: &str, super_type: &str, current_class: &str) -> bool {
+    match (sub_type == KEYWORD_SELF_TYPE, super_type == KEYWORD_SELF_TYPE) {
+      (true, true) => true,
+      (true, false) => self.conforms(current_class, super_type),
+      (false, true) => false,
+      (false, false) => self.conforms(sub_type, super_type),
+    }
+  }
+
+  /// Same as `lub`, with `SELF_TYPE` standing for `SELF_TYPE` of `current_class`.
+  /// The bound of `SELF_TYPE` with itself is `SELF_TYPE`, otherwise `SELF_TYPE` is replaced by `current_class`.
+  pub fn lub_in_class(&self, a: &str, b: &str, current_class: &str) -> Option<String> {
+    let resolve = |type_name: &str| if type_name == KEYWORD_SELF_TYPE { current_class.to_string() } else { type_name.to_string() };
+
+    if a == KEYWORD_SELF_TYPE && b == KEYWORD_SELF_TYPE {
+      return self.contains(current_class).then(|| KEYWORD_SELF_TYPE.to_string());
+    }
+    self.lub(&resolve(a), &resolve(b))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gen::gen_class_map;
+  use crate::models::class::ClassNode;
+  use crate::models::hierarchy::ClassHierarchy;
+  use lexer::model::constants::KEYWORD_SELF_TYPE;
+  use parser::get_ast_from_file_path;
+  use parser::model::class::OBJECT_CLASS_NAME;
+  use std::borrow::Cow;
+  use std::collections::HashMap;
+
+  #[test]
+  fn test_hierarchy_queries() {
+    let program = get_ast_from_file_path("../test_resources/programs/io.cl").expect("Couldn't parse file");
+    let class_map = gen_class_map(&program).expect("Couldn't generate graph");
+    let hierarchy = ClassHierarchy::from(&class_map);
+
+    assert_eq!(hierarchy.ancestors("D"), vec!["D", "C", "IO", "Object"]);
+    assert_eq!(hierarchy.depth("D"), Some(3));
+
+    assert!(hierarchy.conforms("D", "IO"));
+    assert!(hierarchy.conforms("B", "B"));
+    assert!(!hierarchy.conforms("IO", "D"));
+    assert!(!hierarchy.conforms("B", "C"));
+    assert!(!hierarchy.conforms("Undefined", "Object"));
+
+    assert_eq!(hierarchy.lub("D", "Main").as_deref(), Some("IO"));
+    assert_eq!(hierarchy.lub("D", "C").as_deref(), Some("C"));
+    assert_eq!(hierarchy.lub("B", "Int").as_deref(), Some("Object"));
+    assert_eq!(hierarchy.lub("B", "Undefined"), None);
+
+    assert!(hierarchy.conforms_in_class(KEYWORD_SELF_TYPE, "IO", "D"));
+    assert!(hierarchy.conforms_in_class(KEYWORD_SELF_TYPE, KEYWORD_SELF_TYPE, "D"));
+    assert!(!hierarchy.conforms_in_class("D", KEYWORD_SELF_TYPE, "D"));
+    assert_eq!(hierarchy.lub_in_class(KEYWORD_SELF_TYPE, KEYWORD_SELF_TYPE, "D").as_deref(), Some(KEYWORD_SELF_TYPE));
+    assert_eq!(hierarchy.lub_in_class(KEYWORD_SELF_TYPE, "Main", "D").as_deref(), Some("IO"));
+    assert_eq!(hierarchy.ancestors_in_class(KEYWORD_SELF_TYPE, "C"), vec![KEYWORD_SELF_TYPE, "C", "IO", "Object"]);
+  }
+
+  #[test]
+  fn test_deep_hierarchy() {
+    // `Object` <- `C0` <- `C1` <- ... <- `C9999`, with a second branch `Object` <- `D`
+    const DEPTH: usize = 10_000;
+    let mut class_map: HashMap<String, ClassNode> = HashMap::new();
+    let mut object = ClassNode::get_base_object();
+    object.add_child(Cow::from("C0"));
+    object.add_child(Cow::from("D"));
+    class_map.insert(OBJECT_CLASS_NAME.to_string(), object);
+    class_map.insert("D".to_string(), gen_node("D".to_string(), OBJECT_CLASS_NAME.to_string(), None));
+    for i in 0..DEPTH {
+      let parent = if i == 0 { OBJECT_CLASS_NAME.to_string() } else { format!("C{}", i - 1) };
+      let child = (i + 1 < DEPTH).then(|| format!("C{}", i + 1));
+      class_map.insert(format!("C{i}"), gen_node(format!("C{i}"), parent, child));
+    }
+
+    let hierarchy = ClassHierarchy::new(&class_map);
+    assert_eq!(hierarchy.depth("C9999"), Some(DEPTH));
+    assert!(hierarchy.conforms("C9999", "C0"));
+    assert!(!hierarchy.conforms("C0", "C9999"));
+    assert_eq!(hierarchy.lub("C9999", "C1234").as_deref(), Some("C1234"));
+    assert_eq!(hierarchy.lub("C9999", "D").as_deref(), Some(OBJECT_CLASS_NAME));
+    assert_eq!(hierarchy.ancestors("C9999").len(), DEPTH + 1);
+  }
+
+  fn gen_node(name: String, parent: String, child: Option<String>) -> ClassNode {
+    let children = child.into_iter().map(Cow::from).collect();
+    ClassNode { name: Cow::from(name), parent: Cow::from(parent), children, features: Vec::new(), line_num: 0, line_pos: 0 }
+  }
+}
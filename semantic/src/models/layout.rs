@@ -0,0 +1,51 @@
+use std::fmt::{Display, Formatter};
+
+/// An attribute slot in the object layout of a class
+#[derive(PartialEq, Debug, Clone)]
+pub struct AttributeSlot {
+  pub name: String,
+  pub attr_type: String,
+  pub class_name: String, // class that declares the attribute
+}
+
+/// A method slot in the dispatch table of a class
+#[derive(PartialEq, Debug, Clone)]
+pub struct MethodSlot {
+  pub name: String,
+  pub class_name: String, // class whose implementation is dispatched to
+}
+
+/// Object layout and dispatch table of a class.
+/// Slots of the parent class come first and keep their index in all subclasses, so a slot index is valid for
+/// every object that conforms to the class.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ClassLayout {
+  pub class_name: String,
+  pub tag: usize, // pre-order index of the class in the inheritance tree
+  pub max_descendant_tag: usize, // a class `c` conforms to this class iff `tag <= c.tag <= max_descendant_tag`
+  pub attributes: Vec<AttributeSlot>,
+  pub dispatch_table: Vec<MethodSlot>,
+}
+
+impl ClassLayout {
+  pub fn attribute_index(&self, name: &str) -> Option<usize> {
+    self.attributes.iter().position(|slot| slot.name == name)
+  }
+
+  pub fn method_index(&self, name: &str) -> Option<usize> {
+    self.dispatch_table.iter().position(|slot| slot.name == name)
+  }
+}
+
+impl Display for ClassLayout {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "class {} [tag {}..={}]", self.class_name, self.tag, self.max_descendant_tag)?;
+    for (index, slot) in self.attributes.iter().enumerate() {
+      writeln!(f, "  attr {index}: {} : {} ({})", slot.name, slot.attr_type, slot.class_name)?;
+    }
+    for (index, slot) in self.dispatch_table.iter().enumerate() {
+      writeln!(f, "  method {index}: {}.{}", slot.class_name, slot.name)?;
+    }
+    Ok(())
+  }
+}
@@ -1,6 +1,9 @@
 ﻿use std::fmt::{Debug, Display};
 
 pub mod class;
+pub mod diagnostic;
+pub mod hierarchy;
+pub mod layout;
 pub mod symbols;
 pub mod features;
 pub mod expression;
@@ -1,10 +1,30 @@
-﻿use crate::models::class::ClassNode;
+﻿use crate::environments::Environment;
+use crate::gen::entry::EntryPoint;
+use crate::gen::type_check::TypeTable;
+use crate::models::class::ClassNode;
+use crate::models::diagnostic::Diagnostic;
+use crate::models::layout::ClassLayout;
 use crate::models::Node;
+use parser::model::program::ParseProgram;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq)]
 pub struct ProgramNode {
   pub(crate) classes: Vec<ClassNode>,
+  pub warnings: Vec<Diagnostic>,
+}
+
+/// A program that passed all semantic checks, along with everything needed to generate code for it.
+/// The type table is keyed by the expressions of `program`, which is borrowed so it cannot move.
+#[derive(Debug)]
+pub struct CheckedProgram<'a> {
+  pub program: &'a ParseProgram,
+  pub env: Environment,
+  pub types: TypeTable,
+  pub layouts: HashMap<String, ClassLayout>,
+  pub entry: EntryPoint,
+  pub warnings: Vec<Diagnostic>,
 }
 
 impl Display for ProgramNode {
@@ -12,7 +32,7 @@ impl Display for ProgramNode {
     let mut str_class = String::new();
     for class in &self.classes {
       str_class.push_str(format!("{class}").as_str());  
-      str_class.push_str("\n");
+      str_class.push('\n');
     }
     
     write!(f, "{}", str_class.trim())
@@ -1,5 +1,3 @@
-use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME, VOID_CLASS_NAME};
-use parser::model::expressions::Expression;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
@@ -7,6 +5,7 @@ use std::fmt::{Display, Formatter};
 pub enum EnvType {
   Program,
   Class,
+  Method,
   Let,
   If,
   Then,
@@ -21,6 +20,7 @@ impl Display for EnvType {
     match self {
       EnvType::Program => write!(f, "Env: PROGRAM"),
       EnvType::Class => write!(f, "Env: CLASS"),
+      EnvType::Method => write!(f, "Env: METHOD"),
       EnvType::Let => write!(f, "Env: LET"),
       EnvType::If => write!(f, "Env: IF"),
       EnvType::Then => write!(f, "Env: THEN"),
@@ -34,31 +34,14 @@ impl Display for EnvType {
 
 pub type SymbolType = String;
 
-// pub enum SymbolType {
-//   Ident{ident_type: String},
-//   Type,
-// }
-
-// impl Display for SymbolType {
-//   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-//     match self {
-//       SymbolType::Ident {ident_type} => write!(f, "[[ IDENT : {ident_type}]]"),
-//       SymbolType::Type => write!(f, "[[ TYPE ]]"),
-//     }
-//   }
-// }
-
+/// An object identifier bound in some environment, e.g. an attribute, a formal, or a `let`/`case` binding
 #[derive(PartialEq, Debug, Clone)]
 pub struct Symbol {
   pub name: String,
-  pub env_type: EnvType,  // name of the enclosing environment
-  pub sym_type: SymbolType, // type of Symbol (Ident/Type)
-  pub ret_type: SymbolType, // type of Symbol (Ident/Type)
-}
-
-#[derive(PartialEq, Debug, Clone)]
-pub struct SymbolTable {
-  symbols: Vec<HashMap<String, Symbol>>,
+  pub env_type: EnvType,    // kind of the environment that binds the symbol
+  pub sym_type: SymbolType, // declared type of the symbol
+  pub line_num: u32,
+  pub line_pos: u32,
 }
 
 impl Display for Symbol {
@@ -67,7 +50,15 @@ impl Display for Symbol {
   }
 }
 
+/// Scoped environment of object identifiers. Inner scopes shadow the outer ones.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SymbolTable {
+  symbols: Vec<HashMap<String, Symbol>>,
+}
+
 impl SymbolTable {
+  pub fn new() -> Self { SymbolTable { symbols: vec![] } }
+
   pub fn enter_scope(&mut self) {
     self.symbols.push(HashMap::new());
   }
@@ -80,111 +71,38 @@ impl SymbolTable {
     self.symbols.last()
   }
 
-  pub fn put(&mut self, symbol: Symbol) {
+  /// Number of open scopes
+  pub fn depth(&self) -> usize {
+    self.symbols.len()
+  }
+
+  /// Binds the symbol in the innermost scope, returning the symbol it replaces in that scope, if any
+  pub fn put(&mut self, symbol: Symbol) -> Option<Symbol> {
     if self.symbols.is_empty() {
       self.symbols.push(HashMap::new());
     }
-    let mut scope = self.symbols.last_mut().unwrap();
+    let scope = self.symbols.last_mut().unwrap();
 
-    scope.insert(symbol.name.clone(), symbol);
+    scope.insert(symbol.name.clone(), symbol)
   }
 
-  pub fn lookup_symbol(&mut self, name: &str) -> Option<Symbol> { todo!() }
+  /// Returns the innermost binding of `name`
+  pub fn lookup_symbol(&self, name: &str) -> Option<&Symbol> {
+    self.lookup_symbol_with_depth(name).map(|(_, symbol)| symbol)
+  }
 
-  pub fn lookup_symbol_by_env_type(&mut self, name: &str, env_type: EnvType) -> bool {
-    for i in (0..self.symbols.len()).rev() {
-      match self.symbols[i].get(name) {
-        Some(symbol) if symbol.env_type == env_type => return true,
-        _ => {}
-      }
-    }
-    false
+  /// Returns the innermost binding of `name`, along with the index of the scope that binds it; the outermost scope is 0
+  pub fn lookup_symbol_with_depth(&self, name: &str) -> Option<(usize, &Symbol)> {
+    self.symbols.iter().enumerate().rev().find_map(|(depth, scope)| scope.get(name).map(|symbol| (depth, symbol)))
   }
 
-  pub fn new() -> Self { SymbolTable { symbols: vec![] } }
+  /// Returns the binding of `name` in the innermost scope only
+  pub fn lookup_in_cur_scope(&self, name: &str) -> Option<&Symbol> {
+    self.symbols.last().and_then(|scope| scope.get(name))
+  }
 
-  pub fn get_symbol_type(expr: Expression) -> Result<SymbolType, String> {
-    let str_expr_type = expr.get_type();
-    let expr_type: Result<SymbolType, String> = match expr {
-      Expression::PartialAssign { .. } | Expression::PartialDispatch { .. } | Expression::PartialCastDispatch { .. } | Expression::PartialBinary { .. } => panic!("Cannot generate expression type for partial expression {expr:?}"),
-
-      Expression::Plus { left, right } | Expression::Minus { left, right } | Expression::Multiply { left, right } | Expression::Divide { left, right } | Expression::LessThan { left, right } | Expression::Equal { left, right } | Expression::LessThanOrEqual { left, right } => {
-        let left_expr = Self::get_symbol_type(*left);
-        let right_expr = Self::get_symbol_type(*right);
-
-        if left_expr.is_err() || right_expr.is_err() {
-          let mut err_msg = String::from("Error in getting sub-expression type.");
-          if let Err(e) = left_expr {
-            err_msg.push_str(&format!(" Left sub-expression error: {e}"));
-          }
-          if let Err(e) = right_expr {
-            err_msg.push_str(&format!(" Right sub-expression error: {e}"));
-          }
-          return Err(err_msg);
-        }
-
-        let Ok(left_expr_type) = left_expr;
-        let Ok(right_expr_type) = right_expr;
-
-        if left_expr_type == INT_CLASS_NAME && right_expr_type == INT_CLASS_NAME {
-          match str_expr_type.as_str() {
-            "Plus" | "Minus" | "Multiply" | "Divide" => Ok(String::from(INT_CLASS_NAME)),
-
-            // It's either '<', '=', or '<='
-            _ => Ok(String::from(BOOL_CLASS_NAME)),
-          }
-        } else {
-          Err(format!("For boolean or arithmetic operation, sub-expressions must be of type 'int' but found {left_expr_type} and {right_expr_type}"))
-        }
-      }
-
-      Expression::Negate { expr } => {
-        let negate_sub_expr = Self::get_symbol_type(*expr);
-        match negate_sub_expr {
-          Ok(sub_expr) if sub_expr == INT_CLASS_NAME => Ok(String::from(INT_CLASS_NAME)),
-          Ok(sub_expr) => Err(format!("Negate sub-expression must be of type 'int' but found {sub_expr}")),
-          Err(e) => Err(format!("Error in getting sub-expression type. Error {e}")),
-        }
-      }
-
-      Expression::IsVoid { expr } => {
-        let sub_expr_void = Self::get_symbol_type(*expr);
-        match sub_expr_void {
-          Ok(sub_expr) if sub_expr == VOID_CLASS_NAME => Ok(String::from(BOOL_CLASS_NAME)),
-          Ok(sub_expr) => Err(format!("IsVoid sub-expression must be of type 'void' but found {sub_expr}")),
-          Err(e) => Err(format!("Error in getting sub-expression type. Error {e}")),
-        }
-      }
-
-      Expression::Not { expr } => {
-        let sub_expr_type = Self::get_symbol_type(*expr);
-        match sub_expr_type {
-          Ok(sub_expr) if sub_expr == BOOL_CLASS_NAME => Ok(String::from(BOOL_CLASS_NAME)),
-          Ok(sub_expr) => Err(format!("Not sub-expression must be of type 'bool' but found {sub_expr}")),
-          Err(e) => Err(format!("Error in getting sub-expression type. Error {e}")),
-        }
-      }
-      Expression::IntExpr { .. } => Ok(String::from(INT_CLASS_NAME)),
-      Expression::BoolExpr { .. } => Ok(String::from(BOOL_CLASS_NAME)),
-      Expression::StringExpr { .. } => Ok(String::from(STR_CLASS_NAME)),
-
-      Expression::Block { .. } => Ok(String::from("Block")),
-
-      Expression::Assign { name, expr } => Ok(String::from("Assign")),
-      Expression::Dispatch { .. } => Ok(String::from("Dispatch")),
-      Expression::Conditional { .. } => Ok(String::from("Conditional")),
-      Expression::Loop { .. } => Ok(String::from("Loop")),
-      Expression::Case { .. } => Ok(String::from("Case")),
-      Expression::Let { .. } => Ok(String::from("Let")),
-
-      Expression::New { type_name } => Ok(type_name.get_name()),
-      Expression::IdentExpr { name } => Ok(name.get_name()),
-
-      Expression::SelfTypeExpr { .. } => Ok(String::from("SelfTypeExpr")),
-      Expression::SelfExpr => Ok(String::from("SelfExpr")),
-    };
-
-    expr_type
+  pub fn lookup_symbol_by_env_type(&self, name: &str, env_type: EnvType) -> bool {
+    matches!(self.lookup_symbol(name), Some(symbol) if symbol.env_type == env_type)
   }
 }
 
@@ -194,8 +112,31 @@ impl From<Vec<HashMap<String, Symbol>>> for SymbolTable {
   }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct MethodTable {}
+#[cfg(test)]
+mod test {
+  use crate::models::symbols::{EnvType, Symbol, SymbolTable};
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct ClassTable {}
+  fn gen_symbol(name: &str, env_type: EnvType, sym_type: &str) -> Symbol {
+    Symbol { name: name.to_string(), env_type, sym_type: sym_type.to_string(), line_num: 0, line_pos: 0 }
+  }
+
+  #[test]
+  fn test_shadowing() {
+    let mut table = SymbolTable::new();
+    table.enter_scope();
+    table.put(gen_symbol("x", EnvType::Class, "Int"));
+    table.put(gen_symbol("y", EnvType::Class, "Bool"));
+
+    table.enter_scope();
+    table.put(gen_symbol("x", EnvType::Let, "String"));
+    assert_eq!(table.lookup_symbol("x").unwrap().sym_type, "String");
+    assert_eq!(table.lookup_symbol("y").unwrap().sym_type, "Bool");
+    assert!(table.lookup_in_cur_scope("y").is_none());
+    assert_eq!(table.lookup_symbol_with_depth("y").unwrap().0, 0);
+    assert!(table.lookup_symbol_by_env_type("x", EnvType::Let));
+
+    table.exit_scope();
+    assert_eq!(table.lookup_symbol("x").unwrap().sym_type, "Int");
+    assert!(table.lookup_symbol("z").is_none());
+  }
+}
@@ -0,0 +1,253 @@
+use codegen::target::Target;
+use codegen::toolchain::GcOptions;
+use ir::opt::OptLevel;
+use semantic::gen::entry::EntryPoint;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: cool <command> [options] <file.cl>...
+
+Commands:
+  check    Run the semantic checks and lints on the program
+  ir       Print the intermediate representation of the program
+  run      Run the program with the IR interpreter, a `.coolb` file with the bytecode VM, or a MIPS `.s` file
+           with the MIPS simulator
+  build    Compile the program to a native executable, a WebAssembly module or MIPS assembly for SPIM
+  bytecode Compile the program to a `.coolb` bytecode file
+  disasm   Print the bytecode of the program or of a `.coolb` file
+  repl     Evaluate expressions and define classes interactively, after loading the classes of the files
+  debug    Run the program with the IR interpreter under a debugger, with breakpoints, stepping and inspection
+  profile  Run the program with the IR interpreter, then print the calls and time of its methods and the objects
+           allocated by class
+  fuzz     Parse random programs following the grammar and broken copies of them, reporting those which make the
+           parser panic or which aren't printed as COOL that parses back to them
+  generate Print a random program following the grammar, or one which type checks and terminates with `--typed`
+  difftest Run programs which type check and terminate on every backend, reporting those on which a backend
+           differs from the IR interpreter, minimised
+  reduce   Print the smallest program found, made from the program by removing and simplifying its parts, on which
+           the compiler panics at the same place or reports the same diagnostic
+
+Options:
+  --entry <Class.method>  Entry point of the program, `Main.main` by default
+  --config <cool.toml>    Lint configuration, `cool.toml` in the current directory by default
+  -O0, -O1, -O2           Optimisation level of the IR, `-O0` by default
+  --stats                 Print the number of instructions executed by `run`
+  --vm                    Make `run` compile the program to bytecode and run it with the VM
+  --dap                   Make `debug` speak the Debug Adapter Protocol on stdin and stdout, for editors
+  --lcov <file>           Make `profile` write the line, branch and method coverage in the lcov format
+  --folded <file>         Make `profile` write the time of each call stack in the folded format of flame graphs
+  --target <target>       Machine `build` compiles for, `x86-64`, `llvm`, `wasm` or `mips`, `x86-64` by default.
+                          `run --target mips` compiles the program to MIPS and runs it with the simulator
+  --gc <collector>        Garbage collector of the runtime, `none`, `mark-sweep` or `generational`. x86-64 and llvm
+                          have all three, `generational` by default; mips has `none` and `mark-sweep`, the default;
+                          wasm always uses `mark-sweep`
+  --gc-stress             Collect garbage at every allocation, to find missing roots, on every target but wasm
+  -o <file>               File written by `build` or `bytecode`, named after the first file by default
+  -S                      Write the assembly instead of an executable
+  --seed <n>              Seed of the programs of `fuzz`, `generate` and `difftest`, from the time by default
+  --runs <n>              Number of programs `fuzz` and `difftest` generate, 1000 and 100 by default
+  --typed                 Make `generate` print a program which type checks and terminates";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+  Check(Options),
+  Ir(Options),
+  Run(Options),
+  Build(Options),
+  Bytecode(Options),
+  Disasm(Options),
+  Repl(Options),
+  Debug(Options),
+  Profile(Options),
+  Fuzz(Options),
+  Generate(Options),
+  Difftest(Options),
+  Reduce(Options),
+}
+
+/// Options shared by all commands
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Options {
+  pub files: Vec<PathBuf>,
+  pub entry: EntryPoint,
+  pub config: Option<PathBuf>,
+  pub opt_level: OptLevel,
+  pub stats: bool,
+  pub vm: bool,
+  pub dap: bool,
+  pub lcov: Option<PathBuf>,
+  pub folded: Option<PathBuf>,
+  pub target: Target,
+  pub gc: GcOptions,
+  pub output: Option<PathBuf>,
+  pub emit_asm: bool,
+  pub seed: Option<u64>,
+  pub runs: Option<u64>,
+  pub typed: bool,
+}
+
+/// Parses the command line arguments, without the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+  let mut args = args.into_iter();
+  let command = args.next().ok_or("Missing command")?;
+
+  let mut options = Options::default();
+  while let Some(arg) = args.next() {
+    // `--option=value` is the same as `--option value`
+    let (arg, mut inline_value) = match arg.split_once('=') {
+      Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+      _ => (arg, None),
+    };
+    let mut value = |option: &str| inline_value.take().or_else(|| args.next()).ok_or_else(|| format!("Missing value for {option}"));
+    match arg.as_str() {
+      "--entry" => options.entry = value("--entry")?.parse()?,
+      "--config" => options.config = Some(PathBuf::from(value("--config")?)),
+      "--stats" => options.stats = true,
+      "--vm" => options.vm = true,
+      "--dap" => options.dap = true,
+      "--lcov" => options.lcov = Some(PathBuf::from(value("--lcov")?)),
+      "--folded" => options.folded = Some(PathBuf::from(value("--folded")?)),
+      "--target" => options.target = value("--target")?.parse()?,
+      "--gc" => options.gc.mode = Some(value("--gc")?.parse()?),
+      "--gc-stress" => options.gc.stress = true,
+      "-o" => options.output = Some(PathBuf::from(value("-o")?)),
+      "-S" => options.emit_asm = true,
+      "--seed" => options.seed = Some(parse_number("--seed", &value("--seed")?)?),
+      "--runs" => options.runs = Some(parse_number("--runs", &value("--runs")?)?),
+      "--typed" => options.typed = true,
+      option if option.starts_with("-O") => options.opt_level = option["-O".len()..].parse()?,
+      option if option.starts_with("--") => return Err(format!("Unknown option {option}")),
+      file => options.files.push(PathBuf::from(file)),
+    }
+    if inline_value.is_some() {
+      return Err(format!("Option {arg} takes no value"));
+    }
+  }
+
+  if options.files.is_empty() && !["repl", "fuzz", "generate", "difftest"].contains(&command.as_str()) {
+    return Err("No input files".to_string());
+  }
+
+  match command.as_str() {
+    "check" => Ok(Command::Check(options)),
+    "ir" => Ok(Command::Ir(options)),
+    "run" => Ok(Command::Run(options)),
+    "build" => Ok(Command::Build(options)),
+    "bytecode" => Ok(Command::Bytecode(options)),
+    "disasm" => Ok(Command::Disasm(options)),
+    "repl" => Ok(Command::Repl(options)),
+    "debug" => Ok(Command::Debug(options)),
+    "profile" => Ok(Command::Profile(options)),
+    "fuzz" => Ok(Command::Fuzz(options)),
+    "generate" => Ok(Command::Generate(options)),
+    "difftest" => Ok(Command::Difftest(options)),
+    "reduce" => Ok(Command::Reduce(options)),
+    _ => Err(format!("Unknown command {command}")),
+  }
+}
+
+fn parse_number(option: &str, value: &str) -> Result<u64, String> {
+  value.parse().map_err(|_| format!("Invalid value {value} for {option}, expected a number"))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::cli::{parse_args, Command, Options};
+  use codegen::target::Target;
+  use codegen::toolchain::{GcMode, GcOptions};
+  use ir::opt::OptLevel;
+  use semantic::gen::entry::EntryPoint;
+  use std::path::PathBuf;
+
+  fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+  }
+
+  #[test]
+  fn test_parse_args() {
+    let command = parse_args(args("check list.cl --entry ListTest.run atoi.cl")).expect("Arguments must parse");
+    let entry = EntryPoint { class_name: "ListTest".to_string(), method_name: "run".to_string() };
+    let files = vec![PathBuf::from("list.cl"), PathBuf::from("atoi.cl")];
+    assert_eq!(command, Command::Check(Options { files, entry, ..Options::default() }));
+
+    let command = parse_args(args("ir --config lints.toml -O2 a.cl")).expect("Arguments must parse");
+    let Command::Ir(options) = command else {
+      panic!("Expected the ir command, found {command:?}");
+    };
+    assert_eq!(options.entry, EntryPoint::default());
+    assert_eq!(options.config, Some(PathBuf::from("lints.toml")));
+    assert_eq!(options.opt_level, OptLevel::O2);
+
+    let command = parse_args(args("run --stats a.cl")).expect("Arguments must parse");
+    assert!(matches!(command, Command::Run(Options { stats: true, vm: false, .. })));
+
+    let command = parse_args(args("run --vm a.cl")).expect("Arguments must parse");
+    assert!(matches!(command, Command::Run(Options { vm: true, .. })));
+
+    let command = parse_args(args("run --target mips a.cl")).expect("Arguments must parse");
+    assert!(matches!(command, Command::Run(Options { target: Target::Mips, vm: false, .. })));
+
+    let command = parse_args(args("bytecode -o a.coolb a.cl")).expect("Arguments must parse");
+    assert_eq!(command, Command::Bytecode(Options { files: vec![PathBuf::from("a.cl")], output: Some(PathBuf::from("a.coolb")), ..Options::default() }));
+
+    let command = parse_args(args("disasm a.coolb")).expect("Arguments must parse");
+    assert_eq!(command, Command::Disasm(Options { files: vec![PathBuf::from("a.coolb")], ..Options::default() }));
+
+    let command = parse_args(args("repl")).expect("Arguments must parse");
+    assert_eq!(command, Command::Repl(Options::default()));
+
+    let command = parse_args(args("debug --dap a.cl")).expect("Arguments must parse");
+    assert_eq!(command, Command::Debug(Options { files: vec![PathBuf::from("a.cl")], dap: true, ..Options::default() }));
+
+    let command = parse_args(args("profile --lcov cov.info --folded=stacks.txt a.cl")).expect("Arguments must parse");
+    let (lcov, folded) = (Some(PathBuf::from("cov.info")), Some(PathBuf::from("stacks.txt")));
+    assert_eq!(command, Command::Profile(Options { files: vec![PathBuf::from("a.cl")], lcov, folded, ..Options::default() }));
+
+    let command = parse_args(args("build -O1 --target x86-64 -o hello -S a.cl")).expect("Arguments must parse");
+    let Command::Build(options) = command else {
+      panic!("Expected the build command, found {command:?}");
+    };
+    assert_eq!((options.target, options.output, options.emit_asm), (Target::X86_64, Some(PathBuf::from("hello")), true));
+
+    let command = parse_args(args("build --target wasm a.cl")).expect("Arguments must parse");
+    assert!(matches!(command, Command::Build(Options { target: Target::Wasm, emit_asm: false, .. })));
+
+    let command = parse_args(args("build --gc=mark-sweep --gc-stress a.cl")).expect("Arguments must parse");
+    let gc = GcOptions { mode: Some(GcMode::MarkSweep), stress: true };
+    assert_eq!(command, Command::Build(Options { files: vec![PathBuf::from("a.cl")], gc, ..Options::default() }));
+
+    let command = parse_args(args("build --gc none --target=llvm a.cl")).expect("Arguments must parse");
+    assert!(matches!(command, Command::Build(Options { target: Target::Llvm, gc: GcOptions { mode: Some(GcMode::None), stress: false }, .. })));
+
+    let command = parse_args(args("fuzz --seed 42 --runs=10")).expect("Arguments must parse");
+    assert_eq!(command, Command::Fuzz(Options { seed: Some(42), runs: Some(10), ..Options::default() }));
+
+    let command = parse_args(args("generate")).expect("Arguments must parse");
+    assert_eq!(command, Command::Generate(Options::default()));
+
+    let command = parse_args(args("generate --typed --seed=3")).expect("Arguments must parse");
+    assert_eq!(command, Command::Generate(Options { seed: Some(3), typed: true, ..Options::default() }));
+
+    let command = parse_args(args("difftest --runs 20")).expect("Arguments must parse");
+    assert_eq!(command, Command::Difftest(Options { runs: Some(20), ..Options::default() }));
+
+    let command = parse_args(args("reduce -O2 crash.cl")).expect("Arguments must parse");
+    assert_eq!(command, Command::Reduce(Options { files: vec![PathBuf::from("crash.cl")], opt_level: OptLevel::O2, ..Options::default() }));
+  }
+
+  #[test]
+  fn test_invalid_args() {
+    assert_eq!(parse_args(args("")).unwrap_err(), "Missing command");
+    assert_eq!(parse_args(args("check")).unwrap_err(), "No input files");
+    assert_eq!(parse_args(args("check a.cl --entry")).unwrap_err(), "Missing value for --entry");
+    assert_eq!(parse_args(args("check a.cl --entry main")).unwrap_err(), "Invalid entry point `main`, expected `Class.method`");
+    assert_eq!(parse_args(args("check a.cl --verbose")).unwrap_err(), "Unknown option --verbose");
+    assert_eq!(parse_args(args("ir a.cl -O3")).unwrap_err(), "Unknown optimisation level 3, expected 0, 1 or 2");
+    assert_eq!(parse_args(args("build a.cl --target arm")).unwrap_err(), "Unknown target arm, expected x86-64, llvm, wasm or mips");
+    assert_eq!(parse_args(args("build a.cl --gc=copying")).unwrap_err(), "Unknown garbage collector copying, expected none, mark-sweep or generational");
+    assert_eq!(parse_args(args("build a.cl --gc-stress=yes")).unwrap_err(), "Option --gc-stress takes no value");
+    assert_eq!(parse_args(args("reduce")).unwrap_err(), "No input files");
+    assert_eq!(parse_args(args("fuzz --runs many")).unwrap_err(), "Invalid value many for --runs, expected a number");
+    assert_eq!(parse_args(args("compile a.cl")).unwrap_err(), "Unknown command compile");
+  }
+}
@@ -0,0 +1,272 @@
+use crate::debug::{Debuggee, Debugger, Step, Stop};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::{BufRead, Read, Write};
+
+const HELP: &str = "\
+Commands:
+  break <file:line | Class.method> [if <condition>]  Stop there, when the COOL condition is true if there is one
+  delete <n>                                         Remove breakpoint n
+  breakpoints                                        List the breakpoints
+  run, continue                                      Run until a breakpoint or the end of the program
+  step                                               Run to the next line, entering the methods called
+  next                                               Run to the next line of this method
+  finish                                             Run until this method returns
+  backtrace                                          Show the call stack with the class of each receiver
+  frame <n>                                          Select frame n of the call stack
+  locals                                             Show the variables of the selected frame
+  self                                               Show the attributes of self in the selected frame
+  print <expression>                                 Evaluate a COOL expression in the selected frame
+  quit                                               Leave the debugger
+Short forms: b for break, d, r, c, s, n, f for finish, bt, p, q and h for help.";
+
+/// Input of the program, read from the input of the console one line at a time so the lines after it stay there
+struct ProgramInput<'c, 'i> {
+  input: &'c RefCell<&'i mut dyn BufRead>,
+  line: Vec<u8>,
+  consumed: usize,
+}
+
+impl Read for ProgramInput<'_, '_> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let available = self.fill_buf()?;
+    let count = available.len().min(buf.len());
+    buf[..count].copy_from_slice(&available[..count]);
+    self.consume(count);
+    Ok(count)
+  }
+}
+
+impl BufRead for ProgramInput<'_, '_> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    if self.consumed == self.line.len() {
+      self.line.clear();
+      self.consumed = 0;
+      self.input.borrow_mut().read_until(b'\n', &mut self.line)?;
+    }
+    Ok(&self.line[self.consumed..])
+  }
+
+  fn consume(&mut self, amount: usize) {
+    self.consumed += amount;
+  }
+}
+
+/// Output of the program, written to the output of the console
+struct ProgramOutput<'c, 'o> {
+  output: &'c RefCell<&'o mut dyn Write>,
+  line_open: &'c Cell<bool>, // the program wrote part of a line
+}
+
+impl Write for ProgramOutput<'_, '_> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if let Some(last) = buf.last() {
+      self.line_open.set(*last != b'\n');
+    }
+    self.output.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.output.borrow_mut().flush()
+  }
+}
+
+/// Runs the debugger with commands read from the input, which the program reads too
+pub fn run(debuggee: &Debuggee, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+  let input = RefCell::new(input);
+  let output = RefCell::new(output);
+  let line_open = Cell::new(false);
+  let mut program_input = ProgramInput { input: &input, line: Vec::new(), consumed: 0 };
+  let mut program_output = ProgramOutput { output: &output, line_open: &line_open };
+  let mut console = Console { debugger: Debugger::new(debuggee, &mut program_input, &mut program_output), frame: 0 };
+
+  writeln!(output.borrow_mut(), "COOL debugger, help for the commands")?;
+  loop {
+    write!(output.borrow_mut(), "(cool) ")?;
+    output.borrow_mut().flush()?;
+    let mut line = String::new();
+    if input.borrow_mut().read_line(&mut line)? == 0 {
+      writeln!(output.borrow_mut())?;
+      return Ok(());
+    }
+
+    let (command, argument) = line.trim().split_once(' ').map_or((line.trim(), ""), |(command, argument)| (command, argument.trim()));
+    let reply = match command {
+      "" => continue,
+      "quit" | "q" => return Ok(()),
+      command => console.command(command, argument),
+    };
+    if line_open.replace(false) {
+      writeln!(output.borrow_mut())?;
+    }
+    let reply = reply.unwrap_or_else(|e| e);
+    if !reply.is_empty() {
+      writeln!(output.borrow_mut(), "{reply}")?;
+    }
+  }
+}
+
+struct Console<'a> {
+  debugger: Debugger<'a>,
+  frame: usize, // selected frame, 0 for the innermost one
+}
+
+impl Console<'_> {
+  /// Runs a command, returning what to print
+  fn command(&mut self, command: &str, argument: &str) -> Result<String, String> {
+    match command {
+      "help" | "h" => Ok(HELP.to_string()),
+      "break" | "b" => {
+        let (spec, condition) = match argument.split_once(" if ") {
+          Some((spec, condition)) => (spec.trim(), Some(condition.trim().to_string())),
+          None => (argument, None),
+        };
+        let breakpoint = self.debugger.add_breakpoint(spec, condition)?.clone();
+        Ok(format!("Breakpoint {} at {}", breakpoint.id, self.debugger.location_text(&breakpoint.location)))
+      }
+      "delete" | "d" => {
+        let id: usize = argument.parse().map_err(|_| format!("Invalid breakpoint number {argument}"))?;
+        if !self.debugger.breakpoints().iter().any(|breakpoint| breakpoint.id == id) {
+          return Err(format!("No breakpoint {id}"));
+        }
+        self.debugger.retain_breakpoints(|breakpoint| breakpoint.id != id);
+        Ok(String::new())
+      }
+      "breakpoints" => {
+        let lines: Vec<String> = self.debugger.breakpoints().iter()
+            .map(|breakpoint| {
+              let condition = breakpoint.condition.as_ref().map_or(String::new(), |condition| format!(" if {condition}"));
+              format!("{}  {}{condition}", breakpoint.id, self.debugger.location_text(&breakpoint.location))
+            })
+            .collect();
+        Ok(if lines.is_empty() { "No breakpoints".to_string() } else { lines.join("\n") })
+      }
+      "run" | "r" if self.debugger.is_running() => Err("The program is running, continue it with continue".to_string()),
+      "run" | "r" | "continue" | "c" => self.resume(Step::Continue),
+      "step" | "s" => self.resume(Step::In),
+      "next" | "n" => self.resume(Step::Over),
+      "finish" | "f" => self.resume(Step::Out),
+      "backtrace" | "bt" => {
+        let lines: Vec<String> = (0..self.debugger.stack().len()).map(|index| self.frame_text(index)).collect();
+        if lines.is_empty() {
+          return Err(not_running());
+        }
+        Ok(lines.join("\n"))
+      }
+      "frame" => {
+        let index: usize = argument.parse().map_err(|_| format!("Invalid frame number {argument}"))?;
+        if index >= self.debugger.stack().len() {
+          return Err(format!("No frame {index}"));
+        }
+        self.frame = index;
+        Ok(self.frame_text(index) + &self.source_text(index))
+      }
+      "locals" => {
+        let variables = self.debugger.variables(self.frame).map_err(|_| not_running())?;
+        let lines: Vec<String> = variables.iter().map(|(name, value)| format!("{name} = {}", self.debugger.value_text(value))).collect();
+        Ok(if lines.is_empty() { "No variables".to_string() } else { lines.join("\n") })
+      }
+      "self" => {
+        let receiver = self.debugger.receiver(self.frame).map_err(|_| not_running())?;
+        let mut lines = vec![format!("self : {}", self.debugger.class_name(&receiver))];
+        for (name, value) in self.debugger.attributes(&receiver) {
+          lines.push(format!("  {name} = {}", self.debugger.value_text(&value)));
+        }
+        Ok(lines.join("\n"))
+      }
+      "print" | "p" => {
+        if !self.debugger.is_running() {
+          return Err(not_running());
+        }
+        let value = self.debugger.evaluate(self.frame, argument)?;
+        Ok(format!("{} : {}", self.debugger.value_text(&value), self.debugger.class_name(&value)))
+      }
+      _ => Err(format!("Unknown command {command}, help lists the commands")),
+    }
+  }
+
+  fn resume(&mut self, step: Step) -> Result<String, String> {
+    if !self.debugger.is_running() && step != Step::Continue && step != Step::In {
+      return Err(not_running());
+    }
+    let stop = self.debugger.resume(step);
+    self.frame = 0;
+    let reason = match stop {
+      Stop::Step => String::new(),
+      Stop::Breakpoint(id) => format!("Breakpoint {id}, "),
+      Stop::ConditionError { breakpoint, message } => format!("Error in the condition of breakpoint {breakpoint}: {message}\n"),
+      Stop::Error(error) => format!("Runtime error: {error}\n"),
+      Stop::Exited(Ok(())) => return Ok("The program exited".to_string()),
+      Stop::Exited(Err(error)) => return Ok(format!("The program exited with the error: {error}")),
+    };
+    let stack = self.debugger.stack();
+    let frame = &stack[0];
+    let location = self.debugger.debuggee().location_text(frame.class_name, frame.pos);
+    Ok(format!("{reason}{} at {location}{}", frame.function, self.source_text(0)))
+  }
+
+  /// `#n Class.method (self : Receiver) at file:line:column`
+  fn frame_text(&self, index: usize) -> String {
+    let stack = self.debugger.stack();
+    let frame = &stack[index];
+    let location = self.debugger.debuggee().location_text(frame.class_name, frame.pos);
+    format!("#{index}  {} (self : {}) at {location}", frame.function, frame.receiver_class)
+  }
+
+  /// The line the frame is at, on a line of its own
+  fn source_text(&self, index: usize) -> String {
+    let stack = self.debugger.stack();
+    let frame = &stack[index];
+    let debuggee = self.debugger.debuggee();
+    let text = debuggee.class_file(frame.class_name).and_then(|file| debuggee.source_line(file, frame.pos.0));
+    text.map_or(String::new(), |text| format!("\n{}\t{text}", frame.pos.0))
+  }
+}
+
+fn not_running() -> String {
+  "The program is not running".to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::debug::console::run;
+  use crate::debug::test::counter;
+
+  #[test]
+  fn test_session() {
+    let debuggee = counter();
+    let commands = "break Counter.add if n = 2\nrun\nlocals\nbt\nframe 1\nlocals\nself\nprint total + i * 10\nnext\nfinish\nnext\ndelete 1\nc\nbt\nc\nlocals\n";
+    let mut output = Vec::new();
+    run(&debuggee, &mut commands.as_bytes(), &mut output).expect("Session must run");
+    let output = String::from_utf8(output).expect("Output must be UTF-8");
+    assert_eq!(output, "\
+COOL debugger, help for the commands
+(cool) Breakpoint 1 at Counter.add
+(cool) Breakpoint 1, Counter.add at test_resources/debug/counter.cl:6:16
+6\t      count <- count + n;
+(cool) n = 2
+(cool) #0  Counter.add (self : Loud) at test_resources/debug/counter.cl:6:16
+#1  Main.main (self : Main) at test_resources/debug/counter.cl:21:26
+(cool) #1  Main.main (self : Main) at test_resources/debug/counter.cl:21:26
+21\t        total <- counter.add(i);
+(cool) total = 1
+i = 2
+(cool) self : Main
+  counter = Loud { count = 1 }
+(cool) 21 : Int
+(cool) Counter.add at test_resources/debug/counter.cl:7:7
+7\t      count;
+(cool) Main.main at test_resources/debug/counter.cl:21:26
+21\t        total <- counter.add(i);
+(cool) Main.main at test_resources/debug/counter.cl:22:18
+22\t        i <- i + 1;
+(cool) (cool) 6
+Runtime error: test_resources/debug/counter.cl:26: Division by zero.
+Main.main at test_resources/debug/counter.cl:26:25
+26\t      let zero : Int in total / zero;
+(cool) #0  Main.main (self : Main) at test_resources/debug/counter.cl:26:25
+(cool) The program exited with the error: test_resources/debug/counter.cl:26: Division by zero.
+(cool) The program is not running
+(cool) \n");
+  }
+}
@@ -0,0 +1,388 @@
+use crate::debug::json::Json;
+use crate::debug::{Debuggee, Debugger, Location, Step, Stop};
+use ir::interp::Value;
+use std::cell::RefCell;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// The only thread of a COOL program
+const THREAD_ID: i64 = 1;
+
+/// What a variables reference of the client stands for
+enum Handle {
+  Locals(usize),
+  Object(Value),
+}
+
+/// Output of the program, sent to the client in `output` events
+struct ProgramOutput<'c> {
+  buffer: &'c RefCell<Vec<u8>>,
+}
+
+impl Write for ProgramOutput<'_> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.buffer.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Serves the Debug Adapter Protocol on the input and output. The program is the one of the command line, so the
+/// `launch` request only tells whether to stop on entry. Its input is empty and its output is sent in `output` events.
+pub fn serve(debuggee: &Debuggee, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+  let buffer = RefCell::new(Vec::new());
+  let mut program_input = io::empty();
+  let mut program_output = ProgramOutput { buffer: &buffer };
+  let mut server = Server {
+    debugger: Debugger::new(debuggee, &mut program_input, &mut program_output),
+    output,
+    program_output: &buffer,
+    seq: 0,
+    handles: Vec::new(),
+    stop_on_entry: false,
+  };
+
+  while let Some(message) = read_message(input)? {
+    let command = message.get("command").as_str().unwrap_or_default().to_string();
+    let arguments = message.get("arguments");
+    let request_seq = message.get("seq").as_i64().unwrap_or(0);
+    let result = server.request(&command, arguments);
+    match result {
+      Ok((body, then)) => {
+        server.respond(request_seq, &command, Ok(body))?;
+        match then {
+          Then::Nothing => {}
+          Then::Initialized => server.event("initialized", Json::object(Vec::new()))?,
+          Then::Resume(step, reason) => server.resume(step, reason)?,
+          Then::Quit => return Ok(()),
+        }
+      }
+      Err(message) => server.respond(request_seq, &command, Err(message))?,
+    }
+  }
+  Ok(())
+}
+
+/// Reads a message with its `Content-Length` header, none at the end of the input
+fn read_message(input: &mut dyn BufRead) -> io::Result<Option<Json>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() && length.is_some() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      if name.eq_ignore_ascii_case("Content-Length") {
+        length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+
+  let mut body = vec![0; length.unwrap_or(0)];
+  input.read_exact(&mut body)?;
+  let text = String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  Json::parse(&text).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// What to do once a request is answered
+enum Then {
+  Nothing,
+  Initialized,
+  Resume(Step, &'static str),
+  Quit,
+}
+
+struct Server<'a, 'o> {
+  debugger: Debugger<'a>,
+  output: &'o mut dyn Write,
+  program_output: &'a RefCell<Vec<u8>>,
+  seq: i64,
+  handles: Vec<Handle>, // variables references, from 1, valid until the program runs again
+  stop_on_entry: bool,
+}
+
+impl Server<'_, '_> {
+  fn request(&mut self, command: &str, arguments: &Json) -> Result<(Json, Then), String> {
+    let body = match command {
+      "initialize" => {
+        let capabilities = Json::object(vec![
+          ("supportsConfigurationDoneRequest", true.into()),
+          ("supportsFunctionBreakpoints", true.into()),
+          ("supportsConditionalBreakpoints", true.into()),
+          ("supportsEvaluateForHovers", true.into()),
+        ]);
+        return Ok((capabilities, Then::Initialized));
+      }
+      "launch" => {
+        self.stop_on_entry = arguments.get("stopOnEntry").as_bool().unwrap_or(false);
+        Json::Null
+      }
+      "setBreakpoints" => {
+        let path = arguments.get("source").get("path").as_str().ok_or("Missing source path")?;
+        let file = self.debugger.debuggee().file_index(Path::new(path));
+        self.debugger.retain_breakpoints(|breakpoint| !matches!(breakpoint.location, Location::Line { file: other, .. } if Some(other) == file));
+        let breakpoints: Vec<Json> = arguments.get("breakpoints").elements().iter()
+            .map(|breakpoint| {
+              let line = breakpoint.get("line").as_i64().unwrap_or(0);
+              self.add_breakpoint(&format!("{path}:{line}"), breakpoint.get("condition"))
+            })
+            .collect();
+        Json::object(vec![("breakpoints", breakpoints.into())])
+      }
+      "setFunctionBreakpoints" => {
+        self.debugger.retain_breakpoints(|breakpoint| !matches!(breakpoint.location, Location::Function(_)));
+        let breakpoints: Vec<Json> = arguments.get("breakpoints").elements().iter()
+            .map(|breakpoint| self.add_breakpoint(breakpoint.get("name").as_str().unwrap_or_default(), breakpoint.get("condition")))
+            .collect();
+        Json::object(vec![("breakpoints", breakpoints.into())])
+      }
+      // Runtime errors always stop the program
+      "setExceptionBreakpoints" => Json::object(vec![("breakpoints", Vec::new().into())]),
+      "configurationDone" => {
+        let then = if self.stop_on_entry { Then::Resume(Step::In, "entry") } else { Then::Resume(Step::Continue, "") };
+        return Ok((Json::Null, then));
+      }
+      "threads" => {
+        let thread = Json::object(vec![("id", THREAD_ID.into()), ("name", "main".into())]);
+        Json::object(vec![("threads", vec![thread].into())])
+      }
+      "stackTrace" => {
+        let debuggee = self.debugger.debuggee();
+        let frames: Vec<Json> = self.debugger.stack().iter().enumerate()
+            .map(|(index, frame)| {
+              let mut members = vec![
+                ("id", index.into()),
+                ("name", format!("{} (self : {})", frame.function, frame.receiver_class).into()),
+                ("line", frame.pos.0.into()),
+                ("column", frame.pos.1.into()),
+              ];
+              if let Some(file) = debuggee.class_file(frame.class_name) {
+                let path = debuggee.file(file);
+                let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+                members.push(("source", Json::object(vec![("name", name.into()), ("path", path.display().to_string().into())])));
+              }
+              Json::object(members)
+            })
+            .collect();
+        let total = frames.len();
+        Json::object(vec![("stackFrames", frames.into()), ("totalFrames", total.into())])
+      }
+      "scopes" => {
+        let frame = frame_id(arguments)?;
+        let receiver = self.debugger.receiver(frame)?;
+        let locals = self.handle(Handle::Locals(frame));
+        let attributes = self.handle(Handle::Object(receiver));
+        let scope = |name: &str, reference: usize| Json::object(vec![("name", name.into()), ("variablesReference", reference.into()), ("expensive", false.into())]);
+        Json::object(vec![("scopes", vec![scope("Locals", locals), scope("Self", attributes)].into())])
+      }
+      "variables" => {
+        let reference = arguments.get("variablesReference").as_i64().unwrap_or(0) as usize;
+        let variables = match reference.checked_sub(1).and_then(|index| self.handles.get(index)) {
+          Some(Handle::Locals(frame)) => self.debugger.variables(*frame)?,
+          Some(Handle::Object(value)) => self.debugger.attributes(value),
+          None => return Err(format!("Unknown variables reference {reference}")),
+        };
+        let variables: Vec<Json> = variables.into_iter()
+            .map(|(name, value)| {
+              let mut members = self.value_members(value);
+              members.insert(0, ("name", name.into()));
+              Json::object(members)
+            })
+            .collect();
+        Json::object(vec![("variables", variables.into())])
+      }
+      "evaluate" => {
+        let expression = arguments.get("expression").as_str().ok_or("Missing expression")?;
+        let frame = arguments.get("frameId").as_i64().unwrap_or(0) as usize;
+        if !self.debugger.is_running() {
+          return Err("The program is not running".to_string());
+        }
+        let value = self.debugger.evaluate(frame, expression)?;
+        let mut members = self.value_members(value);
+        members[0].0 = "result";
+        Json::object(members)
+      }
+      "continue" => return Ok((Json::object(vec![("allThreadsContinued", true.into())]), Then::Resume(Step::Continue, ""))),
+      "next" => return Ok((Json::Null, Then::Resume(Step::Over, "step"))),
+      "stepIn" => return Ok((Json::Null, Then::Resume(Step::In, "step"))),
+      "stepOut" => return Ok((Json::Null, Then::Resume(Step::Out, "step"))),
+      "disconnect" | "terminate" => return Ok((Json::Null, Then::Quit)),
+      _ => return Err(format!("Unsupported request {command}")),
+    };
+    Ok((body, Then::Nothing))
+  }
+
+  fn add_breakpoint(&mut self, spec: &str, condition: &Json) -> Json {
+    let condition = condition.as_str().filter(|condition| !condition.trim().is_empty()).map(str::to_string);
+    match self.debugger.add_breakpoint(spec, condition) {
+      Ok(breakpoint) => {
+        let mut members = vec![("id", breakpoint.id.into()), ("verified", true.into())];
+        if let Location::Line { line, .. } = breakpoint.location {
+          members.push(("line", line.into()));
+        }
+        Json::object(members)
+      }
+      Err(message) => Json::object(vec![("verified", false.into()), ("message", message.into())]),
+    }
+  }
+
+  fn handle(&mut self, handle: Handle) -> usize {
+    self.handles.push(handle);
+    self.handles.len()
+  }
+
+  /// `value`, `type` and `variablesReference` of a value, which can be expanded if it has attributes
+  fn value_members(&mut self, value: Value) -> Vec<(&'static str, Json)> {
+    let text = self.debugger.value_text(&value);
+    let class_name = self.debugger.class_name(&value);
+    let reference = if self.debugger.attributes(&value).is_empty() { 0 } else { self.handle(Handle::Object(value)) };
+    vec![("value", text.into()), ("type", class_name.into()), ("variablesReference", reference.into())]
+  }
+
+  /// Runs the program and tells the client where it stopped
+  fn resume(&mut self, step: Step, reason: &str) -> io::Result<()> {
+    self.handles.clear();
+    let stop = self.debugger.resume(step);
+    self.send_program_output()?;
+
+    let (reason, mut members) = match stop {
+      Stop::Step if reason.is_empty() => ("step", Vec::new()),
+      Stop::Step => (reason, Vec::new()),
+      Stop::Breakpoint(id) => ("breakpoint", vec![("hitBreakpointIds", vec![Json::from(id)].into())]),
+      Stop::ConditionError { breakpoint, message } => {
+        let text = format!("Error in the condition of breakpoint {breakpoint}: {message}");
+        ("breakpoint", vec![("hitBreakpointIds", vec![Json::from(breakpoint)].into()), ("text", text.into())])
+      }
+      Stop::Error(error) => ("exception", vec![("text", format!("Runtime error: {error}").into())]),
+      Stop::Exited(result) => {
+        if let Err(error) = &result {
+          self.event("output", Json::object(vec![("category", "stderr".into()), ("output", format!("{error}\n").into())]))?;
+        }
+        self.event("exited", Json::object(vec![("exitCode", i64::from(result.is_err()).into())]))?;
+        return self.event("terminated", Json::object(Vec::new()));
+      }
+    };
+    members.extend([("threadId", THREAD_ID.into()), ("allThreadsStopped", true.into())]);
+    members.insert(0, ("reason", reason.into()));
+    self.event("stopped", Json::object(members))
+  }
+
+  fn send_program_output(&mut self) -> io::Result<()> {
+    let bytes = std::mem::take(&mut *self.program_output.borrow_mut());
+    if bytes.is_empty() {
+      return Ok(());
+    }
+    let text = String::from_utf8_lossy(&bytes).to_string();
+    self.event("output", Json::object(vec![("category", "stdout".into()), ("output", text.into())]))
+  }
+
+  fn respond(&mut self, request_seq: i64, command: &str, result: Result<Json, String>) -> io::Result<()> {
+    let mut members = vec![
+      ("type", "response".into()),
+      ("request_seq", request_seq.into()),
+      ("success", result.is_ok().into()),
+      ("command", command.into()),
+    ];
+    match result {
+      Ok(Json::Null) => {}
+      Ok(body) => members.push(("body", body)),
+      Err(message) => members.push(("message", message.into())),
+    }
+    self.send(members)
+  }
+
+  fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+    self.send(vec![("type", "event".into()), ("event", event.into()), ("body", body)])
+  }
+
+  fn send(&mut self, mut members: Vec<(&str, Json)>) -> io::Result<()> {
+    self.seq += 1;
+    members.insert(0, ("seq", self.seq.into()));
+    let body = Json::object(members).to_string();
+    write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    self.output.flush()
+  }
+}
+
+fn frame_id(arguments: &Json) -> Result<usize, String> {
+  arguments.get("frameId").as_i64().map(|id| id as usize).ok_or_else(|| "Missing frameId".to_string())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::debug::dap::{read_message, serve};
+  use crate::debug::json::Json;
+  use crate::debug::test::counter;
+
+  #[test]
+  fn test_session() {
+    let requests = [
+      r#"{"command":"initialize","arguments":{"adapterID":"cool"}}"#,
+      r#"{"command":"launch","arguments":{}}"#,
+      r#"{"command":"setBreakpoints","arguments":{"source":{"path":"test_resources/debug/counter.cl"},"breakpoints":[{"line":21,"condition":"i = 3"},{"line":100}]}}"#,
+      r#"{"command":"configurationDone"}"#,
+      r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+      r#"{"command":"scopes","arguments":{"frameId":0}}"#,
+      r#"{"command":"variables","arguments":{"variablesReference":1}}"#,
+      r#"{"command":"evaluate","arguments":{"expression":"total * 100","frameId":0}}"#,
+      r#"{"command":"stepIn","arguments":{"threadId":1}}"#,
+      r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+      r#"{"command":"continue","arguments":{"threadId":1}}"#,
+      r#"{"command":"continue","arguments":{"threadId":1}}"#,
+      r#"{"command":"disconnect"}"#,
+    ];
+    let mut input = Vec::new();
+    for (index, request) in requests.iter().enumerate() {
+      let request = format!(r#"{{"seq":{},"type":"request",{}"#, index + 1, &request[1..]);
+      input.extend(format!("Content-Length: {}\r\n\r\n{request}", request.len()).bytes());
+    }
+    let mut output = Vec::new();
+    serve(&counter(), &mut input.as_slice(), &mut output).expect("Session must run");
+
+    let mut messages = Vec::new();
+    let mut output = output.as_slice();
+    while let Some(message) = read_message(&mut output).expect("Output must be framed") {
+      messages.push(message);
+    }
+    let response = |command: &str, index: usize| -> Json {
+      let mut responses = messages.iter().filter(|message| message.get("command").as_str() == Some(command));
+      let response = responses.nth(index).expect("Response must be sent").clone();
+      assert_eq!(response.get("success"), &Json::Bool(true), "{response}");
+      response.get("body").clone()
+    };
+    let events: Vec<String> = messages.iter()
+        .filter(|message| message.get("type").as_str() == Some("event"))
+        .map(|message| {
+          let body = message.get("body");
+          let detail = body.get("reason").as_str().or(body.get("output").as_str()).unwrap_or_default();
+          format!("{} {detail}", message.get("event").as_str().unwrap_or_default()).trim().to_string()
+        })
+        .collect();
+    assert_eq!(events, ["initialized", "stopped breakpoint", "stopped step", "output 6", "stopped exception", "output test_resources/debug/counter.cl:26: Division by zero.", "exited", "terminated"]);
+
+    let breakpoints = response("setBreakpoints", 0);
+    let verified: Vec<&Json> = breakpoints.get("breakpoints").elements().iter().map(|breakpoint| breakpoint.get("verified")).collect();
+    assert_eq!(verified, [&Json::Bool(true), &Json::Bool(false)]);
+
+    let frames = response("stackTrace", 0);
+    assert_eq!(frames.get("stackFrames").elements()[0].get("name").as_str(), Some("Main.main (self : Main)"));
+    assert_eq!(frames.get("stackFrames").elements()[0].get("line").as_i64(), Some(21));
+    let variables: Vec<String> = response("variables", 0).get("variables").elements().iter()
+        .map(|variable| format!("{} = {}", variable.get("name").as_str().unwrap_or_default(), variable.get("value").as_str().unwrap_or_default()))
+        .collect();
+    assert_eq!(variables, ["total = 3", "i = 3"]);
+    assert_eq!(response("evaluate", 0).get("result").as_str(), Some("300"));
+
+    let frames = response("stackTrace", 1);
+    let names: Vec<&str> = frames.get("stackFrames").elements().iter().filter_map(|frame| frame.get("name").as_str()).collect();
+    assert_eq!(names, ["Counter.add (self : Loud)", "Main.main (self : Main)"]);
+  }
+}
@@ -0,0 +1,315 @@
+use std::fmt::{Display, Formatter};
+
+/// A JSON value, enough for the messages of the Debug Adapter Protocol. Object members keep their order.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+  pub fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+  }
+
+  /// Member of an object, null if there is none
+  pub fn get(&self, name: &str) -> &Json {
+    match self {
+      Json::Object(members) => members.iter().find(|(member, _)| member == name).map_or(&NULL, |(_, value)| value),
+      _ => &NULL,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(value) => Some(value),
+      _ => None,
+    }
+  }
+
+  pub fn as_i64(&self) -> Option<i64> {
+    match self {
+      Json::Number(value) if value.fract() == 0.0 => Some(*value as i64),
+      _ => None,
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Json::Bool(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  /// Elements of an array, none for other values
+  pub fn elements(&self) -> &[Json] {
+    match self {
+      Json::Array(elements) => elements,
+      _ => &[],
+    }
+  }
+
+  pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+    let value = parser.value()?;
+    parser.skip_spaces();
+    match parser.peek() {
+      None => Ok(value),
+      Some(c) => Err(format!("Unexpected {c} after the value")),
+    }
+  }
+}
+
+impl From<&str> for Json {
+  fn from(value: &str) -> Json {
+    Json::String(value.to_string())
+  }
+}
+
+impl From<String> for Json {
+  fn from(value: String) -> Json {
+    Json::String(value)
+  }
+}
+
+impl From<bool> for Json {
+  fn from(value: bool) -> Json {
+    Json::Bool(value)
+  }
+}
+
+impl From<i64> for Json {
+  fn from(value: i64) -> Json {
+    Json::Number(value as f64)
+  }
+}
+
+impl From<usize> for Json {
+  fn from(value: usize) -> Json {
+    Json::Number(value as f64)
+  }
+}
+
+impl From<u32> for Json {
+  fn from(value: u32) -> Json {
+    Json::Number(value as f64)
+  }
+}
+
+impl From<Vec<Json>> for Json {
+  fn from(elements: Vec<Json>) -> Json {
+    Json::Array(elements)
+  }
+}
+
+impl Display for Json {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(value) => write!(f, "{value}"),
+      Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => write!(f, "{}", *value as i64),
+      Json::Number(value) => write!(f, "{value}"),
+      Json::String(value) => write_string(f, value),
+      Json::Array(elements) => {
+        write!(f, "[")?;
+        for (index, element) in elements.iter().enumerate() {
+          if index > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{element}")?;
+        }
+        write!(f, "]")
+      }
+      Json::Object(members) => {
+        write!(f, "{{")?;
+        for (index, (name, value)) in members.iter().enumerate() {
+          if index > 0 {
+            write!(f, ",")?;
+          }
+          write_string(f, name)?;
+          write!(f, ":{value}")?;
+        }
+        write!(f, "}}")
+      }
+    }
+  }
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> std::fmt::Result {
+  write!(f, "\"")?;
+  for c in value.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{c}")?,
+    }
+  }
+  write!(f, "\"")
+}
+
+struct Parser {
+  chars: Vec<char>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.position).copied()
+  }
+
+  fn next(&mut self) -> Result<char, String> {
+    let c = self.peek().ok_or("Unexpected end of JSON")?;
+    self.position += 1;
+    Ok(c)
+  }
+
+  fn skip_spaces(&mut self) {
+    while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+      self.position += 1;
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), String> {
+    self.skip_spaces();
+    match self.next()? {
+      c if c == expected => Ok(()),
+      c => Err(format!("Expected {expected}, found {c}")),
+    }
+  }
+
+  fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+    for expected in word.chars() {
+      if self.next()? != expected {
+        return Err(format!("Invalid literal, expected {word}"));
+      }
+    }
+    Ok(value)
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    self.skip_spaces();
+    match self.peek().ok_or("Unexpected end of JSON")? {
+      'n' => self.literal("null", Json::Null),
+      't' => self.literal("true", Json::Bool(true)),
+      'f' => self.literal("false", Json::Bool(false)),
+      '"' => Ok(Json::String(self.string()?)),
+      '[' => {
+        self.position += 1;
+        let mut elements = Vec::new();
+        self.skip_spaces();
+        if self.peek() == Some(']') {
+          self.position += 1;
+          return Ok(Json::Array(elements));
+        }
+        loop {
+          elements.push(self.value()?);
+          self.skip_spaces();
+          match self.next()? {
+            ',' => continue,
+            ']' => return Ok(Json::Array(elements)),
+            c => return Err(format!("Expected , or ], found {c}")),
+          }
+        }
+      }
+      '{' => {
+        self.position += 1;
+        let mut members = Vec::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+          self.position += 1;
+          return Ok(Json::Object(members));
+        }
+        loop {
+          self.skip_spaces();
+          let name = self.string()?;
+          self.expect(':')?;
+          members.push((name, self.value()?));
+          self.skip_spaces();
+          match self.next()? {
+            ',' => continue,
+            '}' => return Ok(Json::Object(members)),
+            c => return Err(format!("Expected , or }}, found {c}")),
+          }
+        }
+      }
+      c if c == '-' || c.is_ascii_digit() => {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+          self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse().map(Json::Number).map_err(|_| format!("Invalid number {text}"))
+      }
+      c => Err(format!("Unexpected {c}")),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    if self.next()? != '"' {
+      return Err("Expected a string".to_string());
+    }
+    let mut value = String::new();
+    loop {
+      match self.next()? {
+        '"' => return Ok(value),
+        '\\' => {
+          let c = match self.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'u' => {
+              let unit = self.hex()?;
+              // Characters outside the basic plane are written as a surrogate pair
+              let code = if (0xd800..0xdc00).contains(&unit) && self.chars[self.position..].starts_with(&['\\', 'u']) {
+                self.position += 2;
+                0x10000 + ((unit - 0xd800) << 10) + (self.hex()? - 0xdc00)
+              } else {
+                unit
+              };
+              char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            c => c,
+          };
+          value.push(c);
+        }
+        c => value.push(c),
+      }
+    }
+  }
+
+  fn hex(&mut self) -> Result<u32, String> {
+    let digits: String = (0..4).map(|_| self.next()).collect::<Result<String, String>>()?;
+    u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid escape \\u{digits}"))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::debug::json::Json;
+
+  #[test]
+  fn test_round_trip() {
+    let text = r#"{"seq":1,"type":"request","arguments":{"lines":[1,-2,3.5],"ok":true,"none":null,"text":"a\"b\\c\né😀"}}"#;
+    let json = Json::parse(text).expect("JSON must parse");
+    assert_eq!(json.get("seq").as_i64(), Some(1));
+    assert_eq!(json.get("arguments").get("lines").elements().len(), 3);
+    assert_eq!(json.get("arguments").get("text").as_str(), Some("a\"b\\c\né😀"));
+    assert_eq!(json.get("missing"), &Json::Null);
+    assert_eq!(Json::parse(&json.to_string()), Ok(json));
+
+    assert_eq!(Json::parse("[1, 2").unwrap_err(), "Unexpected end of JSON");
+    assert_eq!(Json::parse("{} x").unwrap_err(), "Unexpected x after the value");
+  }
+}
@@ -0,0 +1,552 @@
+pub mod console;
+pub mod dap;
+mod json;
+
+use crate::repl;
+use ir::interp::{Execution, Frame, Interpreter, ObjectData, RuntimeError, Value};
+use ir::lower::lower_program;
+use ir::model::instruction::Op;
+use ir::model::module::Module;
+use ir::model::Pos;
+use parser::model::feature::ParseFeature;
+use parser::model::program::ParseProgram;
+use semantic::gen::entry::EntryPoint;
+use semantic::gen::{analyze_program, CheckOptions};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Method added to the class of a frame to evaluate an expression in it, with the variables as parameters
+const EVALUATE_METHOD: &str = "debug_expression";
+
+/// A program lowered with debug information, with the files its classes come from
+pub struct Debuggee {
+  module: Module,
+  program: ParseProgram,
+  entry: EntryPoint,
+  files: Vec<PathBuf>,
+  sources: Vec<String>,
+  class_files: HashMap<String, usize>,
+  code_lines: Vec<BTreeSet<u32>>, // lines of each file with instructions
+}
+
+impl Debuggee {
+  /// The module must be lowered from the program with `LowerOptions::debug_info`
+  pub fn new(mut module: Module, program: ParseProgram, entry: EntryPoint, files: &[PathBuf]) -> Result<Debuggee, String> {
+    let (sources, class_files) = read_program_files(files)?;
+    for class in &mut module.classes {
+      class.file = class_files.get(&class.name).map(|&file| files[file].display().to_string());
+    }
+    let mut code_lines = vec![BTreeSet::new(); files.len()];
+    for function in module.functions.iter().filter(|function| !function.is_builtin()) {
+      if let Some(&file) = class_files.get(&function.class_name) {
+        let lines = function.blocks.iter().flat_map(|block| &block.instructions).map(|instruction| instruction.pos.0);
+        code_lines[file].extend(lines.chain([function.pos.0]).filter(|line| *line > 0));
+      }
+    }
+    Ok(Debuggee { module, program, entry, files: files.to_vec(), sources, class_files, code_lines })
+  }
+
+  pub fn file(&self, index: usize) -> &Path {
+    &self.files[index]
+  }
+
+  /// File the class is defined in, none for the basic classes
+  pub fn class_file(&self, class_name: &str) -> Option<usize> {
+    self.class_files.get(class_name).copied()
+  }
+
+  /// Text of a line of a file, numbered from 1
+  pub fn source_line(&self, file: usize, line: u32) -> Option<&str> {
+    self.sources[file].lines().nth((line as usize).checked_sub(1)?)
+  }
+
+  /// The file of the program at the path, also found by its name alone
+  pub fn file_index(&self, path: &Path) -> Option<usize> {
+    let canonical = |path: &Path| fs::canonicalize(path).ok();
+    self.files.iter().position(|file| file == path)
+        .or_else(|| self.files.iter().position(|file| canonical(file).is_some_and(|file| Some(file) == canonical(path))))
+        .or_else(|| {
+          let named: Vec<usize> = (0..self.files.len()).filter(|&index| self.files[index].file_name() == path.file_name()).collect();
+          (named.len() == 1).then(|| named[0])
+        })
+  }
+
+  /// `file:line`, the first line with code from there, or `Class.method`, the method a class has, maybe inherited
+  pub fn resolve(&self, spec: &str) -> Result<Location, String> {
+    if let Some((file, line)) = spec.rsplit_once(':') {
+      let line: u32 = line.parse().map_err(|_| format!("Invalid line number {line}"))?;
+      let file = self.file_index(Path::new(file)).ok_or_else(|| format!("No file {file} in the program"))?;
+      let line = self.code_lines[file].range(line..).next().copied().ok_or_else(|| format!("No code at or after line {line}"))?;
+      return Ok(Location::Line { file, line });
+    }
+
+    let (class_name, method_name) = spec.split_once('.').ok_or_else(|| format!("Invalid breakpoint {spec}, expected `file:line` or `Class.method`"))?;
+    let class = self.module.class(class_name).ok_or_else(|| format!("No class {class_name}"))?;
+    let slot = class.dispatch_table.iter().find(|slot| slot.method == method_name).ok_or_else(|| format!("No method {spec}"))?;
+    match self.module.function(&slot.function) {
+      Some(function) if function.is_builtin() => Err(format!("{} is built in, it has no code to stop in", slot.function)),
+      _ => Ok(Location::Function(slot.function.clone())),
+    }
+  }
+
+  /// `file:line:column` of a position in a function, the position alone for functions of the basic classes
+  pub fn location_text(&self, class_name: &str, (line, column): Pos) -> String {
+    match self.class_file(class_name) {
+      Some(file) => format!("{}:{line}:{column}", self.files[file].display()),
+      None => format!("{line}:{column}"),
+    }
+  }
+
+  /// Compiles a method of the class returning the expression, which can use the attributes of the class and the
+  /// parameters. The positions of the errors are those of the added method, so they are left out.
+  fn compile_expression(&self, class_name: &str, params: &[(String, String)], expression: &str, return_type: &str) -> Result<Module, String> {
+    let formals: Vec<String> = params.iter().map(|(name, type_name)| format!("{name} : {type_name}")).collect();
+    let source = format!("class {class_name} {{ {EVALUATE_METHOD}({}) : {return_type} {{ {expression} \n}}; }};", formals.join(", "));
+    let method = repl::parse(&source).map_err(|e| without_position(&e))?.classes.into_iter()
+        .flat_map(|class| class.features.into_iter().flatten())
+        .find(|feature| matches!(feature, ParseFeature::Method { .. }))
+        .ok_or("Expected an expression")?;
+
+    let mut program = self.program.clone();
+    let class = program.classes.iter_mut().find(|class| class.get_name() == class_name).ok_or_else(|| format!("No class {class_name}"))?;
+    class.features.get_or_insert_with(Vec::new).push(method);
+
+    let options = CheckOptions { entry: self.entry.clone(), ..CheckOptions::default() };
+    let checked = analyze_program(&program, &options).map_err(|diagnostics| {
+      let errors: Vec<String> = diagnostics.iter().filter(|d| d.is_error()).map(|d| d.message.clone()).collect();
+      errors.join("\n")
+    })?;
+    Ok(lower_program(&checked))
+  }
+}
+
+/// Sources of the files of a program, with the index of the file each class is defined in
+pub(crate) fn read_program_files(files: &[PathBuf]) -> Result<(Vec<String>, HashMap<String, usize>), String> {
+  let mut sources = Vec::new();
+  let mut class_files = HashMap::new();
+  for (index, file) in files.iter().enumerate() {
+    let source = fs::read_to_string(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+    let file_program = repl::parse(&source).map_err(|e| format!("{}: {e}", file.display()))?;
+    class_files.extend(file_program.classes.iter().map(|class| (class.get_name(), index)));
+    sources.push(source);
+  }
+  Ok((sources, class_files))
+}
+
+/// Parser errors start with the position
+fn without_position(error: &str) -> String {
+  match error.split_once(' ') {
+    Some((position, message)) if position.split(':').all(|part| part.parse::<u32>().is_ok()) => message.to_string(),
+    _ => error.to_string(),
+  }
+}
+
+/// Where a breakpoint stops: before the first instruction of a line, or on entry to a function
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Location {
+  Line { file: usize, line: u32 },
+  Function(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Breakpoint {
+  pub id: usize,
+  pub location: Location,
+  pub condition: Option<String>, // COOL expression of type Bool evaluated in the frame stopped in
+}
+
+/// How far `Debugger::resume` runs
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+  Continue,
+  /// To the next line, in this function or one it calls
+  In,
+  /// To the next line of this function, or of its caller once it returns
+  Over,
+  /// Until this function returns
+  Out,
+}
+
+/// Why the program stopped
+#[derive(Debug, PartialEq, Clone)]
+pub enum Stop {
+  Step,
+  Breakpoint(usize),
+  /// The condition of the breakpoint could not be evaluated
+  ConditionError { breakpoint: usize, message: String },
+  /// The program failed, its frames can be inspected before it exits
+  Error(RuntimeError),
+  Exited(Result<(), RuntimeError>),
+}
+
+enum State<'a> {
+  NotStarted,
+  /// Running the initialiser of the entry object, or the entry function once it returns
+  Running { execution: Execution<'a>, initialising: bool },
+  Failed { execution: Execution<'a>, error: RuntimeError },
+  Exited,
+}
+
+/// A frame of the call stack as the front ends show it
+pub struct StackFrame<'a> {
+  pub function: &'a str,
+  pub class_name: &'a str, // class of the function
+  pub receiver_class: String,
+  pub pos: Pos,
+}
+
+/// Class of the frame, its visible variables with their types, an expression and the type it must return
+type ExpressionKey = (String, Vec<(String, String)>, String, String);
+
+/// Runs a program with the IR interpreter one line at a time, stopping at breakpoints
+pub struct Debugger<'a> {
+  debuggee: &'a Debuggee,
+  interpreter: Interpreter<'a>,
+  breakpoints: Vec<Breakpoint>,
+  next_breakpoint: usize,
+  state: State<'a>,
+  lines: Vec<u32>, // line each frame last stopped at, 0 before its first instruction
+  compiled: HashMap<ExpressionKey, Result<Module, String>>, // expressions compiled so far
+}
+
+impl<'a> Debugger<'a> {
+  pub fn new(debuggee: &'a Debuggee, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self {
+    Debugger {
+      debuggee,
+      interpreter: Interpreter::new(&debuggee.module, input, output),
+      breakpoints: Vec::new(),
+      next_breakpoint: 1,
+      state: State::NotStarted,
+      lines: Vec::new(),
+      compiled: HashMap::new(),
+    }
+  }
+
+  pub fn debuggee(&self) -> &'a Debuggee {
+    self.debuggee
+  }
+
+  pub fn breakpoints(&self) -> &[Breakpoint] {
+    &self.breakpoints
+  }
+
+  pub fn add_breakpoint(&mut self, spec: &str, condition: Option<String>) -> Result<&Breakpoint, String> {
+    let location = self.debuggee.resolve(spec)?;
+    self.breakpoints.push(Breakpoint { id: self.next_breakpoint, location, condition });
+    self.next_breakpoint += 1;
+    Ok(self.breakpoints.last().expect("Breakpoint was added"))
+  }
+
+  /// Keeps the breakpoints for which the predicate returns `true`
+  pub fn retain_breakpoints(&mut self, keep: impl Fn(&Breakpoint) -> bool) {
+    self.breakpoints.retain(keep);
+  }
+
+  /// `Class.method` or `file:line` of the breakpoint
+  pub fn location_text(&self, location: &Location) -> String {
+    match location {
+      Location::Line { file, line } => format!("{}:{line}", self.debuggee.files[*file].display()),
+      Location::Function(function) => function.clone(),
+    }
+  }
+
+  /// Returns `true` once the program has started and until it exits
+  pub fn is_running(&self) -> bool {
+    matches!(self.state, State::Running { .. } | State::Failed { .. })
+  }
+
+  /// Runs the program until it stops, starting it first if needed
+  pub fn resume(&mut self, step: Step) -> Stop {
+    // A new frame is checked before it runs its first instruction
+    let mut started = false;
+    match std::mem::replace(&mut self.state, State::Exited) {
+      State::NotStarted => {
+        started = true;
+        let entry = self.debuggee.module.class(&self.debuggee.module.entry_class).expect("Entry class is verified");
+        let main = self.interpreter.allocate(entry.tag);
+        match &entry.init {
+          Some(init) => self.state = State::Running { execution: self.interpreter.start(init, vec![main]), initialising: true },
+          None => {
+            if let Err(error) = self.start_entry(main) {
+              return Stop::Exited(Err(error));
+            }
+          }
+        }
+      }
+      State::Failed { error, .. } => return Stop::Exited(Err(error)),
+      State::Exited => return Stop::Exited(Ok(())),
+      running => self.state = running,
+    }
+
+    let mut start_depth = self.frames().len();
+    loop {
+      let State::Running { execution, initialising } = &mut self.state else {
+        unreachable!("The program is running");
+      };
+      let result = if started { Ok(None) } else { self.interpreter.step(execution) };
+      started = false;
+      match result {
+        Ok(None) => {}
+        Ok(Some(main)) if *initialising => {
+          if let Err(error) = self.start_entry(main) {
+            return self.exit(Err(error));
+          }
+        }
+        Ok(Some(_)) => return self.exit(Ok(())),
+        Err(error) => {
+          let State::Running { execution, .. } = std::mem::replace(&mut self.state, State::Exited) else {
+            unreachable!("The program is running");
+          };
+          self.state = State::Failed { execution, error: error.clone() };
+          return Stop::Error(error);
+        }
+      }
+      if !matches!(self.state, State::Running { .. }) {
+        return Stop::Exited(Ok(()));
+      }
+
+      let depth = self.frames().len();
+      self.lines.resize(depth, 0);
+      if step == Step::Out && depth < start_depth {
+        // The caller stops within the line of the call, which `next` must not stop on again
+        self.lines[depth - 1] = self.frames()[depth - 1].pos().0;
+        return Stop::Step;
+      }
+      // Stepping over the end of a function goes on in its caller, not in the next function called at its depth
+      start_depth = start_depth.min(depth);
+      // Lines start at their first instruction, once the variables it binds are in scope
+      let frame = &self.frames()[depth - 1];
+      let line = frame.pos().0;
+      let binds = frame.next_instruction().is_some_and(|instruction| matches!(instruction.op, Op::DebugVar { .. }));
+      if line == 0 || line == self.lines[depth - 1] || binds {
+        continue;
+      }
+      let entry = self.lines[depth - 1] == 0;
+      self.lines[depth - 1] = line;
+
+      if let Some(stop) = self.check_breakpoints(entry) {
+        return stop;
+      }
+      if step == Step::In || (step == Step::Over && depth <= start_depth) {
+        return Stop::Step;
+      }
+    }
+  }
+
+  /// Runs the entry function on the entry object, at once if it is built in
+  fn start_entry(&mut self, main: Value) -> Result<(), RuntimeError> {
+    let entry_function = &self.debuggee.module.entry_function;
+    if self.debuggee.module.function(entry_function).is_some_and(|function| function.is_builtin()) {
+      self.interpreter.call_to_end(entry_function, vec![main])?;
+      self.state = State::Exited;
+    } else {
+      self.state = State::Running { execution: self.interpreter.start(entry_function, vec![main]), initialising: false };
+    }
+    Ok(())
+  }
+
+  fn exit(&mut self, result: Result<(), RuntimeError>) -> Stop {
+    self.state = State::Exited;
+    self.lines.clear();
+    Stop::Exited(result)
+  }
+
+  /// The first breakpoint at the new line of the innermost frame whose condition holds, `entry` at its first line
+  fn check_breakpoints(&mut self, entry: bool) -> Option<Stop> {
+    let frame = self.frames().last().expect("A function is running");
+    let (function, line) = (frame.function(), frame.pos().0);
+    let file = self.debuggee.class_file(&function.class_name);
+    let hits: Vec<Breakpoint> = self.breakpoints.iter()
+        .filter(|breakpoint| match &breakpoint.location {
+          Location::Line { file: bp_file, line: bp_line } => Some(*bp_file) == file && *bp_line == line,
+          Location::Function(name) => entry && *name == function.name,
+        })
+        .cloned()
+        .collect();
+
+    for breakpoint in hits {
+      let Some(condition) = &breakpoint.condition else {
+        return Some(Stop::Breakpoint(breakpoint.id));
+      };
+      match self.evaluate_as(0, condition, "Bool") {
+        Ok(value) if value.as_bool() => return Some(Stop::Breakpoint(breakpoint.id)),
+        Ok(_) => {}
+        Err(message) => return Some(Stop::ConditionError { breakpoint: breakpoint.id, message }),
+      }
+    }
+    None
+  }
+
+  /// Frames of the running program, innermost last
+  fn frames(&self) -> &[Frame<'a>] {
+    match &self.state {
+      State::Running { execution, .. } | State::Failed { execution, .. } => execution.frames(),
+      State::NotStarted | State::Exited => &[],
+    }
+  }
+
+  /// The frame numbered from the innermost one, 0
+  fn frame(&self, index: usize) -> Result<&Frame<'a>, String> {
+    let frames = self.frames();
+    frames.len().checked_sub(index + 1).map(|position| &frames[position]).ok_or_else(|| format!("No frame {index}"))
+  }
+
+  /// The call stack, innermost first
+  pub fn stack(&self) -> Vec<StackFrame<'a>> {
+    self.frames().iter().rev().enumerate()
+        .map(|(index, frame)| {
+          let function = frame.function();
+          StackFrame {
+            function: &function.name,
+            class_name: &function.class_name,
+            receiver_class: self.class_name(frame.receiver()),
+            pos: if index == 0 { frame.pos() } else { frame.call_pos() },
+          }
+        })
+        .collect()
+  }
+
+  /// `self` in the frame
+  pub fn receiver(&self, frame: usize) -> Result<Value, String> {
+    Ok(self.frame(frame)?.receiver().clone())
+  }
+
+  /// Formals and `let` and `case` variables in scope in the frame, without those they shadow
+  pub fn variables(&self, frame: usize) -> Result<Vec<(String, Value)>, String> {
+    Ok(self.visible_variables(frame)?.into_iter().map(|(name, _, value)| (name, value)).collect())
+  }
+
+  fn visible_variables(&self, frame: usize) -> Result<Vec<(String, String, Value)>, String> {
+    let variables = self.frame(frame)?.variables();
+    let visible = variables.iter().enumerate()
+        .filter(|(index, (name, _, _))| !variables[index + 1..].iter().any(|(other, _, _)| other == name))
+        .map(|(_, (name, var_type, value))| {
+          let type_name = if var_type.class_name().is_empty() { "Object" } else { var_type.class_name() };
+          (name.to_string(), type_name.to_string(), (*value).clone())
+        })
+        .collect();
+    Ok(visible)
+  }
+
+  /// Attributes of an object, none for other values
+  pub fn attributes(&self, value: &Value) -> Vec<(String, Value)> {
+    let Value::Object(object) = value else {
+      return Vec::new();
+    };
+    let object = object.borrow();
+    match &object.data {
+      ObjectData::Attributes(attributes) => {
+        let class = &self.debuggee.module.classes[object.tag];
+        class.attributes.iter().map(|attribute| attribute.name.clone()).zip(attributes.iter().cloned()).collect()
+      }
+      _ => Vec::new(),
+    }
+  }
+
+  /// Dynamic class of a value
+  pub fn class_name(&self, value: &Value) -> String {
+    match value {
+      Value::Void => "Void".to_string(),
+      Value::Int(_) => "Int".to_string(),
+      Value::Bool(_) => "Bool".to_string(),
+      Value::Object(object) => self.debuggee.module.classes[object.borrow().tag].name.clone(),
+    }
+  }
+
+  pub fn value_text(&self, value: &Value) -> String {
+    repl::value_text(value, &self.debuggee.module, 0)
+  }
+
+  /// Evaluates a COOL expression in the frame, where it can use `self`, the attributes and the variables in scope.
+  /// Its input is empty and its output is discarded.
+  pub fn evaluate(&mut self, frame: usize, expression: &str) -> Result<Value, String> {
+    self.evaluate_as(frame, expression, "Object")
+  }
+
+  fn evaluate_as(&mut self, frame: usize, expression: &str, return_type: &str) -> Result<Value, String> {
+    let class_name = self.frame(frame)?.function().class_name.clone();
+    let variables = self.visible_variables(frame)?;
+    let params: Vec<(String, String)> = variables.iter().map(|(name, type_name, _)| (name.clone(), type_name.clone())).collect();
+    let receiver = self.receiver(frame)?;
+
+    let key = (class_name.clone(), params, expression.to_string(), return_type.to_string());
+    if !self.compiled.contains_key(&key) {
+      let module = self.debuggee.compile_expression(&class_name, &key.1, expression, return_type);
+      self.compiled.insert(key.clone(), module);
+    }
+    let module = self.compiled[&key].as_ref()?;
+
+    let (mut input, mut output) = (io::empty(), io::sink());
+    let mut interpreter = Interpreter::new(module, &mut input, &mut output);
+    let args = std::iter::once(receiver).chain(variables.into_iter().map(|(_, _, value)| value)).collect();
+    interpreter.call_to_end(&format!("{class_name}.{EVALUATE_METHOD}"), args).map_err(|e| format!("Runtime error: {e}"))
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+  use crate::debug::{Debuggee, Debugger, Location, Step, Stop};
+  use ir::interp::{self, RuntimeError};
+  use ir::lower::{lower_program_with_options, LowerOptions};
+  use parser::get_ast_from_file_path;
+  use semantic::gen::entry::EntryPoint;
+  use semantic::gen::{analyze_program, CheckOptions};
+  use std::path::PathBuf;
+
+  /// `test_resources/debug/counter.cl`, ready to debug
+  pub(crate) fn counter() -> Debuggee {
+    let path = PathBuf::from("test_resources/debug/counter.cl");
+    let program = get_ast_from_file_path(&path.to_string_lossy()).expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program_with_options(&checked, &LowerOptions { debug_info: true });
+    Debuggee::new(module, program.clone(), EntryPoint::default(), &[path]).expect("Program must load")
+  }
+
+  #[test]
+  fn test_breakpoints() {
+    let debuggee = counter();
+    assert_eq!(debuggee.resolve("counter.cl:8"), Ok(Location::Line { file: 0, line: 12 }));
+    assert_eq!(debuggee.resolve("Loud.add"), Ok(Location::Function("Counter.add".to_string())));
+    assert_eq!(debuggee.resolve("list.cl:3"), Err("No file list.cl in the program".to_string()));
+    assert_eq!(debuggee.resolve("counter.cl:40"), Err("No code at or after line 40".to_string()));
+    assert_eq!(debuggee.resolve("Main.out_int"), Err("IO.out_int is built in, it has no code to stop in".to_string()));
+    assert_eq!(debuggee.resolve("main"), Err("Invalid breakpoint main, expected `file:line` or `Class.method`".to_string()));
+
+    let (mut input, mut output) = (&b""[..], Vec::new());
+    let mut debugger = Debugger::new(&debuggee, &mut input, &mut output);
+    debugger.add_breakpoint("Counter.add", Some("count = 1".to_string())).expect("Breakpoint must be set");
+
+    assert_eq!(debugger.resume(Step::Continue), Stop::Breakpoint(1));
+    let variables: Vec<String> = debugger.variables(0).expect("Frame must exist").iter().map(|(name, value)| format!("{name} = {}", debugger.value_text(value))).collect();
+    assert_eq!(variables, ["n = 2"]);
+    assert_eq!(debugger.evaluate(1, "i + total").map(|value| debugger.value_text(&value)), Ok("3".to_string()));
+    assert_eq!(debugger.evaluate(0, "i").map(|value| debugger.value_text(&value)), Err("Undeclared identifier i".to_string()));
+
+    debugger.add_breakpoint("counter.cl:22", Some("i < \"3\"".to_string())).expect("Breakpoint must be set");
+    let error = "Non-Int arguments: Int < String".to_string();
+    assert_eq!(debugger.resume(Step::Continue), Stop::ConditionError { breakpoint: 2, message: error });
+    debugger.retain_breakpoints(|breakpoint| breakpoint.id == 1);
+    let error = RuntimeError::DivisionByZero { at: Some(interp::Location { file: Some("test_resources/debug/counter.cl".to_string()), pos: (26, 25) }) };
+    assert_eq!(debugger.resume(Step::Out), Stop::Error(error.clone()));
+    assert_eq!(debugger.stack().iter().map(|frame| frame.function).collect::<Vec<_>>(), ["Main.main"]);
+    assert_eq!(debugger.resume(Step::Continue), Stop::Exited(Err(error)));
+    assert!(!debugger.is_running());
+    drop(debugger);
+    assert_eq!(String::from_utf8(output).expect("Output must be UTF-8"), "6\n");
+
+    // `next` after `finish` goes on to the next line of the caller
+    let (mut input, mut output) = (&b""[..], Vec::new());
+    let mut debugger = Debugger::new(&debuggee, &mut input, &mut output);
+    debugger.add_breakpoint("Counter.add", None).expect("Breakpoint must be set");
+    assert_eq!(debugger.resume(Step::Continue), Stop::Breakpoint(1));
+    debugger.retain_breakpoints(|_| false);
+    let top = |debugger: &Debugger| debugger.stack().first().map(|frame| (frame.function.to_string(), frame.pos.0));
+    assert_eq!(debugger.resume(Step::Out), Stop::Step);
+    assert_eq!(top(&debugger), Some(("Main.main".to_string(), 21)));
+    assert_eq!(debugger.resume(Step::Over), Stop::Step);
+    assert_eq!(top(&debugger), Some(("Main.main".to_string(), 22)));
+  }
+}
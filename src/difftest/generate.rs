@@ -0,0 +1,613 @@
+//! Random programs which type check and terminate.
+//!
+//! The expressions are made for the type they must have, following the rules of the type checker, from the classes,
+//! variables and methods in scope. The programs terminate by construction:
+//!
+//! * methods have a level, and only call methods of a lower level, the built-in ones being of level 0
+//! * loops count up to a constant with a variable nothing assigns
+//! * attribute initialisers don't call methods, and only create objects of the classes declared before theirs
+//!
+//! Strings are only assigned outside loops in `Main.main`, so they can't double in size at each iteration.
+
+use parser::fuzz::rng::Rng;
+use std::fmt::{Display, Formatter};
+use std::iter::once;
+
+/// Levels of expressions in a method body or an attribute initialiser
+const MAX_DEPTH: usize = 4;
+
+/// Level of `Main.main`, above that of every other method
+const MAIN_LEVEL: usize = 4;
+
+/// Level of attribute initialisers, which may only call the built-in methods
+const INIT_LEVEL: usize = 1;
+
+const USER_CLASSES: [&str; 4] = ["A", "B", "C", "D"];
+const STRING_PARTS: [&str; 8] = ["a", "b", "z", " ", "cool", "\n", "\t", "\""];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+  pub classes: Vec<Class>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+  pub name: String,
+  pub parent: String,
+  pub features: Vec<Feature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+  Attribute { name: String, type_name: String, init: Option<Expr> },
+  Method { name: String, formals: Vec<(String, String)>, return_type: String, body: Expr },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+  Int(i32),
+  Str(String),
+  Bool(bool),
+  Var(String),
+  Assign(String, Box<Expr>),
+  Binary(&'static str, Box<Expr>, Box<Expr>),
+  Unary(&'static str, Box<Expr>),
+  New(String),
+  Dispatch { receiver: Option<Box<Expr>>, static_type: Option<String>, method: String, args: Vec<Expr> },
+  If(Box<Expr>, Box<Expr>, Box<Expr>),
+  /// The body run `count` times, with a counter nothing else assigns
+  Loop { counter: String, count: i32, body: Box<Expr> },
+  Block(Vec<Expr>),
+  Let(Vec<(String, String, Option<Expr>)>, Box<Expr>),
+  Case(Box<Expr>, Vec<(String, String, Expr)>),
+}
+
+impl Expr {
+  /// The expressions this one is made of, in the order they are printed
+  pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+    match self {
+      Expr::Int(_) | Expr::Str(_) | Expr::Bool(_) | Expr::Var(_) | Expr::New(_) => Vec::new(),
+      Expr::Assign(_, value) | Expr::Unary(_, value) | Expr::Loop { body: value, .. } => vec![value.as_mut()],
+      Expr::Binary(_, left, right) => vec![left.as_mut(), right.as_mut()],
+      Expr::Dispatch { receiver, args, .. } => receiver.iter_mut().map(|receiver| receiver.as_mut()).chain(args.iter_mut()).collect(),
+      Expr::If(condition, then, otherwise) => vec![condition.as_mut(), then.as_mut(), otherwise.as_mut()],
+      Expr::Block(body) => body.iter_mut().collect(),
+      Expr::Let(bindings, body) => bindings.iter_mut().filter_map(|(_, _, init)| init.as_mut()).chain(once(body.as_mut())).collect(),
+      Expr::Case(value, branches) => once(value.as_mut()).chain(branches.iter_mut().map(|(_, _, body)| body)).collect(),
+    }
+  }
+}
+
+impl Display for Program {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    for class in &self.classes {
+      write!(f, "{class}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for Class {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "class {} inherits {} {{", self.name, self.parent)?;
+    for feature in &self.features {
+      match feature {
+        Feature::Attribute { name, type_name, init: None } => writeln!(f, "  {name} : {type_name};")?,
+        Feature::Attribute { name, type_name, init: Some(init) } => writeln!(f, "  {name} : {type_name} <- {init};")?,
+        Feature::Method { name, formals, return_type, body } => {
+          let formals: Vec<String> = formals.iter().map(|(name, type_name)| format!("{name} : {type_name}")).collect();
+          writeln!(f, "  {name}({}) : {return_type} {{ {body} }};", formals.join(", "))?;
+        }
+      }
+    }
+    writeln!(f, "}};\n")
+  }
+}
+
+impl Display for Expr {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Expr::Int(value) if *value < 0 => write!(f, "(~{})", value.unsigned_abs()),
+      Expr::Int(value) => write!(f, "{value}"),
+      Expr::Str(value) => {
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+        write!(f, "\"{escaped}\"")
+      }
+      Expr::Bool(value) => write!(f, "{value}"),
+      Expr::Var(name) => write!(f, "{name}"),
+      Expr::Assign(name, value) => write!(f, "({name} <- {value})"),
+      Expr::Binary(operator, left, right) => write!(f, "({left} {operator} {right})"),
+      Expr::Unary(operator, value) => write!(f, "({operator} {value})"),
+      Expr::New(type_name) => write!(f, "(new {type_name})"),
+      Expr::Dispatch { receiver, static_type, method, args } => {
+        match receiver.as_deref() {
+          None => {}
+          Some(receiver @ (Expr::Var(_) | Expr::Str(_) | Expr::New(_) | Expr::Dispatch { .. })) => write!(f, "{receiver}")?,
+          Some(receiver) => write!(f, "({receiver})")?,
+        }
+        if let Some(static_type) = static_type {
+          write!(f, "@{static_type}")?;
+        }
+        if receiver.is_some() {
+          write!(f, ".")?;
+        }
+        let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+        write!(f, "{method}({})", args.join(", "))
+      }
+      Expr::If(condition, then, otherwise) => write!(f, "if {condition} then {then} else {otherwise} fi"),
+      Expr::Loop { counter, count, body } => {
+        write!(f, "(let {counter} : Int <- 0 in while {counter} < {count} loop {{ {body}; {counter} <- {counter} + 1; }} pool)")
+      }
+      Expr::Block(body) => {
+        write!(f, "{{ ")?;
+        for expr in body {
+          write!(f, "{expr}; ")?;
+        }
+        write!(f, "}}")
+      }
+      Expr::Let(bindings, body) => {
+        let bindings: Vec<String> = bindings.iter()
+            .map(|(name, type_name, init)| match init {
+              Some(init) => format!("{name} : {type_name} <- {init}"),
+              None => format!("{name} : {type_name}"),
+            })
+            .collect();
+        write!(f, "(let {} in {body})", bindings.join(", "))
+      }
+      Expr::Case(value, branches) => {
+        write!(f, "case {value} of ")?;
+        for (name, type_name, body) in branches {
+          write!(f, "{name} : {type_name} => {body}; ")?;
+        }
+        write!(f, "esac")
+      }
+    }
+  }
+}
+
+/// A method the generated code may call, from the class which first defines it
+#[derive(Debug, Clone)]
+struct MethodSig {
+  name: String,
+  formals: Vec<String>,
+  return_type: String,
+  level: usize,
+}
+
+#[derive(Debug, Clone)]
+struct ClassSig {
+  name: String,
+  parent: String,
+  attributes: Vec<(String, String)>,
+  methods: Vec<MethodSig>,    // defined by the class
+  overrides: Vec<MethodSig>,  // redefined from an ancestor
+}
+
+impl ClassSig {
+  fn new(name: &str, parent: &str, methods: &[(&str, &[&str], &str)]) -> Self {
+    let methods = methods.iter()
+        .map(|(name, formals, return_type)| MethodSig {
+          name: name.to_string(),
+          formals: formals.iter().map(|formal| formal.to_string()).collect(),
+          return_type: return_type.to_string(),
+          level: 0,
+        })
+        .collect();
+    ClassSig { name: name.to_string(), parent: parent.to_string(), attributes: Vec::new(), methods, overrides: Vec::new() }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct Var {
+  name: String,
+  type_name: String,
+  assignable: bool,
+}
+
+/// Generates random programs which type check and terminate, see the module documentation
+pub struct TypedGenerator {
+  rng: Rng,
+  classes: Vec<ClassSig>, // the built-in classes, then those of the program in order, `Main` last
+  names: usize,           // names made so far, which number the next one
+  class: usize,           // class of the feature being made
+  level: usize,           // methods of a lower level may be called
+  init: bool,             // making an attribute initialiser
+  loops: usize,           // loops around the expression being made
+  vars: Vec<Var>,
+}
+
+impl TypedGenerator {
+  pub fn new(seed: u64) -> Self {
+    // `abort` and the input methods are left out: the programs run to the end, without input
+    let classes = vec![
+      ClassSig::new("Object", "Object", &[("type_name", &[], "String"), ("copy", &[], "SELF_TYPE")]),
+      ClassSig::new("IO", "Object", &[("out_string", &["String"], "SELF_TYPE"), ("out_int", &["Int"], "SELF_TYPE")]),
+      ClassSig::new("Int", "Object", &[]),
+      ClassSig::new("Bool", "Object", &[]),
+      ClassSig::new("String", "Object", &[("length", &[], "Int"), ("concat", &["String"], "String"), ("substr", &["Int", "Int"], "String")]),
+    ];
+    TypedGenerator { rng: Rng::new(seed), classes, names: 0, class: 0, level: 0, init: false, loops: 0, vars: Vec::new() }
+  }
+
+  pub fn program(&mut self) -> Program {
+    let builtins = self.classes.len();
+    for index in 0..1 + self.rng.below(USER_CLASSES.len()) {
+      let parent = match self.rng.below(10) {
+        0..=3 if index > 0 => USER_CLASSES[self.rng.below(index)],
+        4 | 5 => "IO",
+        _ => "Object",
+      };
+      self.classes.push(ClassSig::new(USER_CLASSES[index], parent, &[]));
+    }
+    self.classes.push(ClassSig::new("Main", "IO", &[]));
+
+    for class in builtins..self.classes.len() {
+      self.declare_features(class);
+    }
+    let classes = (builtins..self.classes.len()).map(|class| self.class(class)).collect();
+    Program { classes }
+  }
+
+  fn fresh(&mut self, prefix: &str) -> String {
+    self.names += 1;
+    format!("{prefix}{}", self.names)
+  }
+
+  fn index(&self, class: &str) -> usize {
+    self.classes.iter().position(|sig| sig.name == class).unwrap_or_else(|| panic!("Unknown class {class}"))
+  }
+
+  fn sig(&self, class: &str) -> &ClassSig {
+    &self.classes[self.index(class)]
+  }
+
+  /// Returns `true` if the type is the class or one of its subclasses
+  fn conforms(&self, type_name: &str, class: &str) -> bool {
+    let mut current = type_name;
+    loop {
+      if current == class {
+        return true;
+      }
+      if current == "Object" {
+        return false;
+      }
+      current = &self.sig(current).parent;
+    }
+  }
+
+  /// Types of the values the expressions may have: every class but `Main`. An attribute initialiser may only create
+  /// objects of the classes declared before its own.
+  fn value_types(&self) -> Vec<String> {
+    let classes = if self.init { &self.classes[..self.class] } else { &self.classes[..] };
+    classes.iter().map(|sig| sig.name.clone()).filter(|name| name != "Main").collect()
+  }
+
+  fn value_type(&mut self) -> String {
+    let types = self.value_types();
+    self.rng.pick(&types).clone()
+  }
+
+  /// The classes from `Object` down to the class
+  fn ancestors(&self, class: usize) -> Vec<usize> {
+    let mut ancestors = vec![class];
+    while self.classes[ancestors[0]].name != "Object" {
+      ancestors.insert(0, self.index(&self.classes[ancestors[0]].parent));
+    }
+    ancestors
+  }
+
+  fn declare_features(&mut self, class: usize) {
+    // The types declared before the class may be created by the attribute initialisers
+    self.class = class;
+    self.init = true;
+    for _ in 0..self.rng.below(3) {
+      let name = self.fresh("a");
+      let type_name = self.value_type();
+      self.classes[class].attributes.push((name, type_name));
+    }
+    self.init = false;
+
+    if self.classes[class].name == "Main" {
+      let main = MethodSig { name: "main".to_string(), formals: Vec::new(), return_type: "Object".to_string(), level: MAIN_LEVEL };
+      self.classes[class].methods.push(main);
+    }
+    for _ in 0..self.rng.below(3) {
+      let name = self.fresh("m");
+      let formals = (0..self.rng.below(3)).map(|_| self.value_type()).collect();
+      let return_type = if self.rng.chance(10) { "SELF_TYPE".to_string() } else { self.value_type() };
+      let level = 1 + self.rng.below(MAIN_LEVEL - 1);
+      self.classes[class].methods.push(MethodSig { name, formals, return_type, level });
+    }
+    let ancestors = self.ancestors(class);
+    let inherited: Vec<MethodSig> = ancestors[..ancestors.len() - 1].iter()
+        .flat_map(|ancestor| self.classes[*ancestor].methods.clone())
+        .filter(|method| method.level > 0)
+        .collect();
+    for method in inherited {
+      if self.rng.chance(30) {
+        self.classes[class].overrides.push(method);
+      }
+    }
+  }
+
+  fn class(&mut self, class: usize) -> Class {
+    self.class = class;
+    let sig = self.classes[class].clone();
+    let attributes: Vec<Var> = self.ancestors(class).iter()
+        .flat_map(|ancestor| self.classes[*ancestor].attributes.clone())
+        .map(|(name, type_name)| Var { name, type_name, assignable: true })
+        .collect();
+
+    // The attributes of the ancestors are set first, then those of the class in order
+    let inherited = attributes.len() - sig.attributes.len();
+    let mut features = Vec::new();
+    for (index, (name, type_name)) in sig.attributes.iter().enumerate() {
+      // Attributes which hold objects are always set, so they are rarely void
+      self.init = true;
+      self.level = INIT_LEVEL;
+      self.vars = attributes[..inherited + index].to_vec();
+      let init = if self.is_basic(type_name) && self.rng.chance(50) { None } else { Some(self.expr(type_name, MAX_DEPTH)) };
+      self.init = false;
+      features.push(Feature::Attribute { name: name.clone(), type_name: type_name.clone(), init });
+    }
+    for method in sig.methods.iter().chain(&sig.overrides) {
+      self.level = method.level;
+      self.vars = attributes.clone();
+      let formals: Vec<(String, String)> = method.formals.iter().map(|type_name| (self.fresh("p"), type_name.clone())).collect();
+      self.vars.extend(formals.iter().map(|(name, type_name)| Var { name: name.clone(), type_name: type_name.clone(), assignable: true }));
+      let body = if method.name == "main" { self.block("Object", MAX_DEPTH, 3, 8) } else { self.expr(&method.return_type, MAX_DEPTH) };
+      features.push(Feature::Method { name: method.name.clone(), formals, return_type: method.return_type.clone(), body });
+    }
+    Class { name: sig.name, parent: sig.parent, features }
+  }
+
+  fn is_basic(&self, type_name: &str) -> bool {
+    ["Int", "Bool", "String"].contains(&type_name)
+  }
+
+  /// An expression whose type conforms to the given one
+  fn expr(&mut self, type_name: &str, depth: usize) -> Expr {
+    if type_name == "SELF_TYPE" {
+      return match self.rng.below(3) {
+        0 => Expr::New("SELF_TYPE".to_string()),
+        1 if depth > 0 => {
+          let mut body = self.statements(depth - 1, 1, 2);
+          body.push(Expr::Var("self".to_string()));
+          Expr::Block(body)
+        }
+        _ => Expr::Var("self".to_string()),
+      };
+    }
+    if depth == 0 || self.rng.chance(25) {
+      return self.leaf(type_name);
+    }
+    let depth = depth - 1;
+    let expr = match self.rng.below(12) {
+      0 => Some(Expr::If(Box::new(self.expr("Bool", depth)), Box::new(self.expr(type_name, depth)), Box::new(self.expr(type_name, depth)))),
+      1 => Some(self.block(type_name, depth, 1, 2)),
+      2 => Some(self.let_expr(type_name, depth)),
+      3 => Some(self.case(type_name, depth)),
+      4 | 5 => self.dispatch(type_name, depth),
+      6 => self.assign(type_name, depth),
+      _ => None,
+    };
+    expr.unwrap_or_else(|| self.typed(type_name, depth))
+  }
+
+  /// An expression made with the operators of the type, or a leaf
+  fn typed(&mut self, type_name: &str, depth: usize) -> Expr {
+    let int = |generator: &mut Self| Box::new(generator.expr("Int", depth));
+    match type_name {
+      "Int" => match self.rng.below(5) {
+        0..=2 => Expr::Binary(["+", "-", "*"][self.rng.below(3)], int(self), int(self)),
+        // A division by zero stops the program, which the backends must all report
+        3 => {
+          let divisor = if self.rng.chance(75) { Box::new(Expr::Int(1 + self.rng.below(9) as i32)) } else { int(self) };
+          Expr::Binary("/", int(self), divisor)
+        }
+        _ => Expr::Unary("~", int(self)),
+      },
+      "Bool" => match self.rng.below(5) {
+        0 => Expr::Binary(["<", "<="][self.rng.below(2)], int(self), int(self)),
+        // A basic type is only compared with itself, an expression of type `Object` may have one
+        1 => {
+          let types: Vec<String> = self.value_types().into_iter().filter(|type_name| type_name != "Object").collect();
+          let type_name = self.rng.pick(&types).clone();
+          Expr::Binary("=", Box::new(self.expr(&type_name, depth)), Box::new(self.expr(&type_name, depth)))
+        }
+        2 => Expr::Unary("not", Box::new(self.expr("Bool", depth))),
+        3 => {
+          let type_name = self.value_type();
+          Expr::Unary("isvoid", Box::new(self.expr(&type_name, depth)))
+        }
+        _ => self.leaf(type_name),
+      },
+      "String" => {
+        let receiver = Box::new(self.expr("String", depth));
+        Expr::Dispatch { receiver: Some(receiver), static_type: None, method: "concat".to_string(), args: vec![self.expr("String", depth)] }
+      }
+      _ => self.leaf(type_name),
+    }
+  }
+
+  /// A variable, a constant or a new object
+  fn leaf(&mut self, type_name: &str) -> Expr {
+    let mut vars: Vec<String> = self.vars.iter().filter(|var| self.conforms(&var.type_name, type_name)).map(|var| var.name.clone()).collect();
+    if self.conforms(&self.classes[self.class].name, type_name) {
+      vars.push("self".to_string());
+    }
+    if !vars.is_empty() && self.rng.chance(50) {
+      return Expr::Var(self.rng.pick(&vars).clone());
+    }
+    match type_name {
+      "Int" if self.rng.chance(10) => Expr::Int(self.rng.below(i32::MAX as usize + 1) as i32),
+      "Int" => Expr::Int(self.rng.below(100) as i32),
+      "Bool" => Expr::Bool(self.rng.chance(50)),
+      "String" => {
+        let parts: String = (0..self.rng.below(4)).map(|_| *self.rng.pick(&STRING_PARTS)).collect();
+        Expr::Str(parts)
+      }
+      _ => {
+        let types: Vec<String> = self.value_types().into_iter().filter(|class| self.conforms(class, type_name)).collect();
+        let class = self.rng.pick(&types).clone();
+        match class.as_str() {
+          "Int" | "Bool" | "String" if self.rng.chance(70) => self.leaf(&class),
+          _ => Expr::New(class),
+        }
+      }
+    }
+  }
+
+  /// Between `min` and `max` statements, then an expression of the type
+  fn block(&mut self, type_name: &str, depth: usize, min: usize, max: usize) -> Expr {
+    let mut body = self.statements(depth, min, max);
+    body.push(self.expr(type_name, depth));
+    Expr::Block(body)
+  }
+
+  fn statements(&mut self, depth: usize, min: usize, max: usize) -> Vec<Expr> {
+    (0..min + self.rng.below(max - min + 1)).map(|_| self.statement(depth)).collect()
+  }
+
+  /// An expression run for its effects: printing, a loop, an assignment or a call
+  fn statement(&mut self, depth: usize) -> Expr {
+    match self.rng.below(7) {
+      0..=2 => {
+        let (method, arg) = if self.rng.chance(50) { ("out_int", self.expr("Int", depth)) } else { ("out_string", self.expr("String", depth)) };
+        let receiver = if self.conforms(&self.classes[self.class].name, "IO") && self.rng.chance(70) { None } else { Some(Box::new(Expr::New("IO".to_string()))) };
+        Expr::Dispatch { receiver, static_type: None, method: method.to_string(), args: vec![arg] }
+      }
+      // Loops don't nest in the methods Main.main calls, to bound the time programs take
+      3 if !self.init && depth > 0 && self.loops < if self.level == MAIN_LEVEL { 2 } else { 1 } => {
+        let counter = self.fresh("i");
+        let count = 1 + self.rng.below(4) as i32;
+        self.vars.push(Var { name: counter.clone(), type_name: "Int".to_string(), assignable: false });
+        self.loops += 1;
+        let body = self.block("Object", depth - 1, 0, 1);
+        self.loops -= 1;
+        self.vars.pop();
+        Expr::Loop { counter, count, body: Box::new(body) }
+      }
+      4 => self.assign("Object", depth).unwrap_or_else(|| self.expr("Object", depth)),
+      5 => self.dispatch("Object", depth).unwrap_or_else(|| self.expr("Object", depth)),
+      _ => {
+        let type_name = self.value_type();
+        self.expr(&type_name, depth)
+      }
+    }
+  }
+
+  fn let_expr(&mut self, type_name: &str, depth: usize) -> Expr {
+    let vars = self.vars.len();
+    let mut bindings = Vec::new();
+    for _ in 0..1 + self.rng.below(2) {
+      let name = self.fresh("x");
+      let var_type = self.value_type();
+      let init = if self.is_basic(&var_type) && self.rng.chance(40) { None } else { Some(self.expr(&var_type, depth)) };
+      self.vars.push(Var { name: name.clone(), type_name: var_type.clone(), assignable: true });
+      bindings.push((name, var_type, init));
+    }
+    let body = self.expr(type_name, depth);
+    self.vars.truncate(vars);
+    Expr::Let(bindings, Box::new(body))
+  }
+
+  fn case(&mut self, type_name: &str, depth: usize) -> Expr {
+    let value_type = self.value_type();
+    let value = self.expr(&value_type, depth);
+    let mut types: Vec<String> = Vec::new();
+    for _ in 0..1 + self.rng.below(3) {
+      let branch_type = self.value_type();
+      if !types.contains(&branch_type) {
+        types.push(branch_type);
+      }
+    }
+    // Without a branch for `Object` the value may match none, which stops the program
+    if self.rng.chance(95) && !types.iter().any(|branch_type| branch_type == "Object") {
+      types.push("Object".to_string());
+    }
+    let mut branches = Vec::new();
+    for branch_type in types {
+      let name = self.fresh("c");
+      self.vars.push(Var { name: name.clone(), type_name: branch_type.clone(), assignable: true });
+      let body = self.expr(type_name, depth);
+      self.vars.pop();
+      branches.push((name, branch_type, body));
+    }
+    Expr::Case(Box::new(value), branches)
+  }
+
+  /// A call of a method of a lower level whose result conforms to the type, if there is one
+  fn dispatch(&mut self, type_name: &str, depth: usize) -> Option<Expr> {
+    let class = self.classes[self.class].name.clone();
+    let value_types = self.value_types();
+    let mut candidates = Vec::new();
+    for sig in &self.classes {
+      for method in &sig.methods {
+        if method.level >= self.level {
+          continue;
+        }
+        // The type of the receiver, which the method returns when it returns `SELF_TYPE`
+        let receiver_type = match method.return_type.as_str() {
+          "SELF_TYPE" if type_name == "Object" => sig.name.clone(),
+          "SELF_TYPE" if self.conforms(type_name, &sig.name) => type_name.to_string(),
+          "SELF_TYPE" => continue,
+          return_type if self.conforms(return_type, type_name) => sig.name.clone(),
+          _ => continue,
+        };
+        if value_types.contains(&receiver_type) || self.conforms(&class, &receiver_type) {
+          candidates.push((sig.name.clone(), method.clone(), receiver_type));
+        }
+      }
+    }
+    if candidates.is_empty() {
+      return None;
+    }
+    let (defining_class, method, receiver_type) = self.rng.pick(&candidates).clone();
+
+    let (receiver, static_type) = if self.conforms(&class, &receiver_type) && (!value_types.contains(&receiver_type) || self.rng.chance(40)) {
+      (None, None)
+    } else {
+      let static_type = self.rng.chance(20).then_some(defining_class);
+      (Some(Box::new(self.expr(&receiver_type, depth))), static_type)
+    };
+    // Most substrings are taken where any string has them, as an index out of range stops the program
+    let args = if method.name == "substr" && self.rng.chance(95) {
+      vec![Expr::Int(0), Expr::Int(0)]
+    } else {
+      method.formals.iter().map(|formal| self.expr(formal, depth)).collect()
+    };
+    Some(Expr::Dispatch { receiver, static_type, method: method.name, args })
+  }
+
+  /// An assignment of a variable whose type conforms to the type, if there is one
+  fn assign(&mut self, type_name: &str, depth: usize) -> Option<Expr> {
+    // Strings assigned in loops, or in the methods called in loops, could double in size at each iteration
+    let strings = self.level == MAIN_LEVEL && self.loops == 0;
+    let vars: Vec<Var> = self.vars.iter()
+        .filter(|var| var.assignable && self.conforms(&var.type_name, type_name) && (strings || !self.conforms("String", &var.type_name)))
+        .cloned()
+        .collect();
+    if vars.is_empty() {
+      return None;
+    }
+    let var = self.rng.pick(&vars).clone();
+    Some(Expr::Assign(var.name, Box::new(self.expr(&var.type_name, depth))))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::difftest::generate::TypedGenerator;
+  use crate::difftest::{run_backends, Backend};
+
+  #[test]
+  fn test_generated_programs_check() {
+    for seed in 0..100 {
+      let program = TypedGenerator::new(seed).program().to_string();
+      let outcomes = run_backends(&program, &[Backend::Interpreter]).unwrap_or_else(|e| panic!("Seed {seed}: {e}\n{program}"));
+      assert!(outcomes[0].code.is_some(), "Seed {seed}: {}", outcomes[0].output);
+    }
+    assert_eq!(TypedGenerator::new(7).program(), TypedGenerator::new(7).program());
+  }
+}
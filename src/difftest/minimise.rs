@@ -0,0 +1,136 @@
+//! Greedy minimisation of generated programs.
+//!
+//! Classes, features and expressions are removed or replaced by simpler ones, one at a time, as long as the program
+//! keeps the behaviour looked for, with the driver of `reduce`. The reductions are those of the generated programs
+//! rather than of their AST, as they must never add a loop iteration or a call: the backends would run forever a loop
+//! whose counter is no longer incremented. So the programs still terminate, and the callback type checks each of them.
+
+use crate::difftest::generate::{Expr, Feature, Program};
+use crate::reduce::{reduce_greedily, replace_expression, Tree};
+
+/// The smallest program found by the reductions for which `keep` is `true`, the program itself being one
+pub fn minimise(program: &Program, keep: impl FnMut(&Program) -> bool) -> Program {
+  reduce_greedily(program.clone(), reductions, keep)
+}
+
+impl Tree for Expr {
+  fn parts_mut(&mut self) -> Vec<&mut Self> {
+    self.children_mut()
+  }
+}
+
+/// The programs made by removing the class or feature numbered `site`, but `Main` and `main`, or by simplifying the
+/// expression, or `None` past the last one
+fn reductions(program: &Program, site: usize) -> Option<Vec<Program>> {
+  let classes = program.classes.len();
+  if site < classes {
+    if program.classes[site].name == "Main" {
+      return Some(Vec::new());
+    }
+    let mut candidate = program.clone();
+    candidate.classes.remove(site);
+    return Some(vec![candidate]);
+  }
+
+  let mut site = site - classes;
+  for (class_index, class) in program.classes.iter().enumerate() {
+    if site >= class.features.len() {
+      site -= class.features.len();
+      continue;
+    }
+    let mut candidates = Vec::new();
+    let mut removed = program.clone();
+    match removed.classes[class_index].features.remove(site) {
+      Feature::Method { name, .. } if name == "main" => {}
+      _ => candidates.push(removed),
+    }
+    if let Feature::Attribute { init: Some(_), .. } = &class.features[site] {
+      let mut candidate = program.clone();
+      if let Feature::Attribute { init, .. } = &mut candidate.classes[class_index].features[site] {
+        *init = None;
+      }
+      candidates.push(candidate);
+    }
+    return Some(candidates);
+  }
+  replace_expression(program, site, expressions, simpler)
+}
+
+/// The roots of the expressions numbered by `reductions`: the initialisations and the bodies of the features
+fn expressions(program: &mut Program) -> Vec<&mut Expr> {
+  let features = program.classes.iter_mut().flat_map(|class| class.features.iter_mut());
+  features
+      .filter_map(|feature| match feature {
+        Feature::Attribute { init, .. } => init.as_mut(),
+        Feature::Method { body, .. } => Some(body),
+      })
+      .collect()
+}
+
+/// Expressions which may replace this one: with a part removed, one run of its loop, one of its parts, or a constant
+fn simpler(expr: &mut Expr) -> Vec<Expr> {
+  let mut candidates = Vec::new();
+  match expr {
+    Expr::Block(body) if body.len() > 1 => {
+      candidates.extend((0..body.len()).map(|index| {
+        let mut body = body.clone();
+        body.remove(index);
+        Expr::Block(body)
+      }));
+    }
+    Expr::Let(bindings, body) => {
+      for index in 0..bindings.len() {
+        let mut reduced = bindings.clone();
+        if reduced[index].2.take().is_none() {
+          if reduced.len() == 1 {
+            continue;
+          }
+          reduced.remove(index);
+        }
+        candidates.push(Expr::Let(reduced, body.clone()));
+      }
+    }
+    Expr::Case(value, branches) if branches.len() > 1 => {
+      candidates.extend((0..branches.len()).map(|index| {
+        let mut branches = branches.clone();
+        branches.remove(index);
+        Expr::Case(value.clone(), branches)
+      }));
+    }
+    Expr::Loop { counter, count, body } if *count > 1 => {
+      candidates.push(Expr::Loop { counter: counter.clone(), count: 1, body: body.clone() });
+    }
+    Expr::Dispatch { receiver, static_type: Some(_), method, args } => {
+      candidates.push(Expr::Dispatch { receiver: receiver.clone(), static_type: None, method: method.clone(), args: args.clone() });
+    }
+    _ => {}
+  }
+  candidates.extend(expr.children_mut().into_iter().map(|child| child.clone()));
+  for constant in [Expr::Int(0), Expr::Bool(false), Expr::Str(String::new())] {
+    if *expr != constant {
+      candidates.push(constant);
+    }
+  }
+  candidates
+}
+
+#[cfg(test)]
+mod test {
+  use crate::difftest::generate::{Program, TypedGenerator};
+  use crate::difftest::minimise::minimise;
+  use crate::difftest::{run_backends, Backend};
+
+  #[test]
+  fn test_minimise() {
+    // Programs which check and print something, of which there is one of a single statement
+    let prints = |program: &Program| {
+      run_backends(&program.to_string(), &[Backend::Interpreter]).is_ok_and(|outcomes| outcomes[0].code == Some(0) && !outcomes[0].output.is_empty())
+    };
+    let program = (0..).map(|seed| TypedGenerator::new(seed).program()).find(|program| program.classes.len() > 2 && prints(program)).expect("A program prints");
+    let minimised = minimise(&program, prints);
+    assert!(prints(&minimised));
+    let text = minimised.to_string();
+    assert!(text.starts_with("class Main inherits IO {\n  main() : Object { out_"), "{text}");
+    assert_eq!(text.lines().count(), 4, "{text}");
+  }
+}
@@ -0,0 +1,274 @@
+//! Differential tests of the backends on generated programs.
+//!
+//! Each program of `TypedGenerator` is run by every backend, which must all give the output, runtime error and exit
+//! code of the first one, the IR interpreter. A program on which a backend differs is minimised, keeping the backends
+//! which differ, before it is reported.
+
+pub mod generate;
+pub mod minimise;
+
+use crate::difftest::generate::TypedGenerator;
+use crate::difftest::minimise::minimise;
+use codegen::target::Target;
+use codegen::toolchain::{build_executable, has_compiler, GcOptions};
+use codegen::wasm;
+use ir::interp;
+use ir::lower::lower_program;
+use ir::model::module::Module;
+use ir::opt::{OptLevel, PassManager};
+use parser::get_ast_from_program_text;
+use semantic::gen::{analyze_program, CheckOptions};
+use semantic::models::program::CheckedProgram;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// File the runtime errors name
+const FILE: &str = "difftest.cl";
+
+/// Longest time an executable may run, much more than the generated programs take
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of executables run by this process, so each has its own directory
+static NATIVE_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+  Interpreter,
+  Optimised,
+  Vm,
+  Mips,
+  Wasm,
+  X86_64,
+}
+
+impl Backend {
+  /// The backends which run on this machine, all but x86-64 without a C compiler, the reference first
+  pub fn available() -> Vec<Backend> {
+    let mut backends = vec![Backend::Interpreter, Backend::Optimised, Backend::Vm, Backend::Mips, Backend::Wasm];
+    if has_compiler() {
+      backends.push(Backend::X86_64);
+    }
+    backends
+  }
+}
+
+impl Display for Backend {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Backend::Interpreter => "interpreter",
+      Backend::Optimised => "interpreter -O2",
+      Backend::Vm => "vm",
+      Backend::Mips => "mips",
+      Backend::Wasm => "wasm",
+      Backend::X86_64 => "x86-64",
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// What a program does when run: its output followed by its runtime error if any, and its exit code, none if it was
+/// stopped or the backend failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+  pub output: String,
+  pub code: Option<i32>,
+}
+
+impl Outcome {
+  /// The outcome of a program run in this process, whose runtime error is written after its output
+  fn of_run(output: Vec<u8>, result: Result<(), impl Display>) -> Self {
+    let mut output = String::from_utf8_lossy(&output).to_string();
+    match result {
+      Ok(()) => Outcome { output, code: Some(0) },
+      Err(e) => {
+        output.push_str(&format!("{e}\n"));
+        Outcome { output, code: Some(1) }
+      }
+    }
+  }
+
+  fn failed(message: String) -> Self {
+    Outcome { output: message, code: None }
+  }
+}
+
+impl Display for Outcome {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.code {
+      Some(code) => writeln!(f, "exit code {code}")?,
+      None => writeln!(f, "no exit code")?,
+    }
+    write!(f, "{}", self.output)
+  }
+}
+
+/// A program on which some backends differ from the reference
+#[derive(Debug)]
+pub struct Divergence {
+  pub seed: u64,
+  pub program: String,
+  pub outcomes: Vec<(Backend, Outcome)>, // the reference first, then the backends which differ
+}
+
+/// Generates a program from each seed from `seed` on and runs it on the backends, the first being the reference.
+/// Returns the programs on which backends differ, minimised, or an error if a generated program doesn't type check.
+pub fn difftest(seed: u64, runs: u64, backends: &[Backend]) -> Result<Vec<Divergence>, String> {
+  let mut divergences = Vec::new();
+  for run in 0..runs {
+    let seed = seed.wrapping_add(run);
+    let program = TypedGenerator::new(seed).program();
+    let outcomes = run_backends(&program.to_string(), backends).map_err(|e| format!("The program of seed {seed} doesn't check:\n{e}\n{program}"))?;
+    let differing = differing(backends, &outcomes);
+    if differing.is_empty() {
+      continue;
+    }
+
+    let backends: Vec<Backend> = backends[..1].iter().chain(&differing).copied().collect();
+    let minimised = minimise(&program, |candidate| {
+      run_backends(&candidate.to_string(), &backends).is_ok_and(|outcomes| self::differing(&backends, &outcomes) == differing)
+    });
+    let outcomes = run_backends(&minimised.to_string(), &backends).expect("The minimised program checks");
+    divergences.push(Divergence { seed, program: minimised.to_string(), outcomes: backends.into_iter().zip(outcomes).collect() });
+  }
+  Ok(divergences)
+}
+
+/// The backends whose outcome differs from that of the first
+fn differing(backends: &[Backend], outcomes: &[Outcome]) -> Vec<Backend> {
+  backends.iter().zip(outcomes).skip(1).filter(|(_, outcome)| **outcome != outcomes[0]).map(|(backend, _)| *backend).collect()
+}
+
+/// Runs the program on each backend, without input. A panic of a backend is its outcome.
+pub fn run_backends(source: &str, backends: &[Backend]) -> Result<Vec<Outcome>, String> {
+  let program = get_ast_from_program_text(source)?;
+  let checked = analyze_program(&program, &CheckOptions::default())
+      .map_err(|diagnostics| diagnostics.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n"))?;
+  let mut module = lower_program(&checked);
+  for class in &mut module.classes {
+    class.file = Some(FILE.to_string());
+  }
+
+  let outcomes = backends.iter()
+      .map(|backend| {
+        catch_unwind(AssertUnwindSafe(|| run_backend(*backend, &checked, &module))).unwrap_or_else(|panic| {
+          let message = panic.downcast_ref::<&str>().map(|message| message.to_string());
+          Outcome::failed(format!("Panicked: {}", message.or_else(|| panic.downcast_ref::<String>().cloned()).unwrap_or_default()))
+        })
+      })
+      .collect();
+  Ok(outcomes)
+}
+
+fn run_backend(backend: Backend, checked: &CheckedProgram, module: &Module) -> Outcome {
+  let mut output = Vec::new();
+  let result = match backend {
+    Backend::Interpreter => {
+      let (_, result) = interp::run(module, &mut io::empty(), &mut output);
+      Ok(Outcome::of_run(output, result))
+    }
+    Backend::Optimised => {
+      let mut optimised = module.clone();
+      PassManager::for_level(OptLevel::O2).run(&mut optimised).map(|()| {
+        let (_, result) = interp::run(&optimised, &mut io::empty(), &mut output);
+        Outcome::of_run(output, result)
+      })
+    }
+    Backend::Vm => vm::compile::compile_program(checked).map(|mut program| {
+      for class in &mut program.classes {
+        class.file = Some(FILE.to_string());
+      }
+      let (_, result) = vm::machine::run(&program, &mut io::empty(), &mut output);
+      Outcome::of_run(output, result)
+    }),
+    // The simulated runtime writes the errors itself
+    Backend::Mips => mips::asm::assemble(&Target::Mips.emit(module)).map(|program| {
+      let (_, result) = mips::machine::run(&program, &mut io::empty(), &mut output);
+      let mut outcome = Outcome { output: String::from_utf8_lossy(&output).to_string(), code: None };
+      match result {
+        Ok(code) => outcome.code = Some(code),
+        Err(e) => outcome.output.push_str(&format!("{e}\n")),
+      }
+      outcome
+    }),
+    Backend::Wasm => wasm::assemble(&Target::Wasm.emit(module)).and_then(|binary| wasm::binary::decode(&binary)).and_then(|module| {
+      let mut errors = Vec::new();
+      let code = wasm::interp::run(&module, &mut io::empty(), &mut output, &mut errors)?;
+      output.extend(errors);
+      Ok(Outcome { output: String::from_utf8_lossy(&output).to_string(), code: Some(code) })
+    }),
+    Backend::X86_64 => run_native(module),
+  };
+  result.unwrap_or_else(Outcome::failed)
+}
+
+/// Builds the program for x86-64 and runs it, with its output and error output written to files, which can't fill up
+/// like pipes while the program is waited for
+fn run_native(module: &Module) -> Result<Outcome, String> {
+  let dir = env::temp_dir().join(format!("cool-difftest-{}-{}", process::id(), NATIVE_RUNS.fetch_add(1, Ordering::Relaxed)));
+  fs::create_dir_all(&dir).map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+  let result = (|| {
+    let executable = dir.join("program");
+    build_executable(module, Target::X86_64, GcOptions::default(), &executable)?;
+    let file = |name: &str| fs::File::create(dir.join(name)).map_err(|e| format!("Unable to create {name}: {e}"));
+    let mut child = Command::new(&executable)
+        .stdin(Stdio::null())
+        .stdout(file("stdout")?)
+        .stderr(file("stderr")?)
+        .spawn()
+        .map_err(|e| format!("Unable to run the program: {e}"))?;
+
+    let start = Instant::now();
+    let status = loop {
+      if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+        break Some(status);
+      }
+      if start.elapsed() > TIMEOUT {
+        let _ = child.kill();
+        let _ = child.wait();
+        break None;
+      }
+      thread::sleep(Duration::from_millis(5));
+    };
+    let read = |name: &str| fs::read(dir.join(name)).map(|bytes| String::from_utf8_lossy(&bytes).to_string()).unwrap_or_default();
+    let mut output = read("stdout") + &read("stderr");
+    if status.is_none() {
+      output.push_str("Timed out\n");
+    }
+    Ok(Outcome { output, code: status.and_then(|status| status.code()) })
+  })();
+  let _ = fs::remove_dir_all(&dir);
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use crate::difftest::{difftest, Backend};
+  use codegen::toolchain::has_compiler;
+
+  #[test]
+  fn test_backends_agree() {
+    let backends = [Backend::Interpreter, Backend::Optimised, Backend::Vm, Backend::Mips, Backend::Wasm];
+    let divergences = difftest(0, 25, &backends).unwrap_or_else(|e| panic!("{e}"));
+    if let Some(divergence) = divergences.first() {
+      let outcomes: Vec<String> = divergence.outcomes.iter().map(|(backend, outcome)| format!("{backend}: {outcome}")).collect();
+      panic!("Seed {} diverges:\n{}\n{}", divergence.seed, divergence.program, outcomes.join("\n"));
+    }
+  }
+
+  #[test]
+  fn test_native_backend_agrees() {
+    if !has_compiler() {
+      eprintln!("Skipping native code tests, no C compiler found");
+      return;
+    }
+    let divergences = difftest(0, 3, &[Backend::Interpreter, Backend::X86_64]).unwrap_or_else(|e| panic!("{e}"));
+    assert!(divergences.is_empty(), "{divergences:?}");
+  }
+}
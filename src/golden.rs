@@ -0,0 +1,125 @@
+//! Golden tests of every phase on the programs in `test_resources/programs`.
+//!
+//! Each `X.cl` is run through the whole pipeline and each phase's output is compared with a file next to it:
+//!
+//! * `X.tokens`, the tokens of the lexer, one per line
+//! * `X.ast`, the parsed program printed back as COOL, or the parse error
+//! * `X.err`, the diagnostics of the semantic checks, then the runtime error if the program stops with one
+//! * `X.out`, what the program writes when run by the IR interpreter, reading `X.in` if there is one
+//!
+//! A program made of several files lists the others in `X.deps`, one per line, whose classes are added to its own;
+//! those files have no golden files of their own. `parser::programs` finds the programs, for the tests of the other
+//! crates too.
+//!
+//! A missing file is an empty one. Run the tests with `BLESS=1` to write the files from the current output, after a
+//! change of the output or to add a program: new `.cl` files are found without any change to the tests.
+
+use ir::interp;
+use ir::lower::lower_program;
+use lexer::iter::token::TokenIter;
+use parser::get_ast_from_program_text;
+use parser::model::class::ParseClass;
+use parser::printer::print_program;
+use parser::programs::{find_programs, TestProgram};
+use semantic::gen::{analyze_program, CheckOptions};
+use semantic::lint::suppression::Suppressions;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const PROGRAMS: &str = "test_resources/programs";
+
+/// The outputs of the phases, by the extension of their golden file
+fn run_phases(test_program: &TestProgram) -> Vec<(&'static str, String)> {
+  let path = &test_program.files[0];
+  let source = fs::read_to_string(path).expect("Couldn't read the program");
+  let tokens: String = TokenIter::from_program_text(&source).map(|token| format!("{token}\n")).collect();
+
+  let mut program = match get_ast_from_program_text(&source) {
+    Ok(program) => program,
+    Err(e) => return vec![("tokens", tokens), ("ast", format!("{e}\n")), ("err", String::new()), ("out", String::new())],
+  };
+  let ast = print_program(&program);
+  let suppressions = Suppressions::from_source(&source).for_classes(program.classes.iter().map(ParseClass::get_name));
+  for dependency in &test_program.files[1..] {
+    let text = fs::read_to_string(dependency).expect("Couldn't read the dependency");
+    let dependency = get_ast_from_program_text(&text).unwrap_or_else(|e| panic!("{}: {e}", dependency.display()));
+    program.classes.extend(dependency.classes);
+  }
+
+  let options = CheckOptions { suppressions, ..CheckOptions::default() };
+  let (checked, diagnostics) = match analyze_program(&program, &options) {
+    Ok(checked) => {
+      let warnings = checked.warnings.clone();
+      (Some(checked), warnings)
+    }
+    Err(diagnostics) => (None, diagnostics),
+  };
+  let mut err: String = diagnostics.iter().map(|diagnostic| format!("{diagnostic}\n")).collect();
+
+  let mut out = Vec::new();
+  if let Some(checked) = checked {
+    let mut module = lower_program(&checked);
+    for class in &mut module.classes {
+      class.file = Some(path.display().to_string());
+    }
+    if let (_, Err(e)) = interp::run(&module, &mut test_program.input.as_bytes(), &mut out) {
+      writeln!(err, "{e}").expect("Writing to a string doesn't fail");
+    }
+  }
+  let out = String::from_utf8(out).expect("Output must be UTF-8");
+
+  vec![("tokens", tokens), ("ast", ast), ("err", err), ("out", out)]
+}
+
+/// The programs of the directory, and of its subdirectories too
+fn find_all_programs(dir: &Path) -> Vec<TestProgram> {
+  let mut programs = find_programs(dir);
+  for entry in fs::read_dir(dir).expect("Couldn't read the programs") {
+    let path = entry.expect("Couldn't read the programs").path();
+    if path.is_dir() {
+      programs.extend(find_all_programs(&path));
+    }
+  }
+  programs
+}
+
+/// The lines that differ between the expected and the actual text, around the first difference
+fn diff(expected: &str, actual: &str) -> String {
+  let expected: Vec<&str> = expected.lines().collect();
+  let actual: Vec<&str> = actual.lines().collect();
+  let first = expected.iter().zip(&actual).take_while(|(e, a)| e == a).count();
+  let start = first.saturating_sub(2);
+  let mut text = format!("  first difference at line {}\n", first + 1);
+  for line in &expected[start..expected.len().min(first + 5)] {
+    writeln!(text, "  - {line}").expect("Writing to a string doesn't fail");
+  }
+  for line in &actual[start..actual.len().min(first + 5)] {
+    writeln!(text, "  + {line}").expect("Writing to a string doesn't fail");
+  }
+  text
+}
+
+#[test]
+fn test_golden_programs() {
+  let bless = std::env::var_os("BLESS").is_some_and(|value| value == "1");
+  let mut failures = Vec::new();
+  for program in find_all_programs(Path::new(PROGRAMS)) {
+    for (extension, actual) in run_phases(&program) {
+      let golden = program.files[0].with_extension(extension);
+      if bless {
+        if actual.is_empty() {
+          let _ = fs::remove_file(&golden);
+        } else {
+          fs::write(&golden, &actual).expect("Couldn't write the golden file");
+        }
+        continue;
+      }
+      let expected = fs::read_to_string(&golden).unwrap_or_default();
+      if expected != actual {
+        failures.push(format!("{} differs:\n{}", golden.display(), diff(&expected, &actual)));
+      }
+    }
+  }
+  assert!(failures.is_empty(), "{}\nRun the tests with BLESS=1 to update the golden files", failures.join("\n"));
+}
@@ -1,2 +1,361 @@
+mod cli;
+mod debug;
+mod difftest;
+#[cfg(test)]
+mod golden;
+mod profile;
+mod reduce;
+mod repl;
 
-fn main() {}
+use crate::cli::{parse_args, Command, Options, USAGE};
+use crate::debug::Debuggee;
+use crate::difftest::generate::TypedGenerator;
+use crate::difftest::Backend;
+use crate::repl::Session;
+use codegen::target::Target;
+use codegen::toolchain::{build_executable, mips_program};
+use ir::interp;
+use ir::lower::{lower_program, lower_program_with_options, LowerOptions};
+use ir::model::module::Module;
+use ir::opt::PassManager;
+use parser::fuzz::grammar::ProgramGenerator;
+use parser::get_ast_from_file_path;
+use parser::model::class::ParseClass;
+use parser::model::program::ParseProgram;
+use parser::printer::print_program;
+use semantic::gen::{analyze_program, CheckOptions};
+use semantic::lint::config::{LintConfig, CONFIG_FILE_NAME};
+use semantic::lint::suppression::Suppressions;
+use semantic::models::diagnostic::Diagnostic;
+use semantic::models::program::CheckedProgram;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::process;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use vm::bytecode::Program;
+use vm::compile::compile_program;
+use vm::disasm::disassemble;
+
+fn main() -> ExitCode {
+  let command = match parse_args(std::env::args().skip(1)) {
+    Ok(command) => command,
+    Err(e) => {
+      eprintln!("{e}\n\n{USAGE}");
+      return ExitCode::from(2);
+    }
+  };
+
+  let result = match command {
+    Command::Check(options) => check(&options),
+    Command::Ir(options) => print_ir(&options),
+    Command::Run(options) => run(&options),
+    Command::Build(options) => build(&options),
+    Command::Bytecode(options) => write_bytecode(&options),
+    Command::Disasm(options) => print_bytecode(&options),
+    Command::Repl(options) => repl(&options),
+    Command::Debug(options) => debug(&options),
+    Command::Profile(options) => profile(&options),
+    Command::Fuzz(options) => fuzz(&options),
+    Command::Generate(options) => generate(&options),
+    Command::Difftest(options) => run_difftest(&options),
+    Command::Reduce(options) => reduce(&options),
+  };
+
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn check(options: &Options) -> Result<(), String> {
+  let (program, suppressions, _) = parse_files(&options.files)?;
+  analyze(&program, suppressions, options)?;
+  Ok(())
+}
+
+fn print_ir(options: &Options) -> Result<(), String> {
+  print!("{}", compile(options)?);
+  Ok(())
+}
+
+fn run(options: &Options) -> Result<(), String> {
+  if options.vm || is_bytecode_file(options) {
+    let program = load_bytecode(options)?;
+    let (stats, result) = vm::machine::run(&program, &mut io::stdin().lock(), &mut io::stdout().lock());
+    if options.stats {
+      eprintln!("{stats}");
+    }
+    return result.map_err(|e| e.to_string());
+  }
+  if options.target == Target::Mips || is_assembly_file(options) {
+    return run_mips(options);
+  }
+
+  let module = compile(options)?;
+  let (stats, result) = interp::run(&module, &mut io::stdin().lock(), &mut io::stdout().lock());
+  if options.stats {
+    eprintln!("{stats}");
+  }
+  result.map_err(|e| e.to_string())
+}
+
+/// Runs the MIPS assembly of the program, or a `.s` file, with the simulator. Runtime errors are part of the output
+/// of the program, which exits with their code.
+fn run_mips(options: &Options) -> Result<(), String> {
+  let source = if is_assembly_file(options) {
+    let file = &options.files[0];
+    fs::read_to_string(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?
+  } else {
+    mips_program(&compile(options)?, options.gc)?
+  };
+  let program = mips::asm::assemble(&source)?;
+  let mut stdout = io::stdout().lock();
+  let (stats, result) = mips::machine::run(&program, &mut io::stdin().lock(), &mut stdout);
+  if options.stats {
+    eprintln!("{stats}");
+  }
+  let code = result.map_err(|e| e.to_string())?;
+  if code != 0 {
+    let _ = stdout.flush();
+    process::exit(code);
+  }
+  Ok(())
+}
+
+/// Compiles the program to an executable, or to assembly with `-S`
+fn build(options: &Options) -> Result<(), String> {
+  let module = compile(options)?;
+  let extension = if options.emit_asm { options.target.extension() } else { options.target.executable_extension() };
+  let default_output = options.files[0].with_extension(extension);
+  let output = options.output.as_ref().unwrap_or(&default_output);
+  if options.emit_asm {
+    // MIPS assembly includes the runtime, which the collector is chosen for
+    let assembly = if options.target == Target::Mips { mips_program(&module, options.gc)? } else { options.target.emit(&module) };
+    return fs::write(output, assembly).map_err(|e| format!("Unable to write {}: {e}", output.display()));
+  }
+  build_executable(&module, options.target, options.gc, output)
+}
+
+/// Compiles the program to a `.coolb` file
+fn write_bytecode(options: &Options) -> Result<(), String> {
+  let program = load_bytecode(options)?;
+  let default_output = options.files[0].with_extension("coolb");
+  let output = options.output.as_ref().unwrap_or(&default_output);
+  fs::write(output, vm::file::write(&program)).map_err(|e| format!("Unable to write {}: {e}", output.display()))
+}
+
+fn print_bytecode(options: &Options) -> Result<(), String> {
+  print!("{}", disassemble(&load_bytecode(options)?));
+  Ok(())
+}
+
+fn repl(options: &Options) -> Result<(), String> {
+  let mut session = Session::default();
+  for file in &options.files {
+    let source = fs::read_to_string(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+    session.load(&source).map_err(|e| format!("{}: {e}", file.display()))?;
+  }
+  session.run(&mut io::stdin().lock(), &mut io::stdout().lock()).map_err(|e| e.to_string())
+}
+
+/// Runs the program under the debugger, driven from the console or by an editor with `--dap`
+fn debug(options: &Options) -> Result<(), String> {
+  let (program, suppressions, _) = parse_files(&options.files)?;
+  let checked = analyze(&program, suppressions, options)?;
+  let module = lower_program_with_options(&checked, &LowerOptions { debug_info: true });
+  let debuggee = Debuggee::new(module, program.clone(), options.entry.clone(), &options.files)?;
+  let result = if options.dap {
+    debug::dap::serve(&debuggee, &mut io::stdin().lock(), &mut io::stdout().lock())
+  } else {
+    debug::console::run(&debuggee, &mut io::stdin().lock(), &mut io::stdout())
+  };
+  result.map_err(|e| e.to_string())
+}
+
+/// Runs the program with the IR interpreter, then prints its profile and writes the coverage and call stacks asked for
+fn profile(options: &Options) -> Result<(), String> {
+  let module = compile(options)?;
+  let (profile, result) = interp::run_profiled(&module, &mut io::stdin().lock(), &mut io::stdout().lock());
+  eprint!("{profile}");
+  if let Some(path) = &options.lcov {
+    let lcov = profile::lcov(&profile, &module, &options.files)?;
+    fs::write(path, lcov).map_err(|e| format!("Unable to write {}: {e}", path.display()))?;
+  }
+  if let Some(path) = &options.folded {
+    fs::write(path, profile.folded()).map_err(|e| format!("Unable to write {}: {e}", path.display()))?;
+  }
+  result.map_err(|e| e.to_string())
+}
+
+/// Fuzzes the parser, printing each input which fails with the reason
+fn fuzz(options: &Options) -> Result<(), String> {
+  let seed = options.seed.unwrap_or_else(time_seed);
+  let runs = options.runs.unwrap_or(1000);
+  println!("Fuzzing the parser with {runs} programs from seed {seed}");
+
+  // The panics of the parser are failures, reported with their message
+  let hook = panic::take_hook();
+  panic::set_hook(Box::new(|_| {}));
+  let failures = parser::fuzz::fuzz(seed, runs);
+  panic::set_hook(hook);
+
+  for failure in &failures {
+    println!("\n{}\n{}", failure.message, failure.input);
+  }
+  if !failures.is_empty() {
+    return Err(format!("{} input(s) failed", failures.len()));
+  }
+  println!("No failures");
+  Ok(())
+}
+
+fn generate(options: &Options) -> Result<(), String> {
+  let seed = options.seed.unwrap_or_else(time_seed);
+  if options.typed {
+    print!("{}", TypedGenerator::new(seed).program());
+    return Ok(());
+  }
+  let program = ProgramGenerator::new(seed).program();
+  println!("{}", program.trim_end());
+  Ok(())
+}
+
+/// Runs generated programs on every backend, printing each program on which a backend differs from the IR
+/// interpreter, minimised, with what each backend does
+fn run_difftest(options: &Options) -> Result<(), String> {
+  let seed = options.seed.unwrap_or_else(time_seed);
+  let runs = options.runs.unwrap_or(100);
+  let backends = Backend::available();
+  let names: Vec<String> = backends.iter().map(Backend::to_string).collect();
+  println!("Running {runs} programs from seed {seed} on {}", names.join(", "));
+
+  // The panics of the backends are reported as what they do
+  let hook = panic::take_hook();
+  panic::set_hook(Box::new(|_| {}));
+  let divergences = difftest::difftest(seed, runs, &backends);
+  panic::set_hook(hook);
+
+  let divergences = divergences?;
+  for divergence in &divergences {
+    println!("\nSeed {}:\n{}\n", divergence.seed, divergence.program.trim_end());
+    for (backend, outcome) in &divergence.outcomes {
+      println!("{backend}: {outcome}");
+    }
+  }
+  if !divergences.is_empty() {
+    return Err(format!("{} program(s) diverged", divergences.len()));
+  }
+  println!("No divergences");
+  Ok(())
+}
+
+/// Reduces the program to the smallest one found on which the compiler fails the same way, and prints it. Lint
+/// suppression comments don't survive the printing, so the program is checked without them.
+fn reduce(options: &Options) -> Result<(), String> {
+  let (program, _, _) = parse_files(&options.files)?;
+  let check_options = CheckOptions { lint_config: load_lint_config(options)?, suppressions: Suppressions::default(), entry: options.entry.clone() };
+  let (failure, reduced) = reduce::reduce_failure(&program, &check_options, options.opt_level)?;
+  eprintln!("Reduced the program to one on which the compiler fails with {failure}");
+  println!("{}", print_program(&reduced).trim_end());
+  Ok(())
+}
+
+fn time_seed() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+fn is_bytecode_file(options: &Options) -> bool {
+  options.files.len() == 1 && options.files[0].extension().is_some_and(|ext| ext == "coolb")
+}
+
+fn is_assembly_file(options: &Options) -> bool {
+  options.files.len() == 1 && options.files[0].extension().is_some_and(|ext| ext == "s")
+}
+
+/// Reads a `.coolb` file, or compiles the program to bytecode
+fn load_bytecode(options: &Options) -> Result<Program, String> {
+  if is_bytecode_file(options) {
+    let file = &options.files[0];
+    let bytes = fs::read(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+    return vm::file::read(&bytes).map_err(|e| format!("{}: {e}", file.display()));
+  }
+  let (program, suppressions, class_files) = parse_files(&options.files)?;
+  let checked = analyze(&program, suppressions, options)?;
+  let mut compiled = compile_program(&checked)?;
+  for class in &mut compiled.classes {
+    class.file = class_files.get(&class.name).cloned();
+  }
+  Ok(compiled)
+}
+
+/// Lowers the program to IR and optimises it
+fn compile(options: &Options) -> Result<Module, String> {
+  let (program, suppressions, class_files) = parse_files(&options.files)?;
+  let checked = analyze(&program, suppressions, options)?;
+  let mut module = lower_program(&checked);
+  for class in &mut module.classes {
+    class.file = class_files.get(&class.name).cloned();
+  }
+  PassManager::for_level(options.opt_level).run(&mut module)?;
+  Ok(module)
+}
+
+/// Runs the semantic checks, printing the diagnostics
+fn analyze<'a>(program: &'a ParseProgram, suppressions: Suppressions, options: &Options) -> Result<CheckedProgram<'a>, String> {
+  let lint_config = load_lint_config(options)?;
+  let check_options = CheckOptions { lint_config, suppressions, entry: options.entry.clone() };
+
+  match analyze_program(program, &check_options) {
+    Ok(checked) => {
+      print_diagnostics(&checked.warnings);
+      Ok(checked)
+    }
+    Err(diagnostics) => {
+      print_diagnostics(&diagnostics);
+      let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+      Err(format!("Compilation failed with {errors} error(s)"))
+    }
+  }
+}
+
+/// Parses all files into a single program, collecting the lint suppression comments of each file and the file of each
+/// class
+fn parse_files(files: &[impl AsRef<Path>]) -> Result<(ParseProgram, Suppressions, HashMap<String, String>), String> {
+  let mut program = ParseProgram::default();
+  let mut suppressions = Suppressions::default();
+  let mut class_files = HashMap::new();
+
+  for file in files {
+    let file = file.as_ref();
+    let source = fs::read_to_string(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+    let file_program = get_ast_from_file_path(&file.to_string_lossy()).map_err(|e| format!("{}: {e}", file.display()))?;
+
+    class_files.extend(file_program.classes.iter().map(|class| (class.get_name(), file.display().to_string())));
+    suppressions.extend(Suppressions::from_source(&source).for_classes(file_program.classes.iter().map(ParseClass::get_name)));
+    program.classes.extend(file_program.classes);
+  }
+
+  Ok((program, suppressions, class_files))
+}
+
+fn load_lint_config(options: &Options) -> Result<LintConfig, String> {
+  match &options.config {
+    Some(path) => LintConfig::from_file(path),
+    None if Path::new(CONFIG_FILE_NAME).exists() => LintConfig::from_file(Path::new(CONFIG_FILE_NAME)),
+    None => Ok(LintConfig::default()),
+  }
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic]) {
+  for diagnostic in diagnostics {
+    eprintln!("{diagnostic}");
+  }
+}
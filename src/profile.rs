@@ -0,0 +1,106 @@
+use crate::debug::read_program_files;
+use ir::interp::Profile;
+use ir::model::function::Function;
+use ir::model::module::Module;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Coverage of the program in the lcov tracefile format, with a record for each file: its methods with their calls,
+/// its branches with the times each way was taken, and its lines with code with the times they ran
+pub fn lcov(profile: &Profile, module: &Module, files: &[PathBuf]) -> Result<String, String> {
+  let (_, class_files) = read_program_files(files)?;
+  let methods = profile.methods();
+  let calls: HashMap<&str, u64> = methods.iter().map(|method| (method.function, method.calls)).collect();
+  let lines = profile.lines(module);
+  let branches = profile.branches(module);
+
+  let mut records = Vec::new();
+  for (index, file) in files.iter().enumerate() {
+    let in_file = |class_name: &str| class_files.get(class_name) == Some(&index);
+    let mut record = vec!["TN:".to_string(), format!("SF:{}", file.display())];
+
+    let functions: Vec<&Function> = module.functions.iter().filter(|function| !function.is_builtin() && in_file(&function.class_name)).collect();
+    let function_calls = |function: &Function| calls.get(function.name.as_str()).copied().unwrap_or(0);
+    record.extend(functions.iter().map(|function| format!("FN:{},{}", function.pos.0, function.name)));
+    record.extend(functions.iter().map(|function| format!("FNDA:{},{}", function_calls(function), function.name)));
+    record.push(format!("FNF:{}", functions.len()));
+    record.push(format!("FNH:{}", functions.iter().filter(|function| function_calls(function) > 0).count()));
+
+    let branches: Vec<_> = branches.iter().filter(|branch| in_file(branch.class_name)).collect();
+    for (block, branch) in branches.iter().enumerate() {
+      for (way, taken) in branch.taken.iter().enumerate() {
+        // Branches never reached are `-`, unlike the ways never taken of the branches reached
+        let taken = if branch.taken == [0, 0] { "-".to_string() } else { taken.to_string() };
+        record.push(format!("BRDA:{},{block},{way},{taken}", branch.line));
+      }
+    }
+    record.push(format!("BRF:{}", branches.len() * 2));
+    record.push(format!("BRH:{}", branches.iter().flat_map(|branch| branch.taken).filter(|taken| *taken > 0).count()));
+
+    let lines: Vec<_> = lines.iter().filter(|line| in_file(line.class_name)).collect();
+    record.extend(lines.iter().map(|line| format!("DA:{},{}", line.line, line.hits)));
+    record.push(format!("LF:{}", lines.len()));
+    record.push(format!("LH:{}", lines.iter().filter(|line| line.hits > 0).count()));
+    record.push("end_of_record\n".to_string());
+    records.push(record.join("\n"));
+  }
+  Ok(records.concat())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::profile::lcov;
+  use ir::interp::run_profiled;
+  use ir::lower::lower_program;
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+  use std::path::PathBuf;
+
+  #[test]
+  fn test_lcov() {
+    let path = PathBuf::from("test_resources/debug/counter.cl");
+    let program = get_ast_from_file_path(&path.to_string_lossy()).expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let module = lower_program(&checked);
+    let (mut input, mut output) = (&b""[..], Vec::new());
+    let (profile, result) = run_profiled(&module, &mut input, &mut output);
+    assert_eq!(result.map_err(|e| e.to_string()), Err("line 26: Division by zero.".to_string()));
+
+    let lcov = lcov(&profile, &module, &[path]).expect("Coverage must be written");
+    let expected = "\
+TN:
+SF:test_resources/debug/counter.cl
+FN:1,Counter._init
+FN:4,Counter.add
+FN:12,Loud._init
+FN:15,Main._init
+FN:18,Main.main
+FNDA:1,Counter._init
+FNDA:3,Counter.add
+FNDA:1,Loud._init
+FNDA:1,Main._init
+FNDA:1,Main.main
+FNF:5
+FNH:5
+BRDA:20,0,0,3
+BRDA:20,0,1,1
+BRF:2
+BRH:2
+DA:6,3
+DA:7,3
+DA:12,1
+DA:16,1
+DA:19,1
+DA:20,4
+DA:21,3
+DA:22,3
+DA:24,1
+DA:25,1
+DA:26,1
+LF:11
+LH:11
+end_of_record
+";
+    assert_eq!(lcov, expected);
+  }
+}
@@ -0,0 +1,391 @@
+//! Reduction of programs which make the compiler fail.
+//!
+//! The program is made smaller over its AST, by removing classes, features and expressions and by replacing
+//! expressions with their parts or with constants, for as long as the compiler fails on it the same way: it panics at
+//! the same place, or reports the same diagnostic and no error the program didn't have. Runs of classes and of
+//! features are removed first, halving them like delta debugging, then the parts one at a time until none can be
+//! reduced. Each candidate is printed and parsed back, so the program found is valid COOL.
+//!
+//! The greedy pass over the parts, `reduce_greedily`, and the numbering of the expressions, `replace_expression`, also
+//! minimise the generated programs of `difftest`.
+
+use ir::lower::lower_program;
+use ir::opt::{OptLevel, PassManager};
+use parser::get_ast_from_program_text;
+use parser::model::expressions::Expression;
+use parser::model::feature::ParseFeature;
+use parser::model::program::ParseProgram;
+use parser::printer::print_program;
+use semantic::gen::{analyze_program, CheckOptions};
+use semantic::models::diagnostic::Diagnostic;
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::panic::{self, catch_unwind, AssertUnwindSafe};
+
+thread_local! {
+  /// Place in the compiler of the last panic of this thread
+  static PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A way the compiler fails on a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+  /// A panic, with the place in the compiler where it happened
+  Panic { location: String, message: String },
+  /// IR made invalid by an optimisation pass, known by the first line of the verifier's report
+  InvalidIr { message: String },
+  /// A diagnostic, known by its lint, or by its message for the checks which aren't lints
+  Diagnostic { code: String, is_error: bool },
+}
+
+impl Failure {
+  fn of_diagnostic(diagnostic: &Diagnostic) -> Self {
+    let code = diagnostic.lint.map_or_else(|| diagnostic.message.clone(), str::to_string);
+    Failure::Diagnostic { code, is_error: diagnostic.is_error() }
+  }
+
+  /// Whether the failures are the same, panics being the same when they happen at the same place, as their message
+  /// may show values of the program
+  fn is_same(&self, other: &Failure) -> bool {
+    match (self, other) {
+      (Failure::Panic { location, .. }, Failure::Panic { location: other, .. }) => location == other,
+      _ => self == other,
+    }
+  }
+}
+
+impl Display for Failure {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Failure::Panic { location, message } => write!(f, "a panic at {location}: {message}"),
+      Failure::InvalidIr { message } => write!(f, "{message}"),
+      Failure::Diagnostic { code, is_error: true } => write!(f, "the error {code}"),
+      Failure::Diagnostic { code, is_error: false } => write!(f, "the warning {code}"),
+    }
+  }
+}
+
+/// Reduces a program on which the compiler fails, checked with the options and optimised at the level, to the
+/// smallest one found which fails the same way as its first failure: its panic, or else its first error, or else its
+/// first warning. Returns the failure and the reduced program, or an error if the compiler doesn't fail.
+pub fn reduce_failure(program: &ParseProgram, options: &CheckOptions, opt_level: OptLevel) -> Result<(Failure, ParseProgram), String> {
+  // The panics are failures, recorded with their place instead of printed
+  let hook = panic::take_hook();
+  panic::set_hook(Box::new(|info| {
+    let location = info.location().map(ToString::to_string);
+    PANIC_LOCATION.with(|last| *last.borrow_mut() = location);
+  }));
+  let result = (|| {
+    let failures = failures(&print_program(program), options, opt_level).ok_or("The printed program doesn't parse")?;
+    let target = failures.first().cloned().ok_or("The compiler doesn't fail on the program, there is nothing to reduce")?;
+    let reduced = reduce(program, |candidate| {
+      failures_of(candidate, options, opt_level).is_some_and(|candidate_failures| {
+        candidate_failures.iter().any(|failure| failure.is_same(&target))
+            && candidate_failures.iter().all(|failure| !matches!(failure, Failure::Diagnostic { is_error: true, .. }) || failures.contains(failure))
+      })
+    });
+    Ok((target, reduced))
+  })();
+  panic::set_hook(hook);
+  result
+}
+
+fn failures_of(program: &ParseProgram, options: &CheckOptions, opt_level: OptLevel) -> Option<Vec<Failure>> {
+  failures(&print_program(program), options, opt_level)
+}
+
+/// The ways the compiler fails on the source: its panic, the invalid IR of a pass, or else the errors then the
+/// warnings it reports. `None` if the source doesn't parse.
+fn failures(source: &str, options: &CheckOptions, opt_level: OptLevel) -> Option<Vec<Failure>> {
+  let result = catch_unwind(AssertUnwindSafe(|| {
+    let program = get_ast_from_program_text(source).ok()?;
+    let diagnostics = match analyze_program(&program, options) {
+      Ok(checked) => {
+        let mut module = lower_program(&checked);
+        if let Err(e) = PassManager::for_level(opt_level).run(&mut module) {
+          let message = e.lines().next().unwrap_or_default().to_string();
+          return Some(vec![Failure::InvalidIr { message }]);
+        }
+        checked.warnings
+      }
+      Err(diagnostics) => diagnostics,
+    };
+    let (errors, warnings): (Vec<&Diagnostic>, Vec<&Diagnostic>) = diagnostics.iter().partition(|diagnostic| diagnostic.is_error());
+    Some(errors.into_iter().chain(warnings).map(Failure::of_diagnostic).collect())
+  }));
+  result.unwrap_or_else(|panic| {
+    let location = PANIC_LOCATION.with(|last| last.borrow_mut().take()).unwrap_or_default();
+    Some(vec![Failure::Panic { location, message: panic_message(panic.as_ref()) }])
+  })
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+  let message = panic.downcast_ref::<&str>().map(|message| message.to_string());
+  message.or_else(|| panic.downcast_ref::<String>().cloned()).unwrap_or_default()
+}
+
+/// The smallest program found by the reductions for which `keep` is `true`, the program itself being one
+pub fn reduce(program: &ParseProgram, mut keep: impl FnMut(&ParseProgram) -> bool) -> ParseProgram {
+  let mut best = program.clone();
+  best.classes = remove_runs(&best.classes, |classes| keep(&ParseProgram { classes: classes.to_vec() }));
+  for index in 0..best.classes.len() {
+    let Some(features) = &best.classes[index].features else {
+      continue;
+    };
+    let features = remove_runs(features, |features| {
+      let mut candidate = best.clone();
+      candidate.classes[index].features = Some(features.to_vec());
+      keep(&candidate)
+    });
+    best.classes[index].features = Some(features);
+  }
+  reduce_greedily(best, reductions, keep)
+}
+
+/// The smallest value found by reducing its parts one at a time for which `keep` is `true`, the value itself being
+/// one. `reductions` gives the values made by reducing the part numbered `site`, or `None` past the last part; the
+/// first of them which is kept replaces the value, until a whole pass over the parts keeps none.
+pub fn reduce_greedily<T: Clone + PartialEq>(value: T, mut reductions: impl FnMut(&T, usize) -> Option<Vec<T>>, mut keep: impl FnMut(&T) -> bool) -> T {
+  let mut best = value;
+  loop {
+    let before = best.clone();
+    let mut site = 0;
+    while let Some(candidates) = reductions(&best, site) {
+      // A reduction changes the site, which is tried again
+      match candidates.into_iter().find(|candidate| keep(candidate)) {
+        Some(candidate) => best = candidate,
+        None => site += 1,
+      }
+    }
+    if best == before {
+      return best;
+    }
+  }
+}
+
+/// Expressions made of smaller ones, which the reductions number in the order they are printed
+pub trait Tree {
+  /// The expressions this one is made of, in the order they are printed
+  fn parts_mut(&mut self) -> Vec<&mut Self>;
+}
+
+impl Tree for Expression {
+  fn parts_mut(&mut self) -> Vec<&mut Self> {
+    self.sub_expressions_mut()
+  }
+}
+
+/// The programs made by replacing the expression numbered `n`, counting the roots `roots` gives and their parts, with
+/// each of those `simpler` gives, or `None` past the last expression
+pub fn replace_expression<P: Clone, T: Tree>(program: &P, n: usize, roots: fn(&mut P) -> Vec<&mut T>, simpler: impl Fn(&mut T) -> Vec<T>) -> Option<Vec<P>> {
+  let mut copy = program.clone();
+  let expr = nth_expr(roots(&mut copy), n)?;
+  let candidates = simpler(expr).into_iter()
+      .map(|simpler| {
+        let mut candidate = program.clone();
+        *nth_expr(roots(&mut candidate), n).expect("The expression is in the copy") = simpler;
+        candidate
+      })
+      .collect();
+  Some(candidates)
+}
+
+fn nth_expr<T: Tree>(roots: Vec<&mut T>, n: usize) -> Option<&mut T> {
+  let mut remaining = n;
+  roots.into_iter().find_map(|root| find(root, &mut remaining))
+}
+
+fn find<'a, T: Tree>(expr: &'a mut T, remaining: &mut usize) -> Option<&'a mut T> {
+  if *remaining == 0 {
+    return Some(expr);
+  }
+  *remaining -= 1;
+  for part in expr.parts_mut() {
+    if let Some(found) = find(part, remaining) {
+      return Some(found);
+    }
+  }
+  None
+}
+
+/// The items left by removing runs of them for which `keep` stays `true`: runs of half the items, then of a quarter,
+/// down to single items
+fn remove_runs<T: Clone>(items: &[T], mut keep: impl FnMut(&[T]) -> bool) -> Vec<T> {
+  let mut items = items.to_vec();
+  let mut size = items.len() / 2;
+  while size > 0 {
+    let mut start = 0;
+    while start < items.len() {
+      let mut candidate = items.clone();
+      candidate.drain(start..(start + size).min(items.len()));
+      if keep(&candidate) {
+        items = candidate;
+      } else {
+        start += size;
+      }
+    }
+    size /= 2;
+  }
+  items
+}
+
+/// The programs made by reducing the part of the program numbered `site`: a class, then a feature, then an expression,
+/// or `None` past the last part
+fn reductions(program: &ParseProgram, site: usize) -> Option<Vec<ParseProgram>> {
+  let classes = program.classes.len();
+  if site < classes {
+    let mut removed = program.clone();
+    removed.classes.remove(site);
+    let mut candidates = vec![removed];
+    if program.classes[site].parent_type.get_name() != "Object" {
+      let mut candidate = program.clone();
+      candidate.classes[site].parent_type.0 = "Object".into();
+      candidates.push(candidate);
+    }
+    return Some(candidates);
+  }
+
+  let mut site = site - classes;
+  for (class_index, class) in program.classes.iter().enumerate() {
+    let features = class.features.as_deref().unwrap_or_default();
+    if site >= features.len() {
+      site -= features.len();
+      continue;
+    }
+    let mut removed = program.clone();
+    features_mut(&mut removed, class_index).remove(site);
+    let mut candidates = vec![removed];
+    match &features[site] {
+      ParseFeature::Attribute { attribute } if attribute.expr.is_some() => {
+        let mut candidate = program.clone();
+        if let ParseFeature::Attribute { attribute } = &mut features_mut(&mut candidate, class_index)[site] {
+          attribute.expr = None;
+        }
+        candidates.push(candidate);
+      }
+      ParseFeature::Method { method } => {
+        for index in 0..method.formals.as_ref().map_or(0, Vec::len) {
+          let mut candidate = program.clone();
+          if let ParseFeature::Method { method } = &mut features_mut(&mut candidate, class_index)[site] {
+            method.formals.as_mut().expect("The method has formals").remove(index);
+          }
+          candidates.push(candidate);
+        }
+      }
+      ParseFeature::Attribute { .. } => {}
+    }
+    return Some(candidates);
+  }
+
+  replace_expression(program, site, expressions, |expr| simpler(expr))
+}
+
+fn features_mut(program: &mut ParseProgram, class_index: usize) -> &mut Vec<ParseFeature> {
+  program.classes[class_index].features.as_mut().expect("The class has features")
+}
+
+/// The initialisations of the attributes and the bodies of the methods, in the order they are printed
+fn expressions(program: &mut ParseProgram) -> Vec<&mut Expression> {
+  let features = program.classes.iter_mut().flat_map(|class| class.features.iter_mut().flatten());
+  features
+      .filter_map(|feature| match feature {
+        ParseFeature::Attribute { attribute } => attribute.expr.as_mut(),
+        ParseFeature::Method { method } => Some(&mut method.expr),
+      })
+      .collect()
+}
+
+/// Expressions which may replace this one: with a part removed, one of its parts, or a zero constant unless it is one
+fn simpler(expr: &Expression) -> Vec<Expression> {
+  let mut candidates = Vec::new();
+  match expr {
+    Expression::Block { expr_list } if expr_list.len() > 1 => {
+      candidates.extend((0..expr_list.len()).map(|index| {
+        let mut expr_list = expr_list.clone();
+        expr_list.remove(index);
+        Expression::Block { expr_list }
+      }));
+    }
+    Expression::Let { let_init, in_expr } => {
+      for index in 0..let_init.len() {
+        let mut reduced = let_init.clone();
+        if reduced[index].expr.take().is_none() {
+          if reduced.len() == 1 {
+            continue;
+          }
+          reduced.remove(index);
+        }
+        candidates.push(Expression::Let { let_init: reduced, in_expr: in_expr.clone() });
+      }
+    }
+    Expression::Case { switch_expression, branches } if branches.len() > 1 => {
+      candidates.extend((0..branches.len()).map(|index| {
+        let mut branches = branches.clone();
+        branches.remove(index);
+        Expression::Case { switch_expression: switch_expression.clone(), branches }
+      }));
+    }
+    Expression::Dispatch { calling_expr, cast_type: Some(_), fn_name, param_list } => {
+      let (calling_expr, fn_name, param_list) = (calling_expr.clone(), fn_name.clone(), param_list.clone());
+      candidates.push(Expression::Dispatch { calling_expr, cast_type: None, fn_name, param_list });
+    }
+    _ => {}
+  }
+  candidates.extend(expr.sub_expressions().into_iter().cloned());
+  let is_zero = match expr {
+    Expression::IntExpr { value, .. } => *value == 0,
+    Expression::BoolExpr { value, .. } => !value,
+    Expression::StringExpr { value, .. } => value.is_empty(),
+    _ => false,
+  };
+  if !is_zero {
+    candidates.extend([
+      Expression::IntExpr { value: 0, line_num: 0, line_pos: 0 },
+      Expression::BoolExpr { value: false, line_num: 0, line_pos: 0 },
+      Expression::StringExpr { value: String::new(), line_num: 0, line_pos: 0 },
+    ]);
+  }
+  candidates
+}
+
+#[cfg(test)]
+mod test {
+  use crate::reduce::{failures, reduce, reduce_failure, Failure};
+  use ir::opt::OptLevel;
+  use parser::get_ast_from_program_text;
+  use parser::printer::print_program;
+  use semantic::gen::CheckOptions;
+  use std::fs;
+
+  #[test]
+  fn test_reduce_failure() {
+    let source = fs::read_to_string("test_resources/programs/graph.cl").expect("graph.cl must be readable");
+    let source = source.replacen("out_int(num);", "out_int(num + out);", 1);
+    let program = get_ast_from_program_text(&source).expect("The program must parse");
+    let (failure, reduced) = reduce_failure(&program, &CheckOptions::default(), OptLevel::O0).expect("The program must fail");
+    assert!(matches!(&failure, Failure::Diagnostic { code, is_error: true } if code == "Non-Int arguments: Int + EList"), "{failure:?}");
+
+    let text = print_program(&reduced);
+    assert!(text.lines().count() < 20, "{text}");
+    assert!(text.contains("num + out") || text.contains("+ out"), "{text}");
+    let failures = failures(&text, &CheckOptions::default(), OptLevel::O0).expect("The reduced program must parse");
+    assert!(failures.contains(&failure), "{failures:?}");
+  }
+
+  #[test]
+  fn test_reduce() {
+    // Programs which still call out_string, of which the smallest is the call alone, with constants for its parts
+    let source = fs::read_to_string("test_resources/programs/hello_world.cl").expect("hello_world.cl must be readable");
+    let program = get_ast_from_program_text(&source).expect("The program must parse");
+    let reduced = reduce(&program, |candidate| print_program(candidate).contains("out_string"));
+    assert_eq!(print_program(&reduced), "class Main {\n    main() : SELF_TYPE {\n        0.out_string(0)\n    };\n};\n\n");
+  }
+
+  #[test]
+  fn test_nothing_to_reduce() {
+    let program = get_ast_from_program_text("class Main { main() : Int { 0 }; };").expect("The program must parse");
+    let error = reduce_failure(&program, &CheckOptions::default(), OptLevel::O0).unwrap_err();
+    assert_eq!(error, "The compiler doesn't fail on the program, there is nothing to reduce");
+  }
+}
@@ -0,0 +1,487 @@
+use ir::interp::{Interpreter, Object, ObjectData, Value};
+use ir::lower::lower_program;
+use ir::model::module::Module;
+use lexer::iter::token::TokenIter;
+use lexer::model::token::Token;
+use parser::get_ast_from_program_text;
+use parser::model::feature::ParseFeature;
+use parser::model::program::ParseProgram;
+use semantic::gen::{analyze_program, CheckOptions};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::io::{BufRead, Write};
+use std::panic;
+
+const HELP: &str = "\
+Enter an expression to evaluate it in the scratch Main object, which inherits IO, or a class definition.
+  x : Int <- 5      Declare a variable, an attribute of Main, `x <- 6` assigns it
+  :type <expr>      Print the static type of the expression without evaluating it
+  :hierarchy        Print the inheritance tree of the classes
+  :vars             Print the variables and their values
+  :help             Print this help
+  :quit             Leave the REPL, like the end of the input";
+
+/// Name of the method of the scratch class evaluating the input
+const EVAL_METHOD: &str = "main";
+
+/// State of a REPL session. Expressions are evaluated as the body of `Main.main`, in a `Main` class that inherits
+/// `IO` and has the variables as attributes. The program is compiled again from source for every input, while the
+/// `Main` object and the objects it refers to live on. When a class definition changes the tags of the classes, the
+/// objects are given their new tags.
+#[derive(Default)]
+pub struct Session {
+  classes: Vec<String>,             // source of the class definitions, in the order they were entered
+  variables: Vec<(String, String)>, // name and declared type of each attribute of `Main`
+  class_names: Vec<String>,         // classes of the last module, by tag
+  main: Option<Value>,
+}
+
+/// Program compiled for one input
+struct Compiled {
+  module: Module,
+  static_type: String, // of the body of `Main.main`
+}
+
+/// Output that remembers whether the program left the cursor at the start of a line, so results are printed on
+/// lines of their own
+struct Output<'a> {
+  inner: &'a mut dyn Write,
+  line_start: bool,
+}
+
+impl Write for Output<'_> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let written = self.inner.write(buf)?;
+    if let Some(last) = buf[..written].last() {
+      self.line_start = *last == b'\n';
+    }
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl Session {
+  /// Defines the classes of a file before the session starts
+  pub fn load(&mut self, source: &str) -> Result<(), String> {
+    self.define_classes(source).map(|_| ())
+  }
+
+  /// Reads inputs until `:quit` or the end of the input. Programs read their input from the same stream, so
+  /// `in_string()` reads the next line typed.
+  pub fn run(&mut self, input: &mut dyn BufRead, output: &mut dyn Write) -> std::io::Result<()> {
+    let mut output = Output { inner: output, line_start: true };
+    writeln!(output, "COOL REPL, :help for help")?;
+    while let Some(text) = read_input(input, &mut output)? {
+      let text = text.trim();
+      if text == ":quit" {
+        break;
+      }
+      let result = self.input(text, input, &mut output);
+      if !output.line_start {
+        writeln!(output)?;
+      }
+      match result {
+        Ok(message) if message.is_empty() => {}
+        Ok(message) => writeln!(output, "{message}")?,
+        Err(e) => writeln!(output, "{e}")?,
+      }
+    }
+    Ok(())
+  }
+
+  /// Handles one input, returning what to print
+  fn input(&mut self, text: &str, input: &mut dyn BufRead, output: &mut Output) -> Result<String, String> {
+    if let Some(command) = text.strip_prefix(':') {
+      let (command, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+      return match command {
+        "type" if !argument.trim().is_empty() => Ok(self.compile(&pad(text, argument), None)?.static_type),
+        "type" => Err("Usage: :type <expr>".to_string()),
+        "hierarchy" => Ok(hierarchy(&self.compile("self", None)?.module)),
+        "vars" => self.print_variables(input, output),
+        "help" => Ok(HELP.to_string()),
+        _ => Err(format!("Unknown command :{command}, :help lists the commands")),
+      };
+    }
+
+    let tokens = tokens(text);
+    match tokens.as_slice() {
+      [] => Ok(String::new()),
+      [Token::Class { .. }, ..] => {
+        let names = self.define_classes(text)?;
+        Ok(format!("Defined {}", names.join(", ")))
+      }
+      [Token::Ident { value: name, .. }, colon @ Token::Colon { .. }, type_token @ Token::Ident { value: type_name, .. }, rest @ ..] => {
+        // The body assigns the initial value, `x : Int <- 5` becomes `x       <- 5`
+        let body = match rest {
+          [] => name.clone(),
+          [Token::Assign { .. }, _, ..] => blank(text, colon.get_pos(), type_token.get_pos(), type_name.len()),
+          _ => return Err("Expected `<-` and the initial value of the variable".to_string()),
+        };
+        self.declare(name, type_name, &body, input, output)
+      }
+      _ => {
+        let (value, static_type, module) = self.evaluate(text.trim_end_matches(';'), input, output)?;
+        if self.main.as_ref().is_some_and(|main| same_object(main, &value)) {
+          // Methods of IO return `self`, which is not worth printing after their output
+          return Ok(String::new());
+        }
+        Ok(format!("{} : {static_type}", value_text(&value, &module, 0)))
+      }
+    }
+  }
+
+  /// Checks and adds the classes, returning their names
+  fn define_classes(&mut self, text: &str) -> Result<Vec<String>, String> {
+    let text = if text.trim_end().ends_with(';') { text.to_string() } else { format!("{text};") };
+    let program = parse(&text)?;
+    let names: Vec<String> = program.classes.iter().map(|class| class.get_name()).collect();
+    if let Some(class) = program.classes.iter().find(|class| class.get_name() == "Main" || class.parent_type.get_name() == "Main") {
+      return Err(format!("Error: {}:{} Main is the scratch class of the REPL, it cannot be defined or inherited", class.line_num, class.line_pos));
+    }
+    self.compile("self", Some(&text))?;
+    self.classes.push(text);
+    Ok(names)
+  }
+
+  /// Adds a variable and evaluates the body, which assigns its initial value or reads its default value
+  fn declare(&mut self, name: &str, type_name: &str, body: &str, input: &mut dyn BufRead, output: &mut Output) -> Result<String, String> {
+    if !name.starts_with(|c: char| c.is_ascii_lowercase()) || name == "self" {
+      return Err(format!("Invalid variable name {name}"));
+    }
+    if self.variables.iter().any(|(variable, _)| variable == name) {
+      return Err(format!("Variable {name} is already declared, assign it with `{name} <- ...`"));
+    }
+
+    self.variables.push((name.to_string(), type_name.to_string()));
+    match self.evaluate(body, input, output) {
+      Ok((value, _, module)) => Ok(format!("{name} : {type_name} = {}", value_text(&value, &module, 0))),
+      Err(e) => {
+        self.variables.pop();
+        if let Some(Value::Object(main)) = &self.main {
+          if let ObjectData::Attributes(attributes) = &mut main.borrow_mut().data {
+            attributes.truncate(self.variables.len());
+          }
+        }
+        Err(e)
+      }
+    }
+  }
+
+  fn print_variables(&mut self, input: &mut dyn BufRead, output: &mut Output) -> Result<String, String> {
+    let (_, _, module) = self.evaluate("self", input, output)?;
+    let Some(Value::Object(main)) = &self.main else {
+      return Ok(String::new());
+    };
+    let ObjectData::Attributes(values) = &main.borrow().data else {
+      return Ok(String::new());
+    };
+    let lines: Vec<String> = self.variables.iter().zip(values)
+        .map(|((name, type_name), value)| format!("{name} : {type_name} = {}", value_text(value, &module, 0)))
+        .collect();
+    Ok(lines.join("\n"))
+  }
+
+  /// Compiles the body of `Main.main` with the classes defined so far and the new ones, if any. The input, the
+  /// body or the new classes, starts the program, so positions in errors are those of the input.
+  fn compile(&self, body: &str, new_classes: Option<&str>) -> Result<Compiled, String> {
+    let prefix = format!("class Main inherits IO {{ {EVAL_METHOD}() : Object {{ ");
+    let mut source = match new_classes {
+      Some(classes) => format!("{classes}\n{prefix}{body}\n}};"),
+      None => format!("{prefix}{body}\n}};"),
+    };
+    for (name, type_name) in &self.variables {
+      source.push_str(&format!(" {name} : {type_name};"));
+    }
+    source.push_str(" };\n");
+    for class in &self.classes {
+      source.push_str(class);
+      source.push('\n');
+    }
+    let (input_lines, column_offset) = match new_classes {
+      Some(classes) => (classes.lines().count() as u32, 0),
+      None => (body.lines().count() as u32, prefix.len() as u32),
+    };
+
+    // Positions in the input, none for errors elsewhere in the program
+    let locate = |line: u32, column: u32| match line {
+      1 => format!("1:{} ", column.saturating_sub(column_offset)),
+      line if line <= input_lines => format!("{line}:{column} "),
+      _ => String::new(),
+    };
+    let program = parse(&source).map_err(|e| {
+      let position = e.split_once(' ').and_then(|(position, message)| {
+        let (line, column) = position.split_once(':')?;
+        Some(format!("Error: {}{message}", locate(line.parse().ok()?, column.parse().ok()?)))
+      });
+      position.unwrap_or(format!("Error: {e}"))
+    })?;
+    let checked = analyze_program(&program, &CheckOptions::default()).map_err(|diagnostics| {
+      let errors: Vec<String> = diagnostics.iter()
+          .filter(|diagnostic| diagnostic.is_error())
+          .map(|diagnostic| format!("Error: {}{}", diagnostic.position.map_or(String::new(), |(line, column)| locate(line, column)), diagnostic.message))
+          .collect();
+      errors.join("\n")
+    })?;
+
+    let static_type = program.classes.iter()
+        .filter(|class| class.get_name() == "Main")
+        .flat_map(|class| class.features.iter().flatten())
+        .find_map(|feature| match feature {
+          ParseFeature::Method { method } if method.get_name() == EVAL_METHOD => checked.types.get(&method.expr),
+          _ => None,
+        })
+        .unwrap_or("Object")
+        .to_string();
+    Ok(Compiled { module: lower_program(&checked), static_type })
+  }
+
+  /// Evaluates the body on the `Main` object, returning its value and static type and the module it ran in
+  fn evaluate(&mut self, body: &str, input: &mut dyn BufRead, output: &mut Output) -> Result<(Value, String, Module), String> {
+    let Compiled { module, static_type } = self.compile(body, None)?;
+    self.retag(&module);
+
+    let main_tag = module.class("Main").expect("Main is always defined").tag;
+    let mut interpreter = Interpreter::new(&module, input, output);
+    let fresh = interpreter.allocate(main_tag);
+    let main = self.main.get_or_insert_with(|| fresh.clone()).clone();
+    // New variables start with the default value of their type
+    if let (Value::Object(main), Value::Object(fresh)) = (&main, &fresh) {
+      if !Rc::ptr_eq(main, fresh) {
+        if let (ObjectData::Attributes(attributes), ObjectData::Attributes(defaults)) = (&mut main.borrow_mut().data, &fresh.borrow().data) {
+          let count = attributes.len();
+          attributes.extend(defaults[count..].iter().cloned());
+        }
+      }
+    }
+
+    let result = interpreter.call_to_end(&module.entry_function, vec![main]);
+    let flushed = output.flush();
+    let value = result.map_err(|e| format!("Runtime error: {e}"))?;
+    flushed.map_err(|e| format!("Unable to write the output: {e}"))?;
+    Ok((value, static_type, module))
+  }
+
+  /// Gives the objects reachable from `Main` the tags of their classes in the module, when classes were added
+  fn retag(&mut self, module: &Module) {
+    let names: Vec<String> = module.classes.iter().map(|class| class.name.clone()).collect();
+    if names == self.class_names {
+      return;
+    }
+    let tags: Vec<usize> = self.class_names.iter()
+        .map(|name| module.class(name).expect("Classes are never removed").tag)
+        .collect();
+    self.class_names = names;
+
+    let mut seen: HashSet<*const RefCell<Object>> = HashSet::new();
+    let mut stack: Vec<Value> = self.main.iter().cloned().collect();
+    while let Some(value) = stack.pop() {
+      let Value::Object(object) = value else {
+        continue;
+      };
+      if !seen.insert(Rc::as_ptr(&object)) {
+        continue;
+      }
+      let mut object = object.borrow_mut();
+      object.tag = tags[object.tag];
+      if let ObjectData::Attributes(attributes) = &object.data {
+        stack.extend(attributes.iter().cloned());
+      }
+    }
+  }
+}
+
+/// Parses the program, turning a panic of the parser on malformed input into an error
+pub(crate) fn parse(source: &str) -> Result<ParseProgram, String> {
+  let hook = panic::take_hook();
+  panic::set_hook(Box::new(|_| {}));
+  let result = panic::catch_unwind(|| get_ast_from_program_text(source));
+  panic::set_hook(hook);
+  result.unwrap_or_else(|_| Err("Syntax error".to_string()))
+}
+
+/// Reads a line, and more lines while brackets, `if`, `while` or `case` are left open or the last token needs an
+/// operand, like `in` or `+`. `None` at the end of the input.
+fn read_input(input: &mut dyn BufRead, output: &mut Output) -> std::io::Result<Option<String>> {
+  let mut text = String::new();
+  loop {
+    write!(output, "{}", if text.is_empty() { "cool> " } else { "  ... " })?;
+    output.flush()?;
+    output.line_start = true;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(if text.is_empty() { None } else { Some(text) });
+    }
+    text.push_str(&line);
+    if text.trim_start().starts_with(':') || is_complete(&text) {
+      return Ok(Some(text));
+    }
+  }
+}
+
+fn is_complete(text: &str) -> bool {
+  let tokens = tokens(text);
+  let open: i32 = tokens.iter()
+      .map(|token| match token {
+        Token::OpenParen { .. } | Token::OpenCurl { .. } | Token::If { .. } | Token::While { .. } | Token::Case { .. } => 1,
+        Token::CloseParen { .. } | Token::CloseCurl { .. } | Token::EndIf { .. } | Token::EndLoop { .. } | Token::EndCase { .. } => -1,
+        _ => 0,
+      })
+      .sum();
+  let needs_operand = matches!(
+    tokens.last(),
+    Some(Token::In { .. } | Token::Then { .. } | Token::Else { .. } | Token::Loop { .. } | Token::Of { .. } | Token::Let { .. }
+      | Token::Inherits { .. } | Token::Assign { .. } | Token::CaseBranch { .. } | Token::Plus { .. } | Token::Minus { .. }
+      | Token::Star { .. } | Token::ForwardSlash { .. } | Token::Less { .. } | Token::LessOrEqual { .. }
+      | Token::Equal { .. } | Token::Tilde { .. } | Token::Not { .. } | Token::IsVoid { .. } | Token::New { .. }
+      | Token::Dot { .. } | Token::At { .. } | Token::Comma { .. } | Token::Colon { .. })
+  );
+  open <= 0 && !needs_operand
+}
+
+/// Tokens of the text, without comments
+fn tokens(text: &str) -> Vec<Token> {
+  TokenIter::from_program_text(text)
+      .filter(|token| !matches!(token, Token::Comment { .. } | Token::Empty | Token::EOF))
+      .collect()
+}
+
+/// The text with the characters from the start position to the end of the token at the end position replaced by
+/// spaces, both on the same line
+fn blank(text: &str, (line_num, start): (u32, u32), (end_line, end): (u32, u32), end_length: usize) -> String {
+  let mut lines: Vec<String> = text.lines().map(String::from).collect();
+  if line_num == end_line {
+    if let Some(line) = lines.get_mut(line_num as usize - 1) {
+      let range = start as usize - 1..(end as usize - 1 + end_length).min(line.len());
+      line.replace_range(range.clone(), &" ".repeat(range.len()));
+    }
+  }
+  lines.join("\n")
+}
+
+/// The argument of a command, with the command replaced by spaces so positions in errors match the input
+fn pad(text: &str, argument: &str) -> String {
+  format!("{}{argument}", " ".repeat(text.len() - argument.len()))
+}
+
+fn same_object(a: &Value, b: &Value) -> bool {
+  match (a, b) {
+    (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+    _ => false,
+  }
+}
+
+/// Text of a value: basic objects as literals and other objects as their class and attributes, one level deep
+pub(crate) fn value_text(value: &Value, module: &Module, depth: usize) -> String {
+  let object = match value {
+    Value::Void => return "void".to_string(),
+    Value::Int(value) => return value.to_string(),
+    Value::Bool(value) => return value.to_string(),
+    Value::Object(object) => object.borrow(),
+  };
+  let class = &module.classes[object.tag];
+  match &object.data {
+    ObjectData::Int(value) => value.to_string(),
+    ObjectData::Bool(value) => value.to_string(),
+    ObjectData::Str(value) => format!("{value:?}"),
+    ObjectData::Attributes(attributes) if attributes.is_empty() => format!("{} {{}}", class.name),
+    ObjectData::Attributes(_) if depth > 0 => format!("{} {{ ... }}", class.name),
+    ObjectData::Attributes(attributes) => {
+      let fields: Vec<String> = class.attributes.iter().zip(attributes)
+          .map(|(attribute, value)| format!("{} = {}", attribute.name, value_text(value, module, depth + 1)))
+          .collect();
+      format!("{} {{ {} }}", class.name, fields.join(", "))
+    }
+  }
+}
+
+/// The classes as an indented tree, children under their parent in tag order
+fn hierarchy(module: &Module) -> String {
+  let lines: Vec<String> = module.classes.iter()
+      .map(|class| {
+        let mut depth = 0;
+        let mut parent = class.parent.as_deref();
+        while let Some(name) = parent {
+          depth += 1;
+          parent = module.class(name).and_then(|class| class.parent.as_deref());
+        }
+        format!("{}{}", "  ".repeat(depth), class.name)
+      })
+      .collect();
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+  use crate::repl::Session;
+
+  fn transcript(session: &mut Session, input: &str) -> String {
+    let mut output: Vec<u8> = Vec::new();
+    session.run(&mut input.as_bytes(), &mut output).expect("Session must run");
+    String::from_utf8(output).expect("Output must be UTF-8")
+  }
+
+  #[test]
+  fn test_session() {
+    let mut session = Session::default();
+    session.load("class A { n : Int <- 3; set(m : Int) : A { { n <- m; self; } }; };").expect("Classes must load");
+    let input = "\
+\"hello\".substr(1, 3).concat(\"!\")
+a : A <- new A
+a.set(7)
+out_string(\"hi\")
+class B inherits A { next : A; };
+:type new B
+let b : B <- new B in
+  b.set(8)
+a
+:hierarchy
+x : Int <- \"no\"
+x
+\"ab\".substr(1, 5)
+in_int()
+42
+:vars
+:quit
+1
+";
+    let expected = "\
+COOL REPL, :help for help
+cool> \"ell!\" : String
+cool> a : A = A { n = 3 }
+cool> A { n = 7 } : A
+cool> hi
+cool> Defined B
+cool> B
+cool>   ... B { n = 8, next = void } : A
+cool> A { n = 7 } : A
+cool> Object
+  IO
+    Main
+  Bool
+  Int
+  String
+  A
+    B
+cool> Error: 1:1 Type String of assigned expression does not conform to declared type Int of identifier x
+cool> Error: 1:1 Undeclared identifier x
+cool> Runtime error: line 1: Length to substr too long
+cool> 42 : Int
+cool> a : A = A { n = 7 }
+cool> ";
+    assert_eq!(transcript(&mut session, input), expected);
+  }
+
+  #[test]
+  fn test_errors() {
+    let mut session = Session::default();
+    assert_eq!(session.load("class Main { };"), Err("Error: 1:7 Main is the scratch class of the REPL, it cannot be defined or inherited".to_string()));
+    let output = transcript(&mut session, ":type 1 +\nclass C { f() : Int { 1 + true }; };\n:bogus\n");
+    assert!(output.contains("cool> Error: 1:9 Missing operand\n"), "{output}");
+    assert!(output.contains("cool> Error: 1:23 Non-Int arguments: Int + Bool\n"), "{output}");
+    assert!(output.contains("cool> Unknown command :bogus, :help lists the commands\n"), "{output}");
+  }
+}
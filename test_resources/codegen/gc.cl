@@ -0,0 +1,50 @@
+(* Allocates enough for the native runtime to collect garbage several times: short-lived lists, a long-lived list
+   whose cells are made to point to new ones, strings built by concatenation and copies of objects *)
+class Cell {
+  value : Int;
+  next : Cell;
+  init(v : Int, n : Cell) : Cell { { value <- v; next <- n; self; } };
+  value() : Int { value };
+  next() : Cell { next };
+  set_next(n : Cell) : Cell { next <- n };
+};
+
+class Main inherits IO {
+  kept : Cell;
+
+  sum(list : Cell) : Int {
+    let total : Int <- 0 in {
+      while not isvoid list loop { total <- total + list.value(); list <- list.next(); } pool;
+      total;
+    }
+  };
+
+  build(length : Int) : Cell {
+    let list : Cell, i : Int <- 0 in {
+      while i < length loop { list <- (new Cell).init(i, list); i <- i + 1; } pool;
+      list;
+    }
+  };
+
+  main() : Object {
+    let round : Int <- 0, total : Int <- 0, text : String <- "", cell : Cell in {
+      kept <- build(100);
+      while round < 3000 loop {
+        total <- total + sum(build(40));
+        -- Replace the tail of a cell of the long-lived list by a new cell pointing to the rest
+        cell <- kept;
+        let skip : Int <- round - round / 90 * 90 in
+          while 0 < skip loop { cell <- cell.next(); skip <- skip - 1; } pool;
+        cell.set_next((new Cell).init(round, cell.next().copy().next()));
+        text <- text.concat(if round - round / 10 * 10 = 0 then "x" else "" fi);
+        round <- round + 1;
+      } pool;
+      out_int(total);
+      out_string("\n");
+      out_int(sum(kept));
+      out_string("\n");
+      out_int(text.length());
+      out_string("\n");
+    }
+  };
+};
@@ -0,0 +1,48 @@
+(* Exercises the corners of native code generation: stack arguments, integer wrapping, the runtime's built-in
+   methods, new SELF_TYPE, case and equality of basic objects *)
+class Counter {
+  count : Int;
+  next() : SELF_TYPE { { count <- count + 1; self; } };
+  count() : Int { count };
+  fresh() : SELF_TYPE { new SELF_TYPE };
+};
+
+class Loud inherits Counter {};
+
+class Main inherits IO {
+  sum(a : Int, b : Int, c : Int, d : Int, e : Int, f : Int, g : Int, h : Int, i : Int) : Int {
+    a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8 - i
+  };
+
+  line(x : Int) : SELF_TYPE { { out_int(x); out_string("\n"); } };
+
+  describe(x : Object) : String {
+    case x of
+      i : Int => "Int";
+      s : String => "String ".concat(s);
+      c : Loud => "Loud";
+      c : Counter => "Counter";
+      o : Object => o.type_name();
+    esac
+  };
+
+  main() : Object {
+    let min : Int <- ~2147483647 - 1, c : Counter <- new Loud, s : String <- "tab\there \"quoted\"\n" in {
+      line(sum(1, 2, 3, 4, 5, 6, 7, 8, 9));
+      line(min / ~1);
+      line(min - 1);
+      line(2147483647 * 2);
+      line(~7 / 2);
+      line(c.next().next().count());
+      line(c.fresh().count());
+      line(c.copy().next().count() + c.count());
+      out_string(c.fresh().type_name().concat("\n"));
+      out_string(s);
+      line(s.length());
+      out_string(s.substr(4, 4).concat("|\n"));
+      out_string(describe(3).concat(describe("x")).concat(describe(c)).concat(describe(new Counter)).concat(describe(true)).concat("\n"));
+      if 3 = 3 then if "ab" = "a".concat("b") then if not (c = c.copy()) then out_string("equal\n") else abort() fi else abort() fi else abort() fi;
+      s.substr(20, 5);
+    }
+  };
+};
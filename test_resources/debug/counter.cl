@@ -0,0 +1,29 @@
+class Counter {
+  count : Int;
+
+  add(n : Int) : Int {
+    {
+      count <- count + n;
+      count;
+    }
+  };
+};
+
+class Loud inherits Counter {
+};
+
+class Main inherits IO {
+  counter : Counter <- new Loud;
+
+  main() : Object {
+    let total : Int, i : Int <- 1 in {
+      while i <= 3 loop {
+        total <- counter.add(i);
+        i <- i + 1;
+      } pool;
+      out_int(total);
+      out_string("\n");
+      let zero : Int in total / zero;
+    }
+  };
+};
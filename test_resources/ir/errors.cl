@@ -0,0 +1,27 @@
+(* Fails with the runtime error chosen by the number read *)
+class Node {
+  next : Node;
+
+  link(node : Node) : Node {
+    {
+      next <- node;
+      self;
+    }
+  };
+};
+
+class Main inherits IO {
+  void : Node;
+
+  main() : Object {
+    let choice : Int <- in_int() in
+      if choice = 1 then void.link(void) else
+      if choice = 2 then case void of node : Node => node; esac else
+      if choice = 3 then case 3 of text : String => text; esac else
+      if choice = 4 then "cool".substr(2, 3) else
+      if choice = 5 then "cool".substr(~1, 2) else
+      if choice = 6 then 1 / (choice - 6) else
+      let list : Node in while true loop list <- (new Node).link(list) pool
+      fi fi fi fi fi fi
+  };
+};
@@ -0,0 +1,29 @@
+class Main inherits IO {
+  count : Int <- 1;
+
+  sum(n : Int) : Int {
+    let total : Int in {
+      while 0 < n loop {
+        total <- total + n;
+        n <- n - 1;
+      } pool;
+      total;
+    }
+  };
+
+  describe(x : Object) : String {
+    case x of
+      i : Int => "int";
+      s : String => s;
+      o : Object => o.type_name();
+    esac
+  };
+
+  main() : Object {
+    out_int(if count = 1 then sum(10) else count fi)
+  };
+};
+
+class Shouter inherits Main {
+  describe(x : Object) : String { "LOUD" };
+};
@@ -0,0 +1,16 @@
+class Main inherits IO {
+  fib(n : Int) : Int {
+    if n < 2 then n else fib(n - 1) + fib(n - 2) fi
+  };
+
+  unused() : Int {
+    0
+  };
+
+  main() : Object {
+    {
+      out_int(fib(5));
+      out_string("\n".concat(type_name()));
+    }
+  };
+};
@@ -67,7 +67,7 @@ Class BookList inherits IO {
     *)
     cdr() : BookList { { abort(); new BookList; } };
     
-    print_list() : OBJECT { abort() };
+    print_list() : Object { abort() };
 };
 
 Class Cons inherits BookList {
@@ -90,7 +90,7 @@ Class Cons inherits BookList {
 
     cdr() : BookList { xcdr };
     
-    print_list() : OBJECT {
+    print_list() : Object {
         {
             case xcar.print() of
                 dummy : Book => out_string("- dynamic type was Book -\n");
@@ -104,7 +104,7 @@ Class Cons inherits BookList {
 Class Nil inherits BookList {
     isNil() : Bool { true };
 
-    print_list() : OBJECT { true };
+    print_list() : Object { true };
 };
 
 
@@ -112,7 +112,7 @@ Class Main {
 
     books : BookList;
 
-    main() : OBJECT {
+    main() : Object {
         (let a_book : Book <-
             (new Book).initBook("Compilers, Principles, Techniques, and Tools",
                                 "Aho, Sethi, and Ullman")
@@ -1,7 +1,7 @@
 class Main inherits IO { -- test comment with end of line
     main() : SELF_TYPE {
         {
-            out_string((new OBJECT).type_name().substr(4,1));
+            out_string((new Object).type_name().substr(4,1));
             out_string((isvoid self).type_name().substr(1,3));
             out_string("\n");
         }
@@ -0,0 +1,14 @@
+class Main inherits IO {
+    divide(a : Int, b : Int) : Int {
+        a / b
+    };
+
+    main() : Object {
+        {
+            out_int(divide(12, 4));
+            out_string("\n");
+            out_int(divide(1, 0));
+            out_string("never printed\n");
+        }
+    };
+};
@@ -0,0 +1,17 @@
+class Main inherits IO {
+    describe(x : Object) : String {
+        case x of
+            s : String => s;
+            b : Bool => if b then "true" else "false" fi;
+        esac
+    };
+
+    main() : Object {
+        {
+            out_string(describe("text").concat("\n"));
+            out_string(describe(true).concat("\n"));
+            out_string(describe(3));
+            out_string("never printed\n");
+        }
+    };
+};
@@ -0,0 +1,10 @@
+class Main inherits IO {
+    main() : Object {
+        let word : String <- "runtime" in
+            {
+                out_string(word.substr(3, 4).concat("\n"));
+                out_string(word.substr(5, 3));
+                out_string("never printed\n");
+            }
+    };
+};
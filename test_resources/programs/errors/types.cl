@@ -0,0 +1,10 @@
+class Main inherits IO {
+    count : Int <- "three";
+
+    main() : Object {
+        {
+            out_int(count + true);
+            undefined();
+        }
+    };
+};
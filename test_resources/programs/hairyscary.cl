@@ -17,7 +17,7 @@ class Bar inherits Razz {
 
      c : Int <- doh();
 
-     d : OBJECT <- printh();
+     d : Object <- printh();
 };
 
 
@@ -43,7 +43,7 @@ class Bazz inherits IO {
 			n : Bar => n;
 		  esac;
 
-     i : OBJECT <- printh();
+     i : Object <- printh();
 
      printh() : Int { { out_int(h); 0; } };
 
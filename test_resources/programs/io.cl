@@ -52,7 +52,7 @@ class A {
 
    io : IO <- new IO;
 
-   out_a() : OBJECT { io.out_string("A: Hello world\n") };
+   out_a() : Object { io.out_string("A: Hello world\n") };
 
 };
 
@@ -61,7 +61,7 @@ class B inherits A {
 
    -- B does not have to an extra attribute, since it inherits io from A.
 
-   out_b() : OBJECT { io.out_string("B: Hello world\n") };
+   out_b() : Object { io.out_string("B: Hello world\n") };
 
 };
 
@@ -70,7 +70,7 @@ class C inherits IO {
 
    -- Now the IO methods are part of C.
 
-   out_c() : OBJECT { out_string("C: Hello world\n") };
+   out_c() : Object { out_string("C: Hello world\n") };
 
    -- Note that out_string(...) is just a shorthand for self.out_string(...)
 
@@ -81,7 +81,7 @@ class D inherits C {
 
    -- Inherits IO methods from C.
 
-   out_d() : OBJECT { out_string("D: Hello world\n") };
+   out_d() : Object { out_string("D: Hello world\n") };
 
 };
 
@@ -90,7 +90,7 @@ class Main inherits IO {
 
    -- Same case as class C.
 
-   main() : OBJECT {
+   main() : Object {
       {
 	 (new A).out_a();
 	 (new B).out_b();
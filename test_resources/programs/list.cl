@@ -104,7 +104,7 @@ class Main inherits IO {
    -- Print all elements of the list. Calls itself recursively with
    -- the tail of the list, until the end of the list is reached.
 
-   print_list(l : List) : OBJECT {
+   print_list(l : List) : Object {
       if l.isNil() then out_string("\n")
                    else {
 			   out_int(l.head());
@@ -123,7 +123,7 @@ class Main inherits IO {
    -- List, and thus the method isNil in the List class is called and
    -- returns true.
 
-   main() : OBJECT {
+   main() : Object {
       {
 	 mylist <- new List.cons(1).cons(2).cons(3).cons(4).cons(5);
 	 while (not mylist.isNil()) loop
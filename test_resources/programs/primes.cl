@@ -42,7 +42,7 @@ class Main inherits IO {
 
   stop : Int <- 500;	-- stop is an arbitrary value limiting testee. 	
 
-  m : OBJECT <-		-- m supplants the main method.
+  m : Object <-		-- m supplants the main method.
     while true loop 
       {
 
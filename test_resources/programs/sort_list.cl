@@ -50,7 +50,7 @@ Class List inherits IO {
 
 	rcons(i : Int) : List { cdr() };
 	
-	print_list() : OBJECT { abort() };
+	print_list() : Object { abort() };
 };
 
 Class Cons inherits List {
@@ -88,7 +88,7 @@ Class Cons inherits List {
 
 	rcons(i : Int) : List { (new Cons).init(xcar, xcdr.rcons(i)) };
 
-	print_list() : OBJECT {
+	print_list() : Object {
 		{
 		     out_int(xcar);
 		     out_string("\n");
@@ -108,7 +108,7 @@ Class Nil inherits List {
 
 	rcons(i : Int) : List { (new Cons).init(i,self) };
 
-	print_list() : OBJECT { true };
+	print_list() : Object { true };
 
 };
 
@@ -134,7 +134,7 @@ Class Main inherits IO {
 	    }
 	};		
 
-	main() : OBJECT {
+	main() : Object {
 	   {
 	     out_string("How many numbers to sort?");
 	     iota(in_int()).rev().sort().print_list();
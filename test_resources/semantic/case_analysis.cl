@@ -0,0 +1,22 @@
+class A {};
+class B inherits A {};
+class C {};
+
+class Main {
+  a : A <- new B;
+  main() : Object {{
+    case a of
+      x : A => 1;
+      y : B => 2;
+    esac;
+    case a of
+      x : B => 1;
+      z : C => 3;
+    esac;
+    case a of
+      x : Object => 1;
+      y : A => 2;
+      z : A => 3;
+    esac;
+  }};
+};
@@ -0,0 +1,6 @@
+class Test inherits IO {
+  run() : Object { out_string("ok\n") };
+  check(x : Int) : Bool { x = 0 };
+};
+
+class SubTest inherits Test {};
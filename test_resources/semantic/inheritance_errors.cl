@@ -0,0 +1,13 @@
+class Main inherits Mian { main() : Object { 0 }; };
+
+class Number inherits Int { };
+
+class String { };
+
+class A inherits B { };
+
+class B inherits A { };
+
+class Self inherits Self { };
+
+class A { };
@@ -0,0 +1,20 @@
+class A {
+  count : Int <- 0;
+  unread : String;
+  get(unused : Int) : Int { count };
+};
+
+class Main inherits IO {
+  a : A <- new A;
+  main() : Object {{
+    let a : Int <- 1, spare : Int in a;
+    case a of x : A => x.get(0); esac;
+    while false loop out_string("never") pool;
+    if isvoid a.get(1) then abort() else 0 fi;
+    { abort(); out_string("dead"); };
+    { abort(); new A; };
+    -- cool-lint: allow(unused_variable)
+    let ignored : Int in 0;
+  }};
+  helper() : Object { 0 };
+};
@@ -0,0 +1,12 @@
+class A {
+  x : Int;
+  x : Bool;
+  f(a : Int, a : Bool) : Int { a };
+  g(self : Int) : Object { y };
+};
+
+class B inherits A {
+  x : String;
+  f(a : Int) : Int { z };
+  h() : Object { let self : Int <- 1, w : Int <- w in case w of w : Int => w; esac };
+};
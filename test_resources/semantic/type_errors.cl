@@ -0,0 +1,14 @@
+class Main inherits IO {
+  a : Int <- 1;
+  b : String <- a;
+  main() : Object {{
+    let c : Bool <- 3 in c;
+    a <- "x";
+    if a then 1 else 2 fi;
+    out_int(a).out_string(a);
+    a <- a + true;
+    a <- a < 2;
+    while not a loop 1 pool;
+    (new Main).undefined();
+  }};
+};
@@ -0,0 +1,372 @@
+use std::fmt::{Display, Formatter};
+
+/// A compiled program: a constant pool, the classes with their dispatch tables, and the methods.
+/// Classes are ordered by tag, so `classes[tag]` is the class with that tag, and instructions refer to constants,
+/// classes and methods by their index.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Program {
+  pub constants: Vec<Constant>,
+  pub classes: Vec<Class>,
+  pub methods: Vec<Method>,
+  pub entry_class: u16,
+  pub entry_method: u16,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Constant {
+  Int(i32),
+  Str(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Class {
+  pub name: String,
+  pub parent: Option<u16>,
+  pub max_descendant_tag: u16, // a class conforms to this one iff its tag is in `tag..=max_descendant_tag`
+  pub attributes: Vec<Attribute>,
+  pub vtable: Vec<u16>,     // method run by each dispatch slot
+  pub init: Option<u16>,    // attribute initialiser, none for the basic classes
+  pub file: Option<String>, // source file of the class, for runtime errors, none if unknown
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attribute {
+  pub name: String,
+  pub default: DefaultValue,
+}
+
+/// Value of an attribute before its initialiser runs
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DefaultValue {
+  Void,
+  Int,
+  Bool,
+  Str,
+}
+
+/// A method, with a body of bytecode or implemented by the VM.
+/// Its locals are `self`, the parameters, then the variables bound by `let` and `case`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Method {
+  pub name: String, // `Class.method`, or `Class._init` for an attribute initialiser
+  pub params: u16,
+  pub locals: u16,
+  pub builtin: Option<Builtin>,
+  pub code: Vec<u8>,
+  pub lines: Vec<(u16, u32)>, // offset where the code of each source line starts and the line, by offset
+}
+
+impl Method {
+  /// Source line of the instruction at the offset, none if unknown
+  pub fn line(&self, offset: usize) -> Option<u32> {
+    let index = self.lines.partition_point(|(start, _)| *start as usize <= offset);
+    index.checked_sub(1).map(|index| self.lines[index].1)
+  }
+}
+
+/// Methods of the basic classes
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Builtin {
+  Abort,
+  TypeName,
+  Copy,
+  OutString,
+  OutInt,
+  InString,
+  InInt,
+  Length,
+  Concat,
+  Substr,
+}
+
+impl Builtin {
+  pub const ALL: [Builtin; 10] = [
+    Builtin::Abort,
+    Builtin::TypeName,
+    Builtin::Copy,
+    Builtin::OutString,
+    Builtin::OutInt,
+    Builtin::InString,
+    Builtin::InInt,
+    Builtin::Length,
+    Builtin::Concat,
+    Builtin::Substr,
+  ];
+
+  /// The built-in implementing a method of a basic class, e.g. `IO.out_string`
+  pub fn of(method_name: &str) -> Option<Builtin> {
+    Builtin::ALL.into_iter().find(|builtin| builtin.method_name() == method_name)
+  }
+
+  pub fn method_name(self) -> &'static str {
+    match self {
+      Builtin::Abort => "Object.abort",
+      Builtin::TypeName => "Object.type_name",
+      Builtin::Copy => "Object.copy",
+      Builtin::OutString => "IO.out_string",
+      Builtin::OutInt => "IO.out_int",
+      Builtin::InString => "IO.in_string",
+      Builtin::InInt => "IO.in_int",
+      Builtin::Length => "String.length",
+      Builtin::Concat => "String.concat",
+      Builtin::Substr => "String.substr",
+    }
+  }
+}
+
+/// One instruction of a method body. Each is encoded as an opcode byte followed by its operands, 16-bit little
+/// endian. Operands of the stack are popped and results pushed; jump targets are offsets in the method's code.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction {
+  Const(u16),
+  True,
+  False,
+  Void,
+  Load(u16),
+  Store(u16),
+  LoadAttr(u16),  // attribute of self
+  StoreAttr(u16),
+  Pop,
+  Dup,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Lt,
+  Le,
+  Eq,
+  Neg,
+  Not,
+  IsVoid,
+  Jump(u16),
+  JumpIfFalse(u16),
+  New(u16),       // allocates an object of the class and runs its initialiser
+  NewSelf,        // `new SELF_TYPE`, an object of the class of self
+  Dispatch { slot: u16, args: u16 },         // arguments then receiver on the stack, method found in its vtable
+  StaticDispatch { method: u16, args: u16 }, // same with a known method, for `@Class` and initialisers
+  Return,
+  CaseCheck,      // stops the program if the value of a case is void, leaving it on the stack
+  CaseMatch(u16), // pushes whether the class of the value of a case conforms to the class, leaving the value
+  CaseFail,       // stops the program as no branch matched the value of a case
+}
+
+impl Instruction {
+  /// Decodes the instruction at the offset, returning the offset of the next one
+  pub fn decode(code: &[u8], offset: usize) -> Result<(Instruction, usize), String> {
+    let opcode = *code.get(offset).ok_or_else(|| format!("Missing instruction at {offset}"))?;
+    let operand = |index: usize| -> Result<u16, String> {
+      let start = offset + 1 + 2 * index;
+      match code.get(start..start + 2) {
+        Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+        None => Err(format!("Truncated instruction at {offset}")),
+      }
+    };
+    let instruction = match opcode {
+      0 => Instruction::Const(operand(0)?),
+      1 => Instruction::True,
+      2 => Instruction::False,
+      3 => Instruction::Void,
+      4 => Instruction::Load(operand(0)?),
+      5 => Instruction::Store(operand(0)?),
+      6 => Instruction::LoadAttr(operand(0)?),
+      7 => Instruction::StoreAttr(operand(0)?),
+      8 => Instruction::Pop,
+      9 => Instruction::Dup,
+      10 => Instruction::Add,
+      11 => Instruction::Sub,
+      12 => Instruction::Mul,
+      13 => Instruction::Div,
+      14 => Instruction::Lt,
+      15 => Instruction::Le,
+      16 => Instruction::Eq,
+      17 => Instruction::Neg,
+      18 => Instruction::Not,
+      19 => Instruction::IsVoid,
+      20 => Instruction::Jump(operand(0)?),
+      21 => Instruction::JumpIfFalse(operand(0)?),
+      22 => Instruction::New(operand(0)?),
+      23 => Instruction::NewSelf,
+      24 => Instruction::Dispatch { slot: operand(0)?, args: operand(1)? },
+      25 => Instruction::StaticDispatch { method: operand(0)?, args: operand(1)? },
+      26 => Instruction::Return,
+      27 => Instruction::CaseCheck,
+      28 => Instruction::CaseMatch(operand(0)?),
+      29 => Instruction::CaseFail,
+      _ => return Err(format!("Unknown opcode {opcode} at {offset}")),
+    };
+    Ok((instruction, offset + instruction.size()))
+  }
+
+  pub fn encode(&self, code: &mut Vec<u8>) {
+    code.push(self.opcode());
+    for operand in self.operands() {
+      code.extend(operand.to_le_bytes());
+    }
+  }
+
+  /// Size in bytes of the encoded instruction
+  pub fn size(&self) -> usize {
+    match self {
+      Instruction::Dispatch { .. } | Instruction::StaticDispatch { .. } => 5,
+      Instruction::Const(_) |
+      Instruction::Load(_) |
+      Instruction::Store(_) |
+      Instruction::LoadAttr(_) |
+      Instruction::StoreAttr(_) |
+      Instruction::Jump(_) |
+      Instruction::JumpIfFalse(_) |
+      Instruction::New(_) |
+      Instruction::CaseMatch(_) => 3,
+      _ => 1,
+    }
+  }
+
+  fn opcode(&self) -> u8 {
+    match self {
+      Instruction::Const(_) => 0,
+      Instruction::True => 1,
+      Instruction::False => 2,
+      Instruction::Void => 3,
+      Instruction::Load(_) => 4,
+      Instruction::Store(_) => 5,
+      Instruction::LoadAttr(_) => 6,
+      Instruction::StoreAttr(_) => 7,
+      Instruction::Pop => 8,
+      Instruction::Dup => 9,
+      Instruction::Add => 10,
+      Instruction::Sub => 11,
+      Instruction::Mul => 12,
+      Instruction::Div => 13,
+      Instruction::Lt => 14,
+      Instruction::Le => 15,
+      Instruction::Eq => 16,
+      Instruction::Neg => 17,
+      Instruction::Not => 18,
+      Instruction::IsVoid => 19,
+      Instruction::Jump(_) => 20,
+      Instruction::JumpIfFalse(_) => 21,
+      Instruction::New(_) => 22,
+      Instruction::NewSelf => 23,
+      Instruction::Dispatch { .. } => 24,
+      Instruction::StaticDispatch { .. } => 25,
+      Instruction::Return => 26,
+      Instruction::CaseCheck => 27,
+      Instruction::CaseMatch(_) => 28,
+      Instruction::CaseFail => 29,
+    }
+  }
+
+  fn operands(&self) -> Vec<u16> {
+    match *self {
+      Instruction::Const(operand) |
+      Instruction::Load(operand) |
+      Instruction::Store(operand) |
+      Instruction::LoadAttr(operand) |
+      Instruction::StoreAttr(operand) |
+      Instruction::Jump(operand) |
+      Instruction::JumpIfFalse(operand) |
+      Instruction::New(operand) |
+      Instruction::CaseMatch(operand) => vec![operand],
+      Instruction::Dispatch { slot, args } => vec![slot, args],
+      Instruction::StaticDispatch { method, args } => vec![method, args],
+      _ => Vec::new(),
+    }
+  }
+}
+
+impl Display for Instruction {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Instruction::Const(_) => "const",
+      Instruction::True => "true",
+      Instruction::False => "false",
+      Instruction::Void => "void",
+      Instruction::Load(_) => "load",
+      Instruction::Store(_) => "store",
+      Instruction::LoadAttr(_) => "load_attr",
+      Instruction::StoreAttr(_) => "store_attr",
+      Instruction::Pop => "pop",
+      Instruction::Dup => "dup",
+      Instruction::Add => "add",
+      Instruction::Sub => "sub",
+      Instruction::Mul => "mul",
+      Instruction::Div => "div",
+      Instruction::Lt => "lt",
+      Instruction::Le => "le",
+      Instruction::Eq => "eq",
+      Instruction::Neg => "neg",
+      Instruction::Not => "not",
+      Instruction::IsVoid => "isvoid",
+      Instruction::Jump(_) => "jump",
+      Instruction::JumpIfFalse(_) => "jump_if_false",
+      Instruction::New(_) => "new",
+      Instruction::NewSelf => "new_self",
+      Instruction::Dispatch { .. } => "dispatch",
+      Instruction::StaticDispatch { .. } => "static_dispatch",
+      Instruction::Return => "return",
+      Instruction::CaseCheck => "case_check",
+      Instruction::CaseMatch(_) => "case_match",
+      Instruction::CaseFail => "case_fail",
+    };
+    write!(f, "{name}")?;
+    for operand in self.operands() {
+      write!(f, " {operand}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Program {
+  pub fn class(&self, name: &str) -> Option<&Class> {
+    self.classes.iter().find(|class| class.name == name)
+  }
+
+  /// Checks that every index refers to an existing constant, class, method, attribute or local, and every jump
+  /// to an instruction of its method, so the VM can run the program without checking them
+  pub fn validate(&self) -> Result<(), String> {
+    let check = |valid: bool, message: &dyn Fn() -> String| if valid { Ok(()) } else { Err(message()) };
+    let (classes, methods) = (self.classes.len(), self.methods.len());
+
+    for (tag, class) in self.classes.iter().enumerate() {
+      check(class.parent.is_none_or(|parent| (parent as usize) < tag), &|| format!("Class {} has an invalid parent", class.name))?;
+      check((class.max_descendant_tag as usize) < classes && class.max_descendant_tag as usize >= tag, &|| format!("Class {} has invalid descendants", class.name))?;
+      check(class.vtable.iter().chain(&class.init).all(|method| (*method as usize) < methods), &|| format!("Class {} refers to a missing method", class.name))?;
+    }
+    check((self.entry_class as usize) < classes && (self.entry_method as usize) < methods, &|| "Invalid entry point".to_string())?;
+
+    for method in &self.methods {
+      let in_method = |message: String| format!("{}: {message}", method.name);
+      check(method.params < method.locals, &|| in_method("Fewer locals than parameters".to_string()))?;
+      if method.builtin.is_some() {
+        continue;
+      }
+
+      let mut starts = Vec::new();
+      let mut targets = Vec::new();
+      let mut offset = 0;
+      while offset < method.code.len() {
+        starts.push(offset);
+        let (instruction, next) = Instruction::decode(&method.code, offset).map_err(in_method)?;
+        let valid = match instruction {
+          Instruction::Const(index) => (index as usize) < self.constants.len(),
+          Instruction::Load(index) | Instruction::Store(index) => index < method.locals,
+          Instruction::New(tag) | Instruction::CaseMatch(tag) => (tag as usize) < classes,
+          Instruction::StaticDispatch { method, .. } => (method as usize) < methods,
+          Instruction::Jump(target) | Instruction::JumpIfFalse(target) => {
+            targets.push(target as usize);
+            true
+          }
+          _ => true,
+        };
+        check(valid, &|| in_method(format!("Invalid operand of `{instruction}` at {offset}")))?;
+        offset = next;
+      }
+      check(targets.iter().all(|target| starts.binary_search(target).is_ok()), &|| in_method("Jump between instructions".to_string()))?;
+      let line_starts = method.lines.iter().map(|(start, _)| *start as usize);
+      check(line_starts.is_sorted() && method.lines.iter().all(|(start, _)| starts.binary_search(&(*start as usize)).is_ok()), &|| in_method("Invalid line table".to_string()))?;
+      let last = starts.last().map(|offset| Instruction::decode(&method.code, *offset).map(|(instruction, _)| instruction));
+      check(matches!(last, Some(Ok(Instruction::Return | Instruction::Jump(_) | Instruction::CaseFail))), &|| in_method("Code runs past its end".to_string()))?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,436 @@
+use crate::bytecode::{Attribute, Builtin, Class, Constant, DefaultValue, Instruction, Method, Program};
+use lexer::model::constants::KEYWORD_SELF_TYPE;
+use parser::model::class::{ParseClass, BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use parser::model::expressions::{CaseBranch, Expression};
+use parser::model::feature::ParseFeature;
+use semantic::environments::SELF_OBJECT_NAME;
+use semantic::models::class::PRIMITIVE_TYPES;
+use semantic::models::layout::ClassLayout;
+use semantic::models::program::CheckedProgram;
+use std::collections::HashMap;
+
+/// Suffix of the method that runs the attribute initialisers of a class, e.g. `Main._init`
+pub const INIT_METHOD_NAME: &str = "_init";
+
+/// Compiles a checked program to bytecode.
+///
+/// Values are dynamically typed on the VM, so `Int`, `Bool` and `String` values are never boxed. Methods are
+/// numbered as the IR numbers functions: the methods of the basic classes, then for each class its initialiser
+/// and its methods in declaration order.
+pub fn compile_program(checked: &CheckedProgram) -> Result<Program, String> {
+  let mut layouts: Vec<&ClassLayout> = checked.layouts.values().collect();
+  layouts.sort_by_key(|layout| layout.tag);
+
+  let mut methods = Vec::new();
+  for layout in &layouts {
+    if PRIMITIVE_TYPES.contains(&layout.class_name.as_str()) {
+      for method in checked.env.methods.methods_of(&layout.class_name) {
+        let name = format!("{}.{}", layout.class_name, method.name);
+        let builtin = Builtin::of(&name).ok_or_else(|| format!("No built-in implements {name}"))?;
+        let params = method.formals.len() as u16;
+        methods.push(Method { name, params, locals: params + 1, builtin: Some(builtin), code: Vec::new(), lines: Vec::new() });
+      }
+    }
+  }
+  for class in checked.program.classes() {
+    let class_name = class.get_name();
+    methods.push(Method { name: format!("{class_name}.{INIT_METHOD_NAME}"), params: 0, locals: 1, builtin: None, code: Vec::new(), lines: Vec::new() });
+    for feature in class.features.iter().flatten() {
+      if let ParseFeature::Method { method } = feature {
+        let params = method.formals.iter().flatten().count() as u16;
+        let name = format!("{class_name}.{}", method.get_name());
+        methods.push(Method { name, params, locals: params + 1, builtin: None, code: Vec::new(), lines: Vec::new() });
+      }
+    }
+  }
+  let method_index: HashMap<String, u16> = methods.iter().enumerate().map(|(index, method)| (method.name.clone(), index as u16)).collect();
+
+  let mut compiler = Compiler { checked, method_index, constants: Vec::new() };
+  let classes = layouts.iter().map(|layout| compiler.class(layout)).collect();
+  for class in checked.program.classes() {
+    compiler.compile_class(class, &mut methods)?;
+  }
+
+  let entry_layout = &checked.layouts[&checked.entry.class_name];
+  let entry_slot = entry_layout.method_index(&checked.entry.method_name).expect("Entry point is checked by the semantic pass");
+  let entry_method = compiler.method(&format!("{}.{}", entry_layout.dispatch_table[entry_slot].class_name, checked.entry.method_name));
+  if methods.len() > u16::MAX as usize || compiler.constants.len() > u16::MAX as usize {
+    return Err("The program has more than 65535 methods or constants".to_string());
+  }
+  Ok(Program { constants: compiler.constants, classes, methods, entry_class: entry_layout.tag as u16, entry_method })
+}
+
+/// State shared by the methods of the program
+struct Compiler<'a, 'p> {
+  checked: &'a CheckedProgram<'p>,
+  method_index: HashMap<String, u16>,
+  constants: Vec<Constant>,
+}
+
+impl Compiler<'_, '_> {
+  fn method(&self, name: &str) -> u16 {
+    *self.method_index.get(name).unwrap_or_else(|| panic!("Method {name} is declared"))
+  }
+
+  fn class(&self, layout: &ClassLayout) -> Class {
+    let name = layout.class_name.clone();
+    let parent = self.checked.env.class_map.get(&name)
+        .and_then(|node| self.checked.layouts.get(&node.parent.to_string()))
+        .map(|parent| parent.tag as u16);
+    let attributes = layout.attributes.iter()
+        .map(|slot| {
+          let default = match slot.attr_type.as_str() {
+            INT_CLASS_NAME => DefaultValue::Int,
+            BOOL_CLASS_NAME => DefaultValue::Bool,
+            STR_CLASS_NAME => DefaultValue::Str,
+            _ => DefaultValue::Void,
+          };
+          Attribute { name: slot.name.clone(), default }
+        })
+        .collect();
+    let vtable = layout.dispatch_table.iter().map(|slot| self.method(&format!("{}.{}", slot.class_name, slot.name))).collect();
+    let init = (!PRIMITIVE_TYPES.contains(&name.as_str())).then(|| self.method(&format!("{name}.{INIT_METHOD_NAME}")));
+    // The checked program does not know the files of the classes, the driver adds them
+    Class { name, parent, max_descendant_tag: layout.max_descendant_tag as u16, attributes, vtable, init, file: None }
+  }
+
+  /// Compiles the initialiser and the methods of the class, whose entries are already in `methods`
+  fn compile_class(&mut self, class: &ParseClass, methods: &mut [Method]) -> Result<(), String> {
+    let class_name = class.get_name();
+
+    let index = self.method(&format!("{class_name}.{INIT_METHOD_NAME}"));
+    // `Class._init(self)`: runs the initialiser of the parent class, then those of the attributes in declaration order
+    let mut init = MethodCompiler::new(self, class_name.clone(), 0);
+    let parent = class.parent_type.get_name();
+    if !PRIMITIVE_TYPES.contains(&parent.as_str()) {
+      init.emit(Instruction::Load(0));
+      let method = init.compiler.method(&format!("{parent}.{INIT_METHOD_NAME}"));
+      init.emit(Instruction::StaticDispatch { method, args: 0 });
+      init.emit(Instruction::Pop);
+    }
+    for feature in class.features.iter().flatten() {
+      if let ParseFeature::Attribute { attribute } = feature {
+        if let Some(expr) = &attribute.expr {
+          init.expression(expr);
+          let index = init.attribute(&attribute.get_name());
+          init.emit(Instruction::StoreAttr(index));
+        }
+      }
+    }
+    init.emit(Instruction::Load(0));
+    init.emit(Instruction::Return);
+    init.finish(&mut methods[index as usize])?;
+
+    for feature in class.features.iter().flatten() {
+      let ParseFeature::Method { method } = feature else {
+        continue;
+      };
+      let index = self.method(&format!("{class_name}.{}", method.get_name()));
+      let formals: Vec<String> = method.formals.iter().flatten().map(|formal| formal.formal_name.get_name()).collect();
+      let mut compiler = MethodCompiler::new(self, class_name.clone(), formals.len() as u16);
+      compiler.scopes = formals.into_iter().zip(1..).collect();
+      compiler.expression(&method.expr);
+      compiler.emit(Instruction::Return);
+      compiler.finish(&mut methods[index as usize])?;
+    }
+    Ok(())
+  }
+
+  fn constant(&mut self, constant: Constant) -> u16 {
+    let index = self.constants.iter().position(|existing| *existing == constant).unwrap_or_else(|| {
+      self.constants.push(constant);
+      self.constants.len() - 1
+    });
+    index as u16
+  }
+}
+
+/// Compiles the body of one method. Expressions leave their value on the stack.
+struct MethodCompiler<'c, 'a, 'p> {
+  compiler: &'c mut Compiler<'a, 'p>,
+  class_name: String,
+  code: Vec<u8>,
+  lines: Vec<(u16, u32)>,
+  line: u32, // source line of the expression compiled, 0 if unknown
+  scopes: Vec<(String, u16)>, // local of each variable in scope, innermost last
+  next_local: u16,
+  locals: u16,
+}
+
+impl<'c, 'a, 'p> MethodCompiler<'c, 'a, 'p> {
+  fn new(compiler: &'c mut Compiler<'a, 'p>, class_name: String, params: u16) -> Self {
+    MethodCompiler {
+      compiler,
+      class_name,
+      code: Vec::new(),
+      lines: Vec::new(),
+      line: 0,
+      scopes: Vec::new(),
+      next_local: params + 1,
+      locals: params + 1,
+    }
+  }
+
+  fn finish(self, method: &mut Method) -> Result<(), String> {
+    if self.code.len() > u16::MAX as usize {
+      return Err(format!("Method {} is too large, its code is over 64 KiB", method.name));
+    }
+    method.locals = self.locals;
+    method.code = self.code;
+    method.lines = self.lines;
+    Ok(())
+  }
+
+  fn emit(&mut self, instruction: Instruction) {
+    if self.line > 0 && self.lines.last().is_none_or(|(_, line)| *line != self.line) {
+      self.lines.push((self.here(), self.line));
+    }
+    instruction.encode(&mut self.code);
+  }
+
+  /// Emits a jump to a target not yet known, returning the offset to patch
+  fn emit_jump(&mut self, jump: fn(u16) -> Instruction) -> usize {
+    self.emit(jump(0));
+    self.code.len() - 2
+  }
+
+  /// Makes the jump at the offset go to the end of the code
+  fn patch(&mut self, offset: usize) {
+    let target = (self.code.len() as u16).to_le_bytes();
+    self.code[offset..offset + 2].copy_from_slice(&target);
+  }
+
+  fn here(&self) -> u16 {
+    self.code.len() as u16
+  }
+
+  /// Binds the variable to a new local, which takes the value on the stack
+  fn bind(&mut self, name: String) {
+    let local = self.next_local;
+    self.next_local += 1;
+    self.locals = self.locals.max(self.next_local);
+    self.emit(Instruction::Store(local));
+    self.scopes.push((name, local));
+  }
+
+  fn unbind(&mut self, count: usize) {
+    self.scopes.truncate(self.scopes.len() - count);
+    self.next_local -= count as u16;
+  }
+
+  fn lookup_variable(&self, name: &str) -> Option<u16> {
+    self.scopes.iter().rev().find(|(var_name, _)| var_name == name).map(|(_, local)| *local)
+  }
+
+  fn attribute(&self, name: &str) -> u16 {
+    self.compiler.checked.layouts[&self.class_name].attribute_index(name).expect("Identifiers are checked by the semantic pass") as u16
+  }
+
+  /// Compiles the expression, its instructions taking its line and those of its subexpressions theirs
+  fn expression(&mut self, expr: &Expression) {
+    let outer = self.line;
+    self.line = match expr.get_pos().0 {
+      0 => outer,
+      line => line,
+    };
+    self.expression_code(expr);
+    self.line = outer;
+  }
+
+  fn expression_code(&mut self, expr: &Expression) {
+    match expr {
+      Expression::PartialAssign { .. } |
+      Expression::PartialDispatch { .. } |
+      Expression::PartialCastDispatch { .. } |
+      Expression::PartialBinary { .. } => unreachable!("Partial expressions are rejected by the semantic checks"),
+
+      Expression::IntExpr { value, .. } => {
+        let index = self.compiler.constant(Constant::Int(*value));
+        self.emit(Instruction::Const(index));
+      }
+      Expression::BoolExpr { value, .. } => self.emit(if *value { Instruction::True } else { Instruction::False }),
+      Expression::StringExpr { value, .. } => {
+        let index = self.compiler.constant(Constant::Str(value.clone()));
+        self.emit(Instruction::Const(index));
+      }
+      Expression::SelfExpr | Expression::SelfTypeExpr { .. } => self.emit(Instruction::Load(0)),
+
+      Expression::IdentExpr { name } => {
+        let name = name.get_name();
+        if name == SELF_OBJECT_NAME {
+          self.emit(Instruction::Load(0));
+        } else if let Some(local) = self.lookup_variable(&name) {
+          self.emit(Instruction::Load(local));
+        } else {
+          let index = self.attribute(&name);
+          self.emit(Instruction::LoadAttr(index));
+        }
+      }
+
+      Expression::Assign { name, expr } => {
+        self.expression(expr);
+        self.emit(Instruction::Dup);
+        let name = name.get_name();
+        match self.lookup_variable(&name) {
+          Some(local) => self.emit(Instruction::Store(local)),
+          None => {
+            let index = self.attribute(&name);
+            self.emit(Instruction::StoreAttr(index));
+          }
+        }
+      }
+
+      Expression::New { type_name } => {
+        let type_name = type_name.get_name();
+        if type_name == KEYWORD_SELF_TYPE {
+          self.emit(Instruction::NewSelf);
+        } else {
+          self.emit(Instruction::New(self.compiler.checked.layouts[&type_name].tag as u16));
+        }
+      }
+
+      Expression::Dispatch { calling_expr, cast_type, fn_name, param_list } => {
+        // The arguments are evaluated before the receiver
+        for param in param_list {
+          self.expression(param);
+        }
+        self.expression(calling_expr);
+        let method = fn_name.get_name();
+        let args = param_list.len() as u16;
+
+        let static_class = match cast_type {
+          Some(cast_type) => cast_type.get_name(),
+          None => self.static_type(calling_expr),
+        };
+        let layout = &self.compiler.checked.layouts[&static_class];
+        let slot = layout.method_index(&method).expect("Method is checked by the semantic pass");
+        if cast_type.is_some() {
+          let method = self.compiler.method(&format!("{}.{method}", layout.dispatch_table[slot].class_name));
+          self.emit(Instruction::StaticDispatch { method, args });
+        } else {
+          self.emit(Instruction::Dispatch { slot: slot as u16, args });
+        }
+      }
+
+      Expression::Conditional { predicate, then_expr, else_expr } => {
+        self.expression(predicate);
+        let to_else = self.emit_jump(Instruction::JumpIfFalse);
+        self.expression(then_expr);
+        let to_end = self.emit_jump(Instruction::Jump);
+        self.patch(to_else);
+        self.expression(else_expr);
+        self.patch(to_end);
+      }
+
+      Expression::Loop { predicate, body } => {
+        let start = self.here();
+        self.expression(predicate);
+        let to_end = self.emit_jump(Instruction::JumpIfFalse);
+        self.expression(body);
+        self.emit(Instruction::Pop);
+        self.emit(Instruction::Jump(start));
+        self.patch(to_end);
+        self.emit(Instruction::Void);
+      }
+
+      Expression::Block { expr_list } => {
+        for (index, expr) in expr_list.iter().enumerate() {
+          if index > 0 {
+            self.emit(Instruction::Pop);
+          }
+          self.expression(expr);
+        }
+      }
+
+      Expression::Let { let_init, in_expr } => {
+        for init in let_init {
+          match &init.expr {
+            Some(init_expr) => self.expression(init_expr),
+            None => self.default_value(&init.id_type.get_name()),
+          }
+          self.bind(init.id.get_name());
+        }
+        self.expression(in_expr);
+        self.unbind(let_init.len());
+      }
+
+      Expression::Case { switch_expression, branches } => self.case(switch_expression, branches),
+
+      Expression::Plus { left, right } => self.binary(Instruction::Add, left, right),
+      Expression::Minus { left, right } => self.binary(Instruction::Sub, left, right),
+      Expression::Multiply { left, right } => self.binary(Instruction::Mul, left, right),
+      Expression::Divide { left, right } => self.binary(Instruction::Div, left, right),
+      Expression::LessThan { left, right } => self.binary(Instruction::Lt, left, right),
+      Expression::LessThanOrEqual { left, right } => self.binary(Instruction::Le, left, right),
+      Expression::Equal { left, right } => self.binary(Instruction::Eq, left, right),
+
+      Expression::Negate { expr } => {
+        self.expression(expr);
+        self.emit(Instruction::Neg);
+      }
+      Expression::Not { expr } => {
+        self.expression(expr);
+        self.emit(Instruction::Not);
+      }
+      Expression::IsVoid { expr } => {
+        self.expression(expr);
+        self.emit(Instruction::IsVoid);
+      }
+    }
+  }
+
+  fn binary(&mut self, instruction: Instruction, left: &Expression, right: &Expression) {
+    self.expression(left);
+    self.expression(right);
+    self.emit(instruction);
+  }
+
+  /// Pushes the value of a variable declared without an initialiser
+  fn default_value(&mut self, type_name: &str) {
+    match type_name {
+      INT_CLASS_NAME => {
+        let index = self.compiler.constant(Constant::Int(0));
+        self.emit(Instruction::Const(index));
+      }
+      BOOL_CLASS_NAME => self.emit(Instruction::False),
+      STR_CLASS_NAME => {
+        let index = self.compiler.constant(Constant::Str(String::new()));
+        self.emit(Instruction::Const(index));
+      }
+      _ => self.emit(Instruction::Void),
+    }
+  }
+
+  /// Tests the branches from the most to the least specific type, so the first branch whose type the dynamic class
+  /// of the value conforms to is the one with the closest ancestor
+  fn case(&mut self, switch_expression: &Expression, branches: &[CaseBranch]) {
+    self.expression(switch_expression);
+    self.emit(Instruction::CaseCheck);
+
+    let hierarchy = &self.compiler.checked.env.hierarchy;
+    let mut ordered: Vec<&CaseBranch> = branches.iter().collect();
+    ordered.sort_by_key(|branch| std::cmp::Reverse(hierarchy.depth(&branch.id_type.get_name()).unwrap_or(0)));
+
+    let mut to_end = Vec::new();
+    for branch in ordered {
+      let tag = self.compiler.checked.layouts[&branch.id_type.get_name()].tag;
+      self.emit(Instruction::CaseMatch(tag as u16));
+      let to_next = self.emit_jump(Instruction::JumpIfFalse);
+      self.bind(branch.id.get_name());
+      self.expression(&branch.expr);
+      self.unbind(1);
+      to_end.push(self.emit_jump(Instruction::Jump));
+      self.patch(to_next);
+    }
+    self.emit(Instruction::CaseFail);
+    for offset in to_end {
+      self.patch(offset);
+    }
+  }
+
+  /// Class of the static type of the expression, with `SELF_TYPE` standing for the current class
+  fn static_type(&self, expr: &Expression) -> String {
+    let type_name = self.compiler.checked.types.get(expr).expect("Every expression is typed by the semantic pass");
+    if type_name == KEYWORD_SELF_TYPE { self.class_name.clone() } else { type_name.to_string() }
+  }
+}
@@ -0,0 +1,100 @@
+use crate::bytecode::{Constant, DefaultValue, Instruction, Program};
+use std::fmt::Write;
+
+/// Lists the constants, the classes with their attributes and dispatch tables, and the code of every method, one
+/// instruction per line at its offset, preceded by the source line where its code starts. Operands that are indices
+/// are followed by what they refer to.
+pub fn disassemble(program: &Program) -> String {
+  let mut out = String::new();
+  let entry = &program.methods[program.entry_method as usize].name;
+  writeln!(out, "entry {} on {}", entry, program.classes[program.entry_class as usize].name).unwrap();
+
+  writeln!(out, "\nconstants").unwrap();
+  for (index, constant) in program.constants.iter().enumerate() {
+    writeln!(out, "  {index:<4} {}", constant_text(constant)).unwrap();
+  }
+
+  for (tag, class) in program.classes.iter().enumerate() {
+    write!(out, "\nclass {tag} {}", class.name).unwrap();
+    if let Some(parent) = class.parent {
+      write!(out, " inherits {}", program.classes[parent as usize].name).unwrap();
+    }
+    writeln!(out, ", tags {tag}..={}", class.max_descendant_tag).unwrap();
+    for (index, attribute) in class.attributes.iter().enumerate() {
+      let default = match attribute.default {
+        DefaultValue::Void => "void",
+        DefaultValue::Int => "0",
+        DefaultValue::Bool => "false",
+        DefaultValue::Str => "\"\"",
+      };
+      writeln!(out, "  attribute {index} {} = {default}", attribute.name).unwrap();
+    }
+    for (slot, method) in class.vtable.iter().enumerate() {
+      writeln!(out, "  slot {slot} {}", program.methods[*method as usize].name).unwrap();
+    }
+  }
+
+  for (index, method) in program.methods.iter().enumerate() {
+    write!(out, "\nmethod {index} {}, {} params", method.name, method.params).unwrap();
+    if method.builtin.is_some() {
+      writeln!(out, ", built-in").unwrap();
+      continue;
+    }
+    writeln!(out, ", {} locals", method.locals).unwrap();
+
+    let mut offset = 0;
+    while offset < method.code.len() {
+      let (instruction, next) = match Instruction::decode(&method.code, offset) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+          writeln!(out, "  {e}").unwrap();
+          break;
+        }
+      };
+      if let Some((_, line)) = method.lines.iter().find(|(start, _)| *start as usize == offset) {
+        writeln!(out, "  line {line}").unwrap();
+      }
+      let line = format!("  {offset:04}  {instruction}");
+      let comment = match instruction {
+        Instruction::Const(index) => program.constants.get(index as usize).map(constant_text),
+        Instruction::New(tag) | Instruction::CaseMatch(tag) => program.classes.get(tag as usize).map(|class| class.name.clone()),
+        Instruction::StaticDispatch { method, .. } => program.methods.get(method as usize).map(|method| method.name.clone()),
+        _ => None,
+      };
+      match comment {
+        Some(comment) => writeln!(out, "{line:<28}; {comment}").unwrap(),
+        None => writeln!(out, "{line}").unwrap(),
+      }
+      offset = next;
+    }
+  }
+  out
+}
+
+fn constant_text(constant: &Constant) -> String {
+  match constant {
+    Constant::Int(value) => value.to_string(),
+    Constant::Str(value) => format!("{value:?}"),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::compile::compile_program;
+  use crate::disasm::disassemble;
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_disassemble() {
+    let program = get_ast_from_file_path("../test_resources/programs/hello_world.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let text = disassemble(&compile_program(&checked).expect("Program must compile"));
+
+    assert!(text.starts_with("entry Main.main on Main\n\nconstants\n  0    \"Hello, World.\\n\"\n"), "{text}");
+    assert!(text.contains("\nclass 0 Object, tags 0..=5\n  slot 0 Object.abort\n"), "{text}");
+    assert!(text.contains("\nmethod 3 IO.out_string, 1 params, built-in\n"), "{text}");
+    let main = "method 11 Main.main, 0 params, 1 locals\n  line 3\n  0000  const 0             ; \"Hello, World.\\n\"\n  0003  load 0\n  0006  dispatch 3 1\n  0011  return\n";
+    assert!(text.ends_with(main), "{text}");
+  }
+}
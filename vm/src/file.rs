@@ -0,0 +1,222 @@
+use crate::bytecode::{Attribute, Builtin, Class, Constant, DefaultValue, Method, Program};
+
+/// Start of every `.coolb` file, followed by the version of the format
+const MAGIC: &[u8] = b"COOLB";
+const VERSION: u8 = 2;
+
+/// Serialises the program in the `.coolb` format.
+///
+/// Integers are little endian, counts and lengths are `u32`, strings are their length then their UTF-8 bytes and
+/// optional indices are `u16::MAX` when absent. The file holds the magic and version, the constants, the classes, the
+/// methods with their line tables and the entry point. Classes whose file is unknown have an empty file name.
+pub fn write(program: &Program) -> Vec<u8> {
+  let mut out = Writer(MAGIC.to_vec());
+  out.u8(VERSION);
+
+  out.u32(program.constants.len() as u32);
+  for constant in &program.constants {
+    match constant {
+      Constant::Int(value) => {
+        out.u8(0);
+        out.u32(*value as u32);
+      }
+      Constant::Str(value) => {
+        out.u8(1);
+        out.str(value);
+      }
+    }
+  }
+
+  out.u32(program.classes.len() as u32);
+  for class in &program.classes {
+    out.str(&class.name);
+    out.u16(class.parent.unwrap_or(u16::MAX));
+    out.u16(class.max_descendant_tag);
+    out.u32(class.attributes.len() as u32);
+    for attribute in &class.attributes {
+      out.str(&attribute.name);
+      out.u8(attribute.default as u8);
+    }
+    out.u32(class.vtable.len() as u32);
+    for method in &class.vtable {
+      out.u16(*method);
+    }
+    out.u16(class.init.unwrap_or(u16::MAX));
+    out.str(class.file.as_deref().unwrap_or(""));
+  }
+
+  out.u32(program.methods.len() as u32);
+  for method in &program.methods {
+    out.str(&method.name);
+    out.u16(method.params);
+    out.u16(method.locals);
+    out.u8(method.builtin.map_or(0, |builtin| builtin as u8 + 1));
+    out.u32(method.code.len() as u32);
+    out.0.extend(&method.code);
+    out.u32(method.lines.len() as u32);
+    for (start, line) in &method.lines {
+      out.u16(*start);
+      out.u32(*line);
+    }
+  }
+
+  out.u16(program.entry_class);
+  out.u16(program.entry_method);
+  out.0
+}
+
+/// Reads a program in the `.coolb` format and validates it
+pub fn read(bytes: &[u8]) -> Result<Program, String> {
+  let mut input = Reader { bytes, position: 0 };
+  if input.take(MAGIC.len()).ok() != Some(MAGIC) {
+    return Err("Not a COOL bytecode file".to_string());
+  }
+  let version = input.u8()?;
+  if version != VERSION {
+    return Err(format!("Unsupported bytecode version {version}, expected {VERSION}"));
+  }
+
+  let mut program = Program::default();
+  for _ in 0..input.u32()? {
+    let constant = match input.u8()? {
+      0 => Constant::Int(input.u32()? as i32),
+      1 => Constant::Str(input.str()?),
+      kind => return Err(format!("Unknown constant kind {kind}")),
+    };
+    program.constants.push(constant);
+  }
+
+  for _ in 0..input.u32()? {
+    let name = input.str()?;
+    let parent = input.index()?;
+    let max_descendant_tag = input.u16()?;
+    let mut attributes = Vec::new();
+    for _ in 0..input.u32()? {
+      let name = input.str()?;
+      let default = match input.u8()? {
+        0 => DefaultValue::Void,
+        1 => DefaultValue::Int,
+        2 => DefaultValue::Bool,
+        3 => DefaultValue::Str,
+        kind => return Err(format!("Unknown attribute default {kind}")),
+      };
+      attributes.push(Attribute { name, default });
+    }
+    let mut vtable = Vec::new();
+    for _ in 0..input.u32()? {
+      vtable.push(input.u16()?);
+    }
+    let init = input.index()?;
+    let file = Some(input.str()?).filter(|file| !file.is_empty());
+    program.classes.push(Class { name, parent, max_descendant_tag, attributes, vtable, init, file });
+  }
+
+  for _ in 0..input.u32()? {
+    let name = input.str()?;
+    let params = input.u16()?;
+    let locals = input.u16()?;
+    let builtin = match input.u8()? {
+      0 => None,
+      index => Some(*Builtin::ALL.get(index as usize - 1).ok_or_else(|| format!("Unknown built-in method {index}"))?),
+    };
+    let length = input.u32()? as usize;
+    let code = input.take(length)?.to_vec();
+    let mut lines = Vec::new();
+    for _ in 0..input.u32()? {
+      lines.push((input.u16()?, input.u32()?));
+    }
+    program.methods.push(Method { name, params, locals, builtin, code, lines });
+  }
+
+  program.entry_class = input.u16()?;
+  program.entry_method = input.u16()?;
+  if input.position != bytes.len() {
+    return Err("Unexpected data after the program".to_string());
+  }
+  program.validate()?;
+  Ok(program)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+  fn u8(&mut self, value: u8) {
+    self.0.push(value);
+  }
+
+  fn u16(&mut self, value: u16) {
+    self.0.extend(value.to_le_bytes());
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.0.extend(value.to_le_bytes());
+  }
+
+  fn str(&mut self, value: &str) {
+    self.u32(value.len() as u32);
+    self.0.extend(value.as_bytes());
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+    let bytes = self.bytes.get(self.position..self.position.saturating_add(length)).ok_or("Truncated bytecode file")?;
+    self.position += length;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, String> {
+    let bytes = self.take(2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn u32(&mut self) -> Result<u32, String> {
+    let bytes = self.take(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  /// An optional index, absent when `u16::MAX`
+  fn index(&mut self) -> Result<Option<u16>, String> {
+    let value = self.u16()?;
+    Ok((value != u16::MAX).then_some(value))
+  }
+
+  fn str(&mut self) -> Result<String, String> {
+    let length = self.u32()? as usize;
+    String::from_utf8(self.take(length)?.to_vec()).map_err(|_| "Invalid UTF-8 in a string".to_string())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::compile::compile_program;
+  use crate::file::{read, write};
+  use parser::get_ast_from_file_path;
+  use semantic::gen::{analyze_program, CheckOptions};
+
+  #[test]
+  fn test_round_trip() {
+    for file in ["hello_world.cl", "hairyscary.cl", "life.cl"] {
+      let program = get_ast_from_file_path(&format!("../test_resources/programs/{file}")).expect("Couldn't parse file");
+      let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+      let compiled = compile_program(&checked).expect("Program must compile");
+      assert_eq!(read(&write(&compiled)), Ok(compiled), "{file} changes through the bytecode file");
+    }
+  }
+
+  #[test]
+  fn test_invalid_files() {
+    assert_eq!(read(b"\x7fELF"), Err("Not a COOL bytecode file".to_string()));
+    assert_eq!(read(b"COOLB\x01"), Err("Unsupported bytecode version 1, expected 2".to_string()));
+    assert_eq!(read(b"COOLB\x02\x01\x00\x00\x00\x01\xff"), Err("Truncated bytecode file".to_string()));
+  }
+}
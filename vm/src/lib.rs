@@ -0,0 +1,5 @@
+pub mod bytecode;
+pub mod compile;
+pub mod disasm;
+pub mod file;
+pub mod machine;
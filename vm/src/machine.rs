@@ -0,0 +1,517 @@
+use crate::bytecode::{Builtin, Constant, DefaultValue, Instruction, Method, Program};
+use ir::runtime::{parse_int, read_line, Heap, Location, RuntimeError, SubstrError, MAX_FRAMES, MAX_OBJECTS};
+use parser::model::class::{BOOL_CLASS_NAME, INT_CLASS_NAME, STR_CLASS_NAME};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+/// A value on the VM. Values of the basic classes are immutable, so they are held directly rather than in objects.
+#[derive(Debug, Clone)]
+pub enum Value {
+  Void,
+  Int(i32),
+  Bool(bool),
+  Str(Rc<str>),
+  Object(Rc<RefCell<Object>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Object {
+  pub tag: usize,
+  pub attributes: Vec<Value>,
+}
+
+impl Drop for Object {
+  /// Drops the objects only this one refers to one after the other, as dropping them recursively would overflow the
+  /// stack on long chains of objects like lists
+  fn drop(&mut self) {
+    let mut pending = std::mem::take(&mut self.attributes);
+    while let Some(value) = pending.pop() {
+      if let Value::Object(object) = value {
+        if let Ok(object) = Rc::try_unwrap(object) {
+          pending.append(&mut object.borrow_mut().attributes);
+        }
+      }
+    }
+  }
+}
+
+impl Value {
+  /// COOL `=`: the same object, or values of a basic class that are equal
+  pub fn equals(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Void, Value::Void) => true,
+      (Value::Int(a), Value::Int(b)) => a == b,
+      (Value::Bool(a), Value::Bool(b)) => a == b,
+      (Value::Str(a), Value::Str(b)) => a == b,
+      (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+      _ => false,
+    }
+  }
+
+  fn as_int(&self) -> i32 {
+    match self {
+      Value::Int(value) => *value,
+      _ => panic!("Expected an Int, found {self:?}"),
+    }
+  }
+
+  fn as_bool(&self) -> bool {
+    match self {
+      Value::Bool(value) => *value,
+      _ => panic!("Expected a Bool, found {self:?}"),
+    }
+  }
+
+  fn as_str(&self) -> &str {
+    match self {
+      Value::Str(value) => value,
+      _ => panic!("Expected a String, found {self:?}"),
+    }
+  }
+}
+
+/// Counters of the work done by a program
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Stats {
+  pub instructions: u64, // bytecode instructions executed
+  pub calls: u64,        // calls of methods with a body, including initialisers
+  pub allocations: u64,  // objects allocated, strings built at runtime included
+}
+
+impl Display for Stats {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} instructions, {} calls, {} allocations", self.instructions, self.calls, self.allocations)
+  }
+}
+
+/// Runs the entry method of the program on a new object of the entry class.
+/// The program must be valid, see `Program::validate`.
+pub fn run(program: &Program, input: &mut dyn BufRead, output: &mut dyn Write) -> (Stats, Result<(), RuntimeError>) {
+  let mut machine = Machine::new(program, input, output);
+  let result = machine.run();
+  (machine.stats, result)
+}
+
+/// Activation of a method with a body. Its locals are at the bottom of its part of the stack.
+struct Frame {
+  method: usize,
+  pc: usize,
+  base: usize,
+}
+
+/// Stack machine running bytecode. All frames share one stack of values.
+pub struct Machine<'a> {
+  program: &'a Program,
+  constants: Vec<Value>,
+  input: &'a mut dyn BufRead,
+  output: &'a mut dyn Write,
+  stack: Vec<Value>,
+  frames: Vec<Frame>,
+  int_tag: usize,
+  bool_tag: usize,
+  string_tag: usize,
+  stats: Stats,
+  heap: Heap<RefCell<Object>>,
+}
+
+impl<'a> Machine<'a> {
+  pub fn new(program: &'a Program, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self {
+    let constants = program.constants.iter()
+        .map(|constant| match constant {
+          Constant::Int(value) => Value::Int(*value),
+          Constant::Str(value) => Value::Str(value.as_str().into()),
+        })
+        .collect();
+    let tag = |class_name: &str| program.classes.iter().position(|class| class.name == class_name).unwrap_or(usize::MAX);
+    Machine {
+      program,
+      constants,
+      input,
+      output,
+      stack: Vec::new(),
+      frames: Vec::new(),
+      int_tag: tag(INT_CLASS_NAME),
+      bool_tag: tag(BOOL_CLASS_NAME),
+      string_tag: tag(STR_CLASS_NAME),
+      stats: Stats::default(),
+      heap: Heap::new(MAX_OBJECTS),
+    }
+  }
+
+  pub fn stats(&self) -> Stats {
+    self.stats
+  }
+
+  /// Limits the objects alive at once, `MAX_OBJECTS` by default
+  pub fn set_heap_limit(&mut self, objects: usize) {
+    self.heap.limit = objects;
+  }
+
+  pub fn run(&mut self) -> Result<(), RuntimeError> {
+    let entry_class = self.program.entry_class as usize;
+    let main = self.allocate(entry_class);
+    self.stack.push(main);
+    if let Some(init) = self.program.classes[entry_class].init {
+      self.call(init as usize, 0)?;
+      self.execute()?;
+    }
+    self.call(self.program.entry_method as usize, 0)?;
+    self.execute()?;
+    self.output.flush().map_err(|e| RuntimeError::Io(e.to_string()))
+  }
+
+  /// Runs until the frames running when it is called return
+  fn execute(&mut self) -> Result<(), RuntimeError> {
+    let depth = self.frames.len();
+    if depth == 0 {
+      return Ok(());
+    }
+    let (mut code, mut pc, mut base) = self.enter();
+
+    loop {
+      let offset = pc;
+      let (instruction, next) = Instruction::decode(code, pc).expect("Code is validated");
+      pc = next;
+      self.stats.instructions += 1;
+
+      match instruction {
+        Instruction::Const(index) => self.stack.push(self.constants[index as usize].clone()),
+        Instruction::True => self.stack.push(Value::Bool(true)),
+        Instruction::False => self.stack.push(Value::Bool(false)),
+        Instruction::Void => self.stack.push(Value::Void),
+        Instruction::Load(local) => self.stack.push(self.stack[base + local as usize].clone()),
+        Instruction::Store(local) => {
+          let value = self.pop();
+          self.stack[base + local as usize] = value;
+        }
+        Instruction::LoadAttr(index) => {
+          let value = match &self.stack[base] {
+            Value::Object(object) => object.borrow().attributes[index as usize].clone(),
+            other => panic!("Attributes are read from objects, found {other:?}"),
+          };
+          self.stack.push(value);
+        }
+        Instruction::StoreAttr(index) => {
+          let value = self.pop();
+          match &self.stack[base] {
+            Value::Object(object) => object.borrow_mut().attributes[index as usize] = value,
+            other => panic!("Attributes are written to objects, found {other:?}"),
+          }
+        }
+        Instruction::Pop => {
+          self.pop();
+        }
+        Instruction::Dup => self.stack.push(self.stack.last().expect("Stack is not empty").clone()),
+        Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div | Instruction::Lt | Instruction::Le => {
+          let b = self.pop().as_int();
+          let a = self.pop().as_int();
+          let result = match instruction {
+            Instruction::Add => Value::Int(a.wrapping_add(b)),
+            Instruction::Sub => Value::Int(a.wrapping_sub(b)),
+            Instruction::Mul => Value::Int(a.wrapping_mul(b)),
+            Instruction::Div if b == 0 => return Err(self.error_at(RuntimeError::DivisionByZero { at: None }, offset)),
+            Instruction::Div => Value::Int(a.wrapping_div(b)),
+            Instruction::Lt => Value::Bool(a < b),
+            _ => Value::Bool(a <= b),
+          };
+          self.stack.push(result);
+        }
+        Instruction::Eq => {
+          let b = self.pop();
+          let a = self.pop();
+          self.stack.push(Value::Bool(a.equals(&b)));
+        }
+        Instruction::Neg => {
+          let value = self.pop().as_int();
+          self.stack.push(Value::Int(value.wrapping_neg()));
+        }
+        Instruction::Not => {
+          let value = self.pop().as_bool();
+          self.stack.push(Value::Bool(!value));
+        }
+        Instruction::IsVoid => {
+          let value = self.pop();
+          self.stack.push(Value::Bool(matches!(value, Value::Void)));
+        }
+        Instruction::Jump(target) => pc = target as usize,
+        Instruction::JumpIfFalse(target) => {
+          if !self.pop().as_bool() {
+            pc = target as usize;
+          }
+        }
+        Instruction::New(_) | Instruction::NewSelf => {
+          let tag = match instruction {
+            Instruction::New(tag) => tag as usize,
+            _ => self.tag(&self.stack[base]).expect("self is not void"),
+          };
+          let object = self.allocate(tag);
+          if self.heap.exhausted {
+            return Err(self.error_at(RuntimeError::OutOfMemory { at: None }, offset));
+          }
+          self.stack.push(object);
+          if let Some(init) = self.program.classes[tag].init {
+            self.frames.last_mut().expect("A method is running").pc = pc;
+            self.call(init as usize, 0).map_err(|e| self.error_at(e, offset))?;
+            (code, pc, base) = self.enter();
+          }
+        }
+        Instruction::Dispatch { slot, args } => {
+          let receiver = self.stack.last().expect("Receiver is on the stack");
+          let Some(tag) = self.tag(receiver) else {
+            return Err(self.error_at(RuntimeError::DispatchOnVoid { at: None }, offset));
+          };
+          let method = self.program.classes[tag].vtable[slot as usize];
+          self.frames.last_mut().expect("A method is running").pc = pc;
+          if self.call(method as usize, args as usize).map_err(|e| self.error_at(e, offset))? {
+            (code, pc, base) = self.enter();
+          }
+        }
+        Instruction::StaticDispatch { method, args } => {
+          if matches!(self.stack.last(), Some(Value::Void)) {
+            return Err(self.error_at(RuntimeError::DispatchOnVoid { at: None }, offset));
+          }
+          self.frames.last_mut().expect("A method is running").pc = pc;
+          if self.call(method as usize, args as usize).map_err(|e| self.error_at(e, offset))? {
+            (code, pc, base) = self.enter();
+          }
+        }
+        Instruction::Return => {
+          let value = self.pop();
+          self.stack.truncate(base);
+          self.stack.push(value);
+          self.frames.pop();
+          if self.frames.len() < depth {
+            return Ok(());
+          }
+          (code, pc, base) = self.enter();
+        }
+        Instruction::CaseCheck => {
+          if matches!(self.stack.last(), Some(Value::Void)) {
+            return Err(self.error_at(RuntimeError::CaseOnVoid { at: None }, offset));
+          }
+        }
+        Instruction::CaseMatch(class) => {
+          let tag = self.tag(self.stack.last().expect("Value of the case is on the stack")).expect("Value of the case is not void");
+          let class = class as usize;
+          let matches = class <= tag && tag <= self.program.classes[class].max_descendant_tag as usize;
+          self.stack.push(Value::Bool(matches));
+        }
+        Instruction::CaseFail => {
+          let tag = self.tag(self.stack.last().expect("Value of the case is on the stack")).expect("Value of the case is not void");
+          let error = RuntimeError::CaseNoMatch { class_name: self.program.classes[tag].name.clone(), at: None };
+          return Err(self.error_at(error, offset));
+        }
+      }
+    }
+  }
+
+  /// The error, coming from the instruction at the offset in the running method
+  fn error_at(&self, error: RuntimeError, offset: usize) -> RuntimeError {
+    let frame = self.frames.last().expect("A method is running");
+    let method = &self.program.methods[frame.method];
+    let location = method.line(offset).map(|line| {
+      let class_name = method.name.split('.').next().expect("Methods are named after their class");
+      let file = self.program.class(class_name).and_then(|class| class.file.clone());
+      // The bytecode keeps the lines of the code only
+      Location { file, pos: (line, 0) }
+    });
+    error.located(|| location)
+  }
+
+  /// Code, program counter and base of the running frame
+  fn enter(&self) -> (&'a [u8], usize, usize) {
+    let frame = self.frames.last().expect("A method is running");
+    let program: &'a Program = self.program;
+    (&program.methods[frame.method].code, frame.pc, frame.base)
+  }
+
+  /// Calls the method on the receiver on top of the stack, above its arguments. Built-in methods run at once and
+  /// leave their result; for other methods, the receiver is moved below the arguments to become local 0 of a new frame,
+  /// and the call returns true.
+  fn call(&mut self, index: usize, args: usize) -> Result<bool, RuntimeError> {
+    let program: &'a Program = self.program;
+    let method: &Method = &program.methods[index];
+    if let Some(builtin) = method.builtin {
+      let receiver = self.pop();
+      let args = self.stack.split_off(self.stack.len() - args);
+      let result = self.call_builtin(builtin, receiver, args)?;
+      if self.heap.exhausted {
+        return Err(RuntimeError::OutOfMemory { at: None });
+      }
+      self.stack.push(result);
+      return Ok(false);
+    }
+
+    if self.frames.len() >= MAX_FRAMES {
+      return Err(RuntimeError::StackOverflow);
+    }
+    self.stats.calls += 1;
+    let receiver = self.pop();
+    let base = self.stack.len() - args;
+    self.stack.insert(base, receiver);
+    self.stack.resize(base + method.locals as usize, Value::Void);
+    self.frames.push(Frame { method: index, pc: 0, base });
+    Ok(true)
+  }
+
+  fn pop(&mut self) -> Value {
+    self.stack.pop().expect("Stack is not empty")
+  }
+
+  /// Tag of the class of a value, none for void
+  fn tag(&self, value: &Value) -> Option<usize> {
+    match value {
+      Value::Void => None,
+      Value::Int(_) => Some(self.int_tag),
+      Value::Bool(_) => Some(self.bool_tag),
+      Value::Str(_) => Some(self.string_tag),
+      Value::Object(object) => Some(object.borrow().tag),
+    }
+  }
+
+  /// A new object of the class with default attribute values
+  fn allocate(&mut self, tag: usize) -> Value {
+    self.stats.allocations += 1;
+    match tag {
+      _ if tag == self.int_tag => Value::Int(0),
+      _ if tag == self.bool_tag => Value::Bool(false),
+      _ if tag == self.string_tag => Value::Str("".into()),
+      _ => {
+        let attributes = self.program.classes[tag].attributes.iter()
+            .map(|attribute| match attribute.default {
+              DefaultValue::Void => Value::Void,
+              DefaultValue::Int => Value::Int(0),
+              DefaultValue::Bool => Value::Bool(false),
+              DefaultValue::Str => Value::Str("".into()),
+            })
+            .collect();
+        self.object(Object { tag, attributes })
+      }
+    }
+  }
+
+  fn object(&mut self, object: Object) -> Value {
+    let object = Rc::new(RefCell::new(object));
+    self.heap.register(&object);
+    Value::Object(object)
+  }
+
+  fn call_builtin(&mut self, builtin: Builtin, receiver: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let io_error = |e: std::io::Error| RuntimeError::Io(e.to_string());
+    match builtin {
+      Builtin::Abort => {
+        let tag = self.tag(&receiver).expect("Receiver is checked for void");
+        Err(RuntimeError::Abort { class_name: self.program.classes[tag].name.clone() })
+      }
+      Builtin::TypeName => {
+        let tag = self.tag(&receiver).expect("Receiver is checked for void");
+        Ok(self.string(self.program.classes[tag].name.as_str()))
+      }
+      Builtin::Copy => match receiver {
+        Value::Object(object) => {
+          self.stats.allocations += 1;
+          let copy = object.borrow().clone();
+          Ok(self.object(copy))
+        }
+        value => Ok(value),
+      },
+      Builtin::OutString => {
+        self.output.write_all(args[0].as_str().as_bytes()).map_err(io_error)?;
+        Ok(receiver)
+      }
+      Builtin::OutInt => {
+        write!(self.output, "{}", args[0].as_int()).map_err(io_error)?;
+        Ok(receiver)
+      }
+      Builtin::InString => {
+        let line = read_line(self.input, self.output)?;
+        Ok(self.string(&line))
+      }
+      Builtin::InInt => {
+        let line = read_line(self.input, self.output)?;
+        Ok(Value::Int(parse_int(&line)))
+      }
+      Builtin::Length => Ok(Value::Int(receiver.as_str().chars().count() as i32)),
+      Builtin::Concat => {
+        let value = format!("{}{}", receiver.as_str(), args[0].as_str());
+        Ok(self.string(&value))
+      }
+      Builtin::Substr => {
+        let chars: Vec<char> = receiver.as_str().chars().collect();
+        let (start, length) = (args[0].as_int(), args[1].as_int());
+        if let Some(error) = SubstrError::check(start, length, chars.len()) {
+          return Err(RuntimeError::Substr { error, at: None });
+        }
+        let value: String = chars[start as usize..(start + length) as usize].iter().collect();
+        Ok(self.string(&value))
+      }
+    }
+  }
+
+  fn string(&mut self, value: &str) -> Value {
+    self.stats.allocations += 1;
+    Value::Str(value.into())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::compile::compile_program;
+  use crate::machine::{run, Machine};
+  use parser::get_ast_from_file_path;
+  use parser::programs::{find_programs, TestProgram};
+  use semantic::gen::{analyze_program, CheckOptions};
+  use std::path::Path;
+
+  /// Output and error of a run, whichever runs it
+  fn outcome(output: Vec<u8>, result: Result<(), String>) -> (String, Result<(), String>) {
+    (String::from_utf8(output).expect("Output must be UTF-8"), result)
+  }
+
+  #[test]
+  fn test_programs_match_interpreter() {
+    let mut programs = find_programs("../test_resources/programs");
+    programs.push(TestProgram::new(Path::new("../test_resources/codegen/runtime.cl")));
+
+    for test_program in programs {
+      let name = test_program.name();
+      let program = test_program.parse().expect("Couldn't parse file");
+      let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+
+      let module = ir::lower::lower_program(&checked);
+      let mut output = Vec::new();
+      let (_, result) = ir::interp::run(&module, &mut test_program.input.as_bytes(), &mut output);
+      let expected = outcome(output, result.map_err(|e| e.to_string()));
+
+      let compiled = compile_program(&checked).expect("Program must compile");
+      compiled.validate().unwrap_or_else(|e| panic!("{name}: {e}"));
+      let mut output = Vec::new();
+      let (_, result) = run(&compiled, &mut test_program.input.as_bytes(), &mut output);
+      assert_eq!(outcome(output, result.map_err(|e| e.to_string())), expected, "{name} behaves differently on the VM");
+    }
+  }
+
+  #[test]
+  fn test_runtime_errors_match_interpreter() {
+    let program = get_ast_from_file_path("../test_resources/ir/errors.cl").expect("Couldn't parse file");
+    let checked = analyze_program(&program, &CheckOptions::default()).expect("Program must type check");
+    let mut module = ir::lower::lower_program(&checked);
+    module.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+    let mut compiled = compile_program(&checked).expect("Program must compile");
+    compiled.classes.iter_mut().for_each(|class| class.file = Some("errors.cl".to_string()));
+
+    for choice in ["1", "2", "3", "4", "5", "6", "7"] {
+      let (mut input, mut output) = (choice.as_bytes(), Vec::new());
+      let mut interpreter = ir::interp::Interpreter::new(&module, &mut input, &mut output);
+      interpreter.set_heap_limit(10_000);
+      let expected = interpreter.run().expect_err("Program must fail").to_string();
+
+      let (mut input, mut output) = (choice.as_bytes(), Vec::new());
+      let mut machine = Machine::new(&compiled, &mut input, &mut output);
+      machine.set_heap_limit(10_000);
+      assert_eq!(machine.run().expect_err("Program must fail").to_string(), expected, "Choice {choice} fails differently on the VM");
+    }
+  }
+}